@@ -0,0 +1,95 @@
+// The matrix client's multiply, from C.
+//
+// A goes in as wire bytes through `write-to-host`, B as floats through
+// `write-f32`, and the product comes back both ways: through `read-f32` and as
+// bytes from `read-from-host`. The two reads must agree bit for bit and match
+// a guest-side reference, so anything on either side that treats the byte
+// path as native-endian floats fails here rather than on a big-endian host.
+//
+// Handles left behind on an early return are freed when the runner closes
+// the client's session.
+#include <math.h>
+#include <stdio.h>
+
+#include "c_matrix.h"
+#include "offload_wire.h"
+
+// Names `wit-bindgen c` gives the world's `host-allocator` import.
+typedef c_matrix_host_allocator_handle_t handle_t;
+typedef c_matrix_host_allocator_host_error_t host_error_t;
+typedef c_matrix_host_allocator_matrix_dimensions_t dims_t;
+
+#define M 2
+#define K 3
+#define N 2
+
+static const float A[M * K] = {1.5f, -2.0f, 3.25f, 4.0f, 0.5f, -6.0f};
+static const float B[K * N] = {7.0f, -8.5f, 9.0f, 10.0f, -11.0f, 12.25f};
+
+// Sets `err` and returns false, for `return fail(...)` from the export.
+static bool fail(c_matrix_string_t *err, const char *what, host_error_t *e) {
+    char message[160];
+    if (e) {
+        snprintf(message, sizeof message, "[C Matrix Wasm] %s failed with host-error case %d", what, (int)e->tag);
+        c_matrix_host_allocator_host_error_free(e);
+    } else {
+        snprintf(message, sizeof message, "[C Matrix Wasm] %s", what);
+    }
+    c_matrix_string_dup(err, message);
+    return false;
+}
+
+bool exports_c_matrix_run_c_matrix(c_matrix_string_t *err) {
+    host_error_t e;
+    handle_t a, b, c;
+
+    // A: raw bytes, encoded little-endian explicitly.
+    uint8_t a_bytes[sizeof A];
+    offload_encode_f32(a_bytes, A, M * K);
+    c_matrix_list_u8_t a_list = {a_bytes, sizeof a_bytes};
+    dims_t a_dims = {M, K};
+    if (!c_matrix_host_allocator_allocate_buffer(sizeof a_bytes, &a, &e)) return fail(err, "allocate A", &e);
+    if (!c_matrix_host_allocator_write_to_host(&a_list, a, 0, &e)) return fail(err, "write A", &e);
+    if (!c_matrix_host_allocator_register_matrix_dimensions(a, &a_dims, &e)) return fail(err, "register A", &e);
+
+    // B: typed floats; the canonical ABI takes care of the layout.
+    c_matrix_list_f32_t b_list = {(float *)B, K * N};
+    dims_t b_dims = {K, N};
+    if (!c_matrix_host_allocator_allocate_typed_buffer(C_MATRIX_HOST_ALLOCATOR_ELEMENT_TYPE_F32, K * N, &b, &e)) {
+        return fail(err, "allocate B", &e);
+    }
+    if (!c_matrix_host_allocator_write_f32(b, 0, &b_list, &e)) return fail(err, "write B", &e);
+    if (!c_matrix_host_allocator_register_matrix_dimensions(b, &b_dims, &e)) return fail(err, "register B", &e);
+
+    if (!c_matrix_host_allocator_matrix_multiply_f32(a, b, &c, &e)) return fail(err, "multiply", &e);
+    printf("[C Matrix Wasm] A:%u x B:%u -> C:%u\n", a, b, c);
+
+    c_matrix_list_f32_t typed;
+    c_matrix_list_u8_t wire;
+    if (!c_matrix_host_allocator_read_f32(c, 0, M * N, &typed, &e)) return fail(err, "read C as f32", &e);
+    if (!c_matrix_host_allocator_read_from_host(c, 0, M * N * OFFLOAD_F32_SIZE, &wire, &e)) {
+        c_matrix_list_f32_free(&typed);
+        return fail(err, "read C as bytes", &e);
+    }
+
+    bool ok = typed.len == M * N && wire.len == M * N * OFFLOAD_F32_SIZE;
+    for (size_t i = 0; ok && i < M * N; i++) {
+        size_t row = i / N, col = i % N;
+        float expected = 0.0f;
+        for (size_t k = 0; k < K; k++) {
+            expected += A[row * K + k] * B[k * N + col];
+        }
+        float from_bytes = offload_get_f32_le(wire.ptr + i * OFFLOAD_F32_SIZE);
+        printf("[C Matrix Wasm] C[%zu][%zu] = %g (bytes: %g, expected %g)\n", row, col, typed.ptr[i], from_bytes, expected);
+        ok = typed.ptr[i] == from_bytes && fabsf(typed.ptr[i] - expected) <= 1e-4f * fmaxf(fabsf(expected), 1.0f);
+    }
+    c_matrix_list_f32_free(&typed);
+    c_matrix_list_u8_free(&wire);
+    if (!ok) return fail(err, "C does not match the reference, or its f32 and byte reads disagree", NULL);
+
+    handle_t handles[] = {a, b, c};
+    for (size_t i = 0; i < sizeof handles / sizeof handles[0]; i++) {
+        if (!c_matrix_host_allocator_free_buffer(handles[i], &e)) return fail(err, "free", &e);
+    }
+    return true;
+}
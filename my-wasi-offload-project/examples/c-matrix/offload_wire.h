@@ -0,0 +1,46 @@
+// The host-offload wire format (see wit/host-offload.wit) for C guests.
+//
+// Buffer bytes moved with `write-to-host` / `read-from-host` hold elements
+// little-endian whatever the guest's own byte order, so never memcpy a float
+// array into a `list<u8>`: that only happens to work on little-endian guests.
+// These encode and decode explicitly instead. The typed calls (`write-f32`,
+// `read-f32`) need none of this; the canonical ABI already fixes the layout.
+#ifndef OFFLOAD_WIRE_H
+#define OFFLOAD_WIRE_H
+
+#include <stddef.h>
+#include <stdint.h>
+#include <string.h>
+
+#define OFFLOAD_F32_SIZE 4
+
+static inline void offload_put_f32_le(uint8_t *out, float value) {
+    uint32_t bits;
+    memcpy(&bits, &value, sizeof bits);
+    out[0] = (uint8_t)bits;
+    out[1] = (uint8_t)(bits >> 8);
+    out[2] = (uint8_t)(bits >> 16);
+    out[3] = (uint8_t)(bits >> 24);
+}
+
+static inline float offload_get_f32_le(const uint8_t *in) {
+    uint32_t bits = (uint32_t)in[0] | (uint32_t)in[1] << 8 | (uint32_t)in[2] << 16 | (uint32_t)in[3] << 24;
+    float value;
+    memcpy(&value, &bits, sizeof value);
+    return value;
+}
+
+// `count` floats to `4 * count` wire bytes.
+static inline void offload_encode_f32(uint8_t *out, const float *values, size_t count) {
+    for (size_t i = 0; i < count; i++) {
+        offload_put_f32_le(out + i * OFFLOAD_F32_SIZE, values[i]);
+    }
+}
+
+static inline void offload_decode_f32(float *out, const uint8_t *bytes, size_t count) {
+    for (size_t i = 0; i < count; i++) {
+        out[i] = offload_get_f32_le(bytes + i * OFFLOAD_F32_SIZE);
+    }
+}
+
+#endif
@@ -0,0 +1,64 @@
+// Generate bindings for the `elementwise-plugin` world.
+wit_bindgen::generate!({
+    world: "elementwise-plugin",
+    path: "wit/world.wit",
+});
+
+use offload_common::codec;
+
+use crate::exports::wasi_custom::host_offload::compute_plugin::{ArgSchema, ArgShape, ElementType, Handle, OpSchema};
+use crate::host_allocator;
+// The provider's element type, which the plugin's `element-type` mirrors.
+use crate::wasi_custom::host_offload::host_allocator::ElementType as BufferType;
+
+struct Component;
+
+impl crate::exports::wasi_custom::host_offload::compute_plugin::Guest for Component {
+    // Ops on f32 vectors, each returning a new buffer.
+    fn list_ops() -> Vec<OpSchema> {
+        vec![
+            op("mul-f32", "Z = X * Y element-wise; X and Y must be the same length", &["x", "y"], "z"),
+            op("sum-f32", "A single element holding the sum of X", &["x"], "sum"),
+        ]
+    }
+
+    // The provider has already checked the inputs against the schemas above.
+    fn execute(op_name: String, inputs: Vec<Handle>) -> Result<Vec<Handle>, String> {
+        println!("[Elementwise Plugin Wasm] {} on {:?}", op_name, inputs);
+        let out = match (op_name.as_str(), inputs.as_slice()) {
+            ("mul-f32", &[x, y]) => {
+                let (x, y) = (download(x)?, download(y)?);
+                if x.len() != y.len() {
+                    return Err(format!("mul-f32: lengths {} and {} differ", x.len(), y.len()));
+                }
+                upload(&x.iter().zip(&y).map(|(a, b)| a * b).collect::<Vec<f32>>())?
+            }
+            ("sum-f32", &[x]) => upload(&[download(x)?.iter().sum()])?,
+            (op, _) => return Err(format!("Unknown op '{}'", op)),
+        };
+        Ok(vec![out])
+    }
+}
+
+fn op(name: &str, description: &str, inputs: &[&str], output: &str) -> OpSchema {
+    let vector = |name: &str| ArgSchema { name: name.to_string(), element_type: Some(ElementType::F32), shape: ArgShape::Vector };
+    OpSchema {
+        name: name.to_string(),
+        description: description.to_string(),
+        inputs: inputs.iter().map(|input| vector(input)).collect(),
+        outputs: vec![vector(output)],
+    }
+}
+
+fn download(h: Handle) -> Result<Vec<f32>, String> {
+    let info = host_allocator::describe_handle(h).map_err(|e| format!("Failed to describe {}: {:?}", h, e))?;
+    host_allocator::read_f32(h, 0, info.size / codec::F32_SIZE as u64)
+        .map_err(|e| format!("Failed to read {}: {:?}", h, e))
+}
+
+fn upload(values: &[f32]) -> Result<Handle, String> {
+    let h = host_allocator::allocate_typed_buffer(BufferType::F32, values.len() as u64)
+        .map_err(|e| format!("Failed to allocate {} floats: {:?}", values.len(), e))?;
+    host_allocator::write_f32(h, 0, values).map_err(|e| format!("Failed to write {} floats: {:?}", values.len(), e))?;
+    Ok(h)
+}
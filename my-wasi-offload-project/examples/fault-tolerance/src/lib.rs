@@ -0,0 +1,114 @@
+// Generate bindings for the `fault-tolerance` world.
+wit_bindgen::generate!({
+    world: "fault-tolerance",
+    path: "wit/world.wit",
+});
+
+use std::time::{Duration, Instant};
+
+use crate::host_allocator;
+use crate::wasi_custom::host_offload::host_allocator::{ElementType, Handle, HostError, MatrixLayout, MatrixShape};
+
+// Provokes one failure after another and checks that the provider reports
+// each with the right `host-error` case, then that it still works normally
+// afterwards. Run with a tight `max_ops_per_sec` to exercise the
+// `rate-limited` retry path too (see configs/fault-tolerance.toml).
+
+// A handle no provider hands out this early.
+const BOGUS_HANDLE: Handle = 0xdead_beef;
+
+struct Component;
+
+impl crate::FaultTolerance for Component {
+    fn run_fault_tolerance() -> Result<(), String> {
+        println!("[Fault Wasm] Provoking provider errors...");
+        let mut failed = Vec::new();
+        let mut check = |name: &str, ok: bool, got: String| {
+            if ok {
+                println!("[Fault Wasm]   ok    {}", name);
+            } else {
+                println!("[Fault Wasm]   FAIL  {}: got {}", name, got);
+                failed.push(name.to_string());
+            }
+        };
+
+        let r = retry(|| host_allocator::read_from_host(BOGUS_HANDLE, 0, 4));
+        check("read from unknown handle", matches!(r, Err(HostError::InvalidHandle)), format!("{:?}", r));
+
+        let small = retry(|| host_allocator::allocate_buffer(16)).map_err(|e| format!("Failed to allocate: {:?}", e))?;
+        let r = retry(|| host_allocator::read_from_host(small, 8, 16));
+        check("read past the end", matches!(r, Err(HostError::CopyOutOfBounds)), format!("{:?}", r));
+        let r = retry(|| host_allocator::write_to_host(&[0; 4], small, u64::MAX - 1));
+        check("write at an overflowing offset", matches!(r, Err(HostError::CopyOutOfBounds)), format!("{:?}", r));
+
+        let r = retry(|| host_allocator::allocate_buffer(u64::MAX));
+        check("allocate u64::MAX bytes", matches!(r, Err(HostError::AllocationTooLarge(_))), format!("{:?}", r));
+
+        let odd = retry(|| host_allocator::allocate_buffer(6)).map_err(|e| format!("Failed to allocate: {:?}", e))?;
+        let r = retry(|| host_allocator::read_f32(odd, 0, 1));
+        check("f32 read of a 6-byte buffer", matches!(r, Err(HostError::Misaligned)), format!("{:?}", r));
+
+        let ints = retry(|| host_allocator::allocate_typed_buffer(ElementType::S32, 4)).map_err(|e| format!("Failed to allocate: {:?}", e))?;
+        let r = retry(|| host_allocator::read_f32(ints, 0, 4));
+        check("f32 read of an s32 buffer", matches!(r, Err(HostError::TypeMismatch)), format!("{:?}", r));
+
+        let a = matrix(2, 3)?;
+        let b = matrix(2, 3)?;
+        let r = retry(|| host_allocator::matrix_multiply_f32(a, b));
+        check("2x3 times 2x3", matches!(r, Err(HostError::DimensionMismatch)), format!("{:?}", r));
+
+        let r = retry(|| host_allocator::end_arena(u32::MAX));
+        check("end an arena never begun", matches!(r, Err(HostError::InvalidArena)), format!("{:?}", r));
+
+        // Big enough that a zero budget runs out before the first row block.
+        let big = matrix(512, 512)?;
+        host_allocator::set_op_timeout(Some(0));
+        let r = retry(|| host_allocator::matrix_multiply_f32(big, big));
+        host_allocator::set_op_timeout(None);
+        check("512x512 multiply with a 0 ms budget", matches!(r, Err(HostError::Timeout)), format!("{:?}", r.map(|_| ())));
+
+        for h in [small, odd, ints, a, b, big] {
+            retry(|| host_allocator::free_buffer(h)).map_err(|e| format!("Failed to free {}: {:?}", h, e))?;
+        }
+        let r = retry(|| host_allocator::free_buffer(small));
+        check("double free", matches!(r, Err(HostError::InvalidHandle)), format!("{:?}", r));
+
+        // None of the above may have left the provider unusable.
+        let c = matrix(2, 2).and_then(|m| {
+            retry(|| host_allocator::matrix_multiply_f32(m, m)).map_err(|e| format!("{:?}", e))
+        });
+        check("multiply after all of the above", c.is_ok(), format!("{:?}", c));
+
+        if failed.is_empty() {
+            println!("[Fault Wasm] Every error was reported as expected");
+            Ok(())
+        } else {
+            Err(format!("[Fault Wasm] Unexpected results for: {}", failed.join(", ")))
+        }
+    }
+}
+
+// Runs `call`, waiting out `rate-limited` as often as the provider asks.
+fn retry<T>(mut call: impl FnMut() -> Result<T, HostError>) -> Result<T, HostError> {
+    loop {
+        match call() {
+            Err(HostError::RateLimited(millis)) => {
+                println!("[Fault Wasm] Rate limited, retrying in {} ms", millis);
+                // No sleeping without WASI; spin on the clock instead.
+                let until = Instant::now() + Duration::from_millis(millis);
+                while Instant::now() < until {}
+            }
+            result => return result,
+        }
+    }
+}
+
+fn matrix(rows: u32, cols: u32) -> Result<Handle, String> {
+    let values: Vec<f32> = (0..rows * cols).map(|i| (i % 5) as f32).collect();
+    let h = retry(|| host_allocator::allocate_typed_buffer(ElementType::F32, values.len() as u64))
+        .map_err(|e| format!("Failed to allocate {}x{}: {:?}", rows, cols, e))?;
+    retry(|| host_allocator::write_f32(h, 0, &values)).map_err(|e| format!("Failed to write {}x{}: {:?}", rows, cols, e))?;
+    retry(|| host_allocator::register_matrix_shape(h, MatrixShape { rows, cols, layout: MatrixLayout::RowMajor }))
+        .map_err(|e| format!("Failed to register {}x{}: {:?}", rows, cols, e))?;
+    Ok(h)
+}
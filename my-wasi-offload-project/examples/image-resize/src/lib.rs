@@ -0,0 +1,130 @@
+// Generate bindings for the `image-resize` world.
+wit_bindgen::generate!({
+    world: "image-resize",
+    path: "wit/world.wit",
+});
+
+use crate::host_allocator;
+use crate::wasi_custom::host_offload::host_allocator::{ElementType, Handle, MatrixLayout, MatrixShape, TensorMeta};
+
+// A bilinear resize is separable: `out = R * img * C`, where R (out_h x h)
+// blends source rows and C (w x out_w) blends source columns. The image goes
+// up as u8, is widened and narrowed again with `cast`, and never leaves the
+// host as floats.
+const WIDTH: u32 = 64;
+const HEIGHT: u32 = 48;
+const OUT_WIDTH: u32 = 40;
+const OUT_HEIGHT: u32 = 30;
+
+struct Component;
+
+impl crate::ImageResize for Component {
+    fn run_image_resize() -> Result<(), String> {
+        println!("[Image Resize Wasm] Resizing a {}x{} image to {}x{}", WIDTH, HEIGHT, OUT_WIDTH, OUT_HEIGHT);
+        let image = test_pattern();
+
+        let handle_image = host_allocator::allocate_typed_buffer(ElementType::U8, image.len() as u64)
+            .map_err(|e| format!("Failed to allocate image: {:?}", e))?;
+        host_allocator::write_to_host(&image, handle_image, 0)
+            .map_err(|e| format!("Failed to write image: {:?}", e))?;
+        host_allocator::register_matrix_shape(handle_image, dims(HEIGHT, WIDTH))
+            .map_err(|e| format!("Failed to register image dims: {:?}", e))?;
+        let handle_pixels = host_allocator::cast(handle_image, ElementType::F32, 1.0)
+            .map_err(|e| format!("Failed to widen image: {:?}", e))?;
+
+        let rows = weights(HEIGHT, OUT_HEIGHT);
+        let handle_rows = upload_matrix(&rows, OUT_HEIGHT, HEIGHT)?;
+        let cols = transpose(&weights(WIDTH, OUT_WIDTH), OUT_WIDTH, WIDTH);
+        let handle_cols = upload_matrix(&cols, WIDTH, OUT_WIDTH)?;
+
+        let handle_tall = host_allocator::matrix_multiply_f32(handle_rows, handle_pixels)
+            .map_err(|e| format!("Row pass failed: {:?}", e))?;
+        let handle_resized = host_allocator::matrix_multiply_f32(handle_tall, handle_cols)
+            .map_err(|e| format!("Column pass failed: {:?}", e))?;
+        let handle_out = host_allocator::cast(handle_resized, ElementType::U8, 1.0)
+            .map_err(|e| format!("Failed to narrow result: {:?}", e))?;
+        let resized = host_allocator::read_from_host(handle_out, 0, (OUT_WIDTH * OUT_HEIGHT) as u64)
+            .map_err(|e| format!("Failed to read result: {:?}", e))?;
+
+        for h in [handle_image, handle_pixels, handle_rows, handle_cols, handle_tall, handle_resized, handle_out] {
+            host_allocator::free_buffer(h).map_err(|e| format!("Failed to free {}: {:?}", h, e))?;
+        }
+
+        // The guest reference rounds once at the end as well; a different
+        // summation order can still tip a pixel over a .5 boundary.
+        let expected = reference_resize(&image);
+        let worst = resized.iter().zip(&expected).map(|(&a, &b)| (a as i16 - b as i16).abs()).max().unwrap_or(0);
+        if resized.len() != expected.len() || worst > 1 {
+            return Err(format!("[Image Resize Wasm] Resized image differs from the reference by up to {}", worst));
+        }
+        println!("[Image Resize Wasm] Resize SUCCESSFUL (max pixel difference {})", worst);
+        Ok(())
+    }
+}
+
+fn dims(rows: u32, cols: u32) -> MatrixShape {
+    MatrixShape { rows, cols, layout: MatrixLayout::RowMajor }
+}
+
+// Diagonal gradient with a bright square in the middle.
+fn test_pattern() -> Vec<u8> {
+    (0..HEIGHT)
+        .flat_map(|y| (0..WIDTH).map(move |x| (y, x)))
+        .map(|(y, x)| {
+            let square = (WIDTH / 3..2 * WIDTH / 3).contains(&x) && (HEIGHT / 3..2 * HEIGHT / 3).contains(&y);
+            if square { 255 } else { ((x + y) * 255 / (WIDTH + HEIGHT)) as u8 }
+        })
+        .collect()
+}
+
+// `to x from` interpolation matrix, pixel centres aligned as in most image
+// libraries: output i samples input position (i + 0.5) * from / to - 0.5.
+fn weights(from: u32, to: u32) -> Vec<f32> {
+    let mut m = vec![0.0; (to * from) as usize];
+    let scale = from as f32 / to as f32;
+    for i in 0..to {
+        let src = ((i as f32 + 0.5) * scale - 0.5).clamp(0.0, (from - 1) as f32);
+        let lo = src.floor() as u32;
+        let hi = (lo + 1).min(from - 1);
+        let frac = src - lo as f32;
+        m[(i * from + lo) as usize] += 1.0 - frac;
+        m[(i * from + hi) as usize] += frac;
+    }
+    m
+}
+
+fn transpose(m: &[f32], rows: u32, cols: u32) -> Vec<f32> {
+    (0..cols).flat_map(|c| (0..rows).map(move |r| m[(r * cols + c) as usize])).collect()
+}
+
+fn upload_matrix(values: &[f32], rows: u32, cols: u32) -> Result<Handle, String> {
+    let handle = host_allocator::allocate_typed_buffer(ElementType::F32, values.len() as u64)
+        .map_err(|e| format!("Failed to allocate weights: {:?}", e))?;
+    host_allocator::write_f32(handle, 0, values)
+        .map_err(|e| format!("Failed to write weights: {:?}", e))?;
+    host_allocator::register_tensor_meta(handle, TensorMeta { element_type: ElementType::F32, dims: Some(dims(rows, cols)) })
+        .map_err(|e| format!("Failed to register weights: {:?}", e))?;
+    Ok(handle)
+}
+
+fn reference_resize(image: &[u8]) -> Vec<u8> {
+    let rows = weights(HEIGHT, OUT_HEIGHT);
+    let cols = weights(WIDTH, OUT_WIDTH);
+    let mut out = Vec::with_capacity((OUT_WIDTH * OUT_HEIGHT) as usize);
+    for oy in 0..OUT_HEIGHT {
+        for ox in 0..OUT_WIDTH {
+            let mut sum = 0.0f32;
+            for y in 0..HEIGHT {
+                let wy = rows[(oy * HEIGHT + y) as usize];
+                if wy == 0.0 {
+                    continue;
+                }
+                for x in 0..WIDTH {
+                    sum += wy * cols[(ox * WIDTH + x) as usize] * image[(y * WIDTH + x) as usize] as f32;
+                }
+            }
+            out.push(sum.round().clamp(0.0, 255.0) as u8);
+        }
+    }
+    out
+}
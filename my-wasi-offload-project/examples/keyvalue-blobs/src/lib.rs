@@ -0,0 +1,70 @@
+// Generate bindings for the `keyvalue-blobs` world.
+wit_bindgen::generate!({
+    world: "keyvalue-blobs",
+    path: "wit/world.wit",
+});
+
+use crate::wasi::keyvalue::store::{self, Bucket};
+
+// Stores, overwrites, lists and deletes blobs through the standard keyvalue
+// API and checks every read against what was written.
+const BLOB_LEN: usize = 1 << 20;
+
+struct Component;
+
+impl crate::KeyvalueBlobs for Component {
+    fn run_keyvalue_blobs() -> Result<(), String> {
+        let bucket = store::open("blobs").map_err(|e| format!("Failed to open bucket: {:?}", e))?;
+
+        let blob: Vec<u8> = (0..BLOB_LEN).map(|i| (i % 251) as u8).collect();
+        set(&bucket, "blob", &blob)?;
+        set(&bucket, "greeting", b"hello")?;
+        set(&bucket, "empty", b"")?;
+        expect(&bucket, "blob", Some(&blob))?;
+        expect(&bucket, "greeting", Some(b"hello"))?;
+        expect(&bucket, "empty", Some(b""))?;
+        expect(&bucket, "missing", None)?;
+        println!("[KeyValue Wasm] Stored a {} byte blob and two small values", BLOB_LEN);
+
+        // Same size (written in place) and a different size (a new buffer).
+        set(&bucket, "greeting", b"howdy")?;
+        expect(&bucket, "greeting", Some(b"howdy"))?;
+        set(&bucket, "greeting", b"good morning")?;
+        expect(&bucket, "greeting", Some(b"good morning"))?;
+
+        // A second bucket with the same identifier sees the same keys.
+        let again = store::open("blobs").map_err(|e| format!("Failed to reopen bucket: {:?}", e))?;
+        expect(&again, "greeting", Some(b"good morning"))?;
+
+        let listed = bucket.list_keys(None).map_err(|e| format!("Failed to list keys: {:?}", e))?;
+        if listed.keys != ["blob", "empty", "greeting"] || listed.cursor.is_some() {
+            return Err(format!("[KeyValue Wasm] Unexpected keys {:?} (cursor {:?})", listed.keys, listed.cursor));
+        }
+
+        for key in ["blob", "empty", "greeting", "missing"] {
+            bucket.delete(key).map_err(|e| format!("Failed to delete '{}': {:?}", key, e))?;
+        }
+        if bucket.exists("blob").map_err(|e| format!("exists failed: {:?}", e))? {
+            return Err("[KeyValue Wasm] 'blob' still exists after delete".to_string());
+        }
+        println!("[KeyValue Wasm] Round trip SUCCESSFUL");
+        Ok(())
+    }
+}
+
+fn set(bucket: &Bucket, key: &str, value: &[u8]) -> Result<(), String> {
+    bucket.set(key, value).map_err(|e| format!("Failed to set '{}': {:?}", key, e))
+}
+
+fn expect(bucket: &Bucket, key: &str, expected: Option<&[u8]>) -> Result<(), String> {
+    let got = bucket.get(key).map_err(|e| format!("Failed to get '{}': {:?}", key, e))?;
+    if got.as_deref() != expected {
+        return Err(format!(
+            "[KeyValue Wasm] '{}' read back {:?} bytes, expected {:?}",
+            key,
+            got.map(|v| v.len()),
+            expected.map(|v| v.len())
+        ));
+    }
+    Ok(())
+}
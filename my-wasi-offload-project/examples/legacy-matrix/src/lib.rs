@@ -0,0 +1,67 @@
+// Generate bindings for the `legacy-matrix` world.
+wit_bindgen::generate!({
+    world: "legacy-matrix",
+    path: "wit/world.wit",
+});
+
+use crate::host_allocator;
+use crate::wasi_custom::host_offload::host_allocator::{Handle, HostError, MatrixDimensions};
+use offload_common::codec;
+
+struct Component;
+
+// Everything 0.1.0 offered, plus calls the current provider fails with
+// `host-error` cases added after it. Those have to arrive as 0.1 cases: one
+// this guest can't lift would trap it.
+impl crate::LegacyMatrix for Component {
+    fn run_legacy_matrix() -> Result<(), String> {
+        let a = upload(&[1.0, 2.0, 3.0, 4.0], 2, 2)?;
+        let b = upload(&[5.0, 6.0, 7.0, 8.0], 2, 2)?;
+        let c = host_allocator::matrix_multiply_f32(a, b).map_err(|e| format!("Multiply failed: {:?}", e))?;
+        let dims = host_allocator::get_matrix_dimensions(c).map_err(|e| format!("No dims for C: {:?}", e))?;
+        if (dims.rows, dims.cols) != (2, 2) {
+            return Err(format!("C is {}x{}, expected 2x2", dims.rows, dims.cols));
+        }
+        let bytes = host_allocator::read_from_host(c, 0, 16).map_err(|e| format!("Failed to read C: {:?}", e))?;
+        let values = codec::f32_from_le_bytes(&bytes).ok_or("C is not whole f32s")?;
+        if values != [19.0, 22.0, 43.0, 50.0] {
+            return Err(format!("C = {:?}, expected [19, 22, 43, 50]", values));
+        }
+        println!("[Legacy Matrix Wasm] C = {:?}", values);
+
+        // `allocation-too-large` today.
+        expect("allocating u64::MAX bytes", host_allocator::allocate_buffer(u64::MAX), |e| {
+            matches!(e, HostError::AllocationFailed)
+        })?;
+        let wide = upload(&[1.0; 3], 1, 3)?;
+        expect("multiplying 2x2 by 1x3", host_allocator::matrix_multiply_f32(a, wide), |e| {
+            matches!(e, HostError::DimensionMismatch)
+        })?;
+        expect("reading past the end", host_allocator::read_from_host(a, 8, 16), |e| {
+            matches!(e, HostError::CopyOutOfBounds)
+        })?;
+
+        for h in [a, b, c, wide] {
+            host_allocator::free_buffer(h).map_err(|e| format!("Failed to free {}: {:?}", h, e))?;
+        }
+        expect("freeing twice", host_allocator::free_buffer(a), |e| matches!(e, HostError::InvalidHandle))?;
+        println!("[Legacy Matrix Wasm] Errors arrived as 0.1 cases");
+        Ok(())
+    }
+}
+
+fn upload(values: &[f32], rows: u32, cols: u32) -> Result<Handle, String> {
+    let bytes = codec::f32_to_le_bytes(values);
+    let h = host_allocator::allocate_buffer(bytes.len() as u64).map_err(|e| format!("Failed to allocate: {:?}", e))?;
+    host_allocator::write_to_host(&bytes, h, 0).map_err(|e| format!("Failed to write {}: {:?}", h, e))?;
+    host_allocator::register_matrix_dimensions(h, MatrixDimensions { rows, cols })
+        .map_err(|e| format!("Failed to register {}: {:?}", h, e))?;
+    Ok(h)
+}
+
+fn expect<T: std::fmt::Debug>(what: &str, result: Result<T, HostError>, ok: impl Fn(&HostError) -> bool) -> Result<(), String> {
+    match result {
+        Err(e) if ok(&e) => Ok(()),
+        other => Err(format!("{}: got {:?}", what, other)),
+    }
+}
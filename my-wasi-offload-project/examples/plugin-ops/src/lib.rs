@@ -0,0 +1,73 @@
+// Generate bindings for the `plugin-ops` world.
+wit_bindgen::generate!({
+    world: "plugin-ops",
+    path: "wit/world.wit",
+});
+
+use crate::host_allocator;
+use crate::wasi_custom::host_offload::host_allocator::{ElementType, Handle, HostError};
+
+const N: u32 = 1000;
+
+struct Component;
+
+impl crate::PluginOps for Component {
+    fn run_plugin_ops() -> Result<(), String> {
+        if !host_allocator::supports("ops") || !host_allocator::supports("extensions") {
+            return Err("[Plugin Ops Wasm] Provider doesn't support ops and extensions".to_string());
+        }
+        let ops = host_allocator::list_ops();
+        let extensions = host_allocator::list_extensions();
+        println!("[Plugin Ops Wasm] Provider offers {:?}, of which plugins added {:?}", ops, extensions);
+        for op in ["mul-f32", "sum-f32"] {
+            if !extensions.iter().any(|offered| offered == op) {
+                return Err(format!("[Plugin Ops Wasm] '{}' is missing; is elementwise-plugin loaded?", op));
+            }
+            let schema = host_allocator::describe_op(op).map_err(|e| format!("Failed to describe {}: {:?}", op, e))?;
+            let inputs: Vec<&str> = schema.inputs.iter().map(|arg| arg.name.as_str()).collect();
+            println!("[Plugin Ops Wasm] {}({}): {}", schema.name, inputs.join(", "), schema.description);
+        }
+
+        let x: Vec<f32> = (0..N).map(|i| (i % 11) as f32 * 0.5).collect();
+        let y: Vec<f32> = (0..N).map(|i| (i % 5) as f32 - 2.0).collect();
+        let handle_x = upload(&x)?;
+        let handle_y = upload(&y)?;
+        let product = call("mul-f32", &[handle_x, handle_y])?;
+        let sum = call("sum-f32", &[product])?;
+        let got = host_allocator::read_f32(sum, 0, 1).map_err(|e| format!("Failed to read the sum: {:?}", e))?[0];
+        let expected: f32 = x.iter().zip(&y).map(|(a, b)| a * b).sum();
+        if (got - expected).abs() > 1e-3 * expected.abs().max(1.0) {
+            return Err(format!("[Plugin Ops Wasm] x . y is {}, expected {}", got, expected));
+        }
+        println!("[Plugin Ops Wasm] x . y = {} through the plugin", got);
+
+        match host_allocator::call_op("no-such-op", &[handle_x]) {
+            Err(HostError::UnknownOp) => {}
+            other => return Err(format!("[Plugin Ops Wasm] Unregistered op gave {:?}", other)),
+        }
+        // Checked against the schema before the plugin ever sees it.
+        match host_allocator::call_op("sum-f32", &[handle_x, handle_y]) {
+            Err(HostError::InvalidArguments(_)) => {}
+            other => return Err(format!("[Plugin Ops Wasm] sum-f32 of two vectors gave {:?}", other)),
+        }
+        for h in [handle_x, handle_y, product, sum] {
+            host_allocator::free_buffer(h).map_err(|e| format!("Failed to free {}: {:?}", h, e))?;
+        }
+        Ok(())
+    }
+}
+
+// Runs an op that returns a single buffer.
+fn call(op: &str, inputs: &[Handle]) -> Result<Handle, String> {
+    match host_allocator::call_op(op, inputs).map_err(|e| format!("{} failed: {:?}", op, e))?[..] {
+        [out] => Ok(out),
+        ref outs => Err(format!("{} returned {} buffers, expected 1", op, outs.len())),
+    }
+}
+
+fn upload(values: &[f32]) -> Result<Handle, String> {
+    let h = host_allocator::allocate_typed_buffer(ElementType::F32, values.len() as u64)
+        .map_err(|e| format!("Failed to allocate {} floats: {:?}", values.len(), e))?;
+    host_allocator::write_f32(h, 0, values).map_err(|e| format!("Failed to write {} floats: {:?}", values.len(), e))?;
+    Ok(h)
+}
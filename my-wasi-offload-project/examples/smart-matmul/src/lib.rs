@@ -0,0 +1,78 @@
+// Generate bindings for the `smart-matmul` world. Only the export is used
+// from here; host calls go through `offload_guest` so they share its types.
+wit_bindgen::generate!({
+    world: "smart-matmul",
+    path: "wit/world.wit",
+});
+
+use std::time::{Duration, Instant};
+
+use offload_guest::{local_multiply, SmartMatmul};
+
+// Square products of growing side, each computed `REPEATS` times in the
+// guest and on the host (uploads and read-back included), next to the route
+// `SmartMatmul` picks with a zero threshold, i.e. from estimates alone. The
+// crossover is the first side where the host wins.
+const SIDES: [u32; 7] = [8, 16, 32, 64, 128, 256, 512];
+const REPEATS: u32 = 5;
+
+struct Component;
+
+impl crate::SmartMatmul for Component {
+    fn run_smart_matmul() -> Result<(), String> {
+        println!("[Smart Matmul Wasm] {:>6} {:>14} {:>14} {:>8} {:>8}", "side", "guest", "host", "speedup", "picks");
+        let mut by_estimate = SmartMatmul::new().threshold_flops(0);
+        // A guest this slow loses every estimate, so this one always offloads.
+        let mut always_host = SmartMatmul::new().threshold_flops(0).local_gflops(0.0);
+        let mut crossover = None;
+        for &side in &SIDES {
+            let n = side as usize;
+            let a: Vec<f32> = (0..n * n).map(|i| ((i % 11) as f32 - 5.0) * 0.125).collect();
+            let b: Vec<f32> = (0..n * n).map(|i| ((i % 5) as f32 - 2.0) * 0.25).collect();
+
+            let mut guest = Vec::new();
+            let mut guest_time = Duration::ZERO;
+            for _ in 0..REPEATS {
+                let start = Instant::now();
+                guest = local_multiply(&a, &b, n, n, n);
+                guest_time += start.elapsed();
+            }
+
+            let mut host = Vec::new();
+            let mut host_time = Duration::ZERO;
+            for _ in 0..REPEATS {
+                let start = Instant::now();
+                host = always_host
+                    .multiply(&a, &b, side, side, side)
+                    .map_err(|e| format!("Host multiply failed: {:?}", e))?;
+                host_time += start.elapsed();
+            }
+
+            // Summation order differs between the two, so allow for rounding.
+            let tolerance = 1e-4 * (n as f32).sqrt();
+            if let Some(i) = (0..n * n).find(|&i| (guest[i] - host[i]).abs() > tolerance * guest[i].abs().max(1.0)) {
+                return Err(format!(
+                    "[Smart Matmul Wasm] Side {}: element {} is {} on the host, {} in the guest",
+                    side, i, host[i], guest[i]
+                ));
+            }
+            if crossover.is_none() && host_time < guest_time {
+                crossover = Some(side);
+            }
+            let picks = if by_estimate.offloads(side, side, side) { "host" } else { "guest" };
+            println!(
+                "[Smart Matmul Wasm] {:>6} {:>14?} {:>14?} {:>7.2}x {:>8}",
+                side,
+                guest_time / REPEATS,
+                host_time / REPEATS,
+                guest_time.as_secs_f64() / host_time.as_secs_f64(),
+                picks
+            );
+        }
+        match crossover {
+            Some(side) => println!("[Smart Matmul Wasm] Offloading wins from {0}x{0} on", side),
+            None => println!("[Smart Matmul Wasm] The guest won at every size"),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,80 @@
+// Generate bindings for the `streaming-upload` world, including the
+// `wasi:io/streams` types `buffer-streams` hands out.
+wit_bindgen::generate!({
+    world: "streaming-upload",
+    path: "wit/world.wit",
+});
+
+use offload_common::codec;
+
+use crate::host_allocator;
+use crate::wasi::io::streams::StreamError;
+use crate::wasi_custom::host_offload::buffer_streams;
+use crate::wasi_custom::host_offload::host_allocator::{MatrixLayout, MatrixShape};
+
+// Streams a matrix too large for a comfortable single `write-to-host` into a
+// host buffer, multiplies it by a scaled identity, and streams the result back.
+const N: u32 = 512;
+const SCALE: f32 = 2.0;
+// `blocking-write-and-flush` takes at most 4096 bytes per call.
+const CHUNK: usize = 4096;
+
+struct Component;
+
+impl crate::StreamingUpload for Component {
+    fn run_streaming_upload() -> Result<(), String> {
+        let a_data: Vec<f32> = (0..N * N).map(|i| (i % 97) as f32 * 0.125).collect();
+        let a_bytes = codec::f32_to_le_bytes(&a_data);
+        println!("[Streaming Wasm] Uploading a {}x{} matrix ({} bytes) through a stream", N, N, a_bytes.len());
+
+        let dims = MatrixShape { rows: N, cols: N, layout: MatrixLayout::RowMajor };
+        let handle_a = host_allocator::allocate_buffer(a_bytes.len() as u64)
+            .map_err(|e| format!("Failed to allocate A: {:?}", e))?;
+        let upload = buffer_streams::buffer_write_stream(handle_a, 0)
+            .map_err(|e| format!("Failed to open write stream: {:?}", e))?;
+        for chunk in a_bytes.chunks(CHUNK) {
+            upload.blocking_write_and_flush(chunk)
+                .map_err(|e| format!("Upload failed: {:?}", e))?;
+        }
+        // Finish the upload before anything else touches the buffer.
+        drop(upload);
+        host_allocator::register_matrix_shape(handle_a, dims)
+            .map_err(|e| format!("Failed to register dims A: {:?}", e))?;
+
+        let identity: Vec<f32> = (0..N * N).map(|i| if i % (N + 1) == 0 { SCALE } else { 0.0 }).collect();
+        let handle_i = host_allocator::allocate_buffer(a_bytes.len() as u64)
+            .map_err(|e| format!("Failed to allocate identity: {:?}", e))?;
+        host_allocator::write_f32(handle_i, 0, &identity)
+            .map_err(|e| format!("Failed to write identity: {:?}", e))?;
+        host_allocator::register_matrix_shape(handle_i, dims)
+            .map_err(|e| format!("Failed to register dims identity: {:?}", e))?;
+
+        let handle_c = host_allocator::matrix_multiply_f32(handle_a, handle_i)
+            .map_err(|e| format!("Matrix multiplication failed: {:?}", e))?;
+
+        let download = buffer_streams::buffer_read_stream(handle_c, 0)
+            .map_err(|e| format!("Failed to open read stream: {:?}", e))?;
+        let mut c_bytes = Vec::with_capacity(a_bytes.len());
+        loop {
+            match download.blocking_read(CHUNK as u64) {
+                Ok(chunk) => c_bytes.extend_from_slice(&chunk),
+                Err(StreamError::Closed) => break,
+                Err(e) => return Err(format!("Download failed: {:?}", e)),
+            }
+        }
+        drop(download);
+        println!("[Streaming Wasm] Downloaded {} bytes", c_bytes.len());
+
+        for h in [handle_a, handle_i, handle_c] {
+            host_allocator::free_buffer(h).map_err(|e| format!("Failed to free {}: {:?}", h, e))?;
+        }
+
+        let c_data = codec::f32_from_le_bytes(&c_bytes).ok_or("Failed to parse C data".to_string())?;
+        let expected: Vec<f32> = a_data.iter().map(|v| v * SCALE).collect();
+        if c_data != expected {
+            return Err("[Streaming Wasm] Result is not A scaled by the identity".to_string());
+        }
+        println!("[Streaming Wasm] Round trip SUCCESSFUL");
+        Ok(())
+    }
+}
@@ -0,0 +1,5 @@
+module tinygo-matrix
+
+go 1.22
+
+require go.bytecodealliance.org/cm v0.2.2
@@ -0,0 +1,246 @@
+// A client in TinyGo: checks that the interface holds up from a
+// garbage-collected guest. It provokes `host-error` cases and inspects their
+// payloads through the generated variant accessors, uploads and downloads a
+// matrix through `buffer-streams`, and forces collections while the provider's
+// lists and stream resources are still in use. Needs the native provider for
+// the streams; see configs/tinygo-matrix.toml.
+package main
+
+import (
+	"encoding/binary"
+	"fmt"
+	"math"
+	"runtime"
+
+	"go.bytecodealliance.org/cm"
+
+	hostallocator "tinygo-matrix/internal/wasi-custom/host-offload/host-allocator"
+	bufferstreams "tinygo-matrix/internal/wasi-custom/host-offload/buffer-streams"
+	tinygomatrix "tinygo-matrix/internal/my-org/tinygo-matrix-world/tinygo-matrix"
+)
+
+type handle = hostallocator.Handle
+
+const (
+	n = 64
+	// `blocking-write-and-flush` takes at most 4096 bytes per call.
+	chunk = 4096
+	// A handle no provider hands out this early.
+	bogusHandle handle = 0xdeadbeef
+)
+
+func init() {
+	tinygomatrix.Exports.RunTinygoMatrix = func() cm.Result[string, struct{}, string] {
+		if err := run(); err != nil {
+			return cm.Err[cm.Result[string, struct{}, string]](err.Error())
+		}
+		return cm.OK[cm.Result[string, struct{}, string]](struct{}{})
+	}
+}
+
+// Unwraps a provider result into Go's (value, error) convention.
+func unwrap[Shape, T any](what string, r cm.Result[Shape, T, hostallocator.HostError]) (T, error) {
+	if err := r.Err(); err != nil {
+		var zero T
+		return zero, fmt.Errorf("%s failed: %s", what, describe(*err))
+	}
+	return *r.OK(), nil
+}
+
+// The case name plus its payload, where it has one.
+func describe(e hostallocator.HostError) string {
+	switch {
+	case e.ComputationError() != nil:
+		return "computation-error(" + *e.ComputationError() + ")"
+	case e.InvalidArguments() != nil:
+		return "invalid-arguments(" + *e.InvalidArguments() + ")"
+	case e.RateLimited() != nil:
+		return fmt.Sprintf("rate-limited(%d)", *e.RateLimited())
+	case e.AllocationTooLarge() != nil:
+		limit := e.AllocationTooLarge()
+		return fmt.Sprintf("allocation-too-large(requested %d, max %d)", limit.Requested, limit.Max)
+	}
+	return e.String()
+}
+
+func run() error {
+	if err := checkErrors(); err != nil {
+		return err
+	}
+	return streamRoundTrip()
+}
+
+// Each call must fail with the expected case, and payload-carrying cases must
+// carry something readable.
+func checkErrors() error {
+	fmt.Println("[TinyGo Wasm] Provoking provider errors...")
+	var failed []string
+	check := func(name string, e *hostallocator.HostError, ok func(hostallocator.HostError) bool) {
+		switch {
+		case e == nil:
+			fmt.Printf("[TinyGo Wasm]   FAIL  %s: succeeded\n", name)
+			failed = append(failed, name)
+		case !ok(*e):
+			fmt.Printf("[TinyGo Wasm]   FAIL  %s: got %s\n", name, describe(*e))
+			failed = append(failed, name)
+		default:
+			fmt.Printf("[TinyGo Wasm]   ok    %s: %s\n", name, describe(*e))
+		}
+	}
+
+	check("read from unknown handle", hostallocator.ReadFromHost(bogusHandle, 0, 4).Err(),
+		hostallocator.HostError.InvalidHandle)
+
+	small, err := unwrap("allocate", hostallocator.AllocateBuffer(16))
+	if err != nil {
+		return err
+	}
+	check("read past the end", hostallocator.ReadFromHost(small, 8, 16).Err(),
+		hostallocator.HostError.CopyOutOfBounds)
+	check("allocate 2^64-1 bytes", hostallocator.AllocateBuffer(math.MaxUint64).Err(),
+		func(e hostallocator.HostError) bool {
+			limit := e.AllocationTooLarge()
+			return limit != nil && limit.Requested == math.MaxUint64 && limit.Max < limit.Requested
+		})
+	check("unknown op", hostallocator.CallOp("no-such-op", cm.ToList([]handle{small})).Err(),
+		hostallocator.HostError.UnknownOp)
+	check("matmul-f32 with one input", hostallocator.CallOp("matmul-f32", cm.ToList([]handle{small})).Err(),
+		func(e hostallocator.HostError) bool {
+			return e.InvalidArguments() != nil && len(*e.InvalidArguments()) > 0
+		})
+
+	a, err := matrix(2, 3)
+	if err != nil {
+		return err
+	}
+	check("2x3 times 2x3", hostallocator.MatrixMultiplyF32(a, a).Err(),
+		hostallocator.HostError.DimensionMismatch)
+
+	for _, h := range []handle{small, a} {
+		if _, err := unwrap("free", hostallocator.FreeBuffer(h)); err != nil {
+			return err
+		}
+	}
+	check("double free", hostallocator.FreeBuffer(small).Err(), hostallocator.HostError.InvalidHandle)
+
+	if len(failed) > 0 {
+		return fmt.Errorf("[TinyGo Wasm] %d error checks failed: %v", len(failed), failed)
+	}
+	return nil
+}
+
+// A rows x cols matrix written with `write-f32`.
+func matrix(rows, cols uint32) (handle, error) {
+	values := make([]float32, rows*cols)
+	for i := range values {
+		values[i] = float32(i % 13)
+	}
+	h, err := unwrap("allocate", hostallocator.AllocateTypedBuffer(hostallocator.ElementTypeF32, uint64(len(values))))
+	if err != nil {
+		return 0, err
+	}
+	if _, err := unwrap("write-f32", hostallocator.WriteF32(h, 0, cm.ToList(values))); err != nil {
+		return 0, err
+	}
+	dims := hostallocator.MatrixDimensions{Rows: rows, Cols: cols}
+	_, err = unwrap("register-matrix-dimensions", hostallocator.RegisterMatrixDimensions(h, dims))
+	return h, err
+}
+
+// Streams A up, squares it on the host, and streams the product back, with a
+// collection between every chunk. Values go over the byte path as
+// little-endian f32, whatever the guest's own order.
+func streamRoundTrip() error {
+	aData := make([]float32, n*n)
+	for i := range aData {
+		aData[i] = float32(i%29) * 0.25
+	}
+	aBytes := make([]byte, 4*len(aData))
+	for i, v := range aData {
+		binary.LittleEndian.PutUint32(aBytes[4*i:], math.Float32bits(v))
+	}
+
+	a, err := unwrap("allocate A", hostallocator.AllocateBuffer(uint64(len(aBytes))))
+	if err != nil {
+		return err
+	}
+	upload, err := unwrap("buffer-write-stream", bufferstreams.BufferWriteStream(a, 0))
+	if err != nil {
+		return err
+	}
+	for start := 0; start < len(aBytes); start += chunk {
+		end := min(start+chunk, len(aBytes))
+		if e := upload.BlockingWriteAndFlush(cm.ToList(aBytes[start:end])).Err(); e != nil {
+			return fmt.Errorf("upload failed: %v", *e)
+		}
+		runtime.GC()
+	}
+	// Finish the upload before anything else touches the buffer.
+	upload.ResourceDrop()
+	dims := hostallocator.MatrixDimensions{Rows: n, Cols: n}
+	if _, err := unwrap("register A", hostallocator.RegisterMatrixDimensions(a, dims)); err != nil {
+		return err
+	}
+
+	c, err := unwrap("multiply", hostallocator.MatrixMultiplyF32(a, a))
+	if err != nil {
+		return err
+	}
+
+	download, err := unwrap("buffer-read-stream", bufferstreams.BufferReadStream(c, 0))
+	if err != nil {
+		return err
+	}
+	cBytes := make([]byte, 0, len(aBytes))
+	for {
+		r := download.BlockingRead(chunk)
+		if e := r.Err(); e != nil {
+			if e.Closed() {
+				break
+			}
+			download.ResourceDrop()
+			return fmt.Errorf("download failed: %v", *e)
+		}
+		// The list lives in memory the guest allocated for the provider;
+		// it must survive a collection until it has been copied out.
+		list := *r.OK()
+		runtime.GC()
+		cBytes = append(cBytes, list.Slice()...)
+	}
+	download.ResourceDrop()
+	fmt.Printf("[TinyGo Wasm] Streamed %d bytes up and %d bytes down\n", len(aBytes), len(cBytes))
+
+	// The typed read must agree with the bytes, and both with a reference.
+	typed, err := unwrap("read-f32", hostallocator.ReadF32(c, 0, n*n))
+	if err != nil {
+		return err
+	}
+	runtime.GC()
+	cTyped := typed.Slice()
+	if len(cBytes) != 4*n*n || len(cTyped) != n*n {
+		return fmt.Errorf("[TinyGo Wasm] Got %d bytes and %d floats for a %dx%d result", len(cBytes), len(cTyped), n, n)
+	}
+	for i := 0; i < n*n; i++ {
+		row, col := i/n, i%n
+		var expected float32
+		for k := 0; k < n; k++ {
+			expected += aData[row*n+k] * aData[k*n+col]
+		}
+		fromBytes := math.Float32frombits(binary.LittleEndian.Uint32(cBytes[4*i:]))
+		tolerance := 1e-4 * max(float32(math.Abs(float64(expected))), 1)
+		if fromBytes != cTyped[i] || float32(math.Abs(float64(cTyped[i]-expected))) > tolerance {
+			return fmt.Errorf("[TinyGo Wasm] C[%d][%d]: read-f32 %g, stream %g, expected %g", row, col, cTyped[i], fromBytes, expected)
+		}
+	}
+
+	for _, h := range []handle{a, c} {
+		if _, err := unwrap("free", hostallocator.FreeBuffer(h)); err != nil {
+			return err
+		}
+	}
+	fmt.Println("[TinyGo Wasm] Round trip SUCCESSFUL")
+	return nil
+}
+
+// Exports are set in init; TinyGo's wasip2 target still wants a main.
+func main() {}
@@ -0,0 +1,87 @@
+// Generate bindings for the `vector-dot` world.
+wit_bindgen::generate!({
+    world: "vector-dot",
+    path: "wit/world.wit",
+});
+
+use std::time::{Duration, Instant};
+
+use crate::host_allocator;
+use crate::wasi_custom::host_offload::host_allocator::{Handle, MatrixLayout, MatrixShape};
+
+// Dot products of growing length, each computed `REPEATS` times on the host
+// (as a 1 x n `gemv-f32`) and in the guest. Host timings include uploading
+// both vectors, since that is what a guest offloading a single dot pays.
+const LENGTHS: [u32; 4] = [1 << 10, 1 << 14, 1 << 18, 1 << 20];
+const REPEATS: u32 = 10;
+
+struct Component;
+
+impl crate::VectorDot for Component {
+    fn run_vector_dot() -> Result<(), String> {
+        println!("[Vector Dot Wasm] {:>10} {:>14} {:>14} {:>8}", "length", "host", "guest", "speedup");
+        for &n in &LENGTHS {
+            let x: Vec<f32> = (0..n).map(|i| ((i % 13) as f32 - 6.0) * 0.25).collect();
+            let y: Vec<f32> = (0..n).map(|i| ((i % 7) as f32 - 3.0) * 0.5).collect();
+
+            let mut host_result = 0.0;
+            let mut host_time = Duration::ZERO;
+            for _ in 0..REPEATS {
+                let start = Instant::now();
+                host_result = host_dot(&x, &y)?;
+                host_time += start.elapsed();
+            }
+
+            let mut guest_result = 0.0;
+            let mut guest_time = Duration::ZERO;
+            for _ in 0..REPEATS {
+                let start = Instant::now();
+                guest_result = x.iter().zip(&y).map(|(a, b)| a * b).sum::<f32>();
+                guest_time += start.elapsed();
+            }
+
+            // Summation order differs between the two, so allow for rounding.
+            let tolerance = 1e-4 * guest_result.abs().max(1.0) * (n as f32).sqrt();
+            if (host_result - guest_result).abs() > tolerance {
+                return Err(format!(
+                    "[Vector Dot Wasm] Length {}: host gave {}, guest gave {}",
+                    n, host_result, guest_result
+                ));
+            }
+            println!(
+                "[Vector Dot Wasm] {:>10} {:>14?} {:>14?} {:>7.2}x",
+                n,
+                host_time / REPEATS,
+                guest_time / REPEATS,
+                guest_time.as_secs_f64() / host_time.as_secs_f64()
+            );
+        }
+        Ok(())
+    }
+}
+
+fn host_dot(x: &[f32], y: &[f32]) -> Result<f32, String> {
+    let n = x.len() as u64;
+    let handle_x = upload(x)?;
+    let handle_y = upload(y)?;
+    // x as a single-row matrix, so `gemv` leaves x . y in a one-element output.
+    host_allocator::register_matrix_shape(handle_x, MatrixShape { rows: 1, cols: n as u32, layout: MatrixLayout::RowMajor })
+        .map_err(|e| format!("Failed to register x as a row: {:?}", e))?;
+    let handle_out = upload(&[0.0])?;
+    host_allocator::gemv_f32(1.0, handle_x, handle_y, 0.0, handle_out)
+        .map_err(|e| format!("gemv failed: {:?}", e))?;
+    let out = host_allocator::read_f32(handle_out, 0, 1)
+        .map_err(|e| format!("Failed to read dot product: {:?}", e))?;
+    for h in [handle_x, handle_y, handle_out] {
+        host_allocator::free_buffer(h).map_err(|e| format!("Failed to free {}: {:?}", h, e))?;
+    }
+    Ok(out[0])
+}
+
+fn upload(values: &[f32]) -> Result<Handle, String> {
+    let handle = host_allocator::allocate_buffer(values.len() as u64 * offload_common::codec::F32_SIZE as u64)
+        .map_err(|e| format!("Failed to allocate {} floats: {:?}", values.len(), e))?;
+    host_allocator::write_f32(handle, 0, values)
+        .map_err(|e| format!("Failed to write {} floats: {:?}", values.len(), e))?;
+    Ok(handle)
+}
@@ -0,0 +1,33 @@
+// Compiles the Vulkan backend's GEMM shader variants to SPIR-V; nothing to
+// do without the `vulkan` feature.
+fn main() {
+    #[cfg(feature = "vulkan")]
+    vulkan_shaders();
+}
+
+#[cfg(feature = "vulkan")]
+fn vulkan_shaders() {
+    const SOURCE: &str = "shaders/gemm.comp";
+    println!("cargo:rerun-if-changed={}", SOURCE);
+    let source = std::fs::read_to_string(SOURCE).expect("failed to read the GEMM shader");
+    let out = std::path::PathBuf::from(std::env::var("OUT_DIR").unwrap());
+    let compiler = shaderc::Compiler::new().expect("failed to start shaderc");
+    let variants: [(&str, &[&str]); 4] = [
+        ("gemm_f32", &[]),
+        ("gemm_f64", &["USE_F64"]),
+        ("gemm_f32_subgroup", &["USE_SUBGROUPS"]),
+        ("gemm_f64_subgroup", &["USE_F64", "USE_SUBGROUPS"]),
+    ];
+    for (name, defines) in variants {
+        let mut options = shaderc::CompileOptions::new().unwrap();
+        // Subgroup operations need SPIR-V 1.3.
+        options.set_target_env(shaderc::TargetEnv::Vulkan, shaderc::EnvVersion::Vulkan1_1 as u32);
+        for define in defines {
+            options.add_macro_definition(define, None);
+        }
+        let spirv = compiler
+            .compile_into_spirv(&source, shaderc::ShaderKind::Compute, SOURCE, "main", Some(&options))
+            .unwrap_or_else(|e| panic!("failed to compile {}: {}", name, e));
+        std::fs::write(out.join(format!("{}.spv", name)), spirv.as_binary_u8()).unwrap();
+    }
+}
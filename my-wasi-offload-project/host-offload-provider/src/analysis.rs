@@ -0,0 +1,140 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use crate::wasi_custom::host_offload::host_allocator::Handle;
+use crate::HostState;
+
+// Calls that produce a matrix product, and element-wise ones that could run
+// in the same pass (the graph ops, or a BLAS-1 epilogue) instead of
+// returning to the guest in between.
+const MATMULS: &[&str] = &["matrix-multiply-f32", "matmul-f32", "matmul-accumulate"];
+const ELEMENTWISE: &[&str] = &["add-f32", "relu-f32", "axpy-f32", "scal-f32", "cast", "masked-fill", "where"];
+
+struct Call {
+    op: String,
+    inputs: Vec<Handle>,
+    outputs: Vec<Handle>,
+}
+
+// The guest's calls, in order, while the embedder has analysis on. Kept
+// whole until the report is taken, so meant for profiling runs only.
+#[derive(Default)]
+pub(crate) struct Recorder {
+    calls: Vec<Call>,
+    // xxh64 of each buffer as the guest last wrote it, until it is read.
+    written: HashMap<Handle, u64>,
+    // Buffers read back exactly as written, and their sizes.
+    read_back: Vec<(Handle, u64)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Suggestion {
+    // Each handle in `results` came from a multiply that `ops` then
+    // processed one call at a time; one `execute-graph` could do it all.
+    FuseAfterMatmul { ops: Vec<String>, results: Vec<Handle> },
+    // The guest wrote these buffers and read them back unchanged, `bytes`
+    // in all; it already had the data.
+    UnchangedReadBack { handles: Vec<Handle>, bytes: u64 },
+}
+
+impl fmt::Display for Suggestion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Suggestion::FuseAfterMatmul { ops, results } => write!(
+                f,
+                "{} element-wise op{} ({}) following the matmul on handle{} {:?} could be one fused call",
+                ops.len(),
+                if ops.len() == 1 { "" } else { "s" },
+                ops.join(", "),
+                if results.len() == 1 { "" } else { "s" },
+                results
+            ),
+            Suggestion::UnchangedReadBack { handles, bytes } => {
+                write!(f, "buffers {:?} were written then read back unchanged ({} bytes)", handles, bytes)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnalysisReport {
+    pub calls: usize,
+    pub suggestions: Vec<Suggestion>,
+}
+
+impl Recorder {
+    fn report(&self) -> AnalysisReport {
+        // Chains of element-wise calls, each consuming the previous result,
+        // right after a multiply; grouped by the ops in them.
+        let mut chains: BTreeMap<Vec<String>, Vec<Handle>> = BTreeMap::new();
+        for (i, call) in self.calls.iter().enumerate() {
+            if !MATMULS.contains(&call.op.as_str()) {
+                continue;
+            }
+            let mut current = &call.outputs;
+            let mut ops = Vec::new();
+            for next in &self.calls[i + 1..] {
+                if !ELEMENTWISE.contains(&next.op.as_str()) || !next.inputs.iter().any(|h| current.contains(h)) {
+                    break;
+                }
+                ops.push(next.op.clone());
+                current = &next.outputs;
+            }
+            if !ops.is_empty() {
+                chains.entry(ops).or_default().extend(&call.outputs);
+            }
+        }
+        let mut suggestions: Vec<Suggestion> =
+            chains.into_iter().map(|(ops, results)| Suggestion::FuseAfterMatmul { ops, results }).collect();
+        if !self.read_back.is_empty() {
+            suggestions.push(Suggestion::UnchangedReadBack {
+                handles: self.read_back.iter().map(|&(h, _)| h).collect(),
+                bytes: self.read_back.iter().map(|&(_, bytes)| bytes).sum(),
+            });
+        }
+        AnalysisReport { calls: self.calls.len(), suggestions }
+    }
+}
+
+impl HostState {
+    // Embedder knob: start recording calls for `analysis_report`.
+    pub fn enable_analysis(&mut self) {
+        self.analysis.get_or_insert_with(Recorder::default);
+    }
+
+    // What the calls so far suggest, or None unless analysis is on.
+    pub fn analysis_report(&self) -> Option<AnalysisReport> {
+        self.analysis.as_ref().map(Recorder::report)
+    }
+
+    // Sensitive handles are left out of every call recorded.
+    pub(crate) fn trace(&mut self, op: &str, inputs: &[Handle], outputs: &[Handle]) {
+        if let Some(recorder) = self.analysis.as_mut() {
+            let visible =
+                |handles: &[Handle]| handles.iter().copied().filter(|h| !self.sensitive.contains(h)).collect();
+            recorder.calls.push(Call { op: op.to_string(), inputs: visible(inputs), outputs: visible(outputs) });
+        }
+    }
+
+    pub(crate) fn trace_guest_write(&mut self, h: Handle) {
+        let Some(recorder) = self.analysis.as_mut().filter(|_| !self.sensitive.contains(&h)) else {
+            return;
+        };
+        if let Some(buffer) = self.buffers.get(&h) {
+            recorder.written.insert(h, xxhash_rust::xxh64::xxh64(buffer, 0));
+        }
+        recorder.calls.push(Call { op: "write-to-host".to_string(), inputs: Vec::new(), outputs: vec![h] });
+    }
+
+    pub(crate) fn trace_guest_read(&mut self, h: Handle) {
+        let Some(recorder) = self.analysis.as_mut().filter(|_| !self.sensitive.contains(&h)) else {
+            return;
+        };
+        if let (Some(digest), Some(buffer)) = (recorder.written.remove(&h), self.buffers.get(&h)) {
+            if digest == xxhash_rust::xxh64::xxh64(buffer, 0) {
+                recorder.read_back.push((h, buffer.len() as u64));
+            }
+        }
+        recorder.calls.push(Call { op: "read-from-host".to_string(), inputs: vec![h], outputs: Vec::new() });
+    }
+}
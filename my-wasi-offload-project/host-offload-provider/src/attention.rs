@@ -0,0 +1,45 @@
+use crate::kernels;
+use crate::wasi_custom::host_offload::host_allocator::{Handle, HostError, MatrixLayout};
+use crate::HostState;
+
+impl HostState {
+    pub fn sdpa(&mut self, q: Handle, k: Handle, v: Handle, mask: Option<Handle>, scale: f32) -> Result<Handle, HostError> {
+        log!(Debug, "Attention over q {}, k {}, v {} (mask {:?}, scale {})", q, k, v, mask, scale);
+        self.charge(0)?;
+        for h in [q, k, v] {
+            self.materialize(h)?;
+        }
+        let (dims_q, matrix_q) = self.read_matrix_f32(q)?;
+        let (_, matrix_k) = self.read_matrix_f32(k)?;
+        let (_, matrix_v) = self.read_matrix_f32(v)?;
+        if matrix_q.ncols() != matrix_k.ncols() || matrix_k.nrows() != matrix_v.nrows() {
+            return Err(HostError::DimensionMismatch);
+        }
+        let keys = matrix_k.nrows();
+        let keep = self.attention_mask(mask, matrix_q.nrows(), keys)?;
+        if self.dry_run {
+            let out = nalgebra::DMatrix::zeros(matrix_q.nrows(), matrix_v.ncols());
+            return Ok(self.store_matrix_f32(&out, dims_q.layout));
+        }
+        let keep = |i: usize, j: usize| keep.as_ref().map_or(true, |keep| keep[i * keys + j]);
+        let out = kernels::sdpa_f32(&matrix_q, &matrix_k, &matrix_v, scale, keep, self.compute_mode, self.op_deadline())?;
+        Ok(self.store_matrix_f32(&out, dims_q.layout))
+    }
+
+    // Row-major query x key flags from an attention mask of any element
+    // type; unregistered masks are read in stored order.
+    pub(crate) fn attention_mask(&mut self, mask: Option<Handle>, queries: usize, keys: usize) -> Result<Option<Vec<bool>>, HostError> {
+        let Some(mask) = mask else {
+            return Ok(None);
+        };
+        let (_, flags) = self.elements(mask)?;
+        let dims = self.matrix_dims.get(&mask).copied();
+        let shape = dims.map(|dims| (dims.rows as usize, dims.cols as usize));
+        if flags.len() != queries * keys || shape.is_some_and(|shape| shape != (queries, keys)) {
+            return Err(HostError::DimensionMismatch);
+        }
+        let column_major = dims.is_some_and(|dims| dims.layout == MatrixLayout::ColumnMajor);
+        let at = |i: usize, j: usize| if column_major { j * queries + i } else { i * keys + j };
+        Ok(Some((0..queries * keys).map(|n| flags[at(n / keys, n % keys)] != 0.0).collect()))
+    }
+}
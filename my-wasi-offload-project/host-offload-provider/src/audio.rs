@@ -0,0 +1,149 @@
+use std::f64::consts::PI;
+use std::time::Instant;
+
+use nalgebra::DMatrix;
+use offload_common::codec;
+use rustfft::num_complex::Complex;
+use rustfft::FftPlanner;
+
+use crate::wasi_custom::host_offload::host_allocator::{ElementType, Handle, HostError, MatrixLayout, MelParams};
+use crate::HostState;
+
+// Zero crossings of the interpolating sinc on either side of each output
+// sample, at the lower of the two rates.
+const RESAMPLE_ZEROS: f64 = 16.0;
+
+// Floor applied before the log in `log-scale` spectrograms.
+const LOG_FLOOR: f32 = 1e-10;
+
+fn invalid(message: impl Into<String>) -> HostError {
+    HostError::InvalidArguments(message.into())
+}
+
+fn sinc(x: f64) -> f64 {
+    if x == 0.0 { 1.0 } else { (PI * x).sin() / (PI * x) }
+}
+
+// Band-limited resampling: every output sample is the input under a
+// Hann-windowed sinc, low-passed to the new Nyquist rate when downsampling.
+fn resample_f32(input: &[f32], from: u32, to: u32) -> Vec<f32> {
+    let ratio = to as f64 / from as f64;
+    let cutoff = ratio.min(1.0);
+    let half_width = RESAMPLE_ZEROS / cutoff;
+    let len = (input.len() as u64 * to as u64).div_ceil(from as u64) as usize;
+    (0..len)
+        .map(|n| {
+            let t = n as f64 / ratio;
+            let first = (t - half_width).ceil().max(0.0) as usize;
+            let last = ((t + half_width).floor() as usize).min(input.len() - 1);
+            let mut sum = 0.0;
+            for (i, &x) in input.iter().enumerate().take(last + 1).skip(first) {
+                let offset = i as f64 - t;
+                let window = 0.5 + 0.5 * (PI * offset / half_width).cos();
+                sum += x as f64 * cutoff * sinc(cutoff * offset) * window;
+            }
+            sum as f32
+        })
+        .collect()
+}
+
+// The Slaney mel scale librosa (and so Whisper) uses: linear below 1 kHz,
+// logarithmic above.
+fn hz_to_mel(hz: f64) -> f64 {
+    let log_step = 6.4f64.ln() / 27.0;
+    if hz < 1000.0 { hz * 3.0 / 200.0 } else { 15.0 + (hz / 1000.0).ln() / log_step }
+}
+
+fn mel_to_hz(mel: f64) -> f64 {
+    let log_step = 6.4f64.ln() / 27.0;
+    if mel < 15.0 { mel * 200.0 / 3.0 } else { 1000.0 * ((mel - 15.0) * log_step).exp() }
+}
+
+// `n_mels` x (n_fft / 2 + 1) triangular filters, area-normalized.
+fn mel_filters(sample_rate: f64, n_fft: usize, n_mels: usize, f_min: f64, f_max: f64) -> DMatrix<f32> {
+    let (low, high) = (hz_to_mel(f_min), hz_to_mel(f_max));
+    let edges: Vec<f64> = (0..n_mels + 2).map(|i| mel_to_hz(low + (high - low) * i as f64 / (n_mels + 1) as f64)).collect();
+    DMatrix::from_fn(n_mels, n_fft / 2 + 1, |m, k| {
+        let hz = k as f64 * sample_rate / n_fft as f64;
+        let rising = (hz - edges[m]) / (edges[m + 1] - edges[m]);
+        let falling = (edges[m + 2] - hz) / (edges[m + 2] - edges[m + 1]);
+        let norm = 2.0 / (edges[m + 2] - edges[m]);
+        (rising.min(falling).max(0.0) * norm) as f32
+    })
+}
+
+impl HostState {
+    pub fn resample(&mut self, h: Handle, from_rate: u32, to_rate: u32) -> Result<Handle, HostError> {
+        log!(Debug, "Resampling {} from {} Hz to {} Hz", h, from_rate, to_rate);
+        self.charge(0)?;
+        if from_rate == 0 || to_rate == 0 {
+            return Err(invalid("Sample rates must be nonzero"));
+        }
+        self.materialize(h)?;
+        let input = self.read_f32(h)?;
+        let output = match (from_rate == to_rate, input.is_empty()) {
+            (false, false) => resample_f32(&input, from_rate, to_rate),
+            _ => input,
+        };
+        let handle = self.new_handle();
+        self.buffers.insert(handle, codec::f32_to_le_bytes(&output).into());
+        self.element_types.insert(handle, ElementType::F32);
+        Ok(handle)
+    }
+
+    pub fn mel_spectrogram(&mut self, h: Handle, params: MelParams) -> Result<Handle, HostError> {
+        log!(Debug, "Mel spectrogram of {} ({:?})", h, params);
+        self.charge(0)?;
+        let (n_fft, hop, n_mels) = (params.n_fft as usize, params.hop_length as usize, params.n_mels as usize);
+        let nyquist = params.sample_rate as f32 / 2.0;
+        let f_max = params.f_max.unwrap_or(nyquist);
+        if n_fft == 0 || hop == 0 || n_mels == 0 || params.sample_rate == 0 {
+            return Err(invalid("n-fft, hop-length, n-mels and sample-rate must be nonzero"));
+        }
+        if !(0.0..f_max).contains(&params.f_min) || f_max > nyquist {
+            return Err(invalid(format!("Need 0 <= f-min < f-max <= {} Hz", nyquist)));
+        }
+        self.materialize(h)?;
+        let samples = self.read_f32(h)?;
+        // Frames are centered on multiples of `hop`, with the signal
+        // reflected at both ends.
+        let pad = n_fft / 2;
+        if samples.len() <= n_fft {
+            return Err(invalid(format!("Need more than n-fft ({}) samples", n_fft)));
+        }
+        let padded: Vec<f32> = (0..samples.len() + n_fft)
+            .map(|i| {
+                let i = i as isize - pad as isize;
+                let last = samples.len() as isize - 1;
+                samples[if i < 0 { -i } else if i > last { 2 * last - i } else { i } as usize]
+            })
+            .collect();
+
+        let frames = 1 + samples.len() / hop;
+        let window: Vec<f32> = (0..n_fft).map(|i| (0.5 - 0.5 * (2.0 * PI * i as f64 / n_fft as f64).cos()) as f32).collect();
+        let fft = FftPlanner::<f32>::new().plan_fft_forward(n_fft);
+        let mut power = DMatrix::<f32>::zeros(n_fft / 2 + 1, frames);
+        let mut buffer = vec![Complex::default(); n_fft];
+        let deadline = self.op_deadline();
+        for frame in 0..frames {
+            if deadline.is_some_and(|deadline| Instant::now() > deadline) {
+                return Err(HostError::Timeout);
+            }
+            let start = frame * hop;
+            for (slot, (&x, &w)) in buffer.iter_mut().zip(padded[start..start + n_fft].iter().zip(&window)) {
+                *slot = Complex::new(x * w, 0.0);
+            }
+            fft.process(&mut buffer);
+            for (k, value) in buffer[..=n_fft / 2].iter().enumerate() {
+                power[(k, frame)] = value.norm_sqr();
+            }
+        }
+
+        let filters = mel_filters(params.sample_rate as f64, n_fft, n_mels, params.f_min as f64, f_max as f64);
+        let mut mel = filters * power;
+        if params.log_scale {
+            mel.apply(|v| *v = v.max(LOG_FLOOR).log10());
+        }
+        Ok(self.store_matrix_f32(&mel, MatrixLayout::RowMajor))
+    }
+}
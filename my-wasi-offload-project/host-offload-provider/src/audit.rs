@@ -0,0 +1,58 @@
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::wasi_custom::host_offload::host_allocator::{Handle, HostError};
+
+// An append-only JSONL record of `host-allocator` calls, one line per call:
+//
+//   {"ts_us":1760600000000000,"instance":"matrix-a","op":"write-to-host","handles":[3],"bytes":4096,"error":null}
+//
+// `handles` are the ones the call was given, then any it returned; `bytes`
+// is the size allocated or moved across the boundary, where the call has
+// one. Payloads are never recorded. Lines are written whole, so several
+// hosts (and processes) can share one file.
+pub struct AuditLog {
+    file: Mutex<File>,
+}
+
+impl AuditLog {
+    pub fn open(path: impl AsRef<Path>) -> std::io::Result<Arc<Self>> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Arc::new(AuditLog { file: Mutex::new(file) }))
+    }
+
+    pub(crate) fn record(
+        &self,
+        instance: &str,
+        op: &str,
+        handles: &[Handle],
+        bytes: Option<u64>,
+        error: Option<&HostError>,
+    ) {
+        let ts_us = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_micros() as u64);
+        let entry = serde_json::json!({
+            "ts_us": ts_us,
+            "instance": instance,
+            "op": op,
+            "handles": handles,
+            "bytes": bytes,
+            "error": error.map(|e| format!("{:?}", e)),
+        });
+        let mut line = entry.to_string();
+        line.push('\n');
+        // A lost line is logged rather than failing the guest's call.
+        if let Err(e) = self.file.lock().unwrap().write_all(line.as_bytes()) {
+            log!(Error, "Failed to append to the audit log: {}", e);
+        }
+    }
+}
+
+// The log a host writes to, and the name its calls are recorded under.
+#[derive(Clone)]
+pub(crate) struct Auditor {
+    pub(crate) log: Arc<AuditLog>,
+    pub(crate) instance: String,
+}
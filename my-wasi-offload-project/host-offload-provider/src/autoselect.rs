@@ -0,0 +1,177 @@
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+use nalgebra::DMatrix;
+
+use crate::backend::Operand;
+use crate::kernels;
+use crate::state::matrix_to_bytes;
+use crate::wasi_custom::host_offload::host_allocator::{
+    BackendChoice, BackendTiming, ComputeMode, ElementType, Handle, HostError, MatrixLayout, MatrixShape,
+};
+use crate::HostState;
+
+pub(crate) const BACKEND_NAME: &str = "auto";
+
+// Probes stop growing past this side length; larger classes are timed at it.
+const MAX_PROBE_SIDE: usize = 512;
+
+// Somewhere an f32 multiply can run.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Route {
+    // The fixed-order loop `deterministic` mode uses; cheapest for tiny inputs.
+    Scalar,
+    // nalgebra's blocked, vectorized GEMM.
+    Simd,
+    #[cfg(not(target_arch = "wasm32"))]
+    Numa,
+    Gpu(u32),
+}
+
+struct Choice {
+    route: Route,
+    timings: Vec<(String, Duration)>,
+}
+
+// Per-size-class routing for `set_auto_backend`, filled in lazily.
+#[derive(Default)]
+pub(crate) struct AutoSelect {
+    choices: BTreeMap<u32, Choice>,
+}
+
+// floor(log2) of the multiply-adds in an `m` x `k` by `k` x `n` product.
+fn size_class(m: u32, k: u32, n: u32) -> u32 {
+    (m as u64 * k as u64 * n as u64).max(1).ilog2()
+}
+
+impl HostState {
+    // Embedder knob (not part of the WIT interface): route f32 multiplies
+    // that aren't pinned to a device to whichever backend timed fastest for
+    // their size class. Turning it off forgets the timings.
+    pub fn set_auto_backend(&mut self, enabled: bool) {
+        self.auto = enabled.then(AutoSelect::default);
+    }
+
+    pub fn get_backend_stats(&self) -> Vec<BackendChoice> {
+        let Some(auto) = &self.auto else {
+            return Vec::new();
+        };
+        auto.choices
+            .iter()
+            .map(|(&size_class, choice)| BackendChoice {
+                size_class,
+                chosen: self.route_name(choice.route),
+                timings: choice
+                    .timings
+                    .iter()
+                    .map(|(backend, time)| BackendTiming { backend: backend.clone(), nanos: time.as_nanos() as u64 })
+                    .collect(),
+            })
+            .collect()
+    }
+
+    fn route_name(&self, route: Route) -> String {
+        match route {
+            Route::Scalar => "scalar".to_string(),
+            Route::Simd => crate::state::BACKEND_NAME.to_string(),
+            #[cfg(not(target_arch = "wasm32"))]
+            Route::Numa => crate::numa::BACKEND_NAME.to_string(),
+            Route::Gpu(n) => format!("{}:gpu({})", self.gpu.as_ref().map_or("none", |gpu| gpu.name()), n),
+        }
+    }
+
+    fn routes(&self) -> Vec<Route> {
+        let mut routes = vec![Route::Scalar, Route::Simd];
+        #[cfg(not(target_arch = "wasm32"))]
+        if self.numa.is_some() {
+            routes.push(Route::Numa);
+        }
+        routes.extend((0..self.gpu_devices().len() as u32).map(Route::Gpu));
+        routes
+    }
+
+    // `a * b` for materialized f32 matrices on the fastest route for their
+    // size, timing every route first if the class is new.
+    pub(crate) fn matmul_f32_auto(&mut self, a: Handle, b: Handle, layout: MatrixLayout) -> Result<(Vec<u8>, Vec<u64>), HostError> {
+        let dims_a = self.check_matrix(a, ElementType::F32)?;
+        let dims_b = self.check_matrix(b, ElementType::F32)?;
+        let class = size_class(dims_a.rows, dims_a.cols, dims_b.cols);
+        let known = self.auto.as_ref().and_then(|auto| auto.choices.get(&class)).map(|choice| choice.route);
+        let route = match known {
+            Some(route) => route,
+            None => self.probe(class),
+        };
+        match route {
+            Route::Gpu(n) => Ok((self.gpu_matmul(n, a, b, ElementType::F32)?, Vec::new())),
+            route => {
+                let (_, matrix_a) = self.read_matrix_f32(a)?;
+                let (_, matrix_b) = self.read_matrix_f32(b)?;
+                self.run_on_cpu(route, &matrix_a, &matrix_b, layout, self.op_deadline())
+            }
+        }
+    }
+
+    fn run_on_cpu(
+        &self,
+        route: Route,
+        a: &DMatrix<f32>,
+        b: &DMatrix<f32>,
+        layout: MatrixLayout,
+        deadline: Option<Instant>,
+    ) -> Result<(Vec<u8>, Vec<u64>), HostError> {
+        let mode = match route {
+            Route::Scalar => ComputeMode::Deterministic,
+            #[cfg(not(target_arch = "wasm32"))]
+            Route::Numa => {
+                let pool = self.numa.as_ref().ok_or(HostError::DeviceUnavailable)?;
+                return pool.matmul_f32(a, b, layout, ComputeMode::Fast, self.compute_hint, deadline);
+            }
+            _ => ComputeMode::Fast,
+        };
+        let c = kernels::matmul_f32_within(a, b, mode, deadline)?;
+        Ok((matrix_to_bytes(&c, layout), Vec::new()))
+    }
+
+    // Times every route on square matrices of size `class` and records the
+    // fastest. Each route runs twice and keeps its better time, so one-off
+    // setup (thread pools, shader pipelines) doesn't count against it;
+    // routes that fail are left out.
+    fn probe(&mut self, class: u32) -> Route {
+        let side = (1usize << (class / 3)).min(MAX_PROBE_SIDE);
+        let a = DMatrix::<f32>::from_fn(side, side, |i, j| ((i + j) % 7) as f32 * 0.25);
+        let bytes = matrix_to_bytes(&a, MatrixLayout::RowMajor);
+        let dims = MatrixShape { rows: side as u32, cols: side as u32, layout: MatrixLayout::RowMajor };
+
+        let mut timings = Vec::new();
+        for route in self.routes() {
+            let mut best: Option<Duration> = None;
+            for _ in 0..2 {
+                let start = Instant::now();
+                let result = match route {
+                    Route::Gpu(n) => {
+                        let gpu = self.gpu.as_mut().ok_or(HostError::DeviceUnavailable);
+                        // Handle 0 is never allocated, so no resident copy is used.
+                        let operand = || Operand { handle: 0, bytes: &bytes, dims };
+                        gpu.and_then(|gpu| gpu.matmul_f32(n, operand(), operand(), None)).map(drop)
+                    }
+                    route => self.run_on_cpu(route, &a, &a, MatrixLayout::RowMajor, None).map(drop),
+                };
+                if result.is_err() {
+                    break;
+                }
+                let elapsed = start.elapsed();
+                best = Some(best.map_or(elapsed, |best| best.min(elapsed)));
+            }
+            if let Some(best) = best {
+                timings.push((route, best));
+            }
+        }
+        let route = timings.iter().min_by_key(|(_, time)| *time).map_or(Route::Simd, |(route, _)| *route);
+        let timings = timings.into_iter().map(|(route, time)| (self.route_name(route), time)).collect();
+        log!(Info, "Size class {} ({}x{} probe) goes to {}", class, side, side, self.route_name(route));
+        if let Some(auto) = self.auto.as_mut() {
+            auto.choices.insert(class, Choice { route, timings });
+        }
+        route
+    }
+}
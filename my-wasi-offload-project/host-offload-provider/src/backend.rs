@@ -0,0 +1,166 @@
+use std::time::Instant;
+
+use crate::state::element_size;
+use crate::wasi_custom::host_offload::host_allocator::{
+    ComputeHint, ComputeMode, Device, DeviceInfo, ElementType, Handle, HostError, MatrixLayout, MatrixShape,
+};
+use crate::HostState;
+
+// A matrix operand as the provider stores it: wire-format bytes (little-endian
+// elements in `dims.layout`) and the handle they belong to, so a backend can
+// use a copy it already holds.
+pub struct Operand<'a> {
+    pub handle: Handle,
+    pub bytes: &'a [u8],
+    pub dims: MatrixShape,
+}
+
+// A device backend for the heavy kernels, behind `device::gpu(n)`. Its
+// devices are numbered from 0. Products come back as wire-format bytes in
+// the layout of `a`; a call still running at `deadline` may be abandoned
+// with `timeout`.
+//
+// Backends may keep buffers resident between calls (see `upload`); the
+// provider calls `evict` before a buffer changes or goes away.
+pub trait ComputeBackend: Send {
+    // As `list-devices` reports it in `device-info.backend`.
+    fn name(&self) -> &'static str;
+    fn devices(&self) -> Vec<DeviceInfo>;
+    fn matmul_f32(&mut self, device: u32, a: Operand, b: Operand, deadline: Option<Instant>) -> Result<Vec<u8>, HostError>;
+    fn matmul_f64(&mut self, device: u32, a: Operand, b: Operand, deadline: Option<Instant>) -> Result<Vec<u8>, HostError>;
+
+    // Copies `h`'s bytes to `device` ahead of use, for `set-placement` and
+    // `prefetch`. Backends without device memory of their own ignore it.
+    fn upload(&mut self, _device: u32, _h: Handle, _bytes: &[u8]) -> Result<(), HostError> {
+        Ok(())
+    }
+
+    fn evict(&mut self, _h: Handle) {}
+
+    // The guest's `set-compute-hint`, for backends that can trade speed for
+    // power (clocks, queue priorities). Most have nothing to adjust.
+    fn set_hint(&mut self, _hint: ComputeHint) {}
+}
+
+// A GPU backend by the name runner configs use, if this build includes it.
+#[cfg(not(target_arch = "wasm32"))]
+#[allow(clippy::match_single_binding)] // Every named arm is behind a feature.
+pub fn open_gpu_backend(name: &str) -> Result<Box<dyn ComputeBackend>, String> {
+    match name {
+        #[cfg(feature = "cuda")]
+        "cuda" => Ok(Box::new(crate::cuda::CudaBackend::new()?)),
+        #[cfg(feature = "vulkan")]
+        "vulkan" => Ok(Box::new(crate::vulkan::VulkanBackend::new()?)),
+        #[cfg(all(feature = "metal", target_os = "macos"))]
+        "metal" => Ok(Box::new(crate::metal::MetalBackend::new()?)),
+        _ => Err(format!("this build has no '{}' GPU backend", name)),
+    }
+}
+
+impl HostState {
+    // Embedder knob (not part of the WIT interface): the backend serving
+    // `device::gpu(n)`. Without one, GPU devices are unavailable.
+    pub fn set_gpu_backend(&mut self, mut backend: Option<Box<dyn ComputeBackend>>) {
+        if let Some(backend) = &mut backend {
+            log!(Info, "GPU backend: {} ({} devices)", backend.name(), backend.devices().len());
+            backend.set_hint(self.compute_hint);
+        }
+        self.gpu = backend;
+    }
+
+    pub(crate) fn gpu_devices(&self) -> Vec<DeviceInfo> {
+        self.gpu.as_ref().map(|gpu| gpu.devices()).unwrap_or_default()
+    }
+
+    pub(crate) fn check_device(&self, device: Device) -> Result<(), HostError> {
+        match device {
+            Device::Cpu => Ok(()),
+            Device::Gpu(n) if (n as usize) < self.gpu_devices().len() => Ok(()),
+            Device::Gpu(_) => Err(HostError::DeviceUnavailable),
+        }
+    }
+
+    // Dims of an `elem` matrix whose buffer holds exactly that many elements.
+    pub(crate) fn check_matrix(&self, h: Handle, elem: ElementType) -> Result<MatrixShape, HostError> {
+        self.check_type(h, elem)?;
+        let dims = *self.matrix_dims.get(&h).ok_or_else(|| self.missing(h))?;
+        let len = self.buffers.get(&h).ok_or_else(|| self.missing(h))?.len() as u64;
+        if len != dims.rows as u64 * dims.cols as u64 * element_size(elem) as u64 {
+            return Err(HostError::Other(format!("Buffer {} size mismatch with dims", h)));
+        }
+        Ok(dims)
+    }
+
+    // `a * b` on `device`, for materialized operands: bytes in `layout` and
+    // what the NUMA pool placed on each node. Deterministic mode stays on the
+    // CPU, the only backend whose results are reproducible bit for bit; in
+    // fast mode, splitting or automatic selection may move unplaced work off it
+    // unless the guest hinted `low-power`.
+    pub(crate) fn matmul_f32_on(
+        &mut self,
+        device: Device,
+        a: Handle,
+        b: Handle,
+        layout: MatrixLayout,
+    ) -> Result<(Vec<u8>, Vec<u64>), HostError> {
+        match device {
+            Device::Gpu(n) if self.compute_mode == ComputeMode::Fast => {
+                let bytes = self.gpu_matmul(n, a, b, ElementType::F32)?;
+                Ok((bytes, Vec::new()))
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            Device::Cpu if self.may_leave_cpu() && self.worth_splitting(a, b) => {
+                self.matmul_f32_split(a, b, layout)
+            }
+            Device::Cpu if self.may_leave_cpu() && self.auto.is_some() => {
+                self.matmul_f32_auto(a, b, layout)
+            }
+            _ => {
+                let (_, matrix_a) = self.read_matrix_f32(a)?;
+                let (_, matrix_b) = self.read_matrix_f32(b)?;
+                self.matmul_f32_bytes(&matrix_a, &matrix_b, layout)
+            }
+        }
+    }
+
+    // Whether unplaced work may run somewhere other than the calling CPU
+    // thread's kernels.
+    fn may_leave_cpu(&self) -> bool {
+        self.compute_mode == ComputeMode::Fast && self.compute_hint != ComputeHint::LowPower
+    }
+
+    pub(crate) fn gpu_matmul(&mut self, device: u32, a: Handle, b: Handle, elem: ElementType) -> Result<Vec<u8>, HostError> {
+        let dims_a = self.check_matrix(a, elem)?;
+        let dims_b = self.check_matrix(b, elem)?;
+        let deadline = self.op_deadline();
+        let a = Operand { handle: a, bytes: &self.buffers[&a], dims: dims_a };
+        let b = Operand { handle: b, bytes: &self.buffers[&b], dims: dims_b };
+        let gpu = self.gpu.as_mut().ok_or(HostError::DeviceUnavailable)?;
+        match elem {
+            ElementType::F64 => gpu.matmul_f64(device, a, b, deadline),
+            _ => gpu.matmul_f32(device, a, b, deadline),
+        }
+    }
+
+    // Starts copying `h` to a GPU it was placed or prefetched on. Pending
+    // and spilled buffers are copied when first used there instead.
+    pub(crate) fn upload_to(&mut self, device: Device, h: Handle) -> Result<(), HostError> {
+        let (Device::Gpu(n), Some(gpu)) = (device, self.gpu.as_mut()) else {
+            return Ok(());
+        };
+        match self.buffers.get(&h) {
+            Some(bytes) => gpu.upload(n, h, bytes),
+            None => Ok(()),
+        }
+    }
+
+    // Drops device copies of `h`, which is about to change or go away.
+    pub(crate) fn evict_device_copies(&mut self, h: Handle) {
+        if let Some(gpu) = self.gpu.as_mut() {
+            gpu.evict(h);
+        }
+        if let Some(resident_on) = self.residency.get_mut(&h) {
+            resident_on.retain(|&device| device == Device::Cpu);
+        }
+    }
+}
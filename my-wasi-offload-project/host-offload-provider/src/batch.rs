@@ -0,0 +1,60 @@
+use crate::wasi_custom::host_offload::host_allocator::{BatchInput, Handle, HostError, OpDescriptor, OpResult};
+use crate::HostState;
+
+impl HostState {
+    // Runs `ops` in order through the standalone calls, stopping after the
+    // first failure.
+    pub fn execute_batch(&mut self, ops: Vec<OpDescriptor>) -> Vec<OpResult> {
+        log!(Debug, "Executing a batch of {} ops", ops.len());
+        let mut results = Vec::with_capacity(ops.len());
+        for op in ops {
+            let result = self.execute_op(op, &results).unwrap_or_else(OpResult::Failed);
+            let failed = matches!(result, OpResult::Failed(_));
+            results.push(result);
+            if failed {
+                break;
+            }
+        }
+        results
+    }
+
+    fn execute_op(&mut self, op: OpDescriptor, earlier: &[OpResult]) -> Result<OpResult, HostError> {
+        let handle = |input: BatchInput| resolve(input, earlier);
+        Ok(match op {
+            OpDescriptor::Allocate(size) => OpResult::Handle(self.allocate_buffer(size)?),
+            OpDescriptor::Free(h) => done(self.free_buffer(handle(h)?))?,
+            OpDescriptor::Write((h, offset, bytes)) => done(self.write_to_host(&bytes, handle(h)?, offset))?,
+            OpDescriptor::Read((h, offset, len)) => OpResult::Bytes(self.read_from_host(handle(h)?, offset, len)?),
+            OpDescriptor::WriteF32((h, offset, values)) => done(self.write_f32(handle(h)?, offset, &values))?,
+            OpDescriptor::ReadF32((h, offset, count)) => {
+                OpResult::F32s(self.read_f32_elems(handle(h)?, offset, count)?)
+            }
+            OpDescriptor::RegisterMatrixShape((h, dims)) => {
+                done(self.register_matrix_shape(handle(h)?, dims))?
+            }
+            OpDescriptor::MatrixMultiplyF32((a, b)) => {
+                OpResult::Handle(self.matrix_multiply_f32(handle(a)?, handle(b)?, None)?)
+            }
+            OpDescriptor::MatmulAccumulate((a, b, c)) => {
+                done(self.matmul_accumulate(handle(a)?, handle(b)?, handle(c)?))?
+            }
+            OpDescriptor::AxpyF32((alpha, x, y)) => done(self.axpy_f32(alpha, handle(x)?, handle(y)?))?,
+            OpDescriptor::ScalF32((alpha, x)) => done(self.scal_f32(alpha, handle(x)?))?,
+        })
+    }
+}
+
+fn done(result: Result<(), HostError>) -> Result<OpResult, HostError> {
+    result.map(|()| OpResult::Done)
+}
+
+fn resolve(input: BatchInput, earlier: &[OpResult]) -> Result<Handle, HostError> {
+    match input {
+        BatchInput::Handle(h) => Ok(h),
+        BatchInput::Result(index) => match earlier.get(index as usize) {
+            Some(OpResult::Handle(h)) => Ok(*h),
+            Some(_) => Err(HostError::InvalidArguments(format!("Op {} of the batch produced no handle", index))),
+            None => Err(HostError::InvalidArguments(format!("Op {} of the batch hasn't run yet", index))),
+        },
+    }
+}
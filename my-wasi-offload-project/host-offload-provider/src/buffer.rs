@@ -0,0 +1,74 @@
+use crate::native::OffloadHost;
+use crate::wasi_custom::host_offload::host_allocator::{Handle, HostError};
+
+// A provider buffer owned by the embedder rather than a guest.
+//
+// Like `OffloadHost` it is `Send + Sync`: every method takes the host's lock
+// for just that call, so tasks on different threads can share one host and
+// use their buffers concurrently. Calls on the same host still run one at a
+// time. The buffer is freed on drop unless `into_handle` gave it away.
+pub struct HostBuffer {
+    host: OffloadHost,
+    handle: Handle,
+    // False once `into_handle` has given the buffer away.
+    owned: bool,
+}
+
+// Both are shared across tokio tasks by embedders; keep it that way.
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<OffloadHost>();
+    assert_send_sync::<HostBuffer>();
+};
+
+impl OffloadHost {
+    pub fn allocate(&self, size: u64) -> Result<HostBuffer, HostError> {
+        let handle = self.lock().allocate_buffer(size)?;
+        Ok(HostBuffer { host: self.clone(), handle, owned: true })
+    }
+}
+
+impl HostBuffer {
+    pub fn handle(&self) -> Handle {
+        self.handle
+    }
+
+    // Gives up ownership, e.g. to pass the buffer to a guest, which then frees it.
+    pub fn into_handle(mut self) -> Handle {
+        self.owned = false;
+        self.handle
+    }
+
+    pub fn len(&self) -> Result<u64, HostError> {
+        self.host.lock().buffer_len(self.handle)
+    }
+
+    pub fn is_empty(&self) -> Result<bool, HostError> {
+        Ok(self.len()? == 0)
+    }
+
+    pub fn read(&self, offset: u64, len: u64) -> Result<Vec<u8>, HostError> {
+        self.host.lock().read_from_host(self.handle, offset, len)
+    }
+
+    pub fn write(&self, bytes: &[u8], offset: u64) -> Result<(), HostError> {
+        self.host.lock().write_to_host(bytes, self.handle, offset)
+    }
+
+    pub fn read_f32(&self, offset_in_elems: u64, count: u64) -> Result<Vec<f32>, HostError> {
+        self.host.lock().read_f32_elems(self.handle, offset_in_elems, count)
+    }
+
+    pub fn write_f32(&self, offset_in_elems: u64, values: &[f32]) -> Result<(), HostError> {
+        self.host.lock().write_f32(self.handle, offset_in_elems, values)
+    }
+}
+
+impl Drop for HostBuffer {
+    fn drop(&mut self) {
+        // Fails only if something else (a closed session, a TTL) freed it first.
+        if self.owned {
+            let _ = self.host.lock().free_buffer(self.handle);
+        }
+    }
+}
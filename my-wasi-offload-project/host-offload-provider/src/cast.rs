@@ -0,0 +1,67 @@
+use half::f16;
+use offload_common::codec;
+
+use crate::state::element_size;
+use crate::wasi_custom::host_offload::host_allocator::{ElementType, Handle, HostError};
+use crate::HostState;
+
+// Widens every element to f64, which holds all supported types exactly.
+pub(crate) fn decode(bytes: &[u8], elem: ElementType) -> Vec<f64> {
+    match elem {
+        ElementType::U8 => bytes.iter().map(|&b| b as f64).collect(),
+        ElementType::S8 => bytes.iter().map(|&b| b as i8 as f64).collect(),
+        ElementType::S32 => codec::i32_from_le_bytes(bytes).unwrap().into_iter().map(|v| v as f64).collect(),
+        ElementType::F16 => bytes.chunks_exact(2).map(|c| f16::from_le_bytes([c[0], c[1]]).to_f64()).collect(),
+        ElementType::F32 => codec::f32_from_le_bytes(bytes).unwrap().into_iter().map(|v| v as f64).collect(),
+        ElementType::F64 => codec::f64_from_le_bytes(bytes).unwrap(),
+    }
+}
+
+// `as` from float to int saturates and maps NaN to 0, which is what we want.
+pub(crate) fn encode(values: &[f64], elem: ElementType) -> Vec<u8> {
+    match elem {
+        ElementType::U8 => values.iter().map(|&v| v.round() as u8).collect(),
+        ElementType::S8 => values.iter().map(|&v| v.round() as i8 as u8).collect(),
+        ElementType::S32 => codec::i32_to_le_bytes(&values.iter().map(|&v| v.round() as i32).collect::<Vec<_>>()),
+        ElementType::F16 => values.iter().flat_map(|&v| f16::from_f64(v).to_le_bytes()).collect(),
+        ElementType::F32 => codec::f32_to_le_bytes(&values.iter().map(|&v| v as f32).collect::<Vec<_>>()),
+        ElementType::F64 => codec::f64_to_le_bytes(values),
+    }
+}
+
+fn is_integer(elem: ElementType) -> bool {
+    matches!(elem, ElementType::U8 | ElementType::S8 | ElementType::S32)
+}
+
+impl HostState {
+    pub fn cast(&mut self, h: Handle, target: ElementType, scale: f32) -> Result<Handle, HostError> {
+        log!(Debug, "Casting {} to {:?} (scale {})", h, target, scale);
+        self.charge(0)?;
+        self.materialize(h)?;
+        let source = self.element_types.get(&h).copied().unwrap_or(ElementType::F32);
+        let bytes = self.buffers.get(&h).ok_or_else(|| self.missing(h))?;
+        if bytes.len() % element_size(source) != 0 {
+            return Err(HostError::Misaligned);
+        }
+        if scale == 0.0 || !scale.is_finite() {
+            return Err(HostError::Other(format!("Invalid cast scale {}", scale)));
+        }
+        let scale = scale as f64;
+        let mut values = decode(bytes, source);
+        match (is_integer(source), is_integer(target)) {
+            (false, true) => values.iter_mut().for_each(|v| *v /= scale),
+            (true, false) => values.iter_mut().for_each(|v| *v *= scale),
+            _ => {}
+        }
+        let out = encode(&values, target);
+
+        let handle = self.new_handle();
+        self.buffers.insert(handle, out.into());
+        if let Some(&dims) = self.matrix_dims.get(&h) {
+            self.matrix_dims.insert(handle, dims);
+        }
+        self.element_types.insert(handle, target);
+        self.trace("cast", &[h], &[handle]);
+        Ok(handle)
+    }
+}
@@ -0,0 +1,36 @@
+// How much validation the provider does, fixed at build time by the
+// `strict-checks` and `fast` features; with both enabled, `strict-checks` wins.
+//
+// The profiles only differ on invalid calls. `strict-checks` catches more of
+// them, earlier: dimensions are checked against the buffer when registered,
+// and untyped buffers must hold whole elements of the type an op reads them
+// as. `fast` trusts the guest and drops the per-call type checks; a guest
+// that breaks them gets garbage instead of an error. Matrix sizes are still
+// checked against the buffer before an op reads it, which costs nothing and
+// keeps a wrong size an error rather than a panic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Profile {
+    Strict,
+    Default,
+    Fast,
+}
+
+pub const PROFILE: Profile = if cfg!(feature = "strict-checks") {
+    Profile::Strict
+} else if cfg!(feature = "fast") {
+    Profile::Fast
+} else {
+    Profile::Default
+};
+
+pub(crate) const STRICT: bool = matches!(PROFILE, Profile::Strict);
+pub(crate) const FAST: bool = matches!(PROFILE, Profile::Fast);
+
+// The name `supports` answers to, if any.
+pub(crate) fn feature() -> Option<&'static str> {
+    match PROFILE {
+        Profile::Strict => Some("strict-checks"),
+        Profile::Default => None,
+        Profile::Fast => Some("fast"),
+    }
+}
@@ -0,0 +1,83 @@
+use crate::native::OffloadHost;
+use crate::wasi_custom::host_offload::host_allocator::{Host, HostError, MatrixDimensions};
+
+mod bindings {
+    wasmtime::component::bindgen!({
+        world: "v0-1-host",
+        path: "wit/compat/world.wit",
+        additional_packages: [
+            { package = "wasi-custom:host-offload@0.1.0", path = "../wit/v0.1" },
+        ],
+    });
+}
+
+pub use bindings::wasi_custom::host_offload::host_allocator as v0_1;
+
+// `host-allocator@0.1.0` on top of the current interface, for guests built
+// before 0.2.0. Its seven functions behave as they do today; only
+// `host-error` is narrower, so errors 0.1 has no case for are mapped by
+// `downgrade` instead of reaching a guest that can't lift them.
+impl v0_1::Host for OffloadHost {
+    fn allocate_buffer(&mut self, size: u64) -> wasmtime::Result<Result<v0_1::Handle, v0_1::HostError>> {
+        Ok(Host::allocate_buffer(self, size)?.map_err(downgrade))
+    }
+
+    fn free_buffer(&mut self, h: v0_1::Handle) -> wasmtime::Result<Result<(), v0_1::HostError>> {
+        Ok(Host::free_buffer(self, h)?.map_err(downgrade))
+    }
+
+    fn write_to_host(
+        &mut self,
+        guest_bytes: Vec<u8>,
+        target_handle: v0_1::Handle,
+        target_offset: u64,
+    ) -> wasmtime::Result<Result<(), v0_1::HostError>> {
+        Ok(Host::write_to_host(self, guest_bytes, target_handle, target_offset)?.map_err(downgrade))
+    }
+
+    fn read_from_host(
+        &mut self,
+        source_handle: v0_1::Handle,
+        source_offset: u64,
+        len: u64,
+    ) -> wasmtime::Result<Result<Vec<u8>, v0_1::HostError>> {
+        Ok(Host::read_from_host(self, source_handle, source_offset, len)?.map_err(downgrade))
+    }
+
+    fn register_matrix_dimensions(
+        &mut self,
+        h: v0_1::Handle,
+        dims: v0_1::MatrixDimensions,
+    ) -> wasmtime::Result<Result<(), v0_1::HostError>> {
+        let dims = MatrixDimensions { rows: dims.rows, cols: dims.cols };
+        Ok(Host::register_matrix_dimensions(self, h, dims)?.map_err(downgrade))
+    }
+
+    fn matrix_multiply_f32(
+        &mut self,
+        handle_a: v0_1::Handle,
+        handle_b: v0_1::Handle,
+    ) -> wasmtime::Result<Result<v0_1::Handle, v0_1::HostError>> {
+        Ok(Host::matrix_multiply_f32(self, handle_a, handle_b)?.map_err(downgrade))
+    }
+
+    fn get_matrix_dimensions(&mut self, h: v0_1::Handle) -> wasmtime::Result<Result<v0_1::MatrixDimensions, v0_1::HostError>> {
+        let dims = Host::get_matrix_dimensions(self, h)?.map_err(downgrade)?;
+        Ok(Ok(v0_1::MatrixDimensions { rows: dims.rows, cols: dims.cols }))
+    }
+}
+
+// The closest 0.1 case: a buffer too large to allocate failed to allocate,
+// an expired handle is no longer valid, and the rest keep their description
+// in `other`.
+pub fn downgrade(e: HostError) -> v0_1::HostError {
+    match e {
+        HostError::InvalidHandle | HostError::Expired => v0_1::HostError::InvalidHandle,
+        HostError::AllocationFailed | HostError::AllocationTooLarge(_) => v0_1::HostError::AllocationFailed,
+        HostError::CopyOutOfBounds => v0_1::HostError::CopyOutOfBounds,
+        HostError::ComputationError(message) => v0_1::HostError::ComputationError(message),
+        HostError::DimensionMismatch => v0_1::HostError::DimensionMismatch,
+        HostError::Other(message) => v0_1::HostError::Other(message),
+        e => v0_1::HostError::Other(format!("{:?}", e)),
+    }
+}
@@ -0,0 +1,443 @@
+use std::sync::Mutex;
+use once_cell::sync::Lazy; // For thread-safe static initialization
+
+use crate::mapreduce;
+use crate::registry;
+use crate::session_admin::{LogLevel, SessionId, SessionLimits, SessionUsage};
+use crate::state::HostState;
+use crate::wasi_custom::host_offload::host_allocator::{
+    ArenaId, BackendChoice, BackendInfo, BufferFlags, CompareOp, ComparisonReport, ComputeHint, ComputeMode,
+    ConcatAxis, CostEstimate, Device, DeviceInfo, DumpDestination, DumpFormat, EigenDecomposition, ElementType,
+    EvaluationMode, Graph, Handle, HandleInfo, HashAlgorithm, HostError, InterfaceVersion, JobId, JobProgress,
+    JobState, MatrixDimensions, MatrixShape, MatrixStructure, MelParams, MemoryStats, OpDescriptor, OpResult, OpSchema, Pooling,
+    ReduceOp, ShmAccess, ShmDescriptor, StorageKind, Summation, TensorMeta, TopK
+};
+
+static HOST_STATE: Lazy<Mutex<HostState>> = Lazy::new(|| Mutex::new(HostState::new()));
+
+// This struct will implement the exported interface functions.
+// Each one locks the global state and forwards to `HostState`.
+struct Component;
+
+impl crate::provider::exports::HostAllocator for Component {
+    fn allocate_buffer(size: u64) -> Result<Handle, HostError> {
+        HOST_STATE.lock().unwrap().allocate_buffer(size)
+    }
+
+    fn free_buffer(h: Handle) -> Result<(), HostError> {
+        HOST_STATE.lock().unwrap().free_buffer(h)
+    }
+
+    fn allocate_buffer_with_ttl(size: u64, ttl_ms: u64) -> Result<Handle, HostError> {
+        HOST_STATE.lock().unwrap().allocate_buffer_with_ttl(size, ttl_ms)
+    }
+
+    fn allocate_buffer_with_storage(size: u64, hint: StorageKind) -> Result<Handle, HostError> {
+        HOST_STATE.lock().unwrap().allocate_buffer_with_storage(size, hint)
+    }
+
+    fn get_storage_kind(h: Handle) -> Result<StorageKind, HostError> {
+        HOST_STATE.lock().unwrap().get_storage_kind(h)
+    }
+
+    fn export_shm(h: Handle, access: ShmAccess) -> Result<ShmDescriptor, HostError> {
+        HOST_STATE.lock().unwrap().export_shm(h, access)
+    }
+
+    fn revoke_shm(h: Handle) -> Result<(), HostError> {
+        HOST_STATE.lock().unwrap().revoke_shm(h)
+    }
+
+    fn write_to_host(
+        guest_bytes: Vec<u8>,
+        target_handle: Handle,
+        target_offset: u64,
+    ) -> Result<(), HostError> {
+        HOST_STATE.lock().unwrap().write_to_host(&guest_bytes, target_handle, target_offset)
+    }
+
+    fn read_from_host(
+        source_handle: Handle,
+        source_offset: u64,
+        len: u64,
+    ) -> Result<Vec<u8>, HostError> {
+        HOST_STATE.lock().unwrap().read_from_host(source_handle, source_offset, len)
+    }
+
+    fn write_f32(h: Handle, offset_in_elems: u64, values: Vec<f32>) -> Result<(), HostError> {
+        HOST_STATE.lock().unwrap().write_f32(h, offset_in_elems, &values)
+    }
+
+    fn read_f32(h: Handle, offset_in_elems: u64, count: u64) -> Result<Vec<f32>, HostError> {
+        HOST_STATE.lock().unwrap().read_f32_elems(h, offset_in_elems, count)
+    }
+
+    fn write_f64(h: Handle, offset_in_elems: u64, values: Vec<f64>) -> Result<(), HostError> {
+        HOST_STATE.lock().unwrap().write_f64(h, offset_in_elems, &values)
+    }
+
+    fn read_f64(h: Handle, offset_in_elems: u64, count: u64) -> Result<Vec<f64>, HostError> {
+        HOST_STATE.lock().unwrap().read_f64_elems(h, offset_in_elems, count)
+    }
+
+    fn write_i32(h: Handle, offset_in_elems: u64, values: Vec<i32>) -> Result<(), HostError> {
+        HOST_STATE.lock().unwrap().write_i32(h, offset_in_elems, &values)
+    }
+
+    fn read_i32(h: Handle, offset_in_elems: u64, count: u64) -> Result<Vec<i32>, HostError> {
+        HOST_STATE.lock().unwrap().read_i32_elems(h, offset_in_elems, count)
+    }
+
+    fn register_matrix_dimensions(h: Handle, dims: MatrixDimensions) -> Result<(), HostError> {
+        HOST_STATE.lock().unwrap().register_matrix_dimensions(h, dims)
+    }
+
+    fn register_matrix_shape(h: Handle, dims: MatrixShape) -> Result<(), HostError> {
+        HOST_STATE.lock().unwrap().register_matrix_shape(h, dims)
+    }
+
+    fn matrix_multiply_f32(handle_a: Handle, handle_b: Handle) -> Result<Handle, HostError> {
+        HOST_STATE.lock().unwrap().matrix_multiply_f32(handle_a, handle_b, None)
+    }
+
+    fn matrix_multiply_f32_on(handle_a: Handle, handle_b: Handle, on: Device) -> Result<Handle, HostError> {
+        HOST_STATE.lock().unwrap().matrix_multiply_f32(handle_a, handle_b, Some(on))
+    }
+
+    fn get_matrix_dimensions(h: Handle) -> Result<MatrixDimensions, HostError> {
+        HOST_STATE.lock().unwrap().get_matrix_dimensions(h)
+    }
+
+    fn get_matrix_shape(h: Handle) -> Result<MatrixShape, HostError> {
+        HOST_STATE.lock().unwrap().get_matrix_shape(h)
+    }
+
+    fn allocate_typed_buffer(element_type: ElementType, count: u64) -> Result<Handle, HostError> {
+        HOST_STATE.lock().unwrap().allocate_typed_buffer(element_type, count)
+    }
+
+    fn register_tensor_meta(h: Handle, meta: TensorMeta) -> Result<(), HostError> {
+        HOST_STATE.lock().unwrap().register_tensor_meta(h, meta)
+    }
+
+    fn get_element_type(h: Handle) -> Result<Option<ElementType>, HostError> {
+        HOST_STATE.lock().unwrap().get_element_type(h)
+    }
+
+    fn set_compute_mode(mode: ComputeMode) {
+        HOST_STATE.lock().unwrap().set_compute_mode(mode)
+    }
+
+    fn set_compute_hint(hint: ComputeHint) {
+        HOST_STATE.lock().unwrap().set_compute_hint(hint)
+    }
+
+    fn set_op_timeout(millis: Option<u64>) {
+        HOST_STATE.lock().unwrap().set_op_timeout(millis)
+    }
+
+    fn get_backend_info() -> BackendInfo {
+        HOST_STATE.lock().unwrap().get_backend_info()
+    }
+
+    fn compare_buffers_f32(
+        handle_a: Handle,
+        handle_b: Handle,
+        rtol: f32,
+        atol: f32,
+    ) -> Result<ComparisonReport, HostError> {
+        HOST_STATE.lock().unwrap().compare_buffers_f32(handle_a, handle_b, rtol, atol)
+    }
+
+    fn axpy_f32(alpha: f32, x: Handle, y: Handle) -> Result<(), HostError> {
+        HOST_STATE.lock().unwrap().axpy_f32(alpha, x, y)
+    }
+
+    fn scal_f32(alpha: f32, x: Handle) -> Result<(), HostError> {
+        HOST_STATE.lock().unwrap().scal_f32(alpha, x)
+    }
+
+    fn gemv_f32(alpha: f32, a: Handle, x: Handle, beta: f32, y: Handle) -> Result<(), HostError> {
+        HOST_STATE.lock().unwrap().gemv_f32(alpha, a, x, beta, y)
+    }
+
+    fn matmul_accumulate(a: Handle, b: Handle, c: Handle) -> Result<(), HostError> {
+        HOST_STATE.lock().unwrap().matmul_accumulate(a, b, c)
+    }
+
+    fn set_matrix_structure(h: Handle, structure: MatrixStructure) -> Result<(), HostError> {
+        HOST_STATE.lock().unwrap().set_matrix_structure(h, structure)
+    }
+
+    fn get_matrix_structure(h: Handle) -> Result<MatrixStructure, HostError> {
+        HOST_STATE.lock().unwrap().get_matrix_structure(h)
+    }
+
+    fn trsm_f32(alpha: f32, a: Handle, b: Handle) -> Result<(), HostError> {
+        HOST_STATE.lock().unwrap().trsm_f32(alpha, a, b)
+    }
+
+    fn gbmv_f32(alpha: f32, a: Handle, x: Handle, beta: f32, y: Handle) -> Result<(), HostError> {
+        HOST_STATE.lock().unwrap().gbmv_f32(alpha, a, x, beta, y)
+    }
+
+    fn symmetric_eigen(h: Handle) -> Result<EigenDecomposition, HostError> {
+        HOST_STATE.lock().unwrap().symmetric_eigen(h)
+    }
+
+    fn kron(a: Handle, b: Handle) -> Result<Handle, HostError> {
+        HOST_STATE.lock().unwrap().kron(a, b)
+    }
+
+    fn outer(x: Handle, y: Handle) -> Result<Handle, HostError> {
+        HOST_STATE.lock().unwrap().outer(x, y)
+    }
+
+    fn einsum(spec: String, inputs: Vec<Handle>) -> Result<Handle, HostError> {
+        HOST_STATE.lock().unwrap().einsum(&spec, &inputs)
+    }
+
+    fn gather_rows(h: Handle, rows: Vec<u32>) -> Result<Handle, HostError> {
+        HOST_STATE.lock().unwrap().gather_rows(h, &rows)
+    }
+
+    fn scatter_rows(src: Handle, dst: Handle, rows: Vec<u32>) -> Result<(), HostError> {
+        HOST_STATE.lock().unwrap().scatter_rows(src, dst, &rows)
+    }
+
+    fn embedding_lookup(table: Handle, ids: Vec<u32>, pooling: Pooling) -> Result<Handle, HostError> {
+        HOST_STATE.lock().unwrap().embedding_lookup(table, &ids, pooling)
+    }
+
+    fn concat(inputs: Vec<Handle>, axis: ConcatAxis) -> Result<Handle, HostError> {
+        HOST_STATE.lock().unwrap().concat(&inputs, axis)
+    }
+
+    fn stack(inputs: Vec<Handle>) -> Result<Handle, HostError> {
+        HOST_STATE.lock().unwrap().stack(&inputs)
+    }
+
+    fn cast(h: Handle, target: ElementType, scale: f32) -> Result<Handle, HostError> {
+        HOST_STATE.lock().unwrap().cast(h, target, scale)
+    }
+
+    fn compare(a: Handle, b: Handle, op: CompareOp) -> Result<Handle, HostError> {
+        HOST_STATE.lock().unwrap().compare(a, b, op)
+    }
+
+    fn where_(cond: Handle, a: Handle, b: Handle) -> Result<Handle, HostError> {
+        HOST_STATE.lock().unwrap().where_(cond, a, b)
+    }
+
+    fn masked_fill(h: Handle, mask: Handle, value: f64) -> Result<(), HostError> {
+        HOST_STATE.lock().unwrap().masked_fill(h, mask, value)
+    }
+
+    fn top_k(h: Handle, k: u32, axis: ConcatAxis) -> Result<TopK, HostError> {
+        HOST_STATE.lock().unwrap().top_k(h, k, axis)
+    }
+
+    fn reduce(h: Handle, op: ReduceOp, axis: Option<ConcatAxis>, summation: Summation) -> Result<Handle, HostError> {
+        HOST_STATE.lock().unwrap().reduce(h, op, axis, summation)
+    }
+
+    fn parallel_map(op_name: String, h: Handle, chunks: u32) -> Result<Handle, HostError> {
+        mapreduce::parallel_map(&HOST_STATE, &op_name, h, chunks)
+    }
+
+    fn sdpa(q: Handle, k: Handle, v: Handle, mask: Option<Handle>, scale: f32) -> Result<Handle, HostError> {
+        HOST_STATE.lock().unwrap().sdpa(q, k, v, mask, scale)
+    }
+
+    fn create_kv_cache(layers: u32, heads: u32, max_seq: u32, head_dim: u32) -> Result<Handle, HostError> {
+        HOST_STATE.lock().unwrap().create_kv_cache(layers, heads, max_seq, head_dim)
+    }
+
+    fn append_kv(cache: Handle, layer: u32, k: Handle, v: Handle) -> Result<(), HostError> {
+        HOST_STATE.lock().unwrap().append_kv(cache, layer, k, v)
+    }
+
+    fn kv_cache_length(cache: Handle, layer: u32) -> Result<u32, HostError> {
+        HOST_STATE.lock().unwrap().kv_cache_length(cache, layer)
+    }
+
+    fn sdpa_cached(q: Handle, cache: Handle, layer: u32, mask: Option<Handle>, scale: f32) -> Result<Handle, HostError> {
+        HOST_STATE.lock().unwrap().sdpa_cached(q, cache, layer, mask, scale)
+    }
+
+    fn resample(h: Handle, from_rate: u32, to_rate: u32) -> Result<Handle, HostError> {
+        HOST_STATE.lock().unwrap().resample(h, from_rate, to_rate)
+    }
+
+    fn mel_spectrogram(h: Handle, params: MelParams) -> Result<Handle, HostError> {
+        HOST_STATE.lock().unwrap().mel_spectrogram(h, params)
+    }
+
+    fn dump_matrix(h: Handle, format: DumpFormat, destination: DumpDestination) -> Result<(), HostError> {
+        HOST_STATE.lock().unwrap().dump_matrix(h, format, destination)
+    }
+
+    fn hash_buffer(h: Handle, algo: HashAlgorithm) -> Result<Vec<u8>, HostError> {
+        HOST_STATE.lock().unwrap().hash_buffer(h, algo)
+    }
+
+    fn intern_buffer(hash: Vec<u8>) -> Result<Option<Handle>, HostError> {
+        HOST_STATE.lock().unwrap().intern_buffer(&hash)
+    }
+
+    fn publish_interned(h: Handle, hash: Vec<u8>) -> Result<(), HostError> {
+        HOST_STATE.lock().unwrap().publish_interned(h, &hash)
+    }
+
+    fn begin_arena() -> ArenaId {
+        HOST_STATE.lock().unwrap().begin_arena()
+    }
+
+    fn end_arena(arena: ArenaId) -> Result<(), HostError> {
+        HOST_STATE.lock().unwrap().end_arena(arena)
+    }
+
+    fn begin_transaction() -> Result<(), HostError> {
+        HOST_STATE.lock().unwrap().begin_transaction()
+    }
+
+    fn commit() -> Result<(), HostError> {
+        HOST_STATE.lock().unwrap().commit()
+    }
+
+    fn rollback() -> Result<(), HostError> {
+        HOST_STATE.lock().unwrap().rollback()
+    }
+
+    fn set_placement(h: Handle, target: Device) -> Result<(), HostError> {
+        HOST_STATE.lock().unwrap().set_placement(h, target)
+    }
+
+    fn prefetch(h: Handle, target: Device) -> Result<(), HostError> {
+        HOST_STATE.lock().unwrap().prefetch(h, target)
+    }
+
+    fn describe_handle(h: Handle) -> Result<HandleInfo, HostError> {
+        HOST_STATE.lock().unwrap().describe_handle(h)
+    }
+
+    fn execute_graph(g: Graph) -> Result<Vec<Handle>, HostError> {
+        HOST_STATE.lock().unwrap().execute_graph(&g)
+    }
+
+    fn set_evaluation_mode(mode: EvaluationMode) {
+        HOST_STATE.lock().unwrap().set_evaluation_mode(mode)
+    }
+
+    fn set_dry_run(enabled: bool) {
+        HOST_STATE.lock().unwrap().set_dry_run(enabled)
+    }
+
+    fn materialize(h: Handle) -> Result<(), HostError> {
+        HOST_STATE.lock().unwrap().materialize_handle(h)
+    }
+
+    fn is_materialized(h: Handle) -> Result<bool, HostError> {
+        HOST_STATE.lock().unwrap().is_materialized(h)
+    }
+
+    fn submit_matmul_f32(handle_a: Handle, handle_b: Handle) -> Result<JobId, HostError> {
+        HOST_STATE.lock().unwrap().submit_matmul_f32(handle_a, handle_b)
+    }
+
+    fn job_status(job: JobId) -> Result<JobState, HostError> {
+        HOST_STATE.lock().unwrap().job_status(job)
+    }
+
+    fn poll_job(job: JobId) -> Result<JobProgress, HostError> {
+        HOST_STATE.lock().unwrap().poll_job(job)
+    }
+
+    fn wait_job(job: JobId) -> Result<Handle, HostError> {
+        HOST_STATE.lock().unwrap().wait_job(job)
+    }
+
+    fn list_devices() -> Vec<DeviceInfo> {
+        HOST_STATE.lock().unwrap().list_devices()
+    }
+
+    fn estimate_cost(op: String, shapes: Vec<(u32, u32)>) -> Result<CostEstimate, HostError> {
+        HOST_STATE.lock().unwrap().estimate_cost(&op, &shapes)
+    }
+
+    fn get_backend_stats() -> Vec<BackendChoice> {
+        HOST_STATE.lock().unwrap().get_backend_stats()
+    }
+
+    fn get_memory_stats() -> MemoryStats {
+        HOST_STATE.lock().unwrap().get_memory_stats()
+    }
+
+    fn pin_buffer(h: Handle) -> Result<(), HostError> {
+        HOST_STATE.lock().unwrap().pin_buffer(h)
+    }
+
+    fn unpin_buffer(h: Handle) -> Result<(), HostError> {
+        HOST_STATE.lock().unwrap().unpin_buffer(h)
+    }
+
+    fn allocate_buffer_with_flags(size: u64, options: BufferFlags) -> Result<Handle, HostError> {
+        HOST_STATE.lock().unwrap().allocate_buffer_with_flags(size, options)
+    }
+
+    fn mark_sensitive(h: Handle) -> Result<(), HostError> {
+        HOST_STATE.lock().unwrap().mark_sensitive(h)
+    }
+
+    fn list_ops() -> Vec<String> {
+        HOST_STATE.lock().unwrap().list_ops()
+    }
+
+    fn describe_op(op_name: String) -> Result<OpSchema, HostError> {
+        HOST_STATE.lock().unwrap().describe_op(&op_name)
+    }
+
+    fn call_op(op_name: String, inputs: Vec<Handle>) -> Result<Vec<Handle>, HostError> {
+        registry::call_op(&HOST_STATE, &op_name, &inputs, false)
+    }
+
+    fn list_extensions() -> Vec<String> {
+        HOST_STATE.lock().unwrap().list_extensions()
+    }
+
+    fn call_extension(op_name: String, inputs: Vec<Handle>) -> Result<Vec<Handle>, HostError> {
+        registry::call_op(&HOST_STATE, &op_name, &inputs, true)
+    }
+
+    fn execute_batch(ops: Vec<OpDescriptor>) -> Vec<OpResult> {
+        HOST_STATE.lock().unwrap().execute_batch(ops)
+    }
+
+    fn get_interface_version() -> InterfaceVersion {
+        HOST_STATE.lock().unwrap().get_interface_version()
+    }
+
+    fn supports(feature: String) -> bool {
+        HOST_STATE.lock().unwrap().supports(&feature)
+    }
+}
+
+impl crate::session_admin::Guest for Component {
+    fn open_session(limits: SessionLimits) -> Result<SessionId, String> {
+        HOST_STATE.lock().unwrap().open_session(limits)
+    }
+
+    fn close_session(id: SessionId) -> Result<u32, String> {
+        HOST_STATE.lock().unwrap().close_session(id)
+    }
+
+    fn session_usage(_id: SessionId) -> Result<SessionUsage, String> {
+        Err("The provider component doesn't measure session usage".to_string())
+    }
+
+    fn set_log_level(level: LogLevel) {
+        HOST_STATE.lock().unwrap().set_log_level(level)
+    }
+}
+
+crate::bindings::export!(Component with_โลก_world ()); // This macro binds the `Component` struct to the world exports. The name after `with_` needs to be the snake_case of the world name.
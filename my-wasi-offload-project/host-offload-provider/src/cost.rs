@@ -0,0 +1,133 @@
+use std::time::{Duration, Instant};
+
+use nalgebra::DMatrix;
+
+use crate::wasi_custom::host_offload::host_allocator::{CostEstimate, HostError, MatrixLayout};
+use crate::HostState;
+
+// Side of the square multiply timed to calibrate, and bytes copied to time
+// guest-sized transfers.
+const PROBE_SIDE: usize = 128;
+const PROBE_BYTES: usize = 4 << 20;
+
+// Measured rates of whichever backend was active when they were taken.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Calibration {
+    backend: &'static str,
+    flops_per_sec: f64,
+    bytes_per_sec: f64,
+}
+
+// Best of two runs, so one-off setup doesn't count.
+fn best_of_two(mut run: impl FnMut()) -> Duration {
+    (0..2)
+        .map(|_| {
+            let start = Instant::now();
+            run();
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+        .max(Duration::from_nanos(1))
+}
+
+// Arithmetic and guest copies for `op` over inputs of `shapes` (rows, cols).
+fn model(op: &str, shapes: &[(u32, u32)]) -> Result<(u64, u64), HostError> {
+    let size = |(rows, cols): (u32, u32)| rows as u64 * cols as u64;
+    let arity = |n: usize| match shapes.len() == n {
+        true => Ok(()),
+        false => Err(HostError::InvalidArguments(format!("'{}' takes {} shapes, got {}", op, n, shapes.len()))),
+    };
+    let (flops, elements) = match op {
+        "matrix-multiply-f32" | "matmul-f32" | "matmul-f64" => {
+            arity(2)?;
+            let ((m, k), (k2, n)) = (shapes[0], shapes[1]);
+            if k != k2 {
+                return Err(HostError::DimensionMismatch);
+            }
+            (2 * m as u64 * k as u64 * n as u64, size(shapes[0]) + size(shapes[1]) + m as u64 * n as u64)
+        }
+        "gemv-f32" => {
+            arity(2)?;
+            let ((m, n), x) = (shapes[0], shapes[1]);
+            if size(x) != n as u64 {
+                return Err(HostError::DimensionMismatch);
+            }
+            // y is read and written back.
+            (2 * size(shapes[0]), size(shapes[0]) + n as u64 + 2 * m as u64)
+        }
+        "add-f32" => {
+            arity(2)?;
+            (size(shapes[0]), size(shapes[0]) * 2 + size(shapes[1]))
+        }
+        "axpy-f32" => {
+            arity(2)?;
+            (2 * size(shapes[0]), size(shapes[0]) + 2 * size(shapes[1]))
+        }
+        "relu-f32" | "scal-f32" => {
+            arity(1)?;
+            (size(shapes[0]), size(shapes[0]) * 2)
+        }
+        "sdpa" => {
+            arity(3)?;
+            let ((s, d), (t, d2), (t2, dv)) = (shapes[0], shapes[1], shapes[2]);
+            if d != d2 || t != t2 {
+                return Err(HostError::DimensionMismatch);
+            }
+            let (s, t) = (s as u64, t as u64);
+            let inputs: u64 = shapes.iter().map(|&shape| size(shape)).sum();
+            // Two products plus a handful of operations per score for the softmax.
+            (2 * s * t * (d as u64 + dv as u64) + 5 * s * t, inputs + s * dv as u64)
+        }
+        _ => return Err(HostError::InvalidArguments(format!("No cost model for '{}'", op))),
+    };
+    let element_size = if op == "matmul-f64" { 8 } else { 4 };
+    Ok((flops, elements * element_size))
+}
+
+impl HostState {
+    pub fn estimate_cost(&mut self, op: &str, shapes: &[(u32, u32)]) -> Result<CostEstimate, HostError> {
+        log!(Debug, "Estimating the cost of '{}' on {:?}", op, shapes);
+        self.charge(0)?;
+        let (flops, bytes_moved) = model(op, shapes)?;
+        let rates = self.calibration()?;
+        let seconds = flops as f64 / rates.flops_per_sec + bytes_moved as f64 / rates.bytes_per_sec;
+        Ok(CostEstimate { flops, bytes_moved, est_millis: seconds * 1000.0 })
+    }
+
+    // Rates for the current backend, measured the first time they're needed
+    // and again whenever the embedder switches backends.
+    fn calibration(&mut self) -> Result<Calibration, HostError> {
+        let backend = self.backend_name();
+        if let Some(calibration) = self.calibration.filter(|c| c.backend == backend) {
+            return Ok(calibration);
+        }
+        let a = DMatrix::<f32>::from_fn(PROBE_SIDE, PROBE_SIDE, |i, j| ((i + j) % 7) as f32 * 0.25);
+        let mut failed = None;
+        let multiply = best_of_two(|| {
+            if let Err(e) = self.matmul_f32_bytes(&a, &a, MatrixLayout::RowMajor) {
+                failed = Some(e);
+            }
+        });
+        if let Some(e) = failed {
+            return Err(e);
+        }
+        let source = vec![1u8; PROBE_BYTES];
+        let copy = best_of_two(|| drop(std::hint::black_box(source.to_vec())));
+
+        let calibration = Calibration {
+            backend,
+            flops_per_sec: 2.0 * (PROBE_SIDE as f64).powi(3) / multiply.as_secs_f64(),
+            bytes_per_sec: PROBE_BYTES as f64 / copy.as_secs_f64(),
+        };
+        log!(
+            Info,
+            "Calibrated {}: {:.2} GFLOP/s, {:.2} GB/s",
+            backend,
+            calibration.flops_per_sec / 1e9,
+            calibration.bytes_per_sec / 1e9
+        );
+        self.calibration = Some(calibration);
+        Ok(calibration)
+    }
+}
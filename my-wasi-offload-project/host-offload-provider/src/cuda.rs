@@ -0,0 +1,206 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Instant;
+
+use cudarc::cublas::sys::cublasOperation_t;
+use cudarc::cublas::{CudaBlas, Gemm, GemmConfig};
+use cudarc::driver::{result, CudaDevice, CudaSlice, CudaStream, DevicePtr, DeviceRepr};
+
+use crate::backend::{ComputeBackend, Operand};
+use crate::wasi_custom::host_offload::host_allocator::{Device, DeviceInfo, Handle, HostError, MatrixLayout};
+
+pub(crate) const BACKEND_NAME: &str = "cuda-cublas";
+
+fn cuda_error(e: impl std::fmt::Debug) -> HostError {
+    HostError::ComputationError(format!("CUDA: {:?}", e))
+}
+
+struct Gpu {
+    dev: Arc<CudaDevice>,
+    blas: CudaBlas,
+    // Uploads for `set-placement` and `prefetch` go out on a stream of their
+    // own so they overlap with compute on the default stream, which waits
+    // for them before it reads a resident buffer.
+    uploads: CudaStream,
+    name: String,
+    memory_bytes: u64,
+}
+
+impl Gpu {
+    fn open(ordinal: usize) -> Result<Self, String> {
+        let failed = |e: &dyn std::fmt::Debug| format!("failed to open CUDA device {}: {:?}", ordinal, e);
+        let dev = CudaDevice::new(ordinal).map_err(|e| failed(&e))?;
+        let name = dev.name().map_err(|e| failed(&e))?;
+        // `mem_get_info` reports on the current context.
+        dev.bind_to_thread().map_err(|e| failed(&e))?;
+        let (_, total) = result::mem_get_info().map_err(|e| failed(&e))?;
+        let blas = CudaBlas::new(dev.clone()).map_err(|e| failed(&e))?;
+        let uploads = dev.fork_default_stream().map_err(|e| failed(&e))?;
+        Ok(Gpu { dev, blas, uploads, name, memory_bytes: total as u64 })
+    }
+}
+
+// cuBLAS sgemm/dgemm on every CUDA device the driver reports.
+//
+// Buffers are copied to the device as raw bytes: GPUs are little-endian like
+// the wire format, so no conversion is needed either way. Operands that were
+// placed or prefetched stay resident until `evict`; everything else is copied
+// in and out per call.
+pub struct CudaBackend {
+    gpus: Vec<Gpu>,
+    resident: HashMap<Handle, (u32, CudaSlice<u8>)>,
+}
+
+impl CudaBackend {
+    pub fn new() -> Result<Self, String> {
+        let count = CudaDevice::count().map_err(|e| format!("CUDA driver unavailable: {:?}", e))?;
+        if count == 0 {
+            return Err("no CUDA devices found".to_string());
+        }
+        let gpus = (0..count as usize).map(Gpu::open).collect::<Result<Vec<_>, _>>()?;
+        Ok(CudaBackend { gpus, resident: HashMap::new() })
+    }
+
+    fn gpu(&self, device: u32) -> Result<&Gpu, HostError> {
+        self.gpus.get(device as usize).ok_or(HostError::DeviceUnavailable)
+    }
+
+    // `a` x `b` with elements of type `T`, as wire-format bytes in `a`'s layout.
+    fn matmul<T: DeviceRepr>(
+        &self,
+        device: u32,
+        a: Operand,
+        b: Operand,
+        deadline: Option<Instant>,
+        one: T,
+        zero: T,
+    ) -> Result<Vec<u8>, HostError>
+    where
+        CudaBlas: Gemm<T>,
+    {
+        let gpu = self.gpu(device)?;
+        if deadline.is_some_and(|d| Instant::now() > d) {
+            return Err(HostError::Timeout);
+        }
+        let size = std::mem::size_of::<T>();
+        let (m, k, n) = (a.dims.rows as usize, a.dims.cols as usize, b.dims.cols as usize);
+
+        // Resident copies if this device has them, else fresh ones that live
+        // until the call returns.
+        gpu.dev.wait_for(&gpu.uploads).map_err(cuda_error)?;
+        let resident = |h: Handle| self.resident.get(&h).filter(|(d, _)| *d == device).map(|(_, slice)| slice);
+        let stage = |operand: &Operand| match resident(operand.handle) {
+            Some(_) => Ok(None),
+            None => gpu.dev.htod_sync_copy(operand.bytes).map(Some).map_err(cuda_error),
+        };
+        let (staged_a, staged_b) = (stage(&a)?, stage(&b)?);
+        let a_dev = resident(a.handle).or(staged_a.as_ref()).unwrap();
+        let b_dev = resident(b.handle).or(staged_b.as_ref()).unwrap();
+        let mut c_dev = gpu.dev.alloc_zeros::<u8>(m * n * size).map_err(cuda_error)?;
+
+        // cuBLAS is column-major: a row-major buffer reads as the transpose of
+        // its matrix. A row-major C is computed as C^T = B^T A^T.
+        let op = |layout| match layout {
+            MatrixLayout::ColumnMajor => cublasOperation_t::CUBLAS_OP_N,
+            MatrixLayout::RowMajor => cublasOperation_t::CUBLAS_OP_T,
+        };
+        let ld = |layout, rows: usize, cols: usize| match layout {
+            MatrixLayout::ColumnMajor => rows as i32,
+            MatrixLayout::RowMajor => cols as i32,
+        };
+        let (lda, ldb) = (ld(a.dims.layout, m, k), ld(b.dims.layout, k, n));
+        // SAFETY: each slice holds exactly the elements its dims describe
+        // (checked by the provider), so the views cover the whole buffer.
+        unsafe {
+            let a_view = a_dev.transmute::<T>(m * k).unwrap();
+            let b_view = b_dev.transmute::<T>(k * n).unwrap();
+            let mut c_view = c_dev.transmute_mut::<T>(m * n).unwrap();
+            match a.dims.layout {
+                MatrixLayout::ColumnMajor => {
+                    let cfg = GemmConfig {
+                        transa: op(a.dims.layout),
+                        transb: op(b.dims.layout),
+                        m: m as i32,
+                        n: n as i32,
+                        k: k as i32,
+                        alpha: one,
+                        lda,
+                        ldb,
+                        beta: zero,
+                        ldc: m as i32,
+                    };
+                    gpu.blas.gemm(cfg, &a_view, &b_view, &mut c_view)
+                }
+                MatrixLayout::RowMajor => {
+                    // The transposes flip which layout needs a transpose.
+                    let flip = |layout| match layout {
+                        MatrixLayout::ColumnMajor => MatrixLayout::RowMajor,
+                        MatrixLayout::RowMajor => MatrixLayout::ColumnMajor,
+                    };
+                    let cfg = GemmConfig {
+                        transa: op(flip(b.dims.layout)),
+                        transb: op(flip(a.dims.layout)),
+                        m: n as i32,
+                        n: m as i32,
+                        k: k as i32,
+                        alpha: one,
+                        lda: ldb,
+                        ldb: lda,
+                        beta: zero,
+                        ldc: n as i32,
+                    };
+                    gpu.blas.gemm(cfg, &b_view, &a_view, &mut c_view)
+                }
+            }
+            .map_err(cuda_error)?;
+        }
+        let bytes = gpu.dev.dtoh_sync_copy(&c_dev).map_err(cuda_error)?;
+        // The kernel can't be interrupted; a late result is still abandoned.
+        if deadline.is_some_and(|d| Instant::now() > d) {
+            return Err(HostError::Timeout);
+        }
+        Ok(bytes)
+    }
+}
+
+impl ComputeBackend for CudaBackend {
+    fn name(&self) -> &'static str {
+        BACKEND_NAME
+    }
+
+    fn devices(&self) -> Vec<DeviceInfo> {
+        self.gpus
+            .iter()
+            .enumerate()
+            .map(|(n, gpu)| DeviceInfo {
+                device: Device::Gpu(n as u32),
+                name: gpu.name.clone(),
+                backend: BACKEND_NAME.to_string(),
+                memory_bytes: Some(gpu.memory_bytes),
+            })
+            .collect()
+    }
+
+    fn matmul_f32(&mut self, device: u32, a: Operand, b: Operand, deadline: Option<Instant>) -> Result<Vec<u8>, HostError> {
+        self.matmul(device, a, b, deadline, 1.0f32, 0.0f32)
+    }
+
+    fn matmul_f64(&mut self, device: u32, a: Operand, b: Operand, deadline: Option<Instant>) -> Result<Vec<u8>, HostError> {
+        self.matmul(device, a, b, deadline, 1.0f64, 0.0f64)
+    }
+
+    fn upload(&mut self, device: u32, h: Handle, bytes: &[u8]) -> Result<(), HostError> {
+        let gpu = self.gpu(device)?;
+        // SAFETY: filled by the copy below before any kernel reads it.
+        let slice = unsafe { gpu.dev.alloc::<u8>(bytes.len()) }.map_err(cuda_error)?;
+        // Pageable memory is staged before the call returns, so `bytes` may
+        // change or go away while the copy is still in flight.
+        unsafe { result::memcpy_htod_async(*slice.device_ptr(), bytes, gpu.uploads.stream) }.map_err(cuda_error)?;
+        self.resident.insert(h, (device, slice));
+        Ok(())
+    }
+
+    fn evict(&mut self, h: Handle) {
+        self.resident.remove(&h);
+    }
+}
@@ -0,0 +1,33 @@
+use nalgebra::{DMatrix, SymmetricEigen};
+
+use crate::wasi_custom::host_offload::host_allocator::{EigenDecomposition, ElementType, Handle, HostError, MatrixLayout};
+use crate::HostState;
+
+impl HostState {
+    pub fn symmetric_eigen(&mut self, h: Handle) -> Result<EigenDecomposition, HostError> {
+        log!(Debug, "Symmetric eigendecomposition of {}", h);
+        self.charge(0)?;
+        self.materialize(h)?;
+        let dims = self.check_matrix(h, ElementType::F32)?;
+        if dims.rows != dims.cols {
+            return Err(HostError::DimensionMismatch);
+        }
+        let (_, matrix) = self.read_matrix_f32(h)?;
+        let n = matrix.nrows();
+        // Iterates until converged; only non-finite input keeps it from it.
+        let eigen = SymmetricEigen::try_new(matrix, f32::EPSILON, 0)
+            .ok_or_else(|| HostError::ComputationError("Eigendecomposition did not converge".to_string()))?;
+        if self.op_deadline().is_some_and(|deadline| std::time::Instant::now() > deadline) {
+            return Err(HostError::Timeout);
+        }
+
+        let mut order: Vec<usize> = (0..n).collect();
+        order.sort_by(|&i, &j| eigen.eigenvalues[i].total_cmp(&eigen.eigenvalues[j]));
+        let values = DMatrix::from_iterator(n, 1, order.iter().map(|&i| eigen.eigenvalues[i]));
+        let vectors = DMatrix::from_fn(n, n, |r, c| eigen.eigenvectors[(r, order[c])]);
+        Ok(EigenDecomposition {
+            values: self.store_matrix_f32(&values, MatrixLayout::RowMajor),
+            vectors: self.store_matrix_f32(&vectors, dims.layout),
+        })
+    }
+}
@@ -0,0 +1,52 @@
+use nalgebra::DMatrix;
+
+use crate::wasi_custom::host_offload::host_allocator::DumpFormat;
+
+pub fn render(matrix: &DMatrix<f32>, format: DumpFormat) -> Vec<u8> {
+    match format {
+        DumpFormat::Text => render_text(matrix).into_bytes(),
+        DumpFormat::Csv => render_csv(matrix).into_bytes(),
+        DumpFormat::Npy => render_npy(matrix),
+    }
+}
+
+fn render_text(matrix: &DMatrix<f32>) -> String {
+    let mut out = format!("{}x{} matrix\n", matrix.nrows(), matrix.ncols());
+    for row in matrix.row_iter() {
+        let cells: Vec<String> = row.iter().map(|v| format!("{:>12.5}", v)).collect();
+        out.push_str(&cells.join(" "));
+        out.push('\n');
+    }
+    out
+}
+
+fn render_csv(matrix: &DMatrix<f32>) -> String {
+    let mut out = String::new();
+    for row in matrix.row_iter() {
+        let cells: Vec<String> = row.iter().map(|v| v.to_string()).collect();
+        out.push_str(&cells.join(","));
+        out.push('\n');
+    }
+    out
+}
+
+// NPY v1.0: magic, version, u16 header length, then a Python dict literal
+// padded with spaces so the data starts on a 64-byte boundary.
+fn render_npy(matrix: &DMatrix<f32>) -> Vec<u8> {
+    const MAGIC: &[u8] = b"\x93NUMPY\x01\x00";
+    let mut header = format!(
+        "{{'descr': '<f4', 'fortran_order': False, 'shape': ({}, {}), }}",
+        matrix.nrows(),
+        matrix.ncols()
+    );
+    let unpadded = MAGIC.len() + 2 + header.len() + 1;
+    header.push_str(&" ".repeat((64 - unpadded % 64) % 64));
+    header.push('\n');
+
+    let mut out = Vec::with_capacity(MAGIC.len() + 2 + header.len() + matrix.len() * 4);
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&(header.len() as u16).to_le_bytes());
+    out.extend_from_slice(header.as_bytes());
+    out.extend_from_slice(&offload_common::codec::f32_to_le_bytes(matrix.transpose().as_slice()));
+    out
+}
@@ -0,0 +1,225 @@
+use std::time::Instant;
+
+use nalgebra::DMatrix;
+
+use crate::kernels;
+use crate::wasi_custom::host_offload::host_allocator::{ComputeMode, ElementType, Handle, HostError, MatrixLayout};
+use crate::HostState;
+
+fn invalid(message: impl Into<String>) -> HostError {
+    HostError::InvalidArguments(message.into())
+}
+
+// A parsed spec: index letters per input and for the output.
+struct Spec {
+    inputs: Vec<Vec<char>>,
+    output: Vec<char>,
+}
+
+// `"ij,jk->ik"`, or numpy's implicit form `"ij,jk"`, whose output is every
+// index used exactly once, in alphabetical order.
+fn parse(spec: &str) -> Result<Spec, HostError> {
+    let spec: String = spec.chars().filter(|c| !c.is_whitespace()).collect();
+    let (lhs, rhs) = match spec.split_once("->") {
+        Some((lhs, rhs)) => (lhs, Some(rhs)),
+        None => (spec.as_str(), None),
+    };
+    let letters = |part: &str| -> Result<Vec<char>, HostError> {
+        match part.chars().all(|c| c.is_ascii_lowercase()) {
+            true => Ok(part.chars().collect()),
+            false => Err(invalid(format!("einsum indices must be letters a-z, got '{}'", part))),
+        }
+    };
+    let inputs = lhs.split(',').map(letters).collect::<Result<Vec<_>, _>>()?;
+    let all: Vec<char> = inputs.iter().flatten().copied().collect();
+    let output = match rhs {
+        Some(rhs) => letters(rhs)?,
+        None => {
+            let mut once: Vec<char> = all.iter().copied().filter(|c| all.iter().filter(|d| *d == c).count() == 1).collect();
+            once.sort();
+            once
+        }
+    };
+    for (i, c) in output.iter().enumerate() {
+        if output[..i].contains(c) {
+            return Err(invalid(format!("einsum output repeats index '{}'", c)));
+        }
+        if !all.contains(c) {
+            return Err(invalid(format!("einsum output index '{}' is in no input", c)));
+        }
+    }
+    Ok(Spec { inputs, output })
+}
+
+// A dense row-major tensor with one letter per axis.
+struct Tensor {
+    indices: Vec<char>,
+    shape: Vec<usize>,
+    data: Vec<f32>,
+}
+
+// Calls `f` with every multi-index of `shape`, last axis fastest.
+fn for_each_index(shape: &[usize], mut f: impl FnMut(&[usize])) {
+    if shape.contains(&0) {
+        return;
+    }
+    let mut index = vec![0; shape.len()];
+    loop {
+        f(&index);
+        let Some(axis) = (0..shape.len()).rev().find(|&axis| index[axis] + 1 < shape[axis]) else {
+            return;
+        };
+        index[axis] += 1;
+        index[axis + 1..].iter_mut().for_each(|i| *i = 0);
+    }
+}
+
+impl Tensor {
+    fn strides(&self) -> Vec<usize> {
+        let mut strides = vec![1; self.shape.len()];
+        for axis in (0..self.shape.len().saturating_sub(1)).rev() {
+            strides[axis] = strides[axis + 1] * self.shape[axis + 1];
+        }
+        strides
+    }
+
+    fn size(&self, index: char) -> usize {
+        self.indices.iter().position(|&c| c == index).map_or(1, |axis| self.shape[axis])
+    }
+
+    // A tensor over the distinct letters in `indices`, in that order, summed
+    // over any of this tensor's letters left out. Letters this tensor repeats
+    // (`ii`) read its diagonal.
+    fn reduce_to(&self, indices: &[char]) -> Tensor {
+        let shape: Vec<usize> = indices.iter().map(|&c| self.size(c)).collect();
+        let mut out = Tensor { indices: indices.to_vec(), shape, data: Vec::new() };
+        out.data = vec![0.0; out.shape.iter().product()];
+        let out_strides = out.strides();
+        let strides = self.strides();
+        let mut distinct: Vec<char> = self.indices.clone();
+        distinct.sort();
+        distinct.dedup();
+        let distinct_shape: Vec<usize> = distinct.iter().map(|&c| self.size(c)).collect();
+        for_each_index(&distinct_shape, |at| {
+            let value_of = |c: char| at[distinct.iter().position(|&d| d == c).unwrap()];
+            let from: usize = self.indices.iter().zip(&strides).map(|(&c, s)| value_of(c) * s).sum();
+            let to: usize = indices.iter().zip(&out_strides).map(|(&c, s)| value_of(c) * s).sum();
+            out.data[to] += self.data[from];
+        });
+        out
+    }
+}
+
+fn distinct(indices: &[char]) -> Vec<char> {
+    let mut seen = Vec::new();
+    indices.iter().for_each(|&c| if !seen.contains(&c) { seen.push(c) });
+    seen
+}
+
+// The product of `a` and `b` over the indices in `keep`. Indices only one side
+// has and `keep` drops are summed out first; shared ones `keep` drops become
+// the inner dimension of one GEMM per value of the shared ones it keeps.
+fn contract(a: &Tensor, b: &Tensor, keep: &[char], mode: ComputeMode, deadline: Option<Instant>) -> Result<Tensor, HostError> {
+    let (ia, ib) = (distinct(&a.indices), distinct(&b.indices));
+    let batch: Vec<char> = ia.iter().copied().filter(|c| ib.contains(c) && keep.contains(c)).collect();
+    let inner: Vec<char> = ia.iter().copied().filter(|c| ib.contains(c) && !keep.contains(c)).collect();
+    let free_a: Vec<char> = ia.iter().copied().filter(|c| !ib.contains(c) && keep.contains(c)).collect();
+    let free_b: Vec<char> = ib.iter().copied().filter(|c| !ia.contains(c) && keep.contains(c)).collect();
+
+    let a = a.reduce_to(&[batch.as_slice(), &free_a, &inner].concat());
+    let b = b.reduce_to(&[batch.as_slice(), &inner, &free_b].concat());
+    let product = |indices: &[char], t: &Tensor| indices.iter().map(|&c| t.size(c)).product::<usize>();
+    let (batches, m, k, n) = (product(&batch, &a), product(&free_a, &a), product(&inner, &a), product(&free_b, &b));
+
+    let mut data = Vec::with_capacity(batches * m * n);
+    for slice in 0..batches {
+        let lhs = DMatrix::from_row_slice(m, k, &a.data[slice * m * k..(slice + 1) * m * k]);
+        let rhs = DMatrix::from_row_slice(k, n, &b.data[slice * k * n..(slice + 1) * k * n]);
+        let c = kernels::matmul_f32_within(&lhs, &rhs, mode, deadline)?;
+        data.extend(c.transpose().iter());
+    }
+    let indices = [batch.as_slice(), &free_a, &free_b].concat();
+    let shape = indices.iter().map(|&c| if free_b.contains(&c) { b.size(c) } else { a.size(c) }).collect();
+    Ok(Tensor { indices, shape, data })
+}
+
+impl HostState {
+    pub fn einsum(&mut self, spec: &str, inputs: &[Handle]) -> Result<Handle, HostError> {
+        log!(Debug, "einsum '{}' over {:?}", spec, inputs);
+        self.charge(0)?;
+        let spec = parse(spec)?;
+        if spec.inputs.len() != inputs.len() {
+            return Err(invalid(format!("einsum spec has {} operands, got {} handles", spec.inputs.len(), inputs.len())));
+        }
+        if spec.output.len() > 2 {
+            return Err(invalid("einsum results are at most 2-dimensional"));
+        }
+        let mut tensors = Vec::with_capacity(inputs.len());
+        for (&h, indices) in inputs.iter().zip(&spec.inputs) {
+            tensors.push(self.einsum_operand(h, indices)?);
+        }
+        // Every use of a letter must agree on its size.
+        for (i, t) in tensors.iter().enumerate() {
+            for (axis, &c) in t.indices.iter().enumerate() {
+                if tensors[..=i].iter().any(|u| u.indices.contains(&c) && u.size(c) != t.shape[axis]) {
+                    return Err(HostError::DimensionMismatch);
+                }
+            }
+        }
+
+        if self.dry_run {
+            let size = |c: &char| tensors.iter().map(|t| t.size(*c)).max().unwrap_or(1);
+            let (rows, cols) = match &spec.output[..] {
+                [r, c] => (size(r), size(c)),
+                [r] => (size(r), 1),
+                _ => (1, 1),
+            };
+            self.check_result_size(rows as u64 * cols as u64)?;
+            return Ok(self.store_matrix_f32(&DMatrix::zeros(rows, cols), MatrixLayout::RowMajor));
+        }
+
+        // Left to right, keeping what the output or a later operand needs.
+        let deadline = self.op_deadline();
+        let mut tensors = tensors.into_iter();
+        let mut acc = tensors.next().ok_or_else(|| invalid("einsum needs at least one operand"))?;
+        for (step, next) in tensors.enumerate() {
+            let later = spec.inputs[step + 2..].iter().flatten();
+            let keep: Vec<char> = spec.output.iter().chain(later).copied().collect();
+            let kept = distinct(&[acc.indices.as_slice(), &next.indices].concat()).into_iter().filter(|c| keep.contains(c));
+            self.check_result_size(kept.map(|c| acc.size(c).max(next.size(c)) as u64).product())?;
+            acc = contract(&acc, &next, &keep, self.compute_mode, deadline)?;
+        }
+        let result = acc.reduce_to(&spec.output);
+
+        let (rows, cols) = match result.shape[..] {
+            [rows, cols] => (rows, cols),
+            [rows] => (rows, 1),
+            _ => (1, 1),
+        };
+        let matrix = DMatrix::from_row_slice(rows, cols, &result.data);
+        Ok(self.store_matrix_f32(&matrix, MatrixLayout::RowMajor))
+    }
+
+    // Two letters read `h` as a registered matrix, one as a plain f32 buffer.
+    fn einsum_operand(&mut self, h: Handle, indices: &[char]) -> Result<Tensor, HostError> {
+        self.materialize(h)?;
+        match indices.len() {
+            1 => {
+                let data = self.read_f32(h)?;
+                Ok(Tensor { indices: indices.to_vec(), shape: vec![data.len()], data })
+            }
+            2 => {
+                self.check_matrix(h, ElementType::F32)?;
+                let (dims, matrix) = self.read_matrix_f32(h)?;
+                let data = matrix.transpose().as_slice().to_vec();
+                let shape = vec![dims.rows as usize, dims.cols as usize];
+                // `ii`: only the diagonal takes part.
+                if indices[0] == indices[1] && shape[0] != shape[1] {
+                    return Err(HostError::DimensionMismatch);
+                }
+                Ok(Tensor { indices: indices.to_vec(), shape, data })
+            }
+            n => Err(invalid(format!("einsum operand {} has {} indices; handles are vectors or matrices", h, n))),
+        }
+    }
+}
@@ -0,0 +1,66 @@
+use crate::wasi_custom::host_offload::host_allocator::{Handle, JobId};
+use crate::HostState;
+
+// Something a guest may want to react to. The provider only queues events;
+// the embedder delivers them through the guest's `host-offload-events`
+// export, since a provider can't call into its own client.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Event {
+    BufferEvicted { handle: Handle, reason: EvictionReason },
+    JobComplete(JobId),
+    QuotaWarning { kind: QuotaKind, remaining_percent: u8 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EvictionReason {
+    Spilled,
+    Expired,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QuotaKind {
+    Operations,
+    Bytes,
+}
+
+impl HostState {
+    // Embedder knob: start queueing events. Off by default, so nothing piles
+    // up for clients that don't handle them.
+    pub fn enable_events(&mut self) {
+        self.events.get_or_insert_with(Vec::new);
+    }
+
+    pub fn take_events(&mut self) -> Vec<Event> {
+        self.events.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    pub(crate) fn emit(&mut self, event: Event) {
+        if let Some(events) = self.events.as_mut() {
+            events.push(event);
+        }
+    }
+
+    // Called whenever a lazy handle is computed; announces it if it is the
+    // result of a job nobody has waited on yet.
+    pub(crate) fn job_completed(&mut self, h: Handle) {
+        if let Some(job) = self.jobs.iter().find(|(_, &result)| result == h).map(|(&job, _)| job) {
+            self.emit(Event::JobComplete(job));
+        }
+    }
+
+    // Advances every unfinished job by one slice of work, for embedders that
+    // drive jobs while the guest is idle. Returns whether any job is still
+    // unfinished and making progress; one that fails (e.g. on its timeout)
+    // is left for `wait-job` to report.
+    pub fn advance_jobs(&mut self) -> bool {
+        let unfinished: Vec<Handle> = self.jobs.values().copied().filter(|h| self.pending.contains_key(h)).collect();
+        let mut busy = false;
+        for h in unfinished {
+            match self.step(h) {
+                Ok(percent) => busy |= percent < 100,
+                Err(e) => log!(Debug, "Job result {} stalled: {:?}", h, e),
+            }
+        }
+        busy
+    }
+}
@@ -0,0 +1,38 @@
+use std::sync::Arc;
+
+use crate::registry::{Op, OpImpl};
+use crate::wasi_custom::host_offload::host_allocator::{Handle, OpSchema};
+use crate::HostState;
+
+// An operation the provider doesn't implement itself, such as one from a
+// compute plugin component the runner loaded. It runs without the provider's
+// lock held, so it may call back into the same provider for its buffers.
+pub trait Extension: Send + Sync {
+    fn execute(&self, op: &str, inputs: &[Handle]) -> Result<Vec<Handle>, String>;
+}
+
+impl HostState {
+    // Embedder knob: make the ops in `schemas` callable, all run by
+    // `extension`. Only a weak reference is kept, since a plugin usually
+    // holds the provider itself; the ops go away once the embedder drops
+    // `extension`.
+    pub fn register_extension(&mut self, schemas: Vec<OpSchema>, extension: &Arc<dyn Extension>) -> Result<(), String> {
+        self.ops.retain(|_, op| op.is_live());
+        if let Some(schema) = schemas.iter().find(|schema| self.ops.contains_key(&schema.name)) {
+            return Err(format!("Operation '{}' is already registered", schema.name));
+        }
+        for schema in schemas {
+            let implementation = OpImpl::Extension(Arc::downgrade(extension));
+            self.ops.insert(schema.name.clone(), Op { schema, implementation });
+        }
+        Ok(())
+    }
+
+    pub fn list_extensions(&self) -> Vec<String> {
+        self.ops
+            .iter()
+            .filter(|(_, op)| op.is_extension() && op.is_live())
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+}
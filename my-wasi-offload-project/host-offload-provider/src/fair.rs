@@ -0,0 +1,122 @@
+use std::sync::{Arc, Condvar, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use crate::session_admin::SessionUsage;
+
+// Weighted-fair turns on the process's compute slots, shared by every native host.
+//
+// Each compute call of a session (see `OffloadHost::blocking`) holds one slot
+// while it runs. With all of them taken, waiting calls are queued, and a freed
+// slot goes to the waiting session that has used the least CPU time per unit
+// of its weight, the longest-waiting call first among equals. So a tenant
+// running back-to-back multiplies can't starve one that computes now and then.
+struct Scheduler {
+    queue: Mutex<Queue>,
+    turn: Condvar,
+}
+
+struct Queue {
+    // Unlimited unless the embedder called `set_compute_slots`.
+    slots: usize,
+    running: usize,
+    // Waiting calls, by ticket.
+    waiting: Vec<(u64, Arc<Share>)>,
+    next_ticket: u64,
+}
+
+static SCHEDULER: OnceLock<Scheduler> = OnceLock::new();
+
+fn scheduler() -> &'static Scheduler {
+    SCHEDULER.get_or_init(|| Scheduler {
+        queue: Mutex::new(Queue { slots: usize::MAX, running: 0, waiting: Vec::new(), next_ticket: 0 }),
+        turn: Condvar::new(),
+    })
+}
+
+// How many compute calls may run at once across all sessions; at least one.
+pub fn set_compute_slots(slots: usize) {
+    scheduler().queue.lock().unwrap().slots = slots.max(1);
+    scheduler().turn.notify_all();
+}
+
+// A session's weight and what it has used; its compute calls take turns through it.
+pub struct Share {
+    weight: u32,
+    usage: Mutex<Usage>,
+}
+
+#[derive(Default)]
+struct Usage {
+    cpu: Duration,
+    calls: u64,
+    queued: Duration,
+}
+
+impl Share {
+    pub fn new(weight: Option<u32>) -> Arc<Self> {
+        Arc::new(Share { weight: weight.unwrap_or(1).max(1), usage: Mutex::new(Usage::default()) })
+    }
+
+    // CPU-seconds per unit of weight: lowest goes next.
+    fn virtual_time(&self) -> f64 {
+        self.usage.lock().unwrap().cpu.as_secs_f64() / self.weight as f64
+    }
+
+    pub fn usage(&self) -> SessionUsage {
+        let usage = self.usage.lock().unwrap();
+        SessionUsage {
+            cpu_seconds: usage.cpu.as_secs_f64(),
+            compute_calls: usage.calls,
+            queued_seconds: usage.queued.as_secs_f64(),
+        }
+    }
+
+    // Blocks until this session's call is scheduled; the slot is held until
+    // the returned turn is dropped.
+    pub fn wait_turn(self: &Arc<Self>) -> Turn {
+        let scheduler = scheduler();
+        let queued = Instant::now();
+        let mut queue = scheduler.queue.lock().unwrap();
+        let ticket = queue.next_ticket;
+        queue.next_ticket += 1;
+        queue.waiting.push((ticket, self.clone()));
+        while queue.running >= queue.slots || queue.next() != Some(ticket) {
+            queue = scheduler.turn.wait(queue).unwrap();
+        }
+        queue.waiting.retain(|(waiting, _)| *waiting != ticket);
+        queue.running += 1;
+        drop(queue);
+        // The next in line may fit in another free slot.
+        scheduler.turn.notify_all();
+        self.usage.lock().unwrap().queued += queued.elapsed();
+        Turn { share: self.clone(), started: Instant::now() }
+    }
+}
+
+impl Queue {
+    fn next(&self) -> Option<u64> {
+        self.waiting
+            .iter()
+            .map(|(ticket, share)| (share.virtual_time(), *ticket))
+            .min_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)))
+            .map(|(_, ticket)| ticket)
+    }
+}
+
+// A compute slot, charged to its session and handed on when dropped.
+pub struct Turn {
+    share: Arc<Share>,
+    started: Instant,
+}
+
+impl Drop for Turn {
+    fn drop(&mut self) {
+        let mut usage = self.share.usage.lock().unwrap();
+        usage.cpu += self.started.elapsed();
+        usage.calls += 1;
+        drop(usage);
+        let scheduler = scheduler();
+        scheduler.queue.lock().unwrap().running -= 1;
+        scheduler.turn.notify_all();
+    }
+}
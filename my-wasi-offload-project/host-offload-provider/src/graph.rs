@@ -0,0 +1,137 @@
+use nalgebra::DMatrix;
+
+use crate::kernels;
+use crate::wasi_custom::host_offload::host_allocator::{Graph, GraphInput, GraphOp, HostError};
+use crate::HostState;
+
+// Evaluates `graph` and returns the matrices for its outputs.
+//
+// Each node value is dropped once its last consumer has run. When an
+// element-wise node is the only consumer of its input it takes the input by
+// value and updates it in place, so `matmul -> add -> relu` allocates a
+// single matrix.
+pub fn execute(state: &HostState, graph: &Graph) -> Result<Vec<DMatrix<f32>>, HostError> {
+    let node_count = graph.nodes.len();
+    let mut uses = vec![0usize; node_count];
+    for (index, op) in graph.nodes.iter().enumerate() {
+        for input in op_inputs(op) {
+            if let GraphInput::Node(n) = input {
+                if *n as usize >= index {
+                    return Err(invalid(format!("Node {} refers to node {}, which is not an earlier node", index, n)));
+                }
+                uses[*n as usize] += 1;
+            }
+        }
+    }
+    for &out in &graph.outputs {
+        if out as usize >= node_count {
+            return Err(invalid(format!("Output refers to missing node {}", out)));
+        }
+        uses[out as usize] += 1;
+    }
+
+    let deadline = state.op_deadline();
+    let mut values: Vec<Option<DMatrix<f32>>> = vec![None; node_count];
+    for (index, op) in graph.nodes.iter().enumerate() {
+        let value = match op {
+            GraphOp::Matmul((a, b)) => {
+                let a = borrow_input(state, &values, a)?;
+                let b = borrow_input(state, &values, b)?;
+                if a.ncols() != b.nrows() {
+                    return Err(HostError::DimensionMismatch);
+                }
+                state.check_matmul_dims([a.nrows(), a.ncols(), b.ncols()])?;
+                match state.dry_run {
+                    true => DMatrix::zeros(a.nrows(), b.ncols()),
+                    false => kernels::matmul_f32_within(&a, &b, state.compute_mode, deadline)?,
+                }
+            }
+            GraphOp::Add((a, b)) => {
+                let rhs = borrow_input(state, &values, b)?;
+                let mut lhs = take_input(state, &mut values, &mut uses, a)?;
+                add_in_place(&mut lhs, &rhs)?;
+                lhs
+            }
+            GraphOp::Relu(a) => {
+                let mut value = take_input(state, &mut values, &mut uses, a)?;
+                value.apply(|x| *x = x.max(0.0));
+                value
+            }
+        };
+        // Consumers that only borrowed their inputs release them here.
+        for input in op_inputs(op) {
+            if let GraphInput::Node(n) = input {
+                release_use(&mut values, &mut uses, *n as usize);
+            }
+        }
+        values[index] = Some(value);
+    }
+
+    let dry_run = state.dry_run;
+    graph
+        .outputs
+        .iter()
+        .map(|&out| values[out as usize].clone().ok_or_else(|| invalid(format!("Node {} produced no value", out))))
+        .map(|value| value.map(|m| if dry_run { DMatrix::zeros(m.nrows(), m.ncols()) } else { m }))
+        .collect()
+}
+
+pub fn op_inputs(op: &GraphOp) -> Vec<&GraphInput> {
+    match op {
+        GraphOp::Matmul((a, b)) | GraphOp::Add((a, b)) => vec![a, b],
+        GraphOp::Relu(a) => vec![a],
+    }
+}
+
+fn borrow_input(state: &HostState, values: &[Option<DMatrix<f32>>], input: &GraphInput) -> Result<DMatrix<f32>, HostError> {
+    match input {
+        GraphInput::Handle(h) => Ok(state.read_matrix_f32(*h)?.1),
+        GraphInput::Node(n) => values[*n as usize].clone().ok_or_else(|| invalid(format!("Node {} has no value", n))),
+    }
+}
+
+// Takes ownership of a node value when this is its last use, so the caller
+// can mutate it in place; otherwise falls back to a copy.
+fn take_input(
+    state: &HostState,
+    values: &mut [Option<DMatrix<f32>>],
+    uses: &mut [usize],
+    input: &GraphInput,
+) -> Result<DMatrix<f32>, HostError> {
+    if let GraphInput::Node(n) = input {
+        let n = *n as usize;
+        if uses[n] == 1 {
+            uses[n] = 0;
+            return values[n].take().ok_or_else(|| invalid(format!("Node {} has no value", n)));
+        }
+    }
+    borrow_input(state, values, input)
+}
+
+fn release_use(values: &mut [Option<DMatrix<f32>>], uses: &mut [usize], n: usize) {
+    if uses[n] == 0 {
+        return; // Already taken by value.
+    }
+    uses[n] -= 1;
+    if uses[n] == 0 {
+        values[n] = None;
+    }
+}
+
+fn add_in_place(lhs: &mut DMatrix<f32>, rhs: &DMatrix<f32>) -> Result<(), HostError> {
+    if lhs.shape() == rhs.shape() {
+        *lhs += rhs;
+        Ok(())
+    } else if rhs.nrows() == 1 && rhs.ncols() == lhs.ncols() {
+        for mut row in lhs.row_iter_mut() {
+            row += rhs.row(0);
+        }
+        Ok(())
+    } else {
+        Err(HostError::DimensionMismatch)
+    }
+}
+
+fn invalid(message: String) -> HostError {
+    HostError::InvalidGraph(message)
+}
@@ -0,0 +1,11 @@
+use sha2::{Digest, Sha256};
+
+use crate::wasi_custom::host_offload::host_allocator::HashAlgorithm;
+
+pub fn digest(bytes: &[u8], algo: HashAlgorithm) -> Vec<u8> {
+    match algo {
+        // Canonical xxh64 representation is big-endian, matching `xxhsum`.
+        HashAlgorithm::Xxhash64 => xxhash_rust::xxh64::xxh64(bytes, 0).to_be_bytes().to_vec(),
+        HashAlgorithm::Sha256 => Sha256::digest(bytes).to_vec(),
+    }
+}
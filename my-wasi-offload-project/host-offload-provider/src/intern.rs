@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use sha2::{Digest, Sha256};
+
+use crate::wasi_custom::host_offload::host_allocator::{ElementType, Handle, HostError, MatrixShape, StorageKind};
+use crate::HostState;
+
+// Bytes of unreferenced entries kept before the least recently used go.
+pub const DEFAULT_CAPACITY_BYTES: u64 = 1 << 30;
+
+// Published buffers by SHA-256, shared by every `HostState` in the process.
+// An entry is referenced while any handle still shares its bytes; only
+// unreferenced ones are evicted.
+struct Cache {
+    entries: HashMap<Vec<u8>, Entry>,
+    capacity: u64,
+    // Bumped on every lookup and publish, for least-recently-used order.
+    clock: u64,
+}
+
+struct Entry {
+    bytes: Arc<Vec<u8>>,
+    dims: Option<MatrixShape>,
+    element_type: Option<ElementType>,
+    last_used: u64,
+}
+
+fn cache() -> &'static Mutex<Cache> {
+    static CACHE: OnceLock<Mutex<Cache>> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(Cache { entries: HashMap::new(), capacity: DEFAULT_CAPACITY_BYTES, clock: 0 }))
+}
+
+// For the embedder; applies to the whole process.
+pub fn set_capacity(bytes: u64) {
+    let mut cache = cache().lock().unwrap();
+    cache.capacity = bytes;
+    cache.evict();
+}
+
+impl Cache {
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+
+    // Drops unreferenced entries, oldest first, until the cache fits.
+    fn evict(&mut self) {
+        let mut total: u64 = self.entries.values().map(|e| e.bytes.len() as u64).sum();
+        while total > self.capacity {
+            let oldest = self.entries.iter()
+                .filter(|(_, e)| Arc::strong_count(&e.bytes) == 1)
+                .min_by_key(|(_, e)| e.last_used)
+                .map(|(hash, _)| hash.clone());
+            let Some(hash) = oldest else { break };
+            let entry = self.entries.remove(&hash).unwrap();
+            total -= entry.bytes.len() as u64;
+            log!(Info, "Evicted an interned buffer of {} bytes", entry.bytes.len());
+        }
+    }
+}
+
+fn check_hash(hash: &[u8]) -> Result<(), HostError> {
+    match hash.len() == 32 {
+        true => Ok(()),
+        false => Err(HostError::InvalidArguments(format!("Expected a 32-byte SHA-256, got {} bytes", hash.len()))),
+    }
+}
+
+impl HostState {
+    pub fn intern_buffer(&mut self, hash: &[u8]) -> Result<Option<Handle>, HostError> {
+        log!(Debug, "Looking up interned buffer {:02x?}", hash);
+        self.charge(0)?;
+        check_hash(hash)?;
+        let (bytes, dims, element_type) = {
+            let mut cache = cache().lock().unwrap();
+            let now = cache.tick();
+            let Some(entry) = cache.entries.get_mut(hash) else {
+                return Ok(None);
+            };
+            entry.last_used = now;
+            (entry.bytes.clone(), entry.dims, entry.element_type)
+        };
+        let h = self.new_handle();
+        self.buffers.insert(h, bytes.into());
+        if let Some(dims) = dims {
+            self.matrix_dims.insert(h, dims);
+        }
+        if let Some(elem) = element_type {
+            self.element_types.insert(h, elem);
+        }
+        Ok(Some(h))
+    }
+
+    // A heap buffer is switched over to the shared bytes, so the process
+    // keeps one copy; other kinds of storage stay as they are and the cache
+    // takes a copy.
+    pub fn publish_interned(&mut self, h: Handle, hash: &[u8]) -> Result<(), HostError> {
+        log!(Debug, "Publishing buffer {} as {:02x?}", h, hash);
+        self.charge(0)?;
+        check_hash(hash)?;
+        self.refuse_sensitive(h, "published")?;
+        self.materialize(h)?;
+        let buffer = self.buffers.get(&h).ok_or_else(|| self.missing(h))?;
+        if Sha256::digest(&buffer[..]).as_slice() != hash {
+            return Err(HostError::InvalidArguments(format!("Buffer {} doesn't hash to the given SHA-256", h)));
+        }
+        let bytes = {
+            let mut cache = cache().lock().unwrap();
+            let now = cache.tick();
+            let entry = cache.entries.entry(hash.to_vec()).or_insert_with(|| Entry {
+                bytes: Arc::new(buffer.to_vec()),
+                dims: self.matrix_dims.get(&h).copied(),
+                element_type: self.element_types.get(&h).copied(),
+                last_used: now,
+            });
+            entry.last_used = now;
+            let bytes = entry.bytes.clone();
+            cache.evict();
+            bytes
+        };
+        if buffer.kind() == StorageKind::Heap {
+            self.buffers.insert(h, bytes.into());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,246 @@
+use std::time::Instant;
+
+use nalgebra::{DMatrix, DVector};
+
+use crate::wasi_custom::host_offload::host_allocator::{ComparisonReport, ComputeMode, HostError, Summation};
+
+// Multiply-adds per slice when a product is computed a few rows at a time.
+// Small enough that one slice stays in the low milliseconds.
+pub const STEP_FLOPS: usize = 1 << 24;
+
+// Matrix multiply honoring the session's compute mode.
+//
+// `Fast` hands the product to nalgebra, whose GEMM is free to block, reorder
+// and vectorize the reductions. `Deterministic` uses a plain i-k-j loop with a
+// fixed left-to-right summation order and separate multiply/add steps (no
+// FMA), so the result is bit-identical across runs and backends.
+pub fn matmul_f32(a: &DMatrix<f32>, b: &DMatrix<f32>, mode: ComputeMode) -> DMatrix<f32> {
+    match mode {
+        ComputeMode::Fast => a * b,
+        ComputeMode::Deterministic => matmul_f32_ordered(a, b),
+    }
+}
+
+// `matmul_f32`, computed in row slices so a deadline can be checked between
+// them. Fails with `Timeout` once `deadline` has passed.
+pub fn matmul_f32_within(a: &DMatrix<f32>, b: &DMatrix<f32>, mode: ComputeMode, deadline: Option<Instant>) -> Result<DMatrix<f32>, HostError> {
+    let Some(deadline) = deadline else {
+        return Ok(matmul_f32(a, b, mode));
+    };
+    let rows = a.nrows();
+    let step = (STEP_FLOPS / (a.ncols() * b.ncols()).max(1)).max(1);
+    let mut c = DMatrix::<f32>::zeros(rows, b.ncols());
+    let mut start = 0;
+    while start < rows {
+        if Instant::now() > deadline {
+            return Err(HostError::Timeout);
+        }
+        let count = step.min(rows - start);
+        c.rows_mut(start, count).copy_from(&matmul_f32_rows(a, b, start, count, mode));
+        start += count;
+    }
+    Ok(c)
+}
+
+// Rows `start..start + count` of `a * b`, for computing a product in slices.
+pub fn matmul_f32_rows(a: &DMatrix<f32>, b: &DMatrix<f32>, start: usize, count: usize, mode: ComputeMode) -> DMatrix<f32> {
+    let a_rows = a.rows(start, count).into_owned();
+    matmul_f32(&a_rows, b, mode)
+}
+
+fn matmul_f32_ordered(a: &DMatrix<f32>, b: &DMatrix<f32>) -> DMatrix<f32> {
+    let (rows, inner, cols) = (a.nrows(), a.ncols(), b.ncols());
+    let mut c = DMatrix::<f32>::zeros(rows, cols);
+    for i in 0..rows {
+        for k in 0..inner {
+            let a_ik = a[(i, k)];
+            for j in 0..cols {
+                let product = a_ik * b[(k, j)];
+                c[(i, j)] += product;
+            }
+        }
+    }
+    c
+}
+
+pub fn axpy_f32(alpha: f32, x: &[f32], y: &mut [f32]) {
+    for (yi, &xi) in y.iter_mut().zip(x) {
+        *yi += alpha * xi;
+    }
+}
+
+pub fn scal_f32(alpha: f32, x: &mut [f32]) {
+    for xi in x.iter_mut() {
+        *xi *= alpha;
+    }
+}
+
+// y <- alpha * a * x + beta * y, with the same `Fast`/`Deterministic` split as
+// `matmul_f32`.
+pub fn gemv_f32(alpha: f32, a: &DMatrix<f32>, x: &[f32], beta: f32, y: &mut [f32], mode: ComputeMode) {
+    match mode {
+        ComputeMode::Fast => {
+            let x = DVector::from_column_slice(x);
+            let mut out = DVector::from_column_slice(y);
+            out.gemv(alpha, a, &x, beta);
+            y.copy_from_slice(out.as_slice());
+        }
+        ComputeMode::Deterministic => {
+            for (i, yi) in y.iter_mut().enumerate() {
+                let mut dot = 0.0f32;
+                for (k, &xk) in x.iter().enumerate() {
+                    let product = a[(i, k)] * xk;
+                    dot += product;
+                }
+                let scaled = alpha * dot;
+                *yi = scaled + beta * *yi;
+            }
+        }
+    }
+}
+
+// `gemv_f32` reading only the band of `a` that lies `lower` diagonals below
+// and `upper` above the main one. Summation runs left to right, so both
+// compute modes give the same result.
+pub fn gbmv_f32(alpha: f32, a: &DMatrix<f32>, lower: usize, upper: usize, x: &[f32], beta: f32, y: &mut [f32]) {
+    for (i, yi) in y.iter_mut().enumerate() {
+        let (start, end) = (i.saturating_sub(lower), (i + upper + 1).min(a.ncols()));
+        let mut dot = 0.0f32;
+        for (k, &xk) in x.iter().enumerate().take(end).skip(start) {
+            let product = a[(i, k)] * xk;
+            dot += product;
+        }
+        let scaled = alpha * dot;
+        *yi = scaled + beta * *yi;
+    }
+}
+
+// Overwrites `b` with x solving `a * x = alpha * b`, by forward (`lower`) or
+// back substitution one column at a time. Only the named triangle of `a` is
+// read, and not its diagonal when `unit_diagonal`.
+pub fn trsm_f32(a: &DMatrix<f32>, lower: bool, unit_diagonal: bool, alpha: f32, b: &mut DMatrix<f32>) -> Result<(), HostError> {
+    let n = a.nrows();
+    let rows: Vec<usize> = match lower {
+        true => (0..n).collect(),
+        false => (0..n).rev().collect(),
+    };
+    for j in 0..b.ncols() {
+        for &i in &rows {
+            let solved = if lower { 0..i } else { i + 1..n };
+            let mut value = alpha * b[(i, j)];
+            for k in solved {
+                let product = a[(i, k)] * b[(k, j)];
+                value -= product;
+            }
+            if !unit_diagonal {
+                if a[(i, i)] == 0.0 {
+                    return Err(HostError::ComputationError(format!("Triangular matrix is singular at row {}", i)));
+                }
+                value /= a[(i, i)];
+            }
+            b[(i, j)] = value;
+        }
+    }
+    Ok(())
+}
+
+// Sum of `values` by the chosen order of operations: `Naive` left to right,
+// `Pairwise` by recursive halves (error grows with log n, not n) and `Kahan`
+// left to right carrying each addition's rounding error into the next.
+pub fn sum_f32(values: &[f32], summation: Summation) -> f32 {
+    match summation {
+        Summation::Naive => values.iter().fold(0.0, |sum, &v| sum + v),
+        Summation::Pairwise if values.len() <= 8 => values.iter().fold(0.0, |sum, &v| sum + v),
+        Summation::Pairwise => {
+            let (left, right) = values.split_at(values.len() / 2);
+            sum_f32(left, summation) + sum_f32(right, summation)
+        }
+        Summation::Kahan => {
+            let (mut sum, mut carry) = (0.0f32, 0.0f32);
+            for &v in values {
+                let y = v - carry;
+                let t = sum + y;
+                carry = (t - sum) - y;
+                sum = t;
+            }
+            sum
+        }
+    }
+}
+
+// Keys per block in `sdpa_f32`.
+pub const ATTENTION_TILE: usize = 64;
+
+// softmax(scale * q * k^T) * v, one block of `ATTENTION_TILE` keys at a time
+// with a running max and sum per query (the flash-attention recurrence), so
+// the full query x key score matrix is never held. `keep(i, j)` false masks
+// key j out for query i; a query left with no keys gets a zero row.
+pub fn sdpa_f32(
+    q: &DMatrix<f32>,
+    k: &DMatrix<f32>,
+    v: &DMatrix<f32>,
+    scale: f32,
+    keep: impl Fn(usize, usize) -> bool,
+    mode: ComputeMode,
+    deadline: Option<Instant>,
+) -> Result<DMatrix<f32>, HostError> {
+    let queries = q.nrows();
+    let mut out = DMatrix::<f32>::zeros(queries, v.ncols());
+    let mut max = vec![f32::NEG_INFINITY; queries];
+    let mut sum = vec![0.0f32; queries];
+    for start in (0..k.nrows()).step_by(ATTENTION_TILE) {
+        if deadline.is_some_and(|deadline| Instant::now() > deadline) {
+            return Err(HostError::Timeout);
+        }
+        let count = ATTENTION_TILE.min(k.nrows() - start);
+        let scores = matmul_f32(q, &k.rows(start, count).transpose(), mode) * scale;
+        let mut weights = DMatrix::<f32>::zeros(queries, count);
+        for i in 0..queries {
+            let kept = || (0..count).filter(|&j| keep(i, start + j));
+            let block_max = kept().map(|j| scores[(i, j)]).fold(f32::NEG_INFINITY, f32::max);
+            if block_max == f32::NEG_INFINITY {
+                continue;
+            }
+            // Rescale what earlier blocks added to the new running max.
+            let new_max = max[i].max(block_max);
+            let correction = (max[i] - new_max).exp();
+            sum[i] *= correction;
+            out.row_mut(i).scale_mut(correction);
+            for j in kept() {
+                weights[(i, j)] = (scores[(i, j)] - new_max).exp();
+                sum[i] += weights[(i, j)];
+            }
+            max[i] = new_max;
+        }
+        out += matmul_f32(&weights, &v.rows(start, count).into_owned(), mode);
+    }
+    for (i, &total) in sum.iter().enumerate() {
+        if total > 0.0 {
+            out.row_mut(i).unscale_mut(total);
+        }
+    }
+    Ok(out)
+}
+
+// NaN never compares close, so a NaN on either side is reported as a mismatch
+// with infinite error.
+pub fn compare_f32(a: &[f32], b: &[f32], rtol: f32, atol: f32) -> ComparisonReport {
+    let mut report = ComparisonReport {
+        all_close: true,
+        max_abs_error: 0.0,
+        max_rel_error: 0.0,
+        first_mismatch: None,
+    };
+    for (i, (&x, &y)) in a.iter().zip(b.iter()).enumerate() {
+        let abs_error = if x.is_nan() || y.is_nan() { f32::INFINITY } else { (x - y).abs() };
+        let rel_error = if y != 0.0 { abs_error / y.abs() } else if abs_error == 0.0 { 0.0 } else { f32::INFINITY };
+        report.max_abs_error = report.max_abs_error.max(abs_error);
+        report.max_rel_error = report.max_rel_error.max(rel_error);
+        let close = abs_error <= atol + rtol * y.abs();
+        if !close && report.all_close {
+            report.all_close = false;
+            report.first_mismatch = Some(i as u64);
+        }
+    }
+    report
+}
@@ -0,0 +1,115 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+use crate::native::OffloadHost;
+use crate::wasi::keyvalue::store::{Error, KeyResponse};
+use crate::wasi_custom::host_offload::host_allocator::{Handle, HostError};
+use crate::HostState;
+
+// Keys returned per `list-keys` call; the cursor is the index of the next one.
+const KEYS_PER_PAGE: usize = 1000;
+
+// `wasi:keyvalue/store` over a provider's buffers, for components that already
+// keep blobs in a keyvalue store. Every value is a buffer of its own, created
+// by `set` through `allocate-buffer`, so it counts against the client's
+// session, limits and spilling like any other. Empty values have no buffer.
+//
+// Buckets with the same identifier share their keys. A key whose buffer went
+// away some other way (its session closed, its TTL ran out) reads as absent.
+#[derive(Clone)]
+pub struct KeyValueStore {
+    host: OffloadHost,
+    buckets: Arc<Mutex<BTreeMap<String, BTreeMap<String, Option<Handle>>>>>,
+}
+
+// The `bucket` resource: just the identifier it was opened with.
+pub struct Bucket {
+    name: String,
+}
+
+fn other(e: HostError) -> Error {
+    Error::Other(format!("{:?}", e))
+}
+
+impl KeyValueStore {
+    pub fn new(host: OffloadHost) -> Self {
+        KeyValueStore { host, buckets: Arc::default() }
+    }
+
+    pub fn open(&self, identifier: String) -> Result<Bucket, Error> {
+        log!(Debug, "Opening keyvalue bucket '{}'", identifier);
+        self.buckets.lock().unwrap().entry(identifier.clone()).or_default();
+        Ok(Bucket { name: identifier })
+    }
+
+    // Runs `f` on the bucket's keys after dropping those whose buffer is gone.
+    fn with_keys<R>(
+        &self,
+        bucket: &Bucket,
+        f: impl FnOnce(&mut BTreeMap<String, Option<Handle>>, &mut HostState) -> R,
+    ) -> R {
+        let mut buckets = self.buckets.lock().unwrap();
+        let keys = buckets.entry(bucket.name.clone()).or_default();
+        let mut state = self.host.lock();
+        keys.retain(|_, h| h.map_or(true, |h| state.contains(h)));
+        f(keys, &mut *state)
+    }
+
+    pub fn get(&self, bucket: &Bucket, key: &str) -> Result<Option<Vec<u8>>, Error> {
+        self.with_keys(bucket, |keys, state| match keys.get(key) {
+            None => Ok(None),
+            Some(None) => Ok(Some(Vec::new())),
+            Some(&Some(h)) => {
+                let len = state.buffer_len(h).map_err(other)?;
+                state.read_from_host(h, 0, len).map(Some).map_err(other)
+            }
+        })
+    }
+
+    // Reuses the key's buffer when the new value has the same size.
+    pub fn set(&self, bucket: &Bucket, key: String, value: Vec<u8>) -> Result<(), Error> {
+        self.with_keys(bucket, |keys, state| {
+            let old = keys.get(&key).copied().flatten();
+            let h = match old {
+                _ if value.is_empty() => None,
+                Some(h) if state.buffer_len(h) == Ok(value.len() as u64) => Some(h),
+                _ => Some(state.allocate_buffer(value.len() as u64).map_err(other)?),
+            };
+            if let Some(h) = h {
+                if let Err(e) = state.write_to_host(&value, h, 0) {
+                    if old != Some(h) {
+                        let _ = state.free_buffer(h);
+                    }
+                    return Err(other(e));
+                }
+            }
+            if let Some(old) = old.filter(|&old| Some(old) != h) {
+                state.free_buffer(old).map_err(other)?;
+            }
+            keys.insert(key, h);
+            Ok(())
+        })
+    }
+
+    // Deleting a missing key is not an error.
+    pub fn delete(&self, bucket: &Bucket, key: &str) -> Result<(), Error> {
+        self.with_keys(bucket, |keys, state| match keys.remove(key) {
+            Some(Some(h)) => state.free_buffer(h).map_err(other),
+            _ => Ok(()),
+        })
+    }
+
+    pub fn exists(&self, bucket: &Bucket, key: &str) -> Result<bool, Error> {
+        self.with_keys(bucket, |keys, _| Ok(keys.contains_key(key)))
+    }
+
+    pub fn list_keys(&self, bucket: &Bucket, cursor: Option<u64>) -> Result<KeyResponse, Error> {
+        self.with_keys(bucket, |keys, _| {
+            let start = cursor.unwrap_or(0) as usize;
+            let page: Vec<String> = keys.keys().skip(start).take(KEYS_PER_PAGE).cloned().collect();
+            let next = start + page.len();
+            let cursor = (next < keys.len()).then_some(next as u64);
+            Ok(KeyResponse { keys: page, cursor })
+        })
+    }
+}
@@ -0,0 +1,123 @@
+use nalgebra::DMatrix;
+use offload_common::codec;
+
+use crate::kernels;
+use crate::wasi_custom::host_offload::host_allocator::{ElementType, Handle, HostError};
+use crate::HostState;
+
+// Shape and fill level of a `create-kv-cache` handle. Its f32 buffer holds,
+// per layer, the keys then the values, each head-major with `max_seq` rows
+// of `head_dim`, so one head's cached keys are a contiguous row-major matrix.
+#[derive(Debug, Clone)]
+pub(crate) struct KvCache {
+    layers: u32,
+    heads: u32,
+    max_seq: u32,
+    head_dim: u32,
+    lengths: Vec<u32>,
+}
+
+impl KvCache {
+    // Byte offset of position `pos` of `head` in the keys (`value` false) or values of `layer`.
+    fn offset(&self, layer: u32, value: bool, head: u32, pos: u32) -> usize {
+        let block = (layer as usize * 2 + value as usize) * self.heads as usize + head as usize;
+        (block * self.max_seq as usize + pos as usize) * self.head_dim as usize * codec::F32_SIZE
+    }
+
+    fn width(&self) -> usize {
+        self.heads as usize * self.head_dim as usize
+    }
+}
+
+impl HostState {
+    pub fn create_kv_cache(&mut self, layers: u32, heads: u32, max_seq: u32, head_dim: u32) -> Result<Handle, HostError> {
+        log!(Debug, "Creating KV cache: {} layers, {} heads, {} positions of {}", layers, heads, max_seq, head_dim);
+        if [layers, heads, max_seq, head_dim].contains(&0) {
+            return Err(HostError::InvalidArguments("KV cache dimensions must be nonzero".to_string()));
+        }
+        let count = [2, layers, heads, max_seq, head_dim].iter().fold(1u64, |n, &d| n.saturating_mul(d as u64));
+        let h = self.allocate_typed_buffer(ElementType::F32, count)?;
+        let cache = KvCache { layers, heads, max_seq, head_dim, lengths: vec![0; layers as usize] };
+        self.kv_caches.insert(h, cache);
+        Ok(h)
+    }
+
+    fn kv_cache(&self, h: Handle, layer: u32) -> Result<KvCache, HostError> {
+        let cache = self.kv_caches.get(&h).ok_or_else(|| HostError::InvalidArguments(format!("Handle {} is not a KV cache", h)))?;
+        if layer >= cache.layers {
+            return Err(HostError::InvalidArguments(format!("KV cache {} has {} layers", h, cache.layers)));
+        }
+        Ok(cache.clone())
+    }
+
+    pub fn kv_cache_length(&mut self, cache: Handle, layer: u32) -> Result<u32, HostError> {
+        self.charge(0)?;
+        Ok(self.kv_cache(cache, layer)?.lengths[layer as usize])
+    }
+
+    pub fn append_kv(&mut self, cache: Handle, layer: u32, k: Handle, v: Handle) -> Result<(), HostError> {
+        log!(Debug, "Appending {} and {} to layer {} of KV cache {}", k, v, layer, cache);
+        self.charge(0)?;
+        let shape = self.kv_cache(cache, layer)?;
+        self.materialize(k)?;
+        self.materialize(v)?;
+        let (_, keys) = self.read_matrix_f32(k)?;
+        let (_, values) = self.read_matrix_f32(v)?;
+        if keys.shape() != values.shape() || keys.ncols() != shape.width() {
+            return Err(HostError::DimensionMismatch);
+        }
+        let start = shape.lengths[layer as usize];
+        if start as usize + keys.nrows() > shape.max_seq as usize {
+            return Err(HostError::CopyOutOfBounds);
+        }
+        self.materialize(cache)?;
+        self.journal(cache)?;
+        let Some(buffer) = self.buffers.get_mut(&cache) else {
+            return Err(self.missing(cache));
+        };
+        let dim = shape.head_dim as usize;
+        for (is_value, rows) in [(false, &keys), (true, &values)] {
+            for r in 0..rows.nrows() {
+                for head in 0..shape.heads {
+                    let at = shape.offset(layer, is_value, head, start + r as u32);
+                    let row: Vec<f32> = (0..dim).map(|c| rows[(r, head as usize * dim + c)]).collect();
+                    buffer[at..at + dim * codec::F32_SIZE].copy_from_slice(&codec::f32_to_le_bytes(&row));
+                }
+            }
+        }
+        self.kv_caches.get_mut(&cache).unwrap().lengths[layer as usize] += keys.nrows() as u32;
+        Ok(())
+    }
+
+    pub fn sdpa_cached(&mut self, q: Handle, cache: Handle, layer: u32, mask: Option<Handle>, scale: f32) -> Result<Handle, HostError> {
+        log!(Debug, "Attention over q {} and layer {} of KV cache {}", q, layer, cache);
+        self.charge(0)?;
+        let shape = self.kv_cache(cache, layer)?;
+        self.materialize(q)?;
+        let (dims_q, matrix_q) = self.read_matrix_f32(q)?;
+        if matrix_q.ncols() != shape.width() {
+            return Err(HostError::DimensionMismatch);
+        }
+        let length = shape.lengths[layer as usize];
+        let keep = self.attention_mask(mask, matrix_q.nrows(), length as usize)?;
+        let keep = |i: usize, j: usize| keep.as_ref().map_or(true, |keep| keep[i * length as usize + j]);
+
+        self.materialize(cache)?;
+        let buffer = self.buffers.get(&cache).ok_or_else(|| self.missing(cache))?;
+        let dim = shape.head_dim as usize;
+        let cached = |value: bool, head: u32| {
+            let at = shape.offset(layer, value, head, 0);
+            let floats = codec::f32_from_le_bytes(&buffer[at..at + length as usize * dim * codec::F32_SIZE]).unwrap();
+            DMatrix::from_row_slice(length as usize, dim, &floats)
+        };
+        let mut out = DMatrix::<f32>::zeros(matrix_q.nrows(), shape.width());
+        let deadline = self.op_deadline();
+        for head in 0..shape.heads {
+            let columns = head as usize * dim;
+            let q_head = matrix_q.columns(columns, dim).into_owned();
+            let attended = kernels::sdpa_f32(&q_head, &cached(false, head), &cached(true, head), scale, keep, self.compute_mode, deadline)?;
+            out.columns_mut(columns, dim).copy_from(&attended);
+        }
+        Ok(self.store_matrix_f32(&out, dims_q.layout))
+    }
+}
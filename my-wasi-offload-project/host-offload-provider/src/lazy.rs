@@ -0,0 +1,139 @@
+use nalgebra::DMatrix;
+
+use crate::kernels;
+use crate::wasi_custom::host_offload::host_allocator::{Device, ElementType, Handle, HostError, MatrixLayout, MatrixShape};
+use crate::HostState;
+
+// A compute result that has a handle and dimensions but no data yet.
+pub enum PendingOp {
+    MatmulF32 { a: Handle, b: Handle, layout: MatrixLayout },
+}
+
+// A pending multiply that `step` has started: operands captured at the first
+// step and the rows of the result computed so far.
+pub struct PartialMatmul {
+    a: DMatrix<f32>,
+    b: DMatrix<f32>,
+    c: DMatrix<f32>,
+    layout: MatrixLayout,
+    next_row: usize,
+}
+
+impl PendingOp {
+    fn inputs(&self) -> [Handle; 2] {
+        match self {
+            PendingOp::MatmulF32 { a, b, .. } => [*a, *b],
+        }
+    }
+}
+
+impl HostState {
+    // Validates a multiply and returns a handle for its result without
+    // computing it. Shape errors surface here rather than at materialization.
+    pub(crate) fn defer_matmul_f32(&mut self, a: Handle, b: Handle, device: Device) -> Result<Handle, HostError> {
+        for h in [a, b] {
+            if !self.contains(h) {
+                return Err(self.missing(h));
+            }
+        }
+        let dims_a = *self.matrix_dims.get(&a).ok_or_else(|| self.missing(a))?;
+        let dims_b = *self.matrix_dims.get(&b).ok_or_else(|| self.missing(b))?;
+        if dims_a.cols != dims_b.rows {
+            return Err(HostError::DimensionMismatch);
+        }
+        self.check_matmul_dims([dims_a.rows, dims_a.cols, dims_b.cols].map(|dim| dim as usize))?;
+        self.check_type(a, ElementType::F32)?;
+        self.check_type(b, ElementType::F32)?;
+        let h = self.new_handle();
+        self.matrix_dims.insert(h, MatrixShape { rows: dims_a.rows, cols: dims_b.cols, layout: dims_a.layout });
+        self.element_types.insert(h, ElementType::F32);
+        self.pending.insert(h, PendingOp::MatmulF32 { a, b, layout: dims_a.layout });
+        if device != Device::Cpu {
+            self.placements.insert(h, device);
+        }
+        Ok(h)
+    }
+
+    // Computes `h` (and, first, any pending inputs it depends on) if it is
+    // still pending. A no-op for materialized handles.
+    pub(crate) fn materialize(&mut self, h: Handle) -> Result<(), HostError> {
+        self.reload(h)?;
+        if self.partials.contains_key(&h) {
+            while self.step(h)? < 100 {}
+            return Ok(());
+        }
+        let Some(op) = self.pending.remove(&h) else {
+            return Ok(());
+        };
+        for input in op.inputs() {
+            self.materialize(input)?;
+        }
+        match op {
+            PendingOp::MatmulF32 { a, b, layout } => {
+                let device = self.placements.get(&h).copied().unwrap_or(Device::Cpu);
+                let (bytes, per_node) = self.matmul_f32_on(device, a, b, layout)
+                    .inspect_err(|_| {
+                        // Stays pending, so a later read can retry with more time.
+                        self.pending.insert(h, op);
+                    })?;
+                self.buffers.insert(h, bytes.into());
+                self.record_nodes(h, per_node);
+            }
+        }
+        log!(Debug, "Materialized lazy handle {}", h);
+        self.job_completed(h);
+        Ok(())
+    }
+
+    // Computes the next slice of rows of pending `h` and returns its progress
+    // in percent. Materialized handles report 100.
+    pub(crate) fn step(&mut self, h: Handle) -> Result<u8, HostError> {
+        if !self.partials.contains_key(&h) {
+            let Some(&PendingOp::MatmulF32 { a, b, layout }) = self.pending.get(&h) else {
+                return Ok(100);
+            };
+            self.materialize(a)?;
+            self.materialize(b)?;
+            let (_, a) = self.read_matrix_f32(a)?;
+            let (_, b) = self.read_matrix_f32(b)?;
+            let c = DMatrix::zeros(a.nrows(), b.ncols());
+            self.partials.insert(h, PartialMatmul { a, b, c, layout, next_row: 0 });
+        }
+
+        let mode = self.compute_mode;
+        let partial = self.partials.get_mut(&h).unwrap();
+        let rows = partial.c.nrows();
+        let row_cost = (partial.a.ncols() * partial.b.ncols()).max(1);
+        let count = (kernels::STEP_FLOPS / row_cost).clamp(1, rows.saturating_sub(partial.next_row).max(1));
+        if partial.next_row < rows {
+            let block = kernels::matmul_f32_rows(&partial.a, &partial.b, partial.next_row, count, mode);
+            partial.c.rows_mut(partial.next_row, count).copy_from(&block);
+            partial.next_row += count;
+        }
+        if partial.next_row < rows {
+            return Ok((partial.next_row * 100 / rows) as u8);
+        }
+
+        let partial = self.partials.remove(&h).unwrap();
+        self.pending.remove(&h);
+        self.buffers.insert(h, crate::state::matrix_to_bytes(&partial.c, partial.layout).into());
+        log!(Debug, "Materialized lazy handle {} in steps", h);
+        self.job_completed(h);
+        Ok(100)
+    }
+
+    // Must run before `h` is overwritten or freed, so pending results that
+    // read it still see the data they were defined against.
+    pub(crate) fn materialize_dependents(&mut self, h: Handle) -> Result<(), HostError> {
+        let dependents: Vec<Handle> = self
+            .pending
+            .iter()
+            .filter(|(_, op)| op.inputs().contains(&h))
+            .map(|(&dependent, _)| dependent)
+            .collect();
+        for dependent in dependents {
+            self.materialize(dependent)?;
+        }
+        Ok(())
+    }
+}
@@ -1,9 +1,83 @@
-use std::collections::HashMap;
-use std::sync::Mutex;
-use once_cell::sync::Lazy; // For thread-safe static initialization
+// First, so the `log!` macro is in scope for every module below.
+#[macro_use]
+mod log;
+pub mod analysis;
+mod attention;
+mod audio;
+mod autoselect;
+pub mod backend;
+mod batch;
+mod cast;
+pub mod checks;
+mod cost;
+mod decompose;
+mod dump;
+mod einsum;
+pub mod events;
+pub mod extensions;
+mod graph;
+mod hash;
+pub mod intern;
+mod kernels;
+mod kvcache;
+mod mapreduce;
+mod lazy;
+mod mask;
+pub mod persist;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod numa;
+mod products;
+mod registry;
+mod select;
+mod sensitive;
+mod session;
+mod shape;
+mod spill;
+mod structure;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod split;
+mod state;
+pub mod storage;
+mod transaction;
+mod ttl;
+
+// Built for wasm32 the crate is the provider component; built natively it is a
+// library the runner links in directly (see `native`).
+#[cfg(target_arch = "wasm32")]
+mod component;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod native;
+// `host-allocator@0.1.0` for guests built against it.
+#[cfg(not(target_arch = "wasm32"))]
+pub mod compat;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod audit;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod buffer;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod fair;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod keyvalue;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod policy;
+#[cfg(not(target_arch = "wasm32"))]
+mod random;
+#[cfg(not(target_arch = "wasm32"))]
+mod streams;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod tokenize;
+#[cfg(all(feature = "cuda", not(target_arch = "wasm32")))]
+mod cuda;
+#[cfg(all(feature = "vulkan", not(target_arch = "wasm32")))]
+mod vulkan;
+#[cfg(all(feature = "metal", target_os = "macos"))]
+mod metal;
+
+pub use state::HostState;
 
 // Import the generated bindings for the `provider` world.
 // The name of the module `provider` matches the world name in `wit/world.wit`.
+#[cfg(target_arch = "wasm32")]
 wit_bindgen::generate!({
     world: "provider", // Name of the world in wit/world.wit
     path: "wit/world.wit", // Path to the main WIT definition for this component
@@ -11,180 +85,30 @@ wit_bindgen::generate!({
     // that our `provider` world's interface uses.
     // `generate!` macro can take `additional_packages` or rely on `cargo-component` to provide it.
     // cargo-component uses the [package.metadata.component.dependencies] for this.
+    additional_derives: [PartialEq], // Variants like `device` are compared in residency tracking
 });
 
-use crate::wasi_custom::host_offload::host_allocator::{
-    Handle, HostError, MatrixDimensions
-};
-
-
-// Simulated host state
-struct HostState {
-    buffers: HashMap<Handle, Vec<u8>>,
-    matrix_dims: HashMap<Handle, (u32, u32)>, // rows, cols
-    next_handle: Handle,
-}
-
-impl HostState {
-    fn new() -> Self {
-        HostState {
-            buffers: HashMap::new(),
-            matrix_dims: HashMap::new(),
-            next_handle: 1, // Start handles from 1
-        }
-    }
-
-    fn new_handle(&mut self) -> Handle {
-        let handle = self.next_handle;
-        self.next_handle += 1;
-        if self.next_handle == 0 { panic!("Handle overflow!"); } 
-        handle
-    }
-}
-
-static HOST_STATE: Lazy<Mutex<HostState>> = Lazy::new(|| Mutex::new(HostState::new()));
-
-// This struct will implement the exported interface functions.
-//
-struct Component;
-
-impl crate::provider::exports::HostAllocator for Component {
-    fn allocate_buffer(size: u64) -> Result<Handle, HostError> {
-        println!("[Provider Wasm] Allocating buffer of size {}", size);
-        if size == 0 {
-            return Err(HostError::Other("Cannot allocate zero-size buffer".to_string()));
-        }
-        let mut state = HOST_STATE.lock().unwrap();
-        let handle = state.new_handle();
-        state.buffers.insert(handle, vec![0u8; size as usize]);
-        Ok(handle)
-    }
-
-    fn free_buffer(h: Handle) -> Result<(), HostError> {
-        println!("[Provider Wasm] Freeing buffer {}", h);
-        let mut state = HOST_STATE.lock().unwrap();
-        if state.buffers.remove(&h).is_some() {
-            state.matrix_dims.remove(&h);
-            Ok(())
-        } else {
-            Err(HostError::InvalidHandle)
-        }
-    }
-
-    fn write_to_host(
-        guest_bytes: Vec<u8>,
-        target_handle: Handle,
-        target_offset: u64,
-    ) -> Result<(), HostError> {
-        println!("[Provider Wasm] Writing {} bytes to handle {} at offset {}", guest_bytes.len(), target_handle, target_offset);
-        let mut state = HOST_STATE.lock().unwrap();
-        match state.buffers.get_mut(&target_handle) {
-            Some(buffer) => {
-                let offset = target_offset as usize;
-                let end = offset + guest_bytes.len();
-                if end > buffer.len() {
-                    return Err(HostError::CopyOutOfBounds);
-                }
-                buffer[offset..end].copy_from_slice(&guest_bytes);
-                Ok(())
-            }
-            None => Err(HostError::InvalidHandle),
-        }
-    }
-
-    fn read_from_host(
-        source_handle: Handle,
-        source_offset: u64,
-        len: u64,
-    ) -> Result<Vec<u8>, HostError> {
-        println!("[Provider Wasm] Reading {} bytes from handle {} at offset {}", len, source_handle, source_offset);
-        let state = HOST_STATE.lock().unwrap();
-        match state.buffers.get(&source_handle) {
-            Some(buffer) => {
-                let offset = source_offset as usize;
-                let read_len = len as usize;
-                if offset + read_len > buffer.len() {
-                    return Err(HostError::CopyOutOfBounds);
-                }
-                Ok(buffer[offset..offset + read_len].to_vec())
-            }
-            None => Err(HostError::InvalidHandle),
-        }
-    }
-
-    fn register_matrix_dimensions(h: Handle, dims: crate::wasi_custom::host_offload::host_allocator::MatrixDimensions) -> Result<(), HostError> {
-        println!("[Provider Wasm] Registering dimensions {}x{} for handle {}", dims.rows, dims.cols, h);
-        let mut state = HOST_STATE.lock().unwrap();
-        if !state.buffers.contains_key(&h) {
-            return Err(HostError::InvalidHandle);
-        }
-        state.matrix_dims.insert(h, (dims.rows, dims.cols));
-        Ok(())
-    }
-
-
-    fn matrix_multiply_f32(
-        handle_a: Handle,
-        handle_b: Handle,
-    ) -> Result<Handle, HostError> {
-        println!("[Provider Wasm] Matrix multiply f32 for A:{} and B:{}", handle_a, handle_b);
-        let mut state = HOST_STATE.lock().unwrap();
-
-        let (rows_a, cols_a) = *state.matrix_dims.get(&handle_a).ok_or(HostError::InvalidHandle)?;
-        let buffer_a_bytes = state.buffers.get(&handle_a).ok_or(HostError::InvalidHandle)?;
-        let matrix_a_data = bytes_to_f32_slice(buffer_a_bytes)
-            .ok_or_else(|| HostError::Other("Failed to cast buffer A to f32".to_string()))?;
-        if matrix_a_data.len() != (rows_a * cols_a) as usize { return Err(HostError::Other("Buffer A size mismatch with dims".to_string())); }
-        let matrix_a = nalgebra::DMatrix::<f32>::from_row_slice(rows_a as usize, cols_a as usize, matrix_a_data);
-
-        let (rows_b, cols_b) = *state.matrix_dims.get(&handle_b).ok_or(HostError::InvalidHandle)?;
-        let buffer_b_bytes = state.buffers.get(&handle_b).ok_or(HostError::InvalidHandle)?;
-        let matrix_b_data = bytes_to_f32_slice(buffer_b_bytes)
-            .ok_or_else(|| HostError::Other("Failed to cast buffer B to f32".to_string()))?;
-        if matrix_b_data.len() != (rows_b * cols_b) as usize { return Err(HostError::Other("Buffer B size mismatch with dims".to_string())); }
-        let matrix_b = nalgebra::DMatrix::<f32>::from_row_slice(rows_b as usize, cols_b as usize, matrix_b_data);
-
-        if cols_a != rows_b {
-            return Err(HostError::DimensionMismatch);
-        }
-
-        let matrix_c = matrix_a * matrix_b;
-        let handle_c = state.new_handle();
-        let c_bytes = f32_slice_to_bytes(matrix_c.as_slice());
-        state.buffers.insert(handle_c, c_bytes);
-        state.matrix_dims.insert(handle_c, (matrix_c.nrows() as u32, matrix_c.ncols() as u32));
-        println!("[Provider Wasm] Stored result C ({},{}) with handle {}", matrix_c.nrows(), matrix_c.ncols(), handle_c);
-        Ok(handle_c)
-    }
-
-    fn get_matrix_dimensions(h: Handle) -> Result<crate::wasi_custom::host_offload::host_allocator::MatrixDimensions, HostError> {
-        println!("[Provider Wasm] Getting dimensions for handle {}", h);
-        let state = HOST_STATE.lock().unwrap();
-        match state.matrix_dims.get(&h) {
-            Some(&(rows, cols)) => Ok(MatrixDimensions { rows, cols }),
-            None => Err(HostError::InvalidHandle),
-        }
-    }
-}
-
-
-fn bytes_to_f32_slice(bytes: &[u8]) -> Option<&[f32]> {
-    if bytes.as_ptr() as usize % std::mem::align_of::<f32>() != 0 { return None; } // Alignment check
-    if bytes.len() % std::mem::size_of::<f32>() != 0 { return None; }
-    unsafe {
-        Some(std::slice::from_raw_parts(
-            bytes.as_ptr() as *const f32,
-            bytes.len() / std::mem::size_of::<f32>(),
-        ))
-    }
-}
-
-fn f32_slice_to_bytes(floats: &[f32]) -> Vec<u8> {
-    let mut bytes = Vec::with_capacity(floats.len() * std::mem::size_of::<f32>());
-    for float_val in floats {
-        bytes.extend_from_slice(&float_val.to_ne_bytes());
-    }
-    bytes
-}
+// Host-side bindings for the same interfaces. The generated types live at the
+// same `wasi_custom::host_offload::...` paths as the guest ones, so the shared
+// modules compile unchanged against either.
+#[cfg(not(target_arch = "wasm32"))]
+wasmtime::component::bindgen!({
+    world: "native-host",
+    path: "wit/world.wit",
+    additional_packages: [
+        { package = "wasi-custom:host-offload@0.2.0", path = "../wit" },
+    ],
+    additional_derives: [PartialEq],
+    with: {
+        "wasi:io/error": wasmtime_wasi::preview2::bindings::io::error,
+        "wasi:io/poll": wasmtime_wasi::preview2::bindings::io::poll,
+        "wasi:io/streams": wasmtime_wasi::preview2::bindings::io::streams,
+        "wasi:keyvalue/store/bucket": keyvalue::Bucket,
+    },
+});
 
-bindings::export!(Component with_โลก_world ()); // This macro binds the `Component` struct to the world exports. The name after `with_` needs to be the snake_case of the world name.
+// `session-admin` is exported by the component but imported by `native-host`.
+#[cfg(target_arch = "wasm32")]
+use crate::exports::wasi_custom::host_offload::session_admin;
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::wasi_custom::host_offload::session_admin;
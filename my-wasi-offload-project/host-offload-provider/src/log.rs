@@ -0,0 +1,65 @@
+use std::fmt;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use crate::session_admin::LogLevel;
+
+// Severity of a provider diagnostic, as `host-offload-logging` orders them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+// Least severe level emitted, as a `Level`; `OFF` emits nothing.
+const OFF: u8 = Level::Error as u8 + 1;
+static MIN_LEVEL: AtomicU8 = AtomicU8::new(Level::Info as u8);
+
+pub(crate) fn enabled(level: Level) -> bool {
+    level as u8 >= MIN_LEVEL.load(Ordering::Relaxed)
+}
+
+pub(crate) fn set_level(level: LogLevel) {
+    let min = match level {
+        LogLevel::Off => OFF,
+        LogLevel::Error => Level::Error as u8,
+        LogLevel::Warn => Level::Warn as u8,
+        LogLevel::Info => Level::Info as u8,
+        LogLevel::Debug => Level::Debug as u8,
+        LogLevel::Trace => Level::Trace as u8,
+    };
+    MIN_LEVEL.store(min, Ordering::Relaxed);
+}
+
+// `log!(Debug, "Freeing buffer {}", h)`. The target is the calling module.
+// Nothing is formatted unless the level is enabled.
+macro_rules! log {
+    ($level:ident, $($arg:tt)+) => {
+        if $crate::log::enabled($crate::log::Level::$level) {
+            $crate::log::emit($crate::log::Level::$level, module_path!(), format_args!($($arg)+))
+        }
+    };
+}
+
+// The component hands each message to the embedder; the native build prints
+// it, since the embedder is in the same process and reads stdout anyway.
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn emit(level: Level, target: &str, message: fmt::Arguments<'_>) {
+    use crate::wasi_custom::host_offload::host_offload_logging::{self as logging, Level as WitLevel};
+
+    let level = match level {
+        Level::Trace => WitLevel::Trace,
+        Level::Debug => WitLevel::Debug,
+        Level::Info => WitLevel::Info,
+        Level::Warn => WitLevel::Warn,
+        Level::Error => WitLevel::Error,
+    };
+    logging::log(level, target, &message.to_string());
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn emit(_level: Level, _target: &str, message: fmt::Arguments<'_>) {
+    println!("[Provider Wasm] {}", message);
+}
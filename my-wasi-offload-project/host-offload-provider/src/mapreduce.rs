@@ -0,0 +1,113 @@
+use std::sync::Mutex;
+
+use nalgebra::DMatrix;
+
+use crate::kernels;
+use crate::registry;
+use crate::wasi_custom::host_offload::host_allocator::{ConcatAxis, ElementType, Handle, HostError, MatrixLayout, ReduceOp, Summation};
+use crate::HostState;
+
+fn reduce_line(values: &[f32], op: ReduceOp, summation: Summation) -> f32 {
+    match op {
+        ReduceOp::Sum => kernels::sum_f32(values, summation),
+        ReduceOp::Prod => values.iter().product(),
+        ReduceOp::Min => values.iter().copied().fold(f32::INFINITY, f32::min),
+        ReduceOp::Max => values.iter().copied().fold(f32::NEG_INFINITY, f32::max),
+    }
+}
+
+impl HostState {
+    pub fn reduce(&mut self, h: Handle, op: ReduceOp, axis: Option<ConcatAxis>, summation: Summation) -> Result<Handle, HostError> {
+        log!(Debug, "Reducing {} by {:?} along {:?} ({:?})", h, op, axis, summation);
+        self.charge(0)?;
+        self.materialize(h)?;
+        let result = match axis {
+            None => {
+                let values = self.read_f32(h)?;
+                DMatrix::from_element(1, 1, reduce_line(&values, op, summation))
+            }
+            Some(axis) => {
+                self.check_matrix(h, ElementType::F32)?;
+                let (_, matrix) = self.read_matrix_f32(h)?;
+                match axis {
+                    // Column-major storage makes each column contiguous.
+                    ConcatAxis::Rows => DMatrix::from_iterator(
+                        1,
+                        matrix.ncols(),
+                        matrix.column_iter().map(|column| reduce_line(column.as_slice(), op, summation)),
+                    ),
+                    ConcatAxis::Cols => {
+                        let rows = matrix.transpose();
+                        DMatrix::from_iterator(
+                            matrix.nrows(),
+                            1,
+                            rows.column_iter().map(|row| reduce_line(row.as_slice(), op, summation)),
+                        )
+                    }
+                }
+            }
+        };
+        Ok(self.store_matrix_f32(&result, MatrixLayout::RowMajor))
+    }
+}
+
+// `parallel-map`: `name` over `chunks` row blocks of `h`, each block taken
+// out with `gather-rows` and the op's outputs joined with `concat`. Runs
+// without the lock held, like `call_op`, so extension ops can call back in.
+pub(crate) fn parallel_map(state: &Mutex<HostState>, name: &str, h: Handle, chunks: u32) -> Result<Handle, HostError> {
+    let blocks = {
+        let mut state = state.lock().unwrap();
+        log!(Debug, "Mapping '{}' over {} in {} chunks", name, h, chunks);
+        state.charge(0)?;
+        state.materialize(h)?;
+        let rows = state.matrix_dims.get(&h).ok_or_else(|| state.missing(h))?.rows;
+        let chunks = chunks.clamp(1, rows.max(1)) as u64;
+        let bound = |i: u64| (rows as u64 * i / chunks) as u32;
+        let mut blocks = Vec::with_capacity(chunks as usize);
+        for i in 0..chunks {
+            let range: Vec<u32> = (bound(i)..bound(i + 1)).collect();
+            match state.gather_rows(h, &range) {
+                Ok(block) => blocks.push(block),
+                Err(e) => {
+                    for block in blocks {
+                        state.release(block);
+                    }
+                    return Err(e);
+                }
+            }
+        }
+        blocks
+    };
+
+    #[cfg(not(target_arch = "wasm32"))]
+    let results: Vec<_> = std::thread::scope(|scope| {
+        let workers: Vec<_> =
+            blocks.iter().map(|&block| scope.spawn(move || registry::call_op(state, name, &[block], false))).collect();
+        workers.into_iter().map(|worker| worker.join().unwrap()).collect()
+    });
+    #[cfg(target_arch = "wasm32")]
+    let results: Vec<_> = blocks.iter().map(|&block| registry::call_op(state, name, &[block], false)).collect();
+
+    let mut state = state.lock().unwrap();
+    let mut outputs = Vec::new();
+    let mut failure = None;
+    for result in results {
+        match result {
+            Ok(handles) if handles.len() == 1 => outputs.push(handles[0]),
+            Ok(handles) => {
+                let message = format!("'{}' returned {} handles; parallel-map needs ops with one output", name, handles.len());
+                failure = failure.or(Some(HostError::InvalidArguments(message)));
+                outputs.extend(handles);
+            }
+            Err(e) => failure = failure.or(Some(e)),
+        }
+    }
+    let joined = match failure {
+        Some(e) => Err(e),
+        None => state.concat(&outputs, ConcatAxis::Rows),
+    };
+    for handle in blocks.into_iter().chain(outputs) {
+        state.release(handle);
+    }
+    joined
+}
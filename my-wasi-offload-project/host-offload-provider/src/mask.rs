@@ -0,0 +1,93 @@
+use crate::cast::{decode, encode};
+use crate::state::element_size;
+use crate::wasi_custom::host_offload::host_allocator::{CompareOp, ElementType, Handle, HostError};
+use crate::HostState;
+
+impl HostState {
+    // Element type and values of `h`, widened to f64, in stored order.
+    pub(crate) fn elements(&mut self, h: Handle) -> Result<(ElementType, Vec<f64>), HostError> {
+        self.materialize(h)?;
+        let elem = self.element_types.get(&h).copied().unwrap_or(ElementType::F32);
+        let bytes = self.buffers.get(&h).ok_or_else(|| self.missing(h))?;
+        if bytes.len() % element_size(elem) != 0 {
+            return Err(HostError::Misaligned);
+        }
+        Ok((elem, decode(bytes, elem)))
+    }
+
+    // Elementwise operands pair up in stored order, so matrices must also
+    // agree on dims and layout.
+    fn check_elementwise(&self, a: Handle, b: Handle, len_a: usize, len_b: usize) -> Result<(), HostError> {
+        let dims = (self.matrix_dims.get(&a), self.matrix_dims.get(&b));
+        if len_a != len_b || matches!(dims, (Some(x), Some(y)) if x != y) {
+            return Err(HostError::DimensionMismatch);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn store_elements(&mut self, like: Handle, values: &[f64], elem: ElementType) -> Handle {
+        let handle = self.new_handle();
+        self.buffers.insert(handle, encode(values, elem).into());
+        if let Some(&dims) = self.matrix_dims.get(&like) {
+            self.matrix_dims.insert(handle, dims);
+        }
+        self.element_types.insert(handle, elem);
+        handle
+    }
+
+    pub fn compare(&mut self, a: Handle, b: Handle, op: CompareOp) -> Result<Handle, HostError> {
+        log!(Debug, "Comparing {} {:?} {}", a, op, b);
+        self.charge(0)?;
+        let (_, lhs) = self.elements(a)?;
+        let (_, rhs) = self.elements(b)?;
+        // A one-element `b` is a threshold for every element of `a`.
+        if rhs.len() != 1 {
+            self.check_elementwise(a, b, lhs.len(), rhs.len())?;
+        }
+        let holds = |x: f64, y: f64| match op {
+            CompareOp::Eq => x == y,
+            CompareOp::Ne => x != y,
+            CompareOp::Lt => x < y,
+            CompareOp::Le => x <= y,
+            CompareOp::Gt => x > y,
+            CompareOp::Ge => x >= y,
+        };
+        let mask: Vec<f64> = lhs.iter().enumerate().map(|(i, &x)| holds(x, rhs[i % rhs.len()]) as u8 as f64).collect();
+        Ok(self.store_elements(a, &mask, ElementType::U8))
+    }
+
+    pub fn where_(&mut self, cond: Handle, a: Handle, b: Handle) -> Result<Handle, HostError> {
+        log!(Debug, "Selecting between {} and {} by {}", a, b, cond);
+        self.charge(0)?;
+        let (_, mask) = self.elements(cond)?;
+        let (elem, mut values) = self.elements(a)?;
+        let (elem_b, others) = self.elements(b)?;
+        if elem != elem_b {
+            return Err(HostError::TypeMismatch);
+        }
+        self.check_elementwise(a, b, values.len(), others.len())?;
+        self.check_elementwise(a, cond, values.len(), mask.len())?;
+        for ((v, &m), &other) in values.iter_mut().zip(&mask).zip(&others) {
+            if m == 0.0 {
+                *v = other;
+            }
+        }
+        let result = self.store_elements(a, &values, elem);
+        self.trace("where", &[cond, a, b], &[result]);
+        Ok(result)
+    }
+
+    pub fn masked_fill(&mut self, h: Handle, mask: Handle, value: f64) -> Result<(), HostError> {
+        log!(Debug, "Filling {} with {} where {} is set", h, value, mask);
+        self.charge(0)?;
+        let (_, set) = self.elements(mask)?;
+        let (elem, mut values) = self.elements(h)?;
+        self.check_elementwise(h, mask, values.len(), set.len())?;
+        values.iter_mut().zip(&set).filter(|(_, &m)| m != 0.0).for_each(|(v, _)| *v = value);
+        self.materialize_dependents(h)?;
+        self.journal(h)?;
+        self.store_bytes(h, encode(&values, elem));
+        self.trace("masked-fill", &[h, mask], &[h]);
+        Ok(())
+    }
+}
@@ -0,0 +1,215 @@
+use std::collections::HashMap;
+use std::ffi::c_void;
+use std::time::Instant;
+
+use ::metal::{Buffer, CommandQueue, MTLResourceOptions};
+use foreign_types::ForeignTypeRef;
+use objc::runtime::{Object, BOOL, NO, YES};
+use objc::{class, msg_send, sel, sel_impl};
+
+use crate::backend::{ComputeBackend, Operand};
+use crate::wasi_custom::host_offload::host_allocator::{Device, DeviceInfo, Handle, HostError, MatrixLayout};
+
+pub(crate) const BACKEND_NAME: &str = "metal-mps";
+
+// `MPSDataTypeFloat32`. MPS has no 64-bit float type, so `matmul-f64` is
+// unavailable on this backend.
+const MPS_DATA_TYPE_FLOAT32: u32 = 0x1000_0000 | 32;
+
+#[link(name = "MetalPerformanceShaders", kind = "framework")]
+extern "C" {
+    fn MPSSupportsMTLDevice(device: *mut Object) -> BOOL;
+}
+
+struct Gpu {
+    device: ::metal::Device,
+    queue: CommandQueue,
+    name: String,
+    memory_bytes: u64,
+    // Apple Silicon: the GPU reads host memory directly.
+    unified: bool,
+}
+
+// MPSMatrixMultiplication on every Metal device that MPS supports.
+//
+// All buffers use shared storage. On unified-memory devices a page-aligned
+// operand (an `mmap` or `shared-memory` buffer, or a heap buffer that happens
+// to be aligned) is wrapped in place instead of copied. Operands placed or
+// prefetched on a device are copied once and stay resident until `evict`.
+pub struct MetalBackend {
+    gpus: Vec<Gpu>,
+    resident: HashMap<Handle, (u32, Buffer)>,
+    page_size: usize,
+}
+
+impl MetalBackend {
+    pub fn new() -> Result<Self, String> {
+        let gpus: Vec<_> = ::metal::Device::all()
+            .into_iter()
+            .filter(|device| unsafe { MPSSupportsMTLDevice(device.as_ptr().cast()) } == YES)
+            .map(|device| Gpu {
+                queue: device.new_command_queue(),
+                name: device.name().to_string(),
+                memory_bytes: device.recommended_max_working_set_size(),
+                unified: device.has_unified_memory(),
+                device,
+            })
+            .collect();
+        if gpus.is_empty() {
+            return Err("no Metal devices supported by MetalPerformanceShaders found".to_string());
+        }
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+        Ok(MetalBackend { gpus, resident: HashMap::new(), page_size })
+    }
+
+    fn gpu(&self, device: u32) -> Result<&Gpu, HostError> {
+        self.gpus.get(device as usize).ok_or(HostError::DeviceUnavailable)
+    }
+
+    // A buffer the GPU can read `bytes` through for the length of one call.
+    fn stage(&self, gpu: &Gpu, bytes: &[u8]) -> Buffer {
+        let ptr = bytes.as_ptr();
+        if gpu.unified && !bytes.is_empty() && ptr as usize % self.page_size == 0 {
+            // Whole pages, as Metal requires; the tail of the last page is
+            // mapped whatever owns it and the kernel never reads it.
+            let len = bytes.len().next_multiple_of(self.page_size);
+            return gpu.device.new_buffer_with_bytes_no_copy(
+                ptr.cast::<c_void>(),
+                len as u64,
+                MTLResourceOptions::StorageModeShared,
+                None,
+            );
+        }
+        gpu.device
+            .new_buffer_with_data(ptr.cast(), bytes.len() as u64, MTLResourceOptions::StorageModeShared)
+    }
+
+    fn matmul(&self, device: u32, a: Operand, b: Operand, deadline: Option<Instant>) -> Result<Vec<u8>, HostError> {
+        let gpu = self.gpu(device)?;
+        if deadline.is_some_and(|d| Instant::now() > d) {
+            return Err(HostError::Timeout);
+        }
+        let (m, k, n) = (a.dims.rows as u64, a.dims.cols as u64, b.dims.cols as u64);
+        let resident = |h: Handle| self.resident.get(&h).filter(|(d, _)| *d == device).map(|(_, buffer)| buffer.clone());
+        let a_buffer = resident(a.handle).unwrap_or_else(|| self.stage(gpu, a.bytes));
+        let b_buffer = resident(b.handle).unwrap_or_else(|| self.stage(gpu, b.bytes));
+        let c_buffer = gpu.device.new_buffer(m * n * 4, MTLResourceOptions::StorageModeShared);
+
+        // MPS is row-major: a column-major buffer reads as the transpose of
+        // its matrix. A column-major C is computed as C^T = B^T A^T.
+        let stored = |layout, rows: u64, cols: u64| match layout {
+            MatrixLayout::RowMajor => (rows, cols),
+            MatrixLayout::ColumnMajor => (cols, rows),
+        };
+        let (a_rows, a_cols) = stored(a.dims.layout, m, k);
+        let (b_rows, b_cols) = stored(b.dims.layout, k, n);
+        let (c_rows, c_cols) = stored(a.dims.layout, m, n);
+        let transposed = |layout, wanted| if layout == wanted { NO } else { YES };
+        // SAFETY: every MPS object is created, used and released here, on
+        // buffers sized from dims the provider has checked.
+        unsafe {
+            let matrix = |buffer: &Buffer, rows: u64, cols: u64| -> *mut Object {
+                let descriptor: *mut Object = msg_send![class!(MPSMatrixDescriptor),
+                    matrixDescriptorWithRows: rows as usize
+                    columns: cols as usize
+                    rowBytes: cols as usize * 4
+                    dataType: MPS_DATA_TYPE_FLOAT32];
+                let matrix: *mut Object = msg_send![class!(MPSMatrix), alloc];
+                msg_send![matrix, initWithBuffer: buffer.as_ptr() descriptor: descriptor]
+            };
+            let a_matrix = matrix(&a_buffer, a_rows, a_cols);
+            let b_matrix = matrix(&b_buffer, b_rows, b_cols);
+            let c_matrix = matrix(&c_buffer, c_rows, c_cols);
+            let (left, right, transpose_left, transpose_right, rows, cols) = match a.dims.layout {
+                MatrixLayout::RowMajor => (
+                    a_matrix,
+                    b_matrix,
+                    transposed(a.dims.layout, MatrixLayout::RowMajor),
+                    transposed(b.dims.layout, MatrixLayout::RowMajor),
+                    m,
+                    n,
+                ),
+                MatrixLayout::ColumnMajor => (
+                    b_matrix,
+                    a_matrix,
+                    transposed(b.dims.layout, MatrixLayout::ColumnMajor),
+                    transposed(a.dims.layout, MatrixLayout::ColumnMajor),
+                    n,
+                    m,
+                ),
+            };
+            let kernel: *mut Object = msg_send![class!(MPSMatrixMultiplication), alloc];
+            let kernel: *mut Object = msg_send![kernel,
+                initWithDevice: gpu.device.as_ptr()
+                transposeLeft: transpose_left
+                transposeRight: transpose_right
+                resultRows: rows as usize
+                resultColumns: cols as usize
+                interiorColumns: k as usize
+                alpha: 1.0f64
+                beta: 0.0f64];
+            let commands = gpu.queue.new_command_buffer();
+            let _: () = msg_send![kernel,
+                encodeToCommandBuffer: commands.as_ptr()
+                leftMatrix: left
+                rightMatrix: right
+                resultMatrix: c_matrix];
+            commands.commit();
+            commands.wait_until_completed();
+            for object in [kernel, a_matrix, b_matrix, c_matrix] {
+                let _: () = msg_send![object, release];
+            }
+        }
+        // Shared storage: the product is readable in place.
+        let c = c_buffer.contents().cast::<u8>();
+        let bytes = unsafe { std::slice::from_raw_parts(c, (m * n * 4) as usize) }.to_vec();
+        // The kernel can't be interrupted; a late result is still abandoned.
+        if deadline.is_some_and(|d| Instant::now() > d) {
+            return Err(HostError::Timeout);
+        }
+        Ok(bytes)
+    }
+}
+
+impl ComputeBackend for MetalBackend {
+    fn name(&self) -> &'static str {
+        BACKEND_NAME
+    }
+
+    fn devices(&self) -> Vec<DeviceInfo> {
+        self.gpus
+            .iter()
+            .enumerate()
+            .map(|(n, gpu)| DeviceInfo {
+                device: Device::Gpu(n as u32),
+                name: gpu.name.clone(),
+                backend: BACKEND_NAME.to_string(),
+                memory_bytes: Some(gpu.memory_bytes),
+            })
+            .collect()
+    }
+
+    fn matmul_f32(&mut self, device: u32, a: Operand, b: Operand, deadline: Option<Instant>) -> Result<Vec<u8>, HostError> {
+        self.matmul(device, a, b, deadline)
+    }
+
+    fn matmul_f64(&mut self, device: u32, _a: Operand, _b: Operand, _deadline: Option<Instant>) -> Result<Vec<u8>, HostError> {
+        self.gpu(device)?;
+        Err(HostError::DeviceUnavailable)
+    }
+
+    // Always a copy, even on unified memory: the provider may move or drop
+    // the bytes before it evicts them.
+    fn upload(&mut self, device: u32, h: Handle, bytes: &[u8]) -> Result<(), HostError> {
+        let gpu = self.gpu(device)?;
+        let buffer = gpu
+            .device
+            .new_buffer_with_data(bytes.as_ptr().cast(), bytes.len() as u64, MTLResourceOptions::StorageModeShared);
+        self.resident.insert(h, (device, buffer));
+        Ok(())
+    }
+
+    fn evict(&mut self, h: Handle) {
+        self.resident.remove(&h);
+    }
+}
@@ -0,0 +1,872 @@
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use wasmtime_wasi::preview2::{InputStream, OutputStream};
+
+use crate::audit::{AuditLog, Auditor};
+use crate::mapreduce;
+use crate::policy::{batch_function, op_function, Policy};
+use crate::registry;
+use crate::session_admin::{SessionId, SessionLimits};
+use crate::state::{element_size, HostState};
+use crate::streams::{BufferReadStream, BufferWriteStream};
+use crate::tokenize::Tokenizers;
+use crate::wasi_custom::host_offload::host_allocator::{
+    self, ArenaId, BackendChoice, BackendInfo, BufferFlags, CompareOp, ComparisonReport, ComputeHint, ComputeMode,
+    ConcatAxis, CostEstimate, Device, DeviceInfo, DumpDestination, DumpFormat, EigenDecomposition, ElementType,
+    EvaluationMode, Graph, Handle, HandleInfo, HashAlgorithm, HostError, InterfaceVersion, JobId, JobProgress,
+    JobState, MatrixDimensions, MatrixShape, MatrixStructure, MelParams, MemoryStats, OpDescriptor, OpResult, OpSchema, Pooling,
+    ReduceOp, ShmAccess, ShmDescriptor, StorageKind, Summation, TensorMeta, TopK
+};
+use crate::wasi_custom::host_offload::random::{self, Distribution, RngId};
+
+// The provider linked straight into the runner. Implements `host-allocator`
+// against its own `HostState`, so each client store gets an isolated provider
+// just like a separately instantiated component, and it can hand out
+// `wasi:io` streams, which a provider component cannot.
+//
+// Cloning shares the same provider state. Clones may be used from any thread
+// or task; each call takes the state's lock for its duration, and owned
+// buffers (`buffer::HostBuffer`) do the same.
+#[derive(Clone, Default)]
+pub struct OffloadHost {
+    state: Arc<Mutex<HostState>>,
+    // Where this host's `host-allocator` calls are recorded, if anywhere.
+    audit: Option<Auditor>,
+    policy: Arc<Policy>,
+}
+
+impl OffloadHost {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Direct access for the embedder, e.g. to open and close sessions. Don't
+    // hold the guard across an `.await`: other tasks sharing the host block on it.
+    pub fn lock(&self) -> MutexGuard<'_, HostState> {
+        self.state.lock().unwrap()
+    }
+
+    // Runs a call that may compute or do I/O for a while. On a multi-threaded
+    // tokio runtime (`runner --async`, `runner serve`) `block_in_place` first
+    // hands this worker's other tasks to another thread, so they keep running
+    // meanwhile; elsewhere it's a plain call. Inside a session the call first
+    // waits for its turn at a compute slot (see `fair`), which it is charged for.
+    fn blocking<R>(&self, call: impl FnOnce(&mut HostState) -> R) -> R {
+        off_runtime(|| {
+            let share = self.lock().compute_share();
+            let _turn = share.as_ref().map(|share| share.wait_turn());
+            call(&mut *self.lock())
+        })
+    }
+
+    // Works through a submitted job on a thread of its own, a slice of rows
+    // at a time (see `HostState::step`). The lock is free between slices, so
+    // the guest keeps uploading while the job computes; `wait-job` finishes
+    // whatever is left inline.
+    fn run_job(&self, job: JobId) {
+        let host = self.clone();
+        std::thread::spawn(move || while host.blocking(|state| state.advance_job(job)).is_some() {});
+    }
+
+    pub fn write_stream(&self, h: Handle, offset: u64) -> Result<OutputStream, HostError> {
+        let stream = BufferWriteStream::new(self.state.clone(), h, offset)?;
+        Ok(Box::new(stream))
+    }
+
+    pub fn read_stream(&self, h: Handle, offset: u64) -> Result<InputStream, HostError> {
+        let stream = BufferReadStream::new(self.state.clone(), h, offset)?;
+        Ok(InputStream::Host(Box::new(stream)))
+    }
+
+    // Records every `host-allocator` call the guest makes through this host
+    // (and clones made from it afterwards) in `log`, as `instance`.
+    pub fn set_audit_log(&mut self, log: Arc<AuditLog>, instance: impl Into<String>) {
+        self.audit = Some(Auditor { log, instance: instance.into() });
+    }
+
+    fn audit(
+        &self,
+        op: &str,
+        inputs: impl IntoIterator<Item = Handle>,
+        outputs: impl IntoIterator<Item = Handle>,
+        bytes: Option<u64>,
+        error: Option<&HostError>,
+    ) {
+        if let Some(auditor) = &self.audit {
+            let handles: Vec<Handle> = inputs.into_iter().chain(outputs).collect();
+            auditor.log.record(&auditor.instance, op, &handles, bytes, error);
+        }
+    }
+
+    // Holds the guest's `host-allocator` calls through this host (and clones
+    // made from it afterwards) to `policy`.
+    pub fn set_policy(&mut self, policy: Policy) {
+        self.lock().set_max_matmul_dim(policy.max_matmul_dim);
+        self.policy = Arc::new(policy);
+    }
+
+    // The `tokenizer` interface for this host's guest, held to the same
+    // policy and audit log.
+    pub fn tokenizers(&self, dir: impl Into<PathBuf>) -> Tokenizers {
+        Tokenizers::guarded(dir, self.policy.clone(), self.audit.clone())
+    }
+
+    fn permit(&self, op: &str) -> Result<(), HostError> {
+        self.policy.permit(op)
+    }
+
+    // For calls with no `host-error` to return: a denied one traps the guest.
+    fn permit_or_trap(&self, op: &str) -> wasmtime::Result<()> {
+        if let Err(e) = self.permit(op) {
+            self.audit(op, [], [], None, Some(&e));
+            return Err(wasmtime::Error::msg(format!("{:?}", e)));
+        }
+        Ok(())
+    }
+}
+
+fn off_runtime<R>(call: impl FnOnce() -> R) -> R {
+    match tokio::runtime::Handle::try_current() {
+        Ok(runtime) if runtime.runtime_flavor() == tokio::runtime::RuntimeFlavor::MultiThread => {
+            tokio::task::block_in_place(call)
+        }
+        _ => call(),
+    }
+}
+
+// An open session that closes itself if dropped, e.g. along with the store of
+// a client whose task was aborted or whose thread panicked. Closing cancels
+// the session's unfinished jobs and frees every handle it created, so nothing
+// outlives the client even while other clones of the host are still around.
+pub struct SessionGuard {
+    host: OffloadHost,
+    id: SessionId,
+    closed: bool,
+}
+
+impl OffloadHost {
+    pub fn open_guarded_session(&self, limits: SessionLimits) -> Result<SessionGuard, String> {
+        let id = self.lock().open_session(limits)?;
+        Ok(SessionGuard { host: self.clone(), id, closed: false })
+    }
+}
+
+impl SessionGuard {
+    pub fn id(&self) -> SessionId {
+        self.id
+    }
+
+    // Closes the session now, returning what `close_session` does.
+    pub fn close(mut self) -> Result<u32, String> {
+        self.closed = true;
+        self.host.lock().close_session(self.id)
+    }
+}
+
+impl Drop for SessionGuard {
+    fn drop(&mut self) {
+        if self.closed {
+            return;
+        }
+        // A poisoned lock means the provider panicked mid-call; the state is
+        // dropped with the last clone of the host anyway.
+        if let Ok(mut state) = self.host.state.lock() {
+            if let Ok(freed) = state.close_session(self.id) {
+                log!(Warn, "Session {} dropped while open, freed {} handles", self.id, freed);
+            }
+        }
+    }
+}
+
+impl host_allocator::Host for OffloadHost {
+    fn allocate_buffer(&mut self, size: u64) -> wasmtime::Result<Result<Handle, HostError>> {
+        let result = self.permit("allocate-buffer").and_then(|()| self.lock().allocate_buffer(size));
+        self.audit("allocate-buffer", [], result.iter().copied(), Some(size), result.as_ref().err());
+        Ok(result)
+    }
+
+    fn free_buffer(&mut self, h: Handle) -> wasmtime::Result<Result<(), HostError>> {
+        let result = self.permit("free-buffer").and_then(|()| self.lock().free_buffer(h));
+        self.audit("free-buffer", [h], [], None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn allocate_buffer_with_ttl(&mut self, size: u64, ttl_ms: u64) -> wasmtime::Result<Result<Handle, HostError>> {
+        let result =
+            self.permit("allocate-buffer-with-ttl").and_then(|()| self.lock().allocate_buffer_with_ttl(size, ttl_ms));
+        self.audit("allocate-buffer-with-ttl", [], result.iter().copied(), Some(size), result.as_ref().err());
+        Ok(result)
+    }
+
+    fn allocate_buffer_with_storage(
+        &mut self,
+        size: u64,
+        hint: StorageKind,
+    ) -> wasmtime::Result<Result<Handle, HostError>> {
+        let result = self
+            .permit("allocate-buffer-with-storage")
+            .and_then(|()| self.policy.permit_storage(hint))
+            .and_then(|()| self.lock().allocate_buffer_with_storage(size, hint));
+        self.audit("allocate-buffer-with-storage", [], result.iter().copied(), Some(size), result.as_ref().err());
+        Ok(result)
+    }
+
+    fn get_storage_kind(&mut self, h: Handle) -> wasmtime::Result<Result<StorageKind, HostError>> {
+        let result = self.permit("get-storage-kind").and_then(|()| self.lock().get_storage_kind(h));
+        self.audit("get-storage-kind", [h], [], None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn export_shm(&mut self, h: Handle, access: ShmAccess) -> wasmtime::Result<Result<ShmDescriptor, HostError>> {
+        let result = self.permit("export-shm").and_then(|()| self.lock().export_shm(h, access));
+        self.audit("export-shm", [h], [], None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn revoke_shm(&mut self, h: Handle) -> wasmtime::Result<Result<(), HostError>> {
+        let result = self.permit("revoke-shm").and_then(|()| self.lock().revoke_shm(h));
+        self.audit("revoke-shm", [h], [], None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn write_to_host(&mut self, guest_bytes: Vec<u8>, target_handle: Handle, target_offset: u64) -> wasmtime::Result<Result<(), HostError>> {
+        let result = self
+            .permit("write-to-host")
+            .and_then(|()| self.lock().write_to_host(&guest_bytes, target_handle, target_offset));
+        self.audit("write-to-host", [target_handle], [], Some(guest_bytes.len() as u64), result.as_ref().err());
+        Ok(result)
+    }
+
+    fn read_from_host(&mut self, source_handle: Handle, source_offset: u64, len: u64) -> wasmtime::Result<Result<Vec<u8>, HostError>> {
+        let result =
+            self.permit("read-from-host").and_then(|()| self.lock().read_from_host(source_handle, source_offset, len));
+        let bytes = result.as_ref().ok().map(|bytes| bytes.len() as u64);
+        self.audit("read-from-host", [source_handle], [], bytes, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn write_f32(&mut self, h: Handle, offset_in_elems: u64, values: Vec<f32>) -> wasmtime::Result<Result<(), HostError>> {
+        let result = self.permit("write-f32").and_then(|()| self.lock().write_f32(h, offset_in_elems, &values));
+        self.audit("write-f32", [h], [], Some(values.len() as u64 * 4), result.as_ref().err());
+        Ok(result)
+    }
+
+    fn read_f32(&mut self, h: Handle, offset_in_elems: u64, count: u64) -> wasmtime::Result<Result<Vec<f32>, HostError>> {
+        let result = self.permit("read-f32").and_then(|()| self.lock().read_f32_elems(h, offset_in_elems, count));
+        let bytes = result.as_ref().ok().map(|values| values.len() as u64 * 4);
+        self.audit("read-f32", [h], [], bytes, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn write_f64(&mut self, h: Handle, offset_in_elems: u64, values: Vec<f64>) -> wasmtime::Result<Result<(), HostError>> {
+        let result = self.permit("write-f64").and_then(|()| self.lock().write_f64(h, offset_in_elems, &values));
+        self.audit("write-f64", [h], [], Some(values.len() as u64 * 8), result.as_ref().err());
+        Ok(result)
+    }
+
+    fn read_f64(&mut self, h: Handle, offset_in_elems: u64, count: u64) -> wasmtime::Result<Result<Vec<f64>, HostError>> {
+        let result = self.permit("read-f64").and_then(|()| self.lock().read_f64_elems(h, offset_in_elems, count));
+        let bytes = result.as_ref().ok().map(|values| values.len() as u64 * 8);
+        self.audit("read-f64", [h], [], bytes, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn write_i32(&mut self, h: Handle, offset_in_elems: u64, values: Vec<i32>) -> wasmtime::Result<Result<(), HostError>> {
+        let result = self.permit("write-i32").and_then(|()| self.lock().write_i32(h, offset_in_elems, &values));
+        self.audit("write-i32", [h], [], Some(values.len() as u64 * 4), result.as_ref().err());
+        Ok(result)
+    }
+
+    fn read_i32(&mut self, h: Handle, offset_in_elems: u64, count: u64) -> wasmtime::Result<Result<Vec<i32>, HostError>> {
+        let result = self.permit("read-i32").and_then(|()| self.lock().read_i32_elems(h, offset_in_elems, count));
+        let bytes = result.as_ref().ok().map(|values| values.len() as u64 * 4);
+        self.audit("read-i32", [h], [], bytes, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn register_matrix_dimensions(&mut self, h: Handle, dims: MatrixDimensions) -> wasmtime::Result<Result<(), HostError>> {
+        let result =
+            self.permit("register-matrix-dimensions").and_then(|()| self.lock().register_matrix_dimensions(h, dims));
+        self.audit("register-matrix-dimensions", [h], [], None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn register_matrix_shape(&mut self, h: Handle, dims: MatrixShape) -> wasmtime::Result<Result<(), HostError>> {
+        let result =
+            self.permit("register-matrix-shape").and_then(|()| self.lock().register_matrix_shape(h, dims));
+        self.audit("register-matrix-shape", [h], [], None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn matrix_multiply_f32(&mut self, handle_a: Handle, handle_b: Handle) -> wasmtime::Result<Result<Handle, HostError>> {
+        let result = self
+            .permit("matrix-multiply-f32")
+            .and_then(|()| self.blocking(|state| state.matrix_multiply_f32(handle_a, handle_b, None)));
+        self.audit("matrix-multiply-f32", [handle_a, handle_b], result.iter().copied(), None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn matrix_multiply_f32_on(&mut self, handle_a: Handle, handle_b: Handle, on: Device) -> wasmtime::Result<Result<Handle, HostError>> {
+        let result = self
+            .permit("matrix-multiply-f32-on")
+            .and_then(|()| self.blocking(|state| state.matrix_multiply_f32(handle_a, handle_b, Some(on))));
+        self.audit("matrix-multiply-f32-on", [handle_a, handle_b], result.iter().copied(), None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn get_matrix_dimensions(&mut self, h: Handle) -> wasmtime::Result<Result<MatrixDimensions, HostError>> {
+        let result = self.permit("get-matrix-dimensions").and_then(|()| self.lock().get_matrix_dimensions(h));
+        self.audit("get-matrix-dimensions", [h], [], None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn get_matrix_shape(&mut self, h: Handle) -> wasmtime::Result<Result<MatrixShape, HostError>> {
+        let result = self.permit("get-matrix-shape").and_then(|()| self.lock().get_matrix_shape(h));
+        self.audit("get-matrix-shape", [h], [], None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn allocate_typed_buffer(&mut self, element_type: ElementType, count: u64) -> wasmtime::Result<Result<Handle, HostError>> {
+        let result =
+            self.permit("allocate-typed-buffer").and_then(|()| self.lock().allocate_typed_buffer(element_type, count));
+        let bytes = Some(count.saturating_mul(element_size(element_type) as u64));
+        self.audit("allocate-typed-buffer", [], result.iter().copied(), bytes, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn register_tensor_meta(&mut self, h: Handle, meta: TensorMeta) -> wasmtime::Result<Result<(), HostError>> {
+        let result = self.permit("register-tensor-meta").and_then(|()| self.lock().register_tensor_meta(h, meta));
+        self.audit("register-tensor-meta", [h], [], None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn get_element_type(&mut self, h: Handle) -> wasmtime::Result<Result<Option<ElementType>, HostError>> {
+        let result = self.permit("get-element-type").and_then(|()| self.lock().get_element_type(h));
+        self.audit("get-element-type", [h], [], None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn set_compute_mode(&mut self, mode: ComputeMode) -> wasmtime::Result<()> {
+        self.permit_or_trap("set-compute-mode")?;
+        self.audit("set-compute-mode", [], [], None, None);
+        self.lock().set_compute_mode(mode);
+        Ok(())
+    }
+
+    fn set_compute_hint(&mut self, hint: ComputeHint) -> wasmtime::Result<()> {
+        self.permit_or_trap("set-compute-hint")?;
+        self.audit("set-compute-hint", [], [], None, None);
+        self.lock().set_compute_hint(hint);
+        Ok(())
+    }
+
+    fn set_op_timeout(&mut self, millis: Option<u64>) -> wasmtime::Result<()> {
+        self.permit_or_trap("set-op-timeout")?;
+        self.audit("set-op-timeout", [], [], None, None);
+        self.lock().set_op_timeout(millis);
+        Ok(())
+    }
+
+    fn get_backend_info(&mut self) -> wasmtime::Result<BackendInfo> {
+        self.permit_or_trap("get-backend-info")?;
+        self.audit("get-backend-info", [], [], None, None);
+        Ok(self.lock().get_backend_info())
+    }
+
+    fn compare_buffers_f32(&mut self, handle_a: Handle, handle_b: Handle, rtol: f32, atol: f32) -> wasmtime::Result<Result<ComparisonReport, HostError>> {
+        let result = self
+            .permit("compare-buffers-f32")
+            .and_then(|()| self.blocking(|state| state.compare_buffers_f32(handle_a, handle_b, rtol, atol)));
+        self.audit("compare-buffers-f32", [handle_a, handle_b], [], None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn axpy_f32(&mut self, alpha: f32, x: Handle, y: Handle) -> wasmtime::Result<Result<(), HostError>> {
+        let result = self.permit("axpy-f32").and_then(|()| self.lock().axpy_f32(alpha, x, y));
+        self.audit("axpy-f32", [x, y], [], None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn scal_f32(&mut self, alpha: f32, x: Handle) -> wasmtime::Result<Result<(), HostError>> {
+        let result = self.permit("scal-f32").and_then(|()| self.lock().scal_f32(alpha, x));
+        self.audit("scal-f32", [x], [], None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn gemv_f32(&mut self, alpha: f32, a: Handle, x: Handle, beta: f32, y: Handle) -> wasmtime::Result<Result<(), HostError>> {
+        let result = self.permit("gemv-f32").and_then(|()| self.blocking(|state| state.gemv_f32(alpha, a, x, beta, y)));
+        self.audit("gemv-f32", [a, x, y], [], None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn matmul_accumulate(&mut self, a: Handle, b: Handle, c: Handle) -> wasmtime::Result<Result<(), HostError>> {
+        let result =
+            self.permit("matmul-accumulate").and_then(|()| self.blocking(|state| state.matmul_accumulate(a, b, c)));
+        self.audit("matmul-accumulate", [a, b, c], [], None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn set_matrix_structure(&mut self, h: Handle, structure: MatrixStructure) -> wasmtime::Result<Result<(), HostError>> {
+        let result = self.permit("set-matrix-structure").and_then(|()| self.lock().set_matrix_structure(h, structure));
+        self.audit("set-matrix-structure", [h], [], None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn get_matrix_structure(&mut self, h: Handle) -> wasmtime::Result<Result<MatrixStructure, HostError>> {
+        let result = self.permit("get-matrix-structure").and_then(|()| self.lock().get_matrix_structure(h));
+        self.audit("get-matrix-structure", [h], [], None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn trsm_f32(&mut self, alpha: f32, a: Handle, b: Handle) -> wasmtime::Result<Result<(), HostError>> {
+        let result = self.permit("trsm-f32").and_then(|()| self.blocking(|state| state.trsm_f32(alpha, a, b)));
+        self.audit("trsm-f32", [a, b], [], None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn gbmv_f32(&mut self, alpha: f32, a: Handle, x: Handle, beta: f32, y: Handle) -> wasmtime::Result<Result<(), HostError>> {
+        let result = self.permit("gbmv-f32").and_then(|()| self.blocking(|state| state.gbmv_f32(alpha, a, x, beta, y)));
+        self.audit("gbmv-f32", [a, x, y], [], None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn symmetric_eigen(&mut self, h: Handle) -> wasmtime::Result<Result<EigenDecomposition, HostError>> {
+        let result = self.permit("symmetric-eigen").and_then(|()| self.blocking(|state| state.symmetric_eigen(h)));
+        self.audit("symmetric-eigen", [h], [], None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn kron(&mut self, a: Handle, b: Handle) -> wasmtime::Result<Result<Handle, HostError>> {
+        let result = self.permit("kron").and_then(|()| self.blocking(|state| state.kron(a, b)));
+        self.audit("kron", [a, b], result.iter().copied(), None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn outer(&mut self, x: Handle, y: Handle) -> wasmtime::Result<Result<Handle, HostError>> {
+        let result = self.permit("outer").and_then(|()| self.blocking(|state| state.outer(x, y)));
+        self.audit("outer", [x, y], result.iter().copied(), None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn einsum(&mut self, spec: String, inputs: Vec<Handle>) -> wasmtime::Result<Result<Handle, HostError>> {
+        let result = self.permit("einsum").and_then(|()| self.blocking(|state| state.einsum(&spec, &inputs)));
+        self.audit("einsum", inputs.iter().copied(), result.iter().copied(), None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn gather_rows(&mut self, h: Handle, rows: Vec<u32>) -> wasmtime::Result<Result<Handle, HostError>> {
+        let result = self.permit("gather-rows").and_then(|()| self.lock().gather_rows(h, &rows));
+        self.audit("gather-rows", [h], result.iter().copied(), None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn scatter_rows(&mut self, src: Handle, dst: Handle, rows: Vec<u32>) -> wasmtime::Result<Result<(), HostError>> {
+        let result = self.permit("scatter-rows").and_then(|()| self.lock().scatter_rows(src, dst, &rows));
+        self.audit("scatter-rows", [src, dst], [], None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn embedding_lookup(&mut self, table: Handle, ids: Vec<u32>, pooling: Pooling) -> wasmtime::Result<Result<Handle, HostError>> {
+        let result = self.permit("embedding-lookup").and_then(|()| self.lock().embedding_lookup(table, &ids, pooling));
+        self.audit("embedding-lookup", [table], result.iter().copied(), None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn concat(&mut self, inputs: Vec<Handle>, axis: ConcatAxis) -> wasmtime::Result<Result<Handle, HostError>> {
+        let result = self.permit("concat").and_then(|()| self.lock().concat(&inputs, axis));
+        self.audit("concat", inputs.iter().copied(), result.iter().copied(), None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn stack(&mut self, inputs: Vec<Handle>) -> wasmtime::Result<Result<Handle, HostError>> {
+        let result = self.permit("stack").and_then(|()| self.lock().stack(&inputs));
+        self.audit("stack", inputs.iter().copied(), result.iter().copied(), None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn cast(&mut self, h: Handle, target: ElementType, scale: f32) -> wasmtime::Result<Result<Handle, HostError>> {
+        let result = self.permit("cast").and_then(|()| self.blocking(|state| state.cast(h, target, scale)));
+        self.audit("cast", [h], result.iter().copied(), None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn compare(&mut self, a: Handle, b: Handle, op: CompareOp) -> wasmtime::Result<Result<Handle, HostError>> {
+        let result = self.permit("compare").and_then(|()| self.blocking(|state| state.compare(a, b, op)));
+        self.audit("compare", [a, b], result.iter().copied(), None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn where_(&mut self, cond: Handle, a: Handle, b: Handle) -> wasmtime::Result<Result<Handle, HostError>> {
+        let result = self.permit("where").and_then(|()| self.blocking(|state| state.where_(cond, a, b)));
+        self.audit("where", [cond, a, b], result.iter().copied(), None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn masked_fill(&mut self, h: Handle, mask: Handle, value: f64) -> wasmtime::Result<Result<(), HostError>> {
+        let result = self.permit("masked-fill").and_then(|()| self.blocking(|state| state.masked_fill(h, mask, value)));
+        self.audit("masked-fill", [h, mask], [], None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn top_k(&mut self, h: Handle, k: u32, axis: ConcatAxis) -> wasmtime::Result<Result<TopK, HostError>> {
+        let result = self.permit("top-k").and_then(|()| self.blocking(|state| state.top_k(h, k, axis)));
+        self.audit("top-k", [h], [], None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn reduce(
+        &mut self,
+        h: Handle,
+        op: ReduceOp,
+        axis: Option<ConcatAxis>,
+        summation: Summation,
+    ) -> wasmtime::Result<Result<Handle, HostError>> {
+        let result = self.permit("reduce").and_then(|()| self.blocking(|state| state.reduce(h, op, axis, summation)));
+        self.audit("reduce", [h], result.iter().copied(), None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn parallel_map(&mut self, op_name: String, h: Handle, chunks: u32) -> wasmtime::Result<Result<Handle, HostError>> {
+        // Not `blocking`, for the same reason as `call_op`.
+        let result = self
+            .permit("parallel-map")
+            .and_then(|()| self.permit(op_function(&op_name)))
+            .and_then(|()| off_runtime(|| mapreduce::parallel_map(&self.state, &op_name, h, chunks)));
+        self.audit("parallel-map", [h], result.iter().copied(), None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn sdpa(&mut self, q: Handle, k: Handle, v: Handle, mask: Option<Handle>, scale: f32) -> wasmtime::Result<Result<Handle, HostError>> {
+        let result = self.permit("sdpa").and_then(|()| self.blocking(|state| state.sdpa(q, k, v, mask, scale)));
+        self.audit("sdpa", [q, k, v].into_iter().chain(mask), result.iter().copied(), None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn create_kv_cache(&mut self, layers: u32, heads: u32, max_seq: u32, head_dim: u32) -> wasmtime::Result<Result<Handle, HostError>> {
+        let result =
+            self.permit("create-kv-cache").and_then(|()| self.lock().create_kv_cache(layers, heads, max_seq, head_dim));
+        self.audit("create-kv-cache", [], result.iter().copied(), None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn append_kv(&mut self, cache: Handle, layer: u32, k: Handle, v: Handle) -> wasmtime::Result<Result<(), HostError>> {
+        let result = self.permit("append-kv").and_then(|()| self.lock().append_kv(cache, layer, k, v));
+        self.audit("append-kv", [cache, k, v], [], None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn kv_cache_length(&mut self, cache: Handle, layer: u32) -> wasmtime::Result<Result<u32, HostError>> {
+        let result = self.permit("kv-cache-length").and_then(|()| self.lock().kv_cache_length(cache, layer));
+        self.audit("kv-cache-length", [cache], [], None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn sdpa_cached(&mut self, q: Handle, cache: Handle, layer: u32, mask: Option<Handle>, scale: f32) -> wasmtime::Result<Result<Handle, HostError>> {
+        let result = self
+            .permit("sdpa-cached")
+            .and_then(|()| self.blocking(|state| state.sdpa_cached(q, cache, layer, mask, scale)));
+        self.audit("sdpa-cached", [q, cache].into_iter().chain(mask), result.iter().copied(), None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn resample(&mut self, h: Handle, from_rate: u32, to_rate: u32) -> wasmtime::Result<Result<Handle, HostError>> {
+        let result =
+            self.permit("resample").and_then(|()| self.blocking(|state| state.resample(h, from_rate, to_rate)));
+        self.audit("resample", [h], result.iter().copied(), None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn mel_spectrogram(&mut self, h: Handle, params: MelParams) -> wasmtime::Result<Result<Handle, HostError>> {
+        let result =
+            self.permit("mel-spectrogram").and_then(|()| self.blocking(|state| state.mel_spectrogram(h, params)));
+        self.audit("mel-spectrogram", [h], result.iter().copied(), None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn dump_matrix(&mut self, h: Handle, format: DumpFormat, destination: DumpDestination) -> wasmtime::Result<Result<(), HostError>> {
+        let result = self
+            .permit("dump-matrix")
+            .and_then(|()| self.policy.permit_dump(&destination))
+            .and_then(|()| self.blocking(|state| state.dump_matrix(h, format, destination)));
+        self.audit("dump-matrix", [h], [], None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn hash_buffer(&mut self, h: Handle, algo: HashAlgorithm) -> wasmtime::Result<Result<Vec<u8>, HostError>> {
+        let result = self.permit("hash-buffer").and_then(|()| self.blocking(|state| state.hash_buffer(h, algo)));
+        self.audit("hash-buffer", [h], [], None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn intern_buffer(&mut self, hash: Vec<u8>) -> wasmtime::Result<Result<Option<Handle>, HostError>> {
+        let result = self.permit("intern-buffer").and_then(|()| self.lock().intern_buffer(&hash));
+        self.audit("intern-buffer", [], result.iter().flatten().copied(), None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn publish_interned(&mut self, h: Handle, hash: Vec<u8>) -> wasmtime::Result<Result<(), HostError>> {
+        // Hashes the whole buffer.
+        let result =
+            self.permit("publish-interned").and_then(|()| self.blocking(|state| state.publish_interned(h, &hash)));
+        self.audit("publish-interned", [h], [], None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn begin_arena(&mut self) -> wasmtime::Result<ArenaId> {
+        self.permit_or_trap("begin-arena")?;
+        self.audit("begin-arena", [], [], None, None);
+        Ok(self.lock().begin_arena())
+    }
+
+    fn end_arena(&mut self, arena: ArenaId) -> wasmtime::Result<Result<(), HostError>> {
+        let result = self.permit("end-arena").and_then(|()| self.lock().end_arena(arena));
+        self.audit("end-arena", [], [], None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn begin_transaction(&mut self) -> wasmtime::Result<Result<(), HostError>> {
+        let result = self.permit("begin-transaction").and_then(|()| self.lock().begin_transaction());
+        self.audit("begin-transaction", [], [], None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn commit(&mut self) -> wasmtime::Result<Result<(), HostError>> {
+        let result = self.permit("commit").and_then(|()| self.lock().commit());
+        self.audit("commit", [], [], None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn rollback(&mut self) -> wasmtime::Result<Result<(), HostError>> {
+        let result = self.permit("rollback").and_then(|()| self.lock().rollback());
+        self.audit("rollback", [], [], None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn set_placement(&mut self, h: Handle, target: Device) -> wasmtime::Result<Result<(), HostError>> {
+        let result = self.permit("set-placement").and_then(|()| self.lock().set_placement(h, target));
+        self.audit("set-placement", [h], [], None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn prefetch(&mut self, h: Handle, target: Device) -> wasmtime::Result<Result<(), HostError>> {
+        let result = self.permit("prefetch").and_then(|()| self.lock().prefetch(h, target));
+        self.audit("prefetch", [h], [], None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn describe_handle(&mut self, h: Handle) -> wasmtime::Result<Result<HandleInfo, HostError>> {
+        let result = self.permit("describe-handle").and_then(|()| self.lock().describe_handle(h));
+        self.audit("describe-handle", [h], [], None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn execute_graph(&mut self, g: Graph) -> wasmtime::Result<Result<Vec<Handle>, HostError>> {
+        let result = self.permit("execute-graph").and_then(|()| self.blocking(|state| state.execute_graph(&g)));
+        self.audit("execute-graph", [], result.iter().flatten().copied(), None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn set_evaluation_mode(&mut self, mode: EvaluationMode) -> wasmtime::Result<()> {
+        self.permit_or_trap("set-evaluation-mode")?;
+        self.audit("set-evaluation-mode", [], [], None, None);
+        self.lock().set_evaluation_mode(mode);
+        Ok(())
+    }
+
+    fn set_dry_run(&mut self, enabled: bool) -> wasmtime::Result<()> {
+        self.permit_or_trap("set-dry-run")?;
+        self.audit("set-dry-run", [], [], None, None);
+        self.lock().set_dry_run(enabled);
+        Ok(())
+    }
+
+    fn materialize(&mut self, h: Handle) -> wasmtime::Result<Result<(), HostError>> {
+        let result = self.permit("materialize").and_then(|()| self.blocking(|state| state.materialize_handle(h)));
+        self.audit("materialize", [h], [], None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn is_materialized(&mut self, h: Handle) -> wasmtime::Result<Result<bool, HostError>> {
+        let result = self.permit("is-materialized").and_then(|()| self.lock().is_materialized(h));
+        self.audit("is-materialized", [h], [], None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn submit_matmul_f32(&mut self, handle_a: Handle, handle_b: Handle) -> wasmtime::Result<Result<JobId, HostError>> {
+        let result = self.permit("submit-matmul-f32").and_then(|()| self.lock().submit_matmul_f32(handle_a, handle_b));
+        if let Ok(job) = result {
+            self.run_job(job);
+        }
+        self.audit("submit-matmul-f32", [handle_a, handle_b], [], None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn job_status(&mut self, job: JobId) -> wasmtime::Result<Result<JobState, HostError>> {
+        let result = self.permit("job-status").and_then(|()| self.lock().job_status(job));
+        self.audit("job-status", [], [], None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn poll_job(&mut self, job: JobId) -> wasmtime::Result<Result<JobProgress, HostError>> {
+        let result = self.permit("poll-job").and_then(|()| self.blocking(|state| state.poll_job(job)));
+        self.audit("poll-job", [], [], None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn wait_job(&mut self, job: JobId) -> wasmtime::Result<Result<Handle, HostError>> {
+        let result = self.permit("wait-job").and_then(|()| self.blocking(|state| state.wait_job(job)));
+        self.audit("wait-job", [], result.iter().copied(), None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn list_devices(&mut self) -> wasmtime::Result<Vec<DeviceInfo>> {
+        self.permit_or_trap("list-devices")?;
+        self.audit("list-devices", [], [], None, None);
+        Ok(self.lock().list_devices())
+    }
+
+    fn estimate_cost(&mut self, op: String, shapes: Vec<(u32, u32)>) -> wasmtime::Result<Result<CostEstimate, HostError>> {
+        // The first estimate calibrates, which takes a few milliseconds.
+        let result =
+            self.permit("estimate-cost").and_then(|()| self.blocking(|state| state.estimate_cost(&op, &shapes)));
+        self.audit("estimate-cost", [], [], None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn get_backend_stats(&mut self) -> wasmtime::Result<Vec<BackendChoice>> {
+        self.permit_or_trap("get-backend-stats")?;
+        self.audit("get-backend-stats", [], [], None, None);
+        Ok(self.lock().get_backend_stats())
+    }
+
+    fn get_memory_stats(&mut self) -> wasmtime::Result<MemoryStats> {
+        self.permit_or_trap("get-memory-stats")?;
+        self.audit("get-memory-stats", [], [], None, None);
+        Ok(self.lock().get_memory_stats())
+    }
+
+    fn pin_buffer(&mut self, h: Handle) -> wasmtime::Result<Result<(), HostError>> {
+        let result = self.permit("pin-buffer").and_then(|()| self.lock().pin_buffer(h));
+        self.audit("pin-buffer", [h], [], None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn unpin_buffer(&mut self, h: Handle) -> wasmtime::Result<Result<(), HostError>> {
+        let result = self.permit("unpin-buffer").and_then(|()| self.lock().unpin_buffer(h));
+        self.audit("unpin-buffer", [h], [], None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn allocate_buffer_with_flags(&mut self, size: u64, options: BufferFlags) -> wasmtime::Result<Result<Handle, HostError>> {
+        let result = self
+            .permit("allocate-buffer-with-flags")
+            .and_then(|()| self.lock().allocate_buffer_with_flags(size, options));
+        self.audit("allocate-buffer-with-flags", [], result.iter().copied(), Some(size), result.as_ref().err());
+        Ok(result)
+    }
+
+    fn mark_sensitive(&mut self, h: Handle) -> wasmtime::Result<Result<(), HostError>> {
+        let result = self.permit("mark-sensitive").and_then(|()| self.lock().mark_sensitive(h));
+        self.audit("mark-sensitive", [h], [], None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn list_ops(&mut self) -> wasmtime::Result<Vec<String>> {
+        self.permit_or_trap("list-ops")?;
+        self.audit("list-ops", [], [], None, None);
+        Ok(self.lock().list_ops())
+    }
+
+    fn describe_op(&mut self, op_name: String) -> wasmtime::Result<Result<OpSchema, HostError>> {
+        let result = self.permit("describe-op").and_then(|()| self.lock().describe_op(&op_name));
+        self.audit("describe-op", [], [], None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn call_op(&mut self, op_name: String, inputs: Vec<Handle>) -> wasmtime::Result<Result<Vec<Handle>, HostError>> {
+        // Not `blocking`: an extension op calls back into this host, so the
+        // lock must be free while it runs.
+        let result = self
+            .permit("call-op")
+            .and_then(|()| self.permit(op_function(&op_name)))
+            .and_then(|()| off_runtime(|| registry::call_op(&self.state, &op_name, &inputs, false)));
+        self.audit("call-op", inputs.iter().copied(), result.iter().flatten().copied(), None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn list_extensions(&mut self) -> wasmtime::Result<Vec<String>> {
+        self.permit_or_trap("list-extensions")?;
+        self.audit("list-extensions", [], [], None, None);
+        Ok(self.lock().list_extensions())
+    }
+
+    fn call_extension(&mut self, op_name: String, inputs: Vec<Handle>) -> wasmtime::Result<Result<Vec<Handle>, HostError>> {
+        let result = self
+            .permit("call-extension")
+            .and_then(|()| self.permit(op_function(&op_name)))
+            .and_then(|()| off_runtime(|| registry::call_op(&self.state, &op_name, &inputs, true)));
+        self.audit("call-extension", inputs.iter().copied(), result.iter().flatten().copied(), None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn execute_batch(&mut self, ops: Vec<OpDescriptor>) -> wasmtime::Result<Vec<OpResult>> {
+        self.permit_or_trap("execute-batch")?;
+        self.audit("execute-batch", [], [], None, None);
+        // The batch runs up to its first op the policy denies, which then
+        // fails just as a failing op would end the batch.
+        let mut ops = ops;
+        let denied = ops.iter().enumerate().find_map(|(index, op)| Some((index, self.permit(batch_function(op)).err()?)));
+        if let Some((index, _)) = denied {
+            ops.truncate(index);
+        }
+        let mut results = self.blocking(|state| state.execute_batch(ops));
+        if let Some((_, e)) = denied {
+            if !results.iter().any(|result| matches!(result, OpResult::Failed(_))) {
+                results.push(OpResult::Failed(e));
+            }
+        }
+        Ok(results)
+    }
+
+    fn get_interface_version(&mut self) -> wasmtime::Result<InterfaceVersion> {
+        self.permit_or_trap("get-interface-version")?;
+        self.audit("get-interface-version", [], [], None, None);
+        Ok(self.lock().get_interface_version())
+    }
+
+    fn supports(&mut self, feature: String) -> wasmtime::Result<bool> {
+        self.permit_or_trap("supports")?;
+        self.audit("supports", [], [], None, None);
+        // Only the native host offers `buffer-streams` and `random`.
+        Ok(matches!(feature.as_str(), "streams" | "random") || self.lock().supports(&feature))
+    }
+}
+
+impl random::Host for OffloadHost {
+    fn fill_random_bytes(&mut self, h: Handle, offset: u64, len: u64) -> wasmtime::Result<Result<(), HostError>> {
+        let result = self
+            .permit("random.fill-random-bytes")
+            .and_then(|()| self.blocking(|state| state.fill_random_bytes(h, offset, len)));
+        self.audit("random.fill-random-bytes", [h], [], Some(len), result.as_ref().err());
+        Ok(result)
+    }
+
+    fn create_rng(&mut self, seed: u64) -> wasmtime::Result<Result<RngId, HostError>> {
+        let result = self.permit("random.create-rng").and_then(|()| self.lock().create_rng(seed));
+        self.audit("random.create-rng", [], [], None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn drop_rng(&mut self, rng: RngId) -> wasmtime::Result<Result<(), HostError>> {
+        let result = self.permit("random.drop-rng").and_then(|()| self.lock().drop_rng(rng));
+        self.audit("random.drop-rng", [], [], None, result.as_ref().err());
+        Ok(result)
+    }
+
+    fn rng_fill(&mut self, rng: RngId, h: Handle, dist: Distribution) -> wasmtime::Result<Result<(), HostError>> {
+        let result = self.permit("random.rng-fill").and_then(|()| self.blocking(|state| state.rng_fill(rng, h, dist)));
+        self.audit("random.rng-fill", [h], [], None, result.as_ref().err());
+        Ok(result)
+    }
+}
@@ -0,0 +1,244 @@
+use std::mem::MaybeUninit;
+use std::thread::ScopedJoinHandle;
+use std::time::Instant;
+
+use nalgebra::DMatrix;
+
+use crate::kernels;
+use crate::state::matrix_to_bytes;
+use crate::wasi_custom::host_offload::host_allocator::{ComputeHint, ComputeMode, HostError, MatrixLayout};
+use crate::HostState;
+
+pub(crate) const BACKEND_NAME: &str = "numa-cpu";
+
+// Settings for the NUMA worker pool backend (native builds only).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NumaConfig {
+    // Workers per NUMA node; `None` uses every CPU of the node.
+    pub threads_per_node: Option<usize>,
+    // Smallest multiply (in multiply-adds) worth spreading over the pool;
+    // `None` uses `kernels::STEP_FLOPS`.
+    pub min_flops: Option<usize>,
+}
+
+// Splits large multiplies across worker threads pinned to each NUMA node.
+//
+// The result is cut along its contiguous dimension (rows for row-major,
+// columns for column-major), one contiguous range per node. Each node takes
+// its own copy of the operand every worker needs in full, so workers only ever
+// read node-local memory, and workers write their slice of the result buffer
+// themselves, so its pages are first touched, and therefore placed, on the
+// node that computed them. Workers are spawned per multiply so they can write
+// straight into the result instead of handing rows back.
+pub(crate) struct NumaPool {
+    // CPUs of each node, by node id.
+    nodes: Vec<Vec<usize>>,
+    threads_per_node: Option<usize>,
+    min_flops: usize,
+}
+
+impl NumaPool {
+    pub fn new(config: NumaConfig) -> Self {
+        let nodes = topology();
+        log!(Info, "NUMA backend: {} nodes ({:?} CPUs each)", nodes.len(), nodes.iter().map(Vec::len).collect::<Vec<_>>());
+        NumaPool {
+            nodes,
+            threads_per_node: config.threads_per_node.map(|n| n.max(1)),
+            min_flops: config.min_flops.unwrap_or(kernels::STEP_FLOPS),
+        }
+    }
+
+    // Small multiplies cost more in threads than they save.
+    pub fn worth_it(&self, a: &DMatrix<f32>, b: &DMatrix<f32>) -> bool {
+        a.nrows() * a.ncols() * b.ncols() >= self.min_flops
+    }
+
+    // `a * b` encoded in `layout`, and how many of its bytes landed on each node.
+    pub fn matmul_f32(
+        &self,
+        a: &DMatrix<f32>,
+        b: &DMatrix<f32>,
+        layout: MatrixLayout,
+        mode: ComputeMode,
+        hint: ComputeHint,
+        deadline: Option<Instant>,
+    ) -> Result<(Vec<u8>, Vec<u64>), HostError> {
+        let (lines, line_len) = match layout {
+            MatrixLayout::RowMajor => (a.nrows(), b.ncols()),
+            MatrixLayout::ColumnMajor => (b.ncols(), a.nrows()),
+        };
+        let line_bytes = line_len * std::mem::size_of::<f32>();
+        let tile = (kernels::STEP_FLOPS / (a.ncols() * line_len).max(1)).max(1);
+
+        let mut out = Vec::<u8>::with_capacity(lines * line_bytes);
+        let mut rest = &mut out.spare_capacity_mut()[..lines * line_bytes];
+        let mut per_node = Vec::with_capacity(self.nodes.len());
+        let mut work = Vec::new();
+        for (node, cpus) in self.nodes.iter().enumerate() {
+            let range = split(lines, self.nodes.len(), node);
+            per_node.push((range.len() * line_bytes) as u64);
+            // Low power still spreads over the nodes, for their local memory.
+            let threads = match hint {
+                ComputeHint::LowPower => 1,
+                _ => self.threads_per_node.unwrap_or(cpus.len()).max(1),
+            };
+            let mut workers = Vec::new();
+            for worker in 0..threads {
+                let sub = split(range.len(), threads, worker);
+                let (slice, tail) = std::mem::take(&mut rest).split_at_mut(sub.len() * line_bytes);
+                rest = tail;
+                workers.push((range.start + sub.start..range.start + sub.end, slice));
+            }
+            work.push((cpus, workers));
+        }
+
+        std::thread::scope(|scope| {
+            let nodes: Vec<_> = work
+                .into_iter()
+                .map(|(cpus, workers)| {
+                    worker_thread().spawn_scoped(scope, move || {
+                        pin_current_thread(cpus);
+                        // This node's own copy of the operand every line needs in full.
+                        let shared = match layout {
+                            MatrixLayout::RowMajor => b.clone(),
+                            MatrixLayout::ColumnMajor => a.clone(),
+                        };
+                        let shared = &shared;
+                        std::thread::scope(|scope| {
+                            let handles: Vec<_> = workers
+                                .into_iter()
+                                .map(|(range, slice)| {
+                                    worker_thread().spawn_scoped(scope, move || {
+                                        pin_current_thread(cpus);
+                                        compute_lines(a, b, shared, layout, mode, deadline, range, tile, slice)
+                                    })
+                                })
+                                .collect();
+                            join_all(handles)
+                        })
+                    })
+                })
+                .collect();
+            join_all(nodes)
+        })?;
+        // SAFETY: the workers initialized every byte of the first `lines * line_bytes`.
+        unsafe { out.set_len(lines * line_bytes) };
+        Ok((out, per_node))
+    }
+}
+
+// Pool threads share a name so they can be told apart in `/proc` or a debugger.
+pub const THREAD_NAME: &str = "offload-numa";
+
+fn worker_thread() -> std::thread::Builder {
+    std::thread::Builder::new().name(THREAD_NAME.to_string())
+}
+
+// Waits for every thread that was spawned, then reports the first failure:
+// a thread that couldn't be spawned, one that panicked, or its own error.
+fn join_all(
+    handles: Vec<std::io::Result<ScopedJoinHandle<'_, Result<(), HostError>>>>,
+) -> Result<(), HostError> {
+    handles.into_iter().fold(Ok(()), |result, handle| {
+        let joined = match handle {
+            Ok(handle) => handle
+                .join()
+                .unwrap_or_else(|_| Err(HostError::ComputationError("A NUMA worker panicked".to_string()))),
+            Err(e) => Err(HostError::ComputationError(format!("Failed to spawn a NUMA worker: {}", e))),
+        };
+        result.and(joined)
+    })
+}
+
+// Computes result lines `range` tile by tile into `out`, which holds exactly those lines.
+#[allow(clippy::too_many_arguments)]
+fn compute_lines(
+    a: &DMatrix<f32>,
+    b: &DMatrix<f32>,
+    shared: &DMatrix<f32>,
+    layout: MatrixLayout,
+    mode: ComputeMode,
+    deadline: Option<Instant>,
+    range: std::ops::Range<usize>,
+    tile: usize,
+    out: &mut [MaybeUninit<u8>],
+) -> Result<(), HostError> {
+    let mut written = 0;
+    let mut start = range.start;
+    while start < range.end {
+        if deadline.is_some_and(|d| Instant::now() > d) {
+            return Err(HostError::Timeout);
+        }
+        let count = tile.min(range.end - start);
+        let block = match layout {
+            MatrixLayout::RowMajor => kernels::matmul_f32(&a.rows(start, count).into_owned(), shared, mode),
+            MatrixLayout::ColumnMajor => kernels::matmul_f32(shared, &b.columns(start, count).into_owned(), mode),
+        };
+        let bytes = matrix_to_bytes(&block, layout);
+        for (dst, &src) in out[written..written + bytes.len()].iter_mut().zip(&bytes) {
+            dst.write(src);
+        }
+        written += bytes.len();
+        start += count;
+    }
+    Ok(())
+}
+
+// Part `i` of `0..len` cut into `parts` near-equal contiguous ranges.
+fn split(len: usize, parts: usize, i: usize) -> std::ops::Range<usize> {
+    len * i / parts..len * (i + 1) / parts
+}
+
+// CPUs per NUMA node as the kernel reports them, or one node holding every
+// CPU where it doesn't (non-Linux, or no NUMA support).
+fn topology() -> Vec<Vec<usize>> {
+    let mut nodes = Vec::new();
+    while let Ok(list) = std::fs::read_to_string(format!("/sys/devices/system/node/node{}/cpulist", nodes.len())) {
+        nodes.push(parse_cpu_list(list.trim()));
+    }
+    nodes.retain(|cpus: &Vec<usize>| !cpus.is_empty());
+    if nodes.is_empty() {
+        let cpus = std::thread::available_parallelism().map_or(1, |n| n.get());
+        nodes.push((0..cpus).collect());
+    }
+    nodes
+}
+
+// "0-3,8,10-11" -> [0, 1, 2, 3, 8, 10, 11]
+fn parse_cpu_list(list: &str) -> Vec<usize> {
+    list.split(',')
+        .filter(|part| !part.is_empty())
+        .flat_map(|part| {
+            let (lo, hi) = part.split_once('-').unwrap_or((part, part));
+            match (lo.parse::<usize>(), hi.parse::<usize>()) {
+                (Ok(lo), Ok(hi)) => lo..hi + 1,
+                _ => 0..0,
+            }
+        })
+        .collect()
+}
+
+#[cfg(target_os = "linux")]
+fn pin_current_thread(cpus: &[usize]) {
+    // SAFETY: `set` is a plain bit mask, fully initialized by CPU_ZERO.
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_ZERO(&mut set);
+        for &cpu in cpus.iter().filter(|&&cpu| cpu < libc::CPU_SETSIZE as usize) {
+            libc::CPU_SET(cpu, &mut set);
+        }
+        // Best effort: an unpinned worker is only slower.
+        libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set);
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn pin_current_thread(_cpus: &[usize]) {}
+
+impl HostState {
+    // Embedder knob (not part of the WIT interface): compute large multiplies
+    // on the NUMA worker pool, or on the calling thread again with `None`.
+    pub fn set_numa_backend(&mut self, config: Option<NumaConfig>) {
+        self.numa = config.map(NumaPool::new);
+    }
+}
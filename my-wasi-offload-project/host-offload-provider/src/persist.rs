@@ -0,0 +1,67 @@
+use std::collections::HashSet;
+
+use crate::storage::Buffer;
+use crate::wasi_custom::host_offload::host_allocator::{ElementType, Handle, HostError, MatrixShape, MatrixStructure};
+use crate::HostState;
+
+// A buffer moved out of one `HostState`, with the metadata that describes
+// its contents, waiting to be adopted by another (see `handle-persistence`).
+pub struct DetachedBuffer {
+    buffer: Buffer,
+    dims: Option<MatrixShape>,
+    element_type: Option<ElementType>,
+    structure: Option<MatrixStructure>,
+}
+
+impl HostState {
+    // Moves `handles` out, all or none; they're unknown here afterwards, as if
+    // freed. Pending results are computed and spilled buffers read back first.
+    pub fn detach(&mut self, handles: &[Handle]) -> Result<Vec<DetachedBuffer>, HostError> {
+        log!(Debug, "Detaching handles {:?}", handles);
+        self.charge(0)?;
+        if self.transaction.is_some() {
+            return Err(HostError::InvalidArguments("Handles can't be exported during a transaction".to_string()));
+        }
+        let mut seen = HashSet::new();
+        if let Some(&h) = handles.iter().find(|&&h| !seen.insert(h)) {
+            return Err(HostError::InvalidArguments(format!("Handle {} is listed twice", h)));
+        }
+        for &h in handles {
+            self.refuse_sensitive(h, "exported")?;
+            self.materialize(h)?;
+            self.materialize_dependents(h)?;
+            if !self.buffers.contains_key(&h) {
+                return Err(self.missing(h));
+            }
+        }
+        Ok(handles.iter().map(|&h| {
+            let detached = DetachedBuffer {
+                buffer: self.buffers.remove(&h).unwrap(),
+                dims: self.matrix_dims.get(&h).copied(),
+                element_type: self.element_types.get(&h).copied(),
+                structure: self.structures.get(&h).copied(),
+            };
+            self.release(h);
+            detached
+        }).collect())
+    }
+
+    // Fresh handles for `buffers`, in order.
+    pub fn adopt(&mut self, buffers: Vec<DetachedBuffer>) -> Vec<Handle> {
+        log!(Debug, "Adopting {} detached buffers", buffers.len());
+        buffers.into_iter().map(|detached| {
+            let h = self.new_handle();
+            self.buffers.insert(h, detached.buffer);
+            if let Some(dims) = detached.dims {
+                self.matrix_dims.insert(h, dims);
+            }
+            if let Some(elem) = detached.element_type {
+                self.element_types.insert(h, elem);
+            }
+            if let Some(structure) = detached.structure {
+                self.structures.insert(h, structure);
+            }
+            h
+        }).collect()
+    }
+}
@@ -0,0 +1,89 @@
+use std::collections::BTreeMap;
+
+use crate::wasi_custom::host_offload::host_allocator::{DumpDestination, HostError, OpDescriptor, StorageKind};
+
+// What a host's guest may not do, set by the embedder (the runner builds one
+// per client from its config). Calls it rules out fail with
+// `permission-denied` naming the rule, before they reach the provider state.
+#[derive(Clone, Debug, Default)]
+pub struct Policy {
+    // Denied functions, by WIT name, and the rule that denied each. Those of
+    // `host-allocator` go by their bare name, those of `random` and
+    // `tokenizer` as `interface.function`, or as the bare interface name for
+    // all of them.
+    denied: BTreeMap<String, String>,
+    // See `HostState::set_max_matmul_dim`.
+    pub max_matmul_dim: Option<u32>,
+    // Refuse `shared-memory` buffers, which are files under /dev/shm,
+    // `dump-matrix` to a file, and `load-tokenizer`.
+    pub deny_files: bool,
+}
+
+impl Policy {
+    // Denies `function`; `rule` is what the error quotes, e.g. the profile
+    // name the function was denied through.
+    pub fn deny(&mut self, function: impl Into<String>, rule: impl Into<String>) {
+        self.denied.insert(function.into(), rule.into());
+    }
+
+    pub(crate) fn permit(&self, function: &str) -> Result<(), HostError> {
+        let interface = function.split_once('.').map(|(interface, _)| interface);
+        match self.denied.get(function).or_else(|| self.denied.get(interface?)) {
+            Some(rule) => Err(HostError::PermissionDenied(format!("{} is denied by deny = \"{}\"", function, rule))),
+            None => Ok(()),
+        }
+    }
+
+    pub(crate) fn permit_storage(&self, hint: StorageKind) -> Result<(), HostError> {
+        match (self.deny_files, hint) {
+            (true, StorageKind::SharedMemory) => Err(files_denied("Shared-memory buffers")),
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn permit_dump(&self, destination: &DumpDestination) -> Result<(), HostError> {
+        match (self.deny_files, destination) {
+            (true, DumpDestination::File(_)) => Err(files_denied("Dumps to files")),
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn permit_tokenizer_files(&self) -> Result<(), HostError> {
+        match self.deny_files {
+            true => Err(files_denied("Tokenizer files")),
+            false => Ok(()),
+        }
+    }
+}
+
+fn files_denied(what: &str) -> HostError {
+    HostError::PermissionDenied(format!("{} are denied by files = false", what))
+}
+
+// The function each `execute-batch` op stands for, so a batch can't do what
+// calling it directly would be denied.
+pub(crate) fn batch_function(op: &OpDescriptor) -> &'static str {
+    match op {
+        OpDescriptor::Allocate(_) => "allocate-buffer",
+        OpDescriptor::Free(_) => "free-buffer",
+        OpDescriptor::Write(_) => "write-to-host",
+        OpDescriptor::Read(_) => "read-from-host",
+        OpDescriptor::WriteF32(_) => "write-f32",
+        OpDescriptor::ReadF32(_) => "read-f32",
+        OpDescriptor::RegisterMatrixShape(_) => "register-matrix-shape",
+        OpDescriptor::MatrixMultiplyF32(_) => "matrix-multiply-f32",
+        OpDescriptor::MatmulAccumulate(_) => "matmul-accumulate",
+        OpDescriptor::AxpyF32(_) => "axpy-f32",
+        OpDescriptor::ScalF32(_) => "scal-f32",
+    }
+}
+
+// The function each registry op stands for, for `call-op`, `call-extension`
+// and `parallel-map`. Embedder extensions stand for `call-extension` itself.
+pub(crate) fn op_function(name: &str) -> &'static str {
+    match name {
+        "matmul-f32" | "matmul-f64" => "matrix-multiply-f32",
+        "add-f32" | "relu-f32" => "execute-graph",
+        _ => "call-extension",
+    }
+}
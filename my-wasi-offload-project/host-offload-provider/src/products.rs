@@ -0,0 +1,39 @@
+use nalgebra::DVector;
+
+use crate::wasi_custom::host_offload::host_allocator::{AllocationLimit, Handle, HostError, MatrixLayout};
+use crate::HostState;
+
+impl HostState {
+    pub fn kron(&mut self, a: Handle, b: Handle) -> Result<Handle, HostError> {
+        log!(Debug, "Kronecker product of {} and {}", a, b);
+        self.charge(0)?;
+        self.materialize(a)?;
+        self.materialize(b)?;
+        let (dims_a, matrix_a) = self.read_matrix_f32(a)?;
+        let (_, matrix_b) = self.read_matrix_f32(b)?;
+        self.check_result_size(matrix_a.len() as u64 * matrix_b.len() as u64)?;
+        let c = matrix_a.kronecker(&matrix_b);
+        Ok(self.store_matrix_f32(&c, dims_a.layout))
+    }
+
+    pub fn outer(&mut self, x: Handle, y: Handle) -> Result<Handle, HostError> {
+        log!(Debug, "Outer product of {} and {}", x, y);
+        self.charge(0)?;
+        self.materialize(x)?;
+        self.materialize(y)?;
+        let x = DVector::from_vec(self.read_f32(x)?);
+        let y = DVector::from_vec(self.read_f32(y)?);
+        self.check_result_size(x.len() as u64 * y.len() as u64)?;
+        Ok(self.store_matrix_f32(&(x * y.transpose()), MatrixLayout::RowMajor))
+    }
+
+    // Results much larger than their inputs are held to the same cap as an
+    // allocation the guest asked for.
+    pub(crate) fn check_result_size(&self, elements: u64) -> Result<(), HostError> {
+        let size = elements.saturating_mul(4);
+        if size > self.max_allocation {
+            return Err(HostError::AllocationTooLarge(AllocationLimit { requested: size, max: self.max_allocation }));
+        }
+        Ok(())
+    }
+}
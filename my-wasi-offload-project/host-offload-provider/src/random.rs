@@ -0,0 +1,85 @@
+use rand::rngs::OsRng;
+use rand::{Rng, RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use rand_distr::Normal;
+
+use crate::state::{byte_range, element_size};
+use crate::wasi_custom::host_offload::host_allocator::{ElementType, Handle, HostError};
+use crate::wasi_custom::host_offload::random::{Distribution, RngId};
+use crate::HostState;
+
+fn invalid(message: impl Into<String>) -> HostError {
+    HostError::InvalidArguments(message.into())
+}
+
+impl HostState {
+    pub fn fill_random_bytes(&mut self, h: Handle, offset: u64, len: u64) -> Result<(), HostError> {
+        log!(Debug, "Filling {} bytes of {} at offset {} from the OS generator", len, h, offset);
+        self.materialize(h)?;
+        let (_, end) = byte_range(offset, len)?;
+        if end as u64 > self.buffer_len(h)? {
+            return Err(HostError::CopyOutOfBounds);
+        }
+        let mut bytes = vec![0u8; len as usize];
+        OsRng.try_fill_bytes(&mut bytes).map_err(|e| HostError::Other(format!("OS random generator failed: {}", e)))?;
+        self.write_to_host(&bytes, h, offset)
+    }
+
+    pub fn create_rng(&mut self, seed: u64) -> Result<RngId, HostError> {
+        log!(Debug, "Creating generator with seed {}", seed);
+        self.charge(0)?;
+        let id = self.next_rng;
+        self.next_rng += 1;
+        self.rngs.insert(id, ChaCha20Rng::seed_from_u64(seed));
+        Ok(id)
+    }
+
+    pub fn drop_rng(&mut self, rng: RngId) -> Result<(), HostError> {
+        self.charge(0)?;
+        self.rngs.remove(&rng).map(drop).ok_or_else(|| invalid(format!("No generator {}", rng)))
+    }
+
+    pub fn rng_fill(&mut self, rng: RngId, h: Handle, dist: Distribution) -> Result<(), HostError> {
+        log!(Debug, "Filling {} from generator {} ({:?})", h, rng, dist);
+        self.charge(0)?;
+        self.materialize(h)?;
+        let elem = match dist {
+            Distribution::Bytes => ElementType::U8,
+            Distribution::UniformF32(_) | Distribution::NormalF32(_) => ElementType::F32,
+            Distribution::UniformS32(_) => ElementType::S32,
+        };
+        if !matches!(dist, Distribution::Bytes) && self.element_types.get(&h).is_some_and(|&e| e != elem) {
+            return Err(HostError::TypeMismatch);
+        }
+        let len = self.buffer_len(h)? as usize;
+        if len % element_size(elem) != 0 {
+            return Err(HostError::Misaligned);
+        }
+        let count = len / element_size(elem);
+        let generator = self.rngs.get_mut(&rng).ok_or_else(|| invalid(format!("No generator {}", rng)))?;
+        let bytes = match dist {
+            Distribution::Bytes => {
+                let mut bytes = vec![0u8; len];
+                generator.fill_bytes(&mut bytes);
+                bytes
+            }
+            Distribution::UniformF32(range) => {
+                if range.high <= range.low || !(range.high - range.low).is_finite() {
+                    return Err(invalid(format!("Empty or unbounded range [{}, {})", range.low, range.high)));
+                }
+                (0..count).flat_map(|_| generator.gen_range(range.low..range.high).to_le_bytes()).collect()
+            }
+            Distribution::NormalF32(params) => {
+                let normal = Normal::new(params.mean, params.std_dev).map_err(|e| invalid(e.to_string()))?;
+                (0..count).flat_map(|_| generator.sample(normal).to_le_bytes()).collect()
+            }
+            Distribution::UniformS32(range) => {
+                if range.low >= range.high {
+                    return Err(invalid(format!("Empty range [{}, {})", range.low, range.high)));
+                }
+                (0..count).flat_map(|_| generator.gen_range(range.low..range.high).to_le_bytes()).collect()
+            }
+        };
+        self.write_to_host(&bytes, h, 0)
+    }
+}
@@ -0,0 +1,200 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, Weak};
+
+use crate::extensions::Extension;
+use crate::wasi_custom::host_offload::host_allocator::{
+    ArgSchema, ArgShape, ElementType, Graph, GraphInput, GraphOp, Handle, HostError, OpSchema,
+};
+use crate::HostState;
+
+type Builtin = fn(&mut HostState, &[Handle]) -> Result<Vec<Handle>, HostError>;
+
+pub(crate) enum OpImpl {
+    // Part of the provider; runs under the state's lock.
+    Builtin(Builtin),
+    // Registered by the embedder; runs without it (see `Extension`).
+    Extension(Weak<dyn Extension>),
+}
+
+pub(crate) struct Op {
+    pub(crate) schema: OpSchema,
+    pub(crate) implementation: OpImpl,
+}
+
+impl Op {
+    // An extension whose implementation the embedder dropped is gone.
+    pub(crate) fn is_live(&self) -> bool {
+        match &self.implementation {
+            OpImpl::Builtin(_) => true,
+            OpImpl::Extension(extension) => extension.strong_count() > 0,
+        }
+    }
+
+    pub(crate) fn is_extension(&self) -> bool {
+        matches!(self.implementation, OpImpl::Extension(_))
+    }
+}
+
+fn arg(name: &str, element_type: Option<ElementType>, shape: ArgShape) -> ArgSchema {
+    ArgSchema { name: name.to_string(), element_type, shape }
+}
+
+// The provider's own ops, each a thin wrapper around the typed call.
+pub(crate) fn builtins() -> BTreeMap<String, Op> {
+    let matrix = |name: &str| arg(name, Some(ElementType::F32), ArgShape::Matrix);
+    let matrix_f64 = |name: &str| arg(name, Some(ElementType::F64), ArgShape::Matrix);
+    let ops: [(&str, &str, Vec<ArgSchema>, Vec<ArgSchema>, Builtin); 4] = [
+        ("matmul-f32", "C = A x B, like `matrix-multiply-f32`", vec![matrix("a"), matrix("b")], vec![matrix("c")], matmul),
+        (
+            "matmul-f64",
+            "C = A x B in double precision, on the device A is placed on",
+            vec![matrix_f64("a"), matrix_f64("b")],
+            vec![matrix_f64("c")],
+            |state, inputs| Ok(vec![state.matrix_multiply_f64(inputs[0], inputs[1])?]),
+        ),
+        (
+            "add-f32",
+            "C = A + B element-wise; B may be a single row added to every row of A",
+            vec![matrix("a"), matrix("b")],
+            vec![matrix("c")],
+            |state, inputs| {
+                let op = GraphOp::Add((GraphInput::Handle(inputs[0]), GraphInput::Handle(inputs[1])));
+                graph(state, "add-f32", inputs, op)
+            },
+        ),
+        (
+            "relu-f32",
+            "Y = max(X, 0) element-wise",
+            vec![matrix("x")],
+            vec![matrix("y")],
+            |state, inputs| graph(state, "relu-f32", inputs, GraphOp::Relu(GraphInput::Handle(inputs[0]))),
+        ),
+    ];
+    ops.into_iter()
+        .map(|(name, description, inputs, outputs, run)| {
+            let schema = OpSchema { name: name.to_string(), description: description.to_string(), inputs, outputs };
+            (name.to_string(), Op { schema, implementation: OpImpl::Builtin(run) })
+        })
+        .collect()
+}
+
+fn matmul(state: &mut HostState, inputs: &[Handle]) -> Result<Vec<Handle>, HostError> {
+    Ok(vec![state.matrix_multiply_f32(inputs[0], inputs[1], None)?])
+}
+
+// A single-node graph, for the element-wise ops.
+fn graph(state: &mut HostState, name: &str, inputs: &[Handle], op: GraphOp) -> Result<Vec<Handle>, HostError> {
+    let outputs = state.execute_graph(&Graph { nodes: vec![op], outputs: vec![0] })?;
+    state.trace(name, inputs, &outputs);
+    Ok(outputs)
+}
+
+fn shape_name(shape: ArgShape) -> &'static str {
+    match shape {
+        ArgShape::Any => "any buffer",
+        ArgShape::Vector => "vector",
+        ArgShape::Matrix => "matrix",
+    }
+}
+
+// What `call-op` found while holding the lock.
+enum Dispatch {
+    Done(Vec<Handle>),
+    // To be run after releasing it, expected to return this many handles.
+    Extension(Arc<dyn Extension>, usize),
+}
+
+impl HostState {
+    pub fn list_ops(&self) -> Vec<String> {
+        self.ops.iter().filter(|(_, op)| op.is_live()).map(|(name, _)| name.clone()).collect()
+    }
+
+    pub fn describe_op(&mut self, name: &str) -> Result<OpSchema, HostError> {
+        self.charge(0)?;
+        Ok(self.live_op(name)?.schema.clone())
+    }
+
+    fn live_op(&self, name: &str) -> Result<&Op, HostError> {
+        self.ops.get(name).filter(|op| op.is_live()).ok_or(HostError::UnknownOp)
+    }
+
+    fn check_args(&self, schema: &OpSchema, inputs: &[Handle]) -> Result<(), HostError> {
+        if inputs.len() != schema.inputs.len() {
+            return Err(HostError::InvalidArguments(format!(
+                "'{}' takes {} inputs, got {}",
+                schema.name,
+                schema.inputs.len(),
+                inputs.len()
+            )));
+        }
+        for (&h, arg) in inputs.iter().zip(&schema.inputs) {
+            if !self.contains(h) {
+                return Err(self.missing(h));
+            }
+            if let Some(expected) = arg.element_type {
+                self.check_type(h, expected)?;
+            }
+            let dims = self.matrix_dims.get(&h);
+            let fits = match arg.shape {
+                ArgShape::Any => true,
+                ArgShape::Vector => dims.map_or(true, |d| d.rows == 1 || d.cols == 1),
+                ArgShape::Matrix => dims.is_some(),
+            };
+            if !fits {
+                return Err(HostError::InvalidArguments(format!(
+                    "'{}': input '{}' (handle {}) must be a {}",
+                    schema.name,
+                    arg.name,
+                    h,
+                    shape_name(arg.shape)
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    fn dispatch(&mut self, name: &str, inputs: &[Handle], extensions_only: bool) -> Result<Dispatch, HostError> {
+        self.charge(0)?;
+        let op = self.live_op(name)?;
+        if extensions_only && !op.is_extension() {
+            return Err(HostError::UnknownOp);
+        }
+        self.check_args(&op.schema, inputs)?;
+        match &op.implementation {
+            OpImpl::Builtin(run) => {
+                let run = *run;
+                run(self, inputs).map(Dispatch::Done)
+            }
+            OpImpl::Extension(extension) => {
+                let extension = extension.upgrade().ok_or(HostError::UnknownOp)?;
+                Ok(Dispatch::Extension(extension, op.schema.outputs.len()))
+            }
+        }
+    }
+}
+
+// `call-op`, or `call-extension` with `extensions_only`. Extensions run once
+// the lock is released, since they call back into the provider.
+pub(crate) fn call_op(
+    state: &Mutex<HostState>,
+    name: &str,
+    inputs: &[Handle],
+    extensions_only: bool,
+) -> Result<Vec<Handle>, HostError> {
+    log!(Debug, "Calling op '{}' on {:?}", name, inputs);
+    let (extension, expected) = match state.lock().unwrap().dispatch(name, inputs, extensions_only)? {
+        Dispatch::Done(outputs) => return Ok(outputs),
+        Dispatch::Extension(extension, expected) => (extension, expected),
+    };
+    let outputs = extension.execute(name, inputs).map_err(HostError::ComputationError)?;
+    if outputs.len() != expected {
+        return Err(HostError::ComputationError(format!(
+            "'{}' returned {} handles, its schema lists {}",
+            name,
+            outputs.len(),
+            expected
+        )));
+    }
+    state.lock().unwrap().trace(name, inputs, &outputs);
+    Ok(outputs)
+}
@@ -0,0 +1,59 @@
+use crate::wasi_custom::host_offload::host_allocator::{
+    ConcatAxis, ElementType, Handle, HostError, MatrixLayout, MatrixShape, TopK,
+};
+use crate::HostState;
+
+impl HostState {
+    pub fn top_k(&mut self, h: Handle, k: u32, axis: ConcatAxis) -> Result<TopK, HostError> {
+        log!(Debug, "Top {} of {} along {:?}", k, h, axis);
+        self.charge(0)?;
+        let (elem, values) = self.elements(h)?;
+        let dims = *self.matrix_dims.get(&h).ok_or_else(|| self.missing(h))?;
+        if values.len() as u64 != dims.rows as u64 * dims.cols as u64 {
+            return Err(HostError::Other(format!("Buffer {} size mismatch with dims", h)));
+        }
+        let at = |r: u32, c: u32| match dims.layout {
+            MatrixLayout::RowMajor => values[r as usize * dims.cols as usize + c as usize],
+            MatrixLayout::ColumnMajor => values[c as usize * dims.rows as usize + r as usize],
+        };
+        // `lines` runs of `len` elements each keep their `k` largest.
+        let (lines, len) = match axis {
+            ConcatAxis::Rows => (dims.cols, dims.rows),
+            ConcatAxis::Cols => (dims.rows, dims.cols),
+        };
+        if k == 0 || k > len {
+            return Err(HostError::InvalidArguments(format!("k must be in 1..={}, got {}", len, k)));
+        }
+
+        let mut picked = vec![(0u32, 0f64); lines as usize * k as usize];
+        for line in 0..lines {
+            let get = |i: u32| match axis {
+                ConcatAxis::Rows => at(i, line),
+                ConcatAxis::Cols => at(line, i),
+            };
+            let mut order: Vec<u32> = (0..len).collect();
+            // Stable, so ties keep the lower index first.
+            order.sort_by(|&i, &j| get(j).total_cmp(&get(i)));
+            for (rank, &i) in order[..k as usize].iter().enumerate() {
+                // Row-major output: one row per line along `cols`, one column along `rows`.
+                let slot = match axis {
+                    ConcatAxis::Rows => rank * lines as usize + line as usize,
+                    ConcatAxis::Cols => line as usize * k as usize + rank,
+                };
+                picked[slot] = (i, get(i));
+            }
+        }
+
+        let out_dims = match axis {
+            ConcatAxis::Rows => MatrixShape { rows: k, cols: dims.cols, layout: MatrixLayout::RowMajor },
+            ConcatAxis::Cols => MatrixShape { rows: dims.rows, cols: k, layout: MatrixLayout::RowMajor },
+        };
+        let top: Vec<f64> = picked.iter().map(|&(_, v)| v).collect();
+        let indices: Vec<f64> = picked.iter().map(|&(i, _)| i as f64).collect();
+        let values = self.store_elements(h, &top, elem);
+        let indices = self.store_elements(h, &indices, ElementType::S32);
+        self.matrix_dims.insert(values, out_dims);
+        self.matrix_dims.insert(indices, out_dims);
+        Ok(TopK { values, indices })
+    }
+}
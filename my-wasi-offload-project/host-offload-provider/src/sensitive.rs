@@ -0,0 +1,47 @@
+use zeroize::Zeroize;
+
+use crate::storage::Buffer;
+use crate::wasi_custom::host_offload::host_allocator::{BufferFlags, Handle, HostError, StorageKind};
+use crate::HostState;
+
+// Overwrites `buffer` in place with writes the compiler can't drop, however
+// soon the buffer is freed afterwards.
+pub(crate) fn wipe(buffer: &mut Buffer) {
+    buffer[..].zeroize();
+}
+
+impl HostState {
+    pub fn allocate_buffer_with_flags(&mut self, size: u64, options: BufferFlags) -> Result<Handle, HostError> {
+        let h = self.allocate_buffer_with_storage(size, StorageKind::Heap)?;
+        if options.contains(BufferFlags::SENSITIVE) {
+            self.sensitive.insert(h);
+        }
+        Ok(h)
+    }
+
+    pub fn mark_sensitive(&mut self, h: Handle) -> Result<(), HostError> {
+        log!(Debug, "Marking buffer {} sensitive", h);
+        self.charge(0)?;
+        // Back from disk, where it can't be wiped from, and computed if lazy.
+        self.materialize(h)?;
+        let buffer = self.buffers.get(&h).ok_or_else(|| self.missing(h))?;
+        // Its bytes are in the process-wide cache already, out of reach of a wipe.
+        if buffer.is_aliased() {
+            return Err(HostError::InvalidArguments(format!("Buffer {} came from intern-buffer", h)));
+        }
+        self.sensitive.insert(h);
+        Ok(())
+    }
+
+    pub(crate) fn is_sensitive(&self, h: Handle) -> bool {
+        self.sensitive.contains(&h)
+    }
+
+    // For calls that would copy `h` somewhere it can't be wiped from.
+    pub(crate) fn refuse_sensitive(&self, h: Handle, what: &str) -> Result<(), HostError> {
+        match self.is_sensitive(h) {
+            true => Err(HostError::InvalidArguments(format!("Buffer {} is sensitive and can't be {}", h, what))),
+            false => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,115 @@
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::fair::Share;
+
+use crate::events::QuotaKind;
+use crate::session_admin::{SessionId, SessionLimits};
+use crate::wasi_custom::host_offload::host_allocator::{Handle, HostError};
+
+// A runner-managed session: the handles created while it is active and the
+// rate limits the runner configured for its client.
+pub struct Session {
+    pub id: SessionId,
+    pub handles: Vec<Handle>,
+    // Runner-imposed cap on the guest's operation timeout.
+    pub op_timeout: Option<Duration>,
+    // Its turns at the compute slots, and the CPU time they took.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub share: Arc<Share>,
+    ops: Option<TokenBucket>,
+    bytes: Option<TokenBucket>,
+}
+
+impl Session {
+    pub fn new(id: SessionId, limits: SessionLimits) -> Self {
+        Session {
+            id,
+            handles: Vec::new(),
+            op_timeout: limits.max_op_millis.map(Duration::from_millis),
+            #[cfg(not(target_arch = "wasm32"))]
+            share: Share::new(limits.cpu_weight),
+            ops: limits.max_ops_per_sec.map(|rate| TokenBucket::new(rate as f64)),
+            bytes: limits.max_bytes_per_sec.map(|rate| TokenBucket::new(rate as f64)),
+        }
+    }
+
+    // Accounts for one call moving `bytes` of payload across the boundary.
+    // When over budget nothing is consumed and the call fails with the number
+    // of milliseconds the guest should wait before retrying.
+    pub fn charge(&mut self, bytes: u64) -> Result<(), HostError> {
+        let now = Instant::now();
+        let mut retry_after = 0;
+        if let Some(bucket) = self.ops.as_mut() {
+            bucket.refill(now);
+            retry_after = retry_after.max(bucket.wait_millis(1.0));
+        }
+        if let Some(bucket) = self.bytes.as_mut() {
+            bucket.refill(now);
+            retry_after = retry_after.max(bucket.wait_millis(bytes as f64));
+        }
+        if retry_after > 0 {
+            return Err(HostError::RateLimited(retry_after));
+        }
+        if let Some(bucket) = self.ops.as_mut() {
+            bucket.tokens -= 1.0;
+        }
+        if let Some(bucket) = self.bytes.as_mut() {
+            bucket.tokens -= bytes as f64;
+        }
+        Ok(())
+    }
+
+    // Limits that have just dropped below `QUOTA_WARNING_PERCENT` of their
+    // per-second budget, with the percentage left. Each warns once until it
+    // has refilled past the threshold again.
+    pub fn quota_warnings(&mut self) -> Vec<(QuotaKind, u8)> {
+        [(QuotaKind::Operations, self.ops.as_mut()), (QuotaKind::Bytes, self.bytes.as_mut())]
+            .into_iter()
+            .filter_map(|(kind, bucket)| Some((kind, bucket?.crossed_low()?)))
+            .collect()
+    }
+}
+
+// Remaining budget below which a limit raises a quota warning.
+const QUOTA_WARNING_PERCENT: f64 = 20.0;
+
+// Refills at `rate` tokens per second up to one second's worth. A single
+// request larger than the capacity is let through once the bucket is full and
+// drives it negative, so oversized transfers are delayed rather than refused.
+// `rate` is above 0; `open_session` refuses a limit of 0.
+struct TokenBucket {
+    rate: f64,
+    tokens: f64,
+    last_refill: Instant,
+    // Below the warning threshold since the last warning.
+    low: bool,
+}
+
+impl TokenBucket {
+    fn new(rate: f64) -> Self {
+        TokenBucket { rate, tokens: rate, last_refill: Instant::now(), low: false }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.rate);
+        self.last_refill = now;
+    }
+
+    fn crossed_low(&mut self) -> Option<u8> {
+        let percent = (self.tokens / self.rate * 100.0).clamp(0.0, 100.0);
+        let was_low = std::mem::replace(&mut self.low, percent < QUOTA_WARNING_PERCENT);
+        (self.low && !was_low).then_some(percent as u8)
+    }
+
+    fn wait_millis(&self, cost: f64) -> u64 {
+        let needed = cost.min(self.rate);
+        if self.tokens >= needed {
+            return 0;
+        }
+        (((needed - self.tokens) / self.rate) * 1000.0).ceil().max(1.0) as u64
+    }
+}
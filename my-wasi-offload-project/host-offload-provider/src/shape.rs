@@ -0,0 +1,206 @@
+use nalgebra::DMatrix;
+
+use crate::cast::decode;
+use crate::state::element_size;
+use crate::wasi_custom::host_offload::host_allocator::{
+    ConcatAxis, ElementType, Handle, HostError, MatrixLayout, MatrixShape, Pooling,
+};
+use crate::HostState;
+
+// Byte offset of element (r, c) in a buffer with `dims`.
+fn element_offset(dims: MatrixShape, r: u32, c: u32, elem_size: usize) -> usize {
+    let index = match dims.layout {
+        MatrixLayout::RowMajor => r as usize * dims.cols as usize + c as usize,
+        MatrixLayout::ColumnMajor => c as usize * dims.rows as usize + r as usize,
+    };
+    index * elem_size
+}
+
+// Copies row `from` of `src` into row `to` of `dst`. Both must have the same
+// column count; layouts may differ.
+fn copy_row(src: &[u8], src_dims: MatrixShape, from: u32, dst: &mut [u8], dst_dims: MatrixShape, to: u32, elem_size: usize) {
+    if src_dims.layout == MatrixLayout::RowMajor && dst_dims.layout == MatrixLayout::RowMajor {
+        let row_bytes = src_dims.cols as usize * elem_size;
+        let (s, d) = (from as usize * row_bytes, to as usize * row_bytes);
+        dst[d..d + row_bytes].copy_from_slice(&src[s..s + row_bytes]);
+        return;
+    }
+    for c in 0..src_dims.cols {
+        let s = element_offset(src_dims, from, c, elem_size);
+        let d = element_offset(dst_dims, to, c, elem_size);
+        dst[d..d + elem_size].copy_from_slice(&src[s..s + elem_size]);
+    }
+}
+
+impl HostState {
+    // Dims and element type of a matrix about to be read row by row.
+    fn row_source(&mut self, h: Handle) -> Result<(MatrixShape, ElementType), HostError> {
+        self.materialize(h)?;
+        let dims = *self.matrix_dims.get(&h).ok_or_else(|| self.missing(h))?;
+        let elem = self.element_types.get(&h).copied().unwrap_or(ElementType::F32);
+        let expected = dims.rows as u64 * dims.cols as u64 * element_size(elem) as u64;
+        if self.buffer_len(h)? != expected {
+            return Err(HostError::Other(format!("Buffer {} size mismatch with dims", h)));
+        }
+        Ok((dims, elem))
+    }
+
+    pub fn gather_rows(&mut self, h: Handle, rows: &[u32]) -> Result<Handle, HostError> {
+        log!(Debug, "Gathering {} rows from {}", rows.len(), h);
+        self.charge(0)?;
+        let (dims, elem) = self.row_source(h)?;
+        if rows.iter().any(|&r| r >= dims.rows) {
+            return Err(HostError::CopyOutOfBounds);
+        }
+        let out_dims = MatrixShape { rows: rows.len() as u32, cols: dims.cols, layout: dims.layout };
+        let elem_size = element_size(elem);
+        let mut out = vec![0u8; rows.len() * dims.cols as usize * elem_size];
+        let src = &self.buffers[&h];
+        for (to, &from) in rows.iter().enumerate() {
+            copy_row(src, dims, from, &mut out, out_dims, to as u32, elem_size);
+        }
+        let handle = self.new_handle();
+        self.buffers.insert(handle, out.into());
+        self.matrix_dims.insert(handle, out_dims);
+        self.element_types.insert(handle, elem);
+        Ok(handle)
+    }
+
+    pub fn scatter_rows(&mut self, src: Handle, dst: Handle, rows: &[u32]) -> Result<(), HostError> {
+        log!(Debug, "Scattering {} rows from {} into {}", rows.len(), src, dst);
+        self.charge(0)?;
+        let (src_dims, src_elem) = self.row_source(src)?;
+        let (dst_dims, dst_elem) = self.row_source(dst)?;
+        if src_elem != dst_elem {
+            return Err(HostError::TypeMismatch);
+        }
+        if src_dims.rows as usize != rows.len() || src_dims.cols != dst_dims.cols {
+            return Err(HostError::DimensionMismatch);
+        }
+        if rows.iter().any(|&r| r >= dst_dims.rows) {
+            return Err(HostError::CopyOutOfBounds);
+        }
+        self.materialize_dependents(dst)?;
+        self.journal(dst)?;
+        let elem_size = element_size(src_elem);
+        // Copied out first since `src` and `dst` may be the same buffer.
+        let src_bytes = self.buffers[&src].to_vec();
+        let dst_bytes = self.buffers.get_mut(&dst).unwrap();
+        for (from, &to) in rows.iter().enumerate() {
+            copy_row(&src_bytes, src_dims, from as u32, dst_bytes, dst_dims, to, elem_size);
+        }
+        Ok(())
+    }
+
+    pub fn embedding_lookup(&mut self, table: Handle, ids: &[u32], pooling: Pooling) -> Result<Handle, HostError> {
+        if pooling == Pooling::None {
+            return self.gather_rows(table, ids);
+        }
+        log!(Debug, "Pooling {} rows of {} ({:?})", ids.len(), table, pooling);
+        self.charge(0)?;
+        let (dims, elem) = self.row_source(table)?;
+        if ids.iter().any(|&r| r >= dims.rows) {
+            return Err(HostError::CopyOutOfBounds);
+        }
+        if ids.is_empty() && pooling == Pooling::Mean {
+            return Err(HostError::InvalidArguments("Mean pooling needs at least one id".to_string()));
+        }
+        let values = decode(&self.buffers[&table], elem);
+        let mut pooled = vec![0f64; dims.cols as usize];
+        for &r in ids {
+            for (c, sum) in pooled.iter_mut().enumerate() {
+                *sum += values[element_offset(dims, r, c as u32, 1)];
+            }
+        }
+        if pooling == Pooling::Mean {
+            pooled.iter_mut().for_each(|v| *v /= ids.len() as f64);
+        }
+        let row = DMatrix::from_iterator(1, pooled.len(), pooled.iter().map(|&v| v as f32));
+        Ok(self.store_matrix_f32(&row, MatrixLayout::RowMajor))
+    }
+
+    pub fn concat(&mut self, inputs: &[Handle], axis: ConcatAxis) -> Result<Handle, HostError> {
+        log!(Debug, "Concatenating {} matrices along {:?}", inputs.len(), axis);
+        self.charge(0)?;
+        let mut parts = Vec::with_capacity(inputs.len());
+        for &h in inputs {
+            parts.push((h, self.row_source(h)?));
+        }
+        let Some(&(_, (first, elem))) = parts.first() else {
+            return Err(HostError::Other("Nothing to concatenate".to_string()));
+        };
+        if parts.iter().any(|(_, (_, e))| *e != elem) {
+            return Err(HostError::TypeMismatch);
+        }
+        let (rows, cols) = match axis {
+            ConcatAxis::Rows => {
+                if parts.iter().any(|(_, (d, _))| d.cols != first.cols) {
+                    return Err(HostError::DimensionMismatch);
+                }
+                (parts.iter().map(|(_, (d, _))| d.rows).sum::<u32>(), first.cols)
+            }
+            ConcatAxis::Cols => {
+                if parts.iter().any(|(_, (d, _))| d.rows != first.rows) {
+                    return Err(HostError::DimensionMismatch);
+                }
+                (first.rows, parts.iter().map(|(_, (d, _))| d.cols).sum::<u32>())
+            }
+        };
+        let out_dims = MatrixShape { rows, cols, layout: MatrixLayout::RowMajor };
+        let elem_size = element_size(elem);
+        let mut out = vec![0u8; rows as usize * cols as usize * elem_size];
+        let (mut row_base, mut col_base) = (0, 0);
+        for (h, (dims, _)) in &parts {
+            let src = &self.buffers[h];
+            for r in 0..dims.rows {
+                for c in 0..dims.cols {
+                    let s = element_offset(*dims, r, c, elem_size);
+                    let d = element_offset(out_dims, row_base + r, col_base + c, elem_size);
+                    out[d..d + elem_size].copy_from_slice(&src[s..s + elem_size]);
+                }
+            }
+            match axis {
+                ConcatAxis::Rows => row_base += dims.rows,
+                ConcatAxis::Cols => col_base += dims.cols,
+            }
+        }
+        let handle = self.new_handle();
+        self.buffers.insert(handle, out.into());
+        self.matrix_dims.insert(handle, out_dims);
+        self.element_types.insert(handle, elem);
+        Ok(handle)
+    }
+
+    pub fn stack(&mut self, inputs: &[Handle]) -> Result<Handle, HostError> {
+        log!(Debug, "Stacking {} buffers", inputs.len());
+        self.charge(0)?;
+        let Some(&first) = inputs.first() else {
+            return Err(HostError::Other("Nothing to stack".to_string()));
+        };
+        let elem = self.element_types.get(&first).copied().unwrap_or(ElementType::F32);
+        let elem_size = element_size(elem) as u64;
+        let mut out = Vec::new();
+        let mut row_len = None;
+        for &h in inputs {
+            self.materialize(h)?;
+            if self.element_types.get(&h).copied().unwrap_or(ElementType::F32) != elem {
+                return Err(HostError::TypeMismatch);
+            }
+            let bytes = self.buffers.get(&h).ok_or_else(|| self.missing(h))?;
+            if bytes.len() as u64 % elem_size != 0 {
+                return Err(HostError::Misaligned);
+            }
+            if *row_len.get_or_insert(bytes.len()) != bytes.len() {
+                return Err(HostError::DimensionMismatch);
+            }
+            out.extend_from_slice(bytes);
+        }
+        let cols = (row_len.unwrap_or(0) as u64 / elem_size) as u32;
+        let out_dims = MatrixShape { rows: inputs.len() as u32, cols, layout: MatrixLayout::RowMajor };
+        let handle = self.new_handle();
+        self.buffers.insert(handle, out.into());
+        self.matrix_dims.insert(handle, out_dims);
+        self.element_types.insert(handle, elem);
+        Ok(handle)
+    }
+}
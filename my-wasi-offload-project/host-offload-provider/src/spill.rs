@@ -0,0 +1,189 @@
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::wasi_custom::host_offload::host_allocator::{Handle, HostError, MemoryStats, StorageKind};
+use crate::events::{Event, EvictionReason};
+use crate::HostState;
+
+// Distinguishes the spill directories of providers sharing a process (the
+// native host runs one per client).
+static NEXT_SPILL_DIR: AtomicU64 = AtomicU64::new(0);
+
+// Least-recently-used spilling under a resident-memory budget.
+//
+// With a budget set, the provider checks it at the start of every call and
+// writes the coldest buffers out to files in a private temp directory until
+// the rest fits. A spilled buffer keeps its handle and metadata and is read
+// back by `materialize`, which every operation runs on its operands before
+// touching their bytes, so guests never see the difference. The budget is
+// soft: the operands and result of the call in flight stay resident until
+// the next call.
+#[derive(Default)]
+pub(crate) struct Spill {
+    pub(crate) budget: Option<u64>,
+    // Last-use tick per resident or spilled handle.
+    pub(crate) last_used: HashMap<Handle, u64>,
+    pub(crate) tick: u64,
+    // Byte length of each spilled buffer; its contents live in `path(h)`.
+    pub(crate) spilled: HashMap<Handle, u64>,
+    // Buffers the guest pinned: never spilled, and not reaped by their TTL
+    // until unpinned.
+    pub(crate) pinned: HashSet<Handle>,
+    pub(crate) dir: Option<PathBuf>,
+    pub(crate) evictions: u64,
+    pub(crate) reloads: u64,
+}
+
+impl Spill {
+    fn path(&mut self, h: Handle) -> std::io::Result<PathBuf> {
+        if self.dir.is_none() {
+            let n = NEXT_SPILL_DIR.fetch_add(1, Ordering::Relaxed);
+            let dir = std::env::temp_dir().join(format!("host-offload-spill-{}-{}", std::process::id(), n));
+            std::fs::create_dir_all(&dir)?;
+            self.dir = Some(dir);
+        }
+        Ok(self.dir.as_ref().unwrap().join(h.to_string()))
+    }
+}
+
+impl Drop for Spill {
+    fn drop(&mut self) {
+        if let Some(dir) = &self.dir {
+            let _ = std::fs::remove_dir_all(dir);
+        }
+    }
+}
+
+impl HostState {
+    // Embedder knob (not part of the WIT interface): resident bytes above
+    // which cold buffers are spilled to disk. `None` turns spilling off;
+    // buffers already on disk stay there until used.
+    pub fn set_memory_budget(&mut self, bytes: Option<u64>) {
+        self.spill.budget = bytes;
+    }
+
+    pub fn get_memory_stats(&self) -> MemoryStats {
+        MemoryStats {
+            resident_bytes: self.resident_bytes(),
+            spilled_bytes: self.spill.spilled.values().sum(),
+            pinned_bytes: self.spill.pinned.iter().filter_map(|h| self.buffers.get(h)).map(|b| b.len() as u64).sum(),
+            node_bytes: self.node_bytes_per_node(),
+            live_handles: (self.buffers.len() + self.pending.len() + self.spill.spilled.len()) as u32,
+            evictions: self.spill.evictions,
+            reloads: self.spill.reloads,
+        }
+    }
+
+    pub fn pin_buffer(&mut self, h: Handle) -> Result<(), HostError> {
+        log!(Debug, "Pinning buffer {}", h);
+        self.charge(0)?;
+        // Pinned buffers stay resident, so bring it in now rather than on first use.
+        self.materialize(h)?;
+        if !self.contains(h) {
+            return Err(self.missing(h));
+        }
+        self.spill.pinned.insert(h);
+        Ok(())
+    }
+
+    pub fn unpin_buffer(&mut self, h: Handle) -> Result<(), HostError> {
+        log!(Debug, "Unpinning buffer {}", h);
+        self.charge(0)?;
+        if !self.contains(h) {
+            return Err(self.missing(h));
+        }
+        self.spill.pinned.remove(&h);
+        Ok(())
+    }
+
+    fn node_bytes_per_node(&self) -> Vec<u64> {
+        let mut totals = Vec::new();
+        for per_node in self.node_bytes.values() {
+            totals.resize(totals.len().max(per_node.len()), 0);
+            for (total, bytes) in totals.iter_mut().zip(per_node) {
+                *total += bytes;
+            }
+        }
+        totals
+    }
+
+    fn resident_bytes(&self) -> u64 {
+        self.buffers.values().map(|b| b.len() as u64).sum()
+    }
+
+    pub(crate) fn touch(&mut self, h: Handle) {
+        self.spill.tick += 1;
+        self.spill.last_used.insert(h, self.spill.tick);
+    }
+
+    // Spills least-recently-used buffers until the resident ones fit the budget.
+    pub(crate) fn enforce_memory_budget(&mut self) {
+        let Some(budget) = self.spill.budget else { return };
+        let mut resident = self.resident_bytes();
+        if resident <= budget {
+            return;
+        }
+        let mut coldest: Vec<(u64, Handle)> = self.buffers.keys()
+            // Mapped and shared buffers stay where the guest asked for them,
+            // and sensitive ones never reach the disk.
+            .filter(|h| !self.spill.pinned.contains(h) && !self.is_sensitive(**h))
+            .filter(|h| self.buffers[h].kind() == StorageKind::Heap)
+            .map(|&h| (self.spill.last_used.get(&h).copied().unwrap_or(0), h))
+            .collect();
+        coldest.sort_unstable();
+        for (_, h) in coldest {
+            if resident <= budget {
+                break;
+            }
+            match self.spill_out(h) {
+                Ok(len) => resident -= len,
+                Err(e) => {
+                    log!(Warn, "Failed to spill buffer {}: {}", h, e);
+                    break;
+                }
+            }
+        }
+    }
+
+    fn spill_out(&mut self, h: Handle) -> std::io::Result<u64> {
+        let path = self.spill.path(h)?;
+        std::fs::write(&path, &self.buffers[&h])?;
+        let len = self.buffers.remove(&h).unwrap().len() as u64;
+        log!(Info, "Spilled buffer {} ({} bytes) to disk", h, len);
+        // Read back wherever the reloading thread runs.
+        self.node_bytes.remove(&h);
+        self.spill.spilled.insert(h, len);
+        self.spill.evictions += 1;
+        self.emit(Event::BufferEvicted { handle: h, reason: EvictionReason::Spilled });
+        Ok(len)
+    }
+
+    // Brings a spilled buffer back into memory; a no-op for resident ones.
+    pub(crate) fn reload(&mut self, h: Handle) -> Result<(), HostError> {
+        if self.spill.spilled.contains_key(&h) {
+            let path = self.spill.path(h).map_err(|e| HostError::Other(format!("Spill directory unavailable: {}", e)))?;
+            let bytes = std::fs::read(&path)
+                .map_err(|e| HostError::Other(format!("Failed to reload spilled buffer {}: {}", h, e)))?;
+            let _ = std::fs::remove_file(&path);
+            self.spill.spilled.remove(&h);
+            self.buffers.insert(h, bytes.into());
+            self.spill.reloads += 1;
+        }
+        self.touch(h);
+        Ok(())
+    }
+
+    // Forgets `h`'s spill state, deleting its file if it has one. True if it was spilled.
+    pub(crate) fn drop_spilled(&mut self, h: Handle) -> bool {
+        self.spill.last_used.remove(&h);
+        self.spill.pinned.remove(&h);
+        if self.spill.spilled.remove(&h).is_none() {
+            return false;
+        }
+        if let Ok(path) = self.spill.path(h) {
+            let _ = std::fs::remove_file(path);
+        }
+        true
+    }
+}
@@ -0,0 +1,121 @@
+use std::time::{Duration, Instant};
+
+use nalgebra::DMatrix;
+use offload_common::codec;
+
+use crate::backend::Operand;
+use crate::kernels;
+use crate::state::{matrix_from_slice, matrix_to_bytes};
+use crate::wasi_custom::host_offload::host_allocator::{ComputeMode, Handle, HostError, MatrixLayout, MatrixShape};
+use crate::HostState;
+
+// One GFLOP: below this a second device costs more in copies than it saves.
+const DEFAULT_MIN_FLOPS: u64 = 1 << 30;
+
+// Settings for splitting large multiplies between the CPU and `gpu(0)`
+// (native builds only).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SplitConfig {
+    // Smallest multiply (in multiply-adds) worth splitting; `None` uses 2^30.
+    pub min_flops: Option<u64>,
+}
+
+// Splits the rows of C between the GPU backend and the calling thread, which
+// compute their tiles at the same time. Shares follow each side's measured
+// throughput (multiply-adds per second over recent splits), starting even.
+pub(crate) struct Splitter {
+    min_flops: u64,
+    cpu_rate: Option<f64>,
+    gpu_rate: Option<f64>,
+}
+
+impl Splitter {
+    fn new(config: SplitConfig) -> Self {
+        Splitter { min_flops: config.min_flops.unwrap_or(DEFAULT_MIN_FLOPS), cpu_rate: None, gpu_rate: None }
+    }
+
+    // Rows of an `m`-row product that go to the GPU; the rest stay on the CPU.
+    fn gpu_rows(&self, m: usize) -> usize {
+        let share = match (self.cpu_rate, self.gpu_rate) {
+            (Some(cpu), Some(gpu)) => gpu / (cpu + gpu),
+            _ => 0.5,
+        };
+        ((m as f64 * share).round() as usize).min(m)
+    }
+
+    // Half old, half new, so one noisy split doesn't swing the next.
+    fn record(rate: &mut Option<f64>, flops: u64, took: Duration) {
+        let seconds = took.as_secs_f64();
+        if flops == 0 || seconds == 0.0 {
+            return;
+        }
+        let measured = flops as f64 / seconds;
+        *rate = Some(rate.map_or(measured, |old| (old + measured) / 2.0));
+    }
+}
+
+impl HostState {
+    // Embedder knob (not part of the WIT interface): split large unplaced
+    // f32 multiplies between the CPU and the first GPU, or stop with `None`.
+    pub fn set_split_matmul(&mut self, config: Option<SplitConfig>) {
+        self.split = config.map(Splitter::new);
+    }
+
+    // Whether `a * b`, both materialized, should go to `matmul_f32_split`.
+    pub(crate) fn worth_splitting(&self, a: Handle, b: Handle) -> bool {
+        let (Some(split), Some(a), Some(b)) = (&self.split, self.matrix_dims.get(&a), self.matrix_dims.get(&b)) else {
+            return false;
+        };
+        let flops = a.rows as u64 * a.cols as u64 * b.cols as u64;
+        flops >= split.min_flops && !self.gpu_devices().is_empty()
+    }
+
+    // `a * b` in `layout`, its leading rows from `gpu(0)` and the rest from
+    // the CPU.
+    pub(crate) fn matmul_f32_split(&mut self, a: Handle, b: Handle, layout: MatrixLayout) -> Result<(Vec<u8>, Vec<u64>), HostError> {
+        let (dims_a, matrix_a) = self.read_matrix_f32(a)?;
+        let (dims_b, matrix_b) = self.read_matrix_f32(b)?;
+        let (m, k, n) = (dims_a.rows as usize, dims_a.cols as usize, dims_b.cols as usize);
+        let split = self.split.as_ref().ok_or(HostError::DeviceUnavailable)?;
+        let gpu_rows = split.gpu_rows(m);
+        let deadline = self.op_deadline();
+
+        // The GPU's rows of A as an operand of their own, in A's layout so the
+        // tile comes back in it too. B goes whole, resident copy and all.
+        let tile_dims = MatrixShape { rows: gpu_rows as u32, cols: dims_a.cols, layout: dims_a.layout };
+        let tile_bytes = matrix_to_bytes(&matrix_a.rows(0, gpu_rows).into_owned(), dims_a.layout);
+        let tile = Operand { handle: 0, bytes: &tile_bytes, dims: tile_dims };
+        let whole_b = Operand { handle: b, bytes: &self.buffers[&b], dims: dims_b };
+        let gpu = self.gpu.as_mut().ok_or(HostError::DeviceUnavailable)?;
+
+        let (gpu_result, cpu_result) = std::thread::scope(|scope| {
+            let on_gpu = scope.spawn(move || {
+                let started = Instant::now();
+                let result = match gpu_rows {
+                    0 => Ok(Vec::new()),
+                    _ => gpu.matmul_f32(0, tile, whole_b, deadline),
+                };
+                (result, started.elapsed())
+            });
+            let started = Instant::now();
+            let cpu_a = matrix_a.rows(gpu_rows, m - gpu_rows).into_owned();
+            let on_cpu = kernels::matmul_f32_within(&cpu_a, &matrix_b, ComputeMode::Fast, deadline);
+            (on_gpu.join().expect("GPU tile panicked"), (on_cpu, started.elapsed()))
+        });
+        let ((gpu_bytes, gpu_took), (cpu_tile, cpu_took)) = (gpu_result, cpu_result);
+        let split = self.split.as_mut().unwrap();
+        let gpu_bytes = gpu_bytes?;
+        Splitter::record(&mut split.gpu_rate, (gpu_rows * k * n) as u64, gpu_took);
+        let cpu_tile = cpu_tile?;
+        Splitter::record(&mut split.cpu_rate, ((m - gpu_rows) * k * n) as u64, cpu_took);
+        log!(Debug, "Split {}x{}x{} multiply: {} rows on gpu(0), {} on cpu", m, k, n, gpu_rows, m - gpu_rows);
+
+        let gpu_values = codec::f32_from_le_bytes(&gpu_bytes)
+            .ok_or_else(|| HostError::ComputationError("GPU tile is not a whole number of f32 elements".to_string()))?;
+        let gpu_tile_dims = MatrixShape { rows: gpu_rows as u32, cols: dims_b.cols, layout: dims_a.layout };
+        let mut c = DMatrix::<f32>::zeros(m, n);
+        c.rows_mut(0, gpu_rows).copy_from(&matrix_from_slice(gpu_tile_dims, &gpu_values));
+        c.rows_mut(gpu_rows, m - gpu_rows).copy_from(&cpu_tile);
+        Ok((matrix_to_bytes(&c, layout), Vec::new()))
+    }
+}
@@ -0,0 +1,1099 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use offload_common::codec;
+
+use crate::analysis::Recorder;
+use crate::autoselect::AutoSelect;
+use crate::backend::ComputeBackend;
+use crate::checks;
+use crate::cost::Calibration;
+use crate::dump;
+use crate::graph;
+use crate::hash;
+use crate::events::Event;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::fair::Share;
+use crate::kernels;
+use crate::kvcache::KvCache;
+use crate::lazy::{PartialMatmul, PendingOp};
+use crate::registry::{self, Op};
+use crate::sensitive;
+use crate::session::Session;
+use crate::spill::Spill;
+use crate::storage::Buffer;
+use crate::transaction::Transaction;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::session_admin::SessionUsage;
+use crate::session_admin::{LogLevel, SessionId, SessionLimits};
+use crate::wasi_custom::host_offload::host_allocator::{
+    ArenaId, BackendInfo, ComparisonReport, ComputeHint, ComputeMode, Device, DeviceInfo, DumpDestination,
+    DumpFormat, ElementType, EvaluationMode, Graph, GraphInput, HashAlgorithm, Handle, HandleInfo, HostError,
+    InterfaceVersion, JobId, JobProgress, JobState, MatrixDimensions, MatrixLayout, MatrixShape, MatrixStructure, StorageKind, TensorMeta
+};
+
+pub(crate) const BACKEND_NAME: &str = "nalgebra-cpu";
+
+// The version of the `wasi-custom:host-offload` package in wit/; bumped with
+// it (tests/interface.rs checks the two agree).
+pub(crate) const INTERFACE_VERSION: InterfaceVersion = InterfaceVersion { major: 0, minor: 2, patch: 0 };
+
+// Default cap on a single buffer. A wasm32 provider shares one 4 GiB linear
+// memory between all buffers, its own heap and the copies made while moving
+// data, so single buffers stop at 2 GiB. 64-bit builds (native or memory64)
+// default to 1 TiB; embedders can change it with `set_max_allocation`.
+#[cfg(target_pointer_width = "32")]
+pub const DEFAULT_MAX_ALLOCATION: u64 = 1 << 31;
+#[cfg(not(target_pointer_width = "32"))]
+pub const DEFAULT_MAX_ALLOCATION: u64 = 1 << 40;
+
+// Optional capabilities every build of this provider has. `streams` depends on
+// who embeds it, so the native host adds it on top.
+pub(crate) const FEATURES: &[&str] = &[
+    "f32", "f64", "lazy", "graph", "async-jobs", "sessions", "ttl", "transactions", "ops", "extensions", "dry-run", "batch",
+    "intern", "sensitive",
+];
+
+// Everything the provider tracks for one client. The wasm component keeps a
+// single global instance; the native host keeps one per client store.
+pub struct HostState {
+    pub(crate) buffers: HashMap<Handle, Buffer>,
+    pub(crate) matrix_dims: HashMap<Handle, MatrixShape>,
+    // Registered element types; untyped handles are absent.
+    pub(crate) element_types: HashMap<Handle, ElementType>,
+    // Structure hints; `general` matrices are absent.
+    pub(crate) structures: HashMap<Handle, MatrixStructure>,
+    // Handles made by `create-kv-cache`.
+    pub(crate) kv_caches: HashMap<Handle, KvCache>,
+    // Lazy results: these handles have dims but no entry in `buffers` yet.
+    pub(crate) pending: HashMap<Handle, PendingOp>,
+    // Pending results `poll-job` has started computing.
+    pub(crate) partials: HashMap<Handle, PartialMatmul>,
+    // Home device per handle (absent means cpu) and the devices holding a copy.
+    pub(crate) placements: HashMap<Handle, Device>,
+    pub(crate) residency: HashMap<Handle, Vec<Device>>,
+    pub(crate) next_handle: Handle,
+    // Deadlines of handles allocated with a TTL, and handles reclaimed at
+    // theirs (so later use reports `expired` rather than `invalid-handle`).
+    pub(crate) expiries: HashMap<Handle, Instant>,
+    pub(crate) expired: HashSet<Handle>,
+    // LRU spill-to-disk state; inactive unless the embedder sets a budget.
+    pub(crate) spill: Spill,
+    // Handles to wipe on free and keep out of dumps, exports and traces.
+    pub(crate) sensitive: HashSet<Handle>,
+    // Guest-opened transaction, if any; see `journal`.
+    pub(crate) transaction: Option<Transaction>,
+    // Worker pool for large multiplies, when the embedder enabled it, and how
+    // many bytes of each result it placed on each NUMA node.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) numa: Option<crate::numa::NumaPool>,
+    // Splitting of large multiplies between CPU and GPU, when enabled.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) split: Option<crate::split::Splitter>,
+    // Seeded generators made by `create-rng`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) rngs: HashMap<crate::wasi_custom::host_offload::random::RngId, rand_chacha::ChaCha20Rng>,
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) next_rng: crate::wasi_custom::host_offload::random::RngId,
+    // Embedder-chosen backend behind `device::gpu(n)`, if any.
+    pub(crate) gpu: Option<Box<dyn ComputeBackend>>,
+    // Timed routing for unplaced multiplies, when the embedder enabled it.
+    pub(crate) auto: Option<AutoSelect>,
+    // Rates behind `estimate-cost`, measured on first use.
+    pub(crate) calibration: Option<Calibration>,
+    pub(crate) node_bytes: HashMap<Handle, Vec<u64>>,
+    // Queued events, or None while the embedder hasn't enabled them.
+    pub(crate) events: Option<Vec<Event>>,
+    // Recorded calls, or None while the embedder hasn't enabled analysis.
+    pub(crate) analysis: Option<Recorder>,
+    // Ops callable by name: the built-in ones and those the embedder
+    // registered (see `register_extension`).
+    pub(crate) ops: BTreeMap<String, Op>,
+    pub(crate) max_allocation: u64,
+    // Largest row or column count a multiply operand may have; see `set_max_matmul_dim`.
+    pub(crate) max_matmul_dim: Option<u32>,
+    pub(crate) compute_mode: ComputeMode,
+    pub(crate) compute_hint: ComputeHint,
+    // Guest-chosen limit per compute call; see `op_deadline`.
+    pub(crate) op_timeout: Option<Duration>,
+    pub(crate) evaluation_mode: EvaluationMode,
+    // Guest-set; compute calls validate but skip their arithmetic.
+    pub(crate) dry_run: bool,
+    // Submitted async jobs and the (lazy) handle each one produces.
+    pub(crate) jobs: HashMap<JobId, Handle>,
+    pub(crate) next_job: JobId,
+    // Open arenas, innermost last, and the handles created while each was innermost.
+    pub(crate) arena_stack: Vec<ArenaId>,
+    pub(crate) arenas: HashMap<ArenaId, Vec<Handle>>,
+    pub(crate) next_arena: ArenaId,
+    // Runner-managed session, if one is open.
+    pub(crate) active_session: Option<Session>,
+    pub(crate) next_session: SessionId,
+}
+
+impl Default for HostState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HostState {
+    pub fn new() -> Self {
+        HostState {
+            buffers: HashMap::new(),
+            matrix_dims: HashMap::new(),
+            element_types: HashMap::new(),
+            structures: HashMap::new(),
+            kv_caches: HashMap::new(),
+            pending: HashMap::new(),
+            partials: HashMap::new(),
+            placements: HashMap::new(),
+            residency: HashMap::new(),
+            next_handle: 1, // Start handles from 1
+            expiries: HashMap::new(),
+            expired: HashSet::new(),
+            spill: Spill::default(),
+            sensitive: HashSet::new(),
+            transaction: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            numa: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            split: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            rngs: HashMap::new(),
+            #[cfg(not(target_arch = "wasm32"))]
+            next_rng: 1,
+            gpu: None,
+            auto: None,
+            calibration: None,
+            node_bytes: HashMap::new(),
+            events: None,
+            analysis: None,
+            ops: registry::builtins(),
+            max_allocation: DEFAULT_MAX_ALLOCATION,
+            max_matmul_dim: None,
+            compute_mode: ComputeMode::Fast,
+            compute_hint: ComputeHint::Throughput,
+            op_timeout: None,
+            evaluation_mode: EvaluationMode::Eager,
+            dry_run: false,
+            jobs: HashMap::new(),
+            next_job: 1,
+            arena_stack: Vec::new(),
+            arenas: HashMap::new(),
+            next_arena: 1,
+            active_session: None,
+            next_session: 1,
+        }
+    }
+
+    pub(crate) fn read_f32(&self, h: Handle) -> Result<Vec<f32>, HostError> {
+        self.check_type(h, ElementType::F32)?;
+        let bytes = self.buffers.get(&h).ok_or_else(|| self.missing(h))?;
+        codec::f32_from_le_bytes(bytes)
+            .ok_or_else(|| HostError::Other(format!("Buffer {} is not a whole number of f32 elements", h)))
+    }
+
+    pub(crate) fn read_matrix_f32(&self, h: Handle) -> Result<(MatrixShape, nalgebra::DMatrix<f32>), HostError> {
+        let dims = *self.matrix_dims.get(&h).ok_or_else(|| self.missing(h))?;
+        let data = self.read_f32(h)?;
+        // Kept in `fast` builds: it's O(1), and nalgebra panics on a short slice.
+        if data.len() as u64 != dims.rows as u64 * dims.cols as u64 {
+            return Err(HostError::Other(format!("Buffer {} size mismatch with dims", h)));
+        }
+        Ok((dims, matrix_from_slice(dims, &data)))
+    }
+
+    // `a * b` encoded in `layout`, plus how many of its bytes the NUMA pool
+    // placed on each node (empty when the pool didn't compute it).
+    pub(crate) fn matmul_f32_bytes(
+        &self,
+        a: &nalgebra::DMatrix<f32>,
+        b: &nalgebra::DMatrix<f32>,
+        layout: MatrixLayout,
+    ) -> Result<(Vec<u8>, Vec<u64>), HostError> {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(pool) = self.numa.as_ref().filter(|pool| pool.worth_it(a, b)) {
+            return pool.matmul_f32(a, b, layout, self.compute_mode, self.compute_hint, self.op_deadline());
+        }
+        let c = kernels::matmul_f32_within(a, b, self.compute_mode, self.op_deadline())?;
+        Ok((matrix_to_bytes(&c, layout), Vec::new()))
+    }
+
+    pub(crate) fn record_nodes(&mut self, h: Handle, per_node: Vec<u64>) {
+        if !per_node.is_empty() {
+            self.node_bytes.insert(h, per_node);
+        }
+    }
+
+    pub(crate) fn backend_name(&self) -> &'static str {
+        if self.auto.is_some() {
+            return crate::autoselect::BACKEND_NAME;
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if self.numa.is_some() {
+            return crate::numa::BACKEND_NAME;
+        }
+        BACKEND_NAME
+    }
+
+    // Stores a computed matrix under a fresh handle.
+    pub(crate) fn store_matrix_f32(&mut self, matrix: &nalgebra::DMatrix<f32>, layout: MatrixLayout) -> Handle {
+        let handle = self.new_handle();
+        let dims = MatrixShape {
+            rows: matrix.nrows() as u32,
+            cols: matrix.ncols() as u32,
+            layout,
+        };
+        self.buffers.insert(handle, matrix_to_bytes(matrix, layout).into());
+        self.matrix_dims.insert(handle, dims);
+        self.element_types.insert(handle, ElementType::F32);
+        handle
+    }
+
+    pub(crate) fn new_handle(&mut self) -> Handle {
+        let handle = self.next_handle;
+        self.next_handle += 1;
+        if self.next_handle == 0 { panic!("Handle overflow!"); }
+        if let Some(arena) = self.arena_stack.last() {
+            self.arenas.get_mut(arena).unwrap().push(handle);
+        }
+        if let Some(session) = self.active_session.as_mut() {
+            session.handles.push(handle);
+        }
+        self.touch(handle);
+        self.track_created(handle);
+        handle
+    }
+
+    // Clamped to what `usize` can address on this target.
+    pub fn set_max_allocation(&mut self, max: u64) {
+        self.max_allocation = max.min(usize::MAX as u64);
+    }
+
+    // Multiplies (`matrix-multiply-f32`, `matmul-accumulate`,
+    // `submit-matmul-f32` and `execute-graph` matmul nodes) with an operand
+    // dimension above `max` fail with `permission-denied`. Set by the runner's
+    // client policies.
+    pub fn set_max_matmul_dim(&mut self, max: Option<u32>) {
+        self.max_matmul_dim = max;
+    }
+
+    pub(crate) fn check_matmul_dims(&self, dims: impl IntoIterator<Item = usize>) -> Result<(), HostError> {
+        let Some(max) = self.max_matmul_dim else {
+            return Ok(());
+        };
+        match dims.into_iter().find(|&dim| dim > max as usize) {
+            Some(dim) => {
+                Err(HostError::PermissionDenied(format!("Matmul dimension {} is above max_matmul_dim = {}", dim, max)))
+            }
+            None => Ok(()),
+        }
+    }
+
+    // Untyped handles pass, unless built with `strict-checks` and they don't
+    // hold whole `expected` elements; typed ones must hold `expected`.
+    pub(crate) fn check_type(&self, h: Handle, expected: ElementType) -> Result<(), HostError> {
+        if checks::FAST {
+            return Ok(());
+        }
+        match self.element_types.get(&h) {
+            Some(&actual) if actual != expected => Err(HostError::TypeMismatch),
+            None if checks::STRICT && self.buffer_len(h)? % element_size(expected) as u64 != 0 => Err(HostError::Misaligned),
+            _ => Ok(()),
+        }
+    }
+
+    // When a compute call starting now must give up: the stricter of the
+    // guest's timeout and the session's cap.
+    pub(crate) fn op_deadline(&self) -> Option<Instant> {
+        let cap = self.active_session.as_ref().and_then(|s| s.op_timeout);
+        let timeout = match (self.op_timeout, cap) {
+            (Some(own), Some(cap)) => Some(own.min(cap)),
+            (own, cap) => own.or(cap),
+        };
+        timeout.map(|t| Instant::now() + t)
+    }
+
+    // Applies the active session's rate limits to a call moving `bytes` of payload.
+    pub(crate) fn charge(&mut self, bytes: u64) -> Result<(), HostError> {
+        // Every fallible call passes through here first, which makes it the
+        // place to reclaim buffers whose TTL has run out.
+        self.reap_expired();
+        self.enforce_memory_budget();
+        let Some(session) = self.active_session.as_mut() else {
+            return Ok(());
+        };
+        session.charge(bytes)?;
+        for (kind, remaining_percent) in session.quota_warnings() {
+            self.emit(Event::QuotaWarning { kind, remaining_percent });
+        }
+        Ok(())
+    }
+
+    pub(crate) fn contains(&self, h: Handle) -> bool {
+        self.buffers.contains_key(&h) || self.pending.contains_key(&h) || self.spill.spilled.contains_key(&h)
+    }
+
+    // Byte size of a buffer, or the size a lazy result will have.
+    pub(crate) fn buffer_len(&self, h: Handle) -> Result<u64, HostError> {
+        match (self.buffers.get(&h), self.matrix_dims.get(&h)) {
+            (Some(buffer), _) => Ok(buffer.len() as u64),
+            (None, _) if self.spill.spilled.contains_key(&h) => Ok(self.spill.spilled[&h]),
+            (None, Some(dims)) if self.pending.contains_key(&h) => Ok(dims.rows as u64 * dims.cols as u64 * codec::F32_SIZE as u64),
+            _ => Err(self.missing(h)),
+        }
+    }
+
+    // Drops everything the provider tracks for `h`. Returns false if the handle was unknown.
+    pub(crate) fn release(&mut self, h: Handle) -> bool {
+        if let Err(e) = self.materialize_dependents(h) {
+            // The dependent keeps its dims but will fail on read; nothing better to do here.
+            log!(Warn, "Failed to materialize dependents of {} before freeing it: {:?}", h, e);
+        }
+        self.matrix_dims.remove(&h);
+        self.element_types.remove(&h);
+        self.structures.remove(&h);
+        self.kv_caches.remove(&h);
+        self.evict_device_copies(h);
+        self.placements.remove(&h);
+        self.residency.remove(&h);
+        self.partials.remove(&h);
+        self.expiries.remove(&h);
+        self.node_bytes.remove(&h);
+        let was_pending = self.pending.remove(&h).is_some();
+        let was_spilled = self.drop_spilled(h);
+        let mut buffer = self.buffers.remove(&h);
+        if let (true, Some(buffer)) = (self.sensitive.remove(&h), buffer.as_mut()) {
+            sensitive::wipe(buffer);
+        }
+        buffer.is_some() || was_pending || was_spilled
+    }
+
+    // --- host-allocator ---
+
+    pub fn allocate_buffer(&mut self, size: u64) -> Result<Handle, HostError> {
+        self.allocate_buffer_with_storage(size, StorageKind::Heap)
+    }
+
+    pub fn free_buffer(&mut self, h: Handle) -> Result<(), HostError> {
+        log!(Debug, "Freeing buffer {}", h);
+        self.charge(0)?;
+        self.journal(h)?;
+        if self.release(h) {
+            Ok(())
+        } else {
+            Err(self.missing(h))
+        }
+    }
+
+    pub fn write_to_host(
+        &mut self,
+        guest_bytes: &[u8],
+        target_handle: Handle,
+        target_offset: u64,
+    ) -> Result<(), HostError> {
+        log!(Debug, "Writing {} bytes to handle {} at offset {}", guest_bytes.len(), target_handle, target_offset);
+        self.charge(guest_bytes.len() as u64)?;
+        self.materialize(target_handle)?;
+        self.materialize_dependents(target_handle)?;
+        self.journal(target_handle)?;
+        match self.buffers.get_mut(&target_handle) {
+            Some(buffer) => {
+                let (offset, end) = byte_range(target_offset, guest_bytes.len() as u64)?;
+                if end > buffer.len() {
+                    return Err(HostError::CopyOutOfBounds);
+                }
+                buffer[offset..end].copy_from_slice(guest_bytes);
+                self.trace_guest_write(target_handle);
+                Ok(())
+            }
+            None => Err(self.missing(target_handle)),
+        }
+    }
+
+    pub fn read_from_host(
+        &mut self,
+        source_handle: Handle,
+        source_offset: u64,
+        len: u64,
+    ) -> Result<Vec<u8>, HostError> {
+        log!(Debug, "Reading {} bytes from handle {} at offset {}", len, source_handle, source_offset);
+        self.charge(len)?;
+        self.materialize(source_handle)?;
+        let bytes = match self.buffers.get(&source_handle) {
+            Some(buffer) => {
+                let (offset, end) = byte_range(source_offset, len)?;
+                if end > buffer.len() {
+                    return Err(HostError::CopyOutOfBounds);
+                }
+                buffer[offset..end].to_vec()
+            }
+            None => return Err(self.missing(source_handle)),
+        };
+        self.trace_guest_read(source_handle);
+        Ok(bytes)
+    }
+
+    pub fn write_f32(&mut self, h: Handle, offset_in_elems: u64, values: &[f32]) -> Result<(), HostError> {
+        let offset = self.element_offset(h, offset_in_elems, ElementType::F32)?;
+        self.write_to_host(&codec::f32_to_le_bytes(values), h, offset)
+    }
+
+    pub fn read_f32_elems(&mut self, h: Handle, offset_in_elems: u64, count: u64) -> Result<Vec<f32>, HostError> {
+        let offset = self.element_offset(h, offset_in_elems, ElementType::F32)?;
+        let bytes = self.read_from_host(h, offset, element_bytes(count, codec::F32_SIZE)?)?;
+        Ok(codec::f32_from_le_bytes(&bytes).unwrap())
+    }
+
+    pub fn write_f64(&mut self, h: Handle, offset_in_elems: u64, values: &[f64]) -> Result<(), HostError> {
+        let offset = self.element_offset(h, offset_in_elems, ElementType::F64)?;
+        self.write_to_host(&codec::f64_to_le_bytes(values), h, offset)
+    }
+
+    pub fn read_f64_elems(&mut self, h: Handle, offset_in_elems: u64, count: u64) -> Result<Vec<f64>, HostError> {
+        let offset = self.element_offset(h, offset_in_elems, ElementType::F64)?;
+        let bytes = self.read_from_host(h, offset, element_bytes(count, codec::F64_SIZE)?)?;
+        Ok(codec::f64_from_le_bytes(&bytes).unwrap())
+    }
+
+    pub fn write_i32(&mut self, h: Handle, offset_in_elems: u64, values: &[i32]) -> Result<(), HostError> {
+        let offset = self.element_offset(h, offset_in_elems, ElementType::S32)?;
+        self.write_to_host(&codec::i32_to_le_bytes(values), h, offset)
+    }
+
+    pub fn read_i32_elems(&mut self, h: Handle, offset_in_elems: u64, count: u64) -> Result<Vec<i32>, HostError> {
+        let offset = self.element_offset(h, offset_in_elems, ElementType::S32)?;
+        let bytes = self.read_from_host(h, offset, element_bytes(count, codec::I32_SIZE)?)?;
+        Ok(codec::i32_from_le_bytes(&bytes).unwrap())
+    }
+
+    // Byte offset of element `offset_in_elems`, after checking the buffer's
+    // registered type and that it holds a whole number of elements.
+    fn element_offset(&self, h: Handle, offset_in_elems: u64, elem: ElementType) -> Result<u64, HostError> {
+        let elem_size = element_size(elem);
+        if !checks::FAST && self.buffer_len(h)? % elem_size as u64 != 0 {
+            return Err(HostError::Misaligned);
+        }
+        self.check_type(h, elem)?;
+        element_bytes(offset_in_elems, elem_size)
+    }
+
+    pub fn allocate_typed_buffer(&mut self, elem: ElementType, count: u64) -> Result<Handle, HostError> {
+        let size = element_bytes(count, element_size(elem))?;
+        let handle = self.allocate_buffer(size)?;
+        self.element_types.insert(handle, elem);
+        Ok(handle)
+    }
+
+    pub fn register_tensor_meta(&mut self, h: Handle, meta: TensorMeta) -> Result<(), HostError> {
+        log!(Debug, "Registering element type {:?} for handle {}", meta.element_type, h);
+        if let Some(dims) = meta.dims {
+            self.register_matrix_shape(h, dims)?;
+        } else {
+            self.charge(0)?;
+        }
+        if self.buffer_len(h)? % element_size(meta.element_type) as u64 != 0 {
+            return Err(HostError::Misaligned);
+        }
+        self.journal(h)?;
+        self.element_types.insert(h, meta.element_type);
+        Ok(())
+    }
+
+    pub fn get_element_type(&mut self, h: Handle) -> Result<Option<ElementType>, HostError> {
+        self.charge(0)?;
+        if !self.contains(h) {
+            return Err(self.missing(h));
+        }
+        Ok(self.element_types.get(&h).copied())
+    }
+
+    // `register-matrix-dimensions` as in 0.1.0: always row-major.
+    pub fn register_matrix_dimensions(&mut self, h: Handle, dims: MatrixDimensions) -> Result<(), HostError> {
+        self.register_matrix_shape(h, MatrixShape { rows: dims.rows, cols: dims.cols, layout: MatrixLayout::RowMajor })
+    }
+
+    pub fn register_matrix_shape(&mut self, h: Handle, dims: MatrixShape) -> Result<(), HostError> {
+        log!(Debug, "Registering dimensions {}x{} ({:?}) for handle {}", dims.rows, dims.cols, dims.layout, h);
+        self.charge(0)?;
+        if !self.contains(h) {
+            return Err(self.missing(h));
+        }
+        self.materialize(h)?;
+        self.materialize_dependents(h)?;
+        // Otherwise a mismatch only shows once an op reads the buffer.
+        if checks::STRICT {
+            if let Some(&elem) = self.element_types.get(&h) {
+                if self.buffer_len(h)? != dims.rows as u64 * dims.cols as u64 * element_size(elem) as u64 {
+                    return Err(HostError::DimensionMismatch);
+                }
+            }
+        }
+        self.journal(h)?;
+        self.matrix_dims.insert(h, dims);
+        self.structures.remove(&h);
+        Ok(())
+    }
+
+    pub fn matrix_multiply_f32(
+        &mut self,
+        handle_a: Handle,
+        handle_b: Handle,
+        on: Option<Device>,
+    ) -> Result<Handle, HostError> {
+        log!(Debug, "Matrix multiply f32 for A:{} and B:{}", handle_a, handle_b);
+        self.charge(0)?;
+        let device = on.or_else(|| self.placements.get(&handle_a).copied()).unwrap_or(Device::Cpu);
+        self.check_device(device)?;
+
+        if self.evaluation_mode == EvaluationMode::Lazy && !self.dry_run {
+            let handle_c = self.defer_matmul_f32(handle_a, handle_b, device)?;
+            log!(Debug, "Deferred result C with lazy handle {}", handle_c);
+            self.trace("matrix-multiply-f32", &[handle_a, handle_b], &[handle_c]);
+            return Ok(handle_c);
+        }
+
+        self.materialize(handle_a)?;
+        self.materialize(handle_b)?;
+        let dims_a = self.check_matrix(handle_a, ElementType::F32)?;
+        let dims_b = self.check_matrix(handle_b, ElementType::F32)?;
+
+        if dims_a.cols != dims_b.rows {
+            return Err(HostError::DimensionMismatch);
+        }
+        self.check_matmul_dims([dims_a.rows, dims_a.cols, dims_b.cols].map(|dim| dim as usize))?;
+
+        let (bytes, per_node) = match self.dry_run {
+            true => (vec![0; dims_a.rows as usize * dims_b.cols as usize * codec::F32_SIZE], Vec::new()),
+            false => self.matmul_f32_on(device, handle_a, handle_b, dims_a.layout)?,
+        };
+        let handle_c = self.new_handle();
+        let dims_c = MatrixShape { rows: dims_a.rows, cols: dims_b.cols, layout: dims_a.layout };
+        self.buffers.insert(handle_c, bytes.into());
+        self.matrix_dims.insert(handle_c, dims_c);
+        self.element_types.insert(handle_c, ElementType::F32);
+        self.record_nodes(handle_c, per_node);
+        if device != Device::Cpu {
+            self.placements.insert(handle_c, device);
+        }
+        log!(Debug, "Stored result C ({},{}) with handle {}", dims_c.rows, dims_c.cols, handle_c);
+        self.trace("matrix-multiply-f32", &[handle_a, handle_b], &[handle_c]);
+        Ok(handle_c)
+    }
+
+    // Behind the `matmul-f64` op. Always eager, and on the device `a` is
+    // placed on.
+    pub(crate) fn matrix_multiply_f64(&mut self, handle_a: Handle, handle_b: Handle) -> Result<Handle, HostError> {
+        log!(Debug, "Matrix multiply f64 for A:{} and B:{}", handle_a, handle_b);
+        let device = self.placements.get(&handle_a).copied().unwrap_or(Device::Cpu);
+        self.check_device(device)?;
+        self.materialize(handle_a)?;
+        self.materialize(handle_b)?;
+        let dims_a = self.check_matrix(handle_a, ElementType::F64)?;
+        let dims_b = self.check_matrix(handle_b, ElementType::F64)?;
+        if dims_a.cols != dims_b.rows {
+            return Err(HostError::DimensionMismatch);
+        }
+        self.check_matmul_dims([dims_a.rows, dims_a.cols, dims_b.cols].map(|dim| dim as usize))?;
+
+        let bytes = match device {
+            Device::Gpu(n) if self.compute_mode == ComputeMode::Fast => self.gpu_matmul(n, handle_a, handle_b, ElementType::F64)?,
+            _ => {
+                let a = matrix_from_slice(dims_a, &codec::f64_from_le_bytes(&self.buffers[&handle_a]).unwrap());
+                let b = matrix_from_slice(dims_b, &codec::f64_from_le_bytes(&self.buffers[&handle_b]).unwrap());
+                let c = a * b;
+                match dims_a.layout {
+                    MatrixLayout::RowMajor => codec::f64_to_le_bytes(c.transpose().as_slice()),
+                    MatrixLayout::ColumnMajor => codec::f64_to_le_bytes(c.as_slice()),
+                }
+            }
+        };
+        let handle_c = self.new_handle();
+        self.buffers.insert(handle_c, bytes.into());
+        self.matrix_dims.insert(handle_c, MatrixShape { rows: dims_a.rows, cols: dims_b.cols, layout: dims_a.layout });
+        self.element_types.insert(handle_c, ElementType::F64);
+        if device != Device::Cpu {
+            self.placements.insert(handle_c, device);
+        }
+        Ok(handle_c)
+    }
+
+    pub fn get_matrix_dimensions(&mut self, h: Handle) -> Result<MatrixDimensions, HostError> {
+        self.get_matrix_shape(h).map(|shape| MatrixDimensions { rows: shape.rows, cols: shape.cols })
+    }
+
+    pub fn get_matrix_shape(&mut self, h: Handle) -> Result<MatrixShape, HostError> {
+        log!(Debug, "Getting dimensions for handle {}", h);
+        self.charge(0)?;
+        match self.matrix_dims.get(&h) {
+            Some(&dims) => Ok(dims),
+            None => Err(self.missing(h)),
+        }
+    }
+
+    pub fn set_compute_mode(&mut self, mode: ComputeMode) {
+        log!(Debug, "Setting compute mode to {:?}", mode);
+        self.compute_mode = mode;
+    }
+
+    pub fn set_compute_hint(&mut self, hint: ComputeHint) {
+        log!(Debug, "Setting compute hint to {:?}", hint);
+        self.compute_hint = hint;
+        if let Some(gpu) = self.gpu.as_mut() {
+            gpu.set_hint(hint);
+        }
+    }
+
+    pub fn set_op_timeout(&mut self, millis: Option<u64>) {
+        log!(Debug, "Setting operation timeout to {:?} ms", millis);
+        self.op_timeout = millis.map(Duration::from_millis);
+    }
+
+    pub fn get_backend_info(&self) -> BackendInfo {
+        BackendInfo {
+            name: self.backend_name().to_string(),
+            compute_mode: self.compute_mode,
+        }
+    }
+
+    pub fn compare_buffers_f32(
+        &mut self,
+        handle_a: Handle,
+        handle_b: Handle,
+        rtol: f32,
+        atol: f32,
+    ) -> Result<ComparisonReport, HostError> {
+        log!(Debug, "Comparing f32 buffers {} and {} (rtol={}, atol={})", handle_a, handle_b, rtol, atol);
+        self.charge(0)?;
+        self.materialize(handle_a)?;
+        self.materialize(handle_b)?;
+        let a = self.read_f32(handle_a)?;
+        let b = self.read_f32(handle_b)?;
+        if a.len() != b.len() {
+            return Err(HostError::DimensionMismatch);
+        }
+        Ok(kernels::compare_f32(&a, &b, rtol, atol))
+    }
+
+    pub fn axpy_f32(&mut self, alpha: f32, x: Handle, y: Handle) -> Result<(), HostError> {
+        log!(Debug, "axpy f32: {} <- {} * {} + {}", y, alpha, x, y);
+        self.charge(0)?;
+        self.materialize(x)?;
+        let x_data = self.read_f32(x)?;
+        let mut y_data = self.read_output_f32(y)?;
+        if x_data.len() != y_data.len() {
+            return Err(HostError::DimensionMismatch);
+        }
+        kernels::axpy_f32(alpha, &x_data, &mut y_data);
+        self.store_bytes(y, codec::f32_to_le_bytes(&y_data));
+        self.trace("axpy-f32", &[x, y], &[y]);
+        Ok(())
+    }
+
+    pub fn scal_f32(&mut self, alpha: f32, x: Handle) -> Result<(), HostError> {
+        log!(Debug, "scal f32: {} <- {} * {}", x, alpha, x);
+        self.charge(0)?;
+        let mut x_data = self.read_output_f32(x)?;
+        kernels::scal_f32(alpha, &mut x_data);
+        self.store_bytes(x, codec::f32_to_le_bytes(&x_data));
+        self.trace("scal-f32", &[x], &[x]);
+        Ok(())
+    }
+
+    pub fn gemv_f32(&mut self, alpha: f32, a: Handle, x: Handle, beta: f32, y: Handle) -> Result<(), HostError> {
+        log!(Debug, "gemv f32: {} <- {} * {} * {} + {} * {}", y, alpha, a, x, beta, y);
+        self.charge(0)?;
+        self.materialize(a)?;
+        if let Some(MatrixStructure::Banded(bands)) = self.structures.get(&a).copied() {
+            return self.banded_gemv(alpha, a, bands, x, beta, y);
+        }
+        self.materialize(x)?;
+        let (_, matrix_a) = self.read_matrix_f32(a)?;
+        let x_data = self.read_f32(x)?;
+        let mut y_data = self.read_output_f32(y)?;
+        if x_data.len() != matrix_a.ncols() || y_data.len() != matrix_a.nrows() {
+            return Err(HostError::DimensionMismatch);
+        }
+        if self.dry_run {
+            return Ok(());
+        }
+        kernels::gemv_f32(alpha, &matrix_a, &x_data, beta, &mut y_data, self.compute_mode);
+        self.store_bytes(y, codec::f32_to_le_bytes(&y_data));
+        Ok(())
+    }
+
+    pub fn matmul_accumulate(&mut self, a: Handle, b: Handle, c: Handle) -> Result<(), HostError> {
+        log!(Debug, "Matrix multiply-accumulate f32: {} += {} * {}", c, a, b);
+        self.charge(0)?;
+        let device = self.placements.get(&c).copied().unwrap_or(Device::Cpu);
+        self.check_device(device)?;
+        for h in [a, b, c] {
+            self.materialize(h)?;
+        }
+        let dims_a = self.check_matrix(a, ElementType::F32)?;
+        let dims_b = self.check_matrix(b, ElementType::F32)?;
+        let dims_c = self.check_matrix(c, ElementType::F32)?;
+        if dims_a.cols != dims_b.rows || (dims_c.rows, dims_c.cols) != (dims_a.rows, dims_b.cols) {
+            return Err(HostError::DimensionMismatch);
+        }
+        self.check_matmul_dims([dims_a.rows, dims_a.cols, dims_b.cols].map(|dim| dim as usize))?;
+        if self.dry_run {
+            return Ok(());
+        }
+        // Computed before `c` is touched, so `c` may also be an operand.
+        let (product, _) = self.matmul_f32_on(device, a, b, dims_c.layout)?;
+        let product = codec::f32_from_le_bytes(&product)
+            .ok_or_else(|| HostError::ComputationError("Product is not a whole number of f32 elements".to_string()))?;
+        let mut c_data = self.read_output_f32(c)?;
+        kernels::axpy_f32(1.0, &product, &mut c_data);
+        self.store_bytes(c, codec::f32_to_le_bytes(&c_data));
+        self.trace("matmul-accumulate", &[a, b, c], &[c]);
+        Ok(())
+    }
+
+    // Reads a buffer an in-place operation is about to overwrite.
+    pub(crate) fn read_output_f32(&mut self, h: Handle) -> Result<Vec<f32>, HostError> {
+        self.materialize(h)?;
+        self.materialize_dependents(h)?;
+        self.journal(h)?;
+        self.read_f32(h)
+    }
+
+    pub fn dump_matrix(&mut self, h: Handle, format: DumpFormat, destination: DumpDestination) -> Result<(), HostError> {
+        log!(Debug, "Dumping matrix {} as {:?}", h, format);
+        self.charge(0)?;
+        self.refuse_sensitive(h, "dumped")?;
+        self.materialize(h)?;
+        let (_, matrix) = self.read_matrix_f32(h)?;
+        let bytes = dump::render(&matrix, format);
+        match destination {
+            DumpDestination::Stderr => {
+                use std::io::Write;
+                std::io::stderr()
+                    .write_all(&bytes)
+                    .map_err(|e| HostError::Other(format!("Failed to write dump to stderr: {}", e)))
+            }
+            // Written aside and renamed into place, so an interrupted dump
+            // never leaves a truncated file behind.
+            DumpDestination::File(path) => {
+                let partial = format!("{}.partial", path);
+                std::fs::write(&partial, &bytes)
+                    .and_then(|_| std::fs::rename(&partial, &path))
+                    .map_err(|e| HostError::Other(format!("Failed to write dump to {}: {}", path, e)))
+            }
+        }
+    }
+
+    pub fn hash_buffer(&mut self, h: Handle, algo: HashAlgorithm) -> Result<Vec<u8>, HostError> {
+        log!(Debug, "Hashing buffer {} with {:?}", h, algo);
+        self.charge(0)?;
+        self.materialize(h)?;
+        let bytes = self.buffers.get(&h).ok_or_else(|| self.missing(h))?;
+        Ok(hash::digest(bytes, algo))
+    }
+
+    pub fn begin_arena(&mut self) -> ArenaId {
+        let arena = self.next_arena;
+        self.next_arena += 1;
+        self.arenas.insert(arena, Vec::new());
+        self.arena_stack.push(arena);
+        log!(Debug, "Began arena {}", arena);
+        arena
+    }
+
+    pub fn end_arena(&mut self, arena: ArenaId) -> Result<(), HostError> {
+        log!(Debug, "Ending arena {}", arena);
+        self.charge(0)?;
+        let position = self.arena_stack.iter().position(|&a| a == arena)
+            .ok_or(HostError::InvalidArena)?;
+        // Arenas opened inside this one end with it.
+        let ended: Vec<ArenaId> = self.arena_stack.drain(position..).collect();
+        for a in ended {
+            for h in self.arenas.remove(&a).unwrap_or_default() {
+                // Handles freed explicitly before the arena ended are skipped.
+                self.release(h);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn set_placement(&mut self, h: Handle, target: Device) -> Result<(), HostError> {
+        log!(Debug, "Placing handle {} on {:?}", h, target);
+        self.charge(0)?;
+        if !self.contains(h) {
+            return Err(self.missing(h));
+        }
+        self.check_device(target)?;
+        self.upload_to(target, h)?;
+        self.placements.insert(h, target);
+        mark_resident(self.residency.entry(h).or_default(), target);
+        Ok(())
+    }
+
+    pub fn prefetch(&mut self, h: Handle, target: Device) -> Result<(), HostError> {
+        log!(Debug, "Prefetching handle {} to {:?}", h, target);
+        self.charge(0)?;
+        if !self.contains(h) {
+            return Err(self.missing(h));
+        }
+        self.check_device(target)?;
+        self.materialize(h)?;
+        self.upload_to(target, h)?;
+        mark_resident(self.residency.entry(h).or_default(), target);
+        Ok(())
+    }
+
+    pub fn describe_handle(&mut self, h: Handle) -> Result<HandleInfo, HostError> {
+        self.charge(0)?;
+        // Lazy results have no buffer yet; report the size they will have.
+        let size = self.buffer_len(h)?;
+        // Host RAM always holds the authoritative copy in this provider.
+        let mut resident_on = vec![Device::Cpu];
+        for &device in self.residency.get(&h).into_iter().flatten() {
+            mark_resident(&mut resident_on, device);
+        }
+        Ok(HandleInfo {
+            size,
+            dims: self.matrix_dims.get(&h).copied(),
+            element_type: self.element_types.get(&h).copied(),
+            placement: self.placements.get(&h).copied().unwrap_or(Device::Cpu),
+            resident_on,
+        })
+    }
+
+    pub fn execute_graph(&mut self, g: &Graph) -> Result<Vec<Handle>, HostError> {
+        log!(Debug, "Executing graph with {} nodes, {} outputs", g.nodes.len(), g.outputs.len());
+        self.charge(0)?;
+        for op in &g.nodes {
+            for input in graph::op_inputs(op) {
+                if let GraphInput::Handle(h) = input {
+                    self.materialize(*h)?;
+                }
+            }
+        }
+        let outputs = graph::execute(self, g)?;
+        // Only requested outputs are materialized; intermediates never get a handle.
+        Ok(outputs.iter().map(|m| self.store_matrix_f32(m, MatrixLayout::RowMajor)).collect())
+    }
+
+    pub fn set_evaluation_mode(&mut self, mode: EvaluationMode) {
+        log!(Debug, "Setting evaluation mode to {:?}", mode);
+        self.evaluation_mode = mode;
+    }
+
+    pub fn set_dry_run(&mut self, enabled: bool) {
+        log!(Debug, "Dry run {}", if enabled { "on" } else { "off" });
+        self.dry_run = enabled;
+    }
+
+    // Entry point for the `materialize` export; see `lazy.rs` for the mechanics.
+    pub fn materialize_handle(&mut self, h: Handle) -> Result<(), HostError> {
+        self.charge(0)?;
+        if !self.contains(h) {
+            return Err(self.missing(h));
+        }
+        self.materialize(h)
+    }
+
+    pub fn is_materialized(&mut self, h: Handle) -> Result<bool, HostError> {
+        self.charge(0)?;
+        if self.pending.contains_key(&h) {
+            Ok(false)
+        } else if self.buffers.contains_key(&h) || self.spill.spilled.contains_key(&h) {
+            // A spilled buffer was computed; it only has to be reloaded.
+            Ok(true)
+        } else {
+            Err(self.missing(h))
+        }
+    }
+
+    pub fn submit_matmul_f32(&mut self, handle_a: Handle, handle_b: Handle) -> Result<JobId, HostError> {
+        self.charge(0)?;
+        let device = self.placements.get(&handle_a).copied().unwrap_or(Device::Cpu);
+        let handle_c = self.defer_matmul_f32(handle_a, handle_b, device)?;
+        let job = self.next_job;
+        self.next_job += 1;
+        self.jobs.insert(job, handle_c);
+        log!(Debug, "Submitted job {} for A:{} x B:{} -> {}", job, handle_a, handle_b, handle_c);
+        Ok(job)
+    }
+
+    pub fn job_status(&mut self, job: JobId) -> Result<JobState, HostError> {
+        self.charge(0)?;
+        let h = *self.jobs.get(&job).ok_or(HostError::InvalidJob)?;
+        if self.pending.contains_key(&h) {
+            Ok(JobState::Pending)
+        } else if self.buffers.contains_key(&h) {
+            Ok(JobState::Done)
+        } else {
+            // The result handle was freed before the job was waited on.
+            Err(self.missing(h))
+        }
+    }
+
+    pub fn poll_job(&mut self, job: JobId) -> Result<JobProgress, HostError> {
+        self.charge(0)?;
+        let h = *self.jobs.get(&job).ok_or(HostError::InvalidJob)?;
+        if !self.contains(h) {
+            return Err(self.missing(h));
+        }
+        let percent = self.step(h)?;
+        let state = if percent == 100 { JobState::Done } else { JobState::Pending };
+        Ok(JobProgress { state, percent })
+    }
+
+    // One step of `job` for the native host's job worker, which isn't the
+    // guest's call and so isn't charged. `None` once there is nothing left to
+    // do: the job is done, gone (waited on, or its result freed), or its step
+    // failed, which `wait-job` then reports.
+    pub(crate) fn advance_job(&mut self, job: JobId) -> Option<u8> {
+        let h = *self.jobs.get(&job)?;
+        if !self.contains(h) {
+            return None;
+        }
+        self.step(h).ok().filter(|&percent| percent < 100)
+    }
+
+    pub fn wait_job(&mut self, job: JobId) -> Result<Handle, HostError> {
+        log!(Debug, "Waiting for job {}", job);
+        self.charge(0)?;
+        let h = self.jobs.remove(&job).ok_or(HostError::InvalidJob)?;
+        if !self.contains(h) {
+            return Err(self.missing(h));
+        }
+        self.materialize(h)?;
+        Ok(h)
+    }
+
+    pub fn list_devices(&self) -> Vec<DeviceInfo> {
+        let cpu = DeviceInfo {
+            device: Device::Cpu,
+            name: "host cpu".to_string(),
+            backend: self.backend_name().to_string(),
+            // The provider can't see the host's RAM from inside wasm.
+            memory_bytes: None,
+        };
+        std::iter::once(cpu).chain(self.gpu_devices()).collect()
+    }
+
+    pub fn get_interface_version(&self) -> InterfaceVersion {
+        INTERFACE_VERSION
+    }
+
+    pub fn supports(&self, feature: &str) -> bool {
+        // Only Unix builds have the non-heap storage kinds.
+        FEATURES.contains(&feature)
+            || checks::feature() == Some(feature)
+            || (cfg!(unix) && matches!(feature, "mmap" | "shared-memory"))
+            || (feature == "gpu" && !self.gpu_devices().is_empty())
+    }
+
+    // --- session-admin ---
+
+    pub fn open_session(&mut self, limits: SessionLimits) -> Result<SessionId, String> {
+        if let Some(session) = &self.active_session {
+            return Err(format!("Session {} is still active", session.id));
+        }
+        if limits.max_ops_per_sec == Some(0) || limits.max_bytes_per_sec == Some(0) {
+            return Err("A rate limit of 0 would refuse every call; leave it unset for no limit".to_string());
+        }
+        let id = self.next_session;
+        self.next_session += 1;
+        self.active_session = Some(Session::new(id, limits));
+        log!(Info, "Opened session {}", id);
+        Ok(id)
+    }
+
+    pub fn close_session(&mut self, id: SessionId) -> Result<u32, String> {
+        match self.active_session.take() {
+            Some(session) if session.id == id => {
+                let freed = session.handles.into_iter().filter(|&h| self.release(h)).count() as u32;
+                // Every job's result belonged to the session, so releasing
+                // it above already dropped any partial progress.
+                if !self.jobs.is_empty() {
+                    log!(Info, "Cancelled {} jobs nobody waited on", self.jobs.len());
+                    self.jobs.clear();
+                }
+                // Arenas the guest left open can't outlive its session.
+                self.arena_stack.clear();
+                self.arenas.clear();
+                self.op_timeout = None;
+                self.expired.clear();
+                self.transaction = None;
+                log!(Info, "Closed session {}, freed {} leaked handles", id, freed);
+                Ok(freed)
+            }
+            other => {
+                self.active_session = other;
+                Err(format!("Session {} is not the active session", id))
+            }
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn session_usage(&self, id: SessionId) -> Result<SessionUsage, String> {
+        match &self.active_session {
+            Some(session) if session.id == id => Ok(session.share.usage()),
+            _ => Err(format!("Session {} is not the active session", id)),
+        }
+    }
+
+    // The active session's turns at the compute slots; none outside sessions.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn compute_share(&self) -> Option<Arc<Share>> {
+        self.active_session.as_ref().map(|session| session.share.clone())
+    }
+
+    pub fn set_log_level(&mut self, level: LogLevel) {
+        crate::log::set_level(level);
+        log!(Debug, "Log level set to {:?}", level);
+    }
+}
+
+// `offset..offset + len` as indices, or `copy-out-of-bounds` if it can't even be
+// addressed on this target (which a plain `as usize` would silently wrap).
+pub(crate) fn byte_range(offset: u64, len: u64) -> Result<(usize, usize), HostError> {
+    let end = offset.checked_add(len).ok_or(HostError::CopyOutOfBounds)?;
+    let end = usize::try_from(end).map_err(|_| HostError::CopyOutOfBounds)?;
+    Ok((offset as usize, end))
+}
+
+pub(crate) fn element_size(elem: ElementType) -> usize {
+    match elem {
+        ElementType::U8 | ElementType::S8 => 1,
+        ElementType::F16 => 2,
+        ElementType::S32 => codec::I32_SIZE,
+        ElementType::F32 => codec::F32_SIZE,
+        ElementType::F64 => codec::F64_SIZE,
+    }
+}
+
+fn element_bytes(elems: u64, elem_size: usize) -> Result<u64, HostError> {
+    elems.checked_mul(elem_size as u64).ok_or(HostError::CopyOutOfBounds)
+}
+
+// The nalgebra backend computes in host RAM only.
+fn mark_resident(resident_on: &mut Vec<Device>, device: Device) {
+    if !resident_on.contains(&device) {
+        resident_on.push(device);
+    }
+}
+
+pub(crate) fn matrix_from_slice<T: nalgebra::Scalar>(dims: MatrixShape, data: &[T]) -> nalgebra::DMatrix<T> {
+    let (rows, cols) = (dims.rows as usize, dims.cols as usize);
+    match dims.layout {
+        MatrixLayout::RowMajor => nalgebra::DMatrix::from_row_slice(rows, cols, data),
+        MatrixLayout::ColumnMajor => nalgebra::DMatrix::from_column_slice(rows, cols, data),
+    }
+}
+
+// nalgebra stores matrices column-major, so row-major output needs a transpose.
+pub(crate) fn matrix_to_bytes(matrix: &nalgebra::DMatrix<f32>, layout: MatrixLayout) -> Vec<u8> {
+    match layout {
+        MatrixLayout::RowMajor => codec::f32_to_le_bytes(matrix.transpose().as_slice()),
+        MatrixLayout::ColumnMajor => codec::f32_to_le_bytes(matrix.as_slice()),
+    }
+}
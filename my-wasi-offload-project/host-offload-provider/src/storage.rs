@@ -0,0 +1,331 @@
+use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
+
+use zeroize::Zeroize;
+
+use crate::sensitive;
+use crate::wasi_custom::host_offload::host_allocator::{
+    AllocationLimit, Handle, HostError, ShmAccess, ShmDescriptor, StorageKind,
+};
+use crate::HostState;
+
+// Where one buffer's bytes live: a fixed-size, zero-initialized region the
+// rest of the provider only ever sees as a slice.
+pub trait BufferStorage: Send {
+    fn bytes(&self) -> &[u8];
+    fn bytes_mut(&mut self) -> &mut [u8];
+    fn kind(&self) -> StorageKind;
+
+    // For storage other processes can map by name: lets them open it with
+    // permission bits `mode` (0 for nobody) and returns the name.
+    fn share(&mut self, _mode: u32) -> Option<std::io::Result<&str>> {
+        None
+    }
+
+    // Whether anything else holds these same bytes, so writing them in place
+    // would write a copy instead.
+    fn is_aliased(&self) -> bool {
+        false
+    }
+}
+
+impl BufferStorage for Vec<u8> {
+    fn bytes(&self) -> &[u8] {
+        self
+    }
+
+    fn bytes_mut(&mut self) -> &mut [u8] {
+        self
+    }
+
+    fn kind(&self) -> StorageKind {
+        StorageKind::Heap
+    }
+}
+
+// Bytes several buffers share (see `intern`). Writing through one of them
+// first gives it a copy of its own.
+impl BufferStorage for Arc<Vec<u8>> {
+    fn bytes(&self) -> &[u8] {
+        self
+    }
+
+    fn bytes_mut(&mut self) -> &mut [u8] {
+        Arc::make_mut(self)
+    }
+
+    fn kind(&self) -> StorageKind {
+        StorageKind::Heap
+    }
+
+    fn is_aliased(&self) -> bool {
+        Arc::strong_count(self) > 1
+    }
+}
+
+// A buffer as `HostState` holds it. Compute results and anything else built
+// in a `Vec` are heap buffers; only allocations with a hint get other kinds.
+pub(crate) struct Buffer(Box<dyn BufferStorage>);
+
+impl Buffer {
+    pub(crate) fn kind(&self) -> StorageKind {
+        self.0.kind()
+    }
+
+    pub(crate) fn share(&mut self, mode: u32) -> Option<std::io::Result<&str>> {
+        self.0.share(mode)
+    }
+
+    pub(crate) fn is_aliased(&self) -> bool {
+        self.0.is_aliased()
+    }
+}
+
+impl From<Vec<u8>> for Buffer {
+    fn from(bytes: Vec<u8>) -> Self {
+        Buffer(Box::new(bytes))
+    }
+}
+
+impl From<Arc<Vec<u8>>> for Buffer {
+    fn from(bytes: Arc<Vec<u8>>) -> Self {
+        Buffer(Box::new(bytes))
+    }
+}
+
+impl Deref for Buffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        self.0.bytes()
+    }
+}
+
+impl DerefMut for Buffer {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        self.0.bytes_mut()
+    }
+}
+
+impl AsRef<[u8]> for Buffer {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+// `size` zeroed bytes of the kind asked for. Kinds this build can't provide
+// (anything but the heap in a wasm provider or on non-Unix hosts) fall back
+// to the heap.
+pub(crate) fn allocate(size: usize, hint: StorageKind) -> Result<Buffer, HostError> {
+    match hint {
+        #[cfg(unix)]
+        StorageKind::Mmap => Ok(Buffer(Box::new(unix::Mapping::anonymous(size)?))),
+        #[cfg(unix)]
+        StorageKind::SharedMemory => Ok(Buffer(Box::new(unix::SharedMemory::create(size)?))),
+        _ => {
+            let mut buffer = Vec::new();
+            buffer.try_reserve_exact(size).map_err(|_| HostError::AllocationFailed)?;
+            buffer.resize(size, 0u8);
+            Ok(buffer.into())
+        }
+    }
+}
+
+impl HostState {
+    // A hint: the buffer may end up on the heap anyway (see `allocate`).
+    pub fn allocate_buffer_with_storage(&mut self, size: u64, hint: StorageKind) -> Result<Handle, HostError> {
+        log!(Debug, "Allocating buffer of size {} ({:?})", size, hint);
+        if size == 0 {
+            return Err(HostError::Other("Cannot allocate zero-size buffer".to_string()));
+        }
+        if size > self.max_allocation {
+            return Err(HostError::AllocationTooLarge(AllocationLimit { requested: size, max: self.max_allocation }));
+        }
+        self.charge(0)?;
+        // `max_allocation` never exceeds `usize::MAX`, so this can't truncate.
+        let buffer = allocate(size as usize, hint)?;
+        let handle = self.new_handle();
+        self.buffers.insert(handle, buffer);
+        Ok(handle)
+    }
+
+    pub fn get_storage_kind(&mut self, h: Handle) -> Result<StorageKind, HostError> {
+        self.charge(0)?;
+        self.materialize(h)?;
+        self.buffers.get(&h).map(Buffer::kind).ok_or_else(|| self.missing(h))
+    }
+
+    pub fn export_shm(&mut self, h: Handle, access: ShmAccess) -> Result<ShmDescriptor, HostError> {
+        log!(Debug, "Exporting buffer {} ({:?})", h, access);
+        self.refuse_sensitive(h, "exported")?;
+        let writable = access.contains(ShmAccess::WRITE);
+        let mut mode = if writable { 0o600 } else { 0o400 };
+        if access.contains(ShmAccess::GROUP) {
+            mode |= mode >> 3;
+        }
+        let name = self.share(h, mode)?;
+        let size = self.buffer_len(h)?;
+        Ok(ShmDescriptor { name, size, writable })
+    }
+
+    pub fn revoke_shm(&mut self, h: Handle) -> Result<(), HostError> {
+        log!(Debug, "Revoking export of buffer {}", h);
+        self.share(h, 0).map(|_| ())
+    }
+
+    fn share(&mut self, h: Handle, mode: u32) -> Result<String, HostError> {
+        self.charge(0)?;
+        self.materialize(h)?;
+        let Some(buffer) = self.buffers.get_mut(&h) else {
+            return Err(self.missing(h));
+        };
+        match buffer.share(mode) {
+            Some(Ok(name)) => Ok(name.to_string()),
+            Some(Err(e)) => Err(HostError::Other(format!("Failed to change access to buffer {}: {}", h, e))),
+            None => Err(HostError::InvalidArguments(format!("Buffer {} is not in shared memory", h))),
+        }
+    }
+
+    // New contents for `h`: in place when the size is unchanged, so a mapped
+    // or shared buffer stays where it is, otherwise as a heap buffer. For a
+    // sensitive `h`, whichever copy isn't kept is wiped.
+    pub(crate) fn store_bytes(&mut self, h: Handle, mut bytes: Vec<u8>) {
+        self.evict_device_copies(h);
+        let sensitive = self.is_sensitive(h);
+        match self.buffers.get_mut(&h) {
+            Some(buffer) if buffer.len() == bytes.len() => {
+                buffer.copy_from_slice(&bytes);
+                if sensitive {
+                    bytes.zeroize();
+                }
+            }
+            _ => {
+                if let Some(mut old) = self.buffers.insert(h, bytes.into()) {
+                    if sensitive {
+                        sensitive::wipe(&mut old);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+pub(crate) mod unix {
+    use std::ffi::CString;
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    use super::BufferStorage;
+    use crate::wasi_custom::host_offload::host_allocator::{HostError, StorageKind};
+
+    // `len` bytes mapped read-write, unmapped on drop.
+    pub(crate) struct Mapping {
+        ptr: *mut u8,
+        len: usize,
+    }
+
+    // The mapping belongs to exactly one buffer, which only `HostState`'s
+    // lock hands out.
+    unsafe impl Send for Mapping {}
+
+    impl Mapping {
+        fn map(len: usize, flags: libc::c_int, fd: libc::c_int) -> Result<Self, HostError> {
+            let ptr = unsafe {
+                libc::mmap(std::ptr::null_mut(), len, libc::PROT_READ | libc::PROT_WRITE, flags, fd, 0)
+            };
+            if ptr == libc::MAP_FAILED {
+                return Err(HostError::AllocationFailed);
+            }
+            Ok(Mapping { ptr: ptr.cast(), len })
+        }
+
+        // Private and zero-filled; the kernel returns it whole on free.
+        pub(crate) fn anonymous(len: usize) -> Result<Self, HostError> {
+            Self::map(len, libc::MAP_PRIVATE | libc::MAP_ANONYMOUS, -1)
+        }
+    }
+
+    impl Drop for Mapping {
+        fn drop(&mut self) {
+            unsafe { libc::munmap(self.ptr.cast(), self.len) };
+        }
+    }
+
+    impl BufferStorage for Mapping {
+        fn bytes(&self) -> &[u8] {
+            unsafe { std::slice::from_raw_parts(self.ptr, self.len) }
+        }
+
+        fn bytes_mut(&mut self) -> &mut [u8] {
+            unsafe { std::slice::from_raw_parts_mut(self.ptr, self.len) }
+        }
+
+        fn kind(&self) -> StorageKind {
+            StorageKind::Mmap
+        }
+    }
+
+    // A POSIX shared-memory object of its own, mapped shared, so another
+    // process that opens the same name sees the provider's writes and the
+    // provider sees its. Created with no permissions, so only `share` lets
+    // anyone else open it. Unlinked on drop; existing mappings elsewhere stay
+    // valid until unmapped.
+    pub(crate) struct SharedMemory {
+        mapping: Mapping,
+        name: String,
+        // Kept for changing the permissions later.
+        fd: OwnedFd,
+    }
+
+    impl SharedMemory {
+        pub(crate) fn create(len: usize) -> Result<Self, HostError> {
+            static NEXT: AtomicU64 = AtomicU64::new(0);
+            let name = format!("/offload-{}-{}", std::process::id(), NEXT.fetch_add(1, Ordering::Relaxed));
+            let c_name = CString::new(name.as_str()).unwrap();
+            // The mode only applies to later opens; this descriptor is read-write.
+            let fd = unsafe { libc::shm_open(c_name.as_ptr(), libc::O_CREAT | libc::O_EXCL | libc::O_RDWR, 0 as libc::c_uint) };
+            if fd < 0 {
+                return Err(HostError::AllocationFailed);
+            }
+            let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+            let mapping = if unsafe { libc::ftruncate(fd.as_raw_fd(), len as libc::off_t) } == 0 {
+                Mapping::map(len, libc::MAP_SHARED, fd.as_raw_fd())
+            } else {
+                Err(HostError::AllocationFailed)
+            };
+            if mapping.is_err() {
+                unsafe { libc::shm_unlink(c_name.as_ptr()) };
+            }
+            Ok(SharedMemory { mapping: mapping?, name, fd })
+        }
+    }
+
+    impl Drop for SharedMemory {
+        fn drop(&mut self) {
+            let c_name = CString::new(self.name.as_str()).unwrap();
+            unsafe { libc::shm_unlink(c_name.as_ptr()) };
+        }
+    }
+
+    impl BufferStorage for SharedMemory {
+        fn bytes(&self) -> &[u8] {
+            self.mapping.bytes()
+        }
+
+        fn bytes_mut(&mut self) -> &mut [u8] {
+            self.mapping.bytes_mut()
+        }
+
+        fn kind(&self) -> StorageKind {
+            StorageKind::SharedMemory
+        }
+
+        fn share(&mut self, mode: u32) -> Option<std::io::Result<&str>> {
+            if unsafe { libc::fchmod(self.fd.as_raw_fd(), mode as libc::mode_t) } != 0 {
+                return Some(Err(std::io::Error::last_os_error()));
+            }
+            Some(Ok(&self.name))
+        }
+    }
+}
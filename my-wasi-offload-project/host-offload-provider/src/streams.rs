@@ -0,0 +1,98 @@
+use std::sync::{Arc, Mutex};
+
+use bytes::Bytes;
+use wasmtime_wasi::preview2::{HostInputStream, HostOutputStream, StreamError, StreamResult, Subscribe};
+
+use crate::state::HostState;
+use crate::wasi_custom::host_offload::host_allocator::{Handle, HostError};
+
+// Largest chunk a stream accepts or returns per call. Keeps a single stream
+// from holding the state lock for long.
+const MAX_CHUNK: u64 = 64 * 1024;
+
+// `wasi:io` output stream writing into a buffer from `offset` onwards. Each
+// write goes through `HostState::write_to_host`, so bounds checks, lazy
+// materialization and session rate limits apply exactly as for the plain call.
+pub struct BufferWriteStream {
+    state: Arc<Mutex<HostState>>,
+    handle: Handle,
+    offset: u64,
+}
+
+impl BufferWriteStream {
+    pub fn new(state: Arc<Mutex<HostState>>, handle: Handle, offset: u64) -> Result<Self, HostError> {
+        if offset > state.lock().unwrap().buffer_len(handle)? {
+            return Err(HostError::CopyOutOfBounds);
+        }
+        Ok(BufferWriteStream { state, handle, offset })
+    }
+}
+
+impl HostOutputStream for BufferWriteStream {
+    fn write(&mut self, bytes: Bytes) -> StreamResult<()> {
+        self.state
+            .lock()
+            .unwrap()
+            .write_to_host(&bytes, self.handle, self.offset)
+            .map_err(stream_error)?;
+        self.offset += bytes.len() as u64;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> StreamResult<()> {
+        // Writes land in the buffer immediately.
+        Ok(())
+    }
+
+    fn check_write(&mut self) -> StreamResult<usize> {
+        let len = self.state.lock().unwrap().buffer_len(self.handle).map_err(stream_error)?;
+        match len.saturating_sub(self.offset) {
+            0 => Err(StreamError::Closed),
+            remaining => Ok(remaining.min(MAX_CHUNK) as usize),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Subscribe for BufferWriteStream {
+    async fn ready(&mut self) {}
+}
+
+// `wasi:io` input stream reading a buffer from `offset` to its end.
+pub struct BufferReadStream {
+    state: Arc<Mutex<HostState>>,
+    handle: Handle,
+    offset: u64,
+}
+
+impl BufferReadStream {
+    pub fn new(state: Arc<Mutex<HostState>>, handle: Handle, offset: u64) -> Result<Self, HostError> {
+        if offset > state.lock().unwrap().buffer_len(handle)? {
+            return Err(HostError::CopyOutOfBounds);
+        }
+        Ok(BufferReadStream { state, handle, offset })
+    }
+}
+
+impl HostInputStream for BufferReadStream {
+    fn read(&mut self, size: usize) -> StreamResult<Bytes> {
+        let mut state = self.state.lock().unwrap();
+        let len = state.buffer_len(self.handle).map_err(stream_error)?;
+        let n = len.saturating_sub(self.offset).min(size as u64).min(MAX_CHUNK);
+        if n == 0 && self.offset >= len {
+            return Err(StreamError::Closed);
+        }
+        let bytes = state.read_from_host(self.handle, self.offset, n).map_err(stream_error)?;
+        self.offset += n;
+        Ok(Bytes::from(bytes))
+    }
+}
+
+#[async_trait::async_trait]
+impl Subscribe for BufferReadStream {
+    async fn ready(&mut self) {}
+}
+
+fn stream_error(e: HostError) -> StreamError {
+    StreamError::LastOperationFailed(anyhow::anyhow!("host-offload buffer stream: {:?}", e))
+}
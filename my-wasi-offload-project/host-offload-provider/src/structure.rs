@@ -0,0 +1,78 @@
+use offload_common::codec;
+
+use crate::kernels;
+use crate::state::matrix_to_bytes;
+use crate::wasi_custom::host_offload::host_allocator::{Bandwidths, Handle, HostError, MatrixStructure};
+use crate::HostState;
+
+impl HostState {
+    pub fn set_matrix_structure(&mut self, h: Handle, structure: MatrixStructure) -> Result<(), HostError> {
+        log!(Debug, "Marking matrix {} as {:?}", h, structure);
+        self.charge(0)?;
+        self.materialize(h)?;
+        let dims = *self.matrix_dims.get(&h).ok_or_else(|| self.missing(h))?;
+        let square = matches!(structure, MatrixStructure::Triangular(_) | MatrixStructure::Symmetric);
+        if square && dims.rows != dims.cols {
+            return Err(HostError::DimensionMismatch);
+        }
+        self.journal(h)?;
+        match structure {
+            MatrixStructure::General => self.structures.remove(&h),
+            structure => self.structures.insert(h, structure),
+        };
+        Ok(())
+    }
+
+    pub fn get_matrix_structure(&mut self, h: Handle) -> Result<MatrixStructure, HostError> {
+        self.charge(0)?;
+        if !self.matrix_dims.contains_key(&h) {
+            return Err(self.missing(h));
+        }
+        Ok(self.structures.get(&h).copied().unwrap_or(MatrixStructure::General))
+    }
+
+    pub fn trsm_f32(&mut self, alpha: f32, a: Handle, b: Handle) -> Result<(), HostError> {
+        log!(Debug, "trsm f32: {} <- {} * {}^-1 * {}", b, alpha, a, b);
+        self.charge(0)?;
+        self.materialize(a)?;
+        let Some(MatrixStructure::Triangular(triangle)) = self.structures.get(&a).copied() else {
+            return Err(HostError::InvalidArguments(format!("Matrix {} is not marked triangular", a)));
+        };
+        let (_, matrix_a) = self.read_matrix_f32(a)?;
+        self.materialize(b)?;
+        let (dims_b, mut matrix_b) = self.read_matrix_f32(b)?;
+        if dims_b.rows as usize != matrix_a.nrows() {
+            return Err(HostError::DimensionMismatch);
+        }
+        kernels::trsm_f32(&matrix_a, triangle.lower, triangle.unit_diagonal, alpha, &mut matrix_b)?;
+        self.materialize_dependents(b)?;
+        self.journal(b)?;
+        self.store_bytes(b, matrix_to_bytes(&matrix_b, dims_b.layout));
+        Ok(())
+    }
+
+    pub fn gbmv_f32(&mut self, alpha: f32, a: Handle, x: Handle, beta: f32, y: Handle) -> Result<(), HostError> {
+        log!(Debug, "gbmv f32: {} <- {} * {} * {} + {} * {}", y, alpha, a, x, beta, y);
+        self.charge(0)?;
+        self.materialize(a)?;
+        let Some(MatrixStructure::Banded(bands)) = self.structures.get(&a).copied() else {
+            return Err(HostError::InvalidArguments(format!("Matrix {} is not marked banded", a)));
+        };
+        self.banded_gemv(alpha, a, bands, x, beta, y)
+    }
+
+    // Behind both `gbmv-f32` and `gemv-f32` on a banded matrix.
+    pub(crate) fn banded_gemv(&mut self, alpha: f32, a: Handle, bands: Bandwidths, x: Handle, beta: f32, y: Handle) -> Result<(), HostError> {
+        self.materialize(x)?;
+        let (_, matrix_a) = self.read_matrix_f32(a)?;
+        let x_data = self.read_f32(x)?;
+        let mut y_data = self.read_output_f32(y)?;
+        if x_data.len() != matrix_a.ncols() || y_data.len() != matrix_a.nrows() {
+            return Err(HostError::DimensionMismatch);
+        }
+        kernels::gbmv_f32(alpha, &matrix_a, bands.lower as usize, bands.upper as usize, &x_data, beta, &mut y_data);
+        self.store_bytes(y, codec::f32_to_le_bytes(&y_data));
+        Ok(())
+    }
+}
+
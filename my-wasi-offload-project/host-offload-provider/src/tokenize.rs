@@ -0,0 +1,99 @@
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+use tokenizers::Tokenizer;
+
+use crate::audit::Auditor;
+use crate::policy::Policy;
+use crate::wasi_custom::host_offload::host_allocator::HostError;
+use crate::wasi_custom::host_offload::tokenizer::{self, TokenizerId};
+
+// The `tokenizer` interface for one client: Hugging Face `tokenizer.json`
+// files under `dir`, run with the `tokenizers` crate instead of a copy
+// compiled into the guest. Held to the client's policy and audit log when
+// made with `OffloadHost::tokenizers`.
+pub struct Tokenizers {
+    dir: PathBuf,
+    loaded: HashMap<TokenizerId, Tokenizer>,
+    next: TokenizerId,
+    policy: Arc<Policy>,
+    audit: Option<Auditor>,
+}
+
+impl Tokenizers {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Tokenizers { dir: dir.into(), loaded: HashMap::new(), next: 1, policy: Arc::default(), audit: None }
+    }
+
+    pub(crate) fn guarded(dir: impl Into<PathBuf>, policy: Arc<Policy>, audit: Option<Auditor>) -> Self {
+        Tokenizers { policy, audit, ..Tokenizers::new(dir) }
+    }
+
+    fn audit(&self, function: &str, error: Option<&String>) {
+        if let Some(auditor) = &self.audit {
+            let error = error.map(|message| HostError::ComputationError(message.clone()));
+            auditor.log.record(&auditor.instance, function, &[], None, error.as_ref());
+        }
+    }
+
+    // `path` names a file below `dir`: relative, with no `..`.
+    pub fn load(&mut self, path: &str) -> Result<TokenizerId, String> {
+        log!(Debug, "Loading tokenizer '{}'", path);
+        let relative = Path::new(path);
+        if !relative.components().all(|c| matches!(c, Component::Normal(_) | Component::CurDir)) {
+            return Err(format!("Tokenizer path '{}' must stay inside the tokenizer directory", path));
+        }
+        let tokenizer = Tokenizer::from_file(self.dir.join(relative)).map_err(|e| format!("Failed to load tokenizer '{}': {}", path, e))?;
+        let id = self.next;
+        self.next += 1;
+        self.loaded.insert(id, tokenizer);
+        Ok(id)
+    }
+
+    fn get(&self, tok: TokenizerId) -> Result<&Tokenizer, String> {
+        self.loaded.get(&tok).ok_or_else(|| format!("No tokenizer {}", tok))
+    }
+
+    pub fn encode(&self, tok: TokenizerId, text: &str, add_special_tokens: bool) -> Result<Vec<u32>, String> {
+        let encoding = self.get(tok)?.encode(text, add_special_tokens).map_err(|e| e.to_string())?;
+        Ok(encoding.get_ids().to_vec())
+    }
+
+    pub fn decode(&self, tok: TokenizerId, ids: &[u32], skip_special_tokens: bool) -> Result<String, String> {
+        self.get(tok)?.decode(ids, skip_special_tokens).map_err(|e| e.to_string())
+    }
+}
+
+impl tokenizer::Host for Tokenizers {
+    fn load_tokenizer(&mut self, path: String) -> wasmtime::Result<Result<TokenizerId, String>> {
+        let result = self
+            .policy
+            .permit("tokenizer.load-tokenizer")
+            .and_then(|()| self.policy.permit_tokenizer_files())
+            .map_err(message)
+            .and_then(|()| self.load(&path));
+        self.audit("tokenizer.load-tokenizer", result.as_ref().err());
+        Ok(result)
+    }
+
+    fn encode(&mut self, tok: TokenizerId, text: String, add_special_tokens: bool) -> wasmtime::Result<Result<Vec<u32>, String>> {
+        let result = self.policy.permit("tokenizer.encode").map_err(message).and_then(|()| Tokenizers::encode(self, tok, &text, add_special_tokens));
+        self.audit("tokenizer.encode", result.as_ref().err());
+        Ok(result)
+    }
+
+    fn decode(&mut self, tok: TokenizerId, ids: Vec<u32>, skip_special_tokens: bool) -> wasmtime::Result<Result<String, String>> {
+        let result = self.policy.permit("tokenizer.decode").map_err(message).and_then(|()| Tokenizers::decode(self, tok, &ids, skip_special_tokens));
+        self.audit("tokenizer.decode", result.as_ref().err());
+        Ok(result)
+    }
+}
+
+// Tokenizer errors are plain strings, so a denied call fails with the policy's message.
+fn message(e: HostError) -> String {
+    match e {
+        HostError::PermissionDenied(message) => message,
+        other => format!("{:?}", other),
+    }
+}
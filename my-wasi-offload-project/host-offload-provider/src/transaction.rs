@@ -0,0 +1,129 @@
+use std::collections::{HashMap, HashSet};
+use std::time::Instant;
+
+use zeroize::Zeroize;
+
+use crate::wasi_custom::host_offload::host_allocator::{ElementType, Handle, HostError, MatrixShape, MatrixStructure};
+use crate::kvcache::KvCache;
+use crate::HostState;
+
+// An open transaction: the handles it created and, for every older handle
+// it wrote to, re-described or freed, everything needed to put that handle
+// back as it was at `begin-transaction`. Each handle is saved once, on its
+// first change, so untouched buffers cost nothing.
+#[derive(Default)]
+pub(crate) struct Transaction {
+    created: HashSet<Handle>,
+    saved: HashMap<Handle, Saved>,
+}
+
+struct Saved {
+    bytes: Vec<u8>,
+    dims: Option<MatrixShape>,
+    element_type: Option<ElementType>,
+    structure: Option<MatrixStructure>,
+    kv_cache: Option<KvCache>,
+    expiry: Option<Instant>,
+    pinned: bool,
+    sensitive: bool,
+}
+
+impl HostState {
+    pub fn begin_transaction(&mut self) -> Result<(), HostError> {
+        log!(Debug, "Beginning transaction");
+        self.charge(0)?;
+        if self.transaction.is_some() {
+            return Err(HostError::InvalidTransaction);
+        }
+        self.transaction = Some(Transaction::default());
+        Ok(())
+    }
+
+    pub fn commit(&mut self) -> Result<(), HostError> {
+        log!(Debug, "Committing transaction");
+        self.charge(0)?;
+        let mut transaction = self.transaction.take().ok_or(HostError::InvalidTransaction)?;
+        // Including handles marked sensitive after they were saved.
+        for (h, saved) in transaction.saved.iter_mut() {
+            if saved.sensitive || self.is_sensitive(*h) {
+                saved.bytes.zeroize();
+            }
+        }
+        Ok(())
+    }
+
+    pub fn rollback(&mut self) -> Result<(), HostError> {
+        log!(Debug, "Rolling back transaction");
+        self.charge(0)?;
+        let transaction = self.transaction.take().ok_or(HostError::InvalidTransaction)?;
+        for h in transaction.created {
+            self.release(h);
+        }
+        let restored = transaction.saved.len();
+        for (h, saved) in transaction.saved {
+            // A buffer spilled since it was saved must not end up both on disk and in memory.
+            self.reload(h)?;
+            // Before the bytes go back, so `store_bytes` wipes the copy it doesn't keep.
+            if saved.sensitive {
+                self.sensitive.insert(h);
+            }
+            self.store_bytes(h, saved.bytes);
+            match saved.dims {
+                Some(dims) => self.matrix_dims.insert(h, dims),
+                None => self.matrix_dims.remove(&h),
+            };
+            match saved.element_type {
+                Some(elem) => self.element_types.insert(h, elem),
+                None => self.element_types.remove(&h),
+            };
+            match saved.structure {
+                Some(structure) => self.structures.insert(h, structure),
+                None => self.structures.remove(&h),
+            };
+            if let Some(cache) = saved.kv_cache {
+                self.kv_caches.insert(h, cache);
+            }
+            if let Some(at) = saved.expiry {
+                self.expiries.insert(h, at);
+            }
+            if saved.pinned {
+                self.spill.pinned.insert(h);
+            }
+        }
+        log!(Debug, "Restored {} buffers", restored);
+        Ok(())
+    }
+
+    pub(crate) fn track_created(&mut self, h: Handle) {
+        if let Some(transaction) = self.transaction.as_mut() {
+            transaction.created.insert(h);
+        }
+    }
+
+    // Must run before `h` is changed in any way a rollback has to undo. Also
+    // where device copies of `h` go stale.
+    pub(crate) fn journal(&mut self, h: Handle) -> Result<(), HostError> {
+        self.evict_device_copies(h);
+        match &self.transaction {
+            Some(t) if !t.created.contains(&h) && !t.saved.contains_key(&h) => {}
+            _ => return Ok(()),
+        }
+        self.materialize(h)?;
+        let Some(bytes) = self.buffers.get(&h).map(|b| b.to_vec()) else {
+            // Unknown handle; the caller reports it.
+            return Ok(());
+        };
+        let saved = Saved {
+            bytes,
+            dims: self.matrix_dims.get(&h).copied(),
+            element_type: self.element_types.get(&h).copied(),
+            structure: self.structures.get(&h).copied(),
+            kv_cache: self.kv_caches.get(&h).cloned(),
+            expiry: self.expiries.get(&h).copied(),
+            pinned: self.spill.pinned.contains(&h),
+            sensitive: self.is_sensitive(h),
+        };
+        self.transaction.as_mut().unwrap().saved.insert(h, saved);
+        Ok(())
+    }
+}
@@ -0,0 +1,42 @@
+use std::time::{Duration, Instant};
+
+use crate::wasi_custom::host_offload::host_allocator::{Handle, HostError};
+use crate::events::{Event, EvictionReason};
+use crate::HostState;
+
+impl HostState {
+    pub fn allocate_buffer_with_ttl(&mut self, size: u64, ttl_ms: u64) -> Result<Handle, HostError> {
+        let handle = self.allocate_buffer(size)?;
+        log!(Debug, "Buffer {} expires in {} ms", handle, ttl_ms);
+        self.expiries.insert(handle, Instant::now() + Duration::from_millis(ttl_ms));
+        Ok(handle)
+    }
+
+    // Frees every unpinned TTL buffer past its deadline.
+    pub(crate) fn reap_expired(&mut self) {
+        if self.expiries.is_empty() {
+            return;
+        }
+        let now = Instant::now();
+        let due: Vec<Handle> = self.expiries.iter()
+            .filter(|(h, &at)| at <= now && !self.spill.pinned.contains(h))
+            .map(|(&h, _)| h)
+            .collect();
+        for h in due {
+            if self.release(h) {
+                log!(Info, "Buffer {} expired and was reclaimed", h);
+                self.emit(Event::BufferEvicted { handle: h, reason: EvictionReason::Expired });
+            }
+            self.expired.insert(h);
+        }
+    }
+
+    // The error for a handle the provider doesn't hold.
+    pub(crate) fn missing(&self, h: Handle) -> HostError {
+        if self.expired.contains(&h) {
+            HostError::Expired
+        } else {
+            HostError::InvalidHandle
+        }
+    }
+}
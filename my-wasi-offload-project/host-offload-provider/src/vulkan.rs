@@ -0,0 +1,486 @@
+use std::collections::HashMap;
+use std::ffi::CStr;
+use std::io::Cursor;
+use std::time::Instant;
+
+use ash::vk;
+
+use crate::backend::{ComputeBackend, Operand};
+use crate::wasi_custom::host_offload::host_allocator::{Device, DeviceInfo, Handle, HostError, MatrixLayout, MatrixShape};
+
+pub(crate) const BACKEND_NAME: &str = "vulkan";
+
+// SPIR-V compiled by build.rs from shaders/gemm.comp.
+const GEMM_F32: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/gemm_f32.spv"));
+const GEMM_F64: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/gemm_f64.spv"));
+const GEMM_F32_SUBGROUP: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/gemm_f32_subgroup.spv"));
+const GEMM_F64_SUBGROUP: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/gemm_f64_subgroup.spv"));
+
+// Must match `TILE` in the shader.
+const TILE: u32 = 16;
+// Products with at most this many elements use the subgroup kernel, one
+// workgroup per element, where the device has subgroup arithmetic.
+const SUBGROUP_MAX_OUTPUTS: usize = 4096;
+
+fn vk_error(e: vk::Result) -> HostError {
+    HostError::ComputationError(format!("Vulkan: {:?}", e))
+}
+
+// A storage buffer and the memory bound to it.
+struct Allocation {
+    buffer: vk::Buffer,
+    memory: vk::DeviceMemory,
+    size: u64,
+}
+
+// The tiled and, where supported, subgroup pipeline for one element type.
+struct Kernels {
+    tiled: vk::Pipeline,
+    subgroup: Option<vk::Pipeline>,
+}
+
+struct Gpu {
+    device: ash::Device,
+    queue: vk::Queue,
+    command_pool: vk::CommandPool,
+    descriptor_pool: vk::DescriptorPool,
+    set_layout: vk::DescriptorSetLayout,
+    pipeline_layout: vk::PipelineLayout,
+    memory_properties: vk::PhysicalDeviceMemoryProperties,
+    f32: Kernels,
+    // Only on devices with `shaderFloat64`.
+    f64: Option<Kernels>,
+    name: String,
+    memory_bytes: u64,
+}
+
+// SPIR-V GEMM on every Vulkan 1.1 device with a compute queue, for hosts
+// without CUDA. Devices are numbered in the order the driver lists them.
+//
+// Buffers live in host-visible memory (device-local as well where the device
+// offers both, as integrated GPUs and resizable-BAR cards do), so copies in
+// and out are plain memcpys. Operands placed or prefetched on a device stay
+// resident until `evict`.
+pub struct VulkanBackend {
+    _entry: ash::Entry,
+    instance: ash::Instance,
+    gpus: Vec<Gpu>,
+    resident: HashMap<Handle, (u32, Allocation)>,
+}
+
+// Shader push constants: the shape, then row and column strides (in
+// elements) of A, B and C.
+fn push_constants(a: MatrixShape, b: MatrixShape) -> [u32; 9] {
+    let strides = |dims: MatrixShape| match dims.layout {
+        MatrixLayout::RowMajor => (dims.cols, 1),
+        MatrixLayout::ColumnMajor => (1, dims.rows),
+    };
+    let c = MatrixShape { rows: a.rows, cols: b.cols, layout: a.layout };
+    let ((a_rs, a_cs), (b_rs, b_cs), (c_rs, c_cs)) = (strides(a), strides(b), strides(c));
+    [a.rows, b.cols, a.cols, a_rs, a_cs, b_rs, b_cs, c_rs, c_cs]
+}
+
+impl VulkanBackend {
+    pub fn new() -> Result<Self, String> {
+        let entry = unsafe { ash::Entry::load() }.map_err(|e| format!("Vulkan loader unavailable: {}", e))?;
+        let app = vk::ApplicationInfo::builder().api_version(vk::API_VERSION_1_1);
+        let instance = unsafe { entry.create_instance(&vk::InstanceCreateInfo::builder().application_info(&app), None) }
+            .map_err(|e| format!("failed to create a Vulkan instance: {:?}", e))?;
+        let mut backend = VulkanBackend { _entry: entry, instance, gpus: Vec::new(), resident: HashMap::new() };
+        let physical = unsafe { backend.instance.enumerate_physical_devices() }
+            .map_err(|e| format!("failed to list Vulkan devices: {:?}", e))?;
+        for physical in physical {
+            // Devices without a compute queue (or Vulkan 1.1) are skipped.
+            if let Some(gpu) = Gpu::open(&backend.instance, physical)? {
+                backend.gpus.push(gpu);
+            }
+        }
+        if backend.gpus.is_empty() {
+            return Err("no Vulkan devices with compute support found".to_string());
+        }
+        Ok(backend)
+    }
+
+    fn gpu(&self, device: u32) -> Result<&Gpu, HostError> {
+        self.gpus.get(device as usize).ok_or(HostError::DeviceUnavailable)
+    }
+
+    fn matmul(&self, device: u32, a: Operand, b: Operand, f64: bool, deadline: Option<Instant>) -> Result<Vec<u8>, HostError> {
+        let gpu = self.gpu(device)?;
+        let kernels = match f64 {
+            true => gpu.f64.as_ref().ok_or(HostError::DeviceUnavailable)?,
+            false => &gpu.f32,
+        };
+        if deadline.is_some_and(|d| Instant::now() > d) {
+            return Err(HostError::Timeout);
+        }
+        let (m, n) = (a.dims.rows as usize, b.dims.cols as usize);
+        let c_size = (m * n * if f64 { 8 } else { 4 }) as u64;
+
+        let resident = |h: Handle| self.resident.get(&h).filter(|(d, _)| *d == device).map(|(_, alloc)| alloc);
+        let mut staged = Vec::new();
+        let result = (|| {
+            for operand in [&a, &b] {
+                if resident(operand.handle).is_none() {
+                    staged.push(gpu.upload(operand.bytes)?);
+                }
+            }
+            let mut staged_iter = staged.iter();
+            let mut buffer = |h: Handle| resident(h).unwrap_or_else(|| staged_iter.next().unwrap()).buffer;
+            let (a_buffer, b_buffer) = (buffer(a.handle), buffer(b.handle));
+            let c = gpu.allocate(c_size)?;
+            let c_buffer = c.buffer;
+            staged.push(c);
+
+            let push = push_constants(a.dims, b.dims);
+            match kernels.subgroup.filter(|_| m * n <= SUBGROUP_MAX_OUTPUTS) {
+                Some(pipeline) => gpu.dispatch(pipeline, [a_buffer, b_buffer, c_buffer], push, (n as u32, m as u32))?,
+                None => {
+                    let groups = ((n as u32).div_ceil(TILE), (m as u32).div_ceil(TILE));
+                    gpu.dispatch(kernels.tiled, [a_buffer, b_buffer, c_buffer], push, groups)?
+                }
+            }
+            gpu.read(staged.last().unwrap())
+        })();
+        for allocation in staged {
+            gpu.free(allocation);
+        }
+        let bytes = result.map_err(vk_error)?;
+        // Dispatches can't be interrupted; a late result is still abandoned.
+        if deadline.is_some_and(|d| Instant::now() > d) {
+            return Err(HostError::Timeout);
+        }
+        Ok(bytes)
+    }
+}
+
+impl Gpu {
+    fn open(instance: &ash::Instance, physical: vk::PhysicalDevice) -> Result<Option<Self>, String> {
+        let mut subgroup = vk::PhysicalDeviceSubgroupProperties::default();
+        let mut properties = vk::PhysicalDeviceProperties2::builder().push_next(&mut subgroup);
+        unsafe { instance.get_physical_device_properties2(physical, &mut properties) };
+        let properties = properties.properties;
+        if properties.api_version < vk::API_VERSION_1_1 {
+            return Ok(None);
+        }
+        let families = unsafe { instance.get_physical_device_queue_family_properties(physical) };
+        let Some(family) = families.iter().position(|f| f.queue_flags.contains(vk::QueueFlags::COMPUTE)) else {
+            return Ok(None);
+        };
+        let name = unsafe { CStr::from_ptr(properties.device_name.as_ptr()) }.to_string_lossy().into_owned();
+        let failed = |e: vk::Result| format!("failed to set up Vulkan device '{}': {:?}", name, e);
+
+        let memory_properties = unsafe { instance.get_physical_device_memory_properties(physical) };
+        let memory_bytes = memory_properties.memory_heaps[..memory_properties.memory_heap_count as usize]
+            .iter()
+            .filter(|heap| heap.flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL))
+            .map(|heap| heap.size)
+            .sum();
+        let float64 = unsafe { instance.get_physical_device_features(physical) }.shader_float64 == vk::TRUE;
+        let subgroups = subgroup.supported_stages.contains(vk::ShaderStageFlags::COMPUTE)
+            && subgroup.supported_operations.contains(vk::SubgroupFeatureFlags::ARITHMETIC);
+
+        let queue_info = vk::DeviceQueueCreateInfo::builder().queue_family_index(family as u32).queue_priorities(&[1.0]);
+        let features = vk::PhysicalDeviceFeatures::builder().shader_float64(float64);
+        let device_info = vk::DeviceCreateInfo::builder()
+            .queue_create_infos(std::slice::from_ref(&queue_info))
+            .enabled_features(&features);
+        let device = unsafe { instance.create_device(physical, &device_info, None) }.map_err(failed)?;
+
+        // Everything created from here on is destroyed in `Drop`, which
+        // skips null handles, so a failure part way can just drop `gpu`.
+        let mut gpu = Gpu {
+            queue: unsafe { device.get_device_queue(family as u32, 0) },
+            device,
+            command_pool: vk::CommandPool::null(),
+            descriptor_pool: vk::DescriptorPool::null(),
+            set_layout: vk::DescriptorSetLayout::null(),
+            pipeline_layout: vk::PipelineLayout::null(),
+            memory_properties,
+            f32: Kernels { tiled: vk::Pipeline::null(), subgroup: None },
+            f64: None,
+            name: name.clone(),
+            memory_bytes,
+        };
+        gpu.create_layouts(family as u32).map_err(failed)?;
+        let subgroup_size = subgroups.then_some(subgroup.subgroup_size);
+        gpu.f32 = gpu.kernels(GEMM_F32, GEMM_F32_SUBGROUP, subgroup_size).map_err(failed)?;
+        if float64 {
+            gpu.f64 = Some(gpu.kernels(GEMM_F64, GEMM_F64_SUBGROUP, subgroup_size).map_err(failed)?);
+        }
+        log!(
+            Info,
+            "Vulkan device '{}': f64 {}, subgroup size {:?}",
+            gpu.name,
+            if float64 { "yes" } else { "no" },
+            subgroup_size
+        );
+        Ok(Some(gpu))
+    }
+
+    fn create_layouts(&mut self, family: u32) -> Result<(), vk::Result> {
+        let pool_info = vk::CommandPoolCreateInfo::builder()
+            .queue_family_index(family)
+            .flags(vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER);
+        self.command_pool = unsafe { self.device.create_command_pool(&pool_info, None)? };
+
+        let bindings: Vec<_> = (0..3)
+            .map(|binding| {
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(binding)
+                    .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                    .descriptor_count(1)
+                    .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                    .build()
+            })
+            .collect();
+        let set_info = vk::DescriptorSetLayoutCreateInfo::builder().bindings(&bindings);
+        self.set_layout = unsafe { self.device.create_descriptor_set_layout(&set_info, None)? };
+
+        let push_range = vk::PushConstantRange::builder()
+            .stage_flags(vk::ShaderStageFlags::COMPUTE)
+            .size(std::mem::size_of::<[u32; 9]>() as u32);
+        let layout_info = vk::PipelineLayoutCreateInfo::builder()
+            .set_layouts(std::slice::from_ref(&self.set_layout))
+            .push_constant_ranges(std::slice::from_ref(&push_range));
+        self.pipeline_layout = unsafe { self.device.create_pipeline_layout(&layout_info, None)? };
+
+        // One set per dispatch, reset once it completes.
+        let sizes = [vk::DescriptorPoolSize { ty: vk::DescriptorType::STORAGE_BUFFER, descriptor_count: 3 }];
+        let pool_info = vk::DescriptorPoolCreateInfo::builder().max_sets(1).pool_sizes(&sizes);
+        self.descriptor_pool = unsafe { self.device.create_descriptor_pool(&pool_info, None)? };
+        Ok(())
+    }
+
+    fn kernels(&self, tiled: &[u8], subgroup: &[u8], subgroup_size: Option<u32>) -> Result<Kernels, vk::Result> {
+        Ok(Kernels {
+            tiled: self.pipeline(tiled, None)?,
+            subgroup: subgroup_size.map(|size| self.pipeline(subgroup, Some(size))).transpose()?,
+        })
+    }
+
+    // A compute pipeline for `spirv`, with its workgroup size set to
+    // `subgroup_size` if given (specialization constant 0).
+    fn pipeline(&self, spirv: &[u8], subgroup_size: Option<u32>) -> Result<vk::Pipeline, vk::Result> {
+        let code = ash::util::read_spv(&mut Cursor::new(spirv)).expect("build.rs produced invalid SPIR-V");
+        let module = unsafe { self.device.create_shader_module(&vk::ShaderModuleCreateInfo::builder().code(&code), None)? };
+        let entries = [vk::SpecializationMapEntry { constant_id: 0, offset: 0, size: 4 }];
+        let data = subgroup_size.unwrap_or(0).to_ne_bytes();
+        let specialization = vk::SpecializationInfo::builder().map_entries(&entries).data(&data);
+        let mut stage = vk::PipelineShaderStageCreateInfo::builder()
+            .stage(vk::ShaderStageFlags::COMPUTE)
+            .module(module)
+            .name(CStr::from_bytes_with_nul(b"main\0").unwrap());
+        if subgroup_size.is_some() {
+            stage = stage.specialization_info(&specialization);
+        }
+        let info = vk::ComputePipelineCreateInfo::builder().stage(stage.build()).layout(self.pipeline_layout);
+        let pipelines = unsafe { self.device.create_compute_pipelines(vk::PipelineCache::null(), &[info.build()], None) };
+        unsafe { self.device.destroy_shader_module(module, None) };
+        Ok(pipelines.map_err(|(_, e)| e)?[0])
+    }
+
+    fn allocate(&self, size: u64) -> Result<Allocation, vk::Result> {
+        let info = vk::BufferCreateInfo::builder()
+            .size(size.max(4))
+            .usage(vk::BufferUsageFlags::STORAGE_BUFFER)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE);
+        let buffer = unsafe { self.device.create_buffer(&info, None)? };
+        let requirements = unsafe { self.device.get_buffer_memory_requirements(buffer) };
+        let host = vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT;
+        let types = &self.memory_properties.memory_types[..self.memory_properties.memory_type_count as usize];
+        let usable = |wanted: vk::MemoryPropertyFlags| {
+            types.iter().enumerate().position(|(i, t)| requirements.memory_type_bits & (1 << i) != 0 && t.property_flags.contains(wanted))
+        };
+        let Some(memory_type) = usable(host | vk::MemoryPropertyFlags::DEVICE_LOCAL).or_else(|| usable(host)) else {
+            unsafe { self.device.destroy_buffer(buffer, None) };
+            return Err(vk::Result::ERROR_OUT_OF_DEVICE_MEMORY);
+        };
+        let memory_info = vk::MemoryAllocateInfo::builder()
+            .allocation_size(requirements.size)
+            .memory_type_index(memory_type as u32);
+        let memory = match unsafe { self.device.allocate_memory(&memory_info, None) } {
+            Ok(memory) => memory,
+            Err(e) => {
+                unsafe { self.device.destroy_buffer(buffer, None) };
+                return Err(e);
+            }
+        };
+        let allocation = Allocation { buffer, memory, size };
+        if let Err(e) = unsafe { self.device.bind_buffer_memory(buffer, memory, 0) } {
+            self.free(allocation);
+            return Err(e);
+        }
+        Ok(allocation)
+    }
+
+    fn free(&self, allocation: Allocation) {
+        unsafe {
+            self.device.destroy_buffer(allocation.buffer, None);
+            self.device.free_memory(allocation.memory, None);
+        }
+    }
+
+    // A new allocation holding `bytes`.
+    fn upload(&self, bytes: &[u8]) -> Result<Allocation, vk::Result> {
+        let allocation = self.allocate(bytes.len() as u64)?;
+        let result = unsafe {
+            self.device
+                .map_memory(allocation.memory, 0, allocation.size, vk::MemoryMapFlags::empty())
+                .map(|ptr| {
+                    std::ptr::copy_nonoverlapping(bytes.as_ptr(), ptr.cast(), bytes.len());
+                    self.device.unmap_memory(allocation.memory);
+                })
+        };
+        match result {
+            Ok(()) => Ok(allocation),
+            Err(e) => {
+                self.free(allocation);
+                Err(e)
+            }
+        }
+    }
+
+    fn read(&self, allocation: &Allocation) -> Result<Vec<u8>, vk::Result> {
+        let mut bytes = vec![0u8; allocation.size as usize];
+        unsafe {
+            let ptr = self.device.map_memory(allocation.memory, 0, allocation.size, vk::MemoryMapFlags::empty())?;
+            std::ptr::copy_nonoverlapping(ptr.cast(), bytes.as_mut_ptr(), bytes.len());
+            self.device.unmap_memory(allocation.memory);
+        }
+        Ok(bytes)
+    }
+
+    // Runs `pipeline` over `groups` workgroups and waits for it.
+    fn dispatch(&self, pipeline: vk::Pipeline, buffers: [vk::Buffer; 3], push: [u32; 9], groups: (u32, u32)) -> Result<(), vk::Result> {
+        let device = &self.device;
+        unsafe {
+            let set_info = vk::DescriptorSetAllocateInfo::builder()
+                .descriptor_pool(self.descriptor_pool)
+                .set_layouts(std::slice::from_ref(&self.set_layout));
+            let set = device.allocate_descriptor_sets(&set_info)?[0];
+            let infos: Vec<_> = buffers
+                .iter()
+                .map(|&buffer| vk::DescriptorBufferInfo { buffer, offset: 0, range: vk::WHOLE_SIZE })
+                .collect();
+            let writes: Vec<_> = infos
+                .iter()
+                .enumerate()
+                .map(|(binding, info)| {
+                    vk::WriteDescriptorSet::builder()
+                        .dst_set(set)
+                        .dst_binding(binding as u32)
+                        .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                        .buffer_info(std::slice::from_ref(info))
+                        .build()
+                })
+                .collect();
+            device.update_descriptor_sets(&writes, &[]);
+
+            let command_info = vk::CommandBufferAllocateInfo::builder()
+                .command_pool(self.command_pool)
+                .level(vk::CommandBufferLevel::PRIMARY)
+                .command_buffer_count(1);
+            let commands = device.allocate_command_buffers(&command_info)?[0];
+            let fence = device.create_fence(&vk::FenceCreateInfo::default(), None)?;
+            let result = (|| {
+                let begin = vk::CommandBufferBeginInfo::builder().flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+                device.begin_command_buffer(commands, &begin)?;
+                device.cmd_bind_pipeline(commands, vk::PipelineBindPoint::COMPUTE, pipeline);
+                device.cmd_bind_descriptor_sets(commands, vk::PipelineBindPoint::COMPUTE, self.pipeline_layout, 0, &[set], &[]);
+                let push: Vec<u8> = push.iter().flat_map(|v| v.to_ne_bytes()).collect();
+                device.cmd_push_constants(commands, self.pipeline_layout, vk::ShaderStageFlags::COMPUTE, 0, &push);
+                device.cmd_dispatch(commands, groups.0, groups.1, 1);
+                // Make the shader's writes visible to the host reading C.
+                let barrier = vk::MemoryBarrier::builder()
+                    .src_access_mask(vk::AccessFlags::SHADER_WRITE)
+                    .dst_access_mask(vk::AccessFlags::HOST_READ);
+                device.cmd_pipeline_barrier(
+                    commands,
+                    vk::PipelineStageFlags::COMPUTE_SHADER,
+                    vk::PipelineStageFlags::HOST,
+                    vk::DependencyFlags::empty(),
+                    &[barrier.build()],
+                    &[],
+                    &[],
+                );
+                device.end_command_buffer(commands)?;
+                let submit = vk::SubmitInfo::builder().command_buffers(std::slice::from_ref(&commands));
+                device.queue_submit(self.queue, &[submit.build()], fence)?;
+                device.wait_for_fences(&[fence], true, u64::MAX)
+            })();
+            device.destroy_fence(fence, None);
+            device.free_command_buffers(self.command_pool, &[commands]);
+            device.reset_descriptor_pool(self.descriptor_pool, vk::DescriptorPoolResetFlags::empty())?;
+            result
+        }
+    }
+}
+
+impl Drop for Gpu {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = self.device.device_wait_idle();
+            let kernels = std::iter::once(&self.f32).chain(&self.f64);
+            for pipeline in kernels.flat_map(|k| std::iter::once(k.tiled).chain(k.subgroup)) {
+                self.device.destroy_pipeline(pipeline, None);
+            }
+            self.device.destroy_descriptor_pool(self.descriptor_pool, None);
+            self.device.destroy_pipeline_layout(self.pipeline_layout, None);
+            self.device.destroy_descriptor_set_layout(self.set_layout, None);
+            self.device.destroy_command_pool(self.command_pool, None);
+            self.device.destroy_device(None);
+        }
+    }
+}
+
+impl Drop for VulkanBackend {
+    fn drop(&mut self) {
+        for (_, (device, allocation)) in self.resident.drain() {
+            self.gpus[device as usize].free(allocation);
+        }
+        // Devices before the instance they came from.
+        self.gpus.clear();
+        unsafe { self.instance.destroy_instance(None) };
+    }
+}
+
+impl ComputeBackend for VulkanBackend {
+    fn name(&self) -> &'static str {
+        BACKEND_NAME
+    }
+
+    fn devices(&self) -> Vec<DeviceInfo> {
+        self.gpus
+            .iter()
+            .enumerate()
+            .map(|(n, gpu)| DeviceInfo {
+                device: Device::Gpu(n as u32),
+                name: gpu.name.clone(),
+                backend: BACKEND_NAME.to_string(),
+                memory_bytes: Some(gpu.memory_bytes),
+            })
+            .collect()
+    }
+
+    fn matmul_f32(&mut self, device: u32, a: Operand, b: Operand, deadline: Option<Instant>) -> Result<Vec<u8>, HostError> {
+        self.matmul(device, a, b, false, deadline)
+    }
+
+    fn matmul_f64(&mut self, device: u32, a: Operand, b: Operand, deadline: Option<Instant>) -> Result<Vec<u8>, HostError> {
+        self.matmul(device, a, b, true, deadline)
+    }
+
+    fn upload(&mut self, device: u32, h: Handle, bytes: &[u8]) -> Result<(), HostError> {
+        let allocation = self.gpu(device)?.upload(bytes).map_err(vk_error)?;
+        self.evict(h);
+        self.resident.insert(h, (device, allocation));
+        Ok(())
+    }
+
+    fn evict(&mut self, h: Handle) {
+        if let Some((device, allocation)) = self.resident.remove(&h) {
+            self.gpus[device as usize].free(allocation);
+        }
+    }
+}
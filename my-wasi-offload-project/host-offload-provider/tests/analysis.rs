@@ -0,0 +1,49 @@
+// The embedder-enabled call analysis behind the runner's `analysis_report`.
+
+use host_offload_provider::analysis::Suggestion;
+use host_offload_provider::HostState;
+
+mod common;
+use common::row_major;
+
+#[test]
+fn element_wise_calls_after_a_multiply_are_reported_as_fusable() {
+    let mut state = HostState::new();
+    state.enable_analysis();
+    let a = row_major(&mut state, &[1.0, 2.0, 3.0, 4.0], 2, 2);
+    let bias = row_major(&mut state, &[1.0, 1.0, 1.0, 1.0], 2, 2);
+    for _ in 0..2 {
+        let c = state.matrix_multiply_f32(a, a, None).unwrap();
+        state.axpy_f32(1.0, bias, c).unwrap();
+        state.scal_f32(0.5, c).unwrap();
+        state.read_f32_elems(c, 0, 4).unwrap();
+    }
+
+    let report = state.analysis_report().unwrap();
+    let fusable: Vec<_> = report.suggestions.iter().filter(|s| matches!(s, Suggestion::FuseAfterMatmul { .. })).collect();
+    assert_eq!(fusable.len(), 1);
+    let Suggestion::FuseAfterMatmul { ops, results } = fusable[0] else { unreachable!() };
+    assert_eq!(ops, &["axpy-f32", "scal-f32"]);
+    assert_eq!(results.len(), 2);
+}
+
+#[test]
+fn reading_back_what_was_written_is_reported() {
+    let mut state = HostState::new();
+    state.enable_analysis();
+    let kept = row_major(&mut state, &[1.0, 2.0], 1, 2);
+    let scaled = row_major(&mut state, &[1.0, 2.0], 1, 2);
+    state.scal_f32(2.0, scaled).unwrap();
+    state.read_f32_elems(kept, 0, 2).unwrap();
+    state.read_f32_elems(scaled, 0, 2).unwrap();
+
+    let report = state.analysis_report().unwrap();
+    assert_eq!(report.suggestions, vec![Suggestion::UnchangedReadBack { handles: vec![kept], bytes: 8 }]);
+}
+
+#[test]
+fn nothing_is_recorded_unless_enabled() {
+    let mut state = HostState::new();
+    row_major(&mut state, &[1.0], 1, 1);
+    assert_eq!(state.analysis_report(), None);
+}
@@ -0,0 +1,72 @@
+// Arenas: `end-arena` frees every handle created while the arena was the
+// innermost one open, including results and nested arenas, and nothing else.
+
+use host_offload_provider::wasi_custom::host_offload::host_allocator::HostError;
+use host_offload_provider::HostState;
+
+mod common;
+use common::row_major;
+
+#[test]
+fn ending_an_arena_frees_everything_created_in_it() {
+    let mut state = HostState::new();
+    let arena = state.begin_arena();
+    let a = row_major(&mut state, &[1.0, 2.0, 3.0, 4.0], 2, 2);
+    let raw = state.allocate_buffer(16).unwrap();
+    let product = state.matrix_multiply_f32(a, a, None).unwrap();
+    let inner = state.begin_arena();
+    let nested = state.allocate_buffer(8).unwrap();
+    state.end_arena(inner).unwrap();
+    let after_inner = state.allocate_buffer(8).unwrap();
+    assert_eq!(state.get_memory_stats().live_handles, 4);
+
+    state.end_arena(arena).unwrap();
+    assert_eq!(state.get_memory_stats().live_handles, 0);
+    for h in [a, raw, product, nested, after_inner] {
+        assert_eq!(state.read_from_host(h, 0, 1), Err(HostError::InvalidHandle));
+        assert_eq!(state.free_buffer(h), Err(HostError::InvalidHandle));
+    }
+}
+
+#[test]
+fn handles_from_outside_the_arena_survive_it() {
+    let mut state = HostState::new();
+    let before = row_major(&mut state, &[1.0, 2.0], 1, 2);
+    let arena = state.begin_arena();
+    let inside = state.allocate_buffer(4).unwrap();
+    // Using an outside handle in the arena doesn't move it into the arena.
+    state.scal_f32(2.0, before).unwrap();
+    state.end_arena(arena).unwrap();
+    let after = state.allocate_buffer(4).unwrap();
+
+    assert_eq!(state.read_f32_elems(before, 0, 2).unwrap(), [2.0, 4.0]);
+    assert_eq!(state.read_from_host(inside, 0, 4), Err(HostError::InvalidHandle));
+    assert_eq!(state.read_from_host(after, 0, 4).unwrap(), [0; 4]);
+    assert_eq!(state.get_memory_stats().live_handles, 2);
+}
+
+#[test]
+fn ending_an_outer_arena_ends_the_ones_inside_it() {
+    let mut state = HostState::new();
+    let outer = state.begin_arena();
+    let inner = state.begin_arena();
+    let h = state.allocate_buffer(4).unwrap();
+    state.end_arena(outer).unwrap();
+
+    assert_eq!(state.read_from_host(h, 0, 4), Err(HostError::InvalidHandle));
+    assert_eq!(state.end_arena(inner), Err(HostError::InvalidArena));
+    assert_eq!(state.end_arena(outer), Err(HostError::InvalidArena));
+}
+
+#[test]
+fn handles_freed_inside_an_arena_are_skipped_when_it_ends() {
+    let mut state = HostState::new();
+    let arena = state.begin_arena();
+    let freed = state.allocate_buffer(4).unwrap();
+    let kept = state.allocate_buffer(4).unwrap();
+    state.free_buffer(freed).unwrap();
+    state.end_arena(arena).unwrap();
+
+    assert_eq!(state.read_from_host(kept, 0, 4), Err(HostError::InvalidHandle));
+    assert_eq!(state.get_memory_stats().live_handles, 0);
+}
@@ -0,0 +1,36 @@
+// The audit log the runner enables per client: one JSON line per
+// `host-allocator` call, without the data the guest moved.
+
+use host_offload_provider::audit::AuditLog;
+use host_offload_provider::native::OffloadHost;
+use host_offload_provider::wasi_custom::host_offload::host_allocator::{Host, HostError};
+
+fn entries(path: &std::path::Path) -> Vec<serde_json::Value> {
+    std::fs::read_to_string(path).unwrap().lines().map(|line| serde_json::from_str(line).unwrap()).collect()
+}
+
+#[test]
+fn every_call_is_recorded_without_its_payload() {
+    let path = std::env::temp_dir().join(format!("offload-audit-{}.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let mut host = OffloadHost::new();
+    host.set_audit_log(AuditLog::open(&path).unwrap(), "client-a");
+
+    let h = host.allocate_buffer(16).unwrap().unwrap();
+    host.write_to_host(b"top secret bytes".to_vec(), h, 0).unwrap().unwrap();
+    host.read_from_host(h, 4, 6).unwrap().unwrap();
+    host.free_buffer(h).unwrap().unwrap();
+    assert_eq!(host.free_buffer(h).unwrap(), Err(HostError::InvalidHandle));
+
+    let log = entries(&path);
+    let ops: Vec<&str> = log.iter().map(|entry| entry["op"].as_str().unwrap()).collect();
+    assert_eq!(ops, ["allocate-buffer", "write-to-host", "read-from-host", "free-buffer", "free-buffer"]);
+    assert!(log.iter().all(|entry| entry["instance"] == "client-a" && entry["handles"] == serde_json::json!([h])));
+    let bytes: Vec<Option<u64>> = log.iter().map(|entry| entry["bytes"].as_u64()).collect();
+    assert_eq!(bytes, [Some(16), Some(16), Some(6), None, None]);
+    assert!(log[..4].iter().all(|entry| entry["error"].is_null()));
+    assert!(log[4]["error"].as_str().unwrap().contains("InvalidHandle"));
+    assert!(log.windows(2).all(|pair| pair[0]["ts_us"].as_u64() <= pair[1]["ts_us"].as_u64()));
+    assert!(!std::fs::read_to_string(&path).unwrap().contains("secret"));
+    std::fs::remove_file(&path).unwrap();
+}
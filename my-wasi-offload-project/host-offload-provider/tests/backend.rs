@@ -0,0 +1,190 @@
+// Compute on `device::gpu(n)` goes to the embedder's backend, which is told
+// when its copies of a buffer go stale.
+
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use host_offload_provider::backend::{ComputeBackend, Operand};
+use host_offload_provider::native::OffloadHost;
+use host_offload_provider::split::SplitConfig;
+use host_offload_provider::wasi_custom::host_offload::host_allocator::{
+    ComputeHint, ComputeMode, Device, DeviceInfo, ElementType, Handle, Host, HostError, MatrixLayout, MatrixShape,
+};
+use host_offload_provider::HostState;
+
+// Fills every product with 7s and records what it was asked to do.
+#[derive(Clone, Default)]
+struct Sevens {
+    log: Arc<Mutex<Vec<String>>>,
+}
+
+impl ComputeBackend for Sevens {
+    fn name(&self) -> &'static str {
+        "sevens"
+    }
+
+    fn devices(&self) -> Vec<DeviceInfo> {
+        vec![DeviceInfo { device: Device::Gpu(0), name: "fake".to_string(), backend: "sevens".to_string(), memory_bytes: Some(1 << 30) }]
+    }
+
+    fn matmul_f32(&mut self, device: u32, a: Operand, b: Operand, _: Option<Instant>) -> Result<Vec<u8>, HostError> {
+        self.log.lock().unwrap().push(format!("matmul {} {} on {}", a.handle, b.handle, device));
+        Ok(7.0f32.to_le_bytes().repeat((a.dims.rows * b.dims.cols) as usize))
+    }
+
+    fn matmul_f64(&mut self, _: u32, a: Operand, b: Operand, _: Option<Instant>) -> Result<Vec<u8>, HostError> {
+        Ok(7.0f64.to_le_bytes().repeat((a.dims.rows * b.dims.cols) as usize))
+    }
+
+    fn upload(&mut self, device: u32, h: Handle, _: &[u8]) -> Result<(), HostError> {
+        self.log.lock().unwrap().push(format!("upload {} to {}", h, device));
+        Ok(())
+    }
+
+    fn evict(&mut self, h: Handle) {
+        self.log.lock().unwrap().push(format!("evict {}", h));
+    }
+
+    fn set_hint(&mut self, hint: ComputeHint) {
+        self.log.lock().unwrap().push(format!("hint {:?}", hint));
+    }
+}
+
+fn identity(state: &mut HostState) -> Handle {
+    let h = state.allocate_typed_buffer(ElementType::F32, 4).unwrap();
+    state.write_f32(h, 0, &[1.0, 0.0, 0.0, 1.0]).unwrap();
+    state.register_matrix_shape(h, MatrixShape { rows: 2, cols: 2, layout: MatrixLayout::RowMajor }).unwrap();
+    h
+}
+
+fn with_gpu() -> (HostState, Arc<Mutex<Vec<String>>>) {
+    let backend = Sevens::default();
+    let log = backend.log.clone();
+    let mut state = HostState::new();
+    state.set_gpu_backend(Some(Box::new(backend)));
+    (state, log)
+}
+
+#[test]
+fn multiply_on_picks_the_device_the_plain_multiply_leaves_to_placement() {
+    let mut host = OffloadHost::new();
+    let a = identity(&mut host.lock());
+    assert!(host.matrix_multiply_f32(a, a).unwrap().is_ok());
+    assert!(host.matrix_multiply_f32_on(a, a, Device::Cpu).unwrap().is_ok());
+    assert_eq!(host.matrix_multiply_f32_on(a, a, Device::Gpu(0)).unwrap(), Err(HostError::DeviceUnavailable));
+}
+
+#[test]
+fn gpu_devices_come_from_the_backend() {
+    let (mut state, _) = with_gpu();
+    let devices: Vec<Device> = state.list_devices().into_iter().map(|d| d.device).collect();
+    assert_eq!(devices, [Device::Cpu, Device::Gpu(0)]);
+    assert!(state.supports("gpu"));
+    let a = identity(&mut state);
+    assert_eq!(state.matrix_multiply_f32(a, a, Some(Device::Gpu(1))), Err(HostError::DeviceUnavailable));
+    assert_eq!(HostState::new().matrix_multiply_f32(a, a, Some(Device::Gpu(0))), Err(HostError::DeviceUnavailable));
+}
+
+#[test]
+fn gpu_multiplies_run_on_the_backend_unless_deterministic() {
+    let (mut state, log) = with_gpu();
+    let a = identity(&mut state);
+    log.lock().unwrap().clear();
+    let on_gpu = state.matrix_multiply_f32(a, a, Some(Device::Gpu(0))).unwrap();
+    assert_eq!(state.read_f32_elems(on_gpu, 0, 4).unwrap(), [7.0; 4]);
+    assert_eq!(state.describe_handle(on_gpu).unwrap().placement, Device::Gpu(0));
+    assert_eq!(log.lock().unwrap().as_slice(), [format!("matmul {} {} on 0", a, a)]);
+
+    state.set_compute_mode(ComputeMode::Deterministic);
+    let on_cpu = state.matrix_multiply_f32(a, a, Some(Device::Gpu(0))).unwrap();
+    assert_eq!(state.read_f32_elems(on_cpu, 0, 4).unwrap(), [1.0, 0.0, 0.0, 1.0]);
+    assert_eq!(log.lock().unwrap().len(), 1);
+}
+
+#[test]
+fn placed_buffers_are_uploaded_and_evicted_on_write() {
+    let (mut state, log) = with_gpu();
+    let a = identity(&mut state);
+    log.lock().unwrap().clear();
+    state.set_placement(a, Device::Gpu(0)).unwrap();
+    assert_eq!(state.describe_handle(a).unwrap().resident_on, [Device::Cpu, Device::Gpu(0)]);
+
+    state.write_f32(a, 0, &[2.0]).unwrap();
+    assert_eq!(state.describe_handle(a).unwrap().resident_on, [Device::Cpu]);
+    state.free_buffer(a).unwrap();
+    let log = log.lock().unwrap();
+    assert_eq!(log[..2], [format!("upload {} to 0", a), format!("evict {}", a)]);
+    assert_eq!(log.last().unwrap(), &format!("evict {}", a));
+}
+
+#[test]
+fn auto_selection_times_each_size_class_and_picks_the_fastest() {
+    let (mut state, _) = with_gpu();
+    state.set_auto_backend(true);
+    // Big enough that no CPU route can keep up with a backend that just fills in 7s.
+    let a = state.allocate_typed_buffer(ElementType::F32, 128 * 128).unwrap();
+    state.write_f32(a, 0, &[1.0; 128 * 128]).unwrap();
+    state.register_matrix_shape(a, MatrixShape { rows: 128, cols: 128, layout: MatrixLayout::RowMajor }).unwrap();
+    assert!(state.get_backend_stats().is_empty());
+
+    let c = state.matrix_multiply_f32(a, a, None).unwrap();
+    assert_eq!(state.read_f32_elems(c, 0, 1).unwrap(), [7.0]);
+    let stats = state.get_backend_stats();
+    assert_eq!(stats.len(), 1);
+    assert_eq!((stats[0].size_class, stats[0].chosen.as_str()), (21, "sevens:gpu(0)"));
+    let timed: Vec<&str> = stats[0].timings.iter().map(|t| t.backend.as_str()).collect();
+    assert_eq!(timed, ["scalar", "nalgebra-cpu", "sevens:gpu(0)"]);
+
+    // Deterministic mode stays on the fixed-order CPU kernel.
+    state.set_compute_mode(ComputeMode::Deterministic);
+    let c = state.matrix_multiply_f32(a, a, None).unwrap();
+    assert_eq!(state.read_f32_elems(c, 0, 1).unwrap(), [128.0]);
+    state.set_auto_backend(false);
+    assert!(state.get_backend_stats().is_empty());
+}
+
+#[test]
+fn split_multiplies_take_leading_rows_from_the_gpu() {
+    let (mut state, log) = with_gpu();
+    state.set_split_matmul(Some(SplitConfig { min_flops: Some(0) }));
+    let a = state.allocate_typed_buffer(ElementType::F32, 8).unwrap();
+    state.write_f32(a, 0, &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0]).unwrap();
+    state.register_matrix_shape(a, MatrixShape { rows: 4, cols: 2, layout: MatrixLayout::RowMajor }).unwrap();
+    let b = identity(&mut state);
+    log.lock().unwrap().clear();
+
+    // Nothing measured yet, so the rows are shared evenly.
+    let c = state.matrix_multiply_f32(a, b, None).unwrap();
+    assert_eq!(state.read_f32_elems(c, 0, 8).unwrap(), [7.0, 7.0, 7.0, 7.0, 5.0, 6.0, 7.0, 8.0]);
+    assert_eq!(log.lock().unwrap().as_slice(), [format!("matmul 0 {} on 0", b)]);
+}
+
+#[test]
+fn low_power_keeps_unplaced_work_off_the_gpu() {
+    let (mut state, log) = with_gpu();
+    state.set_split_matmul(Some(SplitConfig { min_flops: Some(0) }));
+    let a = identity(&mut state);
+    log.lock().unwrap().clear();
+    state.set_compute_hint(ComputeHint::LowPower);
+    let c = state.matrix_multiply_f32(a, a, None).unwrap();
+    assert_eq!(state.read_f32_elems(c, 0, 4).unwrap(), [1.0, 0.0, 0.0, 1.0]);
+    // Placed work still goes where the guest put it.
+    state.matrix_multiply_f32(a, a, Some(Device::Gpu(0))).unwrap();
+    assert_eq!(log.lock().unwrap().as_slice(), ["hint LowPower".to_string(), format!("matmul {} {} on 0", a, a)]);
+}
+
+#[test]
+fn cost_estimates_scale_with_the_work() {
+    let mut state = HostState::new();
+    let small = state.estimate_cost("matrix-multiply-f32", &[(4, 4), (4, 4)]).unwrap();
+    assert_eq!(small.flops, 128);
+    assert_eq!(small.bytes_moved, 3 * 16 * 4);
+    let large = state.estimate_cost("matmul-f32", &[(256, 256), (256, 256)]).unwrap();
+    assert!(large.est_millis > small.est_millis && small.est_millis > 0.0);
+
+    let add = state.estimate_cost("add-f32", &[(8, 8), (1, 8)]).unwrap();
+    assert_eq!((add.flops, add.bytes_moved), (64, (64 * 2 + 8) * 4));
+
+    assert_eq!(state.estimate_cost("matmul-f32", &[(4, 3), (4, 4)]), Err(HostError::DimensionMismatch));
+    assert!(matches!(state.estimate_cost("no-such-op", &[]), Err(HostError::InvalidArguments(_))));
+}
@@ -0,0 +1,61 @@
+// `execute-batch`: ops run in order, may use handles produced earlier in the
+// same batch, and the batch stops at the first failure.
+
+use host_offload_provider::wasi_custom::host_offload::host_allocator::{
+    BatchInput, HostError, MatrixLayout, MatrixShape, OpDescriptor, OpResult,
+};
+use host_offload_provider::HostState;
+
+const DIMS: MatrixShape = MatrixShape { rows: 2, cols: 2, layout: MatrixLayout::RowMajor };
+
+#[test]
+fn uploads_multiplies_and_reads_back_in_one_batch() {
+    let mut state = HostState::new();
+    let results = state.execute_batch(vec![
+        OpDescriptor::Allocate(16),
+        OpDescriptor::WriteF32((BatchInput::Result(0), 0, vec![1.0, 2.0, 3.0, 4.0])),
+        OpDescriptor::RegisterMatrixShape((BatchInput::Result(0), DIMS)),
+        OpDescriptor::Allocate(16),
+        OpDescriptor::WriteF32((BatchInput::Result(3), 0, vec![0.0, 1.0, 1.0, 0.0])),
+        OpDescriptor::RegisterMatrixShape((BatchInput::Result(3), DIMS)),
+        OpDescriptor::MatrixMultiplyF32((BatchInput::Result(0), BatchInput::Result(3))),
+        OpDescriptor::ScalF32((2.0, BatchInput::Result(6))),
+        OpDescriptor::ReadF32((BatchInput::Result(6), 0, 4)),
+        OpDescriptor::Free(BatchInput::Result(3)),
+    ]);
+    assert_eq!(results.len(), 10);
+    assert_eq!(results[8], OpResult::F32s(vec![4.0, 2.0, 8.0, 6.0]));
+    assert_eq!(results[9], OpResult::Done);
+
+    // Handles from a batch are ordinary handles afterwards.
+    let OpResult::Handle(a) = results[0] else { panic!("allocate returned {:?}", results[0]) };
+    let read = state.execute_batch(vec![OpDescriptor::Read((BatchInput::Handle(a), 4, 4))]);
+    assert_eq!(read, [OpResult::Bytes(2.0f32.to_le_bytes().to_vec())]);
+}
+
+#[test]
+fn stops_at_the_first_failure() {
+    let mut state = HostState::new();
+    let h = state.allocate_buffer(4).unwrap();
+    let results = state.execute_batch(vec![
+        OpDescriptor::Write((BatchInput::Handle(h), 0, vec![1, 2, 3, 4])),
+        OpDescriptor::Write((BatchInput::Handle(h), 2, vec![9, 9, 9, 9])),
+        OpDescriptor::Write((BatchInput::Handle(h), 0, vec![0, 0, 0, 0])),
+    ]);
+    assert_eq!(results, [OpResult::Done, OpResult::Failed(HostError::CopyOutOfBounds)]);
+    assert_eq!(state.read_from_host(h, 0, 4).unwrap(), [1, 2, 3, 4]);
+}
+
+#[test]
+fn results_without_a_handle_cant_be_referenced() {
+    let mut state = HostState::new();
+    let results = state.execute_batch(vec![
+        OpDescriptor::Allocate(4),
+        OpDescriptor::Write((BatchInput::Result(0), 0, vec![0; 4])),
+        OpDescriptor::Free(BatchInput::Result(1)),
+    ]);
+    assert!(matches!(results[2], OpResult::Failed(HostError::InvalidArguments(_))));
+
+    let results = state.execute_batch(vec![OpDescriptor::Free(BatchInput::Result(0))]);
+    assert!(matches!(results[..], [OpResult::Failed(HostError::InvalidArguments(_))]));
+}
@@ -0,0 +1,89 @@
+// A client can go away at any point: its store dropped with a job half done,
+// its thread panicking between calls. Whatever it left behind must be
+// reclaimed with its session, while the host itself lives on for others.
+
+use host_offload_provider::native::OffloadHost;
+use host_offload_provider::numa::NumaConfig;
+use host_offload_provider::session_admin::SessionLimits;
+use host_offload_provider::wasi_custom::host_offload::host_allocator::{
+    ElementType, Handle, HostError, JobState, MatrixLayout, MatrixShape,
+};
+
+// Big enough that one poll only gets a job part of the way.
+const N: u32 = 512;
+
+fn unlimited() -> SessionLimits {
+    SessionLimits { max_ops_per_sec: None, max_bytes_per_sec: None, max_op_millis: None, cpu_weight: None }
+}
+
+fn square(host: &OffloadHost) -> Handle {
+    let mut state = host.lock();
+    let values: Vec<f32> = (0..N * N).map(|i| (i % 7) as f32).collect();
+    let h = state.allocate_typed_buffer(ElementType::F32, values.len() as u64).unwrap();
+    state.write_f32(h, 0, &values).unwrap();
+    state.register_matrix_shape(h, MatrixShape { rows: N, cols: N, layout: MatrixLayout::RowMajor }).unwrap();
+    h
+}
+
+fn assert_nothing_left(host: &OffloadHost) {
+    let stats = host.lock().get_memory_stats();
+    assert_eq!(stats.live_handles, 0);
+    assert_eq!(stats.resident_bytes, 0);
+    assert_eq!(stats.spilled_bytes, 0);
+}
+
+#[test]
+fn dropping_a_store_mid_job_cancels_it() {
+    let host = OffloadHost::new();
+    let store = wasmtime::Store::new(&wasmtime::Engine::default(), host.open_guarded_session(unlimited()).unwrap());
+    let a = square(&host);
+    let b = square(&host);
+    let job = host.lock().submit_matmul_f32(a, b).unwrap();
+    let progress = host.lock().poll_job(job).unwrap();
+    assert_eq!(progress.state, JobState::Pending);
+    assert!(progress.percent < 100);
+
+    drop(store);
+
+    assert_nothing_left(&host);
+    assert!(matches!(host.lock().job_status(job), Err(HostError::InvalidJob)));
+    // The host is still usable by whoever comes next.
+    let next = host.open_guarded_session(unlimited()).unwrap();
+    next.close().unwrap();
+}
+
+#[test]
+fn a_panicking_client_leaves_nothing_behind() {
+    let host = OffloadHost::new();
+    let client = host.clone();
+    let outcome = std::thread::spawn(move || {
+        let _session = client.open_guarded_session(unlimited()).unwrap();
+        let a = square(&client);
+        let b = square(&client);
+        client.lock().submit_matmul_f32(a, b).unwrap();
+        panic!("client gave up");
+    })
+    .join();
+
+    assert!(outcome.is_err());
+    assert_nothing_left(&host);
+}
+
+#[test]
+fn a_numa_multiply_past_its_deadline_leaves_no_result() {
+    let host = OffloadHost::new();
+    host.lock().set_numa_backend(Some(NumaConfig { threads_per_node: Some(4), min_flops: Some(0) }));
+    let store = wasmtime::Store::new(&wasmtime::Engine::default(), host.open_guarded_session(unlimited()).unwrap());
+    let a = square(&host);
+    let b = square(&host);
+    // Already past by the time the first worker checks.
+    host.lock().set_op_timeout(Some(0));
+
+    assert_eq!(host.lock().matrix_multiply_f32(a, b, None), Err(HostError::Timeout));
+    // Only the operands: the workers' half-written result was never handed out.
+    assert_eq!(host.lock().get_memory_stats().live_handles, 2);
+
+    drop(store);
+
+    assert_nothing_left(&host);
+}
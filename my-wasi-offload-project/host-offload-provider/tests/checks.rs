@@ -0,0 +1,116 @@
+// The `strict-checks` and `fast` builds must agree with the default one on
+// valid input. Run under each:
+//
+//   cargo test --test checks
+//   cargo test --test checks --features strict-checks
+//   cargo test --test checks --features fast
+
+use host_offload_provider::checks::{self, Profile};
+use host_offload_provider::wasi_custom::host_offload::host_allocator::{
+    ElementType, Handle, HostError, MatrixLayout, MatrixShape,
+};
+use host_offload_provider::HostState;
+use offload_common::codec;
+
+mod common;
+use common::matrix;
+
+// The way guests without `allocate-typed-buffer` build operands.
+fn untyped(state: &mut HostState, values: &[f32], rows: u32, cols: u32) -> Handle {
+    let bytes = codec::f32_to_le_bytes(values);
+    let h = state.allocate_buffer(bytes.len() as u64).unwrap();
+    state.write_to_host(&bytes, h, 0).unwrap();
+    state.register_matrix_shape(h, MatrixShape { rows, cols, layout: MatrixLayout::RowMajor }).unwrap();
+    h
+}
+
+#[test]
+fn valid_calls_give_the_same_results_in_every_profile() {
+    let mut state = HostState::new();
+    let a = matrix(&mut state, &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0], 2, 3, MatrixLayout::RowMajor);
+    // Column-major [[1, 0], [0, 1], [2, -1]].
+    let b = matrix(&mut state, &[1.0, 0.0, 2.0, 0.0, 1.0, -1.0], 3, 2, MatrixLayout::ColumnMajor);
+    let c = state.matrix_multiply_f32(a, b, None).unwrap();
+    assert_eq!(state.read_f32_elems(c, 0, 4).unwrap(), [7.0, -1.0, 16.0, -1.0]);
+
+    let x = untyped(&mut state, &[0.5, -2.0, 4.0, 1.0], 2, 2);
+    let y = untyped(&mut state, &[2.0, 0.0, 0.0, 2.0], 2, 2);
+    let z = state.matrix_multiply_f32(x, y, None).unwrap();
+    assert_eq!(state.read_f32_elems(z, 0, 4).unwrap(), [1.0, -4.0, 8.0, 2.0]);
+    assert_eq!(state.read_f32_elems(z, 2, 1).unwrap(), [8.0]);
+
+    state.matmul_accumulate(x, y, z).unwrap();
+    assert_eq!(state.read_f32_elems(z, 0, 4).unwrap(), [2.0, -8.0, 16.0, 4.0]);
+}
+
+#[test]
+fn supports_names_the_profile() {
+    let state = HostState::new();
+    assert_eq!(state.supports("strict-checks"), checks::PROFILE == Profile::Strict);
+    assert_eq!(state.supports("fast"), checks::PROFILE == Profile::Fast);
+}
+
+#[test]
+fn strict_checks_reject_dims_when_registered() {
+    let mut state = HostState::new();
+    let h = state.allocate_typed_buffer(ElementType::F32, 6).unwrap();
+    let registered = state.register_matrix_shape(h, MatrixShape { rows: 2, cols: 2, layout: MatrixLayout::RowMajor });
+    match checks::PROFILE {
+        Profile::Strict => assert_eq!(registered, Err(HostError::DimensionMismatch)),
+        // The mismatch is only found when an op reads the buffer.
+        Profile::Default | Profile::Fast => {
+            assert_eq!(registered, Ok(()));
+            assert!(matches!(state.matrix_multiply_f32(h, h, None), Err(HostError::Other(_))));
+        }
+    }
+}
+
+#[test]
+fn type_mismatches_are_errors_unless_fast() {
+    let mut state = HostState::new();
+    let h = state.allocate_typed_buffer(ElementType::S32, 2).unwrap();
+    let read = state.read_f32_elems(h, 0, 2);
+    match checks::PROFILE {
+        Profile::Fast => assert_eq!(read, Ok(vec![0.0, 0.0])),
+        Profile::Strict | Profile::Default => assert_eq!(read, Err(HostError::TypeMismatch)),
+    }
+}
+
+#[test]
+fn f64_and_i32_buffers_round_trip() {
+    let mut state = HostState::new();
+    let f = state.allocate_typed_buffer(ElementType::F64, 3).unwrap();
+    state.write_f64(f, 0, &[1.5, -0.25, 1e300]).unwrap();
+    state.write_f64(f, 2, &[f64::MIN_POSITIVE]).unwrap();
+    assert_eq!(state.read_f64_elems(f, 0, 3).unwrap(), [1.5, -0.25, f64::MIN_POSITIVE]);
+    assert_eq!(state.read_f64_elems(f, 1, 1).unwrap(), [-0.25]);
+    assert_eq!(state.read_from_host(f, 0, 8).unwrap(), 1.5f64.to_le_bytes());
+
+    let i = state.allocate_typed_buffer(ElementType::S32, 4).unwrap();
+    state.write_i32(i, 1, &[-1, i32::MAX, i32::MIN]).unwrap();
+    assert_eq!(state.read_i32_elems(i, 0, 4).unwrap(), [0, -1, i32::MAX, i32::MIN]);
+    assert_eq!(state.read_from_host(i, 4, 4).unwrap(), (-1i32).to_le_bytes());
+    assert_eq!(state.read_i32_elems(i, 3, 2), Err(HostError::CopyOutOfBounds));
+}
+
+#[test]
+fn each_typed_access_checks_the_element_type_unless_fast() {
+    let mut state = HostState::new();
+    let f64s = state.allocate_typed_buffer(ElementType::F64, 2).unwrap();
+    let f32s = state.allocate_typed_buffer(ElementType::F32, 2).unwrap();
+    let mismatches = [
+        state.read_f32_elems(f64s, 0, 2).map(drop),
+        state.write_i32(f64s, 0, &[1]),
+        state.read_f64_elems(f32s, 0, 1).map(drop),
+        state.write_f64(f32s, 0, &[1.0]),
+    ];
+    for result in mismatches {
+        match checks::PROFILE {
+            Profile::Fast => assert_eq!(result, Ok(())),
+            Profile::Strict | Profile::Default => assert_eq!(result, Err(HostError::TypeMismatch)),
+        }
+    }
+    // The matching accesses still work.
+    state.write_f64(f64s, 0, &[2.0]).unwrap();
+    assert_eq!(state.read_f64_elems(f64s, 0, 1).unwrap(), [2.0]);
+}
@@ -0,0 +1,21 @@
+// Fixtures shared by the integration tests. Each test binary uses some of
+// them, so the rest would warn as dead code there.
+#![allow(dead_code)]
+
+use host_offload_provider::wasi_custom::host_offload::host_allocator::{
+    ElementType, Handle, MatrixLayout, MatrixShape,
+};
+use host_offload_provider::HostState;
+
+// A typed f32 buffer holding `values`, registered as `rows` x `cols` in
+// `layout`.
+pub fn matrix(state: &mut HostState, values: &[f32], rows: u32, cols: u32, layout: MatrixLayout) -> Handle {
+    let h = state.allocate_typed_buffer(ElementType::F32, values.len() as u64).unwrap();
+    state.write_f32(h, 0, values).unwrap();
+    state.register_matrix_shape(h, MatrixShape { rows, cols, layout }).unwrap();
+    h
+}
+
+pub fn row_major(state: &mut HostState, values: &[f32], rows: u32, cols: u32) -> Handle {
+    matrix(state, values, rows, cols, MatrixLayout::RowMajor)
+}
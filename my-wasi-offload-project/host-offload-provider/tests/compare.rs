@@ -0,0 +1,65 @@
+// `compare-buffers-f32`: numpy `allclose` tolerance, measured against the
+// second buffer, with NaN never close to anything.
+
+use host_offload_provider::wasi_custom::host_offload::host_allocator::{ComparisonReport, Handle, HostError};
+use host_offload_provider::HostState;
+
+mod common;
+use common::row_major;
+
+fn vector(state: &mut HostState, values: &[f32]) -> Handle {
+    row_major(state, values, 1, values.len() as u32)
+}
+
+#[test]
+fn elements_within_atol_plus_rtol_are_close() {
+    let mut state = HostState::new();
+    let a = vector(&mut state, &[1.0, 2.0, 100.0]);
+    let b = vector(&mut state, &[1.0, 2.5, 101.0]);
+
+    assert_eq!(
+        state.compare_buffers_f32(a, b, 0.0, 0.5).unwrap(),
+        ComparisonReport { all_close: false, max_abs_error: 1.0, max_rel_error: 0.2, first_mismatch: Some(2) }
+    );
+    // 1.0 <= 0.5 + 0.01 * 101.
+    assert!(state.compare_buffers_f32(a, b, 0.01, 0.5).unwrap().all_close);
+    assert_eq!(state.compare_buffers_f32(a, b, 0.0, 0.4).unwrap().first_mismatch, Some(1));
+    assert!(state.compare_buffers_f32(a, a, 0.0, 0.0).unwrap().all_close);
+}
+
+#[test]
+fn rtol_scales_with_the_second_buffer() {
+    let mut state = HostState::new();
+    let one = vector(&mut state, &[1.0]);
+    let two = vector(&mut state, &[2.0]);
+
+    // |1 - 2| <= 0.5 * |2|, but not <= 0.5 * |1|.
+    assert!(state.compare_buffers_f32(one, two, 0.5, 0.0).unwrap().all_close);
+    assert!(!state.compare_buffers_f32(two, one, 0.5, 0.0).unwrap().all_close);
+}
+
+#[test]
+fn nan_is_never_close() {
+    let mut state = HostState::new();
+    let a = vector(&mut state, &[1.0, f32::NAN, 3.0]);
+    let b = vector(&mut state, &[1.0, 2.0, f32::NAN]);
+
+    let report = state.compare_buffers_f32(a, b, 1.0, 1e30).unwrap();
+    assert!(!report.all_close);
+    assert_eq!(report.first_mismatch, Some(1));
+    assert_eq!(report.max_abs_error, f32::INFINITY);
+    assert_eq!(report.max_rel_error, f32::INFINITY);
+
+    let nan = vector(&mut state, &[f32::NAN]);
+    assert_eq!(state.compare_buffers_f32(nan, nan, 1.0, 1.0).unwrap().first_mismatch, Some(0));
+}
+
+#[test]
+fn buffers_of_different_lengths_are_an_error() {
+    let mut state = HostState::new();
+    let three = vector(&mut state, &[1.0, 2.0, 3.0]);
+    let four = vector(&mut state, &[1.0, 2.0, 3.0, 4.0]);
+
+    assert_eq!(state.compare_buffers_f32(three, four, 0.0, 0.0), Err(HostError::DimensionMismatch));
+    assert_eq!(state.compare_buffers_f32(four, three, 0.0, 0.0), Err(HostError::DimensionMismatch));
+}
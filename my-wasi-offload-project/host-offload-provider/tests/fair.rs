@@ -0,0 +1,75 @@
+// Per-session compute accounting and weighted-fair turns at the compute slots.
+
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use host_offload_provider::fair::{self, Share};
+use host_offload_provider::native::OffloadHost;
+use host_offload_provider::session_admin::SessionLimits;
+use host_offload_provider::wasi_custom::host_offload::host_allocator::{Host, MatrixLayout, MatrixShape};
+
+fn limits(cpu_weight: Option<u32>) -> SessionLimits {
+    SessionLimits { max_ops_per_sec: None, max_bytes_per_sec: None, max_op_millis: None, cpu_weight }
+}
+
+// Holds a turn of `share` for `millis`, charging it that much CPU time.
+fn spend(share: &Arc<Share>, millis: u64) {
+    let _turn = share.wait_turn();
+    thread::sleep(Duration::from_millis(millis));
+}
+
+#[test]
+fn compute_calls_are_charged_to_their_session() {
+    let mut host = OffloadHost::new();
+    let session = host.open_guarded_session(limits(None)).unwrap();
+    let h = host.allocate_buffer(64 * 64 * 4).unwrap().unwrap();
+    let dims = MatrixShape { rows: 64, cols: 64, layout: MatrixLayout::RowMajor };
+    host.register_matrix_shape(h, dims).unwrap().unwrap();
+    host.matrix_multiply_f32(h, h).unwrap().unwrap();
+    host.matrix_multiply_f32(h, h).unwrap().unwrap();
+
+    // Bookkeeping calls aren't compute.
+    let usage = host.lock().session_usage(session.id()).unwrap();
+    assert_eq!(usage.compute_calls, 2);
+    assert!(usage.cpu_seconds > 0.0);
+    assert!(host.lock().session_usage(session.id() + 1).is_err());
+    session.close().unwrap();
+}
+
+#[test]
+fn freed_slots_go_to_the_least_served_session_per_weight() {
+    fair::set_compute_slots(1);
+    let heavy = Share::new(None);
+    let weighted = Share::new(Some(4));
+    let light = Share::new(None);
+    spend(&heavy, 40);
+    spend(&weighted, 40);
+    spend(&light, 20);
+
+    // All three queue up behind a held slot, heaviest user first.
+    let order = Arc::new(Mutex::new(Vec::new()));
+    let blocker = Share::new(None).wait_turn();
+    let waiters: Vec<_> = [("heavy", heavy.clone()), ("light", light), ("weighted", weighted)]
+        .into_iter()
+        .map(|(name, share)| {
+            let order = order.clone();
+            let waiter = thread::spawn(move || {
+                let _turn = share.wait_turn();
+                order.lock().unwrap().push(name);
+            });
+            thread::sleep(Duration::from_millis(20));
+            waiter
+        })
+        .collect();
+    drop(blocker);
+    for waiter in waiters {
+        waiter.join().unwrap();
+    }
+
+    // 40 ms over a weight of 4 is less than 20 ms over 1.
+    assert_eq!(*order.lock().unwrap(), ["weighted", "light", "heavy"]);
+    let usage = heavy.usage();
+    assert_eq!(usage.compute_calls, 2);
+    assert!(usage.queued_seconds > 0.0);
+}
@@ -0,0 +1,158 @@
+// Golden-output tests: every kernel path of the native provider must
+// reproduce the references in tests/golden (made by generate.py) within the
+// fixture's tolerance. A new backend gets a `Backend` case here and has to
+// pass the same fixtures.
+
+use host_offload_provider::wasi_custom::host_offload::host_allocator::{
+    ComputeMode, ElementType, EvaluationMode, Graph, GraphInput, GraphOp, Handle,
+};
+use host_offload_provider::numa::NumaConfig;
+use host_offload_provider::HostState;
+use serde::Deserialize;
+
+mod common;
+use common::row_major;
+
+#[derive(Deserialize)]
+struct GemmCase {
+    name: String,
+    m: u32,
+    k: u32,
+    n: u32,
+    atol: f64,
+    expected: Vec<f64>,
+}
+
+#[derive(Deserialize)]
+struct CastCase {
+    name: String,
+    target: String,
+    scale: f32,
+    expected: Vec<f32>,
+}
+
+// Must match `input_value` in generate.py.
+fn input_value(i: u32, salt: u32) -> f32 {
+    (((i * 37 + salt * 11) % 101) as i32 - 50) as f32 / 13.0
+}
+
+fn inputs(len: u32, salt: u32) -> Vec<f32> {
+    (0..len).map(|i| input_value(i, salt)).collect()
+}
+
+fn load<T: for<'de> Deserialize<'de>>(file: &str) -> Vec<T> {
+    let path = format!("{}/tests/golden/{}", env!("CARGO_MANIFEST_DIR"), file);
+    let text = std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("{}: {}", path, e));
+    serde_json::from_str(&text).unwrap_or_else(|e| panic!("{}: {}", path, e))
+}
+
+// The distinct ways a product reaches the kernels.
+#[derive(Debug, Clone, Copy)]
+enum Backend {
+    Eager(ComputeMode),
+    // Forced onto the worker pool however small the product.
+    Numa,
+    // Timed routing between the CPU kernels (and the pool, when enabled).
+    Auto,
+    Lazy,
+    Graph,
+    Job,
+    // Accumulated into a zeroed result with `matmul-accumulate`.
+    Accumulate,
+}
+
+const BACKENDS: [Backend; 8] = [
+    Backend::Eager(ComputeMode::Fast),
+    Backend::Eager(ComputeMode::Deterministic),
+    Backend::Numa,
+    Backend::Auto,
+    Backend::Lazy,
+    Backend::Graph,
+    Backend::Job,
+    Backend::Accumulate,
+];
+
+fn multiply(state: &mut HostState, backend: Backend, a: Handle, b: Handle) -> Handle {
+    match backend {
+        Backend::Eager(mode) => {
+            state.set_compute_mode(mode);
+            state.matrix_multiply_f32(a, b, None).unwrap()
+        }
+        Backend::Numa => {
+            state.set_numa_backend(Some(NumaConfig { threads_per_node: Some(3), min_flops: Some(0) }));
+            state.matrix_multiply_f32(a, b, None).unwrap()
+        }
+        Backend::Auto => {
+            state.set_auto_backend(true);
+            state.matrix_multiply_f32(a, b, None).unwrap()
+        }
+        Backend::Lazy => {
+            state.set_evaluation_mode(EvaluationMode::Lazy);
+            let c = state.matrix_multiply_f32(a, b, None).unwrap();
+            state.materialize_handle(c).unwrap();
+            c
+        }
+        Backend::Graph => {
+            let graph = Graph {
+                nodes: vec![GraphOp::Matmul((GraphInput::Handle(a), GraphInput::Handle(b)))],
+                outputs: vec![0],
+            };
+            state.execute_graph(&graph).unwrap()[0]
+        }
+        Backend::Job => {
+            let job = state.submit_matmul_f32(a, b).unwrap();
+            state.wait_job(job).unwrap()
+        }
+        Backend::Accumulate => {
+            let m = state.describe_handle(a).unwrap().dims.unwrap().rows;
+            let n = state.describe_handle(b).unwrap().dims.unwrap().cols;
+            let c = matrix(state, &vec![0.0; (m * n) as usize], m, n);
+            state.matmul_accumulate(a, b, c).unwrap();
+            c
+        }
+    }
+}
+
+#[test]
+fn gemm_f32_matches_golden() {
+    for case in load::<GemmCase>("gemm_f32.json") {
+        let a_data = inputs(case.m * case.k, 1);
+        let b_data = inputs(case.k * case.n, 2);
+        for backend in BACKENDS {
+            let mut state = HostState::new();
+            let a = row_major(&mut state, &a_data, case.m, case.k);
+            let b = row_major(&mut state, &b_data, case.k, case.n);
+            let c = multiply(&mut state, backend, a, b);
+            let got = state.read_f32_elems(c, 0, (case.m * case.n) as u64).unwrap();
+            assert_eq!(got.len(), case.expected.len(), "{} on {:?}", case.name, backend);
+            for (i, (&g, &e)) in got.iter().zip(&case.expected).enumerate() {
+                assert!(
+                    (g as f64 - e).abs() <= case.atol,
+                    "{} on {:?}: element {} is {}, expected {} (atol {})",
+                    case.name, backend, i, g, e, case.atol
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn cast_round_trips_match_golden() {
+    for case in load::<CastCase>("cast.json") {
+        let target = match case.target.as_str() {
+            "f16" => ElementType::F16,
+            "s8" => ElementType::S8,
+            "f64" => ElementType::F64,
+            other => panic!("{}: unknown target {}", case.name, other),
+        };
+        let values = inputs(case.expected.len() as u32, 3);
+        let mut state = HostState::new();
+        let h = state.allocate_typed_buffer(ElementType::F32, values.len() as u64).unwrap();
+        state.write_f32(h, 0, &values).unwrap();
+        let narrowed = state.cast(h, target, case.scale).unwrap();
+        let back = state.cast(narrowed, ElementType::F32, case.scale).unwrap();
+        let got = state.read_f32_elems(back, 0, values.len() as u64).unwrap();
+        // Conversions are exactly specified, so no tolerance.
+        assert_eq!(got, case.expected, "{}", case.name);
+    }
+}
@@ -0,0 +1,82 @@
+#!/usr/bin/env python3
+# Regenerates the golden fixtures in this directory. Pure Python, no numpy:
+# references are accumulated in f64 from the exact f32 inputs, so they don't
+# depend on any BLAS. Inputs come from `input_value` in ../golden.rs, which
+# this must match.
+#
+# Only rerun when a case is added or the input formula changes; a kernel
+# change must never require new references.
+import json
+import math
+import struct
+
+
+def f32(x):
+    return struct.unpack('<f', struct.pack('<f', x))[0]
+
+
+def f16(x):
+    return struct.unpack('<e', struct.pack('<e', x))[0]
+
+
+def input_value(i, salt):
+    # Same as golden.rs: integers divided in f32, which rounds once.
+    return f32((((i * 37 + salt * 11) % 101) - 50) / 13.0)
+
+
+def inputs(n, salt):
+    return [input_value(i, salt) for i in range(n)]
+
+
+GEMM_CASES = [(1, 1, 1), (3, 5, 2), (16, 16, 16), (33, 17, 9), (48, 40, 56)]
+
+
+def gemm():
+    cases = []
+    for m, k, n in GEMM_CASES:
+        a = inputs(m * k, 1)
+        b = inputs(k * n, 2)
+        expected = []
+        worst = 0.0
+        for r in range(m):
+            for c in range(n):
+                terms = [a[r * k + i] * b[i * n + c] for i in range(k)]
+                expected.append(math.fsum(terms))
+                worst = max(worst, sum(abs(t) for t in terms))
+        # Any summation order in f32 stays within k ulps of the largest
+        # partial sum; 4x headroom for FMA and blocked kernels.
+        atol = 4 * k * 2.0 ** -24 * worst
+        cases.append({'name': f'gemm_{m}x{k}x{n}', 'm': m, 'k': k, 'n': n, 'atol': atol, 'expected': expected})
+    return cases
+
+
+def round_away(x):
+    return math.copysign(math.floor(abs(x) + 0.5), x)
+
+
+CAST_LEN = 257
+
+
+def cast():
+    values = inputs(CAST_LEN, 3)
+    scale = f32(0.05)
+    to_s8 = [int(max(-128, min(127, round_away(v / scale)))) for v in values]
+    return [
+        {'name': 'f32_to_f16_to_f32', 'target': 'f16', 'scale': 1.0,
+         'expected': [f16(v) for v in values]},
+        {'name': 'f32_to_s8_to_f32', 'target': 's8', 'scale': scale,
+         'expected': [f32(q * scale) for q in to_s8]},
+        {'name': 'f32_to_f64_to_f32', 'target': 'f64', 'scale': 1.0,
+         'expected': values},
+    ]
+
+
+def write(name, cases):
+    with open(name, 'w') as f:
+        json.dump(cases, f, separators=(',', ':'))
+        f.write('\n')
+
+
+if __name__ == '__main__':
+    write('gemm_f32.json', gemm())
+    write('cast.json', cast())
@@ -0,0 +1,47 @@
+// What `get-interface-version` and `supports` tell a guest must match the
+// WIT package it was built against.
+
+use host_offload_provider::native::OffloadHost;
+use host_offload_provider::wasi_custom::host_offload::host_allocator::{Device, Host};
+use host_offload_provider::HostState;
+
+const WIT: &str = include_str!("../../wit/host-offload.wit");
+
+#[test]
+fn interface_version_is_the_package_version() {
+    let declared = WIT
+        .lines()
+        .find_map(|line| line.strip_prefix("package wasi-custom:host-offload@"))
+        .and_then(|rest| rest.strip_suffix(';'))
+        .expect("wit/host-offload.wit declares its package version");
+    let version = HostState::new().get_interface_version();
+    assert_eq!(format!("{}.{}.{}", version.major, version.minor, version.patch), declared);
+}
+
+// The names listed after "Known features:" in the comment above `supports`.
+fn known_features() -> Vec<&'static str> {
+    let start = WIT.find("// Known features:").expect("wit/host-offload.wit lists the known features");
+    WIT[start..]
+        .lines()
+        .take_while(|line| line.trim_start().starts_with("//") && !line.contains("Unknown names"))
+        .flat_map(|line| line.split('"').skip(1).step_by(2))
+        .collect()
+}
+
+#[test]
+fn the_native_host_supports_every_known_feature() {
+    let mut host = OffloadHost::new();
+    let features = known_features();
+    assert!(features.contains(&"f64"), "{:?}", features);
+    let has_gpu = host.list_devices().unwrap().iter().any(|d| matches!(d.device, Device::Gpu(_)));
+    for feature in features {
+        let expected = match feature {
+            // Depends on the machine, and the platform.
+            "gpu" => has_gpu,
+            "mmap" | "shared-memory" => cfg!(unix),
+            _ => true,
+        };
+        assert_eq!(host.supports(feature.to_string()).unwrap(), expected, "{}", feature);
+    }
+    assert!(!host.supports("no-such-feature".to_string()).unwrap());
+}
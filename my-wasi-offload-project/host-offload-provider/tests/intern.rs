@@ -0,0 +1,42 @@
+// Content-addressed buffers shared between provider states. The cache is
+// process-wide, so each test interns contents no other test uses.
+
+use host_offload_provider::wasi_custom::host_offload::host_allocator::{
+    ElementType, HashAlgorithm, HostError, MatrixLayout, MatrixShape,
+};
+use host_offload_provider::HostState;
+
+#[test]
+fn a_second_client_gets_the_published_weights_without_uploading() {
+    let dims = MatrixShape { rows: 1, cols: 3, layout: MatrixLayout::RowMajor };
+    let mut first = HostState::new();
+    let weights = first.allocate_typed_buffer(ElementType::F32, 3).unwrap();
+    first.write_f32(weights, 0, &[0.25, 0.5, 0.75]).unwrap();
+    first.register_matrix_shape(weights, dims).unwrap();
+    let hash = first.hash_buffer(weights, HashAlgorithm::Sha256).unwrap();
+    assert_eq!(first.intern_buffer(&hash).unwrap(), None);
+    first.publish_interned(weights, &hash).unwrap();
+
+    let mut second = HostState::new();
+    let shared = second.intern_buffer(&hash).unwrap().expect("published by the first client");
+    assert_eq!(second.read_f32_elems(shared, 0, 3).unwrap(), [0.25, 0.5, 0.75]);
+    assert_eq!(second.get_matrix_shape(shared).unwrap(), dims);
+
+    // Copy-on-write: the other holders keep the published values.
+    second.write_f32(shared, 0, &[9.0]).unwrap();
+    assert_eq!(second.read_f32_elems(shared, 0, 3).unwrap(), [9.0, 0.5, 0.75]);
+    assert_eq!(first.read_f32_elems(weights, 0, 3).unwrap(), [0.25, 0.5, 0.75]);
+    let mut third = HostState::new();
+    let again = third.intern_buffer(&hash).unwrap().unwrap();
+    assert_eq!(third.read_f32_elems(again, 0, 3).unwrap(), [0.25, 0.5, 0.75]);
+}
+
+#[test]
+fn publishing_checks_the_hash() {
+    let mut state = HostState::new();
+    let h = state.allocate_buffer(4).unwrap();
+    state.write_to_host(b"abcd", h, 0).unwrap();
+    assert!(matches!(state.publish_interned(h, &[0; 32]), Err(HostError::InvalidArguments(_))));
+    assert!(matches!(state.intern_buffer(&[0; 8]), Err(HostError::InvalidArguments(_))));
+    assert_eq!(state.intern_buffer(&[0; 32]).unwrap(), None);
+}
@@ -0,0 +1,37 @@
+// Submitted jobs run on a worker thread of the native host.
+
+use std::time::{Duration, Instant};
+
+use host_offload_provider::native::OffloadHost;
+use host_offload_provider::wasi_custom::host_offload::host_allocator::{Host, JobState, MatrixLayout, MatrixShape};
+
+const N: u32 = 256;
+
+fn ones(host: &mut OffloadHost) -> u32 {
+    let h = host.allocate_buffer(N as u64 * N as u64 * 4).unwrap().unwrap();
+    host.write_f32(h, 0, vec![1.0; (N * N) as usize]).unwrap().unwrap();
+    let dims = MatrixShape { rows: N, cols: N, layout: MatrixLayout::RowMajor };
+    host.register_matrix_shape(h, dims).unwrap().unwrap();
+    h
+}
+
+#[test]
+fn submitted_jobs_finish_while_the_guest_keeps_uploading() {
+    let mut host = OffloadHost::new();
+    let a = ones(&mut host);
+    let job = host.submit_matmul_f32(a, a).unwrap().unwrap();
+
+    // The next operand goes up meanwhile, and the job gets done without the
+    // guest polling or waiting on it.
+    let next = ones(&mut host);
+    let deadline = Instant::now() + Duration::from_secs(30);
+    while host.job_status(job).unwrap().unwrap() == JobState::Pending {
+        assert!(Instant::now() < deadline, "job {} never finished on its own", job);
+        std::thread::sleep(Duration::from_millis(1));
+    }
+
+    let c = host.wait_job(job).unwrap().unwrap();
+    assert_eq!(host.read_f32(c, 0, 1).unwrap().unwrap(), [N as f32]);
+    assert!(host.wait_job(job).unwrap().is_err());
+    host.free_buffer(next).unwrap().unwrap();
+}
@@ -0,0 +1,66 @@
+// `is-materialized`: false while a lazy result is still pending, true once
+// anything has computed it.
+
+use host_offload_provider::wasi_custom::host_offload::host_allocator::{EvaluationMode, HostError};
+use host_offload_provider::HostState;
+
+mod common;
+use common::row_major;
+
+#[test]
+fn a_lazy_result_is_materialized_by_reading_it() {
+    let mut state = HostState::new();
+    state.set_evaluation_mode(EvaluationMode::Lazy);
+    let a = row_major(&mut state, &[1.0, 2.0, 3.0, 4.0], 2, 2);
+    let c = state.matrix_multiply_f32(a, a, None).unwrap();
+
+    assert_eq!(state.is_materialized(a), Ok(true));
+    assert_eq!(state.is_materialized(c), Ok(false));
+    // Asking doesn't compute it.
+    assert_eq!(state.is_materialized(c), Ok(false));
+
+    assert_eq!(state.read_f32_elems(c, 0, 4).unwrap(), [7.0, 10.0, 15.0, 22.0]);
+    assert_eq!(state.is_materialized(c), Ok(true));
+}
+
+#[test]
+fn materialize_and_writes_to_inputs_compute_pending_results() {
+    let mut state = HostState::new();
+    state.set_evaluation_mode(EvaluationMode::Lazy);
+    let a = row_major(&mut state, &[1.0, 2.0, 3.0, 4.0], 2, 2);
+    let explicit = state.matrix_multiply_f32(a, a, None).unwrap();
+    let dependent = state.matrix_multiply_f32(a, a, None).unwrap();
+
+    state.materialize_handle(explicit).unwrap();
+    assert_eq!(state.is_materialized(explicit), Ok(true));
+    assert_eq!(state.is_materialized(dependent), Ok(false));
+
+    // Inputs are captured by value, so overwriting one computes the result
+    // from the old values first.
+    state.write_f32(a, 0, &[0.0; 4]).unwrap();
+    assert_eq!(state.is_materialized(dependent), Ok(true));
+    assert_eq!(state.read_f32_elems(dependent, 0, 4).unwrap(), [7.0, 10.0, 15.0, 22.0]);
+}
+
+#[test]
+fn eager_and_spilled_results_are_materialized() {
+    let mut state = HostState::new();
+    let a = row_major(&mut state, &[1.0, 2.0, 3.0, 4.0], 2, 2);
+    let c = state.matrix_multiply_f32(a, a, None).unwrap();
+    assert_eq!(state.is_materialized(c), Ok(true));
+
+    // Too small a budget for either; both go to disk at the next call.
+    state.set_memory_budget(Some(1));
+    assert_eq!(state.is_materialized(c), Ok(true));
+    assert_eq!(state.get_memory_stats().spilled_bytes, 32);
+}
+
+#[test]
+fn freed_handles_are_an_error() {
+    let mut state = HostState::new();
+    state.set_evaluation_mode(EvaluationMode::Lazy);
+    let a = row_major(&mut state, &[1.0, 2.0, 3.0, 4.0], 2, 2);
+    let c = state.matrix_multiply_f32(a, a, None).unwrap();
+    state.free_buffer(c).unwrap();
+    assert_eq!(state.is_materialized(c), Err(HostError::InvalidHandle));
+}
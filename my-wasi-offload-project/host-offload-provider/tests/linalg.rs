@@ -0,0 +1,212 @@
+// In-place linear algebra: results land in an existing handle, which keeps
+// its layout, and structure hints limit what is read.
+
+use host_offload_provider::wasi_custom::host_offload::host_allocator::{
+    Bandwidths, ElementType, HostError, MatrixDimensions, MatrixLayout, MatrixShape, MatrixStructure, Triangle,
+};
+use host_offload_provider::HostState;
+
+mod common;
+use common::matrix;
+
+#[test]
+fn matmul_accumulate_adds_into_c_in_its_own_layout() {
+    let mut state = HostState::new();
+    let a = matrix(&mut state, &[1.0, 2.0, 3.0, 4.0], 2, 2, MatrixLayout::RowMajor);
+    let id = matrix(&mut state, &[1.0, 0.0, 0.0, 1.0], 2, 2, MatrixLayout::RowMajor);
+    // Column-major: [[10, 20], [30, 40]].
+    let c = matrix(&mut state, &[10.0, 30.0, 20.0, 40.0], 2, 2, MatrixLayout::ColumnMajor);
+
+    state.matmul_accumulate(a, id, c).unwrap();
+    state.matmul_accumulate(a, id, c).unwrap();
+    assert_eq!(state.read_f32_elems(c, 0, 4).unwrap(), [12.0, 36.0, 24.0, 48.0]);
+
+    // `c` may be an operand too: c += c * id.
+    state.matmul_accumulate(c, id, c).unwrap();
+    assert_eq!(state.read_f32_elems(c, 0, 4).unwrap(), [24.0, 72.0, 48.0, 96.0]);
+}
+
+#[test]
+fn matmul_accumulate_checks_c_shape() {
+    let mut state = HostState::new();
+    let a = matrix(&mut state, &[1.0; 6], 2, 3, MatrixLayout::RowMajor);
+    let b = matrix(&mut state, &[1.0; 6], 3, 2, MatrixLayout::RowMajor);
+    let c = matrix(&mut state, &[0.0; 6], 3, 2, MatrixLayout::RowMajor);
+    assert_eq!(state.matmul_accumulate(a, b, c), Err(HostError::DimensionMismatch));
+    assert_eq!(state.read_f32_elems(c, 0, 6).unwrap(), [0.0; 6]);
+}
+
+#[test]
+fn trsm_solves_either_triangle_and_ignores_the_other() {
+    let mut state = HostState::new();
+    // [[2, x], [1, 4]] lower; x is never read.
+    let a = matrix(&mut state, &[2.0, 99.0, 1.0, 4.0], 2, 2, MatrixLayout::RowMajor);
+    let b = matrix(&mut state, &[2.0, 4.0, 5.0, 6.0], 2, 2, MatrixLayout::RowMajor);
+    assert!(matches!(state.trsm_f32(1.0, a, b), Err(HostError::InvalidArguments(_))));
+
+    state.set_matrix_structure(a, MatrixStructure::Triangular(Triangle { lower: true, unit_diagonal: false })).unwrap();
+    state.trsm_f32(2.0, a, b).unwrap();
+    // 2x = 2*2, x + 4y = 2*5 for the first column, and so on.
+    assert_eq!(state.read_f32_elems(b, 0, 4).unwrap(), [2.0, 4.0, 2.0, 2.0]);
+
+    // Upper with a unit diagonal: [[1, 3], [x, 1]].
+    let u = matrix(&mut state, &[7.0, 3.0, 99.0, 7.0], 2, 2, MatrixLayout::RowMajor);
+    state.set_matrix_structure(u, MatrixStructure::Triangular(Triangle { lower: false, unit_diagonal: true })).unwrap();
+    let c = matrix(&mut state, &[7.0, 2.0], 2, 1, MatrixLayout::RowMajor);
+    state.trsm_f32(1.0, u, c).unwrap();
+    assert_eq!(state.read_f32_elems(c, 0, 2).unwrap(), [1.0, 2.0]);
+}
+
+#[test]
+fn banded_matvec_reads_only_the_band() {
+    let mut state = HostState::new();
+    // Tridiagonal, with 99s outside the band.
+    let a = matrix(&mut state, &[2.0, 1.0, 99.0, 1.0, 2.0, 1.0, 99.0, 1.0, 2.0], 3, 3, MatrixLayout::RowMajor);
+    let x = matrix(&mut state, &[1.0, 1.0, 1.0], 3, 1, MatrixLayout::RowMajor);
+    let y = matrix(&mut state, &[1.0, 1.0, 1.0], 3, 1, MatrixLayout::RowMajor);
+    let bands = MatrixStructure::Banded(Bandwidths { lower: 1, upper: 1 });
+    state.set_matrix_structure(a, bands).unwrap();
+    assert_eq!(state.get_matrix_structure(a), Ok(bands));
+
+    state.gbmv_f32(1.0, a, x, 10.0, y).unwrap();
+    assert_eq!(state.read_f32_elems(y, 0, 3).unwrap(), [13.0, 14.0, 13.0]);
+    // gemv takes the banded path too.
+    state.gemv_f32(1.0, a, x, 0.0, y).unwrap();
+    assert_eq!(state.read_f32_elems(y, 0, 3).unwrap(), [3.0, 4.0, 3.0]);
+
+    // New dims, no structure.
+    state.register_matrix_shape(a, MatrixShape { rows: 3, cols: 3, layout: MatrixLayout::ColumnMajor }).unwrap();
+    assert_eq!(state.get_matrix_structure(a), Ok(MatrixStructure::General));
+}
+
+#[test]
+fn symmetric_eigen_sorts_values_and_pairs_vectors() {
+    let mut state = HostState::new();
+    // [[2, 1], [1, 2]]: eigenvalues 1 and 3.
+    let a = matrix(&mut state, &[2.0, 1.0, 1.0, 2.0], 2, 2, MatrixLayout::ColumnMajor);
+    let eigen = state.symmetric_eigen(a).unwrap();
+    let values = state.read_f32_elems(eigen.values, 0, 2).unwrap();
+    assert!((values[0] - 1.0).abs() < 1e-5 && (values[1] - 3.0).abs() < 1e-5, "{:?}", values);
+
+    let vectors = state.describe_handle(eigen.vectors).unwrap();
+    assert_eq!(vectors.dims, Some(MatrixShape { rows: 2, cols: 2, layout: MatrixLayout::ColumnMajor }));
+    // Column-major, so each eigenvector is contiguous: a * v = lambda * v.
+    let v = state.read_f32_elems(eigen.vectors, 0, 4).unwrap();
+    for (i, lambda) in values.iter().enumerate() {
+        let (x, y) = (v[2 * i], v[2 * i + 1]);
+        assert!((2.0 * x + y - lambda * x).abs() < 1e-5 && (x + 2.0 * y - lambda * y).abs() < 1e-5);
+    }
+
+    let rect = matrix(&mut state, &[1.0; 6], 2, 3, MatrixLayout::RowMajor);
+    assert_eq!(state.symmetric_eigen(rect), Err(HostError::DimensionMismatch));
+}
+
+#[test]
+fn kron_and_outer_build_large_results_host_side() {
+    let mut state = HostState::new();
+    let a = matrix(&mut state, &[1.0, 2.0], 1, 2, MatrixLayout::RowMajor);
+    let b = matrix(&mut state, &[1.0, 10.0, 100.0, 1000.0], 2, 2, MatrixLayout::RowMajor);
+    let k = state.kron(a, b).unwrap();
+    assert_eq!(state.describe_handle(k).unwrap().dims, Some(MatrixShape { rows: 2, cols: 4, layout: MatrixLayout::RowMajor }));
+    assert_eq!(state.read_f32_elems(k, 0, 8).unwrap(), [1.0, 10.0, 2.0, 20.0, 100.0, 1000.0, 200.0, 2000.0]);
+
+    let x = state.allocate_typed_buffer(ElementType::F32, 2).unwrap();
+    state.write_f32(x, 0, &[1.0, 2.0]).unwrap();
+    let y = state.allocate_typed_buffer(ElementType::F32, 3).unwrap();
+    state.write_f32(y, 0, &[3.0, 4.0, 5.0]).unwrap();
+    let o = state.outer(x, y).unwrap();
+    assert_eq!(state.read_f32_elems(o, 0, 6).unwrap(), [3.0, 4.0, 5.0, 6.0, 8.0, 10.0]);
+
+    state.set_max_allocation(16);
+    assert!(matches!(state.outer(x, y), Err(HostError::AllocationTooLarge(_))));
+}
+
+#[test]
+fn einsum_lowers_common_contractions() {
+    let mut state = HostState::new();
+    let a = matrix(&mut state, &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0], 2, 3, MatrixLayout::RowMajor);
+    // Column-major: [[1, 0], [0, 1], [1, 1]].
+    let b = matrix(&mut state, &[1.0, 0.0, 1.0, 0.0, 1.0, 1.0], 3, 2, MatrixLayout::ColumnMajor);
+
+    for spec in ["ij,jk->ik", "ij,jk", "ij, jk -> ik"] {
+        let c = state.einsum(spec, &[a, b]).unwrap();
+        assert_eq!(state.describe_handle(c).unwrap().dims, Some(MatrixShape { rows: 2, cols: 2, layout: MatrixLayout::RowMajor }));
+        assert_eq!(state.read_f32_elems(c, 0, 4).unwrap(), [4.0, 5.0, 10.0, 11.0]);
+    }
+    // (a b)^T without a transpose of its own.
+    let ct = state.einsum("ij,jk->ki", &[a, b]).unwrap();
+    assert_eq!(state.read_f32_elems(ct, 0, 4).unwrap(), [4.0, 10.0, 5.0, 11.0]);
+
+    let at = state.einsum("ij->ji", &[a]).unwrap();
+    assert_eq!(state.read_f32_elems(at, 0, 6).unwrap(), [1.0, 4.0, 2.0, 5.0, 3.0, 6.0]);
+    let row_sums = state.einsum("ij->i", &[a]).unwrap();
+    assert_eq!(state.read_f32_elems(row_sums, 0, 2).unwrap(), [6.0, 15.0]);
+    let square = matrix(&mut state, &[1.0, 2.0, 3.0, 4.0], 2, 2, MatrixLayout::RowMajor);
+    let trace = state.einsum("ii->", &[square]).unwrap();
+    assert_eq!(state.read_f32_elems(trace, 0, 1).unwrap(), [5.0]);
+    // Elementwise product, then the sum of it.
+    let hadamard = state.einsum("ij,ij->ij", &[square, square]).unwrap();
+    assert_eq!(state.read_f32_elems(hadamard, 0, 4).unwrap(), [1.0, 4.0, 9.0, 16.0]);
+    let dot = state.einsum("ij,ij->", &[square, square]).unwrap();
+    assert_eq!(state.read_f32_elems(dot, 0, 1).unwrap(), [30.0]);
+
+    // One index: a plain buffer. Three operands chain left to right.
+    let x = state.allocate_typed_buffer(ElementType::F32, 3).unwrap();
+    state.write_f32(x, 0, &[1.0, 1.0, 2.0]).unwrap();
+    let ax = state.einsum("ij,j->i", &[a, x]).unwrap();
+    assert_eq!(state.read_f32_elems(ax, 0, 2).unwrap(), [9.0, 21.0]);
+    let xbx = state.einsum("i,ij,j", &[x, b, x]).unwrap_err();
+    assert_eq!(xbx, HostError::DimensionMismatch);
+    let abt = state.einsum("ij,jk,lk->il", &[a, b, square]).unwrap();
+    assert_eq!(state.read_f32_elems(abt, 0, 4).unwrap(), [14.0, 32.0, 32.0, 74.0]);
+}
+
+#[test]
+fn einsum_rejects_bad_specs() {
+    let mut state = HostState::new();
+    let a = matrix(&mut state, &[1.0; 6], 2, 3, MatrixLayout::RowMajor);
+    for spec in ["ij,jk->ik", "ij->ik", "ij->ii", "i1->i", "ijk->i", "ij,ij->ijk"] {
+        assert!(matches!(state.einsum(spec, &[a]), Err(HostError::InvalidArguments(_))), "{}", spec);
+    }
+    assert_eq!(state.einsum("ij,ij->", &[a, a]).map(drop), Ok(()));
+    assert_eq!(state.einsum("ij,jk->ik", &[a, a]), Err(HostError::DimensionMismatch));
+    assert_eq!(state.einsum("ii->i", &[a]), Err(HostError::DimensionMismatch));
+
+    state.set_max_allocation(16);
+    assert!(matches!(state.einsum("ij,kl->jl", &[a, a]), Err(HostError::AllocationTooLarge(_))));
+}
+
+#[test]
+fn dry_run_checks_shapes_and_skips_the_arithmetic() {
+    let mut state = HostState::new();
+    let a = matrix(&mut state, &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0], 2, 3, MatrixLayout::RowMajor);
+    let b = matrix(&mut state, &[1.0, 1.0, 1.0], 3, 1, MatrixLayout::RowMajor);
+    let c = matrix(&mut state, &[7.0, 8.0], 2, 1, MatrixLayout::RowMajor);
+    state.set_dry_run(true);
+
+    let product = state.matrix_multiply_f32(a, b, None).unwrap();
+    let dims = state.get_matrix_shape(product).unwrap();
+    assert_eq!((dims.rows, dims.cols), (2, 1));
+    assert_eq!(state.read_f32_elems(product, 0, 2).unwrap(), [0.0, 0.0]);
+    assert_eq!(state.matrix_multiply_f32(a, a, None), Err(HostError::DimensionMismatch));
+
+    state.matmul_accumulate(a, b, c).unwrap();
+    assert_eq!(state.read_f32_elems(c, 0, 2).unwrap(), [7.0, 8.0]);
+    let contracted = state.einsum("ij,jk->ik", &[a, b]).unwrap();
+    assert_eq!(state.get_matrix_shape(contracted).unwrap().rows, 2);
+
+    state.set_dry_run(false);
+    let product = state.matrix_multiply_f32(a, b, None).unwrap();
+    assert_eq!(state.read_f32_elems(product, 0, 2).unwrap(), [6.0, 15.0]);
+}
+
+#[test]
+fn matrix_dimensions_are_row_major_shapes() {
+    let mut state = HostState::new();
+    let a = matrix(&mut state, &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0], 2, 3, MatrixLayout::ColumnMajor);
+    assert_eq!(state.get_matrix_dimensions(a), Ok(MatrixDimensions { rows: 2, cols: 3 }));
+
+    // As registered by a guest built against 0.1.0.
+    state.register_matrix_dimensions(a, MatrixDimensions { rows: 3, cols: 2 }).unwrap();
+    assert_eq!(state.get_matrix_shape(a), Ok(MatrixShape { rows: 3, cols: 2, layout: MatrixLayout::RowMajor }));
+}
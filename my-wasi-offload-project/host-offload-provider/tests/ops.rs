@@ -0,0 +1,234 @@
+// Tensor ops that keep intermediate results (masks, selections, pooled rows)
+// host-side, so the guest reads back only what it needs.
+
+use host_offload_provider::wasi_custom::host_offload::host_allocator::{
+    CompareOp, ConcatAxis, ElementType, Handle, HostError, MatrixLayout, MatrixShape, MelParams, Pooling,
+    ReduceOp, Summation,
+};
+use host_offload_provider::HostState;
+
+mod common;
+use common::row_major;
+
+#[test]
+fn attention_masking_stays_host_side() {
+    let mut state = HostState::new();
+    let scores = row_major(&mut state, &[0.5, 2.0, -1.0, 3.0], 2, 2);
+    let threshold = state.allocate_typed_buffer(ElementType::F32, 1).unwrap();
+    state.write_f32(threshold, 0, &[1.0]).unwrap();
+
+    let mask = state.compare(scores, threshold, CompareOp::Lt).unwrap();
+    let info = state.describe_handle(mask).unwrap();
+    assert_eq!(info.element_type, Some(ElementType::U8));
+    assert_eq!(info.dims, Some(MatrixShape { rows: 2, cols: 2, layout: MatrixLayout::RowMajor }));
+    assert_eq!(state.read_from_host(mask, 0, 4).unwrap(), [1, 0, 1, 0]);
+
+    let zeros = row_major(&mut state, &[0.0; 4], 2, 2);
+    let kept = state.where_(mask, zeros, scores).unwrap();
+    assert_eq!(state.read_f32_elems(kept, 0, 4).unwrap(), [0.0, 2.0, 0.0, 3.0]);
+
+    state.masked_fill(scores, mask, f64::NEG_INFINITY).unwrap();
+    assert_eq!(state.read_f32_elems(scores, 0, 4).unwrap(), [f32::NEG_INFINITY, 2.0, f32::NEG_INFINITY, 3.0]);
+}
+
+#[test]
+fn masked_operands_must_line_up() {
+    let mut state = HostState::new();
+    let a = row_major(&mut state, &[1.0; 6], 2, 3);
+    let b = row_major(&mut state, &[1.0; 6], 3, 2);
+    assert_eq!(state.compare(a, b, CompareOp::Eq), Err(HostError::DimensionMismatch));
+
+    let mask = state.compare(a, a, CompareOp::Eq).unwrap();
+    assert_eq!(state.where_(mask, a, mask), Err(HostError::TypeMismatch));
+    let short = row_major(&mut state, &[1.0; 4], 2, 2);
+    assert_eq!(state.masked_fill(short, mask, 0.0), Err(HostError::DimensionMismatch));
+}
+
+#[test]
+fn top_k_returns_values_and_indices_per_line() {
+    let mut state = HostState::new();
+    let logits = row_major(&mut state, &[1.0, 5.0, 3.0, 4.0, 2.0, 6.0], 2, 3);
+
+    let per_row = state.top_k(logits, 2, ConcatAxis::Cols).unwrap();
+    let dims = MatrixShape { rows: 2, cols: 2, layout: MatrixLayout::RowMajor };
+    assert_eq!(state.describe_handle(per_row.values).unwrap().dims, Some(dims));
+    assert_eq!(state.read_f32_elems(per_row.values, 0, 4).unwrap(), [5.0, 3.0, 6.0, 4.0]);
+    assert_eq!(state.read_i32_elems(per_row.indices, 0, 4).unwrap(), [1, 2, 2, 0]);
+
+    let per_col = state.top_k(logits, 1, ConcatAxis::Rows).unwrap();
+    assert_eq!(state.read_f32_elems(per_col.values, 0, 3).unwrap(), [4.0, 5.0, 6.0]);
+    assert_eq!(state.read_i32_elems(per_col.indices, 0, 3).unwrap(), [1, 0, 1]);
+
+    assert!(matches!(state.top_k(logits, 3, ConcatAxis::Rows), Err(HostError::InvalidArguments(_))));
+    assert!(matches!(state.top_k(logits, 0, ConcatAxis::Cols), Err(HostError::InvalidArguments(_))));
+}
+
+#[test]
+fn reduce_folds_whole_buffers_and_single_axes() {
+    let mut state = HostState::new();
+    let h = row_major(&mut state, &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0], 2, 3);
+
+    let total = state.reduce(h, ReduceOp::Sum, None, Summation::Naive).unwrap();
+    assert_eq!(state.read_f32_elems(total, 0, 1).unwrap(), [21.0]);
+    let column_max = state.reduce(h, ReduceOp::Max, Some(ConcatAxis::Rows), Summation::Naive).unwrap();
+    assert_eq!(state.read_f32_elems(column_max, 0, 3).unwrap(), [4.0, 5.0, 6.0]);
+    let row_prod = state.reduce(h, ReduceOp::Prod, Some(ConcatAxis::Cols), Summation::Naive).unwrap();
+    assert_eq!(state.read_f32_elems(row_prod, 0, 2).unwrap(), [6.0, 120.0]);
+    assert_eq!(state.get_matrix_shape(row_prod).unwrap().rows, 2);
+}
+
+#[test]
+fn compensated_sums_hold_on_to_small_terms() {
+    let mut state = HostState::new();
+    // 1.0 followed by many terms each below half an ulp of it.
+    let mut values = vec![1.0f32];
+    values.extend(std::iter::repeat(1e-8).take(100_000));
+    let h = samples(&mut state, &values);
+
+    let sum = |state: &mut HostState, summation| {
+        let total = state.reduce(h, ReduceOp::Sum, None, summation).unwrap();
+        state.read_f32_elems(total, 0, 1).unwrap()[0]
+    };
+    assert_eq!(sum(&mut state, Summation::Naive), 1.0);
+    assert!((sum(&mut state, Summation::Kahan) - 1.001).abs() < 1e-5);
+    assert!((sum(&mut state, Summation::Pairwise) - 1.001).abs() < 1e-5);
+}
+
+#[test]
+fn embedding_lookup_pools_rows_host_side() {
+    let mut state = HostState::new();
+    let table = row_major(&mut state, &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0], 3, 2);
+
+    let rows = state.embedding_lookup(table, &[2, 0], Pooling::None).unwrap();
+    assert_eq!(state.read_f32_elems(rows, 0, 4).unwrap(), [5.0, 6.0, 1.0, 2.0]);
+    let sum = state.embedding_lookup(table, &[2, 0, 2], Pooling::Sum).unwrap();
+    assert_eq!(state.describe_handle(sum).unwrap().dims, Some(MatrixShape { rows: 1, cols: 2, layout: MatrixLayout::RowMajor }));
+    assert_eq!(state.read_f32_elems(sum, 0, 2).unwrap(), [11.0, 14.0]);
+    let mean = state.embedding_lookup(table, &[0, 1], Pooling::Mean).unwrap();
+    assert_eq!(state.read_f32_elems(mean, 0, 2).unwrap(), [2.0, 3.0]);
+
+    assert_eq!(state.embedding_lookup(table, &[3], Pooling::Sum), Err(HostError::CopyOutOfBounds));
+    assert!(matches!(state.embedding_lookup(table, &[], Pooling::Mean), Err(HostError::InvalidArguments(_))));
+}
+
+#[test]
+fn sdpa_matches_a_plain_softmax_across_tiles() {
+    let mut state = HostState::new();
+    let (queries, keys, d) = (3usize, 150usize, 4usize);
+    let value = |n: usize| ((n * 37) % 11) as f32 * 0.1 - 0.5;
+    let qs: Vec<f32> = (0..queries * d).map(value).collect();
+    let ks: Vec<f32> = (0..keys * d).map(|n| value(n + 5)).collect();
+    let vs: Vec<f32> = (0..keys * 2).map(|n| value(n + 9)).collect();
+    let q = row_major(&mut state, &qs, queries as u32, d as u32);
+    let k = row_major(&mut state, &ks, keys as u32, d as u32);
+    let v = row_major(&mut state, &vs, keys as u32, 2);
+    // Query i sees keys 0..=60 * i, so query 0 attends to key 0 alone.
+    let flags: Vec<u8> = (0..queries * keys).map(|n| (n % keys <= 60 * (n / keys)) as u8).collect();
+    let mask = state.allocate_typed_buffer(ElementType::U8, flags.len() as u64).unwrap();
+    state.write_to_host(&flags, mask, 0).unwrap();
+
+    let scale = 0.5;
+    let out = state.sdpa(q, k, v, Some(mask), scale).unwrap();
+    let got = state.read_f32_elems(out, 0, (queries * 2) as u64).unwrap();
+    for i in 0..queries {
+        let kept: Vec<usize> = (0..keys).filter(|&j| flags[i * keys + j] == 1).collect();
+        let scores: Vec<f32> = kept.iter().map(|&j| scale * (0..d).map(|c| qs[i * d + c] * ks[j * d + c]).sum::<f32>()).collect();
+        let max = scores.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+        let weights: Vec<f32> = scores.iter().map(|s| (s - max).exp()).collect();
+        let total: f32 = weights.iter().sum();
+        for c in 0..2 {
+            let expected = kept.iter().zip(&weights).map(|(&j, w)| w * vs[j * 2 + c]).sum::<f32>() / total;
+            assert!((got[i * 2 + c] - expected).abs() < 1e-5, "query {} col {}: {} vs {}", i, c, got[i * 2 + c], expected);
+        }
+    }
+
+    let none = state.allocate_typed_buffer(ElementType::U8, (queries * keys) as u64).unwrap();
+    state.write_to_host(&vec![0; queries * keys], none, 0).unwrap();
+    let masked = state.sdpa(q, k, v, Some(none), scale).unwrap();
+    assert_eq!(state.read_f32_elems(masked, 0, 6).unwrap(), [0.0; 6]);
+    assert_eq!(state.sdpa(q, v, v, None, scale), Err(HostError::DimensionMismatch));
+}
+
+#[test]
+fn kv_cache_grows_per_step_and_feeds_attention() {
+    let mut state = HostState::new();
+    let cache = state.create_kv_cache(2, 2, 3, 1).unwrap();
+    // Two prompt positions, then one decoded; columns are head 0, head 1.
+    let (k1, v1) = (row_major(&mut state, &[1.0, -1.0, 0.5, 2.0], 2, 2), row_major(&mut state, &[1.0, 10.0, 2.0, 20.0], 2, 2));
+    state.append_kv(cache, 1, k1, v1).unwrap();
+    let (k2, v2) = (row_major(&mut state, &[-0.5, 0.0], 1, 2), row_major(&mut state, &[3.0, 30.0], 1, 2));
+    state.append_kv(cache, 1, k2, v2).unwrap();
+    assert_eq!(state.kv_cache_length(cache, 1), Ok(3));
+    assert_eq!(state.kv_cache_length(cache, 0), Ok(0));
+    assert_eq!(state.append_kv(cache, 1, k2, v2), Err(HostError::CopyOutOfBounds));
+    assert!(matches!(state.append_kv(cache, 2, k2, v2), Err(HostError::InvalidArguments(_))));
+
+    let q = row_major(&mut state, &[0.7, -0.3], 1, 2);
+    let out = state.sdpa_cached(q, cache, 1, None, 1.0).unwrap();
+    let got = state.read_f32_elems(out, 0, 2).unwrap();
+    // Each head on its own through plain `sdpa`.
+    let heads = [([0.7], [1.0, 0.5, -0.5], [1.0, 2.0, 3.0]), ([-0.3], [-1.0, 2.0, 0.0], [10.0, 20.0, 30.0])];
+    for (head, (qh, kh, vh)) in heads.iter().enumerate() {
+        let (qh, kh, vh) = (row_major(&mut state, qh, 1, 1), row_major(&mut state, kh, 3, 1), row_major(&mut state, vh, 3, 1));
+        let expected = state.sdpa(qh, kh, vh, None, 1.0).unwrap();
+        let expected = state.read_f32_elems(expected, 0, 1).unwrap()[0];
+        assert!((got[head] - expected).abs() < 1e-5, "head {}: {} vs {}", head, got[head], expected);
+    }
+
+    // Rolling back an append forgets the positions too.
+    let cache = state.create_kv_cache(1, 2, 3, 1).unwrap();
+    state.begin_transaction().unwrap();
+    state.append_kv(cache, 0, k1, v1).unwrap();
+    state.rollback().unwrap();
+    assert_eq!(state.kv_cache_length(cache, 0), Ok(0));
+    assert!(matches!(state.kv_cache_length(q, 0), Err(HostError::InvalidArguments(_))));
+}
+
+fn samples(state: &mut HostState, values: &[f32]) -> Handle {
+    let h = state.allocate_typed_buffer(ElementType::F32, values.len() as u64).unwrap();
+    state.write_f32(h, 0, values).unwrap();
+    h
+}
+
+#[test]
+fn resampling_keeps_a_tone_below_both_nyquist_rates() {
+    let mut state = HostState::new();
+    let tone = |rate: f32, n: usize| (0..n).map(|i| (2.0 * std::f32::consts::PI * 440.0 * i as f32 / rate).sin()).collect::<Vec<_>>();
+    let input = samples(&mut state, &tone(16000.0, 1600));
+    for (to, len) in [(8000u32, 800usize), (22050, 2206)] {
+        let out = state.resample(input, 16000, to).unwrap();
+        assert_eq!(state.describe_handle(out).unwrap().size, len as u64 * 4);
+        let got = state.read_f32_elems(out, 0, len as u64).unwrap();
+        let expected = tone(to as f32, len);
+        // Away from the edges, where the filter runs out of input.
+        let margin = len / 10;
+        for i in margin..len - margin {
+            assert!((got[i] - expected[i]).abs() < 0.01, "{} Hz sample {}: {} vs {}", to, i, got[i], expected[i]);
+        }
+    }
+    assert!(matches!(state.resample(input, 0, 8000), Err(HostError::InvalidArguments(_))));
+}
+
+#[test]
+fn mel_spectrogram_puts_a_tone_in_its_band() {
+    let mut state = HostState::new();
+    let tone: Vec<f32> = (0..1600).map(|i| (2.0 * std::f32::consts::PI * 1000.0 * i as f32 / 16000.0).sin()).collect();
+    let audio = samples(&mut state, &tone);
+    let params = MelParams { sample_rate: 16000, n_fft: 400, hop_length: 160, n_mels: 80, f_min: 0.0, f_max: None, log_scale: true };
+    let mel = state.mel_spectrogram(audio, params).unwrap();
+    assert_eq!(state.describe_handle(mel).unwrap().dims, Some(MatrixShape { rows: 80, cols: 11, layout: MatrixLayout::RowMajor }));
+
+    // 1 kHz is 15 on the Slaney scale, which tops out at about 45.2 at 8 kHz,
+    // so the peak sits in filter 25 or 26 in every frame.
+    let values = state.read_f32_elems(mel, 0, 80 * 11).unwrap();
+    for frame in 0..11 {
+        let column: Vec<f32> = (0..80).map(|m| values[m * 11 + frame]).collect();
+        let peak = (0..80).max_by(|&a, &b| column[a].total_cmp(&column[b])).unwrap();
+        assert!((25..=26).contains(&peak), "frame {} peaks in filter {}", frame, peak);
+    }
+
+    let short = samples(&mut state, &tone[..400]);
+    assert!(matches!(state.mel_spectrogram(short, params), Err(HostError::InvalidArguments(_))));
+    let above_nyquist = MelParams { f_max: Some(9000.0), ..params };
+    assert!(matches!(state.mel_spectrogram(audio, above_nyquist), Err(HostError::InvalidArguments(_))));
+}
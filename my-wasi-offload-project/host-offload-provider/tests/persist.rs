@@ -0,0 +1,38 @@
+// Buffers detached from one provider state and adopted by another, which is
+// how the runner carries handles over to a re-instantiated client.
+
+use host_offload_provider::wasi_custom::host_offload::host_allocator::{
+    ElementType, HostError, MatrixLayout, MatrixShape,
+};
+use host_offload_provider::HostState;
+
+#[test]
+fn adopted_buffers_keep_their_contents_and_metadata() {
+    let mut old = HostState::new();
+    let dims = MatrixShape { rows: 2, cols: 2, layout: MatrixLayout::ColumnMajor };
+    let weights = old.allocate_typed_buffer(ElementType::F32, 4).unwrap();
+    old.write_f32(weights, 0, &[1.0, 2.0, 3.0, 4.0]).unwrap();
+    old.register_matrix_shape(weights, dims).unwrap();
+    let raw = old.allocate_buffer(3).unwrap();
+    old.write_to_host(&[7, 8, 9], raw, 0).unwrap();
+
+    let detached = old.detach(&[raw, weights]).unwrap();
+    assert_eq!(old.read_from_host(weights, 0, 4), Err(HostError::InvalidHandle));
+
+    let mut new = HostState::new();
+    let _unrelated = new.allocate_buffer(8).unwrap();
+    let handles = new.adopt(detached);
+    assert_eq!(new.read_from_host(handles[0], 0, 3).unwrap(), [7, 8, 9]);
+    assert_eq!(new.read_f32_elems(handles[1], 0, 4).unwrap(), [1.0, 2.0, 3.0, 4.0]);
+    assert_eq!(new.get_matrix_shape(handles[1]).unwrap(), dims);
+    assert_eq!(new.get_element_type(handles[1]).unwrap(), Some(ElementType::F32));
+}
+
+#[test]
+fn detach_is_all_or_nothing() {
+    let mut state = HostState::new();
+    let h = state.allocate_buffer(4).unwrap();
+    assert_eq!(state.detach(&[h, 999]).err(), Some(HostError::InvalidHandle));
+    assert!(matches!(state.detach(&[h, h]), Err(HostError::InvalidArguments(_))));
+    assert_eq!(state.read_from_host(h, 0, 4).unwrap(), [0; 4]);
+}
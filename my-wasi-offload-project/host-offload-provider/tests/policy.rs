@@ -0,0 +1,114 @@
+// Client policies: denied calls fail with `permission-denied`, naming the rule.
+
+use host_offload_provider::native::OffloadHost;
+use host_offload_provider::policy::Policy;
+use host_offload_provider::wasi_custom::host_offload::host_allocator::{
+    BatchInput, DumpDestination, DumpFormat, ElementType, Host, HostError, MatrixLayout, MatrixShape,
+    OpDescriptor, OpResult, StorageKind,
+};
+use host_offload_provider::wasi_custom::host_offload::random::Host as _;
+use host_offload_provider::wasi_custom::host_offload::tokenizer::Host as _;
+
+fn denied<T: std::fmt::Debug>(result: Result<T, HostError>, rule: &str) -> bool {
+    matches!(result, Err(HostError::PermissionDenied(ref message)) if message.contains(rule))
+}
+
+fn matrix(host: &mut OffloadHost, rows: u32, cols: u32) -> u32 {
+    let h = host.allocate_buffer(rows as u64 * cols as u64 * 4).unwrap().unwrap();
+    let dims = MatrixShape { rows, cols, layout: MatrixLayout::RowMajor };
+    host.register_matrix_shape(h, dims).unwrap().unwrap();
+    h
+}
+
+fn matrix_f64(host: &mut OffloadHost, rows: u32, cols: u32) -> u32 {
+    let h = host.allocate_typed_buffer(ElementType::F64, rows as u64 * cols as u64).unwrap().unwrap();
+    let dims = MatrixShape { rows, cols, layout: MatrixLayout::RowMajor };
+    host.register_matrix_shape(h, dims).unwrap().unwrap();
+    h
+}
+
+#[test]
+fn denied_functions_fail_or_trap() {
+    let mut policy = Policy::default();
+    policy.deny("begin-transaction", "admin");
+    policy.deny("list-devices", "admin");
+    policy.deny("free-buffer", "free-buffer");
+    let mut host = OffloadHost::new();
+    host.set_policy(policy);
+
+    assert!(denied(host.begin_transaction().unwrap(), "deny = \"admin\""));
+    assert!(host.list_devices().is_err());
+    let h = host.allocate_buffer(16).unwrap().unwrap();
+    assert!(denied(host.free_buffer(h).unwrap(), "free-buffer"));
+
+    // Nor through a batch: the ops before the denied one still run.
+    let batch = vec![OpDescriptor::Allocate(8), OpDescriptor::Free(BatchInput::Result(0)), OpDescriptor::Allocate(8)];
+    let results = host.execute_batch(batch).unwrap();
+    assert_eq!(results.len(), 2);
+    assert!(matches!(results[0], OpResult::Handle(_)));
+    assert!(matches!(results[1], OpResult::Failed(HostError::PermissionDenied(_))));
+}
+
+#[test]
+fn multiplies_are_capped_by_max_matmul_dim() {
+    let mut policy = Policy::default();
+    policy.max_matmul_dim = Some(8);
+    let mut host = OffloadHost::new();
+    host.set_policy(policy);
+    let small = matrix(&mut host, 8, 8);
+    let wide = matrix(&mut host, 8, 9);
+    assert!(host.matrix_multiply_f32(small, small).unwrap().is_ok());
+    assert!(denied(host.matrix_multiply_f32(small, wide).unwrap(), "max_matmul_dim = 8"));
+    assert!(denied(host.submit_matmul_f32(small, wide).unwrap(), "max_matmul_dim = 8"));
+
+    // Nor through the registry's f64 multiply.
+    let small = matrix_f64(&mut host, 8, 8);
+    let wide = matrix_f64(&mut host, 8, 9);
+    assert!(host.call_op("matmul-f64".to_string(), vec![small, small]).unwrap().is_ok());
+    assert!(denied(host.call_op("matmul-f64".to_string(), vec![small, wide]).unwrap(), "max_matmul_dim = 8"));
+}
+
+#[test]
+fn files_can_be_denied() {
+    let mut policy = Policy::default();
+    policy.deny_files = true;
+    let mut host = OffloadHost::new();
+    host.set_policy(policy);
+    let shm = host.allocate_buffer_with_storage(64, StorageKind::SharedMemory).unwrap();
+    assert!(denied(shm, "files = false"));
+    let h = matrix(&mut host, 2, 2);
+    let path = std::env::temp_dir().join("offload-policy-dump.csv").display().to_string();
+    assert!(denied(host.dump_matrix(h, DumpFormat::Csv, DumpDestination::File(path)).unwrap(), "files = false"));
+    assert!(host.dump_matrix(h, DumpFormat::Csv, DumpDestination::Stderr).unwrap().is_ok());
+    assert!(host.allocate_buffer_with_storage(64, StorageKind::Heap).unwrap().is_ok());
+}
+
+#[test]
+fn registry_ops_are_denied_as_the_function_they_stand_for() {
+    let mut policy = Policy::default();
+    policy.deny("matrix-multiply-f32", "matmul");
+    let mut host = OffloadHost::new();
+    host.set_policy(policy);
+    let h = matrix(&mut host, 4, 4);
+    assert!(denied(host.matrix_multiply_f32(h, h).unwrap(), "deny = \"matmul\""));
+    assert!(denied(host.call_op("matmul-f32".to_string(), vec![h, h]).unwrap(), "deny = \"matmul\""));
+    assert!(denied(host.parallel_map("matmul-f32".to_string(), h, 2).unwrap(), "deny = \"matmul\""));
+    assert!(host.call_op("relu-f32".to_string(), vec![h]).unwrap().is_ok());
+}
+
+#[test]
+fn random_and_tokenizer_are_held_to_the_policy() {
+    let mut policy = Policy::default();
+    policy.deny("random", "nn");
+    policy.deny_files = true;
+    let mut host = OffloadHost::new();
+    host.set_policy(policy);
+    assert!(denied(host.create_rng(7).unwrap(), "deny = \"nn\""));
+    let h = host.allocate_buffer(16).unwrap().unwrap();
+    assert!(denied(host.fill_random_bytes(h, 0, 16).unwrap(), "deny = \"nn\""));
+
+    // Refused before the file is looked for.
+    let mut tokenizers = host.tokenizers(std::env::temp_dir());
+    let err = tokenizers.load_tokenizer("tokenizer.json".to_string()).unwrap().unwrap_err();
+    assert!(err.contains("files = false"), "{}", err);
+}
@@ -0,0 +1,65 @@
+// Session rate limits: a call over budget fails with `rate-limited` and the
+// milliseconds to wait, consumes nothing, and goes through once the bucket
+// has refilled.
+
+use std::thread;
+use std::time::Duration;
+
+use host_offload_provider::session_admin::SessionLimits;
+use host_offload_provider::wasi_custom::host_offload::host_allocator::HostError;
+use host_offload_provider::HostState;
+
+fn limits(max_ops_per_sec: Option<u32>, max_bytes_per_sec: Option<u64>) -> SessionLimits {
+    SessionLimits { max_ops_per_sec, max_bytes_per_sec, max_op_millis: None, cpu_weight: None }
+}
+
+#[test]
+fn an_empty_ops_bucket_refuses_calls_until_it_refills() {
+    let mut state = HostState::new();
+    let h = state.allocate_buffer(4).unwrap();
+    state.open_session(limits(Some(20), None)).unwrap();
+
+    let mut accepted = 0;
+    let retry_after = loop {
+        match state.read_from_host(h, 0, 4) {
+            Ok(_) => accepted += 1,
+            Err(HostError::RateLimited(millis)) => break millis,
+            Err(e) => panic!("unexpected {:?}", e),
+        }
+    };
+    // The bucket starts full; the loop may earn back a token while it runs.
+    assert!((20..=21).contains(&accepted), "{} calls went through", accepted);
+    // One token at 20 per second takes at most 50ms.
+    assert!((1..=50).contains(&retry_after), "retry after {}ms", retry_after);
+    // Still empty straight away. The refused calls took nothing, so one wait
+    // is enough.
+    assert!(matches!(state.read_from_host(h, 0, 4), Err(HostError::RateLimited(_))));
+
+    thread::sleep(Duration::from_millis(retry_after + 10));
+    state.read_from_host(h, 0, 4).unwrap();
+}
+
+#[test]
+fn the_bytes_limit_counts_payload() {
+    let mut state = HostState::new();
+    let h = state.allocate_buffer(64).unwrap();
+    state.open_session(limits(None, Some(100))).unwrap();
+
+    state.read_from_host(h, 0, 64).unwrap();
+    assert!(matches!(state.read_from_host(h, 0, 64), Err(HostError::RateLimited(_))));
+    // A smaller read still fits in what is left.
+    state.read_from_host(h, 0, 32).unwrap();
+}
+
+#[test]
+fn a_limit_of_zero_is_refused() {
+    let mut state = HostState::new();
+    assert!(state.open_session(limits(Some(0), None)).is_err());
+    assert!(state.open_session(limits(None, Some(0))).is_err());
+    // Unset means unlimited.
+    state.open_session(limits(None, None)).unwrap();
+    let h = state.allocate_buffer(4).unwrap();
+    for _ in 0..1000 {
+        state.read_from_host(h, 0, 4).unwrap();
+    }
+}
@@ -0,0 +1,76 @@
+// `describe-op`: the provider's own ops and the ones an embedder registered
+// are described by their schema, anything else is `unknown-op`.
+
+use std::sync::Arc;
+
+use host_offload_provider::extensions::Extension;
+use host_offload_provider::wasi_custom::host_offload::host_allocator::{
+    ArgSchema, ArgShape, ElementType, Handle, HostError, OpSchema,
+};
+use host_offload_provider::HostState;
+
+struct Identity;
+
+impl Extension for Identity {
+    fn execute(&self, _op: &str, inputs: &[Handle]) -> Result<Vec<Handle>, String> {
+        Ok(inputs.to_vec())
+    }
+}
+
+fn arg(name: &str, element_type: Option<ElementType>, shape: ArgShape) -> ArgSchema {
+    ArgSchema { name: name.to_string(), element_type, shape }
+}
+
+fn identity_schema() -> OpSchema {
+    OpSchema {
+        name: "identity".to_string(),
+        description: "Returns its input".to_string(),
+        inputs: vec![arg("x", None, ArgShape::Any)],
+        outputs: vec![arg("y", None, ArgShape::Any)],
+    }
+}
+
+#[test]
+fn builtin_ops_are_described() {
+    let mut state = HostState::new();
+    let ops = state.list_ops();
+    for name in ["add-f32", "matmul-f32", "matmul-f64", "relu-f32"] {
+        assert!(ops.iter().any(|op| op == name), "{} isn't listed", name);
+        assert_eq!(state.describe_op(name).unwrap().name, name);
+    }
+
+    let matrix = |name: &str| arg(name, Some(ElementType::F32), ArgShape::Matrix);
+    let schema = state.describe_op("matmul-f32").unwrap();
+    assert_eq!(schema.inputs, [matrix("a"), matrix("b")]);
+    assert_eq!(schema.outputs, [matrix("c")]);
+    let relu = state.describe_op("relu-f32").unwrap();
+    assert_eq!((relu.inputs.len(), relu.outputs.len()), (1, 1));
+    assert_eq!(state.describe_op("matmul-f64").unwrap().inputs[0].element_type, Some(ElementType::F64));
+}
+
+#[test]
+fn registered_ops_are_described_while_registered() {
+    let mut state = HostState::new();
+    let extension: Arc<dyn Extension> = Arc::new(Identity);
+    state.register_extension(vec![identity_schema()], &extension).unwrap();
+
+    assert_eq!(state.describe_op("identity"), Ok(identity_schema()));
+    assert_eq!(state.list_extensions(), ["identity"]);
+    // Names are unique, builtins included.
+    let mut clash = identity_schema();
+    clash.name = "matmul-f32".to_string();
+    assert!(state.register_extension(vec![clash], &extension).is_err());
+
+    drop(extension);
+    assert_eq!(state.describe_op("identity"), Err(HostError::UnknownOp));
+    assert!(!state.list_ops().iter().any(|op| op == "identity"));
+}
+
+#[test]
+fn unknown_ops_are_an_error() {
+    let mut state = HostState::new();
+    assert_eq!(state.describe_op("no-such-op"), Err(HostError::UnknownOp));
+    assert_eq!(state.describe_op(""), Err(HostError::UnknownOp));
+    // Names are matched exactly.
+    assert_eq!(state.describe_op("MATMUL-F32"), Err(HostError::UnknownOp));
+}
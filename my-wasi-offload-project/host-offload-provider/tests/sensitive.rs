@@ -0,0 +1,75 @@
+// Sensitive buffers: kept in memory, out of reports, and refused by every
+// call that would copy them out of the provider.
+
+use host_offload_provider::analysis::Suggestion;
+use host_offload_provider::wasi_custom::host_offload::host_allocator::{
+    BufferFlags, DumpDestination, DumpFormat, Handle, HashAlgorithm, HostError, MatrixLayout, MatrixShape,
+};
+use host_offload_provider::HostState;
+
+fn secret(state: &mut HostState) -> Handle {
+    let h = state.allocate_buffer_with_flags(8, BufferFlags::SENSITIVE).unwrap();
+    state.write_f32(h, 0, &[3.0, 7.0]).unwrap();
+    let dims = MatrixShape { rows: 1, cols: 2, layout: MatrixLayout::RowMajor };
+    state.register_matrix_shape(h, dims).unwrap();
+    h
+}
+
+#[test]
+fn sensitive_buffers_are_not_copied_out() {
+    let mut state = HostState::new();
+    let h = secret(&mut state);
+    let hash = state.hash_buffer(h, HashAlgorithm::Sha256).unwrap();
+    let refused = |result: Result<(), HostError>| matches!(result, Err(HostError::InvalidArguments(_)));
+    assert!(refused(state.dump_matrix(h, DumpFormat::Csv, DumpDestination::Stderr)));
+    assert!(refused(state.publish_interned(h, &hash)));
+    assert!(refused(state.detach(&[h]).map(drop)));
+    // Still usable in the provider.
+    assert_eq!(state.read_f32_elems(h, 0, 2).unwrap(), [3.0, 7.0]);
+    state.free_buffer(h).unwrap();
+    assert_eq!(state.read_f32_elems(h, 0, 2), Err(HostError::InvalidHandle));
+}
+
+#[test]
+fn sensitive_buffers_are_never_spilled() {
+    let mut state = HostState::new();
+    let h = secret(&mut state);
+    let plain = state.allocate_buffer(4096).unwrap();
+    state.set_memory_budget(Some(0));
+    // The budget is enforced at the start of the next call.
+    state.get_storage_kind(h).unwrap();
+    let stats = state.get_memory_stats();
+    assert_eq!(stats.spilled_bytes, 4096);
+    assert_eq!(stats.resident_bytes, 8);
+
+    // Marking brings a spilled buffer back, for good.
+    state.mark_sensitive(plain).unwrap();
+    state.get_storage_kind(h).unwrap();
+    let stats = state.get_memory_stats();
+    assert_eq!(stats.spilled_bytes, 0);
+    assert_eq!(stats.resident_bytes, 4096 + 8);
+}
+
+#[test]
+fn rolling_back_keeps_the_buffer_sensitive() {
+    let mut state = HostState::new();
+    let h = secret(&mut state);
+    state.begin_transaction().unwrap();
+    state.write_f32(h, 0, &[0.0]).unwrap();
+    state.free_buffer(h).unwrap();
+    state.rollback().unwrap();
+    assert_eq!(state.read_f32_elems(h, 0, 2).unwrap(), [3.0, 7.0]);
+    let dumped = state.dump_matrix(h, DumpFormat::Text, DumpDestination::Stderr);
+    assert!(matches!(dumped, Err(HostError::InvalidArguments(_))));
+}
+
+#[test]
+fn sensitive_buffers_leave_no_trace_in_analysis() {
+    let mut state = HostState::new();
+    state.enable_analysis();
+    let h = secret(&mut state);
+    state.read_f32_elems(h, 0, 2).unwrap();
+
+    let report = state.analysis_report().unwrap();
+    assert!(!report.suggestions.iter().any(|s| matches!(s, Suggestion::UnchangedReadBack { .. })));
+}
@@ -0,0 +1,137 @@
+// Row selection and joining: `gather-rows` and `scatter-rows` keep the
+// element type and layout, `concat` and `stack` build row-major results, and
+// bad indices or shapes are errors rather than panics.
+
+use host_offload_provider::wasi_custom::host_offload::host_allocator::{
+    ConcatAxis, ElementType, Handle, HostError, MatrixLayout, MatrixShape,
+};
+use host_offload_provider::HostState;
+
+mod common;
+use common::{matrix, row_major};
+
+fn shape(rows: u32, cols: u32, layout: MatrixLayout) -> Option<MatrixShape> {
+    Some(MatrixShape { rows, cols, layout })
+}
+
+fn i32_matrix(state: &mut HostState, values: &[i32], rows: u32, cols: u32) -> Handle {
+    let h = state.allocate_typed_buffer(ElementType::S32, values.len() as u64).unwrap();
+    state.write_i32(h, 0, values).unwrap();
+    state.register_matrix_shape(h, MatrixShape { rows, cols, layout: MatrixLayout::RowMajor }).unwrap();
+    h
+}
+
+#[test]
+fn gather_picks_rows_in_the_source_layout() {
+    let mut state = HostState::new();
+    let m = row_major(&mut state, &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0], 3, 2);
+    let picked = state.gather_rows(m, &[2, 0, 2]).unwrap();
+    assert_eq!(state.describe_handle(picked).unwrap().dims, shape(3, 2, MatrixLayout::RowMajor));
+    assert_eq!(state.read_f32_elems(picked, 0, 6).unwrap(), [5.0, 6.0, 1.0, 2.0, 5.0, 6.0]);
+
+    // Column-major [[1, 2], [3, 4], [5, 6]].
+    let c = matrix(&mut state, &[1.0, 3.0, 5.0, 2.0, 4.0, 6.0], 3, 2, MatrixLayout::ColumnMajor);
+    let picked = state.gather_rows(c, &[2, 0]).unwrap();
+    assert_eq!(state.describe_handle(picked).unwrap().dims, shape(2, 2, MatrixLayout::ColumnMajor));
+    assert_eq!(state.read_f32_elems(picked, 0, 4).unwrap(), [5.0, 1.0, 6.0, 2.0]);
+
+    let ints = i32_matrix(&mut state, &[1, -2, 3, -4], 2, 2);
+    let picked = state.gather_rows(ints, &[1]).unwrap();
+    assert_eq!(state.describe_handle(picked).unwrap().element_type, Some(ElementType::S32));
+    assert_eq!(state.read_i32_elems(picked, 0, 2).unwrap(), [3, -4]);
+}
+
+#[test]
+fn gather_past_the_last_row_is_an_error() {
+    let mut state = HostState::new();
+    let m = row_major(&mut state, &[1.0, 2.0, 3.0, 4.0], 2, 2);
+    assert_eq!(state.gather_rows(m, &[0, 2]), Err(HostError::CopyOutOfBounds));
+    assert_eq!(state.gather_rows(m, &[u32::MAX]), Err(HostError::CopyOutOfBounds));
+    assert_eq!(state.get_memory_stats().live_handles, 1);
+}
+
+#[test]
+fn scatter_overwrites_rows_and_later_indices_win() {
+    let mut state = HostState::new();
+    let src = row_major(&mut state, &[1.0, 2.0, 3.0, 4.0], 2, 2);
+    let dst = row_major(&mut state, &[0.0; 6], 3, 2);
+    state.scatter_rows(src, dst, &[2, 0]).unwrap();
+    assert_eq!(state.read_f32_elems(dst, 0, 6).unwrap(), [3.0, 4.0, 0.0, 0.0, 1.0, 2.0]);
+
+    state.scatter_rows(src, dst, &[1, 1]).unwrap();
+    assert_eq!(state.read_f32_elems(dst, 0, 6).unwrap(), [3.0, 4.0, 3.0, 4.0, 1.0, 2.0]);
+
+    // Source and destination may be the same matrix.
+    state.scatter_rows(dst, dst, &[2, 1, 0]).unwrap();
+    assert_eq!(state.read_f32_elems(dst, 0, 6).unwrap(), [1.0, 2.0, 3.0, 4.0, 3.0, 4.0]);
+}
+
+#[test]
+fn bad_scatters_are_errors_and_leave_dst_alone() {
+    let mut state = HostState::new();
+    let src = row_major(&mut state, &[1.0, 2.0, 3.0, 4.0], 2, 2);
+    let dst = row_major(&mut state, &[0.0; 6], 3, 2);
+    let wide = row_major(&mut state, &[0.0; 6], 2, 3);
+    let ints = i32_matrix(&mut state, &[0; 6], 3, 2);
+
+    assert_eq!(state.scatter_rows(src, dst, &[0, 3]), Err(HostError::CopyOutOfBounds));
+    assert_eq!(state.scatter_rows(src, dst, &[0]), Err(HostError::DimensionMismatch));
+    assert_eq!(state.scatter_rows(src, wide, &[0, 1]), Err(HostError::DimensionMismatch));
+    assert_eq!(state.scatter_rows(src, ints, &[0, 1]), Err(HostError::TypeMismatch));
+    assert_eq!(state.read_f32_elems(dst, 0, 6).unwrap(), [0.0; 6]);
+}
+
+#[test]
+fn concat_joins_along_either_axis_into_row_major() {
+    let mut state = HostState::new();
+    let top = row_major(&mut state, &[1.0, 2.0], 1, 2);
+    // Column-major [[3, 4], [5, 6]].
+    let bottom = matrix(&mut state, &[3.0, 5.0, 4.0, 6.0], 2, 2, MatrixLayout::ColumnMajor);
+    let joined = state.concat(&[top, bottom], ConcatAxis::Rows).unwrap();
+    assert_eq!(state.describe_handle(joined).unwrap().dims, shape(3, 2, MatrixLayout::RowMajor));
+    assert_eq!(state.read_f32_elems(joined, 0, 6).unwrap(), [1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+
+    let left = row_major(&mut state, &[1.0, 2.0], 2, 1);
+    let joined = state.concat(&[left, bottom, left], ConcatAxis::Cols).unwrap();
+    assert_eq!(state.describe_handle(joined).unwrap().dims, shape(2, 4, MatrixLayout::RowMajor));
+    assert_eq!(state.read_f32_elems(joined, 0, 8).unwrap(), [1.0, 3.0, 4.0, 1.0, 2.0, 5.0, 6.0, 2.0]);
+
+    let ints = i32_matrix(&mut state, &[7, 8], 1, 2);
+    let joined = state.concat(&[ints, ints], ConcatAxis::Rows).unwrap();
+    assert_eq!(state.describe_handle(joined).unwrap().element_type, Some(ElementType::S32));
+    assert_eq!(state.read_i32_elems(joined, 0, 4).unwrap(), [7, 8, 7, 8]);
+}
+
+#[test]
+fn concat_needs_matching_shapes_and_types() {
+    let mut state = HostState::new();
+    let row = row_major(&mut state, &[1.0, 2.0], 1, 2);
+    let wide_row = row_major(&mut state, &[1.0, 2.0, 3.0], 1, 3);
+    let square = row_major(&mut state, &[1.0; 4], 2, 2);
+    let ints = i32_matrix(&mut state, &[1, 2], 1, 2);
+
+    assert_eq!(state.concat(&[row, wide_row], ConcatAxis::Rows), Err(HostError::DimensionMismatch));
+    assert_eq!(state.concat(&[row, square], ConcatAxis::Cols), Err(HostError::DimensionMismatch));
+    assert_eq!(state.concat(&[row, ints], ConcatAxis::Rows), Err(HostError::TypeMismatch));
+    assert!(matches!(state.concat(&[], ConcatAxis::Rows), Err(HostError::Other(_))));
+    assert_eq!(state.get_memory_stats().live_handles, 4);
+}
+
+#[test]
+fn stack_makes_each_buffer_a_row_in_stored_order() {
+    let mut state = HostState::new();
+    let a = row_major(&mut state, &[1.0, 2.0, 3.0, 4.0], 2, 2);
+    let b = matrix(&mut state, &[5.0, 6.0, 7.0, 8.0], 2, 2, MatrixLayout::ColumnMajor);
+    let batch = state.stack(&[a, b, a]).unwrap();
+    assert_eq!(state.describe_handle(batch).unwrap().dims, shape(3, 4, MatrixLayout::RowMajor));
+    assert_eq!(
+        state.read_f32_elems(batch, 0, 12).unwrap(),
+        [1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 1.0, 2.0, 3.0, 4.0]
+    );
+
+    let short = row_major(&mut state, &[1.0, 2.0], 1, 2);
+    let ints = i32_matrix(&mut state, &[1, 2, 3, 4], 2, 2);
+    assert_eq!(state.stack(&[a, short]), Err(HostError::DimensionMismatch));
+    assert_eq!(state.stack(&[a, ints]), Err(HostError::TypeMismatch));
+    assert!(matches!(state.stack(&[]), Err(HostError::Other(_))));
+}
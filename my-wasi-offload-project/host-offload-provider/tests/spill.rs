@@ -0,0 +1,73 @@
+// Spilling under a memory budget: the coldest heap buffers go to disk, come
+// back intact when used, and pinned ones never leave.
+
+use host_offload_provider::HostState;
+use host_offload_provider::wasi_custom::host_offload::host_allocator::Handle;
+
+const SIZE: u64 = 4096;
+
+fn filled(state: &mut HostState, byte: u8) -> Handle {
+    let h = state.allocate_buffer(SIZE).unwrap();
+    state.write_to_host(&vec![byte; SIZE as usize], h, 0).unwrap();
+    h
+}
+
+#[test]
+fn the_least_recently_used_buffer_spills_first() {
+    let mut state = HostState::new();
+    let a = filled(&mut state, 1);
+    let b = filled(&mut state, 2);
+    let c = filled(&mut state, 3);
+    // `b` is now the coldest.
+    state.read_from_host(a, 0, 1).unwrap();
+    state.set_memory_budget(Some(2 * SIZE));
+    // The budget is enforced at the start of the next call.
+    state.get_storage_kind(c).unwrap();
+
+    let stats = state.get_memory_stats();
+    assert_eq!((stats.resident_bytes, stats.spilled_bytes), (2 * SIZE, SIZE));
+    assert_eq!((stats.evictions, stats.reloads), (1, 0));
+    assert_eq!(stats.live_handles, 3);
+
+    // Read back whole, under the same handle.
+    assert_eq!(state.read_from_host(b, 0, SIZE).unwrap(), vec![2; SIZE as usize]);
+    let stats = state.get_memory_stats();
+    assert_eq!((stats.spilled_bytes, stats.reloads), (0, 1));
+}
+
+#[test]
+fn pinned_buffers_stay_resident() {
+    let mut state = HostState::new();
+    let pinned = filled(&mut state, 1);
+    let other = filled(&mut state, 2);
+    state.pin_buffer(pinned).unwrap();
+    state.read_from_host(other, 0, 1).unwrap();
+    state.set_memory_budget(Some(0));
+    state.get_storage_kind(pinned).unwrap();
+
+    let stats = state.get_memory_stats();
+    assert_eq!((stats.resident_bytes, stats.spilled_bytes), (SIZE, SIZE));
+    assert_eq!(stats.pinned_bytes, SIZE);
+
+    // Pinning a spilled buffer brings it back in.
+    state.pin_buffer(other).unwrap();
+    let stats = state.get_memory_stats();
+    assert_eq!((stats.spilled_bytes, stats.pinned_bytes), (0, 2 * SIZE));
+}
+
+#[test]
+fn freeing_a_spilled_buffer_forgets_it() {
+    let mut state = HostState::new();
+    let h = filled(&mut state, 1);
+    let other = filled(&mut state, 2);
+    state.set_memory_budget(Some(0));
+    // Spills both, then reads `other` back.
+    state.get_storage_kind(other).unwrap();
+    assert_eq!(state.get_memory_stats().spilled_bytes, SIZE);
+
+    // So that freeing doesn't spill `other` again.
+    state.set_memory_budget(None);
+    state.free_buffer(h).unwrap();
+    let stats = state.get_memory_stats();
+    assert_eq!((stats.spilled_bytes, stats.live_handles), (0, 1));
+}
@@ -0,0 +1,110 @@
+// Buffers behave the same whatever they are stored in: only where the bytes
+// live changes, and mapped or shared ones stay put under memory pressure.
+
+use host_offload_provider::HostState;
+use host_offload_provider::wasi_custom::host_offload::host_allocator::{HostError, ShmAccess, StorageKind};
+
+const KINDS: [StorageKind; 3] = [StorageKind::Heap, StorageKind::Mmap, StorageKind::SharedMemory];
+
+fn f32_bytes(values: &[f32]) -> Vec<u8> {
+    values.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+#[test]
+fn every_kind_reads_back_what_was_written() {
+    let mut state = HostState::new();
+    for kind in KINDS {
+        let h = state.allocate_buffer_with_storage(16, kind).unwrap();
+        assert_eq!(state.get_storage_kind(h), Ok(kind));
+        assert_eq!(state.read_from_host(h, 0, 16).unwrap(), vec![0; 16]);
+
+        state.write_to_host(&f32_bytes(&[1.0, 2.0, 3.0, 4.0]), h, 0).unwrap();
+        // Updated in place, so the buffer keeps its kind.
+        state.scal_f32(2.0, h).unwrap();
+        assert_eq!(state.get_storage_kind(h), Ok(kind));
+        assert_eq!(state.read_from_host(h, 0, 16).unwrap(), f32_bytes(&[2.0, 4.0, 6.0, 8.0]));
+        state.free_buffer(h).unwrap();
+    }
+}
+
+#[test]
+fn only_heap_buffers_are_spilled() {
+    let mut state = HostState::new();
+    let handles: Vec<_> = KINDS.iter().map(|&kind| state.allocate_buffer_with_storage(4096, kind).unwrap()).collect();
+    state.set_memory_budget(Some(0));
+    // The budget is enforced at the start of the next call; one that doesn't
+    // touch the heap buffer, which would read it straight back in.
+    state.get_storage_kind(handles[1]).unwrap();
+    let stats = state.get_memory_stats();
+    assert_eq!(stats.resident_bytes, 2 * 4096);
+    assert_eq!(stats.spilled_bytes, 4096);
+    for (h, kind) in handles.into_iter().zip(KINDS) {
+        assert_eq!(state.get_storage_kind(h), Ok(kind));
+    }
+}
+
+#[test]
+fn shared_memory_is_unlinked_on_free() {
+    let shm_objects = || {
+        let prefix = format!("offload-{}-", std::process::id());
+        std::fs::read_dir("/dev/shm")
+            .unwrap()
+            .filter(|entry| entry.as_ref().unwrap().file_name().to_string_lossy().starts_with(&prefix))
+            .count()
+    };
+    let mut state = HostState::new();
+    let before = shm_objects();
+    let h = state.allocate_buffer_with_storage(4096, StorageKind::SharedMemory).unwrap();
+    assert_eq!(shm_objects(), before + 1);
+    state.free_buffer(h).unwrap();
+    assert_eq!(shm_objects(), before);
+}
+
+// Maps `name` the way a sibling process would.
+fn map_peer(name: &str, size: usize, writable: bool) -> Option<&'static mut [u8]> {
+    let c_name = std::ffi::CString::new(name).unwrap();
+    let (flags, prot) = match writable {
+        true => (libc::O_RDWR, libc::PROT_READ | libc::PROT_WRITE),
+        false => (libc::O_RDONLY, libc::PROT_READ),
+    };
+    unsafe {
+        let fd = libc::shm_open(c_name.as_ptr(), flags, 0);
+        if fd < 0 {
+            return None;
+        }
+        let ptr = libc::mmap(std::ptr::null_mut(), size, prot, libc::MAP_SHARED, fd, 0);
+        libc::close(fd);
+        assert_ne!(ptr, libc::MAP_FAILED);
+        Some(std::slice::from_raw_parts_mut(ptr.cast(), size))
+    }
+}
+
+#[test]
+fn exported_buffers_are_shared_with_a_peer() {
+    let mut state = HostState::new();
+    let h = state.allocate_buffer_with_storage(4096, StorageKind::SharedMemory).unwrap();
+    state.write_to_host(b"from the provider", h, 0).unwrap();
+
+    let shm = state.export_shm(h, ShmAccess::WRITE).unwrap();
+    assert_eq!((shm.size, shm.writable), (4096, true));
+    let peer = map_peer(&shm.name, 4096, true).expect("exported buffer should open");
+    assert_eq!(&peer[..17], b"from the provider");
+    peer[..13].copy_from_slice(b"from the peer");
+    assert_eq!(state.read_from_host(h, 0, 13).unwrap(), b"from the peer");
+
+    // Existing mappings outlive a revoke and the buffer itself.
+    state.revoke_shm(h).unwrap();
+    state.free_buffer(h).unwrap();
+    assert_eq!(&peer[..13], b"from the peer");
+    assert!(map_peer(&shm.name, 4096, false).is_none());
+    unsafe { libc::munmap(peer.as_mut_ptr().cast(), 4096) };
+}
+
+#[test]
+fn only_shared_memory_buffers_can_be_exported() {
+    let mut state = HostState::new();
+    for kind in [StorageKind::Heap, StorageKind::Mmap] {
+        let h = state.allocate_buffer_with_storage(16, kind).unwrap();
+        assert!(matches!(state.export_shm(h, ShmAccess::empty()), Err(HostError::InvalidArguments(_))));
+    }
+}
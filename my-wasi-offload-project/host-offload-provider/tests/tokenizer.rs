@@ -0,0 +1,36 @@
+// The native `tokenizer` interface, over a tiny word-level tokenizer.
+
+use host_offload_provider::tokenize::Tokenizers;
+
+const WORD_LEVEL: &str = r#"{
+  "version": "1.0",
+  "truncation": null,
+  "padding": null,
+  "added_tokens": [],
+  "normalizer": null,
+  "pre_tokenizer": { "type": "Whitespace" },
+  "post_processor": null,
+  "decoder": null,
+  "model": { "type": "WordLevel", "vocab": { "[UNK]": 0, "hello": 1, "world": 2 }, "unk_token": "[UNK]" }
+}"#;
+
+#[test]
+fn encodes_and_decodes_with_files_under_the_directory() {
+    let dir = std::env::temp_dir().join(format!("offload-tokenizer-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("small")).unwrap();
+    std::fs::write(dir.join("small/tokenizer.json"), WORD_LEVEL).unwrap();
+
+    let mut tokenizers = Tokenizers::new(&dir);
+    let tok = tokenizers.load("small/tokenizer.json").unwrap();
+    let ids = tokenizers.encode(tok, "hello there world", false).unwrap();
+    assert_eq!(ids, [1, 0, 2]);
+    assert_eq!(tokenizers.decode(tok, &[1, 2], false).unwrap(), "hello world");
+
+    assert!(tokenizers.encode(tok + 1, "hello", false).is_err());
+    assert!(tokenizers.load("missing.json").is_err());
+    for escape in ["../tokenizer.json", "/etc/passwd", "small/../../x"] {
+        let err = tokenizers.load(escape).unwrap_err();
+        assert!(err.contains("inside the tokenizer directory"), "{}: {}", escape, err);
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,77 @@
+// Transactions: a rollback puts every handle back as it was at
+// `begin-transaction`, a commit keeps what was done, and only one is open
+// at a time.
+
+use host_offload_provider::HostState;
+use host_offload_provider::wasi_custom::host_offload::host_allocator::{HostError, MatrixLayout, MatrixShape};
+
+mod common;
+use common::row_major;
+
+#[test]
+fn rollback_restores_writes_shapes_and_frees() {
+    let mut state = HostState::new();
+    let written = row_major(&mut state, &[1.0, 2.0, 3.0, 4.0], 2, 2);
+    let reshaped = row_major(&mut state, &[5.0, 6.0], 1, 2);
+    let freed = row_major(&mut state, &[7.0], 1, 1);
+
+    state.begin_transaction().unwrap();
+    state.write_f32(written, 1, &[0.0]).unwrap();
+    state.register_matrix_shape(reshaped, MatrixShape { rows: 2, cols: 1, layout: MatrixLayout::ColumnMajor }).unwrap();
+    state.free_buffer(freed).unwrap();
+    let created = state.allocate_buffer(16).unwrap();
+    state.rollback().unwrap();
+
+    assert_eq!(state.read_f32_elems(written, 0, 4).unwrap(), [1.0, 2.0, 3.0, 4.0]);
+    assert_eq!(state.get_matrix_shape(reshaped).unwrap(), MatrixShape { rows: 1, cols: 2, layout: MatrixLayout::RowMajor });
+    assert_eq!(state.read_f32_elems(freed, 0, 1).unwrap(), [7.0]);
+    assert_eq!(state.read_from_host(created, 0, 1), Err(HostError::InvalidHandle));
+    assert_eq!(state.get_memory_stats().live_handles, 3);
+}
+
+#[test]
+fn commit_keeps_the_changes() {
+    let mut state = HostState::new();
+    let h = row_major(&mut state, &[1.0, 2.0], 1, 2);
+
+    state.begin_transaction().unwrap();
+    state.write_f32(h, 0, &[9.0]).unwrap();
+    let created = state.allocate_buffer(16).unwrap();
+    state.commit().unwrap();
+
+    assert_eq!(state.read_f32_elems(h, 0, 2).unwrap(), [9.0, 2.0]);
+    assert_eq!(state.read_from_host(created, 0, 1), Ok(vec![0]));
+    // Nothing left to roll back.
+    assert_eq!(state.rollback(), Err(HostError::InvalidTransaction));
+}
+
+#[test]
+fn one_transaction_at_a_time() {
+    let mut state = HostState::new();
+    assert_eq!(state.commit(), Err(HostError::InvalidTransaction));
+    assert_eq!(state.rollback(), Err(HostError::InvalidTransaction));
+    state.begin_transaction().unwrap();
+    assert_eq!(state.begin_transaction(), Err(HostError::InvalidTransaction));
+    state.rollback().unwrap();
+    state.begin_transaction().unwrap();
+}
+
+#[test]
+fn rollback_reloads_a_buffer_spilled_since_it_was_saved() {
+    let mut state = HostState::new();
+    let h = row_major(&mut state, &[1.0; 1024], 32, 32);
+    let other = state.allocate_buffer(4).unwrap();
+
+    state.begin_transaction().unwrap();
+    state.write_f32(h, 0, &[0.0]).unwrap();
+    state.set_memory_budget(Some(0));
+    // Spills both at the start of the call, then reads `other` back.
+    state.get_storage_kind(other).unwrap();
+    assert_eq!(state.get_memory_stats().spilled_bytes, 4096);
+    state.set_memory_budget(None);
+    state.rollback().unwrap();
+
+    let stats = state.get_memory_stats();
+    assert_eq!((stats.spilled_bytes, stats.resident_bytes), (0, 4096 + 4));
+    assert_eq!(state.read_f32_elems(h, 0, 2).unwrap(), [1.0, 1.0]);
+}
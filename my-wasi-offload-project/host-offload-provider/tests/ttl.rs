@@ -0,0 +1,57 @@
+// Buffers allocated with a TTL are reclaimed once it runs out, and a handle
+// that expired is told apart from one that never existed.
+
+use std::thread;
+use std::time::Duration;
+
+use host_offload_provider::HostState;
+use host_offload_provider::wasi_custom::host_offload::host_allocator::HostError;
+
+const TTL_MS: u64 = 30;
+
+fn outlive_ttl() {
+    thread::sleep(Duration::from_millis(TTL_MS * 2));
+}
+
+#[test]
+fn expired_buffers_are_reclaimed_on_the_next_call() {
+    let mut state = HostState::new();
+    let short = state.allocate_buffer_with_ttl(64, TTL_MS).unwrap();
+    let plain = state.allocate_buffer(64).unwrap();
+    state.write_to_host(&[7; 4], short, 0).unwrap();
+    assert_eq!(state.read_from_host(short, 0, 4), Ok(vec![7; 4]));
+
+    outlive_ttl();
+
+    assert_eq!(state.read_from_host(short, 0, 4), Err(HostError::Expired));
+    assert_eq!(state.free_buffer(short), Err(HostError::Expired));
+    assert_eq!(state.get_memory_stats().live_handles, 1);
+    assert_eq!(state.read_from_host(plain, 0, 4), Ok(vec![0; 4]));
+    assert_eq!(state.read_from_host(plain + 1000, 0, 4), Err(HostError::InvalidHandle));
+}
+
+#[test]
+fn pinned_buffers_wait_to_be_unpinned() {
+    let mut state = HostState::new();
+    let h = state.allocate_buffer_with_ttl(64, TTL_MS).unwrap();
+    state.pin_buffer(h).unwrap();
+
+    outlive_ttl();
+
+    assert_eq!(state.read_from_host(h, 0, 4), Ok(vec![0; 4]));
+    state.unpin_buffer(h).unwrap();
+    // Past its deadline already, so it goes at the next call.
+    assert_eq!(state.read_from_host(h, 0, 4), Err(HostError::Expired));
+}
+
+#[test]
+fn freeing_before_the_ttl_is_an_ordinary_free() {
+    let mut state = HostState::new();
+    let h = state.allocate_buffer_with_ttl(64, TTL_MS).unwrap();
+    state.free_buffer(h).unwrap();
+
+    outlive_ttl();
+
+    assert_eq!(state.read_from_host(h, 0, 4), Err(HostError::InvalidHandle));
+    assert_eq!(state.get_memory_stats().live_handles, 0);
+}
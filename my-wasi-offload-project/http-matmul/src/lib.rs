@@ -0,0 +1,189 @@
+// Generate bindings for the `http-matmul` world: the host-allocator import
+// plus the wasi:http types behind the exported `incoming-handler`.
+wit_bindgen::generate!({
+    world: "http-matmul",
+    path: "wit/world.wit",
+});
+
+use serde::{Deserialize, Serialize};
+
+use crate::host_allocator;
+use crate::wasi::http::types::{
+    Fields, IncomingRequest, Method, OutgoingBody, OutgoingResponse, ResponseOutparam,
+};
+use crate::wasi::io::streams::StreamError;
+use crate::wasi_custom::host_offload::host_allocator::{Handle, MatrixLayout, MatrixShape};
+use offload_common::codec;
+
+// Request bodies larger than this are rejected before touching the host.
+const MAX_BODY_BYTES: usize = 64 * 1024 * 1024;
+// `blocking-write-and-flush` accepts at most 4096 bytes per call.
+const WRITE_CHUNK: usize = 4096;
+
+// `POST /matmul` computes `A x B` for two row-major f32 matrices.
+//
+// With `content-type: application/json` the body is
+//   {"a": {"rows": 2, "cols": 3, "data": [...]}, "b": {...}}
+// and the response is a single matrix in the same shape.
+//
+// Otherwise the body is binary: `rows_a`, `cols_a`, `cols_b` as little-endian
+// u32, then A and B in the shared wire format. The response is `rows`, `cols`
+// as u32 followed by C.
+struct Component;
+
+#[derive(Deserialize, Serialize)]
+struct JsonMatrix {
+    rows: u32,
+    cols: u32,
+    data: Vec<f32>,
+}
+
+#[derive(Deserialize)]
+struct JsonRequest {
+    a: JsonMatrix,
+    b: JsonMatrix,
+}
+
+impl crate::exports::wasi::http::incoming_handler::Guest for Component {
+    fn handle(request: IncomingRequest, response_out: ResponseOutparam) {
+        let (status, content_type, body) = match serve(&request) {
+            Ok((content_type, body)) => (200, content_type, body),
+            Err((status, message)) => {
+                println!("[HTTP Wasm] {} {}", status, message);
+                (status, "text/plain", message.into_bytes())
+            }
+        };
+        respond(response_out, status, content_type, &body);
+    }
+}
+
+// Returns the response content type and body, or a status and error message.
+fn serve(request: &IncomingRequest) -> Result<(&'static str, Vec<u8>), (u16, String)> {
+    if request.path_with_query().as_deref() != Some("/matmul") {
+        return Err((404, "Only /matmul is served".to_string()));
+    }
+    if !matches!(request.method(), Method::Post) {
+        return Err((405, "Use POST".to_string()));
+    }
+    let json = request
+        .headers()
+        .get(&"content-type".to_string())
+        .iter()
+        .any(|value| value.starts_with(b"application/json"));
+    let body = read_body(request).map_err(|e| (400, e))?;
+
+    if json {
+        let req: JsonRequest = serde_json::from_slice(&body)
+            .map_err(|e| (400, format!("Invalid JSON body: {}", e)))?;
+        check_len(&req.a)?;
+        check_len(&req.b)?;
+        let (rows, cols, data) = offload_matmul(&req.a.data, req.a.rows, req.a.cols, &req.b.data, req.b.rows, req.b.cols)?;
+        let out = serde_json::to_vec(&JsonMatrix { rows, cols, data })
+            .map_err(|e| (500, format!("Failed to encode result: {}", e)))?;
+        Ok(("application/json", out))
+    } else {
+        let header = |i: usize| -> Result<u32, (u16, String)> {
+            body.get(i * 4..i * 4 + 4)
+                .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .ok_or((400, "Binary body is missing its 12-byte header".to_string()))
+        };
+        let (rows_a, inner, cols_b) = (header(0)?, header(1)?, header(2)?);
+        let data = codec::f32_from_le_bytes(&body[12..])
+            .ok_or((400, "Binary payload is not a whole number of f32 elements".to_string()))?;
+        let split = (rows_a as usize) * (inner as usize);
+        if data.len() != split + (inner as usize) * (cols_b as usize) {
+            return Err((400, "Binary payload length does not match the header".to_string()));
+        }
+        let (rows, cols, c) = offload_matmul(&data[..split], rows_a, inner, &data[split..], inner, cols_b)?;
+        let mut out = Vec::with_capacity(8 + c.len() * codec::F32_SIZE);
+        out.extend_from_slice(&rows.to_le_bytes());
+        out.extend_from_slice(&cols.to_le_bytes());
+        out.extend(codec::f32_to_le_bytes(&c));
+        Ok(("application/octet-stream", out))
+    }
+}
+
+fn check_len(m: &JsonMatrix) -> Result<(), (u16, String)> {
+    if m.data.len() != (m.rows as usize) * (m.cols as usize) {
+        return Err((400, format!("Matrix data has {} elements, expected {}x{}", m.data.len(), m.rows, m.cols)));
+    }
+    Ok(())
+}
+
+// Uploads both operands, multiplies on the host and reads C back. Host
+// buffers are scoped to an arena so nothing outlives the request.
+fn offload_matmul(a: &[f32], rows_a: u32, cols_a: u32, b: &[f32], rows_b: u32, cols_b: u32) -> Result<(u32, u32, Vec<f32>), (u16, String)> {
+    if cols_a != rows_b {
+        return Err((422, format!("Cannot multiply {}x{} by {}x{}", rows_a, cols_a, rows_b, cols_b)));
+    }
+    let arena = host_allocator::begin_arena();
+    let result = (|| {
+        let handle_a = upload(a, MatrixShape { rows: rows_a, cols: cols_a, layout: MatrixLayout::RowMajor })?;
+        let handle_b = upload(b, MatrixShape { rows: rows_b, cols: cols_b, layout: MatrixLayout::RowMajor })?;
+        let handle_c = host_allocator::matrix_multiply_f32(handle_a, handle_b)
+            .map_err(|e| format!("Matrix multiplication failed: {:?}", e))?;
+        let c_bytes = host_allocator::read_from_host(handle_c, 0, (rows_a * cols_b) as u64 * codec::F32_SIZE as u64)
+            .map_err(|e| format!("Failed to read result: {:?}", e))?;
+        codec::f32_from_le_bytes(&c_bytes).ok_or("Failed to parse result".to_string())
+    })();
+    if let Err(e) = host_allocator::end_arena(arena) {
+        println!("[HTTP Wasm] Failed to end arena {}: {:?}", arena, e);
+    }
+    result.map(|c| (rows_a, cols_b, c)).map_err(|e| (500, e))
+}
+
+fn upload(data: &[f32], dims: MatrixShape) -> Result<Handle, String> {
+    let bytes = codec::f32_to_le_bytes(data);
+    let handle = host_allocator::allocate_buffer(bytes.len() as u64)
+        .map_err(|e| format!("Failed to allocate: {:?}", e))?;
+    host_allocator::write_to_host(&bytes, handle, 0)
+        .map_err(|e| format!("Failed to write: {:?}", e))?;
+    host_allocator::register_matrix_shape(handle, dims)
+        .map_err(|e| format!("Failed to register dims: {:?}", e))?;
+    Ok(handle)
+}
+
+fn read_body(request: &IncomingRequest) -> Result<Vec<u8>, String> {
+    let body = request.consume().map_err(|_| "Request body already consumed".to_string())?;
+    let stream = body.stream().map_err(|_| "Request body stream unavailable".to_string())?;
+    let mut out = Vec::new();
+    loop {
+        match stream.blocking_read(64 * 1024) {
+            Ok(chunk) => {
+                out.extend_from_slice(&chunk);
+                if out.len() > MAX_BODY_BYTES {
+                    return Err(format!("Request body exceeds {} bytes", MAX_BODY_BYTES));
+                }
+            }
+            Err(StreamError::Closed) => break,
+            Err(StreamError::LastOperationFailed(e)) => {
+                return Err(format!("Failed to read request body: {}", e.to_debug_string()))
+            }
+        }
+    }
+    Ok(out)
+}
+
+fn respond(response_out: ResponseOutparam, status: u16, content_type: &str, body: &[u8]) {
+    let headers = Fields::new();
+    // Only fails for forbidden or malformed header names, and this one is neither.
+    let _ = headers.set(&"content-type".to_string(), &[content_type.as_bytes().to_vec()]);
+    let response = OutgoingResponse::new(headers);
+    let _ = response.set_status_code(status);
+    let outgoing = response.body().expect("outgoing body is taken once");
+    ResponseOutparam::set(response_out, Ok(response));
+
+    let stream = outgoing.write().expect("outgoing stream is taken once");
+    for chunk in body.chunks(WRITE_CHUNK) {
+        if let Err(e) = stream.blocking_write_and_flush(chunk) {
+            println!("[HTTP Wasm] Failed to write response body: {:?}", e);
+            return;
+        }
+    }
+    drop(stream);
+    if let Err(e) = OutgoingBody::finish(outgoing, None) {
+        println!("[HTTP Wasm] Failed to finish response body: {:?}", e);
+    }
+}
+
+export!(Component);
@@ -0,0 +1,90 @@
+#!/usr/bin/env node
+// Runs a client component against the JS host, like the runner does against
+// a provider:
+//   node run.js <client.wasm> <export> [--gpu]
+// The component is transpiled with jco into out/<name>/, its `host-allocator`
+// import bound to ./src/host-allocator.js and WASI to preview2-shim.
+import { mkdir, readFile, writeFile } from 'node:fs/promises';
+import { basename, dirname, join } from 'node:path';
+import { fileURLToPath, pathToFileURL } from 'node:url';
+
+import { transpile } from '@bytecodealliance/jco';
+import * as preview2 from '@bytecodealliance/preview2-shim';
+
+import * as hostAllocator from './src/host-allocator.js';
+import { openGpu } from './src/webgpu.js';
+
+const [path, exportName, ...flags] = process.argv.slice(2);
+if (!path || !exportName) {
+  console.error('Usage: node run.js <client.wasm> <export> [--gpu]');
+  process.exit(2);
+}
+
+const camelCase = (name) => name.replace(/-([a-z0-9])/g, (_, c) => c.toUpperCase());
+
+// On the GPU `matrix-multiply-f32` (and `-on`) returns a promise, which a synchronous
+// import can only do by suspending the guest, i.e. under JSPI.
+let gpu = null;
+if (flags.includes('--gpu')) {
+  if (typeof WebAssembly.Suspending !== 'function') {
+    console.error('[JS Host] --gpu needs JSPI; run with node --experimental-wasm-jspi');
+    process.exit(2);
+  }
+  gpu = await openGpu();
+  if (!gpu) {
+    console.error('[JS Host] No WebGPU adapter found; computing on the CPU');
+  }
+}
+hostAllocator.configure({ gpu });
+
+const name = basename(path, '.wasm').replace(/[^A-Za-z0-9_]/g, '_');
+const { files, imports } = await transpile(await readFile(path), {
+  name,
+  instantiation: { tag: 'async' },
+  noTypescript: true,
+  ...(gpu && {
+    asyncMode: 'jspi',
+    asyncImports: ['host-allocator#matrix-multiply-f32', 'host-allocator#matrix-multiply-f32-on'],
+    asyncExports: [exportName],
+  }),
+});
+const out = join(dirname(fileURLToPath(import.meta.url)), 'out', name);
+for (const [file, contents] of Object.entries(files)) {
+  await mkdir(dirname(join(out, file)), { recursive: true });
+  await writeFile(join(out, file), contents);
+}
+
+// `host-allocator` under its plain name, like every client imports it; WASI
+// interfaces from the shim, which groups them by package.
+const importObject = {};
+for (const specifier of imports) {
+  if (specifier === 'host-allocator') {
+    importObject[specifier] = hostAllocator;
+    continue;
+  }
+  const [, pkg, iface] = specifier.match(/^wasi:([^/]+)\/([^@]+)/) ?? [];
+  const shim = pkg && preview2[pkg]?.[camelCase(iface)];
+  if (!shim) {
+    console.error(`[JS Host] The client imports ${specifier}, which the JS host doesn't provide`);
+    process.exit(1);
+  }
+  importObject[specifier] = shim;
+}
+
+const { instantiate } = await import(pathToFileURL(join(out, `${name}.js`)).href);
+const instance = await instantiate((file) => WebAssembly.compile(files[file]), importObject);
+const run = instance[camelCase(exportName)];
+if (typeof run !== 'function') {
+  console.error(`[JS Host] Client has no export '${exportName}' of type func() -> result<_, string>`);
+  process.exit(1);
+}
+
+console.log(`[JS Host] Calling '${exportName}' in client Wasm...`);
+try {
+  await run();
+  console.log(`[JS Host] '${exportName}' executed successfully.`);
+} catch (e) {
+  // jco throws the `string` of an `Err` as the error's payload.
+  console.error(`[JS Host] '${exportName}' in client returned an error: ${e?.payload ?? e}`);
+  process.exitCode = 1;
+}
@@ -0,0 +1,7 @@
+// @ts-check
+// `npm run check` fails here when ./host-allocator.js drifts from the WIT:
+// the module must satisfy the interface jco generates from it.
+import * as hostAllocator from './host-allocator.js';
+
+/** @type {typeof import('../generated/interfaces/wasi-custom-host-offload-host-allocator.js')} */
+export const checked = hostAllocator;
@@ -0,0 +1,598 @@
+// @ts-check
+// `host-allocator` in JavaScript, for running client components under Node or
+// in a browser through jco (see ../README.md). Covers the core buffer API,
+// typed access, matrix multiply (on WebGPU when `configure` was given a
+// device), comparisons and the op registry's `matmul-f32`; everything else
+// fails with `other`, and `supports` only reports what is here.
+//
+// Values follow jco's mapping of the WIT: u64 is a BigInt, variants are
+// `{ tag, val }`, enums and flags are kebab-case strings, and an error result
+// is returned by throwing an object whose `payload` is the `host-error`.
+//
+// Buffer contents use the same wire format as the Rust providers:
+// little-endian, row-major unless registered otherwise.
+
+const INTERFACE_VERSION = { major: 0, minor: 2, patch: 0 };
+// Same default cap as the 64-bit Rust providers.
+const MAX_ALLOCATION = 1n << 40n;
+const ELEMENT_SIZES = { u8: 1, s8: 1, s32: 4, f16: 2, f32: 4, f64: 8 };
+
+class HostError extends Error {
+  constructor(tag, val) {
+    super(val === undefined ? tag : `${tag}: ${JSON.stringify(val, (_, v) => (typeof v === 'bigint' ? String(v) : v))}`);
+    this.payload = val === undefined ? { tag } : { tag, val };
+  }
+}
+
+/**
+ * @typedef {{ rows: number, cols: number, layout: string }} Dims
+ * @typedef {{ bytes: Uint8Array, dims?: Dims, elementType?: string, expiresAt?: number, pinned: boolean }} Buffer
+ */
+
+/** @type {Map<number, Buffer>} */
+const buffers = new Map();
+// Handles reclaimed by their TTL, so they report `expired` rather than `invalid-handle`.
+const expired = new Set();
+let nextHandle = 1;
+let computeMode = 'fast';
+let evictions = 0n;
+/** @type {{ name: string, matmul: (a: Float32Array, b: Float32Array, m: number, k: number, n: number) => Promise<Float32Array> } | null} */
+let gpu = null;
+
+// Embedder knob: a WebGPU device from `openGpu` in ./webgpu.js, or null for
+// the CPU. With one, `matrixMultiplyF32` and `matrixMultiplyF32On` return a
+// promise, so the client has to be transpiled with JSPI for those imports
+// (run.js does this).
+export function configure(options) {
+  gpu = options.gpu ?? null;
+}
+
+function log(message) {
+  console.log(`[Provider JS] ${message}`);
+}
+
+// Reclaims buffers whose TTL ran out; pinned ones wait until unpinned.
+function reap() {
+  const now = Date.now();
+  for (const [h, buffer] of buffers) {
+    if (buffer.expiresAt !== undefined && buffer.expiresAt <= now && !buffer.pinned) {
+      buffers.delete(h);
+      expired.add(h);
+      evictions += 1n;
+    }
+  }
+}
+
+/** @returns {Buffer} */
+function get(h) {
+  reap();
+  const buffer = buffers.get(h);
+  if (!buffer) {
+    throw new HostError(expired.has(h) ? 'expired' : 'invalid-handle');
+  }
+  return buffer;
+}
+
+function insert(bytes, fields = {}) {
+  const h = nextHandle++;
+  buffers.set(h, { bytes, pinned: false, ...fields });
+  return h;
+}
+
+function checkSize(size) {
+  if (size > MAX_ALLOCATION) {
+    throw new HostError('allocation-too-large', { requested: size, max: MAX_ALLOCATION });
+  }
+  return Number(size);
+}
+
+function checkType(buffer, expected) {
+  if (buffer.elementType !== undefined && buffer.elementType !== expected) {
+    throw new HostError('type-mismatch');
+  }
+}
+
+function range(buffer, offset, len) {
+  const start = BigInt(offset);
+  const end = start + BigInt(len);
+  if (end > BigInt(buffer.bytes.length)) {
+    throw new HostError('copy-out-of-bounds');
+  }
+  return [Number(start), Number(end)];
+}
+
+// --- buffers ---
+
+export function allocateBuffer(size) {
+  const h = insert(new Uint8Array(checkSize(size)));
+  log(`Allocated ${size} bytes with handle ${h}`);
+  return h;
+}
+
+export function allocateBufferWithTtl(size, ttlMs) {
+  const h = insert(new Uint8Array(checkSize(size)), { expiresAt: Date.now() + Number(ttlMs) });
+  log(`Allocated ${size} bytes with handle ${h}, expiring in ${ttlMs} ms`);
+  return h;
+}
+
+// Every buffer here is a typed array on the JS heap, whatever the hint.
+export function allocateBufferWithStorage(size, hint) {
+  const h = insert(new Uint8Array(checkSize(size)));
+  log(`Allocated ${size} bytes with handle ${h} (asked for ${hint}, got heap)`);
+  return h;
+}
+
+export function getStorageKind(h) {
+  get(h);
+  return 'heap';
+}
+
+export function exportShm(h) {
+  get(h);
+  throw new HostError('invalid-arguments', `Buffer ${h} is not in shared memory`);
+}
+
+export function revokeShm(h) {
+  get(h);
+  throw new HostError('invalid-arguments', `Buffer ${h} is not in shared memory`);
+}
+
+export function allocateTypedBuffer(elementType, count) {
+  const size = count * BigInt(ELEMENT_SIZES[elementType]);
+  const h = insert(new Uint8Array(checkSize(size)), { elementType });
+  log(`Allocated ${count} x ${elementType} with handle ${h}`);
+  return h;
+}
+
+export function freeBuffer(h) {
+  get(h);
+  buffers.delete(h);
+  log(`Freed handle ${h}`);
+}
+
+export function writeToHost(guestBytes, targetHandle, targetOffset) {
+  const buffer = get(targetHandle);
+  const [start] = range(buffer, targetOffset, guestBytes.length);
+  buffer.bytes.set(guestBytes, start);
+}
+
+export function readFromHost(sourceHandle, sourceOffset, len) {
+  const buffer = get(sourceHandle);
+  const [start, end] = range(buffer, sourceOffset, len);
+  return buffer.bytes.slice(start, end);
+}
+
+// --- typed access ---
+
+const TYPED = {
+  f32: { size: 4, array: Float32Array, get: 'getFloat32', set: 'setFloat32' },
+  f64: { size: 8, array: Float64Array, get: 'getFloat64', set: 'setFloat64' },
+  s32: { size: 4, array: Int32Array, get: 'getInt32', set: 'setInt32' },
+};
+
+function typedBuffer(h, type) {
+  const buffer = get(h);
+  checkType(buffer, type);
+  if (buffer.bytes.length % TYPED[type].size !== 0) {
+    throw new HostError('misaligned');
+  }
+  return buffer;
+}
+
+function writeTyped(type, h, offsetInElems, values) {
+  const { size, set } = TYPED[type];
+  const buffer = typedBuffer(h, type);
+  const [start] = range(buffer, offsetInElems * BigInt(size), values.length * size);
+  const view = new DataView(buffer.bytes.buffer, buffer.bytes.byteOffset + start);
+  values.forEach((v, i) => view[set](i * size, v, true));
+}
+
+function readTyped(type, h, offsetInElems, count) {
+  const { size, array, get: getter } = TYPED[type];
+  const buffer = typedBuffer(h, type);
+  const [start, end] = range(buffer, offsetInElems * BigInt(size), count * BigInt(size));
+  const view = new DataView(buffer.bytes.buffer, buffer.bytes.byteOffset + start, end - start);
+  const values = new array((end - start) / size);
+  for (let i = 0; i < values.length; i++) {
+    values[i] = view[getter](i * size, true);
+  }
+  return values;
+}
+
+export const writeF32 = (h, offset, values) => writeTyped('f32', h, offset, values);
+export const readF32 = (h, offset, count) => readTyped('f32', h, offset, count);
+export const writeF64 = (h, offset, values) => writeTyped('f64', h, offset, values);
+export const readF64 = (h, offset, count) => readTyped('f64', h, offset, count);
+export const writeI32 = (h, offset, values) => writeTyped('s32', h, offset, values);
+export const readI32 = (h, offset, count) => readTyped('s32', h, offset, count);
+
+// Whole buffer as f32, for compute.
+function allF32(h) {
+  const buffer = typedBuffer(h, 'f32');
+  return readTyped('f32', h, 0n, BigInt(buffer.bytes.length / 4));
+}
+
+function f32Bytes(values) {
+  const bytes = new Uint8Array(values.length * 4);
+  const view = new DataView(bytes.buffer);
+  values.forEach((v, i) => view.setFloat32(i * 4, v, true));
+  return bytes;
+}
+
+// --- metadata ---
+
+// The 0.1.0 form, without a layout: always row-major.
+export function registerMatrixDimensions(h, dims) {
+  registerMatrixShape(h, { rows: dims.rows, cols: dims.cols, layout: 'row-major' });
+}
+
+export function registerMatrixShape(h, shape) {
+  get(h).dims = { ...shape };
+}
+
+export function getMatrixDimensions(h) {
+  const { rows, cols } = getMatrixShape(h);
+  return { rows, cols };
+}
+
+export function getMatrixShape(h) {
+  const { dims } = get(h);
+  if (!dims) {
+    throw new HostError('invalid-handle');
+  }
+  return dims;
+}
+
+export function registerTensorMeta(h, meta) {
+  const buffer = get(h);
+  buffer.elementType = meta.elementType;
+  if (meta.dims) {
+    buffer.dims = { ...meta.dims };
+  }
+}
+
+export function getElementType(h) {
+  return get(h).elementType;
+}
+
+export function describeHandle(h) {
+  const buffer = get(h);
+  return {
+    size: BigInt(buffer.bytes.length),
+    dims: buffer.dims,
+    elementType: buffer.elementType,
+    placement: { tag: 'cpu' },
+    residentOn: [{ tag: 'cpu' }],
+  };
+}
+
+// --- compute ---
+
+function matrix(h) {
+  const { dims } = get(h);
+  if (!dims) {
+    throw new HostError('invalid-handle');
+  }
+  const data = allF32(h);
+  if (data.length !== dims.rows * dims.cols) {
+    throw new HostError('other', `Buffer ${h} size mismatch with dims`);
+  }
+  return { dims, data };
+}
+
+// Row-major copy of a matrix stored in either layout.
+function rowMajor({ dims, data }) {
+  if (dims.layout === 'row-major') {
+    return data;
+  }
+  const out = new Float32Array(data.length);
+  for (let i = 0; i < dims.rows; i++) {
+    for (let j = 0; j < dims.cols; j++) {
+      out[i * dims.cols + j] = data[j * dims.rows + i];
+    }
+  }
+  return out;
+}
+
+// A row-major rows x cols matrix stored in `layout`: for column-major that is
+// the row-major transpose, i.e. the column-major reading of a cols x rows one.
+function toLayout(data, rows, cols, layout) {
+  return layout === 'row-major' ? data : rowMajor({ dims: { rows: cols, cols: rows, layout: 'column-major' }, data });
+}
+
+// Sums in a fixed order, so `deterministic` and `fast` agree here.
+function matmulCpu(a, b, m, k, n) {
+  const c = new Float32Array(m * n);
+  for (let i = 0; i < m; i++) {
+    for (let j = 0; j < n; j++) {
+      let sum = 0;
+      for (let p = 0; p < k; p++) {
+        sum = Math.fround(sum + Math.fround(a[i * k + p] * b[p * n + j]));
+      }
+      c[i * n + j] = sum;
+    }
+  }
+  return c;
+}
+
+function checkDevice(device) {
+  if (device && device.tag === 'gpu' && !(gpu && device.val === 0)) {
+    throw new HostError('device-unavailable');
+  }
+}
+
+export function matrixMultiplyF32(handleA, handleB) {
+  return matrixMultiplyF32On(handleA, handleB, undefined);
+}
+
+export function matrixMultiplyF32On(handleA, handleB, on) {
+  log(`Matrix multiply f32 for A:${handleA} and B:${handleB}`);
+  checkDevice(on);
+  const a = matrix(handleA);
+  const b = matrix(handleB);
+  if (a.dims.cols !== b.dims.rows) {
+    throw new HostError('dimension-mismatch');
+  }
+  const [m, k, n] = [a.dims.rows, a.dims.cols, b.dims.cols];
+  const store = (c) => {
+    const layout = a.dims.layout;
+    const h = insert(f32Bytes(toLayout(c, m, n, layout)), { dims: { rows: m, cols: n, layout }, elementType: 'f32' });
+    log(`Stored result C (${m},${n}) with handle ${h}`);
+    return h;
+  };
+  if (gpu && on?.tag !== 'cpu') {
+    return gpu.matmul(rowMajor(a), rowMajor(b), m, k, n).then(store);
+  }
+  return store(matmulCpu(rowMajor(a), rowMajor(b), m, k, n));
+}
+
+// Always on the CPU: the sum is taken here anyway.
+export function matmulAccumulate(handleA, handleB, handleC) {
+  log(`Matrix multiply-accumulate f32: ${handleC} += ${handleA} * ${handleB}`);
+  const a = matrix(handleA);
+  const b = matrix(handleB);
+  const c = matrix(handleC);
+  if (a.dims.cols !== b.dims.rows || c.dims.rows !== a.dims.rows || c.dims.cols !== b.dims.cols) {
+    throw new HostError('dimension-mismatch');
+  }
+  const [m, k, n] = [a.dims.rows, a.dims.cols, b.dims.cols];
+  const product = toLayout(matmulCpu(rowMajor(a), rowMajor(b), m, k, n), m, n, c.dims.layout);
+  get(handleC).bytes = f32Bytes(allF32(handleC).map((v, i) => v + product[i]));
+}
+
+export function compareBuffersF32(handleA, handleB, rtol, atol) {
+  const a = allF32(handleA);
+  const b = allF32(handleB);
+  if (a.length !== b.length) {
+    throw new HostError('dimension-mismatch');
+  }
+  const report = { allClose: true, maxAbsError: 0, maxRelError: 0, firstMismatch: undefined };
+  for (let i = 0; i < a.length; i++) {
+    const abs = Math.abs(a[i] - b[i]);
+    report.maxAbsError = Math.max(report.maxAbsError, abs);
+    if (b[i] !== 0) {
+      report.maxRelError = Math.max(report.maxRelError, abs / Math.abs(b[i]));
+    }
+    if (!(abs <= atol + rtol * Math.abs(b[i])) && report.firstMismatch === undefined) {
+      report.allClose = false;
+      report.firstMismatch = BigInt(i);
+    }
+  }
+  return report;
+}
+
+export function axpyF32(alpha, x, y) {
+  const xs = allF32(x);
+  const ys = allF32(y);
+  if (xs.length !== ys.length) {
+    throw new HostError('dimension-mismatch');
+  }
+  get(y).bytes = f32Bytes(ys.map((v, i) => alpha * xs[i] + v));
+}
+
+export function scalF32(alpha, x) {
+  get(x).bytes = f32Bytes(allF32(x).map((v) => alpha * v));
+}
+
+// --- settings and introspection ---
+
+export function setComputeMode(mode) {
+  computeMode = mode;
+}
+
+// One backend, one thread: nothing for a hint to change.
+export function setComputeHint(_hint) {}
+
+// Compute calls here run to completion on the event loop; there is nothing
+// to abandon, so the budget is accepted and ignored.
+export function setOpTimeout(_millis) {}
+
+// Everything is computed eagerly; lazy mode is accepted but changes nothing
+// a guest can observe besides timing.
+export function setEvaluationMode(_mode) {}
+
+export function materialize(h) {
+  get(h);
+}
+
+export function isMaterialized(h) {
+  get(h);
+  return true;
+}
+
+export function getBackendInfo() {
+  return { name: gpu ? `webgpu (${gpu.name})` : 'js-cpu', computeMode };
+}
+
+export function listDevices() {
+  const devices = [{ device: { tag: 'cpu' }, name: 'JavaScript', backend: 'js-cpu', memoryBytes: undefined }];
+  if (gpu) {
+    devices.push({ device: { tag: 'gpu', val: 0 }, name: gpu.name, backend: 'webgpu', memoryBytes: undefined });
+  }
+  return devices;
+}
+
+// Each call goes to the one backend that serves its device.
+export function getBackendStats() {
+  return [];
+}
+
+export function setPlacement(h, target) {
+  get(h);
+  checkDevice(target);
+}
+
+export function prefetch(h, target) {
+  get(h);
+  checkDevice(target);
+}
+
+export function getMemoryStats() {
+  reap();
+  let residentBytes = 0n;
+  let pinnedBytes = 0n;
+  for (const buffer of buffers.values()) {
+    residentBytes += BigInt(buffer.bytes.length);
+    if (buffer.pinned) {
+      pinnedBytes += BigInt(buffer.bytes.length);
+    }
+  }
+  return {
+    residentBytes,
+    spilledBytes: 0n,
+    pinnedBytes,
+    nodeBytes: [],
+    liveHandles: buffers.size,
+    evictions,
+    reloads: 0n,
+  };
+}
+
+export function pinBuffer(h) {
+  get(h).pinned = true;
+}
+
+export function unpinBuffer(h) {
+  get(h).pinned = false;
+}
+
+export function getInterfaceVersion() {
+  return INTERFACE_VERSION;
+}
+
+export function supports(feature) {
+  return ['f32', 'ttl', 'ops', ...(gpu ? ['gpu'] : [])].includes(feature);
+}
+
+// --- named ops ---
+
+const matrixArg = (name) => ({ name, elementType: 'f32', shape: 'matrix' });
+const OPS = {
+  'matmul-f32': {
+    schema: {
+      name: 'matmul-f32',
+      description: 'C = A x B, like `matrix-multiply-f32`',
+      inputs: [matrixArg('a'), matrixArg('b')],
+      outputs: [matrixArg('c')],
+    },
+    // On the CPU: only the multiply imports themselves may return a promise.
+    run: ([a, b]) => matrixMultiplyF32On(a, b, { tag: 'cpu' }),
+  },
+};
+
+export function listOps() {
+  return Object.keys(OPS);
+}
+
+export function describeOp(opName) {
+  const op = OPS[opName];
+  if (!op) {
+    throw new HostError('unknown-op');
+  }
+  return op.schema;
+}
+
+export function callOp(opName, inputs) {
+  const { schema, run } = OPS[opName] ?? {};
+  if (!schema) {
+    throw new HostError('unknown-op');
+  }
+  if (inputs.length !== schema.inputs.length) {
+    throw new HostError('invalid-arguments', `'${opName}' takes ${schema.inputs.length} inputs, got ${inputs.length}`);
+  }
+  inputs.forEach((h, i) => {
+    if (!get(h).dims) {
+      throw new HostError('invalid-arguments', `'${opName}': input '${schema.inputs[i].name}' (handle ${h}) must be a matrix`);
+    }
+  });
+  return [run(inputs)];
+}
+
+// No plugins under the JS host.
+export function listExtensions() {
+  return [];
+}
+
+export function callExtension(_opName, _inputs) {
+  throw new HostError('unknown-op');
+}
+
+// --- not offered here ---
+
+// Fails with `other` for result-returning functions; the rest trap.
+function unsupported(name, returnsResult = true) {
+  return () => {
+    if (returnsResult) {
+      throw new HostError('other', `${name} is not supported by the JS host`);
+    }
+    throw new Error(`${name} is not supported by the JS host`);
+  };
+}
+
+export const gemvF32 = unsupported('gemv-f32');
+export const setMatrixStructure = unsupported('set-matrix-structure');
+export const getMatrixStructure = unsupported('get-matrix-structure');
+export const trsmF32 = unsupported('trsm-f32');
+export const gbmvF32 = unsupported('gbmv-f32');
+export const symmetricEigen = unsupported('symmetric-eigen');
+export const kron = unsupported('kron');
+export const outer = unsupported('outer');
+export const einsum = unsupported('einsum');
+export const gatherRows = unsupported('gather-rows');
+export const scatterRows = unsupported('scatter-rows');
+export const embeddingLookup = unsupported('embedding-lookup');
+export const concat = unsupported('concat');
+export const stack = unsupported('stack');
+export const cast = unsupported('cast');
+export const compare = unsupported('compare');
+export const where = unsupported('where');
+export const maskedFill = unsupported('masked-fill');
+export const topK = unsupported('top-k');
+export const estimateCost = unsupported('estimate-cost');
+export const reduce = unsupported('reduce');
+export const parallelMap = unsupported('parallel-map');
+export const sdpa = unsupported('sdpa');
+export const createKvCache = unsupported('create-kv-cache');
+export const appendKv = unsupported('append-kv');
+export const kvCacheLength = unsupported('kv-cache-length');
+export const sdpaCached = unsupported('sdpa-cached');
+export const resample = unsupported('resample');
+export const melSpectrogram = unsupported('mel-spectrogram');
+export const dumpMatrix = unsupported('dump-matrix');
+export const hashBuffer = unsupported('hash-buffer');
+export const internBuffer = unsupported('intern-buffer');
+export const publishInterned = unsupported('publish-interned');
+export const setDryRun = unsupported('set-dry-run', false);
+export const beginArena = unsupported('begin-arena', false);
+export const endArena = unsupported('end-arena');
+export const beginTransaction = unsupported('begin-transaction');
+export const commit = unsupported('commit');
+export const rollback = unsupported('rollback');
+export const executeGraph = unsupported('execute-graph');
+export const submitMatmulF32 = unsupported('submit-matmul-f32');
+export const jobStatus = unsupported('job-status');
+export const waitJob = unsupported('wait-job');
+export const pollJob = unsupported('poll-job');
+export const executeBatch = unsupported('execute-batch', false);
+export const allocateBufferWithFlags = unsupported('allocate-buffer-with-flags');
+export const markSensitive = unsupported('mark-sensitive');
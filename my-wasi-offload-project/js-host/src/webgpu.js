@@ -0,0 +1,92 @@
+// @ts-check
+// The WebGPU side of the JS host: a single f32 matmul kernel. Browsers
+// provide `navigator.gpu`; under Node it comes from the optional `webgpu`
+// package (Dawn).
+
+const SHADER = /* wgsl */ `
+struct Dims { m: u32, k: u32, n: u32 }
+
+@group(0) @binding(0) var<uniform> dims: Dims;
+@group(0) @binding(1) var<storage, read> a: array<f32>;
+@group(0) @binding(2) var<storage, read> b: array<f32>;
+@group(0) @binding(3) var<storage, read_write> c: array<f32>;
+
+@compute @workgroup_size(8, 8)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+  if (id.x >= dims.n || id.y >= dims.m) {
+    return;
+  }
+  var sum = 0.0;
+  for (var p = 0u; p < dims.k; p++) {
+    sum += a[id.y * dims.k + p] * b[p * dims.n + id.x];
+  }
+  c[id.y * dims.n + id.x] = sum;
+}
+`;
+
+// A device for `configure({ gpu })` in ./host-allocator.js, or null when
+// there is no WebGPU adapter to be had.
+export async function openGpu() {
+  let gpu = globalThis.navigator?.gpu;
+  if (!gpu) {
+    try {
+      const { create, globals } = await import('webgpu');
+      Object.assign(globalThis, globals);
+      gpu = create([]);
+    } catch {
+      return null;
+    }
+  }
+  const adapter = await gpu.requestAdapter();
+  if (!adapter) {
+    return null;
+  }
+  const device = await adapter.requestDevice();
+  const pipeline = device.createComputePipeline({
+    layout: 'auto',
+    compute: { module: device.createShaderModule({ code: SHADER }), entryPoint: 'main' },
+  });
+  return {
+    name: adapter.info?.description || adapter.info?.vendor || 'WebGPU adapter',
+    matmul: (a, b, m, k, n) => matmul(device, pipeline, a, b, m, k, n),
+  };
+}
+
+// C = A x B for row-major A (m x k) and B (k x n).
+async function matmul(device, pipeline, a, b, m, k, n) {
+  const { STORAGE, UNIFORM, COPY_SRC, COPY_DST, MAP_READ } = GPUBufferUsage;
+  // WebGPU rejects zero-sized bindings.
+  const buffer = (size, usage) => device.createBuffer({ size: Math.max(size, 16), usage });
+  const upload = (data, usage) => {
+    const gpuBuffer = buffer(data.byteLength, usage | COPY_DST);
+    device.queue.writeBuffer(gpuBuffer, 0, data);
+    return gpuBuffer;
+  };
+
+  const size = m * n * 4;
+  const dims = upload(new Uint32Array([m, k, n, 0]), UNIFORM);
+  const inputs = [upload(a, STORAGE), upload(b, STORAGE)];
+  const output = buffer(size, STORAGE | COPY_SRC);
+  const readback = buffer(size, MAP_READ | COPY_DST);
+  const bindGroup = device.createBindGroup({
+    layout: pipeline.getBindGroupLayout(0),
+    entries: [dims, ...inputs, output].map((gpuBuffer, binding) => ({ binding, resource: { buffer: gpuBuffer } })),
+  });
+
+  const encoder = device.createCommandEncoder();
+  const pass = encoder.beginComputePass();
+  pass.setPipeline(pipeline);
+  pass.setBindGroup(0, bindGroup);
+  pass.dispatchWorkgroups(Math.ceil(n / 8), Math.ceil(m / 8));
+  pass.end();
+  encoder.copyBufferToBuffer(output, 0, readback, 0, Math.max(size, 16));
+  device.queue.submit([encoder.finish()]);
+
+  await readback.mapAsync(GPUMapMode.READ);
+  const c = new Float32Array(readback.getMappedRange().slice(0, size));
+  readback.unmap();
+  for (const gpuBuffer of [dims, ...inputs, output, readback]) {
+    gpuBuffer.destroy();
+  }
+  return c;
+}
@@ -11,9 +11,15 @@ wit_bindgen::generate!({
 // (matches the import key in `client/wit/world.wit`).
 use crate::host_allocator;
 // And types from the imported interface's `use` statement.
-use crate::wasi_custom::host_offload::host_allocator::{HostError, MatrixDimensions};
+use crate::wasi_custom::host_offload::host_allocator::{HostError, MatrixLayout, MatrixShape};
+use offload_common::{codec, testdata};
 
 
+// (seed, m, k, n): A is m x k, B is k x n, both drawn from `testdata`.
+const CASES: [(u64, u32, u32, u32); 3] = [(1, 2, 2, 2), (7, 37, 23, 41), (42, 128, 96, 64)];
+// Results larger than this aren't printed in full.
+const PRINT_LIMIT: usize = 16;
+
 struct Component;
 
 // Implement the `Guest` trait for our world (generated by wit-bindgen).
@@ -21,97 +27,105 @@ struct Component;
 impl crate::Client for Component {
     fn run_matrix_example() -> Result<(), String> {
         println!("[Client Wasm] Starting matrix example...");
-
-        // Define matrices A and B (e.g., 2x2)
-        // A = [[1.0, 2.0], [3.0, 4.0]]
-        // B = [[5.0, 6.0], [7.0, 8.0]]
-        // C = A x B = [[19.0, 22.0], [43.0, 50.0]]
-        let a_data: Vec<f32> = vec![1.0, 2.0, 3.0, 4.0];
-        let b_data: Vec<f32> = vec![5.0, 6.0, 7.0, 8.0];
-        let dims_a = MatrixDimensions { rows: 2, cols: 2 };
-        let dims_b = MatrixDimensions { rows: 2, cols: 2 };
-
-        let a_bytes = f32_vec_to_bytes(&a_data);
-        let b_bytes = f32_vec_to_bytes(&b_data);
-
-        // 1. Allocate host buffers
-        let handle_a = host_allocator::allocate_buffer(a_bytes.len() as u64)
-            .map_err(|e| format!("Failed to allocate for A: {:?}", e))?;
-        println!("[Client Wasm] Allocated A, handle: {}", handle_a);
-
-        let handle_b = host_allocator::allocate_buffer(b_bytes.len() as u64)
-            .map_err(|e| format!("Failed to allocate for B: {:?}", e))?;
-        println!("[Client Wasm] Allocated B, handle: {}", handle_b);
-
-        // 2. Write data to host buffers
-        host_allocator::write_to_host(&a_bytes, handle_a, 0)
-            .map_err(|e| format!("Failed to write A: {:?}", e))?;
-        println!("[Client Wasm] Wrote A data to host");
-        host_allocator::register_matrix_dimensions(handle_a, dims_a)
-             .map_err(|e| format!("Failed to register dims A: {:?}", e))?;
-        println!("[Client Wasm] Registered A dimensions");
-
-
-        host_allocator::write_to_host(&b_bytes, handle_b, 0)
-            .map_err(|e| format!("Failed to write B: {:?}", e))?;
-        println!("[Client Wasm] Wrote B data to host");
-        host_allocator::register_matrix_dimensions(handle_b, dims_b)
-             .map_err(|e| format!("Failed to register dims B: {:?}", e))?;
-        println!("[Client Wasm] Registered B dimensions");
-
-        // 3. Perform matrix multiplication
-        let handle_c = host_allocator::matrix_multiply_f32(handle_a, handle_b)
-            .map_err(|e| format!("Matrix multiplication failed: {:?}", e))?;
-        println!("[Client Wasm] Matrix multiplication done. Result C handle: {}", handle_c);
-
-        // 4. Get dimensions of C and read C back
-        let dims_c = host_allocator::get_matrix_dimensions(handle_c)
-            .map_err(|e| format!("Failed to get C dimensions: {:?}", e))?;
-        println!("[Client Wasm] Got C dimensions: {}x{}", dims_c.rows, dims_c.cols);
-
-        let c_byte_len = (dims_c.rows * dims_c.cols * std::mem::size_of::<f32>() as u32) as u64;
-        let c_bytes = host_allocator::read_from_host(handle_c, 0, c_byte_len)
-            .map_err(|e| format!("Failed to read C: {:?}", e))?;
-        println!("[Client Wasm] Read C data from host ({} bytes)", c_bytes.len());
-
-        let c_data = bytes_to_f32_vec(&c_bytes).ok_or("Failed to parse C data".to_string())?;
-        println!("[Client Wasm] Result C: {:?}", c_data);
-
-        // Expected: [19.0, 22.0, 43.0, 50.0]
-        let expected_c: Vec<f32> = vec![19.0, 22.0, 43.0, 50.0];
-        if c_data.iter().zip(expected_c.iter()).all(|(a,b)| (a-b).abs() < f32::EPSILON) {
-            println!("[Client Wasm] Matrix multiplication SUCCESSFUL!");
-        } else {
-            return Err(format!("[Client Wasm] Matrix multiplication FAILED. Expected {:?}, Got {:?}", expected_c, c_data));
+        for &(seed, m, k, n) in &CASES {
+            run_case(seed, m, k, n)?;
         }
-
-        // 5. Free host buffers
-        host_allocator::free_buffer(handle_a).map_err(|e| format!("Failed to free A: {:?}", e))?;
-        host_allocator::free_buffer(handle_b).map_err(|e| format!("Failed to free B: {:?}", e))?;
-        host_allocator::free_buffer(handle_c).map_err(|e| format!("Failed to free C: {:?}", e))?;
-        println!("[Client Wasm] Freed all handles.");
-
         Ok(())
     }
 }
 
-fn f32_vec_to_bytes(data: &[f32]) -> Vec<u8> {
-    let mut bytes = Vec::with_capacity(data.len() * std::mem::size_of::<f32>());
-    for val in data {
-        bytes.extend_from_slice(&val.to_ne_bytes());
+fn run_case(seed: u64, m: u32, k: u32, n: u32) -> Result<(), String> {
+    println!("[Client Wasm] Case {}x{} times {}x{} (seed {})", m, k, k, n, seed);
+
+    // Seeded matrices A and B, and C = A x B computed in f64 in the guest.
+    let a_data = testdata::matrix(seed, m, k);
+    let b_data = testdata::matrix(seed + 1, k, n);
+    let dims_a = MatrixShape { rows: m, cols: k, layout: MatrixLayout::RowMajor };
+    let dims_b = MatrixShape { rows: k, cols: n, layout: MatrixLayout::RowMajor };
+
+    let a_bytes = codec::f32_to_le_bytes(&a_data);
+    let b_bytes = codec::f32_to_le_bytes(&b_data);
+
+    // 1. Allocate host buffers
+    let handle_a = host_allocator::allocate_buffer(a_bytes.len() as u64)
+        .map_err(|e| format!("Failed to allocate for A: {:?}", e))?;
+    println!("[Client Wasm] Allocated A, handle: {}", handle_a);
+
+    let handle_b = host_allocator::allocate_buffer(b_bytes.len() as u64)
+        .map_err(|e| format!("Failed to allocate for B: {:?}", e))?;
+    println!("[Client Wasm] Allocated B, handle: {}", handle_b);
+
+    // 2. Write data to host buffers
+    host_allocator::write_to_host(&a_bytes, handle_a, 0)
+        .map_err(|e| format!("Failed to write A: {:?}", e))?;
+    println!("[Client Wasm] Wrote A data to host");
+    host_allocator::register_matrix_shape(handle_a, dims_a)
+         .map_err(|e| format!("Failed to register dims A: {:?}", e))?;
+    println!("[Client Wasm] Registered A dimensions");
+
+
+    host_allocator::write_to_host(&b_bytes, handle_b, 0)
+        .map_err(|e| format!("Failed to write B: {:?}", e))?;
+    println!("[Client Wasm] Wrote B data to host");
+    host_allocator::register_matrix_shape(handle_b, dims_b)
+         .map_err(|e| format!("Failed to register dims B: {:?}", e))?;
+    println!("[Client Wasm] Registered B dimensions");
+
+    // 3. Perform matrix multiplication
+    let handle_c = host_allocator::matrix_multiply_f32(handle_a, handle_b)
+        .map_err(|e| format!("Matrix multiplication failed: {:?}", e))?;
+    println!("[Client Wasm] Matrix multiplication done. Result C handle: {}", handle_c);
+
+    // 4. Get dimensions of C and read C back
+    let dims_c = host_allocator::get_matrix_shape(handle_c)
+        .map_err(|e| format!("Failed to get C dimensions: {:?}", e))?;
+    println!("[Client Wasm] Got C dimensions: {}x{} ({:?})", dims_c.rows, dims_c.cols, dims_c.layout);
+    if dims_c.layout != MatrixLayout::RowMajor {
+        return Err(format!("Expected C in row-major layout, got {:?}", dims_c.layout));
     }
-    bytes
-}
 
-fn bytes_to_f32_vec(bytes: &[u8]) -> Option<Vec<f32>> {
-    if bytes.len() % std::mem::size_of::<f32>() != 0 {
-        return None;
+    let c_byte_len = (dims_c.rows * dims_c.cols) as u64 * codec::F32_SIZE as u64;
+    let c_bytes = host_allocator::read_from_host(handle_c, 0, c_byte_len)
+        .map_err(|e| format!("Failed to read C: {:?}", e))?;
+    println!("[Client Wasm] Read C data from host ({} bytes)", c_bytes.len());
+
+    let c_data = codec::f32_from_le_bytes(&c_bytes).ok_or("Failed to parse C data".to_string())?;
+    if c_data.len() <= PRINT_LIMIT {
+        println!("[Client Wasm] Result C: {:?}", c_data);
     }
-    let mut result = Vec::with_capacity(bytes.len() / std::mem::size_of::<f32>());
-    for chunk in bytes.chunks_exact(std::mem::size_of::<f32>()) {
-        result.push(f32::from_ne_bytes(chunk.try_into().unwrap()));
+
+    // Compare on the host with a tolerance rather than exact float equality,
+    // so backends that reorder the reduction still verify.
+    let expected_c: Vec<f32> = testdata::reference_matmul(&a_data, &b_data, m, k, n)
+        .into_iter()
+        .map(|v| v as f32)
+        .collect();
+    let atol = testdata::matmul_f32_tolerance(&a_data, &b_data, m, k, n) as f32;
+    let expected_bytes = codec::f32_to_le_bytes(&expected_c);
+    let handle_expected = host_allocator::allocate_buffer(expected_bytes.len() as u64)
+        .map_err(|e| format!("Failed to allocate for expected C: {:?}", e))?;
+    host_allocator::write_to_host(&expected_bytes, handle_expected, 0)
+        .map_err(|e| format!("Failed to write expected C: {:?}", e))?;
+    let report = host_allocator::compare_buffers_f32(handle_c, handle_expected, 0.0, atol)
+        .map_err(|e| format!("Failed to compare C: {:?}", e))?;
+    if report.all_close {
+        println!("[Client Wasm] Matrix multiplication SUCCESSFUL! (max abs error {})", report.max_abs_error);
+    } else {
+        return Err(format!(
+            "[Client Wasm] Matrix multiplication FAILED for seed {}. First mismatch at {:?}, max abs error {} (allowed {}), max rel error {}",
+            seed, report.first_mismatch, report.max_abs_error, atol, report.max_rel_error
+        ));
     }
-    Some(result)
+
+    // 5. Free host buffers
+    host_allocator::free_buffer(handle_a).map_err(|e| format!("Failed to free A: {:?}", e))?;
+    host_allocator::free_buffer(handle_b).map_err(|e| format!("Failed to free B: {:?}", e))?;
+    host_allocator::free_buffer(handle_c).map_err(|e| format!("Failed to free C: {:?}", e))?;
+    host_allocator::free_buffer(handle_expected).map_err(|e| format!("Failed to free expected C: {:?}", e))?;
+    println!("[Client Wasm] Freed all handles.");
+
+    Ok(())
 }
+
 // Export the component by implementing the world's Guest trait.
 // The `cargo-component` build process handles the actual Wasm export definitions.
@@ -0,0 +1,63 @@
+// Wire format for element data moved through `write-to-host` / `read-from-host`.
+//
+// Every element is encoded little-endian, independent of the byte order of the
+// guest or of the machine the provider happens to run on. This is part of the
+// interface contract documented in `wit/host-offload.wit`.
+
+pub const F32_SIZE: usize = std::mem::size_of::<f32>();
+
+pub fn f32_to_le_bytes(floats: &[f32]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(floats.len() * F32_SIZE);
+    for val in floats {
+        bytes.extend_from_slice(&val.to_le_bytes());
+    }
+    bytes
+}
+
+// Returns `None` if `bytes` is not a whole number of f32 elements.
+pub fn f32_from_le_bytes(bytes: &[u8]) -> Option<Vec<f32>> {
+    if !bytes.len().is_multiple_of(F32_SIZE) {
+        return None;
+    }
+    let mut result = Vec::with_capacity(bytes.len() / F32_SIZE);
+    for chunk in bytes.chunks_exact(F32_SIZE) {
+        result.push(f32::from_le_bytes(chunk.try_into().unwrap()));
+    }
+    Some(result)
+}
+
+pub const F64_SIZE: usize = std::mem::size_of::<f64>();
+
+pub fn f64_to_le_bytes(values: &[f64]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(values.len() * F64_SIZE);
+    for val in values {
+        bytes.extend_from_slice(&val.to_le_bytes());
+    }
+    bytes
+}
+
+// Returns `None` if `bytes` is not a whole number of f64 elements.
+pub fn f64_from_le_bytes(bytes: &[u8]) -> Option<Vec<f64>> {
+    if !bytes.len().is_multiple_of(F64_SIZE) {
+        return None;
+    }
+    Some(bytes.chunks_exact(F64_SIZE).map(|chunk| f64::from_le_bytes(chunk.try_into().unwrap())).collect())
+}
+
+pub const I32_SIZE: usize = std::mem::size_of::<i32>();
+
+pub fn i32_to_le_bytes(values: &[i32]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(values.len() * I32_SIZE);
+    for val in values {
+        bytes.extend_from_slice(&val.to_le_bytes());
+    }
+    bytes
+}
+
+// Returns `None` if `bytes` is not a whole number of i32 elements.
+pub fn i32_from_le_bytes(bytes: &[u8]) -> Option<Vec<i32>> {
+    if !bytes.len().is_multiple_of(I32_SIZE) {
+        return None;
+    }
+    Some(bytes.chunks_exact(I32_SIZE).map(|chunk| i32::from_le_bytes(chunk.try_into().unwrap())).collect())
+}
@@ -0,0 +1,3 @@
+// Helpers shared between the provider and client components.
+pub mod codec;
+pub mod testdata;
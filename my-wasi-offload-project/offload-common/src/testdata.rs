@@ -0,0 +1,67 @@
+// Reproducible test data, so guest and host tests can use the same matrices
+// without hard-coding them: the same seed gives the same values on every
+// platform and in every build.
+
+// SplitMix64. Small, fast, and good enough for test inputs; not for anything
+// that needs real randomness.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    // Uniform in [-1, 1), on a grid of 2^-23 so every value is an exact f32.
+    pub fn next_f32(&mut self) -> f32 {
+        let bits = (self.next_u64() >> 40) as i32; // 24 bits
+        (bits - (1 << 23)) as f32 / (1 << 23) as f32
+    }
+}
+
+// A `rows x cols` row-major matrix of values in [-1, 1).
+pub fn matrix(seed: u64, rows: u32, cols: u32) -> Vec<f32> {
+    let mut rng = Rng::new(seed);
+    (0..rows as usize * cols as usize).map(|_| rng.next_f32()).collect()
+}
+
+// `a (m x k) * b (k x n)`, row-major, accumulated in f64: the reference an
+// f32 kernel is checked against.
+pub fn reference_matmul(a: &[f32], b: &[f32], m: u32, k: u32, n: u32) -> Vec<f64> {
+    let (m, k, n) = (m as usize, k as usize, n as usize);
+    assert_eq!(a.len(), m * k, "a is not {}x{}", m, k);
+    assert_eq!(b.len(), k * n, "b is not {}x{}", k, n);
+    let mut c = vec![0.0; m * n];
+    for r in 0..m {
+        for i in 0..k {
+            let a_ri = a[r * k + i] as f64;
+            for col in 0..n {
+                c[r * n + col] += a_ri * b[i * n + col] as f64;
+            }
+        }
+    }
+    c
+}
+
+// Absolute error an f32 product of the same operands may show against
+// `reference_matmul`, whatever its summation order: k roundings of partial
+// sums bounded by the largest `sum |a| |b|` over the outputs, with 4x headroom
+// for FMA and blocked kernels.
+pub fn matmul_f32_tolerance(a: &[f32], b: &[f32], m: u32, k: u32, n: u32) -> f64 {
+    let (m, k, n) = (m as usize, k as usize, n as usize);
+    let mut worst: f64 = 0.0;
+    for r in 0..m {
+        for col in 0..n {
+            let bound: f64 = (0..k).map(|i| (a[r * k + i] as f64 * b[i * n + col] as f64).abs()).sum();
+            worst = worst.max(bound);
+        }
+    }
+    4.0 * k as f64 * f32::EPSILON as f64 / 2.0 * worst
+}
@@ -0,0 +1,48 @@
+// The wire format is little-endian whatever machine runs the test, so the
+// fixtures below are spelled out byte by byte rather than produced natively.
+
+use offload_common::codec;
+
+// 1.0, -2.5 and the smallest positive normal f32, little-endian.
+const F32_LE: [u8; 12] = [0x00, 0x00, 0x80, 0x3f, 0x00, 0x00, 0x20, 0xc0, 0x00, 0x00, 0x80, 0x00];
+const F32_VALUES: [f32; 3] = [1.0, -2.5, f32::MIN_POSITIVE];
+
+#[test]
+fn f32_round_trips_through_the_fixture() {
+    assert_eq!(codec::f32_to_le_bytes(&F32_VALUES), F32_LE);
+    assert_eq!(codec::f32_from_le_bytes(&F32_LE).unwrap(), F32_VALUES);
+    let awkward = [0.0, -0.0, f32::MAX, f32::EPSILON, f32::INFINITY];
+    let decoded = codec::f32_from_le_bytes(&codec::f32_to_le_bytes(&awkward)).unwrap();
+    assert_eq!(decoded.iter().map(|v| v.to_bits()).collect::<Vec<_>>(), awkward.map(f32::to_bits));
+}
+
+#[test]
+fn big_endian_bytes_are_not_read_as_native() {
+    // What a big-endian guest writing its floats as-is would send.
+    let swapped: Vec<u8> = F32_LE.chunks(4).flat_map(|chunk| chunk.iter().rev().copied()).collect();
+    let decoded = codec::f32_from_le_bytes(&swapped).unwrap();
+    let expected: Vec<u32> = F32_VALUES.iter().map(|v| v.to_bits().swap_bytes()).collect();
+    assert_eq!(decoded.iter().map(|v| v.to_bits()).collect::<Vec<_>>(), expected);
+    assert_ne!(decoded, F32_VALUES);
+}
+
+#[test]
+fn f64_and_i32_use_the_same_byte_order() {
+    let f64_le: [u8; 8] = [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xf0, 0x3f];
+    assert_eq!(codec::f64_to_le_bytes(&[1.0]), f64_le);
+    assert_eq!(codec::f64_from_le_bytes(&f64_le).unwrap(), [1.0]);
+
+    let i32_le: [u8; 8] = [0x01, 0x00, 0x00, 0x00, 0xfe, 0xff, 0xff, 0xff];
+    assert_eq!(codec::i32_to_le_bytes(&[1, -2]), i32_le);
+    assert_eq!(codec::i32_from_le_bytes(&i32_le).unwrap(), [1, -2]);
+    let swapped = [0x00, 0x00, 0x00, 0x01];
+    assert_eq!(codec::i32_from_le_bytes(&swapped).unwrap(), [0x0100_0000]);
+}
+
+#[test]
+fn partial_elements_are_rejected() {
+    assert!(codec::f32_from_le_bytes(&F32_LE[..5]).is_none());
+    assert!(codec::f64_from_le_bytes(&[0; 12]).is_none());
+    assert!(codec::i32_from_le_bytes(&[0; 3]).is_none());
+    assert!(codec::f32_from_le_bytes(&[]).unwrap().is_empty());
+}
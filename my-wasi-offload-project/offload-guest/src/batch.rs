@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+
+use crate::host_allocator::{self, BatchInput, OpDescriptor, OpResult};
+use crate::{Handle, HostError, MatrixShape};
+
+// Ops queued before the builder flushes on its own.
+pub const DEFAULT_MAX_OPS: usize = 256;
+
+// Stands in for `host_allocator`, queuing the small calls and sending them
+// as one `execute-batch` when something is read, when `flush` is called, or
+// every `max_ops` calls:
+//
+//   let mut batch = BatchBuilder::new();
+//   let a = batch.allocate_buffer(16)?;
+//   batch.write_f32(a, 0, &[1.0, 2.0, 3.0, 4.0])?;
+//   batch.register_matrix_shape(a, dims)?;
+//   let c = batch.matrix_multiply_f32(a, a)?;
+//   let values = batch.read_f32(c, 0, 4)?; // one boundary crossing so far
+//
+// Queued calls return right away, so their errors surface from whichever
+// call flushes them; everything queued after a failed op is dropped.
+// Handles from queued calls are `BatchHandle`s, usable in later calls at
+// once; `handle` flushes if needed and gives the host handle.
+pub struct BatchBuilder {
+    queue: Vec<OpDescriptor>,
+    max_ops: usize,
+    // Id of `queue[0]`; ids count every op the builder has queued.
+    first: u32,
+    // Handles produced by ops of batches already run, by id.
+    resolved: HashMap<u32, Handle>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BatchHandle {
+    Host(Handle),
+    Queued(u32),
+}
+
+impl From<Handle> for BatchHandle {
+    fn from(h: Handle) -> Self {
+        BatchHandle::Host(h)
+    }
+}
+
+impl Default for BatchBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BatchBuilder {
+    pub fn new() -> Self {
+        BatchBuilder { queue: Vec::new(), max_ops: DEFAULT_MAX_OPS, first: 0, resolved: HashMap::new() }
+    }
+
+    pub fn max_ops(mut self, max_ops: usize) -> Self {
+        self.max_ops = max_ops.max(1);
+        self
+    }
+
+    pub fn allocate_buffer(&mut self, size: u64) -> Result<BatchHandle, HostError> {
+        self.queue_op(|_| Ok(OpDescriptor::Allocate(size))).map(BatchHandle::Queued)
+    }
+
+    pub fn free_buffer(&mut self, h: impl Into<BatchHandle>) -> Result<(), HostError> {
+        let h = h.into();
+        self.queue_op(|batch| Ok(OpDescriptor::Free(batch.input(h)?))).map(drop)
+    }
+
+    pub fn write_to_host(&mut self, bytes: &[u8], h: impl Into<BatchHandle>, offset: u64) -> Result<(), HostError> {
+        let h = h.into();
+        self.queue_op(|batch| Ok(OpDescriptor::Write((batch.input(h)?, offset, bytes.to_vec())))).map(drop)
+    }
+
+    pub fn write_f32(&mut self, h: impl Into<BatchHandle>, offset: u64, values: &[f32]) -> Result<(), HostError> {
+        let h = h.into();
+        self.queue_op(|batch| Ok(OpDescriptor::WriteF32((batch.input(h)?, offset, values.to_vec())))).map(drop)
+    }
+
+    pub fn register_matrix_shape(
+        &mut self,
+        h: impl Into<BatchHandle>,
+        dims: MatrixShape,
+    ) -> Result<(), HostError> {
+        let h = h.into();
+        self.queue_op(|batch| Ok(OpDescriptor::RegisterMatrixShape((batch.input(h)?, dims)))).map(drop)
+    }
+
+    pub fn matrix_multiply_f32(
+        &mut self,
+        a: impl Into<BatchHandle>,
+        b: impl Into<BatchHandle>,
+    ) -> Result<BatchHandle, HostError> {
+        let (a, b) = (a.into(), b.into());
+        self.queue_op(|batch| Ok(OpDescriptor::MatrixMultiplyF32((batch.input(a)?, batch.input(b)?))))
+            .map(BatchHandle::Queued)
+    }
+
+    pub fn matmul_accumulate(
+        &mut self,
+        a: impl Into<BatchHandle>,
+        b: impl Into<BatchHandle>,
+        c: impl Into<BatchHandle>,
+    ) -> Result<(), HostError> {
+        let (a, b, c) = (a.into(), b.into(), c.into());
+        self.queue_op(|batch| Ok(OpDescriptor::MatmulAccumulate((batch.input(a)?, batch.input(b)?, batch.input(c)?))))
+            .map(drop)
+    }
+
+    pub fn axpy_f32(
+        &mut self,
+        alpha: f32,
+        x: impl Into<BatchHandle>,
+        y: impl Into<BatchHandle>,
+    ) -> Result<(), HostError> {
+        let (x, y) = (x.into(), y.into());
+        self.queue_op(|batch| Ok(OpDescriptor::AxpyF32((alpha, batch.input(x)?, batch.input(y)?)))).map(drop)
+    }
+
+    pub fn scal_f32(&mut self, alpha: f32, x: impl Into<BatchHandle>) -> Result<(), HostError> {
+        let x = x.into();
+        self.queue_op(|batch| Ok(OpDescriptor::ScalF32((alpha, batch.input(x)?)))).map(drop)
+    }
+
+    // Reads flush everything queued, the read included.
+    pub fn read_from_host(&mut self, h: impl Into<BatchHandle>, offset: u64, len: u64) -> Result<Vec<u8>, HostError> {
+        let h = h.into();
+        self.queue_op(|batch| Ok(OpDescriptor::Read((batch.input(h)?, offset, len))))?;
+        match self.run()?.pop() {
+            Some(OpResult::Bytes(bytes)) => Ok(bytes),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    pub fn read_f32(&mut self, h: impl Into<BatchHandle>, offset: u64, count: u64) -> Result<Vec<f32>, HostError> {
+        let h = h.into();
+        self.queue_op(|batch| Ok(OpDescriptor::ReadF32((batch.input(h)?, offset, count))))?;
+        match self.run()?.pop() {
+            Some(OpResult::F32s(values)) => Ok(values),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    // The host handle behind `h`, flushing first if it is still queued.
+    pub fn handle(&mut self, h: BatchHandle) -> Result<Handle, HostError> {
+        if matches!(h, BatchHandle::Queued(id) if id >= self.first) {
+            self.run()?;
+        }
+        match self.input(h)? {
+            BatchInput::Handle(h) => Ok(h),
+            BatchInput::Result(_) => unreachable!("flushed above"),
+        }
+    }
+
+    pub fn flush(&mut self) -> Result<(), HostError> {
+        self.run().map(drop)
+    }
+
+    // Flushes first if the queue is full, so `build` resolves handles
+    // against the batch the op actually lands in. Returns the op's id.
+    fn queue_op(&mut self, build: impl FnOnce(&Self) -> Result<OpDescriptor, HostError>) -> Result<u32, HostError> {
+        if self.queue.len() >= self.max_ops {
+            self.run()?;
+        }
+        let op = build(self)?;
+        self.queue.push(op);
+        Ok(self.first + self.queue.len() as u32 - 1)
+    }
+
+    fn input(&self, h: BatchHandle) -> Result<BatchInput, HostError> {
+        match h {
+            BatchHandle::Host(h) => Ok(BatchInput::Handle(h)),
+            BatchHandle::Queued(id) if id >= self.first => Ok(BatchInput::Result(id - self.first)),
+            BatchHandle::Queued(id) => match self.resolved.get(&id) {
+                Some(&h) => Ok(BatchInput::Handle(h)),
+                None => Err(HostError::InvalidArguments(format!("Queued op {} produced no handle", id))),
+            },
+        }
+    }
+
+    fn run(&mut self) -> Result<Vec<OpResult>, HostError> {
+        if self.queue.is_empty() {
+            return Ok(Vec::new());
+        }
+        let ops = std::mem::take(&mut self.queue);
+        let first = self.first;
+        self.first += ops.len() as u32;
+        let results = host_allocator::execute_batch(&ops);
+        for (id, result) in (first..).zip(&results) {
+            match result {
+                OpResult::Handle(h) => {
+                    self.resolved.insert(id, *h);
+                }
+                OpResult::Failed(e) => return Err(e.clone()),
+                _ => {}
+            }
+        }
+        Ok(results)
+    }
+}
+
+// Ops still queued are sent; call `flush` first to see their errors.
+impl Drop for BatchBuilder {
+    fn drop(&mut self) {
+        let _ = self.run();
+    }
+}
+
+fn unexpected(result: Option<OpResult>) -> HostError {
+    HostError::Other(format!("Unexpected batch result {:?}", result))
+}
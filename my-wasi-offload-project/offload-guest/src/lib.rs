@@ -0,0 +1,18 @@
+// Generate import bindings for the `guest` world. Clients built on this crate
+// should call the host through the re-exported `host_allocator` module so
+// handles and errors have a single Rust type across the client and the library.
+wit_bindgen::generate!({
+    world: "guest",
+    path: "wit/world.wit",
+});
+
+pub use crate::host_allocator;
+pub use crate::wasi_custom::host_offload::host_allocator::{Handle, HostError, MatrixLayout, MatrixShape};
+
+mod batch;
+mod pipeline;
+mod smart;
+
+pub use batch::{BatchBuilder, BatchHandle, DEFAULT_MAX_OPS};
+pub use pipeline::PipelinedUploader;
+pub use smart::{local_multiply, SmartMatmul, DEFAULT_THRESHOLD_FLOPS};
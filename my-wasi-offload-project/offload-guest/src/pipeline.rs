@@ -0,0 +1,104 @@
+use offload_common::codec;
+
+use crate::host_allocator;
+use crate::{Handle, HostError, MatrixLayout, MatrixShape};
+
+// Streams row blocks of a left-hand matrix through `block x rhs` on the host,
+// double-buffering the uploads: block N+1 is written into one staging buffer
+// while the job for block N, submitted from the other, is still running.
+//
+//   let mut uploader = PipelinedUploader::new(rhs, rhs_dims)?;
+//   for block in blocks {
+//       if let Some(rows) = uploader.push(&block, block_rows)? { /* consume */ }
+//   }
+//   let rest = uploader.finish()?;
+//
+// Every result comes back as row-major f32 data, in the order blocks were pushed.
+pub struct PipelinedUploader {
+    rhs: Handle,
+    rhs_dims: MatrixShape,
+    // Two staging buffers, each with its capacity in bytes, used alternately.
+    slots: [Option<(Handle, u64)>; 2],
+    next_slot: usize,
+    in_flight: Option<u32>,
+}
+
+impl PipelinedUploader {
+    pub fn new(rhs: Handle, rhs_dims: MatrixShape) -> Result<Self, HostError> {
+        Ok(PipelinedUploader {
+            rhs,
+            rhs_dims,
+            slots: [None, None],
+            next_slot: 0,
+            in_flight: None,
+        })
+    }
+
+    // Uploads `block` (`rows` x rhs.rows, row-major) and submits its multiply.
+    // Returns the result of the previous block, if there was one in flight.
+    pub fn push(&mut self, block: &[f32], rows: u32) -> Result<Option<Vec<f32>>, HostError> {
+        let len = (rows as usize).checked_mul(self.rhs_dims.rows as usize).ok_or_else(|| {
+            HostError::InvalidArguments(format!("A block of {} rows of {} overflows usize", rows, self.rhs_dims.rows))
+        })?;
+        if block.len() != len {
+            return Err(HostError::DimensionMismatch);
+        }
+        let bytes = codec::f32_to_le_bytes(block);
+        let staging = self.staging_buffer(bytes.len() as u64)?;
+        // Overlaps with the host working on the previous block's job.
+        host_allocator::write_to_host(&bytes, staging, 0)?;
+        host_allocator::register_matrix_shape(
+            staging,
+            MatrixShape { rows, cols: self.rhs_dims.rows, layout: MatrixLayout::RowMajor },
+        )?;
+        let job = host_allocator::submit_matmul_f32(staging, self.rhs)?;
+
+        let previous = self.collect()?;
+        self.in_flight = Some(job);
+        Ok(previous)
+    }
+
+    // Waits for the last block and frees the staging buffers.
+    pub fn finish(mut self) -> Result<Option<Vec<f32>>, HostError> {
+        let last = self.collect()?;
+        for (handle, _) in self.slots.iter_mut().filter_map(Option::take) {
+            host_allocator::free_buffer(handle)?;
+        }
+        Ok(last)
+    }
+
+    fn collect(&mut self) -> Result<Option<Vec<f32>>, HostError> {
+        let Some(job) = self.in_flight.take() else {
+            return Ok(None);
+        };
+        let result = host_allocator::wait_job(job)?;
+        let dims = host_allocator::get_matrix_shape(result)?;
+        let len = dims.rows as u64 * dims.cols as u64 * codec::F32_SIZE as u64;
+        let bytes = host_allocator::read_from_host(result, 0, len)?;
+        host_allocator::free_buffer(result)?;
+        codec::f32_from_le_bytes(&bytes)
+            .map(Some)
+            .ok_or_else(|| HostError::Other("Result is not a whole number of f32 elements".to_string()))
+    }
+
+    // The slot alternates every push, so it is never the one feeding the job
+    // still in flight. Reallocated only when a block outgrows it.
+    fn staging_buffer(&mut self, size: u64) -> Result<Handle, HostError> {
+        let slot = &mut self.slots[self.next_slot];
+        self.next_slot = 1 - self.next_slot;
+        match *slot {
+            Some((handle, capacity)) if capacity == size => Ok(handle),
+            Some((handle, _)) => {
+                host_allocator::free_buffer(handle)?;
+                let handle = host_allocator::allocate_buffer(size)?;
+                *slot = Some((handle, size));
+                Ok(handle)
+            }
+            None => {
+                let handle = host_allocator::allocate_buffer(size)?;
+                *slot = Some((handle, size));
+                Ok(handle)
+            }
+        }
+    }
+}
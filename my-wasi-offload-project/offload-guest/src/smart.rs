@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+
+use offload_common::codec;
+
+use crate::host_allocator;
+use crate::{Handle, HostError, MatrixLayout, MatrixShape};
+
+// Products below this many flops (2 * m * k * n) stay in the guest without
+// asking the host; 2 * 64^3, around where uploads stop dominating.
+pub const DEFAULT_THRESHOLD_FLOPS: u64 = 2 * 64 * 64 * 64;
+
+// Row-major f32 products computed in the guest when small and on the host
+// when the host is worth it:
+//
+//   let mut smart = SmartMatmul::new().threshold_flops(1 << 20);
+//   let c = smart.multiply(&a, &b, m, k, n)?;
+//
+// At or above the threshold it asks `estimate-cost` once per size class and
+// offloads when the host's estimate beats `local_gflops`. Hosts without
+// estimates (the call fails) get everything above the threshold.
+pub struct SmartMatmul {
+    threshold_flops: u64,
+    local_gflops: f64,
+    // Decision per size class, floor(log2(flops)), once estimated.
+    decisions: HashMap<u32, bool>,
+}
+
+impl Default for SmartMatmul {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SmartMatmul {
+    pub fn new() -> Self {
+        SmartMatmul { threshold_flops: DEFAULT_THRESHOLD_FLOPS, local_gflops: 1.0, decisions: HashMap::new() }
+    }
+
+    pub fn threshold_flops(mut self, flops: u64) -> Self {
+        self.threshold_flops = flops;
+        self.decisions.clear();
+        self
+    }
+
+    // Rate of the guest kernel, to weigh against the host's estimates. One
+    // GFLOP/s is about what the plain loop below manages in wasm.
+    pub fn local_gflops(mut self, gflops: f64) -> Self {
+        self.local_gflops = gflops;
+        self.decisions.clear();
+        self
+    }
+
+    // Whether an `m` x `k` by `k` x `n` product would go to the host.
+    pub fn offloads(&mut self, m: u32, k: u32, n: u32) -> bool {
+        let flops = 2 * m as u64 * k as u64 * n as u64;
+        if flops < self.threshold_flops {
+            return false;
+        }
+        let local_millis = flops as f64 / (self.local_gflops * 1e6);
+        *self.decisions.entry(flops.max(1).ilog2()).or_insert_with(|| {
+            match host_allocator::estimate_cost("matrix-multiply-f32", &[(m, k), (k, n)]) {
+                Ok(estimate) => estimate.est_millis < local_millis,
+                Err(_) => true,
+            }
+        })
+    }
+
+    // `a` (m x k) times `b` (k x n), both row-major; the result is m x n, row-major.
+    pub fn multiply(&mut self, a: &[f32], b: &[f32], m: u32, k: u32, n: u32) -> Result<Vec<f32>, HostError> {
+        if a.len() != m as usize * k as usize || b.len() != k as usize * n as usize {
+            return Err(HostError::DimensionMismatch);
+        }
+        match self.offloads(m, k, n) {
+            true => host_multiply(a, b, m, k, n),
+            false => Ok(local_multiply(a, b, m as usize, k as usize, n as usize)),
+        }
+    }
+}
+
+// i-k-j order, so the inner loop walks rows of `b` and `c` contiguously.
+pub fn local_multiply(a: &[f32], b: &[f32], m: usize, k: usize, n: usize) -> Vec<f32> {
+    let mut c = vec![0.0f32; m * n];
+    for i in 0..m {
+        let row = &mut c[i * n..(i + 1) * n];
+        for p in 0..k {
+            let scale = a[i * k + p];
+            for (out, &value) in row.iter_mut().zip(&b[p * n..(p + 1) * n]) {
+                *out += scale * value;
+            }
+        }
+    }
+    c
+}
+
+fn host_multiply(a: &[f32], b: &[f32], m: u32, k: u32, n: u32) -> Result<Vec<f32>, HostError> {
+    let handle_a = upload(a, m, k)?;
+    let handle_b = match upload(b, k, n) {
+        Ok(handle) => handle,
+        Err(e) => {
+            host_allocator::free_buffer(handle_a)?;
+            return Err(e);
+        }
+    };
+    let product = host_allocator::matrix_multiply_f32(handle_a, handle_b);
+    host_allocator::free_buffer(handle_a)?;
+    host_allocator::free_buffer(handle_b)?;
+    let handle_c = product?;
+    let read = host_allocator::read_from_host(handle_c, 0, m as u64 * n as u64 * codec::F32_SIZE as u64);
+    host_allocator::free_buffer(handle_c)?;
+    codec::f32_from_le_bytes(&read?).ok_or_else(|| HostError::Other("Result is not a whole number of f32 elements".to_string()))
+}
+
+fn upload(values: &[f32], rows: u32, cols: u32) -> Result<Handle, HostError> {
+    let bytes = codec::f32_to_le_bytes(values);
+    let handle = host_allocator::allocate_buffer(bytes.len() as u64)?;
+    let written = host_allocator::write_to_host(&bytes, handle, 0).and_then(|()| {
+        host_allocator::register_matrix_shape(handle, MatrixShape { rows, cols, layout: MatrixLayout::RowMajor })
+    });
+    if let Err(e) = written {
+        host_allocator::free_buffer(handle)?;
+        return Err(e);
+    }
+    Ok(handle)
+}
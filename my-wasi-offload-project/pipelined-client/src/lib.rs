@@ -0,0 +1,99 @@
+// Generate bindings for the `pipelined-client` world. Only the export is used
+// from here; host calls go through `offload_guest::host_allocator` so they
+// share types with `PipelinedUploader`.
+wit_bindgen::generate!({
+    world: "pipelined-client",
+    path: "wit/world.wit",
+});
+
+use std::time::Instant;
+
+use offload_common::codec;
+use offload_guest::{host_allocator, MatrixLayout, MatrixShape, PipelinedUploader};
+
+// Streams a tall matrix through `A x B` in row blocks, first one block at a
+// time (upload, multiply, read back, repeat), then with `PipelinedUploader`
+// overlapping each upload with the previous block's multiply.
+const INNER: u32 = 256;
+const COLS: u32 = 256;
+const BLOCK_ROWS: u32 = 64;
+const BLOCKS: u32 = 32;
+
+struct Component;
+
+impl crate::PipelinedClient for Component {
+    fn run_pipelined_example() -> Result<(), String> {
+        println!("[Pipelined Wasm] Streaming {} blocks of {}x{} through a {}x{} multiply", BLOCKS, BLOCK_ROWS, INNER, INNER, COLS);
+
+        let b_data: Vec<f32> = (0..INNER * COLS).map(|i| (i % 7) as f32 * 0.5).collect();
+        let b_bytes = codec::f32_to_le_bytes(&b_data);
+        let handle_b = host_allocator::allocate_buffer(b_bytes.len() as u64)
+            .map_err(|e| format!("Failed to allocate for B: {:?}", e))?;
+        host_allocator::write_to_host(&b_bytes, handle_b, 0)
+            .map_err(|e| format!("Failed to write B: {:?}", e))?;
+        let dims_b = MatrixShape { rows: INNER, cols: COLS, layout: MatrixLayout::RowMajor };
+        host_allocator::register_matrix_shape(handle_b, dims_b)
+            .map_err(|e| format!("Failed to register dims B: {:?}", e))?;
+
+        let blocks: Vec<Vec<f32>> = (0..BLOCKS)
+            .map(|n| (0..BLOCK_ROWS * INNER).map(|i| ((i + n) % 5) as f32).collect())
+            .collect();
+
+        let start = Instant::now();
+        let sequential = run_sequential(&blocks, handle_b)?;
+        let sequential_time = start.elapsed();
+
+        let start = Instant::now();
+        let pipelined = run_pipelined(&blocks, handle_b, dims_b)?;
+        let pipelined_time = start.elapsed();
+
+        host_allocator::free_buffer(handle_b).map_err(|e| format!("Failed to free B: {:?}", e))?;
+
+        if sequential != pipelined {
+            return Err("[Pipelined Wasm] Pipelined results differ from sequential results".to_string());
+        }
+        println!("[Pipelined Wasm] Sequential: {:?}, pipelined: {:?}", sequential_time, pipelined_time);
+        println!(
+            "[Pipelined Wasm] Throughput: {:.1} vs {:.1} blocks/s",
+            BLOCKS as f64 / sequential_time.as_secs_f64(),
+            BLOCKS as f64 / pipelined_time.as_secs_f64()
+        );
+        Ok(())
+    }
+}
+
+fn run_sequential(blocks: &[Vec<f32>], handle_b: u32) -> Result<Vec<f32>, String> {
+    let mut out = Vec::new();
+    for block in blocks {
+        let bytes = codec::f32_to_le_bytes(block);
+        let handle_a = host_allocator::allocate_buffer(bytes.len() as u64)
+            .map_err(|e| format!("Failed to allocate block: {:?}", e))?;
+        host_allocator::write_to_host(&bytes, handle_a, 0)
+            .map_err(|e| format!("Failed to write block: {:?}", e))?;
+        host_allocator::register_matrix_shape(handle_a, MatrixShape { rows: BLOCK_ROWS, cols: INNER, layout: MatrixLayout::RowMajor })
+            .map_err(|e| format!("Failed to register block dims: {:?}", e))?;
+        let handle_c = host_allocator::matrix_multiply_f32(handle_a, handle_b)
+            .map_err(|e| format!("Block multiply failed: {:?}", e))?;
+        let c_bytes = host_allocator::read_from_host(handle_c, 0, (BLOCK_ROWS * COLS) as u64 * codec::F32_SIZE as u64)
+            .map_err(|e| format!("Failed to read block result: {:?}", e))?;
+        out.extend(codec::f32_from_le_bytes(&c_bytes).ok_or("Failed to parse block result".to_string())?);
+        host_allocator::free_buffer(handle_a).map_err(|e| format!("Failed to free block: {:?}", e))?;
+        host_allocator::free_buffer(handle_c).map_err(|e| format!("Failed to free block result: {:?}", e))?;
+    }
+    Ok(out)
+}
+
+fn run_pipelined(blocks: &[Vec<f32>], handle_b: u32, dims_b: MatrixShape) -> Result<Vec<f32>, String> {
+    let mut out = Vec::new();
+    let mut uploader = PipelinedUploader::new(handle_b, dims_b)
+        .map_err(|e| format!("Failed to create uploader: {:?}", e))?;
+    for block in blocks {
+        if let Some(rows) = uploader.push(block, BLOCK_ROWS).map_err(|e| format!("Pipelined push failed: {:?}", e))? {
+            out.extend(rows);
+        }
+    }
+    if let Some(rows) = uploader.finish().map_err(|e| format!("Pipelined finish failed: {:?}", e))? {
+        out.extend(rows);
+    }
+    Ok(out)
+}
@@ -0,0 +1,15 @@
+#!/bin/sh
+# Regenerates the Python client from the current WIT and provider:
+#   provider_bindings/  wasmtime-py bindings of the provider component
+#   offload_api.py      `runner wit python`: one raising wrapper per WIT function
+# Needs cargo-component and `pip install -r requirements.txt`.
+set -e
+here=$(cd "$(dirname "$0")" && pwd)
+project="$here/.."
+
+(cd "$project/host-offload-provider" && cargo component build --release --target wasm32-unknown-unknown)
+python3 -m wasmtime.bindgen \
+    "$project/host-offload-provider/target/wasm32-unknown-unknown/release/host_offload_provider.wasm" \
+    --out-dir "$here/provider_bindings"
+(cd "$project/runner" && cargo run --quiet -- wit python) > "$here/offload_api.py"
+echo "Generated $here/provider_bindings and $here/offload_api.py"
@@ -0,0 +1,85 @@
+"""NumPy conveniences over the generated `offload_api`, for notebooks.
+
+    from offload import Offload
+    with Offload() as off:
+        report = off.cross_check_matmul(a, b)
+
+Runs the same provider component the runner loads, in-process through
+wasmtime-py, so results can be compared against NumPy directly.
+"""
+import numpy as np
+
+from offload_api import HostError, connect
+from provider_bindings.exports.host_allocator import ElementType, MatrixDimensions, MatrixLayout
+from provider_bindings.exports.session_admin import SessionLimits
+
+
+class Offload:
+    def __init__(self, max_ops_per_sec=None, max_bytes_per_sec=None, max_op_millis=None, cpu_weight=None):
+        self.host, self.admin = connect()
+        self._limits = (max_ops_per_sec, max_bytes_per_sec, max_op_millis, cpu_weight)
+        self.session = None
+
+    # A session, as the runner opens one per client: closing it frees every
+    # handle created meanwhile, however the notebook cell ended.
+    def __enter__(self):
+        self.session = self.admin.open_session(SessionLimits(*self._limits))
+        return self
+
+    def __exit__(self, *exc):
+        freed = self.admin.close_session(self.session)
+        self.session = None
+        if freed:
+            print(f"[offload] Session closed, freed {freed} handles")
+        return False
+
+    def upload(self, array):
+        """Copies a 1-D or 2-D array into a new f32 buffer. Returns its handle."""
+        array = np.ascontiguousarray(array, dtype="<f4")
+        h = self.host.allocate_typed_buffer(ElementType.F32, array.size)
+        self.host.write_f32(h, 0, array.ravel().tolist())
+        if array.ndim == 2:
+            rows, cols = array.shape
+            self.host.register_matrix_dimensions(h, MatrixDimensions(rows, cols))
+        elif array.ndim != 1:
+            raise ValueError(f"Only vectors and matrices can be uploaded, not {array.ndim}-D arrays")
+        return h
+
+    def download(self, h):
+        """Reads an f32 buffer back, shaped by its registered dimensions if any."""
+        info = self.host.describe_handle(h)
+        values = np.array(self.host.read_f32(h, 0, info.size // 4), dtype=np.float32)
+        if info.dims is None:
+            return values
+        order = "C" if info.dims.layout == MatrixLayout.ROW_MAJOR else "F"
+        return values.reshape((info.dims.rows, info.dims.cols), order=order)
+
+    def free(self, *handles):
+        for h in handles:
+            self.host.free_buffer(h)
+
+    def matmul(self, a, b):
+        """A @ B on the provider, for NumPy inputs."""
+        ha, hb = self.upload(a), self.upload(b)
+        try:
+            hc = self.host.matrix_multiply_f32(ha, hb)
+            try:
+                return self.download(hc)
+            finally:
+                self.free(hc)
+        finally:
+            self.free(ha, hb)
+
+    def cross_check_matmul(self, a, b, rtol=1e-4, atol=1e-5):
+        """Compares the provider's A @ B with NumPy's, computed in f64."""
+        got = self.matmul(a, b)
+        expected = np.asarray(a, dtype=np.float64) @ np.asarray(b, dtype=np.float64)
+        error = np.abs(got - expected)
+        return {
+            "ok": bool(np.allclose(got, expected, rtol=rtol, atol=atol)),
+            "max_abs_error": float(error.max(initial=0.0)),
+            "max_rel_error": float((error / np.maximum(np.abs(expected), np.finfo(np.float32).tiny)).max(initial=0.0)),
+        }
+
+
+__all__ = ["HostError", "Offload"]
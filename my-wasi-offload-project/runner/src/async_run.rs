@@ -0,0 +1,258 @@
+use std::time::Instant;
+
+use anyhow::{Context, Result};
+use host_offload_provider::keyvalue::KeyValueStore;
+use host_offload_provider::native::OffloadHost;
+use host_offload_provider::wasi::keyvalue::store;
+use host_offload_provider::wasi_custom::host_offload::{buffer_streams, handle_persistence, tokenizer};
+use wasmtime::component::{Component, InstancePre, Linker};
+use wasmtime::{Engine, Store};
+
+use crate::config::{ClientConfig, ProviderLogLevel};
+use crate::events::AsyncEventSink;
+use crate::link;
+use crate::plugins::Plugins;
+use crate::report::{self, ClientReport, Failure, FailureKind};
+use crate::shutdown;
+use crate::state::{self, ClientState};
+
+// The provider's world again, for calling a provider component's
+// `session-admin` from an async store.
+mod provider_bindings {
+    wasmtime::component::bindgen!({
+        world: "provider",
+        path: "../host-offload-provider/wit/world.wit",
+        additional_packages: [
+            { package = "wasi-custom:host-offload@0.2.0", path = "../wit" },
+        ],
+        async: true,
+        // The logging import stays synchronous, so `ClientState` implements
+        // it once for both kinds of store.
+        with: {
+            "wasi-custom:host-offload/host-offload-logging": crate::provider_bindings::wasi_custom::host_offload::host_offload_logging,
+        },
+    });
+}
+
+fn component_log_level(level: ProviderLogLevel) -> provider_bindings::exports::wasi_custom::host_offload::session_admin::LogLevel {
+    use provider_bindings::exports::wasi_custom::host_offload::session_admin::LogLevel;
+    match level {
+        ProviderLogLevel::Off => LogLevel::Off,
+        ProviderLogLevel::Error => LogLevel::Error,
+        ProviderLogLevel::Warn => LogLevel::Warn,
+        ProviderLogLevel::Info => LogLevel::Info,
+        ProviderLogLevel::Debug => LogLevel::Debug,
+        ProviderLogLevel::Trace => LogLevel::Trace,
+    }
+}
+
+// `runner --async`: every client runs as a task on a multi-threaded tokio
+// runtime, in a store with async support, instead of on a thread of its own.
+//
+// Guest code still runs on the runtime's worker threads. What changes is the
+// host side: the native provider runs its compute calls via
+// `block_in_place`, so a long multiply hands the other clients' tasks to
+// another worker instead of stalling them, and the runner can multiplex far
+// more clients than it could afford threads.
+pub fn run_clients(
+    engine: &Engine,
+    provider_component: Option<&Component>,
+    fallback_component: Option<&Component>,
+    plugins: &Plugins,
+    clients: &[(ClientConfig, Component)],
+) -> Result<Vec<ClientReport>> {
+    let runtime = tokio::runtime::Builder::new_multi_thread().enable_all().build()?;
+    Ok(runtime.block_on(async {
+        let tasks: Vec<_> = clients
+            .iter()
+            .map(|(client, component)| {
+                let (engine, plugins) = (engine.clone(), plugins.clone());
+                let (provider_component, fallback_component) = (provider_component.cloned(), fallback_component.cloned());
+                let (client, component) = (client.clone(), component.clone());
+                let name = client.name.clone();
+                let task = tokio::spawn(async move {
+                    let mut report = ClientReport::new(&client.name);
+                    let result = run_client(&engine, provider_component.as_ref(), fallback_component.as_ref(), &plugins, &client, &component, &mut report)
+                        .await;
+                    (report, result)
+                });
+                (name, task)
+            })
+            .collect();
+
+        let mut reports = Vec::with_capacity(tasks.len());
+        for (name, task) in tasks {
+            reports.push(crate::client_report(&name, task.await.ok()));
+        }
+        reports
+    }))
+}
+
+enum Provider {
+    Component(provider_bindings::Provider),
+    Native(OffloadHost),
+}
+
+impl Provider {
+    async fn open_session(&self, store: &mut Store<ClientState>, client: &ClientConfig) -> Result<u32> {
+        let opened = match self {
+            Provider::Component(provider) => {
+                let limits = provider_bindings::exports::wasi_custom::host_offload::session_admin::SessionLimits {
+                    max_ops_per_sec: client.max_ops_per_sec,
+                    max_bytes_per_sec: client.max_bytes_per_sec,
+                    max_op_millis: client.max_op_millis,
+                    cpu_weight: client.cpu_weight,
+                };
+                let admin = provider.wasi_custom_host_offload_session_admin();
+                let opened = admin.call_open_session(&mut *store, limits).await?;
+                if let (Ok(_), Some(level)) = (&opened, client.provider_log_level) {
+                    admin.call_set_log_level(&mut *store, component_log_level(level)).await?;
+                }
+                opened
+            }
+            Provider::Native(host) => host.open_guarded_session(crate::native_limits(client)).map(|guard| {
+                if let Some(level) = client.provider_log_level {
+                    host.lock().set_log_level(crate::native_log_level(level));
+                }
+                let id = guard.id();
+                store.data_mut().session = Some(guard);
+                id
+            }),
+        };
+        opened.map_err(|e| anyhow::anyhow!("Failed to open provider session: {}", e))
+    }
+
+    async fn close_session(&self, store: &mut Store<ClientState>, session: u32) -> Result<Result<u32, String>> {
+        match self {
+            Provider::Component(provider) => provider.wasi_custom_host_offload_session_admin().call_close_session(store, session).await,
+            Provider::Native(host) => Ok(match store.data_mut().session.take() {
+                Some(guard) => guard.close(),
+                None => host.lock().close_session(session),
+            }),
+        }
+    }
+}
+
+// `crate::run_client` for an async store.
+async fn run_client(
+    engine: &Engine,
+    provider_component: Option<&Component>,
+    fallback_component: Option<&Component>,
+    plugins: &Plugins,
+    client: &ClientConfig,
+    client_component: &Component,
+    report: &mut ClientReport,
+) -> Result<(), Failure> {
+    let name = client.name.as_str();
+    let (native, provider_component) = crate::choose_provider(client, provider_component, fallback_component, report)?;
+    link::trace(name, match (&native, report.fallback) {
+        (Some(_), _) => "provider: native host, linked into the runner",
+        (None, true) => "provider: fallback component, instantiated per client",
+        (None, false) => "provider: component, instantiated per client",
+    });
+    let mut store = Store::new(engine, ClientState::new(native.clone()));
+    store.limiter(|state| &mut state.limits);
+    store.data_mut().capture_stdio(client.stdio, name, &client.stdio_dir)?;
+    shutdown::arm(&mut store);
+    let _plugins = match &native {
+        Some(host) => plugins.instantiate(host, name).map_err(|e| Failure::new(FailureKind::Provider, e))?,
+        None => Vec::new(),
+    };
+
+    let mut linker = Linker::new(engine);
+    wasmtime_wasi::preview2::command::add_to_linker(&mut linker)?;
+    link::trace(name, "wasi:cli, wasi:io, wasi:clocks, wasi:filesystem, wasi:random, wasi:sockets -> wasmtime-wasi");
+    let provider = match provider_component {
+        Some(provider_component) => {
+            crate::provider_bindings::wasi_custom::host_offload::host_offload_logging::add_to_linker(&mut linker, |state: &mut ClientState| state)?;
+            link::trace(name, "pre-instantiating the provider component against the WASI and logging linker");
+            let provider_instance_pre: InstancePre<ClientState> = linker.instantiate_pre(provider_component)
+                .context("Failed to pre-instantiate provider component")?;
+            let (provider, provider_instance) = provider_bindings::Provider::instantiate_pre(&mut store, &provider_instance_pre)
+                .await
+                .context("Failed to instantiate provider component")
+                .map_err(|e| Failure::new(FailureKind::Provider, e))?;
+            link::trace(name, "client import host-allocator -> provider instance; nothing else is linked from it");
+            crate::client::add_to_linker_imports(&mut linker, |_, import: &str| {
+                 match import {
+                    "host-allocator" => Ok(provider_instance),
+                    _ => anyhow::bail!("Unknown import: {} (a provider component only serves host-allocator)", import),
+                }
+            })?;
+            Provider::Component(provider)
+        }
+        None => {
+            // Synchronous host functions are fine in an async store; the
+            // expensive ones get off the runtime's way themselves.
+            if link::imports_v0_1(client)? {
+                state::add_v0_1_offload_to_linker(&mut linker)?;
+                link::trace(name, "host-allocator@0.1.0 -> native host, through its 0.1 adapter");
+            } else {
+                state::add_offload_to_linker(&mut linker)?;
+            }
+            buffer_streams::add_to_linker(&mut linker, |state: &mut ClientState| state)?;
+            handle_persistence::add_to_linker(&mut linker, |state: &mut ClientState| state)?;
+            link::trace(name, "host-allocator, buffer-streams, random, handle-persistence -> native host");
+            if client.keyvalue {
+                store.data_mut().keyvalue = native.clone().map(KeyValueStore::new);
+                store::add_to_linker(&mut linker, |state: &mut ClientState| state)?;
+                link::trace(name, "wasi:keyvalue/store -> native host buffers");
+            }
+            if let Some(dir) = &client.tokenizer_dir {
+                store.data_mut().tokenizers = native.as_ref().map(|host| host.tokenizers(dir));
+                tokenizer::add_to_linker(&mut linker, |state: &mut ClientState| state.tokenizers())?;
+                link::trace(name, format_args!("tokenizer -> tokenizers in {}", dir));
+            }
+            Provider::Native(native.unwrap())
+        }
+    };
+
+    let session = provider.open_session(&mut store, client).await
+        .map_err(|e| Failure::new(FailureKind::Provider, e))?;
+    println!("[Runner:{}] Opened provider session {}", name, session);
+    report.session = Some(session);
+
+    println!("[Runner:{}] Instantiating client component and linking with provider...", name);
+    let started = Instant::now();
+    let client_instance = linker.instantiate_async(&mut store, client_component).await
+         .context("Failed to instantiate client component with provider")?;
+    report.instantiate_ms = Some(report::millis(started.elapsed()));
+    link::trace(name, format_args!("client instantiated in {:.1} ms", report.instantiate_ms.unwrap()));
+    let sink = match &provider {
+        Provider::Native(host) => AsyncEventSink::subscribe(&mut store, &client_instance, host),
+        Provider::Component(_) => None,
+    };
+    let export = client.export.as_str();
+    let run = client_instance.get_typed_func::<(), (Result<(), String>,)>(&mut store, export)
+        .with_context(|| format!("Client has no export '{}' of type func() -> result<_, string>", export))?;
+    link::trace(name, format_args!("calling export {}: func() -> result<_, string>", export));
+
+    println!("[Runner:{}] Calling '{}' in client Wasm...", name, export);
+    let started = Instant::now();
+    let called = match run.call_async(&mut store, ()).await {
+        Ok((result,)) => run.post_return_async(&mut store).await.map(|()| result),
+        Err(e) => Err(e),
+    };
+    let outcome = crate::call_outcome(called, name, export);
+    let outcome = match (outcome, &sink, &provider) {
+        (Ok(()), Some(sink), Provider::Native(host)) => sink.deliver(&mut store, host, name).await.map(|delivered| {
+            if delivered > 0 {
+                println!("[Runner:{}] Delivered {} provider events", name, delivered);
+            }
+        }),
+        (outcome, _, _) => outcome,
+    };
+    report.call_ms = Some(report::millis(started.elapsed()));
+    if let Provider::Native(host) = &provider {
+        report.host_calls = Some(store.data().metrics.host_calls);
+        report::record_usage(report, name, host, session);
+    }
+    report.peak_memory_bytes = Some(store.data().limits.peak_memory_bytes as u64);
+    if let (Some(path), Provider::Native(host)) = (&client.analysis_report, &provider) {
+        report::write_analysis(path, name, host);
+    }
+
+    shutdown::disarm(&mut store);
+    let closed = provider.close_session(&mut store, session).await;
+    crate::finish_session(closed, outcome, report, name, session)
+}
@@ -0,0 +1,79 @@
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use wasmtime::component::Component;
+use wasmtime::Engine;
+
+// Where `--precompile` keeps compiled components unless RUNNER_CACHE_DIR says otherwise.
+const DEFAULT_CACHE_DIR: &str = ".runner-cache";
+
+// Loads a component for `engine` from `bytes`, read from `path` (see
+// `signature::load`, which checks them first).
+//
+// `.cwasm` files (from `runner precompile`) are deserialized as-is. With a
+// cache directory, anything else is compiled once and stored there under a key
+// covering both the file contents and the engine settings, so later runs with
+// the same inputs skip Cranelift entirely.
+pub fn load_bytes(engine: &Engine, path: &str, bytes: &[u8], cache: Option<&Path>) -> Result<Component> {
+    if path.ends_with(".cwasm") {
+        // SAFETY: `.cwasm` files are only ever produced by `runner precompile`
+        // and are trusted like the components they were compiled from.
+        return unsafe { Component::deserialize(engine, bytes) }
+            .with_context(|| format!("Failed to load precompiled component {} (built by a different runner?)", path));
+    }
+    let Some(cache) = cache else {
+        return Component::new(engine, bytes).with_context(|| format!("Failed to compile {}", path));
+    };
+
+    let cached = cache.join(format!("{}.cwasm", cache_key(engine, bytes)));
+    if cached.exists() {
+        // SAFETY: as above; the cache directory is only written by the runner,
+        // and only used without `trusted_keys` (see `signature::load`).
+        match unsafe { Component::deserialize_file(engine, &cached) } {
+            Ok(component) => {
+                println!("[Runner] Using cached compilation of {}", path);
+                return Ok(component);
+            }
+            Err(e) => eprintln!("[Runner] Ignoring unusable cache entry {}: {:#}", cached.display(), e),
+        }
+    }
+
+    let component = Component::new(engine, bytes)
+        .with_context(|| format!("Failed to compile {}", path))?;
+    // A cache we can't write to only costs the next run a recompile.
+    let stored = component.serialize()
+        .and_then(|compiled| Ok(std::fs::create_dir_all(cache).and_then(|_| std::fs::write(&cached, compiled))?));
+    if let Err(e) = stored {
+        eprintln!("[Runner] Failed to cache {}: {:#}", path, e);
+    }
+    Ok(component)
+}
+
+// `runner precompile <component.wasm> [out.cwasm]`
+pub fn precompile(engine: &Engine, path: &str, out: Option<&str>) -> Result<()> {
+    let out = out.map(PathBuf::from).unwrap_or_else(|| Path::new(path).with_extension("cwasm"));
+    let bytes = std::fs::read(path).with_context(|| format!("Failed to read {}", path))?;
+    let compiled = engine.precompile_component(&bytes)
+        .with_context(|| format!("Failed to compile {}", path))?;
+    std::fs::write(&out, compiled).with_context(|| format!("Failed to write {}", out.display()))?;
+    println!("[Runner] Wrote {}", out.display());
+    Ok(())
+}
+
+pub fn cache_dir() -> PathBuf {
+    std::env::var_os("RUNNER_CACHE_DIR").map(PathBuf::from).unwrap_or_else(|| PathBuf::from(DEFAULT_CACHE_DIR))
+}
+
+fn cache_key(engine: &Engine, bytes: &[u8]) -> String {
+    // Compiled code is only valid for the engine configuration and wasmtime
+    // version that produced it.
+    let mut engine_hash = std::collections::hash_map::DefaultHasher::new();
+    engine.precompile_compatibility_hash().hash(&mut engine_hash);
+
+    let mut hasher = Sha256::new();
+    hasher.update(engine_hash.finish().to_le_bytes());
+    hasher.update(bytes);
+    hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect()
+}
@@ -0,0 +1,33 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use wasm_compose::composer::ComponentComposer;
+
+use crate::config::DEFAULT_PROVIDER_PATH;
+
+// `runner compose <client.wasm> <out.wasm> [provider.wasm]`
+//
+// Plugs a provider component into the client's `host-allocator` import and
+// writes the result as one component, for runtimes that know nothing about
+// this project. The provider defaults to the crate's wasm build, which does
+// everything in nalgebra inside the component: slow, but it runs anywhere.
+// Clients importing native-only interfaces (`buffer-streams`, `tokenizer`,
+// ...) keep those imports and still need a host that provides them, as does
+// the provider's `host-offload-logging`.
+pub fn run(args: &[String]) -> Result<()> {
+    let usage = "Usage: runner compose <client.wasm> <out.wasm> [provider.wasm]";
+    let client = args.first().context(usage)?;
+    let out = args.get(1).context(usage)?;
+    let provider = args.get(2).map(String::as_str).unwrap_or(DEFAULT_PROVIDER_PATH);
+
+    let config = wasm_compose::config::Config {
+        definitions: vec![PathBuf::from(provider)],
+        ..Default::default()
+    };
+    let bytes = ComponentComposer::new(Path::new(client), &config)
+        .compose()
+        .with_context(|| format!("Failed to compose {} with provider {}", client, provider))?;
+    std::fs::write(out, &bytes).with_context(|| format!("Failed to write {}", out))?;
+    println!("[Runner] Wrote {} ({} bytes): {} with {} linked in", out, bytes.len(), client, provider);
+    Ok(())
+}
@@ -0,0 +1,346 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use crate::policy;
+use crate::profiles::Profiles;
+use crate::signature::TrustedKeys;
+use crate::stdio::GuestStdio;
+
+pub const DEFAULT_PROVIDER_PATH: &str = "../host-offload-provider/target/wasm32-unknown-unknown/release/host_offload_provider.wasm";
+// `provider` value that links the provider into the runner instead of loading a component.
+pub const NATIVE_PROVIDER: &str = "native";
+const DEFAULT_EXPORT: &str = "run-matrix-example";
+const DEFAULT_STDIO_DIR: &str = "guest-logs";
+const DEFAULT_CLIENT_PATH: &str = "../matrix-client/target/wasm32-unknown-unknown/release/matrix_client.wasm";
+
+// Runner configuration, loaded from a TOML file:
+//
+//   provider = "path/to/provider.wasm"   # or "native"
+//   fallback_provider = "path/to/provider.wasm"  # optional, native provider only
+//   intern_capacity_bytes = 1073741824  # optional, native provider only; see `intern-buffer`
+//   compute_slots = 8             # optional, native provider only; compute calls run at once, shared fairly by sessions
+//   trusted_keys = ["d75a98...1a"]  # optional; hex ed25519 keys client, provider and plugin components must be signed by
+//
+//   [[clients]]
+//   name = "matrix-a"
+//   path = "path/to/matrix_client.wasm"
+//   export = "run-matrix-example" # optional; any `func() -> result<_, string>`
+//   max_ops_per_sec = 1000        # optional
+//   max_bytes_per_sec = 67108864  # optional
+//   max_op_millis = 5000          # optional
+//   cpu_weight = 2                # optional; share of contended compute slots, 1 unless set
+//   max_allocation_bytes = 1073741824  # optional, native provider only
+//   memory_budget_bytes = 268435456    # optional, native provider only
+//   numa = true                   # optional, native provider only
+//   numa_threads_per_node = 8     # optional; defaults to every CPU of the node
+//   gpu = "cuda"                  # or "vulkan", or "metal" on macOS; optional, native provider only; needs the runner built with that feature
+//   split_matmul = true           # optional, needs gpu; share large multiplies between CPU and gpu(0)
+//   backend = "auto"              # optional, native provider only; time the backends and route to the fastest
+//   keyvalue = true               # optional, native provider only
+//   tokenizer_dir = "models/tok"  # optional, native provider only; links `tokenizer`
+//   analysis_report = "a.json"    # optional, native provider only; fusion and round-trip suggestions
+//   profiles = ["core", "linalg"] # optional; checked against client and provider before linking (see wit/profiles.toml)
+//   stdio = "prefix"              # or "inherit", or "files"; where the guest's stdout and stderr go
+//   stdio_dir = "guest-logs"      # optional; where stdio = "files" writes <name>.stdout and <name>.stderr
+//   provider_log_level = "debug"  # optional; "off" to "trace", the provider's default is "info"
+//   audit_log = "audit.jsonl"     # optional, native provider only; appends a line per host-allocator, random or tokenizer call
+//
+//   [clients.policy]              # optional, native provider only; denied calls fail with permission-denied
+//   deny = ["admin", "dump-matrix"]  # profiles (see wit/profiles.toml), host-allocator functions, "random", "tokenizer"
+//   max_matmul_dim = 4096         # optional; largest row or column count of a multiply operand
+//   files = false                 # optional; no shared-memory buffers, dump-matrix to files or tokenizer loads
+//
+//   [[plugins]]                   # optional, native provider only
+//   name = "elementwise"
+//   path = "path/to/plugin.wasm"  # a `compute-plugin` component
+//
+//   [serve]                       # optional, `runner serve` only
+//   warm_instances = 16
+//   max_instances = 1000
+//   audit_log = "audit.jsonl"     # optional; each instance's calls, as "http-<n>"
+//
+// Every client runs on its own thread with its own store and its own provider
+// instance, so clients never share handles or provider state.
+#[derive(Debug, Deserialize)]
+pub struct RunnerConfig {
+    #[serde(default = "default_provider_path")]
+    pub provider: String,
+    // Component to run a client on instead when its native provider can't be
+    // set up (its GPU backend doesn't open, or isn't built in). The provider
+    // crate's own wasm build does all of `host-allocator` in nalgebra, slowly.
+    #[serde(default)]
+    pub fallback_provider: Option<String>,
+    // Bytes of interned buffers no handle refers to that the native provider
+    // keeps for later `intern-buffer` calls, across all clients.
+    #[serde(default)]
+    pub intern_capacity_bytes: Option<u64>,
+    // Compute calls the native provider runs at once across all clients;
+    // unlimited unless set. Waiting calls are scheduled by `cpu_weight`.
+    #[serde(default)]
+    pub compute_slots: Option<usize>,
+    // Public keys, one of which must have signed each client, provider and
+    // plugin component (see `signature`); `--precompile` doesn't cache signed
+    // components. Empty loads components unchecked.
+    #[serde(default)]
+    pub trusted_keys: Vec<String>,
+    #[serde(default = "default_clients")]
+    pub clients: Vec<ClientConfig>,
+    // Compute plugins whose ops every client can call through `call-op`.
+    #[serde(default)]
+    pub plugins: Vec<PluginConfig>,
+    #[serde(default)]
+    pub serve: ServeConfig,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ServeConfig {
+    // Handler instances kept instantiated ahead of requests. 0 disables the pool.
+    pub warm_instances: usize,
+    // Upper bound on live instances, warm or serving; sizes the pooling allocator.
+    pub max_instances: u32,
+    pub audit_log: Option<String>,
+}
+
+impl Default for ServeConfig {
+    fn default() -> Self {
+        ServeConfig { warm_instances: 16, max_instances: 1000, audit_log: None }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PluginConfig {
+    pub name: String,
+    pub path: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ClientConfig {
+    pub name: String,
+    pub path: String,
+    // Export to call. The client must import `host-allocator` under that name,
+    // as the matrix client does; it may export anything else.
+    #[serde(default = "default_export")]
+    pub export: String,
+    // Provider-enforced rate limits for this client's session. Unset means
+    // unlimited; 0 is rejected.
+    #[serde(default)]
+    pub max_ops_per_sec: Option<u32>,
+    #[serde(default)]
+    pub max_bytes_per_sec: Option<u64>,
+    // Longest a single compute call may run before failing with `timeout`.
+    #[serde(default)]
+    pub max_op_millis: Option<u64>,
+    // Relative share of contended compute; see `compute_slots`.
+    #[serde(default)]
+    pub cpu_weight: Option<u32>,
+    // Largest single buffer the client may allocate. Only honoured by the
+    // native provider; a provider component keeps its compiled-in limit.
+    #[serde(default)]
+    pub max_allocation_bytes: Option<u64>,
+    // Resident bytes above which the native provider spills least-recently
+    // used buffers to disk. Unset keeps everything in memory.
+    #[serde(default)]
+    pub memory_budget_bytes: Option<u64>,
+    // Run large multiplies on the native provider's NUMA worker pool.
+    #[serde(default)]
+    pub numa: bool,
+    #[serde(default)]
+    pub numa_threads_per_node: Option<usize>,
+    // Backend behind `device::gpu(n)`. Without one, GPU devices are unavailable.
+    #[serde(default)]
+    pub gpu: Option<String>,
+    // Compute the rows of large unplaced multiplies partly on the CPU and
+    // partly on the first GPU, shared by measured throughput.
+    #[serde(default)]
+    pub split_matmul: bool,
+    // "auto" times every backend the client has (CPU kernels, NUMA pool,
+    // GPUs) per size class and sends unplaced multiplies to the fastest.
+    #[serde(default)]
+    pub backend: Option<String>,
+    // Also link `wasi:keyvalue/store`, with values kept in provider buffers.
+    #[serde(default)]
+    pub keyvalue: bool,
+    // Also link `tokenizer`, loading tokenizer files from this directory.
+    #[serde(default)]
+    pub tokenizer_dir: Option<String>,
+    // Record the client's calls and write what could be restructured
+    // (fusable op chains, needless read-backs) to this JSON file.
+    #[serde(default)]
+    pub analysis_report: Option<String>,
+    // Profiles of the interface the client uses, as "name" or
+    // "name@version". Empty skips the check.
+    #[serde(default)]
+    pub profiles: Vec<String>,
+    // The guest's stdout and stderr, tagged with the client's name by default.
+    #[serde(default)]
+    pub stdio: GuestStdio,
+    #[serde(default = "default_stdio_dir")]
+    pub stdio_dir: String,
+    // Set on the provider right after the session opens; unset leaves the
+    // provider's own default. The native provider has one level per
+    // process, so the last client to start sets it for all of them.
+    #[serde(default)]
+    pub provider_log_level: Option<ProviderLogLevel>,
+    // Append a JSONL line for every `host-allocator` call the client makes
+    // (see `host_offload_provider::audit`); clients may share a file.
+    #[serde(default)]
+    pub audit_log: Option<String>,
+    // What the client may not do, for clients that aren't trusted with all
+    // of `host-allocator`, `random` and `tokenizer`.
+    #[serde(default)]
+    pub policy: Option<PolicyConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct PolicyConfig {
+    pub deny: Vec<String>,
+    pub max_matmul_dim: Option<u32>,
+    pub files: bool,
+}
+
+impl Default for PolicyConfig {
+    fn default() -> Self {
+        PolicyConfig { deny: Vec::new(), max_matmul_dim: None, files: true }
+    }
+}
+
+// `session-admin.log-level`, as the config spells it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProviderLogLevel {
+    Off,
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl RunnerConfig {
+    // The native provider additionally serves `buffer-streams` to clients.
+    pub fn uses_native_provider(&self) -> bool {
+        self.provider == NATIVE_PROVIDER
+    }
+
+    pub fn load(path: &str) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read runner config {}", path))?;
+        let config: RunnerConfig = toml::from_str(&text)
+            .with_context(|| format!("Failed to parse runner config {}", path))?;
+        if config.clients.is_empty() {
+            anyhow::bail!("Runner config {} lists no clients", path);
+        }
+        TrustedKeys::parse(&config.trusted_keys).with_context(|| format!("Runner config {}", path))?;
+        // Plugins reach buffers through `host-allocator`, which a provider
+        // component only exports to its one client.
+        if !config.plugins.is_empty() && !config.uses_native_provider() {
+            anyhow::bail!("Runner config {} loads plugins, which need provider = \"{}\"", path, NATIVE_PROVIDER);
+        }
+        if config.intern_capacity_bytes.is_some() && !config.uses_native_provider() {
+            anyhow::bail!("Runner config {} sets intern_capacity_bytes, which needs provider = \"{}\"", path, NATIVE_PROVIDER);
+        }
+        if config.compute_slots.is_some() && !config.uses_native_provider() {
+            anyhow::bail!("Runner config {} sets compute_slots, which needs provider = \"{}\"", path, NATIVE_PROVIDER);
+        }
+        if config.fallback_provider.is_some() && !config.uses_native_provider() {
+            anyhow::bail!("Runner config {} sets a fallback_provider, which needs provider = \"{}\"", path, NATIVE_PROVIDER);
+        }
+        if let Some(client) = config.clients.iter().find(|c| c.gpu.is_some() && !config.uses_native_provider()) {
+            anyhow::bail!("Client '{}' asks for a GPU backend, which needs provider = \"{}\"", client.name, NATIVE_PROVIDER);
+        }
+        if let Some(client) = config.clients.iter().find(|c| c.split_matmul && c.gpu.is_none()) {
+            anyhow::bail!("Client '{}' asks for split_matmul without a gpu backend", client.name);
+        }
+        if let Some(client) = config.clients.iter().find(|c| c.backend.is_some() && !config.uses_native_provider()) {
+            anyhow::bail!("Client '{}' sets a backend, which needs provider = \"{}\"", client.name, NATIVE_PROVIDER);
+        }
+        if let Some(client) = config.clients.iter().find(|c| c.backend.as_deref().is_some_and(|b| b != "auto")) {
+            anyhow::bail!("Client '{}' asks for backend {:?}; only \"auto\" is supported", client.name, client.backend.as_deref().unwrap());
+        }
+        if let Some(client) = config.clients.iter().find(|c| c.keyvalue && !config.uses_native_provider()) {
+            anyhow::bail!("Client '{}' asks for keyvalue, which needs provider = \"{}\"", client.name, NATIVE_PROVIDER);
+        }
+        if let Some(client) = config.clients.iter().find(|c| c.tokenizer_dir.is_some() && !config.uses_native_provider()) {
+            anyhow::bail!("Client '{}' sets a tokenizer_dir, which needs provider = \"{}\"", client.name, NATIVE_PROVIDER);
+        }
+        if let Some(client) = config.clients.iter().find(|c| c.analysis_report.is_some() && !config.uses_native_provider()) {
+            anyhow::bail!("Client '{}' asks for an analysis_report, which needs provider = \"{}\"", client.name, NATIVE_PROVIDER);
+        }
+        if let Some(client) = config.clients.iter().find(|c| c.audit_log.is_some() && !config.uses_native_provider()) {
+            anyhow::bail!("Client '{}' asks for an audit_log, which needs provider = \"{}\"", client.name, NATIVE_PROVIDER);
+        }
+        if let Some(client) = config.clients.iter().find(|c| c.max_ops_per_sec == Some(0) || c.max_bytes_per_sec == Some(0)) {
+            anyhow::bail!("Client '{}' sets a rate limit of 0, which would refuse every call; leave it unset for no limit", client.name);
+        }
+        if let Some(client) = config.clients.iter().find(|c| c.policy.is_some() && !config.uses_native_provider()) {
+            anyhow::bail!("Client '{}' sets a policy, which needs provider = \"{}\"", client.name, NATIVE_PROVIDER);
+        }
+        for client in &config.clients {
+            if let Some(policy) = &client.policy {
+                policy::resolve(policy).with_context(|| format!("Client '{}'", client.name))?;
+            }
+            for declared in &client.profiles {
+                Profiles::builtin().get(declared).with_context(|| format!("Client '{}'", client.name))?;
+            }
+        }
+        Ok(config)
+    }
+}
+
+impl Default for RunnerConfig {
+    // The single matrix client the runner has always run.
+    fn default() -> Self {
+        RunnerConfig {
+            provider: default_provider_path(),
+            fallback_provider: None,
+            intern_capacity_bytes: None,
+            compute_slots: None,
+            trusted_keys: Vec::new(),
+            clients: default_clients(),
+            plugins: Vec::new(),
+            serve: ServeConfig::default(),
+        }
+    }
+}
+
+fn default_provider_path() -> String {
+    DEFAULT_PROVIDER_PATH.to_string()
+}
+
+fn default_stdio_dir() -> String {
+    DEFAULT_STDIO_DIR.to_string()
+}
+
+fn default_export() -> String {
+    DEFAULT_EXPORT.to_string()
+}
+
+fn default_clients() -> Vec<ClientConfig> {
+    vec![ClientConfig {
+        name: "matrix-client".to_string(),
+        path: DEFAULT_CLIENT_PATH.to_string(),
+        export: default_export(),
+        max_ops_per_sec: None,
+        max_bytes_per_sec: None,
+        max_op_millis: None,
+        cpu_weight: None,
+        max_allocation_bytes: None,
+        memory_budget_bytes: None,
+        numa: false,
+        numa_threads_per_node: None,
+        gpu: None,
+        split_matmul: false,
+        backend: None,
+        keyvalue: false,
+        tokenizer_dir: None,
+        analysis_report: None,
+        profiles: Vec::new(),
+        stdio: GuestStdio::default(),
+        stdio_dir: default_stdio_dir(),
+        provider_log_level: None,
+        audit_log: None,
+        policy: None,
+    }]
+}
@@ -0,0 +1,136 @@
+use host_offload_provider::events::{Event, EvictionReason, QuotaKind};
+use host_offload_provider::native::OffloadHost;
+use wasmtime::component::Instance;
+use wasmtime::Store;
+
+use crate::report::{Failure, FailureKind};
+use crate::shutdown;
+use crate::state::ClientState;
+
+mod bindings {
+    wasmtime::component::bindgen!({
+        world: "event-sink",
+        path: "wit/event-sink.wit",
+        additional_packages: [
+            { package = "wasi-custom:host-offload@0.2.0", path = "../wit" },
+        ],
+    });
+}
+
+// Same world for `runner --async`, whose stores can only be called into asynchronously.
+mod async_bindings {
+    wasmtime::component::bindgen!({
+        world: "event-sink",
+        path: "wit/event-sink.wit",
+        additional_packages: [
+            { package = "wasi-custom:host-offload@0.2.0", path = "../wit" },
+        ],
+        async: true,
+    });
+}
+
+use async_bindings::exports::wasi_custom::host_offload::host_offload_events as async_guest;
+use bindings::exports::wasi_custom::host_offload::host_offload_events as guest;
+
+// The provider's event enums in terms of one set of generated bindings.
+macro_rules! eviction_reason {
+    ($guest:ident, $reason:expr) => {
+        match $reason {
+            EvictionReason::Spilled => $guest::EvictionReason::Spilled,
+            EvictionReason::Expired => $guest::EvictionReason::Expired,
+        }
+    };
+}
+
+macro_rules! quota_kind {
+    ($guest:ident, $kind:expr) => {
+        match $kind {
+            QuotaKind::Operations => $guest::QuotaKind::Operations,
+            QuotaKind::Bytes => $guest::QuotaKind::Bytes,
+        }
+    };
+}
+
+// A client's `host-offload-events` export.
+pub struct EventSink(bindings::EventSink);
+
+impl EventSink {
+    // Returns None for clients that don't export the interface. Otherwise
+    // tells `host` to start queueing events for it.
+    pub fn subscribe(store: &mut Store<ClientState>, instance: &Instance, host: &OffloadHost) -> Option<Self> {
+        let sink = bindings::EventSink::new(store, instance).ok()?;
+        host.lock().enable_events();
+        Some(EventSink(sink))
+    }
+
+    // Runs the client's event loop after its entry point has returned; see
+    // `host-offload-events` in the WIT. Returns the number of events delivered.
+    pub fn deliver(&self, store: &mut Store<ClientState>, host: &OffloadHost, name: &str) -> Result<u32, Failure> {
+        let handlers = self.0.wasi_custom_host_offload_host_offload_events();
+        let mut delivered = 0;
+        while let Some(events) = next_batch(host) {
+            for event in events {
+                println!("[Runner:{}] Delivering {:?}", name, event);
+                let called = match event {
+                    Event::BufferEvicted { handle, reason } => handlers.call_on_buffer_evicted(&mut *store, handle, eviction_reason!(guest, reason)),
+                    Event::JobComplete(job) => handlers.call_on_job_complete(&mut *store, job),
+                    Event::QuotaWarning { kind, remaining_percent } => {
+                        handlers.call_on_quota_warning(&mut *store, quota_kind!(guest, kind), remaining_percent)
+                    }
+                };
+                called.map_err(|e| handler_trap(e, event))?;
+                delivered += 1;
+            }
+        }
+        Ok(delivered)
+    }
+}
+
+// `EventSink` for `runner --async`.
+pub struct AsyncEventSink(async_bindings::EventSink);
+
+impl AsyncEventSink {
+    pub fn subscribe(store: &mut Store<ClientState>, instance: &Instance, host: &OffloadHost) -> Option<Self> {
+        let sink = async_bindings::EventSink::new(store, instance).ok()?;
+        host.lock().enable_events();
+        Some(AsyncEventSink(sink))
+    }
+
+    pub async fn deliver(&self, store: &mut Store<ClientState>, host: &OffloadHost, name: &str) -> Result<u32, Failure> {
+        let handlers = self.0.wasi_custom_host_offload_host_offload_events();
+        let mut delivered = 0;
+        while let Some(events) = next_batch(host) {
+            for event in events {
+                println!("[Runner:{}] Delivering {:?}", name, event);
+                let called = match event {
+                    Event::BufferEvicted { handle, reason } => {
+                        handlers.call_on_buffer_evicted(&mut *store, handle, eviction_reason!(async_guest, reason)).await
+                    }
+                    Event::JobComplete(job) => handlers.call_on_job_complete(&mut *store, job).await,
+                    Event::QuotaWarning { kind, remaining_percent } => {
+                        handlers.call_on_quota_warning(&mut *store, quota_kind!(async_guest, kind), remaining_percent).await
+                    }
+                };
+                called.map_err(|e| handler_trap(e, event))?;
+                delivered += 1;
+            }
+        }
+        Ok(delivered)
+    }
+}
+
+// Advances outstanding jobs and returns the events queued since the last
+// batch, or None once there are neither (or shutdown was requested).
+fn next_batch(host: &OffloadHost) -> Option<Vec<Event>> {
+    if shutdown::requested() {
+        return None;
+    }
+    let mut state = host.lock();
+    let busy = state.advance_jobs();
+    let events = state.take_events();
+    (busy || !events.is_empty()).then_some(events)
+}
+
+fn handler_trap(e: anyhow::Error, event: Event) -> Failure {
+    Failure::new(FailureKind::Trap, e.context(format!("Trap in client's handler for {:?}", event)))
+}
@@ -0,0 +1,57 @@
+use std::sync::OnceLock;
+
+use anyhow::Result;
+use wasmtime::ResourceLimiter;
+
+// `--max-guest-memory` (bytes per linear memory) and `--max-guest-table`
+// (elements per table), applied to every client and plugin store. Unset
+// leaves only the module's own maximum.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GuestLimits {
+    pub memory_bytes: Option<usize>,
+    pub table_elements: Option<u32>,
+}
+
+static LIMITS: OnceLock<GuestLimits> = OnceLock::new();
+
+pub fn set(limits: GuestLimits) {
+    let _ = LIMITS.set(limits);
+}
+
+// Enforces the configured limits for one store and remembers how large its
+// memories got. A refused growth fails the guest's call with the reason
+// instead of letting `memory.grow` quietly return -1.
+#[derive(Debug, Default)]
+pub struct Limiter {
+    limits: GuestLimits,
+    pub peak_memory_bytes: usize,
+}
+
+impl Limiter {
+    pub fn configured() -> Self {
+        Limiter { limits: LIMITS.get().copied().unwrap_or_default(), peak_memory_bytes: 0 }
+    }
+}
+
+impl ResourceLimiter for Limiter {
+    fn memory_growing(&mut self, current: usize, desired: usize, _maximum: Option<usize>) -> Result<bool> {
+        if let Some(max) = self.limits.memory_bytes.filter(|&max| desired > max) {
+            anyhow::bail!(
+                "Guest tried to grow a linear memory from {} to {} bytes, over the {} byte limit (--max-guest-memory)",
+                current, desired, max
+            );
+        }
+        self.peak_memory_bytes = self.peak_memory_bytes.max(desired);
+        Ok(true)
+    }
+
+    fn table_growing(&mut self, current: u32, desired: u32, _maximum: Option<u32>) -> Result<bool> {
+        if let Some(max) = self.limits.table_elements.filter(|&max| desired > max) {
+            anyhow::bail!(
+                "Guest tried to grow a table from {} to {} elements, over the limit of {} (--max-guest-table)",
+                current, desired, max
+            );
+        }
+        Ok(true)
+    }
+}
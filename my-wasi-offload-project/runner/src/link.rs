@@ -0,0 +1,255 @@
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use anyhow::Result;
+
+use crate::config::ClientConfig;
+use crate::wit_tool::{self, WorldInterface};
+
+// Packages the WASI p2 command linker serves to every client.
+const WASI_PACKAGES: &[&str] = &["wasi:cli", "wasi:clocks", "wasi:filesystem", "wasi:io", "wasi:random", "wasi:sockets"];
+// Provider exports the runner calls itself rather than linking into clients.
+const RUNNER_EXPORTS: &[&str] = &["wasi-custom:host-offload/session-admin@0.2.0"];
+const RANDOM: &str = "wasi-custom:host-offload/random@0.2.0";
+const TOKENIZER: &str = "wasi-custom:host-offload/tokenizer@0.2.0";
+const PERSISTENCE: &str = "wasi-custom:host-offload/handle-persistence@0.2.0";
+const KEYVALUE: &str = "wasi:keyvalue/store@0.2.0-draft";
+// The release before `host-error` grew; see `imports_v0_1`.
+const V0_1: &str = "0.1.0";
+
+static DEBUG: AtomicBool = AtomicBool::new(false);
+
+// `--debug-linking`: log every import as it is resolved, and each step of
+// setting up a client's provider and instance.
+pub fn set_debug(on: bool) {
+    DEBUG.store(on, Ordering::SeqCst);
+}
+
+pub fn debugging() -> bool {
+    DEBUG.load(Ordering::SeqCst)
+}
+
+pub fn trace(client: &str, message: impl fmt::Display) {
+    if debugging() {
+        eprintln!("[Link:{}] {}", client, message);
+    }
+}
+
+// Where one of the client's imports comes from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Source {
+    Provider,
+    Wasi,
+    // Nothing links it; why, and what would.
+    Missing(String),
+}
+
+#[derive(Debug)]
+pub struct Resolution {
+    pub import: WorldInterface,
+    pub source: Source,
+    // Functions the client imports that its source doesn't have.
+    pub missing: Vec<String>,
+}
+
+impl Resolution {
+    pub fn satisfied(&self) -> bool {
+        !matches!(self.source, Source::Missing(_)) && self.missing.is_empty()
+    }
+}
+
+// How a client's imports line up with what the runner would link for it.
+#[derive(Debug)]
+pub struct LinkPlan {
+    pub provider: String,
+    pub imports: Vec<Resolution>,
+    // Provider exports the client doesn't use: the interface, and the
+    // functions of it left unused (all of them if it isn't imported at all).
+    pub unused: Vec<(String, Vec<String>)>,
+}
+
+impl LinkPlan {
+    pub fn satisfied(&self) -> bool {
+        self.imports.iter().all(Resolution::satisfied)
+    }
+}
+
+// Reads the client's imports, and the provider component's exports unless
+// `provider` is None for the native provider, without instantiating either.
+pub fn plan(client: &ClientConfig, provider: Option<&str>) -> Result<LinkPlan> {
+    let (imports, _) = wit_tool::world_interfaces(&client.path)?;
+    let (imports, unused) = match provider {
+        Some(path) => {
+            let (_, exports) = wit_tool::world_interfaces(path)?;
+            let imports: Vec<Resolution> = imports.into_iter().map(|import| from_component(import, &exports)).collect();
+            let unused = exports.iter()
+                .filter(|export| !RUNNER_EXPORTS.contains(&export.name.as_str()))
+                .filter_map(|export| {
+                    let used = imports.iter().find(|r| r.import.name == export.name && r.source == Source::Provider);
+                    let idle: Vec<String> = export.functions.iter()
+                        .filter(|f| used.map_or(true, |r| !r.import.functions.contains(f)))
+                        .cloned()
+                        .collect();
+                    (!idle.is_empty()).then(|| (export.name.clone(), idle))
+                })
+                .collect();
+            (imports, unused)
+        }
+        None => {
+            let linked = native_imports(client);
+            let imports: Vec<Resolution> = imports.into_iter().map(|import| from_native(import, &linked)).collect();
+            let unused = linked.iter()
+                .filter(|name| !imports.iter().any(|r| r.import.name == **name))
+                .map(|name| (name.to_string(), Vec::new()))
+                .collect();
+            (imports, unused)
+        }
+    };
+    Ok(LinkPlan { provider: provider.unwrap_or("the native provider").to_string(), imports, unused })
+}
+
+// Whether the client was built against host-offload 0.1.0, and so needs the
+// native provider's 0.1 `host-allocator` (`state::add_v0_1_offload_to_linker`)
+// rather than the current one. Precompiled clients can't be read and are
+// taken to be current.
+pub fn imports_v0_1(client: &ClientConfig) -> Result<bool> {
+    if client.path.ends_with(".cwasm") {
+        return Ok(false);
+    }
+    let (imports, _) = wit_tool::world_interfaces(&client.path)?;
+    Ok(imports.iter().any(|import| import.package == wit_tool::PACKAGE && import.version.as_deref() == Some(V0_1)))
+}
+
+// Fails with the whole plan if any import would go unresolved.
+pub fn check(client: &ClientConfig, provider: Option<&str>) -> Result<()> {
+    let plan = plan(client, provider)?;
+    if debugging() {
+        trace_plan(&client.name, &plan);
+    }
+    if !plan.satisfied() {
+        anyhow::bail!("Client '{}' can't be linked against {}:\n{}", client.name, plan.provider, plan);
+    }
+    Ok(())
+}
+
+fn trace_plan(client: &str, plan: &LinkPlan) {
+    for r in &plan.imports {
+        let from = match &r.source {
+            Source::Provider => plan.provider.clone(),
+            Source::Wasi => "WASI p2 (wasmtime-wasi)".to_string(),
+            Source::Missing(why) => format!("nothing ({})", why),
+        };
+        trace(client, format_args!("import {} ({}/{}) <- {}", r.import.name, r.import.package, r.import.interface, from));
+        for signature in &r.import.signatures {
+            let lacking = r.missing.iter().any(|f| signature.starts_with(&format!("{}:", f)));
+            trace(client, format_args!("    {}{}", signature, if lacking { "  (not provided)" } else { "" }));
+        }
+    }
+    for (name, functions) in &plan.unused {
+        match functions.is_empty() {
+            true => trace(client, format_args!("unused: {}", name)),
+            false => trace(client, format_args!("unused: {}: {}", name, functions.join(", "))),
+        }
+    }
+}
+
+// Names the native path links for this client (see `run_client`).
+fn native_imports(client: &ClientConfig) -> Vec<&'static str> {
+    let mut linked = vec!["host-allocator", "buffer-streams", RANDOM, PERSISTENCE];
+    if client.keyvalue {
+        linked.push(KEYVALUE);
+    }
+    if client.tokenizer_dir.is_some() {
+        linked.push(TOKENIZER);
+    }
+    linked
+}
+
+fn is_wasi(import: &WorldInterface) -> bool {
+    WASI_PACKAGES.contains(&import.package.as_str())
+}
+
+fn from_native(import: WorldInterface, linked: &[&str]) -> Resolution {
+    let source = if is_wasi(&import) {
+        Source::Wasi
+    } else if linked.contains(&import.name.as_str()) {
+        Source::Provider
+    } else {
+        Source::Missing(match import.name.as_str() {
+            TOKENIZER => "set tokenizer_dir for this client".to_string(),
+            KEYVALUE => "set keyvalue = true for this client".to_string(),
+            _ if import.package == wit_tool::PACKAGE => match linked.iter().find(|name| name.contains(&import.interface)) {
+                Some(name) => format!("the runner links this interface as `{}`", name),
+                None => "the runner doesn't link this interface into clients".to_string(),
+            },
+            _ => "nothing in the runner provides it".to_string(),
+        })
+    };
+    Resolution { import, source, missing: Vec::new() }
+}
+
+fn from_component(import: WorldInterface, exports: &[WorldInterface]) -> Resolution {
+    if is_wasi(&import) {
+        return Resolution { import, source: Source::Wasi, missing: Vec::new() };
+    }
+    // Only `host-allocator` is wired to the provider instance.
+    if import.name != "host-allocator" {
+        let why = match import.package == wit_tool::PACKAGE {
+            true => "only host-allocator comes from a provider component; this needs provider = \"native\"",
+            false => "nothing in the runner provides it",
+        };
+        return Resolution { import, source: Source::Missing(why.to_string()), missing: Vec::new() };
+    }
+    match exports.iter().find(|export| export.package == import.package && export.interface == import.interface) {
+        // The provider component only exports the current version; the 0.1
+        // adapter is part of the native provider.
+        Some(export) if export.version != import.version => {
+            let why = format!(
+                "the client was built against {} {}, the provider exports {}; this needs provider = \"native\"",
+                wit_tool::PACKAGE,
+                import.version.as_deref().unwrap_or("unversioned"),
+                export.version.as_deref().unwrap_or("unversioned")
+            );
+            Resolution { import, source: Source::Missing(why), missing: Vec::new() }
+        }
+        Some(export) => {
+            let missing = import.functions.iter().filter(|f| !export.functions.contains(f)).cloned().collect();
+            Resolution { import, source: Source::Provider, missing }
+        }
+        None => Resolution { import, source: Source::Missing("the provider doesn't export it".to_string()), missing: Vec::new() },
+    }
+}
+
+impl fmt::Display for LinkPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self.imports.iter().map(|r| r.import.name.len()).max().unwrap_or(0).max("import".len());
+        writeln!(f, "  {:<width$}  {:<12}  {:>9}  from", "import", "version", "functions")?;
+        for r in &self.imports {
+            let from = match (&r.source, r.missing.is_empty()) {
+                (Source::Missing(why), _) => format!("MISSING: {}", why),
+                (Source::Provider, false) => format!("provider, lacking {} of them", r.missing.len()),
+                (Source::Provider, true) => "provider".to_string(),
+                (Source::Wasi, _) => "wasi".to_string(),
+            };
+            let version = r.import.version.as_deref().unwrap_or("-");
+            writeln!(f, "  {:<width$}  {:<12}  {:>9}  {}", r.import.name, version, r.import.functions.len(), from)?;
+            let unresolved = match &r.source {
+                Source::Missing(_) => &r.import.functions,
+                _ => &r.missing,
+            };
+            if !unresolved.is_empty() {
+                writeln!(f, "  {:<width$}    {}", "", unresolved.join(", "))?;
+            }
+        }
+        if !self.unused.is_empty() {
+            writeln!(f, "  unused provider exports:")?;
+            for (name, functions) in &self.unused {
+                match functions.is_empty() {
+                    true => writeln!(f, "    {}", name)?,
+                    false => writeln!(f, "    {}: {}", name, functions.join(", "))?,
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,75 +1,657 @@
+use std::time::Instant;
+
 use anyhow::{Result, Context};
 use wasmtime::component::{Component, Linker, InstancePre};
+use tracing_subscriber::EnvFilter;
 use wasmtime::{Config, Engine, Store};
 
+use host_offload_provider::audit::AuditLog;
+use host_offload_provider::keyvalue::KeyValueStore;
+use host_offload_provider::native::OffloadHost;
+use host_offload_provider::backend::open_gpu_backend;
+use host_offload_provider::numa::NumaConfig;
+use host_offload_provider::split::SplitConfig;
+use host_offload_provider::wasi::keyvalue::store;
+use host_offload_provider::wasi_custom::host_offload::{buffer_streams, handle_persistence, tokenizer};
+
+mod async_run;
+mod cache;
+mod compose;
+mod config;
+mod events;
+mod limits;
+mod link;
+mod persist;
+mod plugins;
+mod policy;
+mod profiles;
+mod report;
+mod serve;
+mod shutdown;
+mod signature;
+mod state;
+mod stdio;
+mod wit_python;
+mod wit_tool;
+
+use config::{ClientConfig, ProviderLogLevel, RunnerConfig};
+use events::EventSink;
+use plugins::Plugins;
+use report::{ClientReport, Failure, FailureKind, OutputFormat, RunReport};
+use signature::TrustedKeys;
+use state::ClientState;
+
 wasmtime::component::bindgen!({
     // For running the client.
     world: "client",
     path: "../matrix-client/wit/world.wit", // Path to client's world WIT
     additional_packages: [ // Dependencies of client's world
-        { package = "wasi-custom:host-offload@0.1.0", path = "../wit/host-offload.wit" },
+        { package = "wasi-custom:host-offload@0.2.0", path = "../wit" },
     ],
     interface_imports: true, 
 });
 
-// We don't strictly need to generate bindings for the provider's world if we are just
-// taking its instance and passing it to the linker. However, if we wanted to
-// call its functions from the host, we would.
-// For linking, Wasmtime primarily needs the component instance that exports the interface.
+// Bindings for the provider's world. The runner calls the provider's
+// `session-admin` exports directly to scope the client's handles to its run.
+// Kept in its own module so the generated `wasi_custom` paths don't clash with
+// the client bindings above.
+mod provider_bindings {
+    wasmtime::component::bindgen!({
+        world: "provider",
+        path: "../host-offload-provider/wit/world.wit",
+        additional_packages: [
+            { package = "wasi-custom:host-offload@0.2.0", path = "../wit" },
+        ],
+    });
+}
+
 
+// Exit status: 0 on success, 130 when stopped by a signal, otherwise
+// `FailureKind::exit_code` of the most serious failure (see `report`).
+fn main() {
+    // Provider components log through `host-offload-logging`; RUNNER_LOG
+    // filters them like RUST_LOG, e.g. `host_offload_provider::state=debug`.
+    // Only warnings and errors by default.
+    let filter = EnvFilter::try_from_env("RUNNER_LOG").unwrap_or_else(|_| EnvFilter::new("warn"));
+    tracing_subscriber::fmt().with_env_filter(filter).with_writer(std::io::stderr).init();
+    let result = run();
+    let code = match result {
+        Ok(()) => 0,
+        Err(failure) => {
+            eprintln!("Error: {:#}", failure.error);
+            failure.kind.exit_code()
+        }
+    };
+    shutdown::flush();
+    if shutdown::requested() {
+        std::process::exit(shutdown::EXIT_INTERRUPTED);
+    }
+    std::process::exit(code);
+}
+
+fn run() -> Result<(), Failure> {
+    // Usage: runner [--precompile] [--async] [--output text|json] [--debug-linking]
+    //               [--max-guest-memory <bytes>] [--max-guest-table <elements>] [config.toml]
+    //        runner [--precompile] serve <http-component.wasm> [addr] [config.toml]
+    //        runner [--async] precompile <component.wasm> [out.cwasm]
+    //        runner wit <show | check | diff | python | profiles> ...
+    //        runner compose <client.wasm> <out.wasm> [provider.wasm]
+    //
+    // `--precompile` caches compiled components on disk (see `cache`).
+    // `--async` runs clients as tasks on a tokio runtime (see `async_run`).
+    // `--output json` prints a machine-readable `RunReport` (see `report`).
+    // `--debug-linking` logs how every client gets linked (see `link`).
+    // `--max-guest-memory` and `--max-guest-table` cap what guests can grow (see `limits`).
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let cache_dir = take_flag(&mut args, "--precompile").then(cache::cache_dir);
+    let use_async = take_flag(&mut args, "--async");
+    link::set_debug(take_flag(&mut args, "--debug-linking"));
+    limits::set(limits::GuestLimits {
+        memory_bytes: take_number(&mut args, "--max-guest-memory")?,
+        table_elements: take_number(&mut args, "--max-guest-table")?,
+    });
+    let output = match take_option(&mut args, "--output")? {
+        Some(format) => OutputFormat::parse(&format)?,
+        None => OutputFormat::Text,
+    };
+    let cache_dir = cache_dir.as_deref();
+    if args.first().map(String::as_str) == Some("wit") {
+        return Ok(wit_tool::run(&args[1..])?);
+    }
+    if args.first().map(String::as_str) == Some("compose") {
+        return Ok(compose::run(&args[1..])?);
+    }
+    if args.first().map(String::as_str) == Some("serve") {
+        let component = args.get(1).context("Usage: runner serve <http-component.wasm> [addr] [config.toml]")?;
+        let addr = args.get(2).map(String::as_str).unwrap_or(serve::DEFAULT_ADDR);
+        let config = match args.get(3) {
+            Some(path) => RunnerConfig::load(path)?,
+            None => RunnerConfig::default(),
+        };
+        if let Some(bytes) = config.intern_capacity_bytes {
+            host_offload_provider::intern::set_capacity(bytes);
+        }
+        if let Some(slots) = config.compute_slots {
+            host_offload_provider::fair::set_compute_slots(slots);
+        }
+        let trusted_keys = TrustedKeys::parse(&config.trusted_keys)?;
+        return Ok(serve::serve(component, addr, cache_dir, &config.serve, &trusted_keys)?);
+    }
+    if args.first().map(String::as_str) == Some("precompile") {
+        let component = args.get(1).context("Usage: runner precompile <component.wasm> [out.cwasm]")?;
+        return Ok(cache::precompile(&new_engine(use_async)?, component, args.get(2).map(String::as_str))?);
+    }
+    let config = match args.first() {
+        Some(path) => RunnerConfig::load(path)?,
+        None => RunnerConfig::default(),
+    };
+    let mut json_out = match output {
+        OutputFormat::Json => Some(report::take_stdout()?),
+        OutputFormat::Text => None,
+    };
+    let started = Instant::now();
 
-fn main() -> Result<()> {
-    println!("[Runner] Setting up Wasmtime engine and store...");
-    let mut config = Config::new();
-    config.wasm_component_model(true);
-    
-    let engine = Engine::new(&config)?;
-    let mut store = Store::new(&engine, ()); // No complex host state needed for this runner
+    println!("[Runner] Setting up Wasmtime engine...");
+    let engine = new_engine(use_async)?;
+    let trusted_keys = TrustedKeys::parse(&config.trusted_keys)?;
+    match config.trusted_keys.len() {
+        0 => println!("[Runner] No trusted_keys configured; component signatures are not checked"),
+        n => println!("[Runner] Client, provider and plugin components must be signed by one of {} trusted keys", n),
+    }
+    let (plugins, mut signatures) = Plugins::load(&config.plugins, cache_dir, &trusted_keys)?;
+    shutdown::install(vec![engine.clone(), plugins.engine().clone()])?;
 
     // --- Load Provider Component ---
-    let provider_component_path = "../host-offload-provider/target/wasm32-unknown-unknown/release/host_offload_provider.wasm";
-    println!("[Runner] Loading provider component from: {}", provider_component_path);
-    let provider_component = Component::from_file(&engine, provider_component_path)
-        .context("Failed to load provider component")?;
+    // Compiled once; every client gets its own instance of it. The native
+    // provider needs no component: each client gets its own `OffloadHost`.
+    let provider_component = if config.uses_native_provider() {
+        println!("[Runner] Hosting the provider natively");
+        if let Some(bytes) = config.intern_capacity_bytes {
+            host_offload_provider::intern::set_capacity(bytes);
+        }
+        if let Some(slots) = config.compute_slots {
+            host_offload_provider::fair::set_compute_slots(slots);
+        }
+        None
+    } else {
+        println!("[Runner] Loading provider component from: {}", config.provider);
+        let (component, verification) = signature::load(&engine, &config.provider, cache_dir, &trusted_keys)
+            .context("Failed to load provider component")?;
+        signatures.insert(config.provider.clone(), verification);
+        Some(component)
+    };
+    let fallback_component = match &config.fallback_provider {
+        Some(path) => {
+            println!("[Runner] Loading fallback provider component from: {}", path);
+            let (component, verification) = signature::load(&engine, path, cache_dir, &trusted_keys)
+                .context("Failed to load fallback provider component")?;
+            signatures.insert(path.clone(), verification);
+            Some(component)
+        }
+        None => None,
+    };
+
+    // --- Load Client Components ---
+    let mut clients = Vec::with_capacity(config.clients.len());
+    for client in &config.clients {
+        println!("[Runner] Loading client '{}' from: {}", client.name, client.path);
+        let (component, verification) = signature::load(&engine, &client.path, cache_dir, &trusted_keys)
+            .with_context(|| format!("Failed to load client component '{}'", client.name))?;
+        signatures.insert(client.path.clone(), verification);
+        clients.push((client.clone(), component));
+    }
 
-    // --- Load Client Component ---
-    let client_component_path = "../matrix-client/target/wasm32-unknown-unknown/release/matrix_client.wasm";
-    println!("[Runner] Loading client component from: {}", client_component_path);
-    let client_component = Component::from_file(&engine, client_component_path)
-        .context("Failed to load client component")?;
+    // --- Check profiles and imports before linking anything ---
+    profiles::check(&config)?;
+    // Every unresolvable import at once, rather than instantiation failing on
+    // the first. Precompiled components can't be read and are left to that.
+    let provider_path = (!config.uses_native_provider()).then_some(config.provider.as_str());
+    for (client, _) in &clients {
+        if !client.path.ends_with(".cwasm") && !provider_path.is_some_and(|p| p.ends_with(".cwasm")) {
+            link::check(client, provider_path)?;
+        } else {
+            link::trace(&client.name, "precompiled component; imports resolve at instantiation");
+        }
+    }
 
+    // --- Run every client concurrently, each with its own store ---
+    let reports = if use_async {
+        async_run::run_clients(&engine, provider_component.as_ref(), fallback_component.as_ref(), &plugins, &clients)?
+    } else {
+        run_clients(&engine, provider_component.as_ref(), fallback_component.as_ref(), &plugins, &clients)
+    };
+
+    let failures = reports.iter().filter(|r| !r.ok).count();
+    let worst = reports.iter().filter_map(|r| r.failure).max();
+    if let Some(out) = json_out.as_mut() {
+        let run = RunReport {
+            provider: config.provider.clone(),
+            signatures,
+            total_ms: report::millis(started.elapsed()),
+            failures,
+            clients: reports,
+        };
+        report::write_json(out, &run)?;
+    }
+    if let Some(kind) = worst {
+        return Err(Failure::new(kind, anyhow::anyhow!("{} of {} clients failed", failures, clients.len())));
+    }
+    Ok(())
+}
+
+// Runs every client on a thread of its own.
+fn run_clients(
+    engine: &Engine,
+    provider_component: Option<&Component>,
+    fallback_component: Option<&Component>,
+    plugins: &Plugins,
+    clients: &[(ClientConfig, Component)],
+) -> Vec<ClientReport> {
+    std::thread::scope(|scope| {
+        let workers: Vec<_> = clients
+            .iter()
+            .map(|(client, component)| {
+                let worker = scope.spawn(move || {
+                    let mut report = ClientReport::new(&client.name);
+                    let result = run_client(engine, provider_component, fallback_component, plugins, client, component, &mut report);
+                    (report, result)
+                });
+                (&client.name, worker)
+            })
+            .collect();
+
+        workers
+            .into_iter()
+            .map(|(name, worker)| client_report(name, worker.join().ok()))
+            .collect()
+    })
+}
+
+// Engine for running clients. `runner precompile` output is only loadable by
+// an engine configured identically, so precompile with `--async` for `--async` runs.
+fn new_engine(async_support: bool) -> Result<Engine> {
+    let mut wasm_config = Config::new();
+    wasm_config.wasm_component_model(true);
+    wasm_config.async_support(async_support);
+    wasm_config.epoch_interruption(true); // For `shutdown`
+    Engine::new(&wasm_config)
+}
+
+// Removes `name <value>` or `name=value` from `args`, returning the value.
+fn take_option(args: &mut Vec<String>, name: &str) -> Result<Option<String>> {
+    let prefix = format!("{}=", name);
+    if let Some(i) = args.iter().position(|arg| arg.starts_with(&prefix)) {
+        return Ok(Some(args.remove(i)[prefix.len()..].to_string()));
+    }
+    match args.iter().position(|arg| arg == name) {
+        Some(i) if i + 1 < args.len() => {
+            args.remove(i);
+            Ok(Some(args.remove(i)))
+        }
+        Some(_) => anyhow::bail!("{} needs a value", name),
+        None => Ok(None),
+    }
+}
+
+// `take_option` for a numeric value.
+fn take_number<N: std::str::FromStr>(args: &mut Vec<String>, name: &str) -> Result<Option<N>>
+where
+    N::Err: std::error::Error + Send + Sync + 'static,
+{
+    take_option(args, name)?
+        .map(|value| value.parse().with_context(|| format!("{} takes a number, not '{}'", name, value)))
+        .transpose()
+}
+
+// Removes `flag` from `args`, wherever it appears. True if it was there.
+fn take_flag(args: &mut Vec<String>, flag: &str) -> bool {
+    let before = args.len();
+    args.retain(|arg| arg != flag);
+    args.len() != before
+}
+
+// The provider a client is linked against.
+enum Provider {
+    Component(provider_bindings::Provider),
+    Native(OffloadHost),
+}
+
+impl Provider {
+    fn open_session(&self, store: &mut Store<ClientState>, client: &ClientConfig) -> Result<u32> {
+        let opened = match self {
+            Provider::Component(provider) => {
+                let limits = provider_bindings::exports::wasi_custom::host_offload::session_admin::SessionLimits {
+                    max_ops_per_sec: client.max_ops_per_sec,
+                    max_bytes_per_sec: client.max_bytes_per_sec,
+                    max_op_millis: client.max_op_millis,
+                    cpu_weight: client.cpu_weight,
+                };
+                let admin = provider.wasi_custom_host_offload_session_admin();
+                let opened = admin.call_open_session(&mut *store, limits)?;
+                if let (Ok(_), Some(level)) = (&opened, client.provider_log_level) {
+                    admin.call_set_log_level(&mut *store, component_log_level(level))?;
+                }
+                opened
+            }
+            Provider::Native(host) => host.open_guarded_session(native_limits(client)).map(|guard| {
+                if let Some(level) = client.provider_log_level {
+                    host.lock().set_log_level(native_log_level(level));
+                }
+                let id = guard.id();
+                store.data_mut().session = Some(guard);
+                id
+            }),
+        };
+        opened.map_err(|e| anyhow::anyhow!("Failed to open provider session: {}", e))
+    }
+
+    fn close_session(&self, store: &mut Store<ClientState>, session: u32) -> Result<Result<u32, String>> {
+        match self {
+            Provider::Component(provider) => provider.wasi_custom_host_offload_session_admin().call_close_session(store, session),
+            Provider::Native(host) => Ok(match store.data_mut().session.take() {
+                Some(guard) => guard.close(),
+                None => host.lock().close_session(session),
+            }),
+        }
+    }
+}
+
+// Runs one client against a provider instance of its own. Nothing is shared
+// with other clients except the compiled components and the engine.
+fn run_client(
+    engine: &Engine,
+    provider_component: Option<&Component>,
+    fallback_component: Option<&Component>,
+    plugins: &Plugins,
+    client: &ClientConfig,
+    client_component: &Component,
+    report: &mut ClientReport,
+) -> Result<(), Failure> {
+    let name = client.name.as_str();
+    let (native, provider_component) = choose_provider(client, provider_component, fallback_component, report)?;
+    link::trace(name, match (&native, report.fallback) {
+        (Some(_), _) => "provider: native host, linked into the runner",
+        (None, true) => "provider: fallback component, instantiated per client",
+        (None, false) => "provider: component, instantiated per client",
+    });
+    let mut store = Store::new(engine, ClientState::new(native.clone()));
+    store.limiter(|state| &mut state.limits);
+    store.data_mut().capture_stdio(client.stdio, name, &client.stdio_dir)?;
+    shutdown::arm(&mut store);
+    // Held until the client is done; its provider forgets their ops after.
+    let _plugins = match &native {
+        Some(host) => plugins.instantiate(host, name).map_err(|e| Failure::new(FailureKind::Provider, e))?,
+        None => Vec::new(),
+    };
 
     // --- Link Components ---
     // The client component imports "host-allocator".
     // The provider component exports "host-allocator".
     // We need to tell the linker for the client how to satisfy this import.
 
-    let mut linker = Linker::new(&engine);
+    let mut linker = Linker::new(engine);
+    // WASI p2 for the client. Also serves the `wasi:io` streams handed out by
+    // `buffer-streams`.
+    wasmtime_wasi::preview2::command::sync::add_to_linker(&mut linker)?;
+    link::trace(name, "wasi:cli, wasi:io, wasi:clocks, wasi:filesystem, wasi:random, wasi:sockets -> wasmtime-wasi");
 
-    let provider_instance_pre: InstancePre<()> = linker.instantiate_pre(&provider_component)
-        .context("Failed to pre-instantiate provider component")?;
-    
-    client::add_to_linker_imports(&mut linker, |_, name: &str| {
-         match name {
-            "host-allocator" => Ok(provider_instance_pre.clone()), // Provide the pre-instantiated provider?
-            _ => anyhow::bail!("Unknown import: {}", name),
+    let provider = match provider_component {
+        Some(provider_component) => {
+            provider_bindings::wasi_custom::host_offload::host_offload_logging::add_to_linker(&mut linker, |state: &mut ClientState| state)?;
+            link::trace(name, "pre-instantiating the provider component against the WASI and logging linker");
+            let provider_instance_pre: InstancePre<ClientState> = linker.instantiate_pre(provider_component)
+                .context("Failed to pre-instantiate provider component")?;
+            let (provider, provider_instance) = provider_bindings::Provider::instantiate_pre(&mut store, &provider_instance_pre)
+                .context("Failed to instantiate provider component")
+                .map_err(|e| Failure::new(FailureKind::Provider, e))?;
+            link::trace(name, "client import host-allocator -> provider instance; nothing else is linked from it");
+            client::add_to_linker_imports(&mut linker, |_, import: &str| {
+                 match import {
+                    "host-allocator" => Ok(provider_instance), // The provider instance owning the session
+                    _ => anyhow::bail!("Unknown import: {} (a provider component only serves host-allocator)", import),
+                }
+            })?;
+            Provider::Component(provider)
+        }
+        None => {
+            // Same interface, implemented by the host itself. Only this path can
+            // offer `buffer-streams`, since the streams are host resources.
+            if link::imports_v0_1(client)? {
+                state::add_v0_1_offload_to_linker(&mut linker)?;
+                link::trace(name, "host-allocator@0.1.0 -> native host, through its 0.1 adapter");
+            } else {
+                state::add_offload_to_linker(&mut linker)?;
+            }
+            buffer_streams::add_to_linker(&mut linker, |state: &mut ClientState| state)?;
+            handle_persistence::add_to_linker(&mut linker, |state: &mut ClientState| state)?;
+            link::trace(name, "host-allocator, buffer-streams, random, handle-persistence -> native host");
+            if client.keyvalue {
+                store.data_mut().keyvalue = native.clone().map(KeyValueStore::new);
+                store::add_to_linker(&mut linker, |state: &mut ClientState| state)?;
+                link::trace(name, "wasi:keyvalue/store -> native host buffers");
+            }
+            if let Some(dir) = &client.tokenizer_dir {
+                store.data_mut().tokenizers = native.as_ref().map(|host| host.tokenizers(dir));
+                tokenizer::add_to_linker(&mut linker, |state: &mut ClientState| state.tokenizers())?;
+                link::trace(name, format_args!("tokenizer -> tokenizers in {}", dir));
+            }
+            Provider::Native(native.unwrap())
         }
-    })?;
+    };
 
-    println!("[Runner] Instantiating client component and linking with provider...");
-    // Instantiate the client, which will in turn fully instantiate the provider
-    // because `provider_instance_pre` is now linked.
-    let (client_instance, _) = client::Client::instantiate_pre(&mut store, &client_component, &linker)
+    // Every handle the client creates from here on belongs to this session and
+    // is freed when it is closed, even if the client traps.
+    let session = provider.open_session(&mut store, client)
+        .map_err(|e| Failure::new(FailureKind::Provider, e))?;
+    println!("[Runner:{}] Opened provider session {}", name, session);
+    report.session = Some(session);
+
+    println!("[Runner:{}] Instantiating client component and linking with provider...", name);
+    let started = Instant::now();
+    let client_instance = linker.instantiate(&mut store, client_component)
          .context("Failed to instantiate client component with provider")?;
+    report.instantiate_ms = Some(report::millis(started.elapsed()));
+    link::trace(name, format_args!("client instantiated in {:.1} ms", report.instantiate_ms.unwrap()));
+    let sink = match &provider {
+        Provider::Native(host) => EventSink::subscribe(&mut store, &client_instance, host),
+        Provider::Component(_) => None,
+    };
+    // Every example client exports the same shape as `client`'s `run-matrix-example`.
+    let export = client.export.as_str();
+    let run = client_instance.get_typed_func::<(), (Result<(), String>,)>(&mut store, export)
+        .with_context(|| format!("Client has no export '{}' of type func() -> result<_, string>", export))?;
+    link::trace(name, format_args!("calling export {}: func() -> result<_, string>", export));
 
 
     // --- Calling the Client's Exported Function ---
-    println!("[Runner] Calling 'run-matrix-example' in client Wasm...");
-    match client_instance.call_run_matrix_example(&mut store) {
-        Ok(Ok(_)) => println!("[Runner] 'run-matrix-example' executed successfully."),
-        Ok(Err(e)) => eprintln!("[Runner] 'run-matrix-example' in client returned an error: {}", e),
-        Err(e) => eprintln!("[Runner] Trap during 'run-matrix-example' in client: {}", e),
+    println!("[Runner:{}] Calling '{}' in client Wasm...", name, export);
+    let started = Instant::now();
+    let called = run.call(&mut store, ()).and_then(|(result,)| {
+        run.post_return(&mut store)?;
+        Ok(result)
+    });
+    let outcome = call_outcome(called, name, export);
+    // Only after a successful run: a failed client has nothing left to react with.
+    let outcome = match (outcome, &sink, &provider) {
+        (Ok(()), Some(sink), Provider::Native(host)) => sink.deliver(&mut store, host, name).map(|delivered| {
+            if delivered > 0 {
+                println!("[Runner:{}] Delivered {} provider events", name, delivered);
+            }
+        }),
+        (outcome, _, _) => outcome,
+    };
+    report.call_ms = Some(report::millis(started.elapsed()));
+    if let Provider::Native(host) = &provider {
+        report.host_calls = Some(store.data().metrics.host_calls);
+        report::record_usage(report, name, host, session);
+    }
+    report.peak_memory_bytes = Some(store.data().limits.peak_memory_bytes as u64);
+    if let (Some(path), Provider::Native(host)) = (&client.analysis_report, &provider) {
+        report::write_analysis(path, name, host);
     }
 
-    Ok(())
+    // Runs even after a shutdown trap, so the provider still frees the
+    // client's buffers.
+    shutdown::disarm(&mut store);
+    let closed = provider.close_session(&mut store, session);
+    finish_session(closed, outcome, report, name, session)
+}
+
+fn native_limits(client: &ClientConfig) -> host_offload_provider::session_admin::SessionLimits {
+    host_offload_provider::session_admin::SessionLimits {
+        max_ops_per_sec: client.max_ops_per_sec,
+        max_bytes_per_sec: client.max_bytes_per_sec,
+        max_op_millis: client.max_op_millis,
+        cpu_weight: client.cpu_weight,
+    }
+}
+
+fn component_log_level(level: ProviderLogLevel) -> provider_bindings::exports::wasi_custom::host_offload::session_admin::LogLevel {
+    use provider_bindings::exports::wasi_custom::host_offload::session_admin::LogLevel;
+    match level {
+        ProviderLogLevel::Off => LogLevel::Off,
+        ProviderLogLevel::Error => LogLevel::Error,
+        ProviderLogLevel::Warn => LogLevel::Warn,
+        ProviderLogLevel::Info => LogLevel::Info,
+        ProviderLogLevel::Debug => LogLevel::Debug,
+        ProviderLogLevel::Trace => LogLevel::Trace,
+    }
+}
+
+fn native_log_level(level: ProviderLogLevel) -> host_offload_provider::session_admin::LogLevel {
+    use host_offload_provider::session_admin::LogLevel;
+    match level {
+        ProviderLogLevel::Off => LogLevel::Off,
+        ProviderLogLevel::Error => LogLevel::Error,
+        ProviderLogLevel::Warn => LogLevel::Warn,
+        ProviderLogLevel::Info => LogLevel::Info,
+        ProviderLogLevel::Debug => LogLevel::Debug,
+        ProviderLogLevel::Trace => LogLevel::Trace,
+    }
+}
+
+// The client's native host, or None and the component to run it on instead:
+// the configured provider component, or the fallback one when the native
+// host can't be set up for this client.
+fn choose_provider<'a>(
+    client: &ClientConfig,
+    provider_component: Option<&'a Component>,
+    fallback_component: Option<&'a Component>,
+    report: &mut ClientReport,
+) -> Result<(Option<OffloadHost>, Option<&'a Component>), Failure> {
+    if provider_component.is_some() {
+        return Ok((None, provider_component));
+    }
+    match (native_host(client), fallback_component) {
+        (Ok(host), _) => Ok((Some(host), None)),
+        // The provider component can't keep an audit log or enforce a policy.
+        (Err(e), Some(fallback)) if client.audit_log.is_none() && client.policy.is_none() => {
+            // Only `host-allocator` comes from a component; native-only imports
+            // (keyvalue, tokenizer, buffer-streams) stay unresolved.
+            eprintln!(
+                "[Runner:{}] Native provider unavailable ({:#}); falling back to the provider component",
+                client.name, e
+            );
+            report.fallback = true;
+            Ok((None, Some(fallback)))
+        }
+        (Err(e), _) => Err(Failure::new(FailureKind::Provider, e)),
+    }
+}
+
+// The client's own provider, for `provider = "native"`, with its per-client knobs applied.
+fn native_host(client: &ClientConfig) -> Result<OffloadHost> {
+    let mut host = OffloadHost::new();
+    if let Some(path) = &client.audit_log {
+        let log = AuditLog::open(path).with_context(|| format!("Failed to open audit log {}", path))?;
+        host.set_audit_log(log, &client.name);
+    }
+    if let Some(policy) = &client.policy {
+        host.set_policy(policy::resolve(policy)?);
+    }
+    let mut state = host.lock();
+    if let Some(max) = client.max_allocation_bytes {
+        state.set_max_allocation(max);
+    }
+    state.set_memory_budget(client.memory_budget_bytes);
+    if client.numa {
+        state.set_numa_backend(Some(NumaConfig { threads_per_node: client.numa_threads_per_node, min_flops: None }));
+    }
+    if let Some(gpu) = &client.gpu {
+        let backend = open_gpu_backend(gpu).map_err(|e| anyhow::anyhow!("Client '{}': {}", client.name, e))?;
+        state.set_gpu_backend(Some(backend));
+    }
+    if client.split_matmul {
+        state.set_split_matmul(Some(SplitConfig::default()));
+    }
+    if client.backend.is_some() {
+        state.set_auto_backend(true);
+    }
+    if client.analysis_report.is_some() {
+        state.enable_analysis();
+    }
+    drop(state);
+    Ok(host)
+}
+
+fn call_outcome(called: Result<Result<(), String>>, name: &str, export: &str) -> Result<(), Failure> {
+    match called {
+        Ok(Ok(())) => {
+            println!("[Runner:{}] '{}' executed successfully.", name, export);
+            Ok(())
+        }
+        Ok(Err(e)) => Err(Failure::new(
+            FailureKind::Client,
+            anyhow::anyhow!("'{}' in client returned an error: {}", export, e),
+        )),
+        Err(e) => Err(Failure::new(FailureKind::Trap, e.context(format!("Trap during '{}' in client", export)))),
+    }
+}
+
+// Combines the client's outcome with the result of closing its session. A
+// session that fails to close is only reported as the failure when the client
+// itself succeeded.
+fn finish_session(
+    closed: Result<Result<u32, String>>,
+    outcome: Result<(), Failure>,
+    report: &mut ClientReport,
+    name: &str,
+    session: u32,
+) -> Result<(), Failure> {
+    let closed = match closed {
+        Ok(closed) => closed,
+        Err(e) => {
+            outcome?;
+            return Err(Failure::new(FailureKind::Provider, e.context("Trap while closing provider session")));
+        }
+    };
+    report.reclaimed_handles = closed.as_ref().ok().copied();
+    match closed {
+        Ok(0) => println!("[Runner:{}] Closed provider session {}", name, session),
+        Ok(freed) => println!("[Runner:{}] Closed provider session {}, reclaimed {} handles the client left behind", name, session, freed),
+        Err(e) => {
+            eprintln!("[Runner:{}] Failed to close provider session {}: {}", name, session, e);
+            outcome?;
+            return Err(Failure::new(FailureKind::Provider, anyhow::anyhow!("Failed to close provider session {}: {}", session, e)));
+        }
+    }
+    outcome
+}
+
+// The report for a finished client; `None` if its worker panicked.
+fn client_report(name: &str, finished: Option<(ClientReport, Result<(), Failure>)>) -> ClientReport {
+    let (mut report, result) = finished.unwrap_or_else(|| {
+        // Host code panicked; with a native provider that's the provider.
+        let panicked = Failure::new(FailureKind::Provider, anyhow::anyhow!("Worker panicked"));
+        (ClientReport::new(name), Err(panicked))
+    });
+    match result {
+        Ok(()) => report.ok = true,
+        Err(failure) => {
+            eprintln!("[Runner:{}] Failed ({:?}): {:#}", name, failure.kind, failure.error);
+            report.failure = Some(failure.kind);
+            report.error = Some(format!("{:#}", failure.error));
+        }
+    }
+    report
 }
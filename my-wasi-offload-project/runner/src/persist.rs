@@ -0,0 +1,47 @@
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::BuildHasher;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+
+use host_offload_provider::persist::DetachedBuffer;
+use host_offload_provider::wasi_custom::host_offload::handle_persistence;
+use host_offload_provider::wasi_custom::host_offload::host_allocator::{Handle, HostError};
+
+use crate::state::{ClientState, OffloadView};
+
+// Buffers exported by one instance and not yet imported by another, by
+// token. Shared by every client of the runner process.
+static VAULT: OnceLock<Mutex<HashMap<String, Vec<DetachedBuffer>>>> = OnceLock::new();
+static NEXT_TOKEN: AtomicU64 = AtomicU64::new(0);
+
+fn vault() -> &'static Mutex<HashMap<String, Vec<DetachedBuffer>>> {
+    VAULT.get_or_init(Default::default)
+}
+
+// Unique per export and hard to guess, so a client can't claim buffers whose
+// token it wasn't handed.
+fn new_token() -> String {
+    let export = NEXT_TOKEN.fetch_add(1, Ordering::Relaxed);
+    let [a, b] = [RandomState::new(), RandomState::new()].map(|keys| keys.hash_one(export));
+    format!("{:016x}{:016x}", a, b)
+}
+
+impl handle_persistence::Host for ClientState {
+    fn export_handles(&mut self, handles: Vec<Handle>) -> wasmtime::Result<Result<String, HostError>> {
+        let buffers = match self.offload().lock().detach(&handles) {
+            Ok(buffers) => buffers,
+            Err(e) => return Ok(Err(e)),
+        };
+        let token = new_token();
+        vault().lock().unwrap().insert(token.clone(), buffers);
+        Ok(Ok(token))
+    }
+
+    fn import_handles(&mut self, token: String) -> wasmtime::Result<Result<Vec<Handle>, HostError>> {
+        let Some(buffers) = vault().lock().unwrap().remove(&token) else {
+            return Ok(Err(HostError::InvalidArguments("Unknown or already imported handle token".to_string())));
+        };
+        Ok(Ok(self.offload().lock().adopt(buffers)))
+    }
+}
@@ -0,0 +1,142 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use anyhow::{Context, Result};
+use host_offload_provider::extensions::Extension;
+use host_offload_provider::native::OffloadHost;
+use host_offload_provider::wasi_custom::host_offload::host_allocator::{
+    self, ArgSchema, ArgShape, ElementType, Handle, OpSchema,
+};
+use wasmtime::component::{Component, Linker};
+use wasmtime::{Engine, Store};
+
+use crate::config::PluginConfig;
+use crate::shutdown;
+use crate::signature::{self, TrustedKeys, Verification};
+use crate::state::{self, ClientState};
+use crate::stdio::GuestStdio;
+
+mod bindings {
+    wasmtime::component::bindgen!({
+        world: "compute-plugin",
+        path: "wit/compute-plugin.wit",
+        additional_packages: [
+            { package = "wasi-custom:host-offload@0.2.0", path = "../wit" },
+        ],
+    });
+}
+
+use bindings::exports::wasi_custom::host_offload::compute_plugin as guest;
+
+// The configured plugins, compiled once for every client.
+//
+// Plugins get a synchronous engine of their own: they run from inside a
+// client's `call-op`, i.e. in a host function, where an async store
+// (`runner --async`) couldn't be entered.
+#[derive(Clone)]
+pub struct Plugins {
+    engine: Engine,
+    components: Vec<(String, Component)>,
+}
+
+impl Plugins {
+    // Plugins run in every client's provider, so with `trusted_keys` they
+    // must be signed like the client and provider components; what was
+    // checked is returned by path.
+    pub fn load(
+        configs: &[PluginConfig],
+        cache_dir: Option<&Path>,
+        keys: &TrustedKeys,
+    ) -> Result<(Self, BTreeMap<String, Verification>)> {
+        let engine = crate::new_engine(false)?;
+        let mut components = Vec::with_capacity(configs.len());
+        let mut signatures = BTreeMap::new();
+        for plugin in configs {
+            println!("[Runner] Loading plugin '{}' from: {}", plugin.name, plugin.path);
+            let (component, verification) = signature::load(&engine, &plugin.path, cache_dir, keys)
+                .with_context(|| format!("Failed to load plugin component '{}'", plugin.name))?;
+            signatures.insert(plugin.path.clone(), verification);
+            components.push((plugin.name.clone(), component));
+        }
+        Ok((Plugins { engine, components }, signatures))
+    }
+
+    pub fn engine(&self) -> &Engine {
+        &self.engine
+    }
+
+    // Instantiates every plugin against a client's native provider and
+    // registers its ops there. The host only keeps weak references, so the
+    // ops stay callable for as long as the caller holds on to the result.
+    pub fn instantiate(&self, host: &OffloadHost, client: &str) -> Result<Vec<Arc<Plugin>>> {
+        let mut linker = Linker::new(&self.engine);
+        wasmtime_wasi::preview2::command::sync::add_to_linker(&mut linker)?;
+        state::add_offload_to_linker(&mut linker)?;
+
+        let mut plugins = Vec::with_capacity(self.components.len());
+        for (name, component) in &self.components {
+            let mut store = Store::new(&self.engine, ClientState::new(Some(host.clone())));
+            store.limiter(|state| &mut state.limits);
+            store.data_mut().capture_stdio(GuestStdio::Prefix, &format!("{}:{}", client, name), "")?;
+            shutdown::arm(&mut store);
+            let (bindings, _) = bindings::ComputePlugin::instantiate(&mut store, component, &linker)
+                .with_context(|| format!("Failed to instantiate plugin '{}'", name))?;
+            let schemas: Vec<OpSchema> = bindings.wasi_custom_host_offload_compute_plugin().call_list_ops(&mut store)
+                .with_context(|| format!("Plugin '{}' trapped listing its ops", name))?
+                .into_iter()
+                .map(op_schema)
+                .collect();
+            let ops: Vec<&str> = schemas.iter().map(|schema| schema.name.as_str()).collect();
+            println!("[Runner:{}] Plugin '{}' provides {:?}", client, name, ops);
+            let plugin = Arc::new(Plugin { name: name.clone(), instance: Mutex::new((store, bindings)) });
+            host.lock().register_extension(schemas, &(plugin.clone() as Arc<dyn Extension>))
+                .map_err(|e| anyhow::anyhow!("Plugin '{}': {}", name, e))?;
+            plugins.push(plugin);
+        }
+        Ok(plugins)
+    }
+}
+
+// One plugin instance, in a store of its own that shares the client's provider.
+pub struct Plugin {
+    name: String,
+    instance: Mutex<(Store<ClientState>, bindings::ComputePlugin)>,
+}
+
+impl Extension for Plugin {
+    fn execute(&self, op: &str, inputs: &[Handle]) -> Result<Vec<Handle>, String> {
+        let mut instance = self.instance.lock().unwrap();
+        let (store, bindings) = &mut *instance;
+        bindings
+            .wasi_custom_host_offload_compute_plugin()
+            .call_execute(store, op, inputs)
+            .unwrap_or_else(|trap| Err(format!("Plugin '{}' trapped in '{}': {:#}", self.name, op, trap)))
+    }
+}
+
+// A plugin's schema in the provider's types, which mirror the plugin's.
+fn op_schema(schema: guest::OpSchema) -> OpSchema {
+    let arg = |arg: guest::ArgSchema| ArgSchema {
+        name: arg.name,
+        element_type: arg.element_type.map(|element_type| match element_type {
+            guest::ElementType::U8 => ElementType::U8,
+            guest::ElementType::S8 => ElementType::S8,
+            guest::ElementType::S32 => ElementType::S32,
+            guest::ElementType::F16 => ElementType::F16,
+            guest::ElementType::F32 => ElementType::F32,
+            guest::ElementType::F64 => ElementType::F64,
+        }),
+        shape: match arg.shape {
+            guest::ArgShape::Any => ArgShape::Any,
+            guest::ArgShape::Vector => ArgShape::Vector,
+            guest::ArgShape::Matrix => ArgShape::Matrix,
+        },
+    };
+    OpSchema {
+        name: schema.name,
+        description: schema.description,
+        inputs: schema.inputs.into_iter().map(arg).collect(),
+        outputs: schema.outputs.into_iter().map(arg).collect(),
+    }
+}
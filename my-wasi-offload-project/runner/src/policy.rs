@@ -0,0 +1,46 @@
+use anyhow::Result;
+
+use host_offload_provider::policy::Policy;
+
+use crate::config::PolicyConfig;
+use crate::profiles::Profiles;
+
+const HOST_ALLOCATOR: &str = "host-allocator";
+// The other interfaces the native provider holds to a policy. Their functions
+// are denied as `interface.function`, or all at once by the interface name.
+const GUARDED: [&str; 2] = ["random", "tokenizer"];
+
+// A client's `[clients.policy]` as the native provider enforces it. A `deny`
+// entry is a profile, denying each of its `host-allocator`, `random` and
+// `tokenizer` functions, a single `host-allocator` function, or one of
+// `random` and `tokenizer` (whole, or `tokenizer.load-tokenizer`).
+pub fn resolve(config: &PolicyConfig) -> Result<Policy> {
+    let profiles = Profiles::builtin();
+    let mut policy = Policy::default();
+    for entry in &config.deny {
+        if let Ok((_, profile)) = profiles.get(entry) {
+            for function in profile.functions_of(HOST_ALLOCATOR) {
+                policy.deny(function, entry);
+            }
+            for listed in profile.functions.iter().filter(|listed| guarded(listed)) {
+                policy.deny(listed.as_str(), entry);
+            }
+        } else if profiles.owner(HOST_ALLOCATOR, entry).is_some() || guarded(entry) {
+            policy.deny(entry, entry);
+        } else {
+            anyhow::bail!(
+                "Policy denies '{}', which is neither a profile nor a {}, random or tokenizer function",
+                entry,
+                HOST_ALLOCATOR
+            );
+        }
+    }
+    policy.max_matmul_dim = config.max_matmul_dim;
+    policy.deny_files = !config.files;
+    Ok(policy)
+}
+
+fn guarded(entry: &str) -> bool {
+    let interface = entry.split_once('.').map_or(entry, |(interface, _)| interface);
+    GUARDED.contains(&interface)
+}
@@ -0,0 +1,129 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use crate::config::RunnerConfig;
+use crate::wit_tool;
+
+pub const PROFILES_FILE: &str = "profiles.toml";
+
+// The table the runner was built with, so checking a config needs no WIT
+// directory at run time. `runner wit profiles` checks a table against the WIT.
+const BUILTIN: &str = include_str!("../../wit/profiles.toml");
+
+#[derive(Debug, Deserialize)]
+pub struct Profile {
+    pub version: String,
+    // `interface.function`, or a bare interface name for all of it.
+    pub functions: Vec<String>,
+}
+
+impl Profile {
+    // The functions of `iface` listed one by one.
+    pub fn functions_of<'a>(&'a self, iface: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.functions.iter().filter_map(move |entry| entry.strip_prefix(iface)?.strip_prefix('.'))
+    }
+
+    fn contains(&self, iface: &str, func: &str) -> bool {
+        self.functions.iter().any(|entry| match entry.split_once('.') {
+            Some((i, f)) => i == iface && f == func,
+            None => entry == iface,
+        })
+    }
+}
+
+// Profiles of the host-offload package by name (see `wit/profiles.toml`).
+pub struct Profiles(BTreeMap<String, Profile>);
+
+impl Profiles {
+    pub fn parse(text: &str) -> Result<Self> {
+        Ok(Profiles(toml::from_str(text)?))
+    }
+
+    pub fn builtin() -> Self {
+        Self::parse(BUILTIN).expect("wit/profiles.toml is valid")
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Profile)> {
+        self.0.iter().map(|(name, profile)| (name.as_str(), profile))
+    }
+
+    // Resolves a config's "name" or "name@version" to a profile.
+    pub fn get(&self, declared: &str) -> Result<(&str, &Profile)> {
+        let (name, version) = match declared.split_once('@') {
+            Some((name, version)) => (name, Some(version)),
+            None => (declared, None),
+        };
+        let Some((name, profile)) = self.0.get_key_value(name) else {
+            let known: Vec<&str> = self.0.keys().map(String::as_str).collect();
+            anyhow::bail!("Unknown profile '{}' (known: {})", name, known.join(", "));
+        };
+        if version.is_some_and(|v| v != profile.version) {
+            anyhow::bail!("Profile {} is at {} in this runner, not {}", name, profile.version, version.unwrap());
+        }
+        Ok((name.as_str(), profile))
+    }
+
+    pub fn owner(&self, iface: &str, func: &str) -> Option<&str> {
+        self.iter().find(|(_, profile)| profile.contains(iface, func)).map(|(name, _)| name)
+    }
+}
+
+// For each client that declares `profiles`, before anything is linked: it
+// must import nothing outside them, and the provider component it runs on
+// must export all of them. The native provider implements every profile; a
+// fallback provider that can't stand in for a client is only reported.
+// Precompiled components can't be inspected and are taken on trust.
+pub fn check(config: &RunnerConfig) -> Result<()> {
+    let profiles = Profiles::builtin();
+    let mut providers: Vec<(&str, bool)> = config.fallback_provider.iter().map(|p| (p.as_str(), true)).collect();
+    if !config.uses_native_provider() {
+        providers.push((&config.provider, false));
+    }
+    let mut exports: BTreeMap<&str, BTreeMap<String, BTreeSet<String>>> = BTreeMap::new();
+    for client in config.clients.iter().filter(|c| !c.profiles.is_empty()) {
+        let declared = client.profiles.iter().map(|p| profiles.get(p)).collect::<Result<Vec<_>>>()
+            .with_context(|| format!("Client '{}'", client.name))?;
+        if !client.path.ends_with(".cwasm") {
+            let surface = wit_tool::surface(&client.path)?;
+            let outside: Vec<String> = surface.imports.iter()
+                .flat_map(|(iface, funcs)| funcs.iter().map(move |func| (iface, func)))
+                .filter(|(iface, func)| !declared.iter().any(|(_, profile)| profile.contains(iface, func)))
+                .map(|(iface, func)| match profiles.owner(iface, func) {
+                    Some(owner) => format!("{}.{} ({})", iface, func, owner),
+                    None => format!("{}.{}", iface, func),
+                })
+                .collect();
+            if !outside.is_empty() {
+                anyhow::bail!("Client '{}' imports outside its profiles: {}", client.name, outside.join(", "));
+            }
+        }
+        for &(provider, fallback) in providers.iter().filter(|(p, _)| !p.ends_with(".cwasm")) {
+            if !exports.contains_key(provider) {
+                exports.insert(provider, wit_tool::surface(provider)?.exports);
+            }
+            let exported = &exports[provider];
+            let missing: Vec<String> = declared.iter()
+                .flat_map(|(name, profile)| profile.functions.iter().map(move |entry| (name, entry)))
+                .filter(|(_, entry)| match entry.split_once('.') {
+                    Some((iface, func)) => !exported.get(iface).is_some_and(|funcs| funcs.contains(func)),
+                    None => !exported.contains_key(entry.as_str()),
+                })
+                .map(|(name, entry)| format!("{} ({})", entry, name))
+                .collect();
+            match (missing.is_empty(), fallback) {
+                (true, _) => {}
+                (false, true) => eprintln!(
+                    "[Runner] Fallback provider {} can't stand in for client '{}', which needs {}",
+                    provider, client.name, missing.join(", ")
+                ),
+                (false, false) => anyhow::bail!(
+                    "Provider {} lacks profiles client '{}' declares: {}",
+                    provider, client.name, missing.join(", ")
+                ),
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,210 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Write;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use host_offload_provider::analysis::Suggestion;
+use host_offload_provider::native::OffloadHost;
+use serde::Serialize;
+
+use crate::signature::Verification;
+
+// `--output`: human-readable logs (the default), or a single JSON document on
+// stdout with every log line moved to stderr.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+impl OutputFormat {
+    pub fn parse(value: &str) -> Result<Self> {
+        match value {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            _ => anyhow::bail!("Unknown output format '{}' (expected text or json)", value),
+        }
+    }
+}
+
+// Why a run failed, from most to least specific to one client. Each class has
+// its own process exit status so scripts can tell them apart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FailureKind {
+    // The client's export returned `Err`.
+    Client,
+    // The provider failed to instantiate or to manage the client's session.
+    Provider,
+    // Guest code trapped.
+    Trap,
+    // Bad arguments or config, unloadable components, unresolved imports.
+    Setup,
+}
+
+impl FailureKind {
+    pub fn exit_code(self) -> i32 {
+        match self {
+            FailureKind::Client => 1,
+            FailureKind::Provider => 2,
+            FailureKind::Trap => 3,
+            FailureKind::Setup => 4,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Failure {
+    pub kind: FailureKind,
+    pub error: anyhow::Error,
+}
+
+impl Failure {
+    pub fn new(kind: FailureKind, error: anyhow::Error) -> Self {
+        Failure { kind, error }
+    }
+}
+
+// Anything not classified where it happened is a setup problem.
+impl From<anyhow::Error> for Failure {
+    fn from(error: anyhow::Error) -> Self {
+        Failure::new(FailureKind::Setup, error)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct RunReport {
+    pub provider: String,
+    // Each client and provider component loaded, by path.
+    pub signatures: BTreeMap<String, Verification>,
+    pub total_ms: f64,
+    pub failures: usize,
+    pub clients: Vec<ClientReport>,
+}
+
+// What happened to one client. Fields stay `None` for stages it never reached.
+#[derive(Debug, Default, Serialize)]
+pub struct ClientReport {
+    pub name: String,
+    pub ok: bool,
+    pub failure: Option<FailureKind>,
+    pub error: Option<String>,
+    pub instantiate_ms: Option<f64>,
+    pub call_ms: Option<f64>,
+    pub session: Option<u32>,
+    // Ran on the fallback provider component because its native provider
+    // couldn't be set up.
+    pub fallback: bool,
+    // Calls the client made into the native provider; None for components.
+    pub host_calls: Option<u64>,
+    // Largest linear memory in the client's store: the client's own, or a
+    // provider component's.
+    pub peak_memory_bytes: Option<u64>,
+    // Handles the provider freed on the client's behalf when its session closed.
+    pub reclaimed_handles: Option<u32>,
+    // The session's compute, as `session-usage` measured it; None for components.
+    pub cpu_seconds: Option<f64>,
+    pub compute_calls: Option<u64>,
+    pub queued_seconds: Option<f64>,
+}
+
+impl ClientReport {
+    pub fn new(name: &str) -> Self {
+        ClientReport { name: name.to_string(), ..Default::default() }
+    }
+}
+
+pub fn millis(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000.0
+}
+
+// Keeps the real stdout for the report and points fd 1 at stderr, so that
+// everything else printing to stdout (the runner, a native provider, guests
+// with inherited stdio) lands on stderr instead.
+#[cfg(unix)]
+pub fn take_stdout() -> Result<File> {
+    use std::os::unix::io::FromRawFd;
+
+    std::io::stdout().flush()?;
+    // SAFETY: plain fd duplication; `saved` is owned by the returned File.
+    unsafe {
+        let saved = libc::dup(libc::STDOUT_FILENO);
+        if saved < 0 || libc::dup2(libc::STDERR_FILENO, libc::STDOUT_FILENO) < 0 {
+            return Err(std::io::Error::last_os_error()).context("Failed to redirect stdout");
+        }
+        Ok(File::from_raw_fd(saved))
+    }
+}
+
+#[cfg(not(unix))]
+pub fn take_stdout() -> Result<File> {
+    anyhow::bail!("--output json is only supported on Unix")
+}
+
+pub fn write_json(out: &mut File, report: &RunReport) -> Result<()> {
+    serde_json::to_writer_pretty(&mut *out, report)?;
+    writeln!(out)?;
+    Ok(())
+}
+
+// `analysis_report`: one client's suggestions, for tooling.
+#[derive(Serialize)]
+struct Analysis<'a> {
+    client: &'a str,
+    calls: usize,
+    suggestions: Vec<SuggestionJson>,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+enum SuggestionJson {
+    FuseAfterMatmul { ops: Vec<String>, results: Vec<u32>, message: String },
+    UnchangedReadBack { handles: Vec<u32>, bytes: u64, message: String },
+}
+
+impl From<Suggestion> for SuggestionJson {
+    fn from(suggestion: Suggestion) -> Self {
+        let message = suggestion.to_string();
+        match suggestion {
+            Suggestion::FuseAfterMatmul { ops, results } => SuggestionJson::FuseAfterMatmul { ops, results, message },
+            Suggestion::UnchangedReadBack { handles, bytes } => SuggestionJson::UnchangedReadBack { handles, bytes, message },
+        }
+    }
+}
+
+// Written after the client's call, whatever its outcome; a failed write is
+// only logged, as the run itself is unaffected.
+// Logs and reports what the client's session computed, before it closes.
+pub fn record_usage(report: &mut ClientReport, name: &str, host: &OffloadHost, session: u32) {
+    let usage = match host.lock().session_usage(session) {
+        Ok(usage) => usage,
+        Err(e) => return eprintln!("[Runner:{}] No session usage: {}", name, e),
+    };
+    println!(
+        "[Runner:{}] Used {:.3} CPU-seconds in {} compute calls, {:.3} s of them queued",
+        name, usage.cpu_seconds, usage.compute_calls, usage.queued_seconds
+    );
+    report.cpu_seconds = Some(usage.cpu_seconds);
+    report.compute_calls = Some(usage.compute_calls);
+    report.queued_seconds = Some(usage.queued_seconds);
+}
+
+pub fn write_analysis(path: &str, name: &str, host: &OffloadHost) {
+    let Some(report) = host.lock().analysis_report() else {
+        return;
+    };
+    let analysis = Analysis {
+        client: name,
+        calls: report.calls,
+        suggestions: report.suggestions.into_iter().map(SuggestionJson::from).collect(),
+    };
+    let written = File::create(path)
+        .map_err(anyhow::Error::from)
+        .and_then(|mut out| Ok(serde_json::to_writer_pretty(&mut out, &analysis)?))
+        .with_context(|| format!("Failed to write analysis report {}", path));
+    match written {
+        Ok(()) => println!("[Runner:{}] Wrote {} suggestions to {}", name, analysis.suggestions.len(), path),
+        Err(e) => eprintln!("[Runner:{}] {:#}", name, e),
+    }
+}
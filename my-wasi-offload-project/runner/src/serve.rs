@@ -0,0 +1,186 @@
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+
+use anyhow::{Context, Result};
+use host_offload_provider::audit::AuditLog;
+use host_offload_provider::native::OffloadHost;
+use host_offload_provider::wasi_custom::host_offload::handle_persistence;
+use http_body_util::BodyExt;
+use hyper::body::Incoming;
+use tokio::sync::{mpsc, Mutex};
+use wasmtime::component::{InstancePre, Linker};
+use wasmtime::{Config, Engine, InstanceAllocationStrategy, PoolingAllocationConfig, Store};
+use wasmtime_wasi_http::bindings::Proxy;
+use wasmtime_wasi_http::body::HyperOutgoingBody;
+use wasmtime_wasi_http::io::TokioIo;
+use wasmtime_wasi_http::{hyper_response_error, WasiHttpView};
+
+use crate::config::ServeConfig;
+use crate::signature::TrustedKeys;
+use crate::state::{self, ClientState};
+
+pub const DEFAULT_ADDR: &str = "127.0.0.1:8080";
+
+// `[serve] audit_log`, and the number of the next instance to record in it.
+static AUDIT: OnceLock<Arc<AuditLog>> = OnceLock::new();
+static NEXT_INSTANCE: AtomicU64 = AtomicU64::new(0);
+
+// A store with the handler already instantiated in it, waiting for a request.
+struct Warm {
+    store: Store<ClientState>,
+    proxy: Proxy,
+}
+
+// Serves a component exporting `wasi:http/incoming-handler` over HTTP/1.1.
+//
+// Every request gets a fresh store and a fresh native provider, so handles
+// never leak between requests even if the handler traps. To keep that off the
+// request path, a background task instantiates up to `warm_instances` of them
+// ahead of time out of wasmtime's pooling allocator; a request only pays for
+// instantiation itself when the pool has run dry.
+pub fn serve(
+    component_path: &str,
+    addr: &str,
+    cache_dir: Option<&Path>,
+    config: &ServeConfig,
+    trusted_keys: &TrustedKeys,
+) -> Result<()> {
+    let addr: SocketAddr = addr.parse().with_context(|| format!("Invalid listen address {}", addr))?;
+    if let Some(path) = &config.audit_log {
+        let log = AuditLog::open(path).with_context(|| format!("Failed to open audit log {}", path))?;
+        let _ = AUDIT.set(log);
+    }
+
+    let mut pooling = PoolingAllocationConfig::default();
+    pooling.total_component_instances(config.max_instances);
+    // A component instance is the handler plus whatever its adapter pulls in.
+    pooling.total_core_instances(config.max_instances * 4);
+    pooling.total_memories(config.max_instances * 2);
+    pooling.total_tables(config.max_instances * 2);
+
+    let mut wasm_config = Config::new();
+    wasm_config.wasm_component_model(true);
+    wasm_config.async_support(true);
+    wasm_config.allocation_strategy(InstanceAllocationStrategy::Pooling(pooling));
+    wasm_config.epoch_interruption(true);
+    let engine = Engine::new(&wasm_config)?;
+    crate::shutdown::install(vec![engine.clone()])?;
+
+    println!("[Runner] Loading HTTP component from: {}", component_path);
+    let (component, _) = crate::signature::load(&engine, component_path, cache_dir, trusted_keys)
+        .context("Failed to load HTTP component")?;
+
+    let mut linker = Linker::new(&engine);
+    wasmtime_wasi::preview2::command::add_to_linker(&mut linker)?;
+    wasmtime_wasi_http::proxy::add_only_http_to_linker(&mut linker)?;
+    state::add_offload_to_linker(&mut linker)?;
+    // Lets a handler pass what it uploaded on to the instance serving the next request.
+    handle_persistence::add_to_linker(&mut linker, |state: &mut ClientState| state)?;
+    let pre = Arc::new(linker.instantiate_pre(&component)
+        .context("Failed to pre-instantiate HTTP component")?);
+
+    let runtime = tokio::runtime::Builder::new_multi_thread().enable_io().build()?;
+    runtime.block_on(async move {
+        let pool = warm_pool(engine.clone(), pre.clone(), config.warm_instances);
+        let listener = tokio::net::TcpListener::bind(addr).await
+            .with_context(|| format!("Failed to bind {}", addr))?;
+        println!("[Runner] Serving HTTP on http://{} ({} warm instances)", addr, config.warm_instances);
+        loop {
+            // On shutdown stop accepting; the epoch bump has already trapped
+            // whatever handlers were still running.
+            let (stream, peer) = tokio::select! {
+                accepted = listener.accept() => accepted?,
+                _ = crate::shutdown::wait() => {
+                    println!("[Runner] Shutting down HTTP server");
+                    return Ok(());
+                }
+            };
+            let engine = engine.clone();
+            let pre = pre.clone();
+            let pool = pool.clone();
+            tokio::task::spawn(async move {
+                let service = hyper::service::service_fn(move |req| handle(engine.clone(), pre.clone(), pool.clone(), req));
+                if let Err(e) = hyper::server::conn::http1::Builder::new()
+                    .keep_alive(true)
+                    .serve_connection(TokioIo::new(stream), service)
+                    .await
+                {
+                    eprintln!("[Runner] Connection from {} failed: {:?}", peer, e);
+                }
+            });
+        }
+    })
+}
+
+async fn instantiate(engine: &Engine, pre: &InstancePre<ClientState>) -> Result<Warm> {
+    let mut host = OffloadHost::new();
+    if let Some(log) = AUDIT.get() {
+        host.set_audit_log(log.clone(), format!("http-{}", NEXT_INSTANCE.fetch_add(1, Ordering::Relaxed)));
+    }
+    let mut store = Store::new(engine, ClientState::new(Some(host)));
+    crate::shutdown::arm(&mut store);
+    let (proxy, _) = Proxy::instantiate_pre(&mut store, pre).await?;
+    Ok(Warm { store, proxy })
+}
+
+type Pool = Arc<Mutex<mpsc::Receiver<Warm>>>;
+
+// Keeps up to `size` instances ready. The filler blocks once the pool is
+// full and resumes as requests take instances out.
+fn warm_pool(engine: Engine, pre: Arc<InstancePre<ClientState>>, size: usize) -> Pool {
+    let (sender, receiver) = mpsc::channel(size.max(1));
+    if size > 0 {
+        tokio::task::spawn(async move {
+            loop {
+                match instantiate(&engine, &pre).await {
+                    Ok(warm) => {
+                        if sender.send(warm).await.is_err() {
+                            break;
+                        }
+                    }
+                    Err(e) => {
+                        eprintln!("[Runner] Failed to pre-instantiate HTTP component: {:#}", e);
+                        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+                    }
+                }
+            }
+        });
+    }
+    Arc::new(Mutex::new(receiver))
+}
+
+async fn handle(
+    engine: Engine,
+    pre: Arc<InstancePre<ClientState>>,
+    pool: Pool,
+    req: hyper::Request<Incoming>,
+) -> Result<hyper::Response<HyperOutgoingBody>> {
+    let warm = pool.try_lock().ok().and_then(|mut pool| pool.try_recv().ok());
+    let Warm { mut store, proxy } = match warm {
+        Some(warm) => warm,
+        None => instantiate(&engine, &pre).await?,
+    };
+    let (sender, receiver) = tokio::sync::oneshot::channel();
+    let req = store.data_mut().new_incoming_request(req.map(|body| body.map_err(hyper_response_error).boxed()))?;
+    let out = store.data_mut().new_response_outparam(sender)?;
+
+    let task = tokio::task::spawn(async move {
+        proxy.wasi_http_incoming_handler().call_handle(&mut store, req, out).await
+    });
+
+    match receiver.await {
+        Ok(Ok(response)) => Ok(response),
+        Ok(Err(e)) => Err(e.into()),
+        // The handler returned or trapped without setting a response.
+        Err(_) => {
+            let e = match task.await {
+                Ok(Ok(())) => anyhow::anyhow!("handler returned without a response"),
+                Ok(Err(e)) => e,
+                Err(e) => e.into(),
+            };
+            Err(e.context("HTTP component did not respond"))
+        }
+    }
+}
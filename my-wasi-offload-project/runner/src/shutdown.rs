@@ -0,0 +1,70 @@
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use anyhow::Result;
+use wasmtime::{Engine, Store};
+
+// Conventional exit status for a process stopped by SIGINT/SIGTERM.
+pub const EXIT_INTERRUPTED: i32 = 130;
+
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+// Installs the SIGINT/SIGTERM handler for the process.
+//
+// The first signal bumps the epoch of every engine given here, which traps
+// guest code at its next loop header or function entry, so in-flight calls
+// unwind through the normal error paths: sessions get closed and provider
+// memory freed. A second signal exits on the spot.
+//
+// The epoch only interrupts wasm. A native provider call already running
+// (a multiply, a decomposition, a GPU kernel, a `wait-job` finishing its job
+// inline) runs to completion before its guest traps, and calls waiting for a
+// compute slot behind it still get their turn. So the first signal can take
+// as long as those calls do. `max_op_millis` bounds the compute calls, which
+// fail with `timeout` past it, but not a `wait-job`. The second signal is the
+// way out when that's too long: it skips all cleanup, so spill directories
+// (`host-offload-spill-<pid>-*` in the temp directory) and shared-memory
+// objects (`/offload-<pid>-*`) may be left behind.
+pub fn install(engines: Vec<Engine>) -> Result<()> {
+    ctrlc::set_handler(move || {
+        if REQUESTED.swap(true, Ordering::SeqCst) {
+            eprintln!("[Runner] Second interrupt, exiting immediately");
+            std::process::exit(EXIT_INTERRUPTED);
+        }
+        eprintln!("[Runner] Interrupted, stopping guests (interrupt again to force)");
+        for engine in &engines {
+            engine.increment_epoch();
+        }
+    })?;
+    Ok(())
+}
+
+pub fn requested() -> bool {
+    REQUESTED.load(Ordering::SeqCst)
+}
+
+// Resolves once a shutdown has been requested.
+pub async fn wait() {
+    while !requested() {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+}
+
+// Guests run until the next epoch bump, i.e. until a shutdown signal.
+pub fn arm<T>(store: &mut Store<T>) {
+    store.epoch_deadline_trap();
+    store.set_epoch_deadline(1);
+}
+
+// Lets the host call back into a store after a shutdown trap, e.g. to close
+// the provider session.
+pub fn disarm<T>(store: &mut Store<T>) {
+    store.set_epoch_deadline(u64::MAX);
+}
+
+// Called on the way out, so nothing printed before the signal is lost.
+pub fn flush() {
+    let _ = std::io::stdout().flush();
+    let _ = std::io::stderr().flush();
+}
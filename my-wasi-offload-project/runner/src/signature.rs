@@ -0,0 +1,80 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use ed25519_dalek::{Signature, VerifyingKey, SIGNATURE_LENGTH};
+use serde::Serialize;
+use wasmtime::component::Component;
+use wasmtime::Engine;
+
+use crate::cache;
+
+// `trusted_keys`: ed25519 public keys, hex-encoded. When any are configured,
+// the client, provider and plugin components (and `runner serve`'s handler) only
+// load if `<path>.sig` holds a raw 64-byte signature over the file's exact
+// bytes (`.cwasm` files included) by one of them, e.g. from
+// `openssl pkeyutl -sign -rawin`.
+pub struct TrustedKeys(Vec<VerifyingKey>);
+
+// What checking a component's signature found, for the startup log and `--output json`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "status", rename_all = "kebab-case")]
+pub enum Verification {
+    // No `trusted_keys`, so nothing was checked.
+    Unchecked,
+    // Signed by the key with this hex fingerprint (its first 8 bytes).
+    Verified { key: String },
+}
+
+impl TrustedKeys {
+    pub fn parse(keys: &[String]) -> Result<Self> {
+        let keys = keys
+            .iter()
+            .map(|key| {
+                let bytes: [u8; 32] = hex::decode(key.trim())
+                    .ok()
+                    .and_then(|bytes| bytes.try_into().ok())
+                    .with_context(|| format!("Trusted key '{}' is not 64 hex digits", key))?;
+                VerifyingKey::from_bytes(&bytes).with_context(|| format!("Trusted key '{}' is not an ed25519 key", key))
+            })
+            .collect::<Result<_>>()?;
+        Ok(TrustedKeys(keys))
+    }
+
+    fn verify(&self, path: &str, bytes: &[u8]) -> Result<Verification> {
+        if self.0.is_empty() {
+            return Ok(Verification::Unchecked);
+        }
+        let sig_path = format!("{}.sig", path);
+        let sig = std::fs::read(&sig_path)
+            .with_context(|| format!("{} is unsigned: failed to read {}", path, sig_path))?;
+        let sig: [u8; SIGNATURE_LENGTH] = sig
+            .try_into()
+            .map_err(|_| anyhow::anyhow!("{} is not a raw {}-byte signature", sig_path, SIGNATURE_LENGTH))?;
+        let sig = Signature::from_bytes(&sig);
+        match self.0.iter().find(|key| key.verify_strict(bytes, &sig).is_ok()) {
+            Some(key) => Ok(Verification::Verified { key: hex::encode(&key.as_bytes()[..8]) }),
+            None => anyhow::bail!("{} is not signed by any trusted key ({})", path, sig_path),
+        }
+    }
+}
+
+// Loads the component at `path` once it passes `keys`. The bytes checked are
+// the bytes compiled: nothing signs the `--precompile` cache, so a verified
+// component is never read from it or written to it.
+pub fn load(
+    engine: &Engine,
+    path: &str,
+    cache: Option<&Path>,
+    keys: &TrustedKeys,
+) -> Result<(Component, Verification)> {
+    let bytes = std::fs::read(path).with_context(|| format!("Failed to read {}", path))?;
+    let verification = keys.verify(path, &bytes)?;
+    if let Verification::Verified { key } = &verification {
+        println!("[Runner] Signature of {} verified (key {})", path, key);
+    }
+    let cache = match verification {
+        Verification::Unchecked => cache,
+        Verification::Verified { .. } => None,
+    };
+    Ok((cache::load_bytes(engine, path, &bytes, cache)?, verification))
+}
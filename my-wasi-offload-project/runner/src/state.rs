@@ -0,0 +1,204 @@
+use host_offload_provider::compat;
+use host_offload_provider::keyvalue::{Bucket, KeyValueStore};
+use host_offload_provider::native::{OffloadHost, SessionGuard};
+use host_offload_provider::tokenize::Tokenizers;
+use host_offload_provider::wasi::keyvalue::store::{self, Error, KeyResponse};
+use host_offload_provider::wasi_custom::host_offload::host_allocator::{self, Handle, HostError};
+use host_offload_provider::wasi_custom::host_offload::{buffer_streams, random};
+use wasmtime::component::{Linker, Resource, ResourceTable};
+use wasmtime_wasi::preview2::{InputStream, OutputStream, WasiCtx, WasiCtxBuilder, WasiView};
+use wasmtime_wasi_http::{WasiHttpCtx, WasiHttpView};
+
+use crate::limits::Limiter;
+use crate::provider_bindings::wasi_custom::host_offload::host_offload_logging;
+use crate::stdio::{self, GuestStdio};
+
+// Accessors each subsystem's `add_to_linker` goes through, so a subsystem
+// can be linked into any store whose data provides its part (WASI has
+// `WasiView` for the same purpose).
+pub trait OffloadView {
+    fn offload(&mut self) -> &mut OffloadHost;
+}
+
+pub trait MetricsView {
+    fn metrics(&mut self) -> &mut Metrics;
+}
+
+// What the runner measures per client, whichever subsystem does the work.
+#[derive(Debug, Default, Clone)]
+pub struct Metrics {
+    // Calls into the native provider (`host-allocator`, `random`, streams).
+    pub host_calls: u64,
+}
+
+// Links the native provider's guest-facing interfaces for stores of `T`.
+pub fn add_offload_to_linker<T: OffloadView + Send + 'static>(linker: &mut Linker<T>) -> wasmtime::Result<()> {
+    host_allocator::add_to_linker(linker, |state: &mut T| state.offload())?;
+    random::add_to_linker(linker, |state: &mut T| state.offload())?;
+    Ok(())
+}
+
+// The same for clients built against 0.1.0, which only had `host-allocator`
+// (see `host_offload_provider::compat`).
+pub fn add_v0_1_offload_to_linker<T: OffloadView + Send + 'static>(linker: &mut Linker<T>) -> wasmtime::Result<()> {
+    compat::v0_1::add_to_linker(linker, |state: &mut T| state.offload())
+}
+
+// Per-client store data: WASI p2 state for the client and, when the runner
+// hosts the provider natively, that client's provider.
+pub struct ClientState {
+    table: ResourceTable,
+    wasi: WasiCtx,
+    http: WasiHttpCtx,
+    pub offload: Option<OffloadHost>,
+    // Guest memory and table limits; installed with `Store::limiter`.
+    pub limits: Limiter,
+    pub metrics: Metrics,
+    // The native provider's session for this client. Held by the store so
+    // that dropping the store early (an aborted task, a panic) still closes it.
+    pub session: Option<SessionGuard>,
+    // Set for clients with `keyvalue = true`; see `store::Host` below.
+    pub keyvalue: Option<KeyValueStore>,
+    // Set for clients with a `tokenizer_dir`.
+    pub tokenizers: Option<Tokenizers>,
+}
+
+impl ClientState {
+    pub fn new(offload: Option<OffloadHost>) -> Self {
+        ClientState {
+            table: ResourceTable::new(),
+            wasi: WasiCtxBuilder::new().inherit_stdio().build(),
+            http: WasiHttpCtx {},
+            offload,
+            limits: Limiter::configured(),
+            metrics: Metrics::default(),
+            session: None,
+            keyvalue: None,
+            tokenizers: None,
+        }
+    }
+
+    // Replaces the inherited stdio the store started with; see `stdio`.
+    pub fn capture_stdio(&mut self, stdio: GuestStdio, name: &str, dir: &str) -> anyhow::Result<()> {
+        let mut builder = WasiCtxBuilder::new();
+        stdio::configure(&mut builder, stdio, name, dir)?;
+        self.wasi = builder.build();
+        Ok(())
+    }
+
+    pub fn tokenizers(&mut self) -> &mut Tokenizers {
+        self.tokenizers.as_mut().expect("tokenizer is only linked for clients with a tokenizer_dir")
+    }
+}
+
+impl OffloadView for ClientState {
+    fn offload(&mut self) -> &mut OffloadHost {
+        self.metrics.host_calls += 1;
+        self.offload.as_mut().expect("host-allocator is only linked for the native provider")
+    }
+}
+
+impl MetricsView for ClientState {
+    fn metrics(&mut self) -> &mut Metrics {
+        &mut self.metrics
+    }
+}
+
+impl WasiView for ClientState {
+    fn table(&mut self) -> &mut ResourceTable {
+        &mut self.table
+    }
+
+    fn ctx(&mut self) -> &mut WasiCtx {
+        &mut self.wasi
+    }
+}
+
+// Only used when serving an HTTP component (see `serve`).
+impl WasiHttpView for ClientState {
+    fn ctx(&mut self) -> &mut WasiHttpCtx {
+        &mut self.http
+    }
+
+    fn table(&mut self) -> &mut ResourceTable {
+        &mut self.table
+    }
+}
+
+// The streams live in the client's resource table, next to its other WASI
+// streams, so the standard `wasi:io` host functions drive them.
+impl buffer_streams::Host for ClientState {
+    fn buffer_write_stream(&mut self, h: Handle, offset: u64) -> wasmtime::Result<Result<Resource<OutputStream>, HostError>> {
+        match self.offload().write_stream(h, offset) {
+            Ok(stream) => Ok(Ok(self.table.push(stream)?)),
+            Err(e) => Ok(Err(e)),
+        }
+    }
+
+    fn buffer_read_stream(&mut self, h: Handle, offset: u64) -> wasmtime::Result<Result<Resource<InputStream>, HostError>> {
+        match self.offload().read_stream(h, offset) {
+            Ok(stream) => Ok(Ok(self.table.push(stream)?)),
+            Err(e) => Ok(Err(e)),
+        }
+    }
+}
+
+// Diagnostics from a provider component, as `log` records under the
+// provider's own targets so `RUNNER_LOG` can filter them (see `main`).
+impl host_offload_logging::Host for ClientState {
+    fn log(&mut self, level: host_offload_logging::Level, target: String, message: String) -> wasmtime::Result<()> {
+        let level = match level {
+            host_offload_logging::Level::Trace => log::Level::Trace,
+            host_offload_logging::Level::Debug => log::Level::Debug,
+            host_offload_logging::Level::Info => log::Level::Info,
+            host_offload_logging::Level::Warn => log::Level::Warn,
+            host_offload_logging::Level::Error => log::Level::Error,
+        };
+        log::log!(target: &target, level, "{}", message);
+        Ok(())
+    }
+}
+
+// `wasi:keyvalue/store` over the client's native provider. Buckets are
+// resources in the client's table; their keys live in `KeyValueStore`.
+impl ClientState {
+    fn keyvalue(&self) -> &KeyValueStore {
+        self.keyvalue.as_ref().expect("wasi:keyvalue is only linked for clients with keyvalue = true")
+    }
+}
+
+impl store::Host for ClientState {
+    fn open(&mut self, identifier: String) -> wasmtime::Result<Result<Resource<Bucket>, Error>> {
+        match self.keyvalue().open(identifier) {
+            Ok(bucket) => Ok(Ok(self.table.push(bucket)?)),
+            Err(e) => Ok(Err(e)),
+        }
+    }
+}
+
+impl store::HostBucket for ClientState {
+    fn get(&mut self, bucket: Resource<Bucket>, key: String) -> wasmtime::Result<Result<Option<Vec<u8>>, Error>> {
+        Ok(self.keyvalue().get(self.table.get(&bucket)?, &key))
+    }
+
+    fn set(&mut self, bucket: Resource<Bucket>, key: String, value: Vec<u8>) -> wasmtime::Result<Result<(), Error>> {
+        Ok(self.keyvalue().set(self.table.get(&bucket)?, key, value))
+    }
+
+    fn delete(&mut self, bucket: Resource<Bucket>, key: String) -> wasmtime::Result<Result<(), Error>> {
+        Ok(self.keyvalue().delete(self.table.get(&bucket)?, &key))
+    }
+
+    fn exists(&mut self, bucket: Resource<Bucket>, key: String) -> wasmtime::Result<Result<bool, Error>> {
+        Ok(self.keyvalue().exists(self.table.get(&bucket)?, &key))
+    }
+
+    fn list_keys(&mut self, bucket: Resource<Bucket>, cursor: Option<u64>) -> wasmtime::Result<Result<KeyResponse, Error>> {
+        Ok(self.keyvalue().list_keys(self.table.get(&bucket)?, cursor))
+    }
+
+    fn drop(&mut self, bucket: Resource<Bucket>) -> wasmtime::Result<()> {
+        self.table.delete(bucket)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,123 @@
+use std::fs::File;
+use std::io::{self, Write};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use anyhow::Result;
+use serde::Deserialize;
+use tokio::io::AsyncWrite;
+use wasmtime_wasi::preview2::{AsyncStdoutStream, AsyncWriteStream, WasiCtxBuilder};
+
+// Bytes a guest can have in flight to one of its streams before its writes wait.
+const WRITE_BUDGET: usize = 64 * 1024;
+
+// Where a guest's stdout and stderr go (the `stdio` client key).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GuestStdio {
+    // Straight to the runner's own stdout and stderr.
+    Inherit,
+    // The runner's stdout and stderr, a line at a time, each line tagged
+    // `[name] ` so concurrent clients stay readable.
+    #[default]
+    Prefix,
+    // `<dir>/<name>.stdout` and `<dir>/<name>.stderr`, truncated per run.
+    Files,
+}
+
+enum Sink {
+    Stdout,
+    Stderr,
+    File(File),
+}
+
+// Line-buffered writer behind one guest stream.
+struct Lines {
+    prefix: String,
+    sink: Sink,
+    pending: Vec<u8>,
+}
+
+impl Lines {
+    fn write_line(&mut self, line: &[u8]) -> io::Result<()> {
+        match &mut self.sink {
+            Sink::Stdout => write_prefixed(&mut io::stdout().lock(), &self.prefix, line),
+            Sink::Stderr => write_prefixed(&mut io::stderr().lock(), &self.prefix, line),
+            Sink::File(file) => write_prefixed(file, &self.prefix, line),
+        }
+    }
+}
+
+fn write_prefixed(out: &mut impl Write, prefix: &str, line: &[u8]) -> io::Result<()> {
+    out.write_all(prefix.as_bytes())?;
+    out.write_all(line)?;
+    if !line.ends_with(b"\n") {
+        out.write_all(b"\n")?;
+    }
+    out.flush()
+}
+
+impl AsyncWrite for Lines {
+    fn poll_write(self: Pin<&mut Self>, _: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        this.pending.extend_from_slice(buf);
+        while let Some(end) = this.pending.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = this.pending.drain(..=end).collect();
+            this.write_line(&line)?;
+        }
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    // A partial line stays buffered until its newline or the stream closes.
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if !this.pending.is_empty() {
+            let line = std::mem::take(&mut this.pending);
+            this.write_line(&line)?;
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl Drop for Lines {
+    fn drop(&mut self) {
+        if !self.pending.is_empty() {
+            let line = std::mem::take(&mut self.pending);
+            let _ = self.write_line(&line);
+        }
+    }
+}
+
+fn stream(prefix: String, sink: Sink) -> AsyncStdoutStream {
+    AsyncStdoutStream::new(AsyncWriteStream::new(WRITE_BUDGET, Lines { prefix, sink, pending: Vec::new() }))
+}
+
+// Points the guest's stdout and stderr where `stdio` says; `name` tags or
+// names its output.
+pub fn configure(builder: &mut WasiCtxBuilder, stdio: GuestStdio, name: &str, dir: &str) -> Result<()> {
+    match stdio {
+        GuestStdio::Inherit => {
+            builder.inherit_stdio();
+        }
+        GuestStdio::Prefix => {
+            builder.inherit_stdin();
+            builder.stdout(stream(format!("[{}] ", name), Sink::Stdout));
+            builder.stderr(stream(format!("[{}] ", name), Sink::Stderr));
+        }
+        GuestStdio::Files => {
+            std::fs::create_dir_all(dir)?;
+            let open = |ext: &str| {
+                let path = format!("{}/{}.{}", dir, name, ext);
+                File::create(&path).map_err(|e| anyhow::anyhow!("Failed to create {}: {}", path, e))
+            };
+            builder.inherit_stdin();
+            builder.stdout(stream(String::new(), Sink::File(open("stdout")?)));
+            builder.stderr(stream(String::new(), Sink::File(open("stderr")?)));
+        }
+    }
+    Ok(())
+}
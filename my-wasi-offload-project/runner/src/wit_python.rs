@@ -0,0 +1,136 @@
+use std::fmt::Write;
+
+use anyhow::{Context, Result};
+use wit_parser::{Function, Resolve, Results, Type, TypeDefKind};
+
+use crate::wit_tool;
+
+// Interfaces the provider component exports, i.e. what a Python host can call.
+const INTERFACES: [&str; 2] = ["host-allocator", "session-admin"];
+
+// Hand-written part of the module; see `python-client/README.md`.
+const PRELUDE: &str = r#"import logging
+
+from wasmtime import Store
+
+from provider_bindings import Root
+from provider_bindings.imports import RootImports
+from provider_bindings.types import Err
+
+
+class HostError(Exception):
+    """A provider call returned `Err`. `error` is the WIT error value."""
+
+    def __init__(self, func, error):
+        super().__init__(f"{func} failed: {error}")
+        self.func = func
+        self.error = error
+
+
+class _Logging:
+    """`host-offload-logging`: provider diagnostics into the `logging` module, by target."""
+
+    _LEVELS = {"TRACE": 5, "DEBUG": logging.DEBUG, "INFO": logging.INFO, "WARN": logging.WARNING, "ERROR": logging.ERROR}
+
+    def log(self, level, target, message):
+        logging.getLogger(target).log(self._LEVELS[level.name], message)
+
+
+class _Interface:
+    def __init__(self, store, api):
+        self._store = store
+        self._api = api
+
+    def _unwrap(self, func, result):
+        if isinstance(result, Err):
+            raise HostError(func, result.value)
+        return result.value
+"#;
+
+// `runner wit python [wit-dir]`: prints `offload_api.py`, Python wrappers
+// around the wasmtime-py bindings of the provider component, one method per
+// function of the exported interfaces. Generated from the WIT rather than
+// written by hand so it can't drift from it.
+pub fn python(dir: &str) -> Result<()> {
+    let mut resolve = Resolve::new();
+    let (pkg, _) = resolve.push_dir(dir).with_context(|| format!("Failed to resolve WIT package in {}", dir))?;
+    let package = &resolve.packages[pkg];
+    let version = package.name.version.as_ref().map(|v| v.to_string()).unwrap_or_default();
+
+    let mut out = String::new();
+    writeln!(out, "# Generated by `runner wit python` from {}@{}. Do not edit;", wit_tool::PACKAGE, version)?;
+    writeln!(out, "# rerun python-client/generate.sh after changing the WIT.")?;
+    writeln!(out)?;
+    out.push_str(PRELUDE);
+    writeln!(out)?;
+    writeln!(out, "PACKAGE_VERSION = {:?}", version)?;
+
+    for name in INTERFACES {
+        let id = *package.interfaces.get(name).with_context(|| format!("{} has no interface {}", dir, name))?;
+        writeln!(out)?;
+        writeln!(out)?;
+        writeln!(out, "class {}(_Interface):", class_name(name))?;
+        for (func_name, func) in &resolve.interfaces[id].functions {
+            writeln!(out)?;
+            method(&mut out, &resolve, func_name, func)?;
+        }
+    }
+
+    writeln!(out)?;
+    writeln!(out)?;
+    writeln!(out, "def connect():")?;
+    writeln!(out, "    \"\"\"Instantiates the provider in a fresh store. Returns one wrapper per interface.\"\"\"")?;
+    writeln!(out, "    store = Store()")?;
+    writeln!(out, "    root = Root(store, RootImports(host_offload_logging=_Logging()))")?;
+    let wrappers: Vec<String> = INTERFACES
+        .iter()
+        .map(|name| format!("{}(store, root.{}())", class_name(name), snake_case(name)))
+        .collect();
+    writeln!(out, "    return {}", wrappers.join(", "))?;
+    print!("{}", out);
+    Ok(())
+}
+
+fn method(out: &mut String, resolve: &Resolve, name: &str, func: &Function) -> Result<()> {
+    let args: String = func.params.iter().map(|(param, _)| format!(", {}", identifier(param))).collect();
+    let call = format!("self._api.{}(self._store{})", identifier(name), args);
+    writeln!(out, "    def {}(self{}):", identifier(name), args)?;
+    writeln!(out, "        \"\"\"{}: {}\"\"\"", name, wit_tool::signature(resolve, func))?;
+    if returns_result(resolve, func) {
+        writeln!(out, "        return self._unwrap({:?}, {})", name, call)?;
+    } else {
+        writeln!(out, "        return {}", call)?;
+    }
+    Ok(())
+}
+
+fn returns_result(resolve: &Resolve, func: &Function) -> bool {
+    match &func.results {
+        Results::Anon(Type::Id(id)) => matches!(resolve.types[*id].kind, TypeDefKind::Result(_)),
+        _ => false,
+    }
+}
+
+fn snake_case(name: &str) -> String {
+    name.replace('-', "_")
+}
+
+// Snake case, clear of Python keywords the way wasmtime-py's bindgen does it.
+fn identifier(name: &str) -> String {
+    const KEYWORDS: [&str; 12] = ["and", "as", "from", "global", "import", "in", "is", "lambda", "not", "or", "pass", "with"];
+    let name = snake_case(name);
+    if KEYWORDS.contains(&name.as_str()) {
+        format!("{}_", name)
+    } else {
+        name
+    }
+}
+
+fn class_name(name: &str) -> String {
+    name.split('-')
+        .map(|word| {
+            let mut chars = word.chars();
+            chars.next().map(|c| c.to_ascii_uppercase().to_string() + chars.as_str()).unwrap_or_default()
+        })
+        .collect()
+}
@@ -0,0 +1,389 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::{Context, Result};
+use wit_parser::{Function, InterfaceId, Resolve, Results, Type, TypeDefKind, WorldId, WorldItem, WorldKey};
+
+// Package every check is about. Other packages (wasi:*) are ignored.
+pub const PACKAGE: &str = "wasi-custom:host-offload";
+pub const DEFAULT_WIT_DIR: &str = "../wit";
+
+// `runner wit ...`: inspect the host-offload WIT package, check built
+// components against it before they fail at instantiation, and generate the
+// Python client (see `wit_python`).
+pub fn run(args: &[String]) -> Result<()> {
+    match args.first().map(String::as_str) {
+        Some("show") => show(args.get(1).map(String::as_str).unwrap_or(DEFAULT_WIT_DIR)),
+        Some("check") => {
+            let wasm = args.get(1).context("Usage: runner wit check <component.wasm> [wit-dir]")?;
+            check(wasm, args.get(2).map(String::as_str).unwrap_or(DEFAULT_WIT_DIR))
+        }
+        Some("diff") => match (args.get(1), args.get(2)) {
+            (Some(old), Some(new)) => diff(old, new),
+            _ => anyhow::bail!("Usage: runner wit diff <old-wit-dir> <new-wit-dir>"),
+        },
+        Some("python") => crate::wit_python::python(args.get(1).map(String::as_str).unwrap_or(DEFAULT_WIT_DIR)),
+        Some("profiles") => profiles(args.get(1).map(String::as_str).unwrap_or(DEFAULT_WIT_DIR)),
+        _ => anyhow::bail!(
+            "Usage: runner wit <show [wit-dir] | check <component.wasm> [wit-dir] | diff <old> <new> | python [wit-dir] \
+             | profiles [wit-dir]>"
+        ),
+    }
+}
+
+// One interface of the package, flattened to comparable strings.
+struct InterfaceSummary {
+    version: String,
+    functions: BTreeMap<String, String>,
+    types: BTreeMap<String, String>,
+}
+
+fn load_dir(dir: &str) -> Result<(Resolve, BTreeMap<String, InterfaceSummary>)> {
+    let mut resolve = Resolve::new();
+    let (pkg, _) = resolve.push_dir(dir).with_context(|| format!("Failed to resolve WIT package in {}", dir))?;
+    let interfaces: Vec<InterfaceId> = resolve.packages[pkg].interfaces.values().copied().collect();
+    let summaries = interfaces.into_iter().filter_map(|id| summarize(&resolve, id)).collect();
+    Ok((resolve, summaries))
+}
+
+fn show(dir: &str) -> Result<()> {
+    let (resolve, interfaces) = load_dir(dir)?;
+    for (name, iface) in &interfaces {
+        println!("interface {}@{}", name, iface.version);
+        for (ty, def) in &iface.types {
+            println!("  type {} = {}", ty, def);
+        }
+        for (func, sig) in &iface.functions {
+            println!("  {}: {}", func, sig);
+        }
+    }
+    for (_, world) in resolve.worlds.iter() {
+        println!("world {}", world.name);
+        for (key, item) in &world.imports {
+            if let WorldItem::Interface(_) = item {
+                println!("  import {}", resolve.name_world_key(key));
+            }
+        }
+        for (key, item) in &world.exports {
+            if let WorldItem::Interface(_) = item {
+                println!("  export {}", resolve.name_world_key(key));
+            }
+        }
+    }
+    Ok(())
+}
+
+// Compares every host-offload interface a component imports or exports with
+// the package in `dir`. Exits with an error if anything would fail to link.
+fn check(wasm: &str, dir: &str) -> Result<()> {
+    let (_, expected) = load_dir(dir)?;
+    let (resolve, world) = decode_component(wasm)?;
+    let resolve = &resolve;
+    let world = &resolve.worlds[world];
+
+    let mut problems = 0;
+    let mut seen = 0;
+    let items = world.imports.values().map(|item| ("imports", item))
+        .chain(world.exports.values().map(|item| ("exports", item)));
+    for (direction, item) in items {
+        let WorldItem::Interface(id) = item else { continue };
+        let Some(actual) = summarize(resolve, *id) else { continue };
+        let name = resolve.interfaces[*id].name.clone().unwrap_or_default();
+        seen += 1;
+        println!("{} {} {}@{}", wasm, direction, name, actual.version);
+        let Some(local) = expected.get(&name) else {
+            println!("  error: {} has no interface named {}", dir, name);
+            problems += 1;
+            continue;
+        };
+        if local.version != actual.version {
+            println!("  error: version {} does not match {} in {}", actual.version, local.version, dir);
+            problems += 1;
+        }
+        for (func, sig) in &actual.functions {
+            match local.functions.get(func) {
+                None => {
+                    println!("  error: {} is not defined in {}", func, dir);
+                    problems += 1;
+                }
+                Some(local_sig) if local_sig != sig => {
+                    println!("  error: {} has signature\n      {}\n    but {} defines\n      {}", func, sig, dir, local_sig);
+                    problems += 1;
+                }
+                Some(_) => {}
+            }
+        }
+        for func in local.functions.keys().filter(|f| !actual.functions.contains_key(*f)) {
+            // Fine for imports (the guest just doesn't use it); a provider
+            // missing an export leaves clients that call it unlinkable.
+            let level = if direction == "exports" { "warning" } else { "note" };
+            println!("  {}: {} does not mention {}", level, wasm, func);
+        }
+    }
+
+    if seen == 0 {
+        println!("{} neither imports nor exports {}", wasm, PACKAGE);
+    }
+    if problems > 0 {
+        anyhow::bail!("{} incompatibilities between {} and {}", problems, wasm, dir);
+    }
+    println!("{} is compatible with {}", wasm, dir);
+    Ok(())
+}
+
+// A component's types and world, as `wit-component` recovers them.
+fn decode_component(wasm: &str) -> Result<(Resolve, WorldId)> {
+    let bytes = std::fs::read(wasm).with_context(|| format!("Failed to read {}", wasm))?;
+    match wit_component::decode(&bytes).with_context(|| format!("{} is not a component", wasm))? {
+        wit_component::DecodedWasm::Component(resolve, world) => Ok((resolve, world)),
+        wit_component::DecodedWasm::WitPackage(..) => anyhow::bail!("{} is a WIT package, not a component", wasm),
+    }
+}
+
+// The host-offload functions a component imports and exports, by interface.
+#[derive(Debug, Default)]
+pub struct Surface {
+    pub imports: BTreeMap<String, BTreeSet<String>>,
+    pub exports: BTreeMap<String, BTreeSet<String>>,
+}
+
+pub fn surface(wasm: &str) -> Result<Surface> {
+    let (resolve, world) = decode_component(wasm)?;
+    let world = &resolve.worlds[world];
+    let mut surface = Surface::default();
+    for (items, side) in [(&world.imports, &mut surface.imports), (&world.exports, &mut surface.exports)] {
+        for item in items.values() {
+            let WorldItem::Interface(id) = item else { continue };
+            let Some(summary) = summarize(&resolve, *id) else { continue };
+            let name = resolve.interfaces[*id].name.clone().unwrap_or_default();
+            side.entry(name).or_default().extend(summary.functions.into_keys());
+        }
+    }
+    Ok(surface)
+}
+
+// One interface in a component's world, from any package.
+#[derive(Debug, Clone)]
+pub struct WorldInterface {
+    // As the world names it: `host-allocator`, `wasi:io/streams@0.2.0`, ...
+    pub name: String,
+    // Package and name of the interface itself, e.g. `wasi:io` and `streams`.
+    pub package: String,
+    pub interface: String,
+    pub version: Option<String>,
+    pub functions: Vec<String>,
+    // `name: func(...) -> ...` for each of `functions`, in the same order.
+    pub signatures: Vec<String>,
+}
+
+// Every interface a component imports and exports, in world order.
+pub fn world_interfaces(wasm: &str) -> Result<(Vec<WorldInterface>, Vec<WorldInterface>)> {
+    let (resolve, world) = decode_component(wasm)?;
+    let world = &resolve.worlds[world];
+    Ok((interfaces_of(&resolve, world.imports.iter()), interfaces_of(&resolve, world.exports.iter())))
+}
+
+fn interfaces_of<'a>(resolve: &Resolve, items: impl Iterator<Item = (&'a WorldKey, &'a WorldItem)>) -> Vec<WorldInterface> {
+    items
+        .filter_map(|(key, item)| match item {
+            WorldItem::Interface(id) => Some((key, &resolve.interfaces[*id])),
+            _ => None,
+        })
+        .map(|(key, iface)| {
+            let package = iface.package.map(|pkg| &resolve.packages[pkg].name);
+            WorldInterface {
+                name: resolve.name_world_key(key),
+                package: package.map(|p| format!("{}:{}", p.namespace, p.name)).unwrap_or_default(),
+                interface: iface.name.clone().unwrap_or_default(),
+                version: package.and_then(|p| p.version.as_ref()).map(|v| v.to_string()),
+                functions: iface.functions.keys().cloned().collect(),
+                signatures: iface.functions.iter().map(|(name, f)| format!("{}: {}", name, signature(resolve, f))).collect(),
+            }
+        })
+        .collect()
+}
+
+// Checks `profiles.toml` in `dir` against the package there: every entry
+// names something that exists, and every function of an interface it
+// mentions is in exactly one profile. Then prints the profiles.
+fn profiles(dir: &str) -> Result<()> {
+    let path = format!("{}/{}", dir, crate::profiles::PROFILES_FILE);
+    let text = std::fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path))?;
+    let profiles = crate::profiles::Profiles::parse(&text).with_context(|| format!("Failed to parse {}", path))?;
+    let (_, interfaces) = load_dir(dir)?;
+
+    let mut problems = 0;
+    let mut owners: BTreeMap<(String, String), Vec<&str>> = BTreeMap::new();
+    for (name, profile) in profiles.iter() {
+        for entry in &profile.functions {
+            let (iface, func) = entry.split_once('.').unwrap_or((entry.as_str(), ""));
+            let Some(summary) = interfaces.get(iface) else {
+                println!("error: profile {} lists {}, but {} has no interface {}", name, entry, dir, iface);
+                problems += 1;
+                continue;
+            };
+            let funcs: Vec<&String> = match func {
+                "" => summary.functions.keys().collect(),
+                func => match summary.functions.get_key_value(func) {
+                    Some((func, _)) => vec![func],
+                    None => {
+                        println!("error: profile {} lists {}, which is not defined in {}", name, entry, dir);
+                        problems += 1;
+                        continue;
+                    }
+                },
+            };
+            for func in funcs {
+                owners.entry((iface.to_string(), func.clone())).or_default().push(name);
+            }
+        }
+    }
+    let mentioned: BTreeSet<&str> = owners.keys().map(|(iface, _)| iface.as_str()).collect();
+    for iface in mentioned {
+        for func in interfaces[iface].functions.keys() {
+            match owners.get(&(iface.to_string(), func.clone())).map(Vec::as_slice) {
+                None | Some([]) => {
+                    println!("error: {}.{} is in no profile", iface, func);
+                    problems += 1;
+                }
+                Some([_]) => {}
+                Some(names) => {
+                    println!("error: {}.{} is in several profiles: {}", iface, func, names.join(", "));
+                    problems += 1;
+                }
+            }
+        }
+    }
+    if problems > 0 {
+        anyhow::bail!("{} problems with {}", problems, path);
+    }
+    for (name, profile) in profiles.iter() {
+        let count = owners.values().filter(|names| names.contains(&name)).count();
+        println!("profile {}@{}: {} functions", name, profile.version, count);
+    }
+    Ok(())
+}
+
+fn diff(old_dir: &str, new_dir: &str) -> Result<()> {
+    let (_, old) = load_dir(old_dir)?;
+    let (_, new) = load_dir(new_dir)?;
+    let names: std::collections::BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+    for name in names {
+        match (old.get(name), new.get(name)) {
+            (Some(_), None) => println!("- interface {}", name),
+            (None, Some(_)) => println!("+ interface {}", name),
+            (Some(a), Some(b)) => {
+                if a.version != b.version {
+                    println!("~ interface {}: {} -> {}", name, a.version, b.version);
+                }
+                diff_items(name, "type", &a.types, &b.types);
+                diff_items(name, "func", &a.functions, &b.functions);
+            }
+            (None, None) => unreachable!(),
+        }
+    }
+    Ok(())
+}
+
+fn diff_items(iface: &str, kind: &str, old: &BTreeMap<String, String>, new: &BTreeMap<String, String>) {
+    for (name, def) in old {
+        match new.get(name) {
+            None => println!("- {} {}.{}: {}", kind, iface, name, def),
+            Some(new_def) if new_def != def => println!("~ {} {}.{}: {} -> {}", kind, iface, name, def, new_def),
+            Some(_) => {}
+        }
+    }
+    for (name, def) in new.iter().filter(|(name, _)| !old.contains_key(*name)) {
+        println!("+ {} {}.{}: {}", kind, iface, name, def);
+    }
+}
+
+// None for interfaces outside the host-offload package.
+fn summarize(resolve: &Resolve, id: InterfaceId) -> Option<InterfaceSummary> {
+    let iface = &resolve.interfaces[id];
+    let pkg = &resolve.packages[iface.package?].name;
+    if format!("{}:{}", pkg.namespace, pkg.name) != PACKAGE {
+        return None;
+    }
+    Some(InterfaceSummary {
+        version: pkg.version.as_ref().map(|v| v.to_string()).unwrap_or_else(|| "unversioned".to_string()),
+        functions: iface.functions.iter().map(|(name, f)| (name.clone(), signature(resolve, f))).collect(),
+        types: iface.types.iter().map(|(name, &ty)| (name.clone(), type_def(resolve, ty))).collect(),
+    })
+}
+
+pub fn signature(resolve: &Resolve, f: &Function) -> String {
+    let params: Vec<String> = f.params.iter().map(|(name, ty)| format!("{}: {}", name, type_name(resolve, ty))).collect();
+    let results = match &f.results {
+        Results::Anon(ty) => format!(" -> {}", type_name(resolve, ty)),
+        Results::Named(named) if named.is_empty() => String::new(),
+        Results::Named(named) => {
+            let named: Vec<String> = named.iter().map(|(name, ty)| format!("{}: {}", name, type_name(resolve, ty))).collect();
+            format!(" -> ({})", named.join(", "))
+        }
+    };
+    format!("func({}){}", params.join(", "), results)
+}
+
+// Structural description of a type, so the same definition compares equal
+// across two independently resolved packages.
+fn type_name(resolve: &Resolve, ty: &Type) -> String {
+    match ty {
+        Type::Bool => "bool".into(),
+        Type::U8 => "u8".into(),
+        Type::U16 => "u16".into(),
+        Type::U32 => "u32".into(),
+        Type::U64 => "u64".into(),
+        Type::S8 => "s8".into(),
+        Type::S16 => "s16".into(),
+        Type::S32 => "s32".into(),
+        Type::S64 => "s64".into(),
+        Type::Float32 => "f32".into(),
+        Type::Float64 => "f64".into(),
+        Type::Char => "char".into(),
+        Type::String => "string".into(),
+        Type::Id(id) => type_def(resolve, *id),
+    }
+}
+
+fn type_def(resolve: &Resolve, id: wit_parser::TypeId) -> String {
+    let def = &resolve.types[id];
+    let opt = |ty: &Option<Type>| ty.as_ref().map(|t| type_name(resolve, t)).unwrap_or_else(|| "_".into());
+    match &def.kind {
+        TypeDefKind::Record(r) => {
+            let fields: Vec<String> = r.fields.iter().map(|f| format!("{}: {}", f.name, type_name(resolve, &f.ty))).collect();
+            format!("record {{ {} }}", fields.join(", "))
+        }
+        TypeDefKind::Variant(v) => {
+            let cases: Vec<String> = v.cases.iter()
+                .map(|c| match &c.ty {
+                    Some(ty) => format!("{}({})", c.name, type_name(resolve, ty)),
+                    None => c.name.clone(),
+                })
+                .collect();
+            format!("variant {{ {} }}", cases.join(", "))
+        }
+        TypeDefKind::Enum(e) => {
+            let cases: Vec<&str> = e.cases.iter().map(|c| c.name.as_str()).collect();
+            format!("enum {{ {} }}", cases.join(", "))
+        }
+        TypeDefKind::Flags(f) => {
+            let flags: Vec<&str> = f.flags.iter().map(|f| f.name.as_str()).collect();
+            format!("flags {{ {} }}", flags.join(", "))
+        }
+        TypeDefKind::Tuple(t) => {
+            let types: Vec<String> = t.types.iter().map(|ty| type_name(resolve, ty)).collect();
+            format!("tuple<{}>", types.join(", "))
+        }
+        TypeDefKind::Option(ty) => format!("option<{}>", type_name(resolve, ty)),
+        TypeDefKind::Result(r) => format!("result<{}, {}>", opt(&r.ok), opt(&r.err)),
+        TypeDefKind::List(ty) => format!("list<{}>", type_name(resolve, ty)),
+        TypeDefKind::Type(ty) => type_name(resolve, ty),
+        // Resources and other handle-like types compare by name.
+        TypeDefKind::Resource => format!("resource {}", def.name.as_deref().unwrap_or("?")),
+        TypeDefKind::Handle(wit_parser::Handle::Own(r)) => format!("own<{}>", resolve.types[*r].name.as_deref().unwrap_or("?")),
+        TypeDefKind::Handle(wit_parser::Handle::Borrow(r)) => format!("borrow<{}>", resolve.types[*r].name.as_deref().unwrap_or("?")),
+        TypeDefKind::Future(ty) => format!("future<{}>", opt(ty)),
+        TypeDefKind::Stream(s) => format!("stream<{}, {}>", opt(&s.element), opt(&s.end)),
+        TypeDefKind::Unknown => "unknown".into(),
+    }
+}
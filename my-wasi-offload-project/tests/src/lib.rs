@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+
+use serde_json::Value;
+
+// Target every config in `configs/` points at.
+const TARGET: &str = "wasm32-unknown-unknown";
+
+pub fn project_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).parent().unwrap().to_path_buf()
+}
+
+// Scratch space for generated configs, inside this crate's target dir.
+pub fn scratch_dir() -> PathBuf {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("target").join("e2e");
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+// Builds the component crate at `crate_dir` (relative to the project) with
+// `cargo component` and returns the path of the `.wasm`. Each crate is built
+// once per test binary, however many tests use it.
+pub fn component(crate_dir: &str) -> PathBuf {
+    static BUILT: Mutex<Option<HashMap<String, PathBuf>>> = Mutex::new(None);
+    let mut built = BUILT.lock().unwrap_or_else(|e| e.into_inner());
+    let built = built.get_or_insert_with(HashMap::new);
+    if let Some(path) = built.get(crate_dir) {
+        return path.clone();
+    }
+
+    let dir = project_dir().join(crate_dir);
+    let status = Command::new("cargo")
+        .args(["component", "build", "--release", "--target", TARGET])
+        .current_dir(&dir)
+        .status()
+        .expect("failed to run `cargo component`; is cargo-component installed?");
+    assert!(status.success(), "`cargo component build` failed in {}", dir.display());
+
+    let name = crate_dir.rsplit('/').next().unwrap().replace('-', "_");
+    let wasm = dir.join("target").join(TARGET).join("release").join(format!("{}.wasm", name));
+    assert!(wasm.exists(), "build succeeded but {} is missing", wasm.display());
+    built.insert(crate_dir.to_string(), wasm.clone());
+    wasm
+}
+
+pub fn provider_component() -> PathBuf {
+    component("host-offload-provider")
+}
+
+// One `[[clients]]` entry of a generated runner config.
+pub struct Client {
+    pub name: &'static str,
+    pub crate_dir: &'static str,
+    pub export: &'static str,
+    pub extra: &'static str,
+}
+
+impl Client {
+    pub fn new(name: &'static str, crate_dir: &'static str, export: &'static str) -> Self {
+        Client { name, crate_dir, export, extra: "" }
+    }
+
+    pub fn matrix() -> Self {
+        Client::new("matrix", "matrix-client", "run-matrix-example")
+    }
+
+    // Additional TOML lines for the entry, e.g. limits.
+    pub fn with(mut self, extra: &'static str) -> Self {
+        self.extra = extra;
+        self
+    }
+}
+
+pub enum Provider {
+    Component,
+    Native,
+}
+
+// What a runner invocation produced.
+pub struct RunOutcome {
+    pub code: i32,
+    pub report: Option<Value>,
+    pub stderr: String,
+}
+
+impl RunOutcome {
+    pub fn client(&self, name: &str) -> &Value {
+        let clients = self.report.as_ref().expect("runner printed no report")["clients"].as_array().unwrap();
+        clients.iter().find(|c| c["name"] == name).unwrap_or_else(|| panic!("no client {} in report", name))
+    }
+
+    pub fn assert_success(&self) {
+        assert_eq!(self.code, 0, "runner failed:\n{}", self.stderr);
+    }
+}
+
+// Writes a config for `clients` and runs the runner on it with `--output json`.
+pub fn run(test: &str, provider: Provider, clients: &[Client]) -> RunOutcome {
+    run_with_flags(test, provider, &[], clients)
+}
+
+// `run` with extra runner flags, e.g. `--async`.
+pub fn run_with_flags(test: &str, provider: Provider, flags: &[&str], clients: &[Client]) -> RunOutcome {
+    let provider = match provider {
+        Provider::Component => provider_component().display().to_string(),
+        Provider::Native => "native".to_string(),
+    };
+    run_config(test, format!("provider = {:?}\n", provider), flags, clients)
+}
+
+// `run` against the native provider with compute plugins loaded, given as
+// `(name, crate_dir)`.
+pub fn run_with_plugins(test: &str, plugins: &[(&str, &str)], clients: &[Client]) -> RunOutcome {
+    let mut config = "provider = \"native\"\n".to_string();
+    for (name, crate_dir) in plugins {
+        config.push_str(&format!(
+            "\n[[plugins]]\nname = {:?}\npath = {:?}\n",
+            name,
+            component(crate_dir).display().to_string()
+        ));
+    }
+    run_config(test, config, &[], clients)
+}
+
+fn run_config(test: &str, mut config: String, flags: &[&str], clients: &[Client]) -> RunOutcome {
+    for client in clients {
+        config.push_str(&format!(
+            "\n[[clients]]\nname = {:?}\npath = {:?}\nexport = {:?}\n{}\n",
+            client.name,
+            component(client.crate_dir).display().to_string(),
+            client.export,
+            client.extra
+        ));
+    }
+    let path = scratch_dir().join(format!("{}.toml", test));
+    std::fs::write(&path, config).unwrap();
+    let mut args = flags.to_vec();
+    args.extend(["--output", "json", path.to_str().unwrap()]);
+    runner(&args)
+}
+
+// Runs the runner binary with `args`, parsing stdout as a JSON report if it is one.
+pub fn runner(args: &[&str]) -> RunOutcome {
+    runner_with_env(args, &[])
+}
+
+// `runner` with extra environment variables, e.g. `RUNNER_CACHE_DIR`.
+pub fn runner_with_env(args: &[&str], env: &[(&str, &str)]) -> RunOutcome {
+    let output = Command::new("cargo")
+        .args(["run", "--quiet", "--release", "--"])
+        .args(args)
+        .envs(env.iter().copied())
+        .current_dir(project_dir().join("runner"))
+        .output()
+        .expect("failed to run the runner");
+    RunOutcome {
+        code: output.status.code().unwrap_or(-1),
+        report: serde_json::from_slice(&output.stdout).ok(),
+        stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+    }
+}
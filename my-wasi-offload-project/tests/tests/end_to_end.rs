@@ -0,0 +1,122 @@
+use offload_integration_tests::{provider_component, run, run_with_flags, run_with_plugins, runner, Client, Provider};
+
+#[test]
+fn matrix_client_against_provider_component() {
+    let outcome = run("matrix_component", Provider::Component, &[Client::matrix()]);
+    outcome.assert_success();
+    let client = outcome.client("matrix");
+    assert_eq!(client["ok"], true);
+    // The example frees everything it allocates.
+    assert_eq!(client["reclaimed_handles"], 0);
+}
+
+#[test]
+fn matrix_client_against_native_provider() {
+    let outcome = run("matrix_native", Provider::Native, &[Client::matrix()]);
+    outcome.assert_success();
+    assert_eq!(outcome.client("matrix")["ok"], true);
+}
+
+#[test]
+fn clients_run_side_by_side() {
+    let clients = [
+        Client::matrix(),
+        Client::new("matrix-limited", "matrix-client", "run-matrix-example").with("max_ops_per_sec = 1000"),
+        Client::new("resize", "examples/image-resize", "run-image-resize"),
+    ];
+    let outcome = run("side_by_side", Provider::Component, &clients);
+    outcome.assert_success();
+    assert_eq!(outcome.report.as_ref().unwrap()["failures"], 0);
+}
+
+#[test]
+fn async_clients_share_the_native_provider() {
+    // The native provider's compute calls go through `block_in_place`, so
+    // neither client's multiplies stall the other's task.
+    let clients = [Client::matrix(), Client::new("matrix-2", "matrix-client", "run-matrix-example")];
+    let outcome = run_with_flags("async_native", Provider::Native, &["--async"], &clients);
+    outcome.assert_success();
+    assert_eq!(outcome.report.as_ref().unwrap()["failures"], 0);
+    for name in ["matrix", "matrix-2"] {
+        let client = outcome.client(name);
+        assert_eq!(client["ok"], true);
+        assert_eq!(client["reclaimed_handles"], 0);
+    }
+}
+
+#[test]
+fn async_clients_against_provider_component() {
+    let clients = [Client::matrix(), Client::new("resize", "examples/image-resize", "run-image-resize")];
+    let outcome = run_with_flags("async_component", Provider::Component, &["--async"], &clients);
+    outcome.assert_success();
+    assert_eq!(outcome.report.as_ref().unwrap()["failures"], 0);
+}
+
+// A guest built against the frozen 0.1.0 package, before `host-error` grew.
+fn legacy_client() -> Client {
+    Client::new("legacy-matrix", "examples/legacy-matrix", "run-legacy-matrix")
+}
+
+#[test]
+fn v0_1_guest_runs_on_the_native_provider() {
+    let outcome = run("legacy_native", Provider::Native, &[legacy_client()]);
+    outcome.assert_success();
+    // It checks itself that errors added since 0.1.0 arrive as 0.1 cases.
+    let client = outcome.client("legacy-matrix");
+    assert_eq!(client["ok"], true);
+    assert_eq!(client["reclaimed_handles"], 0);
+}
+
+#[test]
+fn v0_1_guest_is_refused_by_a_provider_component() {
+    let outcome = run("legacy_component", Provider::Component, &[legacy_client()]);
+    assert_ne!(outcome.code, 0);
+    assert!(outcome.stderr.contains("built against wasi-custom:host-offload 0.1.0"), "{}", outcome.stderr);
+}
+
+#[test]
+fn fault_tolerance_example() {
+    let client = Client::new("faults", "examples/fault-tolerance", "run-fault-tolerance").with("max_ops_per_sec = 20");
+    let outcome = run("fault_tolerance", Provider::Component, &[client]);
+    outcome.assert_success();
+}
+
+#[test]
+fn streaming_upload_example() {
+    let client = Client::new("streaming", "examples/streaming-upload", "run-streaming-upload");
+    let outcome = run("streaming_upload", Provider::Native, &[client]);
+    outcome.assert_success();
+}
+
+#[test]
+fn keyvalue_blobs_example() {
+    let client = Client::new("keyvalue", "examples/keyvalue-blobs", "run-keyvalue-blobs").with("keyvalue = true");
+    let outcome = run("keyvalue_blobs", Provider::Native, &[client]);
+    outcome.assert_success();
+    // Every value was deleted, so its buffer was freed before the session closed.
+    assert_eq!(outcome.client("keyvalue")["reclaimed_handles"], 0);
+}
+
+#[test]
+fn plugin_ops_example() {
+    let client = Client::new("plugin-ops", "examples/plugin-ops", "run-plugin-ops");
+    let outcome = run_with_plugins("plugin_ops", &[("elementwise", "examples/elementwise-plugin")], &[client]);
+    outcome.assert_success();
+    // The plugin's results belong to the client's session and are freed by it.
+    assert_eq!(outcome.client("plugin-ops")["reclaimed_handles"], 0);
+}
+
+#[test]
+fn missing_export_is_a_setup_failure() {
+    let client = Client::new("wrong-export", "matrix-client", "no-such-export");
+    let outcome = run("missing_export", Provider::Component, &[client]);
+    assert_eq!(outcome.code, 4, "{}", outcome.stderr);
+    assert_eq!(outcome.client("wrong-export")["failure"], "setup");
+}
+
+#[test]
+fn provider_matches_the_wit_package() {
+    let provider = provider_component();
+    let outcome = runner(&["wit", "check", provider.to_str().unwrap()]);
+    outcome.assert_success();
+}
@@ -0,0 +1,121 @@
+// `trusted_keys` covers plugins too: a plugin loads only with a valid `.sig`.
+// And what runs is what was verified, whatever the compile cache holds.
+
+use std::path::{Path, PathBuf};
+
+use ed25519_dalek::{Signer, SigningKey};
+use offload_integration_tests::{component, runner, runner_with_env, scratch_dir, RunOutcome};
+
+fn key() -> SigningKey {
+    SigningKey::from_bytes(&[7; 32])
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// A copy of the component built from `crate_dir`, with `<copy>.sig` by `key()`.
+fn signed_copy(test: &str, crate_dir: &str) -> PathBuf {
+    let dir = scratch_dir().join(test);
+    std::fs::create_dir_all(&dir).unwrap();
+    let copy = dir.join(format!("{}.wasm", crate_dir.rsplit('/').next().unwrap()));
+    let bytes = std::fs::read(component(crate_dir)).unwrap();
+    std::fs::write(&copy, &bytes).unwrap();
+    std::fs::write(sig_path(&copy), key().sign(&bytes).to_bytes()).unwrap();
+    copy
+}
+
+fn sig_path(path: &Path) -> PathBuf {
+    PathBuf::from(format!("{}.sig", path.display()))
+}
+
+// Runs the plugin-ops example on the elementwise plugin, both signed, after
+// `prepare` has had its way with the plugin's copy.
+fn run_signed(test: &str, trusted_key: &str, prepare: impl FnOnce(&Path)) -> (RunOutcome, PathBuf) {
+    let client = signed_copy(test, "examples/plugin-ops");
+    let plugin = signed_copy(test, "examples/elementwise-plugin");
+    prepare(&plugin);
+    let config = format!(
+        "provider = \"native\"\ntrusted_keys = [{:?}]\n\n[[plugins]]\nname = \"elementwise\"\npath = {:?}\n\n\
+         [[clients]]\nname = \"plugin-ops\"\npath = {:?}\nexport = \"run-plugin-ops\"\n",
+        trusted_key,
+        plugin.display().to_string(),
+        client.display().to_string()
+    );
+    let path = scratch_dir().join(format!("{}.toml", test));
+    std::fs::write(&path, config).unwrap();
+    (runner(&["--output", "json", path.to_str().unwrap()]), plugin)
+}
+
+fn trusted() -> String {
+    hex(key().verifying_key().as_bytes())
+}
+
+#[test]
+fn signed_plugin_loads_and_is_reported() {
+    let (outcome, plugin) = run_signed("signed_plugin", &trusted(), |_| {});
+    outcome.assert_success();
+    let signature = &outcome.report.as_ref().unwrap()["signatures"][plugin.display().to_string()];
+    assert_eq!(signature["status"], "verified");
+    assert_eq!(signature["key"], hex(&key().verifying_key().as_bytes()[..8]));
+}
+
+#[test]
+fn tampered_plugin_is_refused() {
+    let (outcome, _) = run_signed("tampered_plugin", &trusted(), |plugin| {
+        let mut bytes = std::fs::read(plugin).unwrap();
+        bytes.push(0);
+        std::fs::write(plugin, bytes).unwrap();
+    });
+    assert_ne!(outcome.code, 0);
+    assert!(outcome.stderr.contains("is not signed by any trusted key"), "{}", outcome.stderr);
+}
+
+#[test]
+fn unsigned_plugin_is_refused() {
+    let (outcome, _) = run_signed("unsigned_plugin", &trusted(), |plugin| std::fs::remove_file(sig_path(plugin)).unwrap());
+    assert_ne!(outcome.code, 0);
+    assert!(outcome.stderr.contains("is unsigned"), "{}", outcome.stderr);
+}
+
+#[test]
+fn malformed_trusted_key_is_refused() {
+    let (outcome, _) = run_signed("malformed_key", "not-a-key", |_| {});
+    assert_ne!(outcome.code, 0);
+    assert!(outcome.stderr.contains("is not 64 hex digits"), "{}", outcome.stderr);
+}
+
+#[test]
+fn planted_cache_entry_is_not_run_for_a_signed_client() {
+    let test = "planted_cache";
+    let client = signed_copy(test, "matrix-client");
+    let cache = scratch_dir().join(test).join("cache");
+    let _ = std::fs::remove_dir_all(&cache);
+    let env = [("RUNNER_CACHE_DIR", cache.to_str().unwrap())];
+    let config = |name: &str, keys: &str| {
+        let path = scratch_dir().join(test).join(format!("{}.toml", name));
+        let config = format!(
+            "provider = \"native\"\ntrusted_keys = [{}]\n\n[[clients]]\nname = \"matrix\"\npath = {:?}\nexport = \"run-matrix-example\"\n",
+            keys,
+            client.display().to_string()
+        );
+        std::fs::write(&path, config).unwrap();
+        path.display().to_string()
+    };
+
+    // An unchecked run fills the cache for the client's bytes...
+    runner_with_env(&["--precompile", "--output", "json", &config("unchecked", "")], &env).assert_success();
+    // ...and its entry is swapped for a different component's code, which
+    // lacks the client's export.
+    let planted = scratch_dir().join(test).join("planted.cwasm");
+    let other = component("examples/plugin-ops");
+    runner(&["precompile", other.to_str().unwrap(), planted.to_str().unwrap()]).assert_success();
+    let entries: Vec<_> = std::fs::read_dir(&cache).unwrap().map(|entry| entry.unwrap().path()).collect();
+    assert_eq!(entries.len(), 1);
+    std::fs::copy(&planted, &entries[0]).unwrap();
+
+    let outcome = runner_with_env(&["--precompile", "--output", "json", &config("signed", &format!("{:?}", trusted()))], &env);
+    outcome.assert_success();
+    assert_eq!(outcome.client("matrix")["ok"], true);
+    assert_eq!(outcome.report.as_ref().unwrap()["signatures"][client.display().to_string()]["status"], "verified");
+}